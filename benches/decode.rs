@@ -0,0 +1,179 @@
+//Throughput of decoding large CoMPASS files with CompassHits, the reader of the event builder, mapped
+//and buffered, against the nom reader the mapped one replaced. The nom reader has none of the
+//bookkeeping of CompassFile around each record (corruption checks, segments, waveforms), which
+//the buffered reader has, so mapped against buffered is the gain of mapping alone. Run with `cargo bench --bench decode`; each reader reads
+//each file SAMPLES times and the median time is printed with the throughput.
+use std::fs::File;
+use std::hint::black_box;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use cebra_eventbuilder::{
+    generate_board_channel_uuid, ChannelMap, CompassData, CompassHits, SegmentAccess,
+};
+use nom::number::complete::{le_u16, le_u32, le_u64};
+use rand::Rng;
+
+//Energy and short energy, as written by most of the boards
+const ENERGIES_HEADER: u16 = 0x0001 | 0x0004;
+//The same with the waves recorded
+const WAVES_HEADER: u16 = ENERGIES_HEADER | 0x0008;
+const WAVE_SAMPLES: u32 = 64;
+const SAMPLES: usize = 10;
+
+//A file of a single board, the hits spread over its 16 channels, 1 us apart
+fn write_file(path: &Path, header: u16, hits: u64) {
+    let mut writer = BufWriter::new(File::create(path).unwrap());
+    writer.write_all(&header.to_le_bytes()).unwrap();
+    for hit in 0..hits {
+        writer.write_all(&0u16.to_le_bytes()).unwrap();
+        writer
+            .write_all(&((hit % 16) as u16).to_le_bytes())
+            .unwrap();
+        writer.write_all(&(hit * 1_000_000).to_le_bytes()).unwrap();
+        writer
+            .write_all(&((hit % 4096) as u16).to_le_bytes())
+            .unwrap();
+        writer
+            .write_all(&((hit % 1024) as u16).to_le_bytes())
+            .unwrap();
+        writer.write_all(&0u32.to_le_bytes()).unwrap();
+        if header & WAVES_HEADER == WAVES_HEADER {
+            writer.write_all(&[1]).unwrap();
+            writer.write_all(&WAVE_SAMPLES.to_le_bytes()).unwrap();
+            for sample in 0..WAVE_SAMPLES {
+                writer.write_all(&(sample as u16).to_le_bytes()).unwrap();
+            }
+        }
+    }
+    writer.flush().unwrap();
+}
+
+//The hits read, which every reader must agree on
+fn read_hits(path: &Path, map: &ChannelMap, access: SegmentAccess) -> u64 {
+    CompassHits::with_access(&[path.to_path_buf()], map, access)
+        .unwrap()
+        .fold(0, |hits, hit| {
+            black_box(hit.unwrap());
+            hits + 1
+        })
+}
+
+type NomError<'a> = nom::error::Error<&'a [u8]>;
+
+//The reader before the files were memory mapped: a BufReader of 24000 records, each record copied
+//out of it and decoded with nom. Each record becomes a hit as CompassData::new makes it, so only
+//the reading and decoding differ from CompassHits.
+fn read_buffered(path: &Path) -> u64 {
+    let mut file = File::open(path).unwrap();
+    let mut header = [0u8; 2];
+    file.read_exact(&mut header).unwrap();
+    let header_word = u16::from_le_bytes(header);
+    let has_waves = header_word & 0x0008 != 0;
+    //Board, channel, timestamp, energy, short energy and flags, then the wave code and samples
+    let data_size_bytes = 20 + if has_waves { 5 } else { 0 };
+    let mut wave_samples = 0;
+    if has_waves {
+        file.seek(SeekFrom::Start((2 + data_size_bytes - 4) as u64))
+            .unwrap();
+        let mut samples = [0u8; 4];
+        file.read_exact(&mut samples).unwrap();
+        wave_samples = u32::from_le_bytes(samples) as usize;
+        file.seek(SeekFrom::Start(2)).unwrap();
+    }
+    let mut reader = BufReader::with_capacity((data_size_bytes + 2 * wave_samples) * 24000, file);
+
+    let mut hits = 0;
+    loop {
+        let mut dataword: Vec<u8> = vec![0; data_size_bytes];
+        if reader.read_exact(&mut dataword).is_err() {
+            break;
+        }
+        let (rest, board) = le_u16::<_, NomError>(dataword.as_slice()).unwrap();
+        let (rest, channel) = le_u16::<_, NomError>(rest).unwrap();
+        let (rest, timestamp) = le_u64::<_, NomError>(rest).unwrap();
+        let (rest, energy) = le_u16::<_, NomError>(rest).unwrap();
+        let (rest, energy_short) = le_u16::<_, NomError>(rest).unwrap();
+        let (rest, flags) = le_u32::<_, NomError>(rest).unwrap();
+        if has_waves {
+            let (_, samples) = le_u32::<_, NomError>(&rest[1..]).unwrap();
+            let mut sampleword: Vec<u8> = vec![0; 2 * samples as usize];
+            reader.read_exact(&mut sampleword).unwrap();
+            let samples: Vec<u16> = sampleword
+                .chunks_exact(2)
+                .map(|sample| le_u16::<_, NomError>(sample).unwrap().1)
+                .collect();
+            black_box(samples);
+        }
+        let timestamp = timestamp as f64 * 1.0e-3;
+        black_box(CompassData {
+            uuid: generate_board_channel_uuid(&(board as u32), &(channel as u32)),
+            energy: energy as f64 + rand::thread_rng().gen::<f64>(),
+            energy_short: energy_short as f64 + rand::thread_rng().gen::<f64>(),
+            timestamp,
+            raw_timestamp: timestamp,
+            waveform: None,
+            flags,
+        });
+        hits += 1;
+    }
+    hits
+}
+
+//Median time of the samples, after one read to warm the page cache
+fn time_reads(mut read: impl FnMut() -> u64) -> Duration {
+    black_box(read());
+    let mut times: Vec<Duration> = (0..SAMPLES)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(read());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[SAMPLES / 2]
+}
+
+fn main() {
+    let map = ChannelMap::new(&[]);
+    let files = [
+        ("energies", ENERGIES_HEADER, 4_000_000),
+        ("waves", WAVES_HEADER, 500_000),
+    ];
+
+    for (name, header, hits) in files {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("cebra_eventbuilder_bench_{}.BIN", name));
+        write_file(&path, header, hits);
+        let bytes = std::fs::metadata(&path).unwrap().len() as f64;
+        let hits = read_buffered(&path);
+        assert_eq!(hits, read_hits(&path, &map, SegmentAccess::Mapped));
+        assert_eq!(hits, read_hits(&path, &map, SegmentAccess::Buffered));
+        let buffered = time_reads(|| read_buffered(black_box(&path)));
+        let mapped = time_reads(|| read_hits(black_box(&path), &map, SegmentAccess::Mapped));
+        //The reader of files still being written to
+        let now_buffered =
+            time_reads(|| read_hits(black_box(&path), &map, SegmentAccess::Buffered));
+        for (reader, time) in [
+            ("before (nom)", buffered),
+            ("mapped", mapped),
+            ("buffered", now_buffered),
+        ] {
+            println!(
+                "decode/{:<10}{:<20}{:>10.2} ms{:>10.0} MB/s",
+                name,
+                reader,
+                time.as_secs_f64() * 1.0e3,
+                bytes / time.as_secs_f64() / 1.0e6
+            );
+        }
+        println!(
+            "decode/{:<10}mapped is {:.2}x the reader before, {:.2}x buffered",
+            name,
+            buffered.as_secs_f64() / mapped.as_secs_f64(),
+            now_buffered.as_secs_f64() / mapped.as_secs_f64()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
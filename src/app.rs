@@ -1,54 +1,694 @@
-use log::{error, info};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
 
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 
 use eframe::egui::{self, Color32, RichText};
 use eframe::App;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
 
-use super::channel_map::{Board, ChannelType};
+use super::batch_schedule::ScheduleStatus;
+use super::build_diagnostics::{DiagnosticLog, Severity};
+use super::calibration_spectra::CalibrationSpectraParams;
+use super::campaign::{CampaignEntry, CampaignParams};
+use super::channel_data::TimeUnit;
+use super::channel_discovery::{discover_channels, discover_run_channels, DiscoveredChannel};
+use super::channel_map::{
+    find_channel_conflicts, get_detector_count, get_passthrough_count, get_selectable_types,
+    merge_channel_maps, Board, ChannelConflict, ChannelGroup, ChannelMap, ChannelType,
+    DetectorGroup,
+};
+use super::channel_rates::ChannelRateParams;
+use super::checkpoint::CheckpointParams;
+use super::codec_stats::{analyze_codecs, CodecReport, DEFAULT_SAMPLE_ROWS};
+use super::coincidence_trend::CoincidenceTrendParams;
+use super::column_codec::{ColumnCodec, ColumnCompression, ColumnEncoding, ZSTD_LEVEL_RANGE};
+use super::column_naming::{ColumnNaming, ColumnNamingScheme};
+use super::compass_data::{EnergyWidth, TimestampUnit};
 use super::compass_run::{process_runs, ProcessParams};
+use super::compass_settings::CompassSettings;
+use super::config_file::{
+    format_config, parse_config, read_config_file, ConfigFormat, CONFIG_VERSION,
+};
+use super::config_validation::{
+    describe_parse_error, lint_channel_map, validate_bad_energies, validate_calibration_spectra,
+    validate_campaign, validate_channel_map, validate_channel_rates, validate_checkpoints,
+    validate_coincidence_trend, validate_column_codecs, validate_column_naming,
+    validate_data_reduction, validate_detector_groups, validate_detector_spectra,
+    validate_efficiency, validate_energy_calibration, validate_energy_rank,
+    validate_energy_smearing, validate_energy_thresholds, validate_event_filters,
+    validate_gain_anchors, validate_histogram_only, validate_invalid_values, validate_merge_runs,
+    validate_multi_hit, validate_network_sink, validate_output_formats, validate_pair_windows,
+    validate_passthrough, validate_pulser, validate_qa, validate_quick_build, validate_quick_look,
+    validate_rate_limit, validate_resource_limits, validate_run_boundaries, validate_run_range,
+    validate_run_stitching, validate_scaler_list, validate_scaler_rates, validate_shift_map,
+    validate_skip_list, validate_sort_column, validate_state_gates, validate_state_spectra,
+    validate_streaming, validate_time_differences, validate_time_jitter, validate_time_walk,
+    validate_timestamp_sanitizer, validate_trigger, validate_waveforms, validate_write_limit,
+    ConfigIssue,
+};
+use super::data_reduction::{DataReductionParams, ReductionMode};
+use super::delayed_window::DelayedWindowParams;
+use super::detector_spectra::DetectorSpectraParams;
+use super::efficiency::{get_weight_field, EfficiencyEntry};
+use super::energy_calibration::EnergyCalibrationEntry;
+use super::energy_policy::{BadEnergyParams, BadEnergyPolicy};
+use super::energy_rank::{EnergyRankParams, MAX_ENERGY_RANKS};
+use super::energy_smearing::{EnergySmearingParams, SmearingEntry};
+use super::energy_threshold::EnergyThresholdEntry;
 use super::error::EVBError;
+use super::event_builder::{BuildStrategy, MissingTriggerBehavior, TriggerParams};
+use super::event_filter::{EventFilterEntry, EventFilterKind};
+use super::focal_plane::{SpsParams, StateGate};
+use super::formatting::{format_bytes, format_clock_time, format_count, format_duration};
+use super::gain_drift::{GainAnchor, GainEntry};
+use super::gate_file::GateFile;
+use super::hdf5_writer::Hdf5Params;
+use super::histogram::{
+    build_histogram, read_columns, DiagnosticKind, DiagnosticParams, Histogram,
+};
+use super::histogram_only::{HistogramOnlyParams, MAX_HISTOGRAM_ONLY_BINS};
+use super::hit_filter::{HitFilter, HitFilters};
+use super::invalid_values::InvalidValues;
+use super::multi_hit::{MultiHitEntry, MultiHitPolicy};
+use super::network_sink::NetworkSinkParams;
+use super::opened_build::{list_spectra_files, read_spectra_file, OpenedBuild};
+use super::output_format::OutputFormats;
+use super::output_recovery::{find_incomplete_runs, IncompleteRun};
+use super::output_sink::{OutputSink, OutputSinks};
+use super::pair_window::PairWindowEntry;
+use super::parquet_writer::StreamingParams;
+use super::passthrough::{get_passthrough_columns, PassthroughEntry};
+use super::plot_export::{paint_plot, paint_small_multiples, write_png, write_svg};
+use super::post_batch::{PostBatchHook, PostBatchHooks};
+use super::progress::JobProgress;
+use super::quick_build::QuickBuildParams;
+use super::quick_look::QuickLookParams;
+use super::rate_limit::RateLimitParams;
+use super::resource_limits::{ResourceLimits, ResourceOverrides};
+use super::run_archive::find_run_archive;
+use super::run_boundary::RunBoundaryParams;
+use super::run_comparison::{ComparisonParams, RunComparison};
+use super::run_layout::{RunLayout, EVENTS_STEM};
+use super::run_list::parse_run_list;
+use super::run_log::LogLevel;
+use super::run_notes::{write_campaign_summary, RunNotes};
+use super::run_qa::{QaParams, QaReference};
+use super::run_stitching::RunStitchingParams;
 use super::scaler_list::ScalerEntryUI;
-use super::shift_map::ShiftMapEntry;
+use super::scaler_rates::ScalerRateParams;
+use super::shared_memory::SharedMemoryParams;
+use super::shift_map::{read_shift_file, ShiftMapEntry};
+use super::shutdown::{is_shutdown_requested, wake_on_shutdown};
+use super::skip_list::SkipEntry;
+use super::time_calibration::{CampaignTimeOffsets, PulserParams};
+use super::time_differences::TimeDifferenceParams;
+use super::time_jitter::{JitterEntry, TimeJitterParams};
+use super::time_walk::TimeWalkParams;
+use super::timestamp_sanitizer::TimestampSanitizerParams;
+use super::unmapped::UnmappedPolicy;
+use super::waveform::{WaveformMode, WaveformParams};
+use super::workspace_bundle::{export_workspace_bundle, import_workspace_bundle};
+use super::write_limit::WriteLimitParams;
 use super::ws::{Workspace, WorkspaceError};
 
 #[derive(Debug, Serialize, Deserialize)]
-struct EvbAppParams {
+pub struct EvbAppParams {
+    //Layout of the file the config was saved to, 0 before configs had versions, see CONFIG_VERSION
+    #[serde(default)]
+    pub config_version: u32,
     pub workspace: Option<Workspace>,
+    //In coincidence_window_unit
     pub coincidence_window: f64,
+    #[serde(default)]
+    pub coincidence_window_unit: TimeUnit,
+    #[serde(default)]
+    pub pair_windows: Vec<PairWindowEntry>,
     pub run_min: i32,
     pub run_max: i32,
+    //Runs and ranges to build instead of run_min to run_max, e.g. "120-147, !133"
+    #[serde(default)]
+    pub run_list: String,
     pub channel_map_entries: Vec<Board>,
     pub shift_map_entries: Vec<ShiftMapEntry>,
     pub scaler_list_entries: Vec<ScalerEntryUI>,
+    #[serde(default)]
+    pub efficiency_entries: Vec<EfficiencyEntry>,
+    #[serde(default)]
+    pub time_unit: TimeUnit,
+    #[serde(default)]
+    pub shared_memory: SharedMemoryParams,
+    #[serde(default)]
+    pub network_sink: NetworkSinkParams,
+    #[serde(default)]
+    pub delayed_window: DelayedWindowParams,
+    #[serde(default)]
+    pub sps: SpsParams,
+    #[serde(default)]
+    pub gain_anchors: Vec<GainAnchor>,
+    #[serde(default = "default_slow_control_pattern")]
+    pub slow_control_pattern: String,
+    #[serde(default)]
+    pub trigger: TriggerParams,
+    #[serde(default)]
+    pub pulser: PulserParams,
+    #[serde(default)]
+    pub column_codecs: Vec<ColumnCodec>,
+    #[serde(default)]
+    pub write_hits: bool,
+    #[serde(default)]
+    pub keep_raw_times: bool,
+    #[serde(default)]
+    pub psd_columns: bool,
+    #[serde(default)]
+    pub flag_columns: bool,
+    #[serde(default)]
+    pub event_time_columns: bool,
+    #[serde(default)]
+    pub split_singles: bool,
+    #[serde(default)]
+    pub merge_runs: bool,
+    #[serde(default)]
+    pub unmapped_channels: UnmappedPolicy,
+    #[serde(default)]
+    pub sort_column: String,
+    #[serde(default)]
+    pub column_naming: ColumnNaming,
+    #[serde(default)]
+    pub invalid_values: InvalidValues,
+    #[serde(default)]
+    pub quick_build: QuickBuildParams,
+    #[serde(default)]
+    pub data_reduction: DataReductionParams,
+    #[serde(default)]
+    pub energy_smearing: EnergySmearingParams,
+    #[serde(default)]
+    pub time_reversed_check: bool,
+    #[serde(default)]
+    pub write_limit: WriteLimitParams,
+    #[serde(default)]
+    pub quick_look: QuickLookParams,
+    #[serde(default)]
+    pub skip_files: Vec<SkipEntry>,
+    #[serde(default)]
+    pub time_jitter: TimeJitterParams,
+    #[serde(default)]
+    pub time_walk: TimeWalkParams,
+    #[serde(default)]
+    pub timestamp_sanitizer: TimestampSanitizerParams,
+    #[serde(default)]
+    pub detector_groups: Vec<DetectorGroup>,
+    #[serde(default)]
+    pub passthrough: Vec<PassthroughEntry>,
+    #[serde(default)]
+    pub multi_hit: Vec<MultiHitEntry>,
+    #[serde(default)]
+    pub event_filters: Vec<EventFilterEntry>,
+    #[serde(default)]
+    pub energy_rank: EnergyRankParams,
+    #[serde(default)]
+    pub run_boundaries: RunBoundaryParams,
+    #[serde(default)]
+    pub run_stitching: RunStitchingParams,
+    #[serde(default)]
+    pub calibration_spectra: CalibrationSpectraParams,
+    #[serde(default)]
+    pub streaming: StreamingParams,
+    #[serde(default)]
+    pub waveforms: WaveformParams,
+    #[serde(default)]
+    pub bad_energies: BadEnergyParams,
+    #[serde(default)]
+    pub rate_limit: RateLimitParams,
+    #[serde(default)]
+    pub energy_calibration: Vec<EnergyCalibrationEntry>,
+    #[serde(default)]
+    pub energy_thresholds: Vec<EnergyThresholdEntry>,
+    #[serde(default)]
+    pub output_formats: OutputFormats,
+    #[serde(default)]
+    pub hdf5: Hdf5Params,
+    #[serde(default)]
+    pub time_differences: TimeDifferenceParams,
+    #[serde(default)]
+    pub histogram_only: HistogramOnlyParams,
+    #[serde(default)]
+    pub scaler_rates: ScalerRateParams,
+    #[serde(default)]
+    pub coincidence_trend: CoincidenceTrendParams,
+    #[serde(default)]
+    pub channel_rates: ChannelRateParams,
+    #[serde(default)]
+    pub qa: QaParams,
+    #[serde(default)]
+    pub checkpoints: CheckpointParams,
+    #[serde(default)]
+    pub detector_spectra: DetectorSpectraParams,
+    #[serde(default)]
+    pub campaign: CampaignParams,
+    #[serde(default)]
+    pub resource_limits: ResourceLimits,
+    #[serde(default = "default_worker_threads")]
+    pub worker_threads: usize,
+    #[serde(default = "default_build_threads")]
+    pub build_threads: usize,
+    #[serde(default)]
+    pub log_level: LogLevel,
+}
+
+fn default_worker_threads() -> usize {
+    1
+}
+
+fn default_build_threads() -> usize {
+    1
+}
+
+fn default_slow_control_pattern() -> String {
+    String::from("slow_control")
 }
 
 impl Default for EvbAppParams {
     fn default() -> Self {
         EvbAppParams {
+            config_version: CONFIG_VERSION,
             workspace: None,
             coincidence_window: 3.0e3,
+            coincidence_window_unit: TimeUnit::Nanoseconds,
+            pair_windows: Vec::new(),
             run_min: 0,
             run_max: 0,
+            run_list: String::new(),
             channel_map_entries: Vec::new(),
             shift_map_entries: Vec::new(),
             scaler_list_entries: Vec::new(),
+            efficiency_entries: Vec::new(),
+            time_unit: TimeUnit::default(),
+            shared_memory: SharedMemoryParams::default(),
+            network_sink: NetworkSinkParams::default(),
+            delayed_window: DelayedWindowParams::default(),
+            sps: SpsParams::default(),
+            gain_anchors: Vec::new(),
+            slow_control_pattern: default_slow_control_pattern(),
+            trigger: TriggerParams::default(),
+            pulser: PulserParams::default(),
+            column_codecs: Vec::new(),
+            write_hits: false,
+            keep_raw_times: false,
+            psd_columns: false,
+            flag_columns: false,
+            event_time_columns: false,
+            split_singles: false,
+            merge_runs: false,
+            unmapped_channels: UnmappedPolicy::default(),
+            sort_column: String::new(),
+            column_naming: ColumnNaming::default(),
+            invalid_values: InvalidValues::default(),
+            quick_build: QuickBuildParams::default(),
+            data_reduction: DataReductionParams::default(),
+            energy_smearing: EnergySmearingParams::default(),
+            time_reversed_check: false,
+            write_limit: WriteLimitParams::default(),
+            quick_look: QuickLookParams::default(),
+            skip_files: Vec::new(),
+            time_jitter: TimeJitterParams::default(),
+            time_walk: TimeWalkParams::default(),
+            timestamp_sanitizer: TimestampSanitizerParams::default(),
+            detector_groups: Vec::new(),
+            passthrough: Vec::new(),
+            multi_hit: Vec::new(),
+            event_filters: Vec::new(),
+            energy_rank: EnergyRankParams::default(),
+            run_boundaries: RunBoundaryParams::default(),
+            run_stitching: RunStitchingParams::default(),
+            calibration_spectra: CalibrationSpectraParams::default(),
+            streaming: StreamingParams::default(),
+            waveforms: WaveformParams::default(),
+            bad_energies: BadEnergyParams::default(),
+            rate_limit: RateLimitParams::default(),
+            energy_calibration: Vec::new(),
+            energy_thresholds: Vec::new(),
+            output_formats: OutputFormats::default(),
+            hdf5: Hdf5Params::default(),
+            time_differences: TimeDifferenceParams::default(),
+            histogram_only: HistogramOnlyParams::default(),
+            scaler_rates: ScalerRateParams::default(),
+            coincidence_trend: CoincidenceTrendParams::default(),
+            channel_rates: ChannelRateParams::default(),
+            qa: QaParams::default(),
+            checkpoints: CheckpointParams::default(),
+            detector_spectra: DetectorSpectraParams::default(),
+            campaign: CampaignParams::default(),
+            resource_limits: ResourceLimits::default(),
+            worker_threads: default_worker_threads(),
+            build_threads: default_build_threads(),
+            log_level: LogLevel::default(),
+        }
+    }
+}
+
+impl EvbAppParams {
+    //A saved config file, with where in the file it fails to parse
+    pub fn read(path: &Path) -> Result<Self, EVBError> {
+        Ok(read_config_file(path)?.0)
+    }
+
+    //In ns, as the event builder takes it
+    pub fn get_coincidence_window(&self) -> f64 {
+        self.coincidence_window_unit
+            .get_nanoseconds(self.coincidence_window)
+    }
+
+    //The run list when there is one, otherwise [run_min, run_max]. An invalid list builds nothing,
+    //it is flagged when the config is checked.
+    pub fn get_runs(&self) -> Vec<i32> {
+        if self.run_list.trim().is_empty() {
+            (self.run_min..=self.run_max).collect()
+        } else {
+            parse_run_list(&self.run_list).unwrap_or_default()
         }
     }
+
+    pub fn get_process_params(
+        &self,
+        workspace: &Workspace,
+        post_batch_hooks: PostBatchHooks,
+        output_sinks: OutputSinks,
+        hit_filters: HitFilters,
+        paranoid: bool,
+    ) -> Result<ProcessParams, WorkspaceError> {
+        Ok(ProcessParams {
+            archive_dir: workspace.get_archive_dir()?,
+            unpack_dir: workspace.get_unpack_dir()?,
+            output_dir: workspace.get_output_dir()?,
+            channel_map: self.channel_map_entries.clone(),
+            scaler_list: self.scaler_list_entries.clone(),
+            shift_map: self.shift_map_entries.clone(),
+            coincidence_window: self.get_coincidence_window(),
+            pair_windows: self.pair_windows.clone(),
+            time_unit: self.time_unit,
+            efficiency_entries: self.efficiency_entries.clone(),
+            runs: self.get_runs(),
+            shared_memory: self.shared_memory.clone(),
+            network_sink: self.network_sink.clone(),
+            output_sinks,
+            post_batch_hooks,
+            hit_filters,
+            delayed_window: self.delayed_window.clone(),
+            sps: self.sps.clone(),
+            gain_anchors: self.gain_anchors.clone(),
+            slow_control_pattern: self.slow_control_pattern.clone(),
+            trigger: self.trigger.clone(),
+            pulser: self.pulser.clone(),
+            column_codecs: self.column_codecs.clone(),
+            write_hits: self.write_hits,
+            keep_raw_times: self.keep_raw_times,
+            psd_columns: self.psd_columns,
+            flag_columns: self.flag_columns,
+            event_time_columns: self.event_time_columns,
+            split_singles: self.split_singles,
+            merge_runs: self.merge_runs,
+            unmapped_channels: self.unmapped_channels,
+            sort_column: self.column_naming.get_column_name(&self.sort_column),
+            column_naming: self.column_naming.clone(),
+            invalid_values: self.invalid_values,
+            quick_build: None,
+            paranoid,
+            data_reduction: self.data_reduction.clone(),
+            energy_smearing: self.energy_smearing.clone(),
+            time_reversed_check: self.time_reversed_check,
+            write_limit: self.write_limit.clone(),
+            quick_look: self.quick_look.clone(),
+            skip_files: self.skip_files.clone(),
+            time_jitter: self.time_jitter.clone(),
+            time_walk: self.time_walk.clone(),
+            timestamp_sanitizer: self.timestamp_sanitizer.clone(),
+            detector_groups: self.detector_groups.clone(),
+            passthrough: self.passthrough.clone(),
+            multi_hit: self.multi_hit.clone(),
+            event_filters: self.event_filters.clone(),
+            energy_rank: self.energy_rank.clone(),
+            run_boundaries: self.run_boundaries.clone(),
+            run_stitching: self.run_stitching.clone(),
+            calibration_spectra: self.calibration_spectra.clone(),
+            streaming: self.streaming.clone(),
+            waveforms: self.waveforms.clone(),
+            bad_energies: self.bad_energies.clone(),
+            rate_limit: self.rate_limit.clone(),
+            energy_calibration: self.energy_calibration.clone(),
+            energy_thresholds: self.energy_thresholds.clone(),
+            output_formats: self.output_formats,
+            hdf5: self.hdf5.clone(),
+            memory_output: None,
+            time_differences: self.time_differences.clone(),
+            histogram_only: self.histogram_only.clone(),
+            scaler_rates: self.scaler_rates.clone(),
+            coincidence_trend: self.coincidence_trend.clone(),
+            channel_rates: self.channel_rates.clone(),
+            qa: self.qa.clone(),
+            checkpoints: self.checkpoints.clone(),
+            detector_spectra: self.detector_spectra.clone(),
+            campaign: self.campaign.clone(),
+            resource_limits: self.resource_limits.clone(),
+            worker_threads: self.worker_threads,
+            build_threads: self.build_threads,
+            diagnostics: DiagnosticLog::default(),
+            log_level: self.log_level,
+            config_text: serde_yaml::to_string(self).unwrap_or_default(),
+        })
+    }
+}
+
+//Every check of a config, as done when it is loaded in the GUI or built from the command line
+pub(crate) fn validate_params(yaml_str: &str, params: &EvbAppParams) -> Vec<ConfigIssue> {
+    let mut issues = validate_channel_map(yaml_str, &params.channel_map_entries);
+    let passthrough_columns = get_passthrough_columns(
+        &params.passthrough,
+        get_passthrough_count(&params.channel_map_entries),
+    );
+    issues.extend(validate_detector_groups(yaml_str, &params.detector_groups));
+    issues.extend(validate_passthrough(
+        yaml_str,
+        &params.passthrough,
+        &params.channel_map_entries,
+    ));
+    issues.extend(validate_multi_hit(yaml_str, &params.multi_hit));
+    issues.extend(validate_event_filters(
+        yaml_str,
+        &params.event_filters,
+        &params.detector_groups,
+        &passthrough_columns,
+    ));
+    issues.extend(validate_energy_rank(yaml_str, &params.energy_rank));
+    issues.extend(validate_quick_build(yaml_str, &params.quick_build));
+    issues.extend(validate_shift_map(yaml_str, &params.shift_map_entries));
+    issues.extend(validate_scaler_list(yaml_str, &params.scaler_list_entries));
+    issues.extend(validate_skip_list(yaml_str, &params.skip_files));
+    issues.extend(validate_campaign(yaml_str, &params.campaign));
+    issues.extend(validate_efficiency(
+        yaml_str,
+        &params.efficiency_entries,
+        get_detector_count(&params.channel_map_entries),
+    ));
+    issues.extend(validate_state_gates(yaml_str, &params.sps.state_gates));
+    issues.extend(validate_state_spectra(yaml_str, &params.sps.spectra));
+    issues.extend(validate_calibration_spectra(
+        yaml_str,
+        &params.calibration_spectra,
+    ));
+    issues.extend(validate_gain_anchors(yaml_str, &params.gain_anchors));
+    issues.extend(validate_energy_calibration(
+        yaml_str,
+        &params.energy_calibration,
+    ));
+    issues.extend(validate_energy_thresholds(
+        yaml_str,
+        &params.energy_thresholds,
+    ));
+    issues.extend(validate_column_codecs(yaml_str, &params.column_codecs));
+    issues.extend(validate_pulser(
+        yaml_str,
+        &params.pulser,
+        &params.channel_map_entries,
+    ));
+    issues.extend(validate_energy_smearing(yaml_str, &params.energy_smearing));
+    issues.extend(validate_time_jitter(yaml_str, &params.time_jitter));
+    issues.extend(validate_time_walk(yaml_str, &params.time_walk));
+    issues.extend(validate_timestamp_sanitizer(
+        yaml_str,
+        &params.timestamp_sanitizer,
+    ));
+    issues.extend(validate_write_limit(yaml_str, &params.write_limit));
+    issues.extend(validate_streaming(yaml_str, &params.streaming));
+    issues.extend(validate_network_sink(yaml_str, &params.network_sink));
+    issues.extend(validate_time_differences(
+        yaml_str,
+        &params.time_differences,
+        &params.channel_map_entries,
+    ));
+    issues.extend(validate_histogram_only(yaml_str, &params.histogram_only));
+    issues.extend(validate_merge_runs(
+        yaml_str,
+        params.merge_runs,
+        &params.output_formats,
+        &params.histogram_only,
+        &params.run_boundaries,
+    ));
+    issues.extend(validate_scaler_rates(yaml_str, &params.scaler_rates));
+    issues.extend(validate_detector_spectra(
+        yaml_str,
+        &params.detector_spectra,
+    ));
+    issues.extend(validate_coincidence_trend(
+        yaml_str,
+        &params.coincidence_trend,
+        &params.channel_map_entries,
+    ));
+    issues.extend(validate_channel_rates(yaml_str, &params.channel_rates));
+    issues.extend(validate_qa(
+        yaml_str,
+        &params.qa,
+        &params.output_formats,
+        &params.histogram_only,
+    ));
+    issues.extend(validate_checkpoints(yaml_str, &params.checkpoints));
+    issues.extend(validate_resource_limits(yaml_str, &params.resource_limits));
+    issues.extend(validate_output_formats(
+        yaml_str,
+        &params.output_formats,
+        &params.hdf5,
+        &params.streaming,
+    ));
+    issues.extend(validate_waveforms(yaml_str, &params.waveforms));
+    issues.extend(validate_bad_energies(yaml_str, &params.bad_energies));
+    issues.extend(validate_rate_limit(yaml_str, &params.rate_limit));
+    issues.extend(validate_run_boundaries(yaml_str, &params.run_boundaries));
+    issues.extend(validate_run_stitching(yaml_str, &params.run_stitching));
+    issues.extend(validate_pair_windows(
+        yaml_str,
+        &params.pair_windows,
+        params.trigger.strategy,
+    ));
+    issues.extend(validate_sort_column(
+        yaml_str,
+        &params.sort_column,
+        &params.detector_groups,
+        &passthrough_columns,
+    ));
+    issues.extend(validate_column_naming(
+        yaml_str,
+        &params.column_naming,
+        get_detector_count(&params.channel_map_entries),
+        &params.detector_groups,
+        &passthrough_columns,
+    ));
+    issues.extend(validate_invalid_values(
+        yaml_str,
+        &params.invalid_values,
+        &params.bad_energies,
+    ));
+    issues.extend(validate_data_reduction(yaml_str, &params.data_reduction));
+    issues.extend(validate_quick_look(yaml_str, &params.quick_look));
+    issues.extend(validate_trigger(
+        yaml_str,
+        &params.trigger,
+        &params.channel_map_entries,
+    ));
+    issues.extend(validate_run_range(
+        yaml_str,
+        params.run_min,
+        params.run_max,
+        &params.run_list,
+        params.get_coincidence_window(),
+    ));
+    issues
+}
+
+//Only the channel map is taken from an imported config file
+#[derive(Debug, Serialize, Deserialize)]
+struct ChannelMapFile {
+    pub channel_map_entries: Vec<Board>,
+}
+
+pub(crate) fn read_channel_map_file(path: &Path) -> Result<Vec<Board>, EVBError> {
+    let text = std::fs::read_to_string(path)?;
+    let file = match ConfigFormat::of_path(path) {
+        ConfigFormat::Yaml => serde_yaml::from_str::<ChannelMapFile>(&text)?,
+        ConfigFormat::Toml => toml::from_str::<ChannelMapFile>(&text)
+            .map_err(|x| EVBError::Config(format!("{}, {}", path.display(), x.message())))?,
+    };
+    Ok(file.channel_map_entries)
+}
+
+//A file with only the channel map, which can be imported into any config or given to --channel-map
+fn write_channel_map_file(path: &Path, boards: &[Board]) -> Result<(), EVBError> {
+    let file = ChannelMapFile {
+        channel_map_entries: boards.to_vec(),
+    };
+    std::fs::write(path, serde_yaml::to_string(&file)?)?;
+    Ok(())
+}
+
+//For --print-map, the mapping table of the channel map in a config file
+pub fn format_channel_map_file(path: &Path) -> Result<String, EVBError> {
+    Ok(ChannelMap::new(&read_channel_map_file(path)?).format_table())
+}
+
+//For --lint-map, the problems found by checking the channel map, shift map and energy calibration
+//of a config file against each other
+pub fn lint_channel_map_file(path: &Path) -> Result<Vec<String>, EVBError> {
+    let (params, yaml_str) = read_config_file(path)?;
+    Ok(lint_channel_map(
+        &yaml_str,
+        &params.channel_map_entries,
+        &params.shift_map_entries,
+        &params.energy_calibration,
+        params.get_coincidence_window(),
+    )
+    .iter()
+    .map(|issue| issue.to_string())
+    .collect())
+}
+
+#[derive(Debug, Default)]
+struct ChannelMapImport {
+    pub imported: Vec<Board>,
+    pub conflicts: Vec<ChannelConflict>,
+}
+
+impl ChannelMapImport {
+    //What is wrong with the map the merge would give, e.g. a detector mapped to a channel on each
+    //side. Filling the empty channels of one map from the other is not checked otherwise.
+    fn get_errors(&self, current: &[Board]) -> Vec<String> {
+        let merged = merge_channel_maps(current, &self.imported, &self.conflicts);
+        validate_channel_map("", &merged)
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect()
+    }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, EnumIter, Serialize, Deserialize)]
 enum ActiveTab {
     MainTab,
     ChannelMap,
     ShiftMap,
     ScalerList,
+    SkipList,
+    Campaign,
+    Efficiency,
+    Sps,
+    GainDrift,
+    ColumnCodecs,
+    Simulation,
+    Outputs,
+    Histograms,
+    RunComparison,
 }
 
 impl Default for ActiveTab {
@@ -57,71 +697,449 @@ impl Default for ActiveTab {
     }
 }
 
+impl ActiveTab {
+    fn get_label(&self) -> &'static str {
+        match self {
+            ActiveTab::MainTab => "Eventbuilder",
+            ActiveTab::ChannelMap => "Channel Map",
+            ActiveTab::ShiftMap => "Shift Map",
+            ActiveTab::ScalerList => "Scaler List",
+            ActiveTab::SkipList => "Skip List",
+            ActiveTab::Campaign => "Campaign",
+            ActiveTab::Efficiency => "Efficiency",
+            ActiveTab::Sps => "SPS",
+            ActiveTab::GainDrift => "Gain Drift",
+            ActiveTab::ColumnCodecs => "Compression",
+            ActiveTab::Simulation => "Simulation",
+            ActiveTab::Outputs => "Outputs",
+            ActiveTab::Histograms => "Histograms",
+            ActiveTab::RunComparison => "Compare Runs",
+        }
+    }
+
+    //Tab order wraps around in both directions
+    fn get_step(&self, step: i32) -> Self {
+        let tabs: Vec<ActiveTab> = ActiveTab::iter().collect();
+        let index = tabs.iter().position(|tab| tab == self).unwrap_or(0) as i32;
+        let count = tabs.len() as i32;
+        tabs[((index + step).rem_euclid(count)) as usize]
+    }
+}
+
+//Steps of the first-run setup wizard, in order
+#[derive(Debug, Clone, Copy, PartialEq, EnumIter)]
+enum WizardStep {
+    Workspace,
+    ChannelMap,
+    CoincidenceWindow,
+    TestBuild,
+}
+
+impl WizardStep {
+    fn get_title(&self) -> &'static str {
+        match self {
+            WizardStep::Workspace => "Workspace",
+            WizardStep::ChannelMap => "Channel Map",
+            WizardStep::CoincidenceWindow => "Coincidence Window",
+            WizardStep::TestBuild => "Test Build",
+        }
+    }
+
+    fn get_help(&self) -> &'static str {
+        match self {
+            WizardStep::Workspace => {
+                "Pick the workspace of the campaign: the directory with the run archives in its \
+                raw_binary folder. Built runs go to its built folder. If the workspace already \
+                has an evb_config.yaml, its settings are loaded."
+            }
+            WizardStep::ChannelMap => {
+                "Tell the eventbuilder which detector is on each board and channel, by importing \
+                the channel map of another config file, or by adding boards on the Channel Map tab."
+            }
+            WizardStep::CoincidenceWindow => {
+                "Hits closer in time than the coincidence window after the first hit of an event \
+                are built into the same event."
+            }
+            WizardStep::TestBuild => {
+                "Build a single run to check the setup before building the whole campaign. The \
+                result is listed on the Outputs tab."
+            }
+        }
+    }
+
+    //None past the first and last step
+    fn get_step(&self, step: i32) -> Option<Self> {
+        let steps: Vec<WizardStep> = WizardStep::iter().collect();
+        let index = steps.iter().position(|other| other == self).unwrap_or(0) as i32 + step;
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| steps.get(index))
+            .copied()
+    }
+}
+
+const OPEN_CONFIG_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O);
+const SAVE_CONFIG_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S);
+const OPEN_WORKSPACE_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::O,
+);
+const RUN_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::R);
+const NEXT_TAB_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::PageDown);
+const PREVIOUS_TAB_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::PageUp);
+const DARK_MODE_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::D);
+//Ctrl+1 to Ctrl+9 jump straight to the first nine tabs
+const TAB_KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
+const PREFERENCES_KEY: &str = "cebra_evb_user_preferences";
+const MAX_RECENT_WORKSPACES: usize = 8;
+
+//Cosmetic, per-user state. Saved through eframe storage rather than the run config, so sharing a
+//config file between users doesn't carry along anyone's theme or directories.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct UserPreferences {
+    pub dark_mode: bool,
+    pub active_tab: ActiveTab,
+    pub last_config_dir: Option<PathBuf>,
+    pub last_workspace_dir: Option<PathBuf>,
+    //Most recent first, for switching between campaigns from the File menu
+    pub recent_workspaces: Vec<PathBuf>,
+    //The setup wizard was finished or skipped once, so it is not opened at start any more
+    pub finished_setup: bool,
+}
+
+impl Default for UserPreferences {
+    fn default() -> Self {
+        UserPreferences {
+            dark_mode: true,
+            active_tab: ActiveTab::MainTab,
+            last_config_dir: None,
+            last_workspace_dir: None,
+            recent_workspaces: Vec::new(),
+            finished_setup: false,
+        }
+    }
+}
+
+impl UserPreferences {
+    fn dialog_dir(last_dir: &Option<PathBuf>) -> PathBuf {
+        match last_dir {
+            Some(dir) if dir.exists() => dir.clone(),
+            _ => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        Self::dialog_dir(&self.last_config_dir)
+    }
+
+    pub fn workspace_dir(&self) -> PathBuf {
+        Self::dialog_dir(&self.last_workspace_dir)
+    }
+
+    pub fn remember_config_file(&mut self, path: &Path) {
+        self.last_config_dir = path.parent().map(Path::to_path_buf);
+    }
+
+    //Users who have opened a workspace before setup wizards existed know their way around
+    pub fn is_first_run(&self) -> bool {
+        !self.finished_setup && self.recent_workspaces.is_empty()
+    }
+
+    pub fn remember_workspace(&mut self, path: &Path) {
+        self.last_workspace_dir = path.parent().map(Path::to_path_buf);
+        self.recent_workspaces.retain(|recent| recent != path);
+        self.recent_workspaces.insert(0, path.to_path_buf());
+        self.recent_workspaces.truncate(MAX_RECENT_WORKSPACES);
+    }
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Debug, Default)]
 pub struct EVBApp {
     #[serde(skip)]
-    progress: Arc<Mutex<f32>>,
+    progress: JobProgress,
 
     parameters: EvbAppParams,
     rxn_eqn: String,
-    active_tab: ActiveTab,
+
+    #[serde(skip)]
+    preferences: UserPreferences,
 
     #[serde(skip)]
     thread_handle: Option<JoinHandle<Result<(), EVBError>>>,
 
+    //Warnings and errors of the last job started
+    #[serde(skip)]
+    build_diagnostics: DiagnosticLog,
+
+    #[serde(skip)]
+    channel_map_import: Option<ChannelMapImport>,
+
+    //Scan of a run for the channels that have data, while it runs
+    #[serde(skip)]
+    channel_discovery: Option<JoinHandle<Result<Vec<DiscoveredChannel>, EVBError>>>,
+
+    //Channels found by the last scan, and the run scanned
+    #[serde(skip)]
+    discovered_channels: Vec<DiscoveredChannel>,
+    #[serde(skip)]
+    discovery_run: i32,
+
+    //Step shown while the setup wizard is open
+    #[serde(skip)]
+    setup_wizard: Option<WizardStep>,
+
+    //Runs of the workspace left behind by aborted builds, shown until cleaned or dismissed
+    #[serde(skip)]
+    incomplete_runs: Vec<IncompleteRun>,
+
+    #[serde(skip)]
+    post_batch_hooks: PostBatchHooks,
+
+    #[serde(skip)]
+    output_sinks: OutputSinks,
+
+    #[serde(skip)]
+    hit_filters: HitFilters,
+
+    #[serde(skip)]
+    output_run: i32,
+
+    //Run of the quick build in progress, shown once it is done
+    #[serde(skip)]
+    quick_build_run: Option<i32>,
+
+    //The Outputs and Histograms tabs show the quick builds instead of the full ones
+    #[serde(skip)]
+    show_quick_build: bool,
+
+    //Notes of the workspace they were read from, read again when the workspace changes
+    #[serde(skip)]
+    run_notes: Option<(PathBuf, RunNotes)>,
+
+    #[serde(skip)]
+    diagnostic: DiagnosticParams,
+
+    #[serde(skip)]
+    diagnostic_columns: Option<(i32, Vec<String>)>,
+
+    #[serde(skip)]
+    histogram: Option<Histogram>,
+
+    //Codec statistics of the events of a built run, see the Compression tab
+    #[serde(skip)]
+    codec_report: Option<(i32, CodecReport)>,
+
+    #[serde(skip)]
+    comparison: ComparisonParams,
+
+    #[serde(skip)]
+    run_comparison: Option<RunComparison>,
+
+    //Built runs of another workspace, shown read-only on the diagnostics tabs in place of those of
+    //the workspace
+    #[serde(skip)]
+    opened_build: Option<OpenedBuild>,
+
+    //Report of the run shown on the Outputs tab
+    #[serde(skip)]
+    shown_report: Option<(PathBuf, serde_json::Value)>,
+
+    //Spectra file picked on the Outputs tab, with its spectra
+    #[serde(skip)]
+    shown_spectra: Option<(PathBuf, Vec<Histogram>)>,
+
+    #[serde(skip)]
+    paranoid: bool,
+
+    #[serde(skip)]
+    resource_overrides: ResourceOverrides,
+    //From --log-level, replacing the log level of the config
+    log_level: Option<LogLevel>,
+
     window: bool,
 }
 
 impl EVBApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>, window: bool) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, window: bool) -> Self {
+        let preferences: UserPreferences = match cc.storage {
+            Some(storage) => eframe::get_value(storage, PREFERENCES_KEY).unwrap_or_default(),
+            None => UserPreferences::default(),
+        };
+        cc.egui_ctx.set_visuals(if preferences.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+
+        let setup_wizard = if preferences.is_first_run() {
+            Some(WizardStep::Workspace)
+        } else {
+            None
+        };
+        wake_on_shutdown(cc.egui_ctx.clone());
+
         #[cfg(not(target_arch = "wasm32"))]
         EVBApp {
-            progress: Arc::new(Mutex::new(0.0)),
+            progress: JobProgress::default(),
             parameters: EvbAppParams::default(),
-            active_tab: ActiveTab::MainTab,
+            preferences,
             rxn_eqn: String::from("None"),
             thread_handle: None,
+            build_diagnostics: DiagnosticLog::default(),
+            channel_map_import: None,
+            channel_discovery: None,
+            discovered_channels: Vec::new(),
+            discovery_run: 0,
+            setup_wizard,
+            incomplete_runs: Vec::new(),
+            post_batch_hooks: PostBatchHooks::default(),
+            output_sinks: OutputSinks::default(),
+            hit_filters: HitFilters::default(),
+            output_run: 0,
+            quick_build_run: None,
+            show_quick_build: false,
+            run_notes: None,
+            diagnostic: DiagnosticParams::default(),
+            diagnostic_columns: None,
+            histogram: None,
+            codec_report: None,
+            comparison: ComparisonParams::default(),
+            run_comparison: None,
+            opened_build: None,
+            shown_report: None,
+            shown_spectra: None,
+            paranoid: false,
+            resource_overrides: ResourceOverrides::default(),
+            log_level: None,
             window,
         }
     }
 
-    fn check_and_startup_processing_thread(&mut self) -> Result<(), WorkspaceError> {
+    //Lets an experiment's own binary run custom processing on every built batch
+    pub fn with_post_batch_hook(self, hook: Box<dyn PostBatchHook>) -> Self {
+        match self.post_batch_hooks.lock() {
+            Ok(mut hooks) => hooks.push(hook),
+            Err(_) => error!("Could not aquire lock to add a post batch hook"),
+        };
+        self
+    }
+
+    //Lets an experiment's own binary receive every event as it is built, e.g. for online monitoring
+    pub fn with_output_sink(self, sink: Box<dyn OutputSink>) -> Self {
+        match self.output_sinks.lock() {
+            Ok(mut sinks) => sinks.push(sink),
+            Err(_) => error!("Could not aquire lock to add an output sink"),
+        };
+        self
+    }
+
+    //Lets an experiment's own binary drop or correct single hits before they are built
+    pub fn with_hit_filter(self, filter: Box<dyn HitFilter>) -> Self {
+        match self.hit_filters.lock() {
+            Ok(mut filters) => filters.push(filter),
+            Err(_) => error!("Could not aquire lock to add a hit filter"),
+        };
+        self
+    }
+
+    //Expensive runtime checks for validating a new configuration (--paranoid on the command line)
+    pub fn with_paranoid(mut self, paranoid: bool) -> Self {
+        self.paranoid = paranoid;
+        self
+    }
+
+    //Limits from the command line (--threads, --memory-budget), replacing the ones of the config
+    pub fn with_resource_limits(
+        mut self,
+        threads: Option<usize>,
+        memory_budget_mb: Option<usize>,
+    ) -> Self {
+        self.resource_overrides = ResourceOverrides {
+            threads,
+            memory_budget_mb,
+        };
+        self
+    }
+
+    //Config file from the command line (--config), loaded in place of the default settings
+    pub fn with_config(mut self, path: Option<&Path>) -> Self {
+        if let Some(path) = path {
+            if self.read_params_from_file(path) {
+                self.preferences.remember_config_file(path);
+                self.setup_wizard = None;
+                info!("Loaded config {}", path.display());
+            }
+        }
+        self
+    }
+
+    //Log level from the command line (--log-level), replacing the one of the config
+    pub fn with_log_level(mut self, log_level: Option<LogLevel>) -> Self {
+        self.log_level = log_level;
+        self
+    }
+
+    //A quick build only builds the start of the first run of the run list, into the quick build
+    //directory of the workspace
+    fn check_and_startup_processing_thread(
+        &mut self,
+        is_quick_build: bool,
+    ) -> Result<(), WorkspaceError> {
         if self.thread_handle.is_none()
             && self.parameters.workspace.is_some()
             && !self.parameters.channel_map_entries.is_empty()
         {
+            //Edits on the tabs are not checked as they are made, so the settings are checked as a
+            //whole before anything is built, as a loaded config is
+            let yaml_str = serde_yaml::to_string(&self.parameters).unwrap_or_default();
+            let issues = validate_params(&yaml_str, &self.parameters);
+            if !issues.is_empty() {
+                for issue in issues.iter() {
+                    error!("Cannot build with this configuration: {}", issue.message);
+                }
+                return Ok(());
+            }
             let prog = self.progress.clone();
-            let r_params = ProcessParams {
-                archive_dir: self
-                    .parameters
-                    .workspace
-                    .as_ref()
-                    .unwrap()
-                    .get_archive_dir()?,
-                unpack_dir: self
-                    .parameters
-                    .workspace
-                    .as_ref()
-                    .unwrap()
-                    .get_unpack_dir()?,
-                output_dir: self
-                    .parameters
-                    .workspace
-                    .as_ref()
-                    .unwrap()
-                    .get_output_dir()?,
-                channel_map: self.parameters.channel_map_entries.clone(),
-                scaler_list: self.parameters.scaler_list_entries.clone(),
-                shift_map: self.parameters.shift_map_entries.clone(),
-                coincidence_window: self.parameters.coincidence_window,
-                run_min: self.parameters.run_min,
-                run_max: self.parameters.run_max + 1, //Make it [run_min, run_max]
-            };
+            let workspace = self.parameters.workspace.as_ref().unwrap();
+            let mut r_params = self.parameters.get_process_params(
+                workspace,
+                self.post_batch_hooks.clone(),
+                self.output_sinks.clone(),
+                self.hit_filters.clone(),
+                self.paranoid,
+            )?;
+            self.resource_overrides.apply(&mut r_params.resource_limits);
+            if let Some(log_level) = self.log_level {
+                r_params.log_level = log_level;
+            }
+            self.quick_build_run = None;
+            if is_quick_build {
+                r_params.runs.truncate(1);
+                r_params.output_dir = workspace.get_quick_build_dir()?;
+                r_params.quick_build = Some(self.parameters.quick_build.clone());
+                self.quick_build_run = r_params.runs.first().copied();
+            }
 
-            match self.progress.lock() {
-                Ok(mut x) => *x = 0.0,
-                Err(_) => error!("Could not aquire lock at starting processor..."),
-            };
+            self.progress.reset();
+            self.build_diagnostics = r_params.diagnostics.clone();
             self.thread_handle = Some(std::thread::spawn(|| process_runs(r_params, prog)));
         } else {
             error!("Cannot run event builder without all filepaths specified");
@@ -142,13 +1160,23 @@ impl EVBApp {
                 }
                 Err(_) => error!("An error occured in joining the processing thread!"),
             };
+            //Straight to the histograms of the quick build, to check the gates and map
+            if let Some(run) = self.quick_build_run.take() {
+                self.opened_build = None;
+                self.output_run = run;
+                self.show_quick_build = true;
+                self.diagnostic_columns = None;
+                self.histogram = None;
+                self.preferences.active_tab = ActiveTab::Histograms;
+            }
         }
     }
 
+    //YAML or TOML by the extension of the file
     fn write_params_to_file(&self, path: &Path) {
         if let Ok(mut config) = File::create(path) {
-            match serde_yaml::to_string(&self.parameters) {
-                Ok(yaml_str) => match config.write(yaml_str.as_bytes()) {
+            match format_config(&self.parameters, ConfigFormat::of_path(path)) {
+                Ok(text) => match config.write(text.as_bytes()) {
                     Ok(_) => (),
                     Err(x) => error!("Error writing config to file{}: {}", path.display(), x),
                 },
@@ -162,8 +1190,9 @@ impl EVBApp {
         }
     }
 
-    fn read_params_from_file(&mut self, path: &Path) {
-        let yaml_str = match std::fs::read_to_string(path) {
+    //Returns false when the file could not be used, the parameters are then unchanged
+    fn read_params_from_file(&mut self, path: &Path) -> bool {
+        let text = match std::fs::read_to_string(path) {
             Ok(s) => s,
             Err(x) => {
                 error!(
@@ -171,123 +1200,694 @@ impl EVBApp {
                     path.display(),
                     x
                 );
+                return false;
+            }
+        };
+
+        let format = ConfigFormat::of_path(path);
+        let params = match parse_config(&text, format) {
+            Ok(params) => params,
+            Err(x) => {
+                error!("Config file {} is invalid, {}", path.display(), x);
+                return false;
+            }
+        };
+
+        //The checks only find lines in YAML
+        let yaml_str = match format {
+            ConfigFormat::Yaml => text.as_str(),
+            ConfigFormat::Toml => "",
+        };
+        let issues = validate_params(yaml_str, &params);
+        if !issues.is_empty() {
+            for issue in issues.iter() {
+                error!("Config file {} is invalid, {}", path.display(), issue);
+            }
+            return false;
+        }
+
+        self.parameters = params;
+        true
+    }
+
+    //Text and CSV files are plain shift maps, anything else is read as YAML
+    fn read_shift_map_from_file(&mut self, path: &Path) {
+        let is_text = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("txt") || ext.eq_ignore_ascii_case("csv"));
+        if is_text {
+            match read_shift_file(path) {
+                Ok(entries) => self.parameters.shift_map_entries = entries,
+                Err(x) => error!("Unable to read shift map {}: {}", path.display(), x),
+            };
+            return;
+        }
+
+        let yaml_str = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(x) => {
+                error!(
+                    "Unable to open and read time offsets file {} with error {}",
+                    path.display(),
+                    x
+                );
                 return;
             }
         };
 
-        match serde_yaml::from_str::<EvbAppParams>(&yaml_str) {
-            Ok(params) => self.parameters = params,
-            Err(x) => error!(
-                "Unable to write configuration to file, serializer error: {}",
-                x
-            ),
+        //Either a shift map, or the time offsets saved by a time calibrator run
+        match serde_yaml::from_str::<Vec<ShiftMapEntry>>(&yaml_str) {
+            Ok(entries) => self.parameters.shift_map_entries = entries,
+            Err(x) => match serde_yaml::from_str::<CampaignTimeOffsets>(&yaml_str) {
+                Ok(offsets) => self.parameters.shift_map_entries = offsets.entries,
+                Err(_) => error!("Unable to read time offsets, serializer error: {}", x),
+            },
         };
     }
 
-    fn channel_map_ui(&mut self, ui: &mut egui::Ui) {
-        ui.label(
-            RichText::new("Channel Map")
-                .color(Color32::LIGHT_BLUE)
-                .size(18.0),
+    //Replaces the state gates with the Xavg gates of a plotter gate file
+    fn read_state_gates_from_file(&mut self, path: &Path) {
+        let file = match GateFile::read(path) {
+            Ok(file) => file,
+            Err(x) => {
+                error!("Unable to read gate file {}: {}", path.display(), x);
+                return;
+            }
+        };
+        let (gates, unused) = file.get_state_gates();
+        if !unused.is_empty() {
+            warn!(
+                "Gate file {} has gates that are not on Xavg, which were not imported: {}",
+                path.display(),
+                unused.join(", ")
+            );
+        }
+        info!(
+            "Imported {} state gates from {}",
+            gates.len(),
+            path.display()
         );
+        self.parameters.sps.state_gates = gates;
+    }
 
-        if ui.button("Add Board").clicked() {
-            self.parameters.channel_map_entries.push(Board::default()); // This line seems correct, assuming boards is a Vec<Board>
+    fn write_state_gates_to_file(&self, path: &Path) {
+        match GateFile::from_state_gates(&self.parameters.sps.state_gates).write(path) {
+            Ok(()) => info!("Wrote state gates to {}", path.display()),
+            Err(x) => error!("Unable to write gate file {}: {}", path.display(), x),
         }
+    }
 
-        // Use a DragValue to adjust the desired number of boards
-        ui.horizontal(|ui| {
-            ui.label("Number of Boards:");
-            let mut desired_board_count = self.parameters.channel_map_entries.len();
-            if ui
-                .add(egui::DragValue::new(&mut desired_board_count).clamp_range(0..=16))
-                .changed()
-            {
-                self.parameters
-                    .channel_map_entries
-                    .resize_with(desired_board_count, Board::default);
+    //A calibration run's gains, added as an anchor at the run number found in the file name
+    fn read_gain_anchor_from_file(&mut self, path: &Path) {
+        let yaml_str = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(x) => {
+                error!(
+                    "Unable to open and read gain file {} with error {}",
+                    path.display(),
+                    x
+                );
+                return;
             }
-        });
+        };
 
-        // Use a horizontal scroll area to contain all the boards
-        egui::ScrollArea::horizontal().show(ui, |ui| {
-            ui.horizontal(|ui| {
-                for (board_idx, board) in self.parameters.channel_map_entries.iter_mut().enumerate()
-                {
-                    ui.vertical(|ui| {
-                        ui.group(|ui| {
-                            egui::Grid::new(format!("board_{}", board_idx))
-                                .num_columns(2)
-                                .spacing([20.0, 4.0])
-                                .show(ui, |ui| {
-                                    ui.label(format!("Board {}", board_idx));
-                                    ui.label("Channel Number");
-                                    ui.end_row();
+        match serde_yaml::from_str::<Vec<GainEntry>>(&yaml_str) {
+            Ok(entries) => {
+                let run_number = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.split('_').find_map(|part| part.parse::<i32>().ok()))
+                    .unwrap_or(0);
+                self.parameters.gain_anchors.push(GainAnchor {
+                    run_number,
+                    entries,
+                });
+            }
+            Err(x) => error!("Unable to read gains, serializer error: {}", x),
+        };
+    }
 
-                                    for (channel_idx, channel_type) in
-                                        board.channels.iter_mut().enumerate()
-                                    {
-                                        ui.label(format!("{}", channel_idx));
-                                        egui::ComboBox::from_id_source(format!(
+    fn read_energy_calibration_from_file(&mut self, path: &Path) {
+        let yaml_str = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(x) => {
+                error!(
+                    "Unable to open and read energy calibration file {} with error {}",
+                    path.display(),
+                    x
+                );
+                return;
+            }
+        };
+
+        match serde_yaml::from_str::<Vec<EnergyCalibrationEntry>>(&yaml_str) {
+            Ok(entries) => self.parameters.energy_calibration = entries,
+            Err(x) => error!("Unable to read energy calibration, serializer error: {}", x),
+        };
+    }
+
+    fn import_channel_map_from_file(&mut self, path: &Path) {
+        let yaml_str = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(x) => {
+                error!(
+                    "Unable to open and read channel map file {} with error {}",
+                    path.display(),
+                    x
+                );
+                return;
+            }
+        };
+
+        let imported = match serde_yaml::from_str::<ChannelMapFile>(&yaml_str) {
+            Ok(file) => file.channel_map_entries,
+            Err(x) => {
+                error!(
+                    "Channel map file {} is invalid, {}",
+                    path.display(),
+                    describe_parse_error(&yaml_str, &x)
+                );
+                return;
+            }
+        };
+
+        self.import_channel_map(imported, path);
+    }
+
+    //Merged straight away when the imported map agrees with the current one and the merged map is
+    //valid, otherwise the merge dialog is opened
+    fn import_channel_map(&mut self, imported: Vec<Board>, path: &Path) {
+        let import = ChannelMapImport {
+            conflicts: find_channel_conflicts(&self.parameters.channel_map_entries, &imported),
+            imported,
+        };
+        if import.conflicts.is_empty()
+            && import
+                .get_errors(&self.parameters.channel_map_entries)
+                .is_empty()
+        {
+            self.parameters.channel_map_entries =
+                merge_channel_maps(&self.parameters.channel_map_entries, &import.imported, &[]);
+            info!("Imported channel map from {}", path.display());
+        } else {
+            self.channel_map_import = Some(import);
+        }
+    }
+
+    fn import_channel_map_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.config_dir())
+            .add_filter("Config file", &["yaml", "toml"])
+            .pick_file();
+
+        if let Some(real_path) = result {
+            self.preferences.remember_config_file(&real_path);
+            self.import_channel_map_from_file(&real_path)
+        }
+    }
+
+    //Takes the channel types from the labels of the enabled channels of a CoMPASS settings.xml,
+    //with the same merge as importing a channel map
+    fn import_compass_settings_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .add_filter("CoMPASS settings", &["xml"])
+            .pick_file();
+
+        let path = match result {
+            Some(path) => path,
+            None => return,
+        };
+        let imported = match CompassSettings::read(&path) {
+            Ok(settings) => settings.get_channel_map(),
+            Err(x) => {
+                error!("{}", x);
+                return;
+            }
+        };
+        self.import_channel_map(imported, &path);
+    }
+
+    fn save_channel_map_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.config_dir())
+            .set_file_name("channel_map.yaml")
+            .add_filter("YAML file", &["yaml"])
+            .save_file();
+
+        if let Some(real_path) = result {
+            match write_channel_map_file(&real_path, &self.parameters.channel_map_entries) {
+                Ok(()) => {
+                    self.preferences.remember_config_file(&real_path);
+                    info!("Wrote channel map to {}", real_path.display());
+                }
+                Err(x) => error!("Unable to write channel map {}: {}", real_path.display(), x),
+            }
+        }
+    }
+
+    //Scans the archive of a run of the workspace on its own thread, the archive is unpacked to a
+    //scratch directory to read it
+    fn start_run_discovery(&mut self) {
+        let archive_dir = match self
+            .parameters
+            .workspace
+            .as_ref()
+            .map(|workspace| workspace.get_archive_dir())
+        {
+            Some(Ok(dir)) => dir,
+            Some(Err(x)) => {
+                error!("Unable to scan run {}: {}", self.discovery_run, x);
+                return;
+            }
+            None => {
+                error!("Open a workspace to scan one of its runs");
+                return;
+            }
+        };
+        let archive_path = match find_run_archive(&archive_dir, self.discovery_run) {
+            Some(path) => path,
+            None => {
+                error!(
+                    "{} has no archive of run {}",
+                    archive_dir.display(),
+                    self.discovery_run
+                );
+                return;
+            }
+        };
+        let boards = self.parameters.channel_map_entries.clone();
+        self.channel_discovery = Some(std::thread::spawn(move || {
+            discover_run_channels(&archive_path, &ChannelMap::new(&boards))
+        }));
+    }
+
+    //The same for a directory of CoMPASS files, e.g. the UNFILTERED directory of a run on the DAQ
+    //machine
+    fn scan_folder_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .pick_folder();
+
+        if let Some(real_path) = result {
+            let boards = self.parameters.channel_map_entries.clone();
+            self.channel_discovery = Some(std::thread::spawn(move || {
+                discover_channels(&real_path, &ChannelMap::new(&boards))
+            }));
+        }
+    }
+
+    fn poll_channel_discovery(&mut self) {
+        if !self
+            .channel_discovery
+            .as_ref()
+            .is_some_and(|handle| handle.is_finished())
+        {
+            return;
+        }
+        if let Some(handle) = self.channel_discovery.take() {
+            match handle.join() {
+                Ok(Ok(channels)) => {
+                    info!("Found {} channels with data", channels.len());
+                    self.discovered_channels = channels;
+                }
+                Ok(Err(x)) => error!("Unable to scan for channels with data: {}", x),
+                Err(_) => error!("An error occured in joining the scanning thread!"),
+            }
+        }
+    }
+
+    //Merge dialog shown while an imported channel map disagrees with the current one
+    fn channel_map_import_ui(&mut self, ctx: &egui::Context) {
+        let mut finished = false;
+        let mut apply = false;
+        if let Some(import) = &mut self.channel_map_import {
+            let errors = import.get_errors(&self.parameters.channel_map_entries);
+            egui::Window::new("Channel Map Conflicts")
+                .collapsible(false)
+                .show(ctx, |ui| {
+                    if !import.conflicts.is_empty() {
+                        ui.label(
+                            "These channels are mapped differently in the imported channel map:",
+                        );
+                        egui::Grid::new("channel_map_conflicts")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.label("Board");
+                                ui.label("Channel");
+                                ui.label("Current");
+                                ui.label("Imported");
+                                ui.end_row();
+
+                                for conflict in import.conflicts.iter_mut() {
+                                    ui.label(format!("{}", conflict.board));
+                                    ui.label(format!("{}", conflict.channel));
+                                    ui.radio_value(
+                                        &mut conflict.use_imported,
+                                        false,
+                                        conflict.current.to_string(),
+                                    );
+                                    ui.radio_value(
+                                        &mut conflict.use_imported,
+                                        true,
+                                        conflict.imported.to_string(),
+                                    );
+                                    ui.end_row();
+                                }
+                            });
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Keep All Current").clicked() {
+                                import
+                                    .conflicts
+                                    .iter_mut()
+                                    .for_each(|c| c.use_imported = false);
+                            }
+                            if ui.button("Use All Imported").clicked() {
+                                import
+                                    .conflicts
+                                    .iter_mut()
+                                    .for_each(|c| c.use_imported = true);
+                            }
+                        });
+                    }
+
+                    //Nothing is merged while the result would be invalid
+                    if !errors.is_empty() {
+                        ui.label("The merged channel map would not be valid:");
+                    }
+                    for error in errors.iter() {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(errors.is_empty(), egui::Button::new("Apply"))
+                            .clicked()
+                        {
+                            apply = true;
+                            finished = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            finished = true;
+                        }
+                    });
+                });
+        }
+
+        if finished {
+            if let Some(import) = self.channel_map_import.take() {
+                if apply {
+                    self.parameters.channel_map_entries = merge_channel_maps(
+                        &self.parameters.channel_map_entries,
+                        &import.imported,
+                        &import.conflicts,
+                    );
+                    info!("Merged imported channel map");
+                }
+            }
+        }
+    }
+
+    //Walks a new user through what a first build needs, one step at a time. Every setting is made
+    //in the app's parameters as on the tabs, so the wizard can be left at any step.
+    fn setup_wizard_ui(&mut self, ctx: &egui::Context) {
+        let step = match self.setup_wizard {
+            Some(step) => step,
+            None => return,
+        };
+        let step_count = WizardStep::iter().count();
+        let step_number = WizardStep::iter()
+            .position(|other| other == step)
+            .unwrap_or(0)
+            + 1;
+        let mut next_step = Some(step);
+        let mut finished = false;
+        egui::Window::new("Setup")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new(format!(
+                        "Step {} of {}: {}",
+                        step_number,
+                        step_count,
+                        step.get_title()
+                    ))
+                    .color(Color32::LIGHT_BLUE)
+                    .size(18.0),
+                );
+                ui.label(step.get_help());
+                ui.separator();
+
+                let is_ready = match step {
+                    WizardStep::Workspace => {
+                        ui.horizontal(|ui| {
+                            ui.label("Workspace:");
+                            ui.label(match &self.parameters.workspace {
+                                Some(ws) => ws.get_parent_str(),
+                                None => "None",
+                            });
+                            if ui.button("Open...").clicked() {
+                                self.open_workspace_dialog();
+                            }
+                        });
+                        self.parameters.workspace.is_some()
+                    }
+                    WizardStep::ChannelMap => {
+                        ui.horizontal(|ui| {
+                            if ui.button("Import Channel Map...").clicked() {
+                                self.import_channel_map_dialog();
+                            }
+                            if ui.button("Edit on the Channel Map Tab").clicked() {
+                                self.preferences.active_tab = ActiveTab::ChannelMap;
+                            }
+                        });
+                        let mapped = self
+                            .parameters
+                            .channel_map_entries
+                            .iter()
+                            .flat_map(|board| board.channels.iter())
+                            .filter(|channel| **channel != ChannelType::None)
+                            .count();
+                        ui.label(format!(
+                            "{} boards, {} channels mapped",
+                            self.parameters.channel_map_entries.len(),
+                            mapped
+                        ));
+                        mapped > 0
+                    }
+                    WizardStep::CoincidenceWindow => {
+                        ui.horizontal(|ui| {
+                            ui.label("Coincidence Window");
+                            ui.add(
+                                egui::widgets::DragValue::new(
+                                    &mut self.parameters.coincidence_window,
+                                )
+                                .speed(100)
+                                .custom_formatter(|n, _| format!("{:e}", n)),
+                            );
+                            ui.label(self.parameters.coincidence_window_unit.as_ref());
+                        });
+                        self.parameters.coincidence_window > 0.0
+                    }
+                    WizardStep::TestBuild => {
+                        let is_running = self.thread_handle.is_some();
+                        ui.horizontal(|ui| {
+                            ui.label("Run");
+                            ui.add_enabled(
+                                !is_running,
+                                egui::widgets::DragValue::new(&mut self.parameters.run_min)
+                                    .speed(1),
+                            );
+                            if ui
+                                .add_enabled(!is_running, egui::Button::new("Build"))
+                                .clicked()
+                            {
+                                self.parameters.run_max = self.parameters.run_min;
+                                self.parameters.run_list.clear();
+                                self.start_processing();
+                            }
+                            if is_running {
+                                ui.add(egui::Spinner::new());
+                            }
+                        });
+                        ui.add(
+                            egui::widgets::ProgressBar::new(self.progress.get_fraction())
+                                .show_percentage(),
+                        );
+                        if ui.button("Show Outputs").clicked() {
+                            self.output_run = self.parameters.run_min;
+                            self.preferences.active_tab = ActiveTab::Outputs;
+                        }
+                        !is_running
+                    }
+                };
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    let previous = step.get_step(-1);
+                    if ui
+                        .add_enabled(previous.is_some(), egui::Button::new("Back"))
+                        .clicked()
+                    {
+                        next_step = previous;
+                    }
+                    match step.get_step(1) {
+                        Some(following) => {
+                            if ui
+                                .add_enabled(is_ready, egui::Button::new("Next"))
+                                .clicked()
+                            {
+                                next_step = Some(following);
+                            }
+                        }
+                        None => {
+                            if ui
+                                .add_enabled(is_ready, egui::Button::new("Finish"))
+                                .clicked()
+                            {
+                                finished = true;
+                            }
+                        }
+                    }
+                    if ui.button("Skip Setup").clicked() {
+                        finished = true;
+                    }
+                });
+            });
+
+        if finished {
+            self.preferences.finished_setup = true;
+            self.setup_wizard = None;
+        } else {
+            self.setup_wizard = next_step;
+        }
+    }
+
+    fn channel_map_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Channel Map")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        if ui.button("Import Channel Map...").clicked() {
+            self.import_channel_map_dialog();
+        }
+
+        if ui
+            .button("Import CoMPASS Settings...")
+            .on_hover_text(
+                "Map the enabled channels of a CoMPASS settings.xml by their labels, e.g. a \
+                channel labelled Cebra3 becomes Cebra3",
+            )
+            .clicked()
+        {
+            self.import_compass_settings_dialog();
+        }
+
+        if ui
+            .button("Save Channel Map...")
+            .on_hover_text("Write only the channel map to a file, to import into other configs")
+            .clicked()
+        {
+            self.save_channel_map_dialog();
+        }
+
+        if ui.button("Add Board").clicked() {
+            self.parameters.channel_map_entries.push(Board::default()); // This line seems correct, assuming boards is a Vec<Board>
+        }
+
+        // Use a DragValue to adjust the desired number of boards
+        ui.horizontal(|ui| {
+            ui.label("Number of Boards:");
+            let mut desired_board_count = self.parameters.channel_map_entries.len();
+            if ui
+                .add(egui::DragValue::new(&mut desired_board_count).clamp_range(0..=16))
+                .changed()
+            {
+                self.parameters
+                    .channel_map_entries
+                    .resize_with(desired_board_count, Board::default);
+            }
+        });
+
+        let channel_types = get_selectable_types(&self.parameters.channel_map_entries);
+
+        // Use a horizontal scroll area to contain all the boards
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (board_idx, board) in self.parameters.channel_map_entries.iter_mut().enumerate()
+                {
+                    ui.vertical(|ui| {
+                        ui.group(|ui| {
+                            egui::Grid::new(format!("board_{}", board_idx))
+                                .num_columns(2)
+                                .spacing([20.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.label(format!("Board {}", board_idx));
+                                    ui.label("Channel Number");
+                                    ui.end_row();
+
+                                    ui.label("Energy").on_hover_text(
+                                        "Energy field width, 64 bit for extended HEX energies",
+                                    );
+                                    egui::ComboBox::from_id_source(format!(
+                                        "energy_width_{}",
+                                        board_idx
+                                    ))
+                                    .selected_text(board.energy_width.as_ref())
+                                    .show_ui(ui, |ui| {
+                                        for width in EnergyWidth::iter() {
+                                            ui.selectable_value(
+                                                &mut board.energy_width,
+                                                width,
+                                                width.as_ref(),
+                                            );
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    ui.label("Timestamps").on_hover_text(
+                                        "Unit of the board's timestamps: ps as written by \
+                                        CoMPASS, or ticks of the sampling clock",
+                                    );
+                                    egui::ComboBox::from_id_source(format!(
+                                        "timestamp_unit_{}",
+                                        board_idx
+                                    ))
+                                    .selected_text(board.timestamp_unit.as_ref())
+                                    .show_ui(ui, |ui| {
+                                        for unit in TimestampUnit::iter() {
+                                            ui.selectable_value(
+                                                &mut board.timestamp_unit,
+                                                unit,
+                                                unit.as_ref(),
+                                            );
+                                        }
+                                    });
+                                    ui.end_row();
+
+                                    for (channel_idx, channel_type) in
+                                        board.channels.iter_mut().enumerate()
+                                    {
+                                        ui.label(format!("{}", channel_idx));
+                                        egui::ComboBox::from_id_source(format!(
                                             "channel_type_{}_{}",
                                             board_idx, channel_idx
                                         ))
-                                        .selected_text(format!("{:?}", channel_type))
+                                        .selected_text(channel_type.to_string())
                                         .show_ui(
                                             ui,
                                             |ui| {
                                                 // Populate ComboBox with channel types
-
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra0,
-                                                    "Cebra0",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra1,
-                                                    "Cebra1",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra2,
-                                                    "Cebra2",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra3,
-                                                    "Cebra3",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra4,
-                                                    "Cebra4",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra5,
-                                                    "Cebra5",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra6,
-                                                    "Cebra6",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra7,
-                                                    "Cebra7",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::Cebra8,
-                                                    "Cebra8",
-                                                );
-                                                ui.selectable_value(
-                                                    channel_type,
-                                                    ChannelType::None,
-                                                    "None",
-                                                );
+                                                for option in channel_types.iter() {
+                                                    ui.selectable_value(
+                                                        channel_type,
+                                                        *option,
+                                                        option.to_string(),
+                                                    );
+                                                }
                                             },
                                         );
                                         ui.end_row();
@@ -299,225 +1899,3125 @@ impl EVBApp {
                 }
             });
         });
+
+        self.poll_channel_discovery();
+        ui.collapsing("Discover Channels", |ui| {
+            ui.label(
+                "Channels with data in a run and their hits, to assign a type to each. Boards \
+                the map does not have yet are added when one of their channels is assigned",
+            );
+            let is_scanning = self.channel_discovery.is_some();
+            ui.horizontal(|ui| {
+                ui.label("Run:");
+                ui.add(egui::DragValue::new(&mut self.discovery_run).speed(1));
+                if ui
+                    .add_enabled(!is_scanning, egui::Button::new("Scan Run"))
+                    .on_hover_text("Read the archive of the run in the workspace")
+                    .clicked()
+                {
+                    self.start_run_discovery();
+                }
+                if ui
+                    .add_enabled(!is_scanning, egui::Button::new("Scan Folder..."))
+                    .on_hover_text("Read a directory of CoMPASS files instead")
+                    .clicked()
+                {
+                    self.scan_folder_dialog();
+                }
+                if is_scanning {
+                    ui.spinner();
+                }
+            });
+            egui::Grid::new("discovered_channels")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Board");
+                    ui.label("Channel");
+                    ui.label("Hits");
+                    ui.label("Type");
+                    ui.end_row();
+                    for channel in self.discovered_channels.iter() {
+                        ui.label(channel.board_number.to_string());
+                        ui.label(channel.channel_number.to_string());
+                        ui.label(format_count(channel.hits));
+                        let mut channel_type =
+                            channel.get_channel_type(&self.parameters.channel_map_entries);
+                        let previous = channel_type;
+                        egui::ComboBox::from_id_source(format!(
+                            "discovered_{}_{}",
+                            channel.board_number, channel.channel_number
+                        ))
+                        .selected_text(channel_type.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in channel_types.iter() {
+                                ui.selectable_value(&mut channel_type, *option, option.to_string());
+                            }
+                        });
+                        if channel_type != previous {
+                            channel.set_channel_type(
+                                &mut self.parameters.channel_map_entries,
+                                channel_type,
+                            );
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        ui.collapsing("By Detector", |ui| {
+            let map = ChannelMap::new(&self.parameters.channel_map_entries);
+            egui::Grid::new("detector_channels")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label("Detector");
+                    ui.label("Board/Channel");
+                    ui.end_row();
+                    for entry in map.get_detector_channels() {
+                        ui.label(entry.detector.to_string());
+                        ui.label(entry.describe_channels());
+                        ui.end_row();
+                    }
+                });
+            if ui
+                .button("Copy Table")
+                .on_hover_text("Copy the full mapping table as plain text")
+                .clicked()
+            {
+                ui.output_mut(|output| output.copied_text = map.format_table());
+            }
+        });
+
+        ui.collapsing("Detector Groups", |ui| {
+            ui.label("Summed energy in <Name>Energy, number of hits in <Name>Mult");
+            if ui.button("Add Group").clicked() {
+                self.parameters
+                    .detector_groups
+                    .push(DetectorGroup::default());
+            }
+            let mut to_remove = Vec::new();
+            for (index, group) in self.parameters.detector_groups.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut group.name);
+                    ui.menu_button(format!("Detectors ({})", group.detectors.len()), |ui| {
+                        for detector in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|d| *d != ChannelType::None)
+                        {
+                            let mut is_member = group.detectors.contains(&detector);
+                            if ui.checkbox(&mut is_member, detector.to_string()).changed() {
+                                if is_member {
+                                    group.detectors.push(detector);
+                                } else {
+                                    group.detectors.retain(|d| *d != detector);
+                                }
+                            }
+                        }
+                    });
+                    ui.label(group.describe_detectors());
+                    if ui.button("❌").clicked() {
+                        to_remove.push(index);
+                    }
+                });
+            }
+            for &index in to_remove.iter().rev() {
+                self.parameters.detector_groups.remove(index);
+            }
+        });
+
+        ui.collapsing("Passthrough Channels", |ui| {
+            ui.label(
+                "Energy and time of a Passthrough channel as they are, in <Name>Energy and \
+                <Name>Time. Channels not named here use Passthrough<n>",
+            );
+            if ui.button("Add Name").clicked() {
+                self.parameters
+                    .passthrough
+                    .push(PassthroughEntry::default());
+            }
+            let mut to_remove = Vec::new();
+            for (index, entry) in self.parameters.passthrough.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label("Channel:");
+                    egui::ComboBox::from_id_source(format!("passthrough_channel_{}", index))
+                        .selected_text(entry.channel.to_string())
+                        .show_ui(ui, |ui| {
+                            for channel in channel_types
+                                .iter()
+                                .copied()
+                                .filter(|c| c.get_passthrough().is_some())
+                            {
+                                ui.selectable_value(
+                                    &mut entry.channel,
+                                    channel,
+                                    channel.to_string(),
+                                );
+                            }
+                        });
+                    ui.label("Name:");
+                    ui.text_edit_singleline(&mut entry.name);
+                    if ui.button("❌").clicked() {
+                        to_remove.push(index);
+                    }
+                });
+            }
+            for &index in to_remove.iter().rev() {
+                self.parameters.passthrough.remove(index);
+            }
+        });
+
+        ui.collapsing("Pair Windows", |ui| {
+            ui.label(
+                "Coincidence window between the hits of two channel groups, e.g. CeBrA and SPS \
+                with the flight time. Pairs not listed use the coincidence window",
+            );
+            if ui.button("Add Pair").clicked() {
+                self.parameters
+                    .pair_windows
+                    .push(PairWindowEntry::default());
+            }
+            let mut to_remove = Vec::new();
+            for (index, entry) in self.parameters.pair_windows.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    for (side, group) in
+                        [("first", &mut entry.first), ("second", &mut entry.second)]
+                    {
+                        egui::ComboBox::from_id_source(format!("pair_window_{}_{}", side, index))
+                            .selected_text(group.as_ref())
+                            .show_ui(ui, |ui| {
+                                for option in ChannelGroup::iter()
+                                    .filter(|option| *option != ChannelGroup::Scalers)
+                                {
+                                    ui.selectable_value(group, option, option.as_ref());
+                                }
+                            });
+                    }
+                    ui.label("Window:");
+                    ui.add(egui::DragValue::new(&mut entry.window).speed(1.0));
+                    egui::ComboBox::from_id_source(format!("pair_window_unit_{}", index))
+                        .selected_text(entry.unit.as_ref())
+                        .show_ui(ui, |ui| {
+                            for unit in TimeUnit::iter() {
+                                ui.selectable_value(&mut entry.unit, unit, unit.as_ref());
+                            }
+                        });
+                    if ui.button("❌").clicked() {
+                        to_remove.push(index);
+                    }
+                });
+            }
+            for &index in to_remove.iter().rev() {
+                self.parameters.pair_windows.remove(index);
+            }
+        });
+
+        ui.collapsing("Multiple Hits", |ui| {
+            ui.label("Which hit fills the columns when a detector fires more than once in an event");
+            ui.label(format!(
+                "Detectors not listed: {}",
+                MultiHitPolicy::default().as_ref()
+            ));
+            if ui.button("Add Detector").clicked() {
+                self.parameters.multi_hit.push(MultiHitEntry::default());
+            }
+            let mut to_remove = Vec::new();
+            for (index, entry) in self.parameters.multi_hit.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label("Detector:");
+                    egui::ComboBox::from_id_source(format!("multi_hit_detector_{}", index))
+                        .selected_text(entry.detector.to_string())
+                        .show_ui(ui, |ui| {
+                            for detector in channel_types
+                                .iter()
+                                .copied()
+                                .filter(|d| *d != ChannelType::None)
+                            {
+                                ui.selectable_value(
+                                    &mut entry.detector,
+                                    detector,
+                                    detector.to_string(),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_source(format!("multi_hit_policy_{}", index))
+                        .selected_text(entry.policy.as_ref())
+                        .show_ui(ui, |ui| {
+                            for policy in MultiHitPolicy::iter() {
+                                ui.selectable_value(&mut entry.policy, policy, policy.as_ref());
+                            }
+                        })
+                        .response
+                        .on_hover_text("Expand also writes every hit to list columns, e.g. Cebra0EnergyList (parquet only)");
+                    if ui.button("❌").clicked() {
+                        to_remove.push(index);
+                    }
+                });
+            }
+            for &index in to_remove.iter().rev() {
+                self.parameters.multi_hit.remove(index);
+            }
+        });
+
+        ui.collapsing("Energy Ranked Columns", |ui| {
+            ui.label("CeBrA hits ordered by energy, highest first: E1st, T1st, Id1st, E2nd, ...");
+            let rank = &mut self.parameters.energy_rank;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut rank.enabled, "Enabled");
+                ui.label("Ranks:");
+                ui.add(egui::DragValue::new(&mut rank.ranks).clamp_range(1..=MAX_ENERGY_RANKS));
+            });
+        });
+
+        ui.collapsing("Event Filters", |ui| {
+            ui.label("Only events passing every filter are written");
+            if ui.button("Add Filter").clicked() {
+                self.parameters
+                    .event_filters
+                    .push(EventFilterEntry::default());
+            }
+            let mut to_remove = Vec::new();
+            for (index, entry) in self.parameters.event_filters.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(format!("event_filter_kind_{}", index))
+                        .selected_text(entry.kind.as_ref())
+                        .show_ui(ui, |ui| {
+                            for kind in EventFilterKind::iter() {
+                                ui.selectable_value(&mut entry.kind, kind, kind.as_ref());
+                            }
+                        });
+                    match entry.kind {
+                        EventFilterKind::CebraEnergyAbove => {
+                            ui.label("Min:");
+                            ui.add(egui::DragValue::new(&mut entry.min).speed(1.0));
+                        }
+                        EventFilterKind::DetectorPresent => {
+                            egui::ComboBox::from_id_source(format!(
+                                "event_filter_detector_{}",
+                                index
+                            ))
+                            .selected_text(entry.detector.to_string())
+                            .show_ui(ui, |ui| {
+                                for detector in channel_types
+                                    .iter()
+                                    .copied()
+                                    .filter(|d| *d != ChannelType::None)
+                                {
+                                    ui.selectable_value(
+                                        &mut entry.detector,
+                                        detector,
+                                        detector.to_string(),
+                                    );
+                                }
+                            });
+                        }
+                        EventFilterKind::ColumnGate => {
+                            ui.label("Column:");
+                            ui.text_edit_singleline(&mut entry.column);
+                            ui.label("Min:");
+                            ui.add(egui::DragValue::new(&mut entry.min).speed(1.0));
+                            ui.label("Max:");
+                            ui.add(egui::DragValue::new(&mut entry.max).speed(1.0));
+                        }
+                    }
+                    if ui.button("❌").clicked() {
+                        to_remove.push(index);
+                    }
+                });
+            }
+            for &index in to_remove.iter().rev() {
+                self.parameters.event_filters.remove(index);
+            }
+        });
+    }
+
+    fn shift_map_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Time Shift Map")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        //Offsets derived from a TimeCalibrator run are applied to later runs without loading them
+        ui.label(
+            "The time offsets of the first TimeCalibrator run are saved to time_offsets.yaml in \
+            the built directory, and replace these shifts for every run after it",
+        );
+        if ui.button("Load Time Offsets...").clicked() {
+            let result = rfd::FileDialog::new()
+                .set_directory(self.preferences.config_dir())
+                .add_filter("YAML file", &["yaml"])
+                .add_filter("Text or CSV file", &["txt", "csv"])
+                .pick_file();
+
+            if let Some(real_path) = result {
+                self.preferences.remember_config_file(&real_path);
+                self.read_shift_map_from_file(&real_path)
+            }
+        }
+
+        // Assuming `self.shift_map_entries` is a Vec<ShiftMapEntry>
+        if ui.button("Add Entry").clicked() {
+            // Add a new entry with default values
+            self.parameters.shift_map_entries.push(ShiftMapEntry {
+                board_number: 0,
+                channel_number: 0,
+                time_shift: 0.0,
+            });
+        }
+
+        // Iterate over each entry with its index
+        let mut to_remove = Vec::new(); // Collect indices of entries to remove
+        for (index, entry) in self.parameters.shift_map_entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                // Allow the user to input board number, channel number, and time shift
+                ui.label("Board:");
+                ui.add(egui::DragValue::new(&mut entry.board_number));
+                ui.label("Channel:");
+                ui.add(egui::DragValue::new(&mut entry.channel_number));
+                ui.label("Time Shift:");
+                ui.add(egui::DragValue::new(&mut entry.time_shift).suffix(" ns"));
+
+                // Button to remove the current entry
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        // Remove entries marked for removal
+        // Iterate in reverse to ensure indices remain valid after removals
+        for &index in to_remove.iter().rev() {
+            self.parameters.shift_map_entries.remove(index);
+        }
+
+        ui.separator();
+        ui.label(
+            RichText::new("Time Walk Correction")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.horizontal(|ui| {
+            let walk = &mut self.parameters.time_walk;
+            ui.checkbox(&mut walk.enabled, "Enabled").on_hover_text(
+                "Take the CFD time walk of each detector, a polynomial in the raw energy, off its \
+                hit times before events are built",
+            );
+            ui.label("File:");
+            let mut path_str = walk.file.to_string_lossy().to_string();
+            if ui.text_edit_singleline(&mut path_str).changed() {
+                walk.file = PathBuf::from(path_str);
+            }
+            if ui.button("Browse...").clicked() {
+                let result = rfd::FileDialog::new()
+                    .set_directory(self.preferences.config_dir())
+                    .add_filter("Text or CSV file", &["txt", "csv"])
+                    .pick_file();
+                if let Some(real_path) = result {
+                    self.preferences.remember_config_file(&real_path);
+                    self.parameters.time_walk.file = real_path;
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Max Correction:");
+            ui.add(
+                egui::DragValue::new(&mut self.parameters.time_walk.max_correction)
+                    .clamp_range(0.0..=f64::MAX)
+                    .suffix(" ns"),
+            );
+        });
+    }
+
+    fn scaler_list_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Scalar List")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        if ui.button("Add Scaler Entry").clicked() {
+            // Add a new entry with default values
+            self.parameters.scaler_list_entries.push(ScalerEntryUI {
+                file_pattern: "".to_string(),
+                scaler_name: "".to_string(),
+            });
+        }
+
+        // Use a `ScrollArea` to ensure the UI can handle many entries
+        // egui::ScrollArea::horizontal().show(ui, |ui| {
+        let mut to_remove = Vec::new(); // Indices of entries to remove
+        for (index, entry) in self.parameters.scaler_list_entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("File Pattern:")
+                    .on_hover_text("Data_CH<channel_number>@<board_type>_<board_serial_number>");
+                ui.text_edit_singleline(&mut entry.file_pattern);
+                ui.label("Scaler Name:");
+                ui.text_edit_singleline(&mut entry.scaler_name);
+
+                // Button to remove the current entry
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        // Remove entries marked for removal, in reverse order to maintain correct indices
+        for &index in to_remove.iter().rev() {
+            self.parameters.scaler_list_entries.remove(index);
+        }
+        // });
+    }
+
+    fn skip_list_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Skip List")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Input files left out of a run, listed in its report");
+
+        if ui.button("Add Skipped File").clicked() {
+            self.parameters.skip_files.push(SkipEntry {
+                run: self.parameters.run_min,
+                ..Default::default()
+            });
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, entry) in self.parameters.skip_files.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Run:");
+                ui.add(egui::DragValue::new(&mut entry.run).speed(1));
+                ui.label("File Pattern:").on_hover_text(
+                    "A full segment name, or Data_CH<channel>@<board>_<serial> for all of its segments",
+                );
+                ui.text_edit_singleline(&mut entry.file_pattern);
+                ui.label("Reason:");
+                ui.text_edit_singleline(&mut entry.reason);
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.skip_files.remove(index);
+        }
+    }
+
+    fn campaign_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Campaign")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Target and beam of each range of runs, written to the outputs of its runs");
+        ui.checkbox(
+            &mut self.parameters.campaign.add_columns,
+            "Add a CampaignConfiguration column to the event tables",
+        );
+
+        if ui.button("Add Run Range").clicked() {
+            self.parameters.campaign.entries.push(CampaignEntry {
+                first_run: self.parameters.run_min,
+                last_run: self.parameters.run_max,
+                ..Default::default()
+            });
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, entry) in self.parameters.campaign.entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Runs:");
+                ui.add(egui::DragValue::new(&mut entry.first_run).speed(1));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut entry.last_run).speed(1));
+                ui.label("Target:");
+                ui.text_edit_singleline(&mut entry.target);
+                ui.label("Beam:");
+                ui.text_edit_singleline(&mut entry.beam);
+                ui.label("Label:");
+                ui.text_edit_singleline(&mut entry.label);
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.campaign.entries.remove(index);
+        }
+    }
+
+    fn efficiency_ui(&mut self, ui: &mut egui::Ui) {
+        let channel_types = get_selectable_types(&self.parameters.channel_map_entries);
+        ui.label(
+            RichText::new("Efficiency Curves")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("ln(eff) = c0 + c1*ln(E) + c2*ln(E)^2 + ..., written as a 1/eff weight column");
+
+        if ui.button("Add Curve").clicked() {
+            self.parameters.efficiency_entries.push(EfficiencyEntry {
+                detector: ChannelType::Cebra(0),
+                coefficients: vec![0.0],
+            });
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, entry) in self.parameters.efficiency_entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Detector:");
+                egui::ComboBox::from_id_source(format!("efficiency_detector_{}", index))
+                    .selected_text(entry.detector.to_string())
+                    .show_ui(ui, |ui| {
+                        for detector in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|d| get_weight_field(d).is_some())
+                        {
+                            ui.selectable_value(
+                                &mut entry.detector,
+                                detector,
+                                detector.to_string(),
+                            );
+                        }
+                    });
+
+                for (order, coeff) in entry.coefficients.iter_mut().enumerate() {
+                    ui.label(format!("c{}:", order));
+                    ui.add(egui::DragValue::new(coeff).speed(0.01));
+                }
+                if ui.button("+").clicked() {
+                    entry.coefficients.push(0.0);
+                }
+                if ui.button("-").clicked() && entry.coefficients.len() > 1 {
+                    entry.coefficients.pop();
+                }
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.efficiency_entries.remove(index);
+        }
+    }
+
+    fn simulation_ui(&mut self, ui: &mut egui::Ui) {
+        let channel_types = get_selectable_types(&self.parameters.channel_map_entries);
+        ui.label(
+            RichText::new("Energy Smearing")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("FWHM = resolution * E0 * sqrt(E / E0), applied to the Energy of each hit");
+
+        let smearing = &mut self.parameters.energy_smearing;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut smearing.enabled, "Enabled");
+            let mut has_seed = smearing.seed.is_some();
+            if ui
+                .checkbox(&mut has_seed, "Seed")
+                .on_hover_text("Without a seed, each run draws a new one")
+                .changed()
+            {
+                smearing.seed = if has_seed { Some(0) } else { None };
+            }
+            if let Some(seed) = &mut smearing.seed {
+                ui.add(egui::widgets::DragValue::new(seed));
+            }
+        });
+
+        if ui.button("Add Detector").clicked() {
+            smearing.entries.push(SmearingEntry::default());
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, entry) in smearing.entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Detector:");
+                egui::ComboBox::from_id_source(format!("smearing_detector_{}", index))
+                    .selected_text(entry.detector.to_string())
+                    .show_ui(ui, |ui| {
+                        for detector in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|d| *d != ChannelType::None)
+                        {
+                            ui.selectable_value(
+                                &mut entry.detector,
+                                detector,
+                                detector.to_string(),
+                            );
+                        }
+                    });
+                ui.label("Resolution:");
+                ui.add(
+                    egui::DragValue::new(&mut entry.resolution)
+                        .speed(0.001)
+                        .clamp_range(0.0..=1.0),
+                );
+                ui.label("at E0:");
+                ui.add(egui::DragValue::new(&mut entry.reference_energy).speed(1.0));
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            smearing.entries.remove(index);
+        }
+
+        ui.separator();
+        ui.label(
+            RichText::new("Time Jitter")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Gaussian jitter added to the hit times, for timing systematics");
+
+        let jitter = &mut self.parameters.time_jitter;
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut jitter.enabled, "Enabled");
+            let mut has_seed = jitter.seed.is_some();
+            if ui
+                .checkbox(&mut has_seed, "Seed")
+                .on_hover_text("Without a seed, each run draws a new one")
+                .changed()
+            {
+                jitter.seed = if has_seed { Some(0) } else { None };
+            }
+            if let Some(seed) = &mut jitter.seed {
+                ui.add(egui::widgets::DragValue::new(seed));
+            }
+        });
+
+        if ui.button("Add Jittered Detector").clicked() {
+            jitter.entries.push(JitterEntry::default());
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, entry) in jitter.entries.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Detector:");
+                egui::ComboBox::from_id_source(format!("jitter_detector_{}", index))
+                    .selected_text(entry.detector.to_string())
+                    .show_ui(ui, |ui| {
+                        for detector in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|d| *d != ChannelType::None)
+                        {
+                            ui.selectable_value(
+                                &mut entry.detector,
+                                detector,
+                                detector.to_string(),
+                            );
+                        }
+                    });
+                ui.label("Sigma (ns):");
+                ui.add(
+                    egui::DragValue::new(&mut entry.sigma)
+                        .speed(0.1)
+                        .clamp_range(0.0..=f64::MAX),
+                );
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            jitter.entries.remove(index);
+        }
+    }
+
+    fn column_codecs_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Column Compression")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label(
+            "Columns without a match are plain and zstd compressed. A trailing * matches a prefix.",
+        );
+
+        if ui.button("Add Column").clicked() {
+            self.parameters.column_codecs.push(ColumnCodec::default());
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, codec) in self.parameters.column_codecs.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Column:");
+                ui.text_edit_singleline(&mut codec.column);
+
+                ui.label("Encoding:");
+                egui::ComboBox::from_id_source(format!("codec_encoding_{}", index))
+                    .selected_text(codec.encoding.as_ref())
+                    .show_ui(ui, |ui| {
+                        for encoding in ColumnEncoding::iter() {
+                            ui.selectable_value(&mut codec.encoding, encoding, encoding.as_ref());
+                        }
+                    });
+
+                ui.label("Compression:");
+                egui::ComboBox::from_id_source(format!("codec_compression_{}", index))
+                    .selected_text(codec.compression.as_ref())
+                    .show_ui(ui, |ui| {
+                        for compression in ColumnCompression::iter() {
+                            ui.selectable_value(
+                                &mut codec.compression,
+                                compression,
+                                compression.as_ref(),
+                            );
+                        }
+                    });
+
+                if codec.compression == ColumnCompression::Zstd {
+                    let mut has_level = codec.level.is_some();
+                    ui.checkbox(&mut has_level, "Level");
+                    if has_level {
+                        let level = codec.level.get_or_insert(3);
+                        ui.add(egui::DragValue::new(level).clamp_range(ZSTD_LEVEL_RANGE));
+                    } else {
+                        codec.level = None;
+                    }
+                }
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.column_codecs.remove(index);
+        }
+
+        ui.separator();
+        self.codec_stats_ui(ui);
+    }
+
+    //Writes a sample of the events of a built run with every codec, to see which columns are
+    //worth a codec of their own
+    fn codec_stats_ui(&mut self, ui: &mut egui::Ui) {
+        let output_dir = match self.get_shown_output_dir() {
+            Some(Ok(dir)) => dir,
+            _ => {
+                ui.label("Set a workspace to analyze the compression of its built runs");
+                return;
+            }
+        };
+
+        self.shown_run_ui(ui);
+
+        let layout = RunLayout::new(&output_dir, self.output_run);
+        let events_path = [None, Some(0)]
+            .into_iter()
+            .map(|fragment| layout.get_table_path(EVENTS_STEM, fragment))
+            .find(|path| path.exists());
+        let events_path = match events_path {
+            Some(path) => path,
+            None => {
+                ui.label(format!("Run {} has not been built", self.output_run));
+                return;
+            }
+        };
+
+        if ui
+            .button("Analyze Run")
+            .on_hover_text("Write a sample of the events with every codec and compare the sizes")
+            .clicked()
+        {
+            match analyze_codecs(&events_path, DEFAULT_SAMPLE_ROWS) {
+                Ok(report) => self.codec_report = Some((self.output_run, report)),
+                Err(x) => error!("Could not analyze {}: {x}", events_path.display()),
+            }
+        }
+
+        let (run, report) = match &self.codec_report {
+            Some((run, report)) => (*run, report),
+            None => return,
+        };
+        ui.label(format!("Codec statistics of run {}", run));
+        let recommended = report.get_recommended_codecs();
+        if !recommended.is_empty() && ui.button("Use Recommended").clicked() {
+            //Recommendations replace the entries of the same columns and go first, the first
+            //matching codec wins
+            self.parameters.column_codecs.retain(|codec| {
+                !recommended
+                    .iter()
+                    .any(|recommendation| recommendation.column == codec.column)
+            });
+            let mut codecs = recommended;
+            codecs.append(&mut self.parameters.column_codecs);
+            self.parameters.column_codecs = codecs;
+        }
+        egui::ScrollArea::vertical()
+            .id_source("codec_stats_scroll")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                ui.label(RichText::new(report.format_table()).monospace());
+            });
+    }
+
+    fn sps_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("SPS Focal Plane")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Used when delay lines are in the channel map");
+
+        ui.horizontal(|ui| {
+            ui.label("Front Weight:")
+                .on_hover_text("Xavg = w * X1 + (1 - w) * X2");
+            ui.add(
+                egui::DragValue::new(&mut self.parameters.sps.front_weight)
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0),
+            );
+        });
+
+        ui.horizontal(|ui| {
+            let spectra = &mut self.parameters.sps.spectra;
+            ui.checkbox(&mut spectra.enabled, "Write Gated Spectra")
+                .on_hover_text(
+                    "Write run_<num>/spectra/state_spectra.csv with a gamma spectrum per gate",
+                );
+            ui.label("Bins:");
+            ui.add(egui::DragValue::new(&mut spectra.bins).clamp_range(1..=1_000_000));
+            ui.label("Min:");
+            ui.add(egui::DragValue::new(&mut spectra.min));
+            ui.label("Max:");
+            ui.add(egui::DragValue::new(&mut spectra.max));
+        });
+
+        ui.separator();
+        ui.label(
+            RichText::new("State Gates")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Gate").clicked() {
+                self.parameters.sps.state_gates.push(StateGate {
+                    name: String::new(),
+                    x_min: 0.0,
+                    x_max: 0.0,
+                });
+            }
+
+            //Gates shared with the spectrum plotter, in its JSON format
+            if ui.button("Import Gates...").clicked() {
+                let result = rfd::FileDialog::new()
+                    .set_directory(self.preferences.config_dir())
+                    .add_filter("JSON file", &["json"])
+                    .pick_file();
+                if let Some(real_path) = result {
+                    self.read_state_gates_from_file(&real_path);
+                }
+            }
+            if ui.button("Export Gates...").clicked() {
+                let result = rfd::FileDialog::new()
+                    .set_directory(self.preferences.config_dir())
+                    .add_filter("JSON file", &["json"])
+                    .save_file();
+                if let Some(real_path) = result {
+                    self.write_state_gates_to_file(&real_path);
+                }
+            }
+        });
+
+        let mut to_remove = Vec::new();
+        for (index, gate) in self.parameters.sps.state_gates.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("StateId {}:", index));
+                ui.text_edit_singleline(&mut gate.name);
+                ui.label("Xavg Min:");
+                ui.add(egui::DragValue::new(&mut gate.x_min).suffix(" mm"));
+                ui.label("Xavg Max:");
+                ui.add(egui::DragValue::new(&mut gate.x_max).suffix(" mm"));
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.sps.state_gates.remove(index);
+        }
+    }
+
+    fn outputs_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Run Outputs")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        let output_dir = match self.get_shown_output_dir() {
+            Some(Ok(dir)) => dir,
+            Some(Err(x)) => {
+                ui.label(format!("Workspace has no output directory: {x}"));
+                return;
+            }
+            None => {
+                ui.label("Set a workspace, or open a build, to see its built runs");
+                return;
+            }
+        };
+
+        self.shown_run_ui(ui);
+
+        match &self.opened_build {
+            Some(build) => {
+                ui.label(format!(
+                    "Note: {}",
+                    build.get_notes().get_note(self.output_run)
+                ));
+                ui.separator();
+            }
+            None => self.run_notes_ui(ui, &output_dir),
+        }
+
+        let layout = RunLayout::new(&output_dir, self.output_run);
+        let files = match layout.list_files() {
+            Ok(files) => files,
+            Err(_) => {
+                ui.label(format!("Run {} has not been built", self.output_run));
+                return;
+            }
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(layout.get_dir().display().to_string());
+            if ui.button("Browse...").clicked() {
+                //Only opens the directory; picking a file does nothing further
+                let _ = rfd::FileDialog::new()
+                    .set_directory(layout.get_dir())
+                    .pick_file();
+            }
+        });
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::CollapsingHeader::new(format!("Files ({})", files.len()))
+                .id_source("run_outputs_files")
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::Grid::new("run_outputs_grid")
+                        .striped(true)
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            for (path, size) in files.iter() {
+                                ui.label(path.display().to_string());
+                                ui.label(format_bytes(*size as usize));
+                                ui.end_row();
+                            }
+                        });
+                });
+            self.run_report_ui(ui, &layout);
+            self.run_spectra_ui(ui, &layout);
+        });
+    }
+
+    //report.json of the run as a tree, read again when another run is shown
+    fn run_report_ui(&mut self, ui: &mut egui::Ui, layout: &RunLayout) {
+        let report_path = layout.get_report_path();
+        if self
+            .shown_report
+            .as_ref()
+            .map_or(true, |(path, _)| *path != report_path)
+        {
+            let report = std::fs::read_to_string(&report_path)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok())
+                .unwrap_or(serde_json::Value::Null);
+            self.shown_report = Some((report_path, report));
+        }
+        let report = match &self.shown_report {
+            Some((_, serde_json::Value::Object(report))) => report,
+            _ => {
+                ui.label("The run has no report");
+                return;
+            }
+        };
+        egui::CollapsingHeader::new("Report")
+            .id_source("run_report")
+            .show(ui, |ui| {
+                for (key, value) in report.iter() {
+                    Self::json_value_ui(ui, key, value);
+                }
+            });
+    }
+
+    fn json_value_ui(ui: &mut egui::Ui, key: &str, value: &serde_json::Value) {
+        match value {
+            serde_json::Value::Object(object) => {
+                egui::CollapsingHeader::new(key).show(ui, |ui| {
+                    for (key, value) in object.iter() {
+                        Self::json_value_ui(ui, key, value);
+                    }
+                });
+            }
+            serde_json::Value::Array(array) => {
+                egui::CollapsingHeader::new(format!("{} ({})", key, array.len())).show(ui, |ui| {
+                    for (index, value) in array.iter().enumerate() {
+                        Self::json_value_ui(ui, &index.to_string(), value);
+                    }
+                });
+            }
+            value => {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(key).strong());
+                    ui.label(value.to_string());
+                });
+            }
+        }
+    }
+
+    //The spectra and rates written with the run, one file at a time
+    fn run_spectra_ui(&mut self, ui: &mut egui::Ui, layout: &RunLayout) {
+        let files = list_spectra_files(layout);
+        if files.is_empty() {
+            return;
+        }
+        //A file of another run is not shown
+        if self
+            .shown_spectra
+            .as_ref()
+            .is_some_and(|(path, _)| !files.contains(path))
+        {
+            self.shown_spectra = None;
+        }
+        let mut picked = None;
+        egui::CollapsingHeader::new(format!("Spectra ({})", files.len()))
+            .id_source("run_spectra")
+            .show(ui, |ui| {
+                let shown = self
+                    .shown_spectra
+                    .as_ref()
+                    .and_then(|(path, _)| path.file_name())
+                    .map_or(String::from("None"), |name| {
+                        name.to_string_lossy().to_string()
+                    });
+                egui::ComboBox::from_id_source("run_spectra_file")
+                    .selected_text(shown)
+                    .show_ui(ui, |ui| {
+                        for file in files.iter() {
+                            let name = file
+                                .file_name()
+                                .map_or(String::new(), |name| name.to_string_lossy().to_string());
+                            if ui.selectable_label(false, name).clicked() {
+                                picked = Some(file.clone());
+                            }
+                        }
+                    });
+                if let Some((_, spectra)) = &self.shown_spectra {
+                    paint_small_multiples(ui, spectra, self.preferences.dark_mode);
+                }
+            });
+        if let Some(path) = picked {
+            match read_spectra_file(&path) {
+                Ok(spectra) => self.shown_spectra = Some((path, spectra)),
+                Err(x) => error!("Could not read the spectra of {}: {x}", path.display()),
+            }
+        }
+    }
+
+    //The note of the selected run, which does not have to be built yet
+    fn run_notes_ui(&mut self, ui: &mut egui::Ui, output_dir: &Path) {
+        let notes_path = match self.parameters.workspace.as_ref() {
+            Some(ws) => ws.get_run_notes_path(),
+            None => return,
+        };
+        if self
+            .run_notes
+            .as_ref()
+            .map_or(true, |(path, _)| *path != notes_path)
+        {
+            let notes = match RunNotes::read(&notes_path) {
+                Ok(notes) => notes,
+                Err(x) => {
+                    error!("Unable to read run notes {}: {}", notes_path.display(), x);
+                    RunNotes::default()
+                }
+            };
+            self.run_notes = Some((notes_path.clone(), notes));
+        }
+        let notes = match self.run_notes.as_mut() {
+            Some((_, notes)) => notes,
+            None => return,
+        };
+
+        ui.label("Note:")
+            .on_hover_text("Free-text note of the run, kept in run_notes.yaml in the workspace");
+        ui.add(
+            egui::TextEdit::multiline(notes.get_note_mut(self.output_run))
+                .desired_rows(3)
+                .desired_width(f32::INFINITY),
+        );
+        ui.horizontal(|ui| {
+            if ui.button("Save Notes").clicked() {
+                if let Err(x) = notes.save(&notes_path) {
+                    error!("Unable to save run notes {}: {}", notes_path.display(), x);
+                }
+            }
+            if ui
+                .button("Write Campaign Summary")
+                .on_hover_text("Write built/campaign_summary.csv with every run and its note")
+                .clicked()
+            {
+                match write_campaign_summary(output_dir, notes) {
+                    Ok(path) => info!("Wrote campaign summary to {}", path.display()),
+                    Err(x) => error!("Unable to write campaign summary: {}", x),
+                }
+            }
+        });
+        ui.separator();
+    }
+
+    fn export_histogram(&self, layout: &RunLayout, extension: &str) {
+        let histogram = match &self.histogram {
+            Some(histogram) => histogram,
+            None => return,
+        };
+        let file_name: String = histogram
+            .title
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let result = rfd::FileDialog::new()
+            .set_directory(layout.get_spectra_dir())
+            .set_file_name(format!("{}.{}", file_name, extension))
+            .add_filter(extension.to_uppercase(), &[extension])
+            .save_file();
+
+        if let Some(real_path) = result {
+            let written = if extension == "svg" {
+                write_svg(histogram, &real_path, self.preferences.dark_mode)
+            } else {
+                write_png(histogram, &real_path, self.preferences.dark_mode)
+            };
+            match written {
+                Ok(_) => info!("Exported plot to {}", real_path.display()),
+                Err(x) => error!("{x}"),
+            }
+        }
+    }
+
+    //The opened build, else the full builds of the workspace, or its quick builds while those are
+    //shown. None without either.
+    fn get_shown_output_dir(&self) -> Option<Result<PathBuf, WorkspaceError>> {
+        if let Some(build) = &self.opened_build {
+            return Some(Ok(build.get_dir().to_path_buf()));
+        }
+        self.parameters.workspace.as_ref().map(|workspace| {
+            if self.show_quick_build {
+                workspace.get_quick_build_dir()
+            } else {
+                workspace.get_output_dir()
+            }
+        })
+    }
+
+    //The runs of an opened build to pick from, else any run of the workspace
+    fn shown_run_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Run:");
+            match &self.opened_build {
+                Some(build) => {
+                    egui::ComboBox::from_id_source("opened_build_run")
+                        .selected_text(self.output_run.to_string())
+                        .show_ui(ui, |ui| {
+                            for run in build.get_runs().iter() {
+                                ui.selectable_value(&mut self.output_run, *run, run.to_string());
+                            }
+                        });
+                }
+                None => {
+                    ui.add(egui::DragValue::new(&mut self.output_run).speed(1));
+                    self.show_quick_build_ui(ui);
+                }
+            }
+        });
+    }
+
+    fn show_quick_build_ui(&mut self, ui: &mut egui::Ui) {
+        if ui
+            .checkbox(&mut self.show_quick_build, "Quick Build")
+            .on_hover_text("Show the quick builds of the start of a run instead of the full builds")
+            .changed()
+        {
+            self.diagnostic_columns = None;
+            self.histogram = None;
+        }
+    }
+
+    fn histograms_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Histograms")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        let output_dir = match self.get_shown_output_dir() {
+            Some(Ok(dir)) => dir,
+            _ => {
+                ui.label("Set a workspace, or open a build, to histogram its built runs");
+                return;
+            }
+        };
+
+        self.shown_run_ui(ui);
+
+        let layout = RunLayout::new(&output_dir, self.output_run);
+        //A fragmented run is histogrammed from its first fragment
+        let events_path = [None, Some(0)]
+            .into_iter()
+            .map(|fragment| layout.get_table_path(EVENTS_STEM, fragment))
+            .find(|path| path.exists());
+        let events_path = match events_path {
+            Some(path) => path,
+            None => {
+                ui.label(format!("Run {} has not been built", self.output_run));
+                return;
+            }
+        };
+
+        if self
+            .diagnostic_columns
+            .as_ref()
+            .map_or(true, |(run, _)| *run != self.output_run)
+        {
+            let columns = read_columns(&events_path).unwrap_or_else(|x| {
+                error!(
+                    "Could not read the columns of {}: {x}",
+                    events_path.display()
+                );
+                vec![]
+            });
+            self.diagnostic_columns = Some((self.output_run, columns));
+        }
+        let columns = match &self.diagnostic_columns {
+            Some((_, columns)) => columns.clone(),
+            None => vec![],
+        };
+
+        let diagnostic = &mut self.diagnostic;
+        egui::Grid::new("histogram_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Plot");
+                egui::ComboBox::from_id_source("diagnostic_kind")
+                    .selected_text(diagnostic.kind.as_ref())
+                    .show_ui(ui, |ui| {
+                        for kind in DiagnosticKind::iter() {
+                            ui.selectable_value(&mut diagnostic.kind, kind, kind.as_ref());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Column");
+                egui::ComboBox::from_id_source("diagnostic_column")
+                    .selected_text(diagnostic.column.as_str())
+                    .show_ui(ui, |ui| {
+                        for column in columns.iter() {
+                            ui.selectable_value(&mut diagnostic.column, column.clone(), column);
+                        }
+                    });
+                ui.end_row();
+
+                if diagnostic.kind == DiagnosticKind::TimeDifference {
+                    ui.label("Minus");
+                    egui::ComboBox::from_id_source("diagnostic_reference")
+                        .selected_text(diagnostic.reference_column.as_str())
+                        .show_ui(ui, |ui| {
+                            for column in columns.iter() {
+                                ui.selectable_value(
+                                    &mut diagnostic.reference_column,
+                                    column.clone(),
+                                    column,
+                                );
+                            }
+                        });
+                    ui.end_row();
+                }
+
+                if diagnostic.kind != DiagnosticKind::RateVsTime {
+                    ui.label("Bins");
+                    ui.horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut diagnostic.bins).clamp_range(1..=65536));
+                        ui.label("Min");
+                        ui.add(egui::DragValue::new(&mut diagnostic.min).speed(1));
+                        ui.label("Max");
+                        ui.add(egui::DragValue::new(&mut diagnostic.max).speed(1));
+                    });
+                    ui.end_row();
+                }
+            });
+
+        ui.horizontal(|ui| {
+            if ui.button("Fill").clicked() {
+                match build_histogram(&self.diagnostic, &events_path, self.output_run) {
+                    Ok(histogram) => self.histogram = Some(histogram),
+                    Err(x) => error!("Could not fill the histogram: {x}"),
+                }
+            }
+            if self.histogram.is_some() {
+                if ui.button("Export PNG...").clicked() {
+                    self.export_histogram(&layout, "png");
+                }
+                if ui.button("Export SVG...").clicked() {
+                    self.export_histogram(&layout, "svg");
+                }
+            }
+        });
+
+        if let Some(histogram) = &self.histogram {
+            paint_plot(ui, histogram, self.preferences.dark_mode);
+        }
+    }
+
+    fn run_comparison_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Compare Runs")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+
+        //The full builds, or those of the opened build
+        let output_dir = match &self.opened_build {
+            Some(build) => Some(Ok(build.get_dir().to_path_buf())),
+            None => self
+                .parameters
+                .workspace
+                .as_ref()
+                .map(|ws| ws.get_output_dir()),
+        };
+        let output_dir = match output_dir {
+            Some(Ok(dir)) => dir,
+            _ => {
+                ui.label("Set a workspace, or open a build, to compare its built runs");
+                return;
+            }
+        };
+
+        let comparison = &mut self.comparison;
+        egui::Grid::new("run_comparison_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Runs");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut comparison.first_run).speed(1));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut comparison.last_run).speed(1));
+                });
+                ui.end_row();
+
+                ui.label("Prompt Peak").on_hover_text(
+                    "Time difference whose peak width (FWHM) is compared, in the time unit of \
+                    the runs",
+                );
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut comparison.prompt_column);
+                    ui.label("minus");
+                    ui.text_edit_singleline(&mut comparison.prompt_reference);
+                });
+                ui.end_row();
+
+                ui.label("Bins");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut comparison.prompt_bins).clamp_range(1..=65536),
+                    );
+                    ui.label("Min");
+                    ui.add(egui::DragValue::new(&mut comparison.prompt_min).speed(1));
+                    ui.label("Max");
+                    ui.add(egui::DragValue::new(&mut comparison.prompt_max).speed(1));
+                });
+                ui.end_row();
+            });
+
+        if ui.button("Compare").clicked() {
+            if self.comparison.last_run < self.comparison.first_run {
+                error!("The last run to compare is before the first");
+            } else {
+                match RunComparison::new(&self.comparison, &output_dir) {
+                    Ok(comparison) => self.run_comparison = Some(comparison),
+                    Err(x) => error!("Could not compare the runs: {x}"),
+                }
+            }
+        }
+
+        let comparison = match &self.run_comparison {
+            Some(comparison) => comparison,
+            None => return,
+        };
+        if comparison.runs.is_empty() {
+            ui.label("None of the runs have been built");
+            return;
+        }
+        let dark_mode = self.preferences.dark_mode;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.label(RichText::new("Trends").size(16.0));
+            paint_small_multiples(ui, &comparison.get_trend_plots(), dark_mode);
+            ui.label(RichText::new("Multiplicity").size(16.0));
+            paint_small_multiples(ui, &comparison.get_multiplicity_plots(), dark_mode);
+        });
+    }
+
+    fn gain_drift_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new("Gain Drift Anchors")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Gains of each run are interpolated between the anchor runs around it");
+
+        if ui.button("Add Anchor From File...").clicked() {
+            let result = rfd::FileDialog::new()
+                .set_directory(self.preferences.config_dir())
+                .add_filter("YAML file", &["yaml"])
+                .pick_file();
+
+            if let Some(real_path) = result {
+                self.preferences.remember_config_file(&real_path);
+                self.read_gain_anchor_from_file(&real_path)
+            }
+        }
+
+        let mut to_remove = Vec::new();
+        for (index, anchor) in self.parameters.gain_anchors.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Run:");
+                ui.add(egui::DragValue::new(&mut anchor.run_number));
+                ui.label(format!("{} channels", anchor.entries.len()));
+
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+
+        for &index in to_remove.iter().rev() {
+            self.parameters.gain_anchors.remove(index);
+        }
+
+        ui.separator();
+        ui.label(
+            RichText::new("Energy Calibration")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label("Calibrated channels get a keV column next to the Energy column");
+        ui.horizontal(|ui| {
+            if ui.button("Load Calibration...").clicked() {
+                let result = rfd::FileDialog::new()
+                    .set_directory(self.preferences.config_dir())
+                    .add_filter("YAML file", &["yaml"])
+                    .pick_file();
+
+                if let Some(real_path) = result {
+                    self.preferences.remember_config_file(&real_path);
+                    self.read_energy_calibration_from_file(&real_path)
+                }
+            }
+            if ui.button("Clear").clicked() {
+                self.parameters.energy_calibration.clear();
+            }
+        });
+        ui.label(format!(
+            "{} calibrated channels",
+            self.parameters.energy_calibration.len()
+        ));
+
+        ui.separator();
+        ui.label(
+            RichText::new("Energy Thresholds")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        ui.label(
+            "Hits with a raw energy below the threshold of their channel are dropped as the \
+            files are read, and counted in the run report",
+        );
+        if ui.button("Add Entry").clicked() {
+            self.parameters
+                .energy_thresholds
+                .push(EnergyThresholdEntry {
+                    board_number: 0,
+                    channel_number: 0,
+                    threshold: 0.0,
+                });
+        }
+        let mut to_remove = Vec::new();
+        for (index, entry) in self.parameters.energy_thresholds.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label("Board:");
+                ui.add(egui::DragValue::new(&mut entry.board_number));
+                ui.label("Channel:");
+                ui.add(egui::DragValue::new(&mut entry.channel_number));
+                ui.label("Threshold:");
+                ui.add(egui::DragValue::new(&mut entry.threshold));
+                if ui.button("❌").clicked() {
+                    to_remove.push(index);
+                }
+            });
+        }
+        for &index in to_remove.iter().rev() {
+            self.parameters.energy_thresholds.remove(index);
+        }
+    }
+
+    fn data_reduction_ui(ui: &mut egui::Ui, reduction: &mut DataReductionParams, id: &str) {
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source(format!("{}_mode", id))
+                .selected_text(reduction.mode.as_ref())
+                .show_ui(ui, |ui| {
+                    for mode in ReductionMode::iter() {
+                        ui.selectable_value(&mut reduction.mode, mode, mode.as_ref());
+                    }
+                });
+            match reduction.mode {
+                ReductionMode::None => (),
+                ReductionMode::Prescale => {
+                    ui.label("Keep 1 in");
+                    ui.add(
+                        egui::widgets::DragValue::new(&mut reduction.prescale_factor)
+                            .clamp_range(1..=u32::MAX),
+                    );
+                }
+                ReductionMode::Random => {
+                    ui.label("Fraction");
+                    ui.add(
+                        egui::widgets::DragValue::new(&mut reduction.fraction)
+                            .speed(0.01)
+                            .clamp_range(0.0..=1.0),
+                    );
+                }
+            }
+            if reduction.is_enabled() {
+                let mut has_seed = reduction.seed.is_some();
+                if ui
+                    .checkbox(&mut has_seed, "Seed")
+                    .on_hover_text("Without a seed, each run draws a new one")
+                    .changed()
+                {
+                    reduction.seed = if has_seed { Some(0) } else { None };
+                }
+                if let Some(seed) = &mut reduction.seed {
+                    ui.add(egui::widgets::DragValue::new(seed));
+                }
+            }
+        });
+    }
+
+    fn main_tab_ui(&mut self, ui: &mut egui::Ui) {
+        let channel_types = get_selectable_types(&self.parameters.channel_map_entries);
+        //Files/Workspace
+        ui.separator();
+        ui.label(
+            RichText::new("Run Information")
+                .color(Color32::LIGHT_BLUE)
+                .size(18.0),
+        );
+        egui::Grid::new("RunGrid").show(ui, |ui| {
+            ui.label("Workspace: ");
+            ui.label(match &self.parameters.workspace {
+                Some(ws) => ws.get_parent_str(),
+                None => "None",
+            });
+
+            ui.horizontal(|ui| {
+                if ui.button("Open").clicked() {
+                    self.open_workspace_dialog();
+                }
+                if ui
+                    .add_enabled(
+                        self.parameters.workspace.is_some(),
+                        egui::Button::new("Export Bundle..."),
+                    )
+                    .on_hover_text(
+                        "Save the config, run notes, scaler lists and reports of the workspace, \
+                        without the data, to set up the analysis elsewhere",
+                    )
+                    .clicked()
+                {
+                    self.export_bundle_dialog();
+                }
+                if ui
+                    .button("Import Bundle...")
+                    .on_hover_text("Set up a new workspace from an exported bundle")
+                    .clicked()
+                {
+                    self.import_bundle_dialog();
+                }
+            });
+
+            ui.end_row();
+
+            ui.label("Coincidence Window");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::widgets::DragValue::new(&mut self.parameters.coincidence_window)
+                        .speed(100)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+                egui::ComboBox::from_id_source("coincidence_window_unit")
+                    .selected_text(self.parameters.coincidence_window_unit.as_ref())
+                    .show_ui(ui, |ui| {
+                        for unit in TimeUnit::iter() {
+                            ui.selectable_value(
+                                &mut self.parameters.coincidence_window_unit,
+                                unit,
+                                unit.as_ref(),
+                            );
+                        }
+                    });
+            });
+            ui.end_row();
+
+            ui.label("Output Time Unit");
+            egui::ComboBox::from_id_source("time_unit")
+                .selected_text(self.parameters.time_unit.as_ref())
+                .show_ui(ui, |ui| {
+                    for unit in TimeUnit::iter() {
+                        ui.selectable_value(&mut self.parameters.time_unit, unit, unit.as_ref());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Run Min");
+            ui.add(egui::widgets::DragValue::new(&mut self.parameters.run_min).speed(1));
+            ui.end_row();
+
+            ui.label("Run Max");
+            ui.add(egui::widgets::DragValue::new(&mut self.parameters.run_max).speed(1));
+            ui.end_row();
+
+            ui.label("Run List").on_hover_text(
+                "Runs and ranges to build instead of Run Min to Run Max, e.g. 120-147, !133 \
+                builds 120 to 147 without 133. Leave empty to use Run Min and Run Max.",
+            );
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.parameters.run_list);
+                if !self.parameters.run_list.trim().is_empty() {
+                    match parse_run_list(&self.parameters.run_list) {
+                        Ok(runs) => ui.label(format!("{} runs", runs.len())),
+                        Err(e) => ui.label(RichText::new(e).color(Color32::RED)),
+                    };
+                }
+            });
+            ui.end_row();
+
+            ui.label("Shared Memory Output")
+                .on_hover_text("Publish built events to a ring buffer for an online histogrammer");
+            ui.horizontal(|ui| {
+                let shm = &mut self.parameters.shared_memory;
+                ui.checkbox(&mut shm.enabled, "");
+                let mut path_str = shm.path.to_string_lossy().to_string();
+                if ui.text_edit_singleline(&mut path_str).changed() {
+                    shm.path = PathBuf::from(path_str);
+                }
+                ui.label("Slots");
+                ui.add(egui::widgets::DragValue::new(&mut shm.slots).clamp_range(1..=u32::MAX));
+            });
+            ui.end_row();
+
+            ui.label("Network Output").on_hover_text(
+                "Stream built events as Arrow record batches over TCP to an online monitor on \
+                another machine, which connects to this address",
+            );
+            ui.horizontal(|ui| {
+                let network = &mut self.parameters.network_sink;
+                ui.checkbox(&mut network.enabled, "");
+                ui.text_edit_singleline(&mut network.address);
+                ui.label("Batch");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut network.batch_events)
+                        .clamp_range(1..=usize::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Slow Control Pattern").on_hover_text(
+                "Files in the run archive containing this are read as temperature/HV logs",
+            );
+            ui.text_edit_singleline(&mut self.parameters.slow_control_pattern);
+            ui.end_row();
+
+            ui.label("Delayed Window (ns)").on_hover_text(
+                "Also build run_<num>/delayed.parquet, tagged with prompt event ids",
+            );
+            ui.horizontal(|ui| {
+                let delayed = &mut self.parameters.delayed_window;
+                ui.checkbox(&mut delayed.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut delayed.coincidence_window)
+                        .speed(100)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+                ui.label("Max Delay");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut delayed.max_delay)
+                        .speed(100)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Trigger Channel")
+                .on_hover_text("Only hits in this channel open events, none uses the earliest hit");
+            ui.horizontal(|ui| {
+                let trigger = &mut self.parameters.trigger;
+                egui::ComboBox::from_id_source("trigger_channel")
+                    .selected_text(match &trigger.channel {
+                        Some(channel) => channel.to_string(),
+                        None => String::from("None"),
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut trigger.channel, None, "None");
+                        for channel in channel_types.iter().copied().filter(|c| *c != ChannelType::None) {
+                            ui.selectable_value(
+                                &mut trigger.channel,
+                                Some(channel),
+                                channel.to_string(),
+                            );
+                        }
+                    });
+                ui.label("If Missing").on_hover_text(
+                    "What to do with a run which has no hits in the trigger channel",
+                );
+                egui::ComboBox::from_id_source("missing_trigger")
+                    .selected_text(trigger.on_missing.as_ref())
+                    .show_ui(ui, |ui| {
+                        for behavior in MissingTriggerBehavior::iter() {
+                            ui.selectable_value(
+                                &mut trigger.on_missing,
+                                behavior,
+                                behavior.as_ref(),
+                            );
+                        }
+                    });
+            });
+            ui.end_row();
+
+            ui.label("Build Strategy").on_hover_text(
+                "Leading Edge: a window opens on the earliest (trigger) hit and covers the window \
+                after it. Fixed Window: events are fixed slices of the window width. Reference \
+                Trigger: a window opens on a trigger hit and covers the window before and after it",
+            );
+            egui::ComboBox::from_id_source("build_strategy")
+                .selected_text(self.parameters.trigger.strategy.as_ref())
+                .show_ui(ui, |ui| {
+                    for strategy in BuildStrategy::iter() {
+                        ui.selectable_value(
+                            &mut self.parameters.trigger.strategy,
+                            strategy,
+                            strategy.as_ref(),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Pulser Run (ns)").on_hover_text(
+                "Build every hit with this wide window and write per-channel timing summaries",
+            );
+            ui.horizontal(|ui| {
+                let pulser = &mut self.parameters.pulser;
+                ui.checkbox(&mut pulser.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut pulser.coincidence_window)
+                        .speed(100)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Write Raw Hits").on_hover_text(
+                "Also write run_<num>/hits.parquet with every hit, as large as the raw data",
+            );
+            ui.checkbox(&mut self.parameters.write_hits, "");
+            ui.end_row();
+
+            ui.label("Keep Raw Times").on_hover_text(
+                "Also write Cebra<n>RawTime columns with the times before time shifts and jitter",
+            );
+            ui.checkbox(&mut self.parameters.keep_raw_times, "");
+            ui.end_row();
+
+            ui.label("PSD Columns").on_hover_text(
+                "Also write Cebra<n>Psd columns with (energy - short) / energy of every detector",
+            );
+            ui.checkbox(&mut self.parameters.psd_columns, "");
+            ui.end_row();
+
+            ui.label("Flag Columns").on_hover_text(
+                "Also write Cebra<n>Flags with the pileup (1), saturation (2) and lost events (4) \
+                flags of the hit of every detector, and EventFlags with those of every hit of \
+                the event. Gate on 0 for clean hits",
+            );
+            ui.checkbox(&mut self.parameters.flag_columns, "");
+            ui.end_row();
+
+            ui.label("Event Time Columns").on_hover_text(
+                "Also write TimeSincePrevious, the time from the event built before, and \
+                WallClock, the time of the event in seconds since 1970-01-01 UTC from the start \
+                of the run in the CoMPASS run.info file",
+            );
+            ui.checkbox(&mut self.parameters.event_time_columns, "");
+            ui.end_row();
+
+            ui.label("Split Singles").on_hover_text(
+                "Write the events with only one hit to singles.parquet and keep the coincidences, \
+                two or more hits or a hit of the trigger channel, in events.parquet. Both get a \
+                Coincidence column, 1 for coincidences and 0 for singles",
+            );
+            ui.checkbox(&mut self.parameters.split_singles, "");
+            ui.end_row();
+
+            ui.label("Unmapped Channels").on_hover_text(
+                "What is done with the hits of channels mapped as None or of boards not in the \
+                channel map. They are built into events either way. Drop leaves them out of the \
+                columns, Warn does the same and warns about every such channel with hits, \
+                Columns writes UnmappedBoard<b>Chan<c>Energy and Time columns for every channel \
+                mapped as None. The hits of each are counted in the run report",
+            );
+            egui::ComboBox::from_id_source("unmapped_channels")
+                .selected_text(self.parameters.unmapped_channels.as_ref())
+                .show_ui(ui, |ui| {
+                    for policy in UnmappedPolicy::iter() {
+                        ui.selectable_value(
+                            &mut self.parameters.unmapped_channels,
+                            policy,
+                            policy.as_ref(),
+                        );
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Sort Column").on_hover_text(
+                "Write the events sorted by this column instead of in build order, e.g. EventID. \
+                Leave empty for build order",
+            );
+            ui.text_edit_singleline(&mut self.parameters.sort_column);
+            ui.end_row();
+
+            ui.label("Column Naming").on_hover_text(
+                "How the columns of the event tables are named: Cebra0Energy, cebra0_energy, or \
+                the CeBrA detector columns by a template with {n} for the detector number, \
+                {field} for the field (Energy) and {f} for its short form (E)",
+            );
+            ui.horizontal(|ui| {
+                let naming = &mut self.parameters.column_naming;
+                egui::ComboBox::from_id_source("column_naming")
+                    .selected_text(naming.scheme.as_ref())
+                    .show_ui(ui, |ui| {
+                        for scheme in ColumnNamingScheme::iter() {
+                            ui.selectable_value(&mut naming.scheme, scheme, scheme.as_ref());
+                        }
+                    });
+                if naming.scheme == ColumnNamingScheme::Template {
+                    ui.text_edit_singleline(&mut naming.template);
+                }
+            });
+            ui.end_row();
+
+            ui.label("Invalid Values").on_hover_text(
+                "Value written for missing hits in the energy, time and other columns, \
+                recorded in the invalid_value metadata of every column. With Nulls, the parquet \
+                and Arrow IPC columns are nullable and hold nulls instead",
+            );
+            ui.horizontal(|ui| {
+                let invalid = &mut self.parameters.invalid_values;
+                ui.checkbox(&mut invalid.nulls, "Nulls");
+                ui.add_enabled_ui(!invalid.nulls, |ui| {
+                    ui.label("Energy");
+                    ui.add(egui::DragValue::new(&mut invalid.energy));
+                    ui.label("Time");
+                    ui.add(egui::DragValue::new(&mut invalid.time));
+                    ui.label("Other");
+                    ui.add(egui::DragValue::new(&mut invalid.other));
+                });
+            });
+            ui.end_row();
+
+            ui.label("Detector Spectra").on_hover_text(
+                "Fill the energy spectrum of every CeBrA detector while the events are built, \
+                written to run_<num>/spectra/detector_spectra.csv",
+            );
+            ui.horizontal(|ui| {
+                let spectra = &mut self.parameters.detector_spectra;
+                ui.checkbox(&mut spectra.enabled, "");
+                ui.label("Bins");
+                ui.add(egui::DragValue::new(&mut spectra.bins).clamp_range(1..=1_000_000));
+                ui.label("Min");
+                ui.add(egui::DragValue::new(&mut spectra.min));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(&mut spectra.max));
+            });
+            ui.end_row();
+
+            ui.label("Calibration Spectra").on_hover_text(
+                "Write one file with the energy spectrum of every channel, summed over all runs",
+            );
+            ui.horizontal(|ui| {
+                let spectra = &mut self.parameters.calibration_spectra;
+                ui.checkbox(&mut spectra.enabled, "");
+                ui.label("Bins");
+                ui.add(egui::DragValue::new(&mut spectra.bins).clamp_range(1..=1_000_000));
+                ui.label("Min");
+                ui.add(egui::DragValue::new(&mut spectra.min));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(&mut spectra.max));
+            });
+            ui.end_row();
+
+            ui.label("Time Differences (ns)").on_hover_text(
+                "Histogram the time of every channel type minus the nearest reference hit, to \
+                choose the coincidence window, written to run_<num>/spectra/time_differences.csv",
+            );
+            ui.horizontal(|ui| {
+                let differences = &mut self.parameters.time_differences;
+                ui.checkbox(&mut differences.enabled, "");
+                egui::ComboBox::from_id_source("time_difference_reference")
+                    .selected_text(differences.reference.to_string())
+                    .show_ui(ui, |ui| {
+                        for channel in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|c| *c != ChannelType::None)
+                        {
+                            ui.selectable_value(
+                                &mut differences.reference,
+                                channel,
+                                channel.to_string(),
+                            );
+                        }
+                    });
+                ui.label("Bins");
+                ui.add(egui::DragValue::new(&mut differences.bins).clamp_range(1..=1_000_000));
+                ui.label("Min");
+                ui.add(egui::DragValue::new(&mut differences.min));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(&mut differences.max));
+            });
+            ui.end_row();
+
+            ui.label("Coincidence Trend").on_hover_text(
+                "Count the prompt coincidences of every detector with a reference channel in time \
+                bins, written to run_<num>/coincidence_trend.csv with a plot per detector in \
+                run_<num>/spectra, and warn about detectors whose rate drops below a fraction of \
+                their median",
+            );
+            ui.horizontal(|ui| {
+                let trend = &mut self.parameters.coincidence_trend;
+                ui.checkbox(&mut trend.enabled, "");
+                egui::ComboBox::from_id_source("coincidence_trend_reference")
+                    .selected_text(trend.reference.to_string())
+                    .show_ui(ui, |ui| {
+                        for channel in channel_types
+                            .iter()
+                            .copied()
+                            .filter(|c| *c != ChannelType::None)
+                        {
+                            ui.selectable_value(&mut trend.reference, channel, channel.to_string());
+                        }
+                    });
+                ui.label("Interval");
+                ui.add(
+                    egui::DragValue::new(&mut trend.interval)
+                        .speed(1.0)
+                        .clamp_range(0.001..=f64::MAX)
+                        .suffix(" s"),
+                );
+                ui.label("Prompt");
+                ui.add(egui::DragValue::new(&mut trend.prompt_min).suffix(" ns"));
+                ui.label("to");
+                ui.add(egui::DragValue::new(&mut trend.prompt_max).suffix(" ns"));
+                ui.label("Drop Below");
+                ui.add(
+                    egui::DragValue::new(&mut trend.drop_fraction)
+                        .speed(0.01)
+                        .clamp_range(0.0..=0.99),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Channel Rates").on_hover_text(
+                "Count the hits of every channel in time bins, written to \
+                run_<num>/channel_rates.csv, and warn about channels without hits or whose rate \
+                drops below a fraction of their median",
+            );
+            ui.horizontal(|ui| {
+                let rates = &mut self.parameters.channel_rates;
+                ui.checkbox(&mut rates.enabled, "");
+                ui.label("Interval");
+                ui.add(
+                    egui::DragValue::new(&mut rates.interval)
+                        .speed(1.0)
+                        .clamp_range(0.001..=f64::MAX)
+                        .suffix(" s"),
+                );
+                ui.label("Drop Below");
+                ui.add(
+                    egui::DragValue::new(&mut rates.drop_fraction)
+                        .speed(0.01)
+                        .clamp_range(0.0..=0.99),
+                );
+            });
+            ui.end_row();
+
+            ui.label("QA Report").on_hover_text(
+                "After each run is built, compare the hit rate, mean energy and coincidence rate \
+                of every detector with a reference run or the average of the runs built before \
+                it, warn about detectors off by more than the threshold (a fraction of the \
+                reference), and write run_<num>/qa.json and run_<num>/qa.html",
+            );
+            ui.horizontal(|ui| {
+                let qa = &mut self.parameters.qa;
+                ui.checkbox(&mut qa.enabled, "");
+                egui::ComboBox::from_id_source("qa_reference")
+                    .selected_text(qa.reference.as_ref())
+                    .show_ui(ui, |ui| {
+                        for reference in QaReference::iter() {
+                            ui.selectable_value(&mut qa.reference, reference, reference.as_ref());
+                        }
+                    });
+                match qa.reference {
+                    QaReference::Run => {
+                        ui.label("Run");
+                        ui.add(egui::DragValue::new(&mut qa.reference_run));
+                    }
+                    QaReference::RollingAverage => {
+                        ui.label("Runs");
+                        ui.add(egui::DragValue::new(&mut qa.rolling_runs).clamp_range(1..=1000));
+                    }
+                }
+                ui.label("Threshold");
+                ui.add(
+                    egui::DragValue::new(&mut qa.threshold)
+                        .speed(0.01)
+                        .clamp_range(0.001..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Scaler Interval (s)").on_hover_text(
+                "Width of the time bins that the channels mapped as Scaler are counted in, written \
+                with their rates to run_<num>/scaler_rates.csv",
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.parameters.scaler_rates.interval)
+                    .speed(0.1)
+                    .clamp_range(0.001..=f64::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Histograms Only").on_hover_text(
+                "Build the events without writing any event table, only the histograms: the \
+                energy spectrum of every channel in run_<num>/spectra/channel_spectra.csv plus \
+                any enabled state spectra, time differences and calibration spectra",
+            );
+            ui.horizontal(|ui| {
+                let histogram_only = &mut self.parameters.histogram_only;
+                ui.checkbox(&mut histogram_only.enabled, "");
+                ui.label("Bins");
+                ui.add(
+                    egui::DragValue::new(&mut histogram_only.bins)
+                        .clamp_range(1..=MAX_HISTOGRAM_ONLY_BINS),
+                );
+                ui.label("Min");
+                ui.add(egui::DragValue::new(&mut histogram_only.min));
+                ui.label("Max");
+                ui.add(egui::DragValue::new(&mut histogram_only.max));
+            });
+            ui.end_row();
+
+            ui.label("Write Limit (MB/s)").on_hover_text(
+                "Cap the rate the event and hit tables are written at, for shared storage",
+            );
+            ui.horizontal(|ui| {
+                let limit = &mut self.parameters.write_limit;
+                ui.checkbox(&mut limit.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut limit.megabytes_per_second)
+                        .speed(1.0)
+                        .clamp_range(0.1..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Stream Output (rows)").on_hover_text(
+                "Write the event tables in pieces of this many events while building, for long runs",
+            );
+            ui.horizontal(|ui| {
+                let streaming = &mut self.parameters.streaming;
+                ui.checkbox(&mut streaming.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut streaming.chunk_rows)
+                        .speed(1000.0)
+                        .clamp_range(1..=usize::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Checkpoints (s)").on_hover_text(
+                "Write out the tables and where the build stands this often, to \
+                run_<num>/checkpoint.json, so a build that is killed or stopped is resumed from \
+                there when the run is built again",
+            );
+            ui.horizontal(|ui| {
+                let checkpoints = &mut self.parameters.checkpoints;
+                ui.checkbox(&mut checkpoints.enabled, "");
+                ui.add(
+                    egui::DragValue::new(&mut checkpoints.interval)
+                        .speed(10.0)
+                        .clamp_range(1.0..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Output Formats").on_hover_text(
+                "Write the events and delayed tables as Parquet, as a ROOT TTree, as HDF5 \
+                datasets and/or as Arrow IPC (Feather), ROOT and HDF5 need the event builder \
+                built with their feature",
+            );
+            ui.horizontal(|ui| {
+                let formats = &mut self.parameters.output_formats;
+                ui.checkbox(&mut formats.parquet, "Parquet");
+                ui.checkbox(&mut formats.root, "ROOT");
+                ui.checkbox(&mut formats.hdf5, "HDF5");
+                ui.checkbox(&mut formats.ipc, "Arrow IPC");
+                ui.add_enabled_ui(formats.hdf5, |ui| {
+                    let hdf5 = &mut self.parameters.hdf5;
+                    ui.label("Deflate")
+                        .on_hover_text("Compression level of the HDF5 datasets, 0 for none");
+                    ui.add(
+                        egui::widgets::DragValue::new(&mut hdf5.compression_level)
+                            .speed(1.0)
+                            .clamp_range(0..=9),
+                    );
+                    ui.label("Chunk Rows");
+                    ui.add(
+                        egui::widgets::DragValue::new(&mut hdf5.chunk_rows)
+                            .speed(1000.0)
+                            .clamp_range(1..=usize::MAX),
+                    );
+                });
+            });
+            ui.end_row();
+
+            ui.label("Worker Threads").on_hover_text(
+                "Threads decoding the data files while the events are built, 0 for as many as \
+                the thread limit allows and 1 to decode on the build thread",
+            );
+            ui.add(
+                egui::widgets::DragValue::new(&mut self.parameters.worker_threads)
+                    .speed(1.0)
+                    .clamp_range(0..=usize::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Build Threads").on_hover_text(
+                "Threads building the hits into events and filling the event table, 0 for as many \
+                as the thread limit allows and 1 to build on the build thread",
+            );
+            ui.add(
+                egui::widgets::DragValue::new(&mut self.parameters.build_threads)
+                    .speed(1.0)
+                    .clamp_range(0..=usize::MAX),
+            );
+            ui.end_row();
+
+            ui.label("Resource Limits").on_hover_text(
+                "Most threads a build uses for decoding, building and encoding the parquet files, 0 \
+                for one per core, and how large a table grows before it is written out as a fragment. \
+                --threads and --memory-budget on the command line replace these",
+            );
+            ui.horizontal(|ui| {
+                let limits = &mut self.parameters.resource_limits;
+                ui.label("Threads");
+                ui.add(egui::DragValue::new(&mut limits.threads).clamp_range(0..=usize::MAX));
+                ui.label("Memory Budget (MB)");
+                ui.add(
+                    egui::DragValue::new(&mut limits.memory_budget_mb)
+                        .speed(100.0)
+                        .clamp_range(1..=usize::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Log Level").on_hover_text(
+                "Most detailed records written to logs/build.log of every run, Debug adds the files \
+                of the run as they are read. --log-level on the command line replaces this",
+            );
+            egui::ComboBox::from_id_source("log_level")
+                .selected_text(self.parameters.log_level.as_ref())
+                .show_ui(ui, |ui| {
+                    for level in LogLevel::iter() {
+                        ui.selectable_value(&mut self.parameters.log_level, level, level.as_ref());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Waveforms").on_hover_text(
+                "What to do with the samples of files written with wave recording: drop them, \
+                reduce them to baseline, amplitude and rise time columns, or write them per channel",
+            );
+            ui.horizontal(|ui| {
+                let waveforms = &mut self.parameters.waveforms;
+                egui::ComboBox::from_id_source("waveform_mode")
+                    .selected_text(waveforms.mode.as_ref())
+                    .show_ui(ui, |ui| {
+                        for mode in WaveformMode::iter() {
+                            ui.selectable_value(&mut waveforms.mode, mode, mode.as_ref());
+                        }
+                    });
+                ui.label("Baseline Samples")
+                    .on_hover_text("Leading samples averaged for the baseline");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut waveforms.baseline_samples)
+                        .speed(1.0)
+                        .clamp_range(1..=usize::MAX),
+                );
+                ui.label("Sample Period (ns)");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut waveforms.sample_period)
+                        .speed(0.1)
+                        .clamp_range(0.001..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Bad Energies").on_hover_text(
+                "What to do with hits whose raw energy is 0 or wrapped, they are counted in the report",
+            );
+            ui.horizontal(|ui| {
+                let bad_energies = &mut self.parameters.bad_energies;
+                egui::ComboBox::from_id_source("bad_energy_policy")
+                    .selected_text(bad_energies.policy.as_ref())
+                    .show_ui(ui, |ui| {
+                        for policy in BadEnergyPolicy::iter() {
+                            ui.selectable_value(&mut bad_energies.policy, policy, policy.as_ref());
+                        }
+                    });
+                ui.label("Wrapped From")
+                    .on_hover_text("Raw energies at or above this are wrapped");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut bad_energies.wrap_threshold)
+                        .speed(1.0)
+                        .clamp_range(1.0..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Rate Limit (Hz)").on_hover_text(
+                "Drop the hits of a channel while its rate over the window is above this, e.g. a sparking PMT",
+            );
+            ui.horizontal(|ui| {
+                let rate_limit = &mut self.parameters.rate_limit;
+                ui.checkbox(&mut rate_limit.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut rate_limit.max_rate_hz)
+                        .speed(100.0)
+                        .clamp_range(0.0..=f64::MAX)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+                ui.label("Window (s)");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut rate_limit.window_seconds)
+                        .speed(1.0e-4)
+                        .clamp_range(1.0e-9..=f64::MAX)
+                        .custom_formatter(|n, _| format!("{:e}", n)),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Timestamp Rollover (s)").on_hover_text(
+                "Correct clock rollovers of this period (0 for none) and put hits up to the \
+                reorder window out of order back in order, before the files are merged",
+            );
+            ui.horizontal(|ui| {
+                let sanitizer = &mut self.parameters.timestamp_sanitizer;
+                ui.checkbox(&mut sanitizer.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut sanitizer.rollover_seconds)
+                        .speed(1.0)
+                        .clamp_range(0.0..=f64::MAX),
+                );
+                ui.label("Reorder Window (ns)");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut sanitizer.reorder_window)
+                        .speed(10.0)
+                        .clamp_range(0.0..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Run Boundaries (s)").on_hover_text(
+                "Start a new run wherever a file's timestamps jump back by more than this",
+            );
+            ui.horizontal(|ui| {
+                let boundaries = &mut self.parameters.run_boundaries;
+                ui.checkbox(&mut boundaries.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut boundaries.min_reset_seconds)
+                        .speed(0.1)
+                        .clamp_range(0.001..=f64::MAX),
+                );
+                ui.checkbox(&mut boundaries.split_output, "Split Output");
+            });
+            ui.end_row();
+
+            ui.label("Stitch Runs (s)").on_hover_text(
+                "Carry the window open at the end of a run into the next run of the job, which \
+                starts this long after the last hit",
+            );
+            ui.horizontal(|ui| {
+                let stitching = &mut self.parameters.run_stitching;
+                ui.checkbox(&mut stitching.enabled, "");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut stitching.restart_gap_seconds)
+                        .speed(0.1)
+                        .clamp_range(0.0..=f64::MAX),
+                );
+            });
+            ui.end_row();
+
+            ui.label("Merge Runs").on_hover_text(
+                "Once every run is built, also write the events of all runs of the job to one \
+                merged_events_<first>-<last>.parquet with a RunNumber column, the times of each \
+                run moved to follow the run before",
+            );
+            ui.checkbox(&mut self.parameters.merge_runs, "");
+            ui.end_row();
+
+            ui.label("Time-Reversed Check").on_hover_text(
+                "Also build each run backwards in time and compare the event counts, for small runs",
+            );
+            ui.checkbox(&mut self.parameters.time_reversed_check, "");
+            ui.end_row();
+
+            ui.label("Data Reduction").on_hover_text(
+                "Keep only part of the built events, recorded in the report and parquet metadata",
+            );
+            Self::data_reduction_ui(ui, &mut self.parameters.data_reduction, "data_reduction");
+            ui.end_row();
+
+            ui.label("Quick-Look File").on_hover_text(
+                "Also write run_<num>/quicklook.parquet with a filtered subsample of the events",
+            );
+            ui.horizontal(|ui| {
+                let quick_look = &mut self.parameters.quick_look;
+                ui.checkbox(&mut quick_look.enabled, "");
+                ui.label("Min Hits");
+                ui.add(
+                    egui::widgets::DragValue::new(&mut quick_look.min_multiplicity)
+                        .clamp_range(1..=usize::MAX),
+                );
+                ui.menu_button("Required Detectors", |ui| {
+                    for detector in channel_types.iter().copied().filter(|d| *d != ChannelType::None) {
+                        let mut is_required = quick_look.required_detectors.contains(&detector);
+                        if ui.checkbox(&mut is_required, detector.to_string()).changed() {
+                            if is_required {
+                                quick_look.required_detectors.push(detector);
+                            } else {
+                                quick_look.required_detectors.retain(|d| *d != detector);
+                            }
+                        }
+                    }
+                });
+            });
+            ui.end_row();
+
+            ui.label("Quick-Look Reduction")
+                .on_hover_text("Subsample of the events passing the quick-look filter");
+            Self::data_reduction_ui(ui, &mut self.parameters.quick_look.reduction, "quick_look");
+        });
+    }
+
+    //The warnings and errors of the last job, also in the report of every run and its build.log
+    //Estimated start and finish of every run of a batch, updated as the runs are built
+    fn batch_schedule_ui(&self, ui: &mut egui::Ui) {
+        let (schedule, fraction) = self.progress.get_schedule();
+        let estimates = schedule.get_estimates(fraction);
+        if estimates.len() < 2 {
+            return;
+        }
+        let title = match (
+            schedule.get_throughput(fraction),
+            schedule.get_remaining_seconds(fraction),
+        ) {
+            (Some(throughput), Some(seconds)) => format!(
+                "Batch Schedule (done at {}, {} left at {}/s)",
+                format_clock_time(seconds),
+                format_duration(seconds),
+                format_bytes(throughput as usize)
+            ),
+            _ => String::from("Batch Schedule (estimated once the first run is under way)"),
+        };
+        egui::CollapsingHeader::new(title)
+            .id_source("batch_schedule")
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical()
+                    .id_source("batch_schedule_scroll")
+                    .max_height(150.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("batch_schedule_grid")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for header in ["Run", "Size", "Status", "Start", "Finish"] {
+                                    ui.label(RichText::new(header).strong());
+                                }
+                                ui.end_row();
+                                for estimate in estimates.iter() {
+                                    let run = &estimate.run;
+                                    ui.label(run.run_number.to_string());
+                                    ui.label(format_bytes(run.bytes as usize));
+                                    ui.label(match &run.status {
+                                        ScheduleStatus::Waiting => "waiting",
+                                        ScheduleStatus::Building => "building",
+                                        ScheduleStatus::Finished(status) => status.as_ref(),
+                                    });
+                                    match (estimate.start_in, estimate.finish_in, run.seconds) {
+                                        (Some(start), Some(finish), _) => {
+                                            ui.label(format_clock_time(start));
+                                            ui.label(format_clock_time(finish));
+                                        }
+                                        (_, _, Some(seconds)) => {
+                                            ui.label("");
+                                            ui.label(format!("took {}", format_duration(seconds)));
+                                        }
+                                        _ => {
+                                            ui.label("");
+                                            ui.label("");
+                                        }
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+    }
+
+    fn build_diagnostics_ui(&self, ui: &mut egui::Ui) {
+        let diagnostics = self.build_diagnostics.get_all();
+        if diagnostics.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(format!(
+            "Build Diagnostics ({} warnings, {} errors)",
+            self.build_diagnostics.count(Severity::Warning),
+            self.build_diagnostics.count(Severity::Error)
+        ))
+        .id_source("build_diagnostics")
+        .show(ui, |ui| {
+            egui::ScrollArea::vertical()
+                .id_source("build_diagnostics_scroll")
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    for diagnostic in diagnostics.iter() {
+                        let color = match diagnostic.severity {
+                            Severity::Warning => Color32::YELLOW,
+                            Severity::Error => Color32::RED,
+                        };
+                        ui.label(RichText::new(diagnostic.render()).color(color));
+                    }
+                });
+        });
+    }
+
+    fn ui_tabs(&mut self, ui: &mut egui::Ui) {
+        egui::TopBottomPanel::top("cebra_top_panel").show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                for tab in ActiveTab::iter() {
+                    if ui
+                        .selectable_label(self.preferences.active_tab == tab, tab.get_label())
+                        .clicked()
+                    {
+                        self.preferences.active_tab = tab;
+                    }
+                }
+            });
+        });
+
+        match self.preferences.active_tab {
+            ActiveTab::MainTab => self.main_tab_ui(ui),
+            ActiveTab::ChannelMap => self.channel_map_ui(ui),
+            ActiveTab::ShiftMap => self.shift_map_ui(ui),
+            ActiveTab::ScalerList => self.scaler_list_ui(ui),
+            ActiveTab::SkipList => self.skip_list_ui(ui),
+            ActiveTab::Campaign => self.campaign_ui(ui),
+            ActiveTab::Efficiency => self.efficiency_ui(ui),
+            ActiveTab::Sps => self.sps_ui(ui),
+            ActiveTab::GainDrift => self.gain_drift_ui(ui),
+            ActiveTab::ColumnCodecs => self.column_codecs_ui(ui),
+            ActiveTab::Simulation => self.simulation_ui(ui),
+            ActiveTab::Outputs => self.outputs_ui(ui),
+            ActiveTab::Histograms => self.histograms_ui(ui),
+            ActiveTab::RunComparison => self.run_comparison_ui(ui),
+        }
     }
 
-    fn shift_map_ui(&mut self, ui: &mut egui::Ui) {
-        ui.label(
-            RichText::new("Time Shift Map")
-                .color(Color32::LIGHT_BLUE)
-                .size(18.0),
-        );
+    fn open_config_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.config_dir())
+            .add_filter("Config file", &["yaml", "toml"])
+            .pick_file();
 
-        // Assuming `self.shift_map_entries` is a Vec<ShiftMapEntry>
-        if ui.button("Add Entry").clicked() {
-            // Add a new entry with default values
-            self.parameters.shift_map_entries.push(ShiftMapEntry {
-                board_number: 0,
-                channel_number: 0,
-                time_shift: 0.0,
-            });
+        if let Some(real_path) = result {
+            self.preferences.remember_config_file(&real_path);
+            self.read_params_from_file(&real_path);
         }
+    }
 
-        // Iterate over each entry with its index
-        let mut to_remove = Vec::new(); // Collect indices of entries to remove
-        for (index, entry) in self.parameters.shift_map_entries.iter_mut().enumerate() {
-            ui.horizontal(|ui| {
-                // Allow the user to input board number, channel number, and time shift
-                ui.label("Board:");
-                ui.add(egui::DragValue::new(&mut entry.board_number));
-                ui.label("Channel:");
-                ui.add(egui::DragValue::new(&mut entry.channel_number));
-                ui.label("Time Shift:");
-                ui.add(egui::DragValue::new(&mut entry.time_shift).suffix(" ns"));
+    fn save_config_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.config_dir())
+            .add_filter("YAML file", &["yaml"])
+            .add_filter("TOML file", &["toml"])
+            .save_file();
 
-                // Button to remove the current entry
-                if ui.button("❌").clicked() {
-                    to_remove.push(index);
-                }
-            });
+        if let Some(real_path) = result {
+            self.preferences.remember_config_file(&real_path);
+            self.write_params_to_file(&real_path)
         }
+    }
 
-        // Remove entries marked for removal
-        // Iterate in reverse to ensure indices remain valid after removals
-        for &index in to_remove.iter().rev() {
-            self.parameters.shift_map_entries.remove(index);
+    fn open_workspace_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .pick_folder();
+
+        if let Some(real_path) = result {
+            self.switch_workspace(&real_path);
         }
     }
 
-    fn scaler_list_ui(&mut self, ui: &mut egui::Ui) {
-        ui.label(
-            RichText::new("Scalar List")
-                .color(Color32::LIGHT_BLUE)
-                .size(18.0),
-        );
+    //The built runs of another workspace, or of this one, to look at on the diagnostics tabs
+    fn open_build_dialog(&mut self) {
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .pick_folder();
 
-        if ui.button("Add Scaler Entry").clicked() {
-            // Add a new entry with default values
-            self.parameters.scaler_list_entries.push(ScalerEntryUI {
-                file_pattern: "".to_string(),
-                scaler_name: "".to_string(),
-            });
+        if let Some(real_path) = result {
+            match OpenedBuild::open(&real_path) {
+                Ok((build, run)) => {
+                    info!(
+                        "Opened {} built runs in {}",
+                        build.get_runs().len(),
+                        build.get_dir().display()
+                    );
+                    self.output_run = run
+                        .or(build.get_runs().first().copied())
+                        .unwrap_or_default();
+                    self.opened_build = Some(build);
+                    self.clear_shown_runs();
+                    if !matches!(
+                        self.preferences.active_tab,
+                        ActiveTab::Outputs | ActiveTab::Histograms | ActiveTab::RunComparison
+                    ) {
+                        self.preferences.active_tab = ActiveTab::Outputs;
+                    }
+                }
+                Err(x) => error!("Could not open the build {}: {}", real_path.display(), x),
+            }
         }
+    }
 
-        // Use a `ScrollArea` to ensure the UI can handle many entries
-        // egui::ScrollArea::horizontal().show(ui, |ui| {
-        let mut to_remove = Vec::new(); // Indices of entries to remove
-        for (index, entry) in self.parameters.scaler_list_entries.iter_mut().enumerate() {
-            ui.horizontal(|ui| {
-                ui.label("File Pattern:")
-                    .on_hover_text("Data_CH<channel_number>@<board_type>_<board_serial_number>");
-                ui.text_edit_singleline(&mut entry.file_pattern);
-                ui.label("Scaler Name:");
-                ui.text_edit_singleline(&mut entry.scaler_name);
+    fn close_build(&mut self) {
+        self.opened_build = None;
+        self.output_run = self.parameters.run_min;
+        self.clear_shown_runs();
+    }
 
-                // Button to remove the current entry
-                if ui.button("❌").clicked() {
-                    to_remove.push(index);
-                }
-            });
+    //What was read from the runs shown, read again from those shown next
+    fn clear_shown_runs(&mut self) {
+        self.diagnostic_columns = None;
+        self.histogram = None;
+        self.codec_report = None;
+        self.run_comparison = None;
+        self.shown_report = None;
+        self.shown_spectra = None;
+    }
+
+    //The configuration is saved to the workspace first, so the bundle has the one in use
+    fn export_bundle_dialog(&mut self) {
+        let workspace_dir = match &self.parameters.workspace {
+            Some(ws) => {
+                self.write_params_to_file(&ws.get_config_path());
+                ws.get_parent_dir().to_path_buf()
+            }
+            None => return,
+        };
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .add_filter("Workspace bundle", &["gz"])
+            .save_file();
+
+        if let Some(real_path) = result {
+            if let Err(x) = export_workspace_bundle(&workspace_dir, &real_path) {
+                error!("{}", x);
+            }
         }
+    }
 
-        // Remove entries marked for removal, in reverse order to maintain correct indices
-        for &index in to_remove.iter().rev() {
-            self.parameters.scaler_list_entries.remove(index);
+    //Imports into an empty folder, which then becomes the workspace
+    fn import_bundle_dialog(&mut self) {
+        let bundle = match rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .add_filter("Workspace bundle", &["gz"])
+            .pick_file()
+        {
+            Some(bundle) => bundle,
+            None => return,
+        };
+        let result = rfd::FileDialog::new()
+            .set_directory(self.preferences.workspace_dir())
+            .pick_folder();
+
+        if let Some(real_path) = result {
+            match import_workspace_bundle(&bundle, &real_path) {
+                Ok(()) => self.switch_workspace(&real_path),
+                Err(x) => error!("{}", x),
+            }
         }
-        // });
     }
 
-    fn main_tab_ui(&mut self, ui: &mut egui::Ui) {
-        //Files/Workspace
-        ui.separator();
-        ui.label(
-            RichText::new("Run Information")
-                .color(Color32::LIGHT_BLUE)
-                .size(18.0),
-        );
-        egui::Grid::new("RunGrid").show(ui, |ui| {
-            ui.label("Workspace: ");
-            ui.label(match &self.parameters.workspace {
-                Some(ws) => ws.get_parent_str(),
-                None => "None",
-            });
+    //Each workspace keeps the configuration of its campaign (channel map, shift map, gain anchors,
+    //...) in its evb_config.yaml. Switching saves the current configuration to the old workspace
+    //and loads the one of the new workspace, if it has one.
+    fn switch_workspace(&mut self, path: &Path) {
+        if self.thread_handle.is_some() {
+            error!("Cannot switch workspace while runs are being built");
+            return;
+        }
+        let workspace = match Workspace::new(path) {
+            Ok(ws) => ws,
+            Err(e) => {
+                error!("Error creating workspace {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if let Some(current) = &self.parameters.workspace {
+            if current.get_parent_dir() == workspace.get_parent_dir() {
+                return;
+            }
+            self.write_params_to_file(&current.get_config_path());
+        }
 
-            if ui.button("Open").clicked() {
-                let result = rfd::FileDialog::new()
-                    .set_directory(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
-                    .pick_folder();
+        let config_path = workspace.get_config_path();
+        if config_path.exists() {
+            if !self.read_params_from_file(&config_path) {
+                error!(
+                    "Keeping workspace {}, the configuration of {} could not be loaded",
+                    self.parameters
+                        .workspace
+                        .as_ref()
+                        .map_or("None", |ws| ws.get_parent_str()),
+                    path.display()
+                );
+                return;
+            }
+            info!("Loaded the configuration of workspace {}", path.display());
+        } else {
+            info!(
+                "Workspace {} has no configuration yet, keeping the current one",
+                path.display()
+            );
+        }
+        self.parameters.workspace = Some(workspace);
+        self.preferences.remember_workspace(path);
+        self.clear_workspace_state();
+        self.check_incomplete_runs();
+    }
 
-                if let Some(real_path) = result {
-                    self.parameters.workspace = match Workspace::new(&real_path) {
-                        Ok(ws) => Some(ws),
-                        Err(e) => {
-                            eprintln!("Error creating workspace: {}", e);
-                            None
+    //Looks for runs of an aborted build in the built directory, nothing is being built into it
+    //while the workspace is switched
+    fn check_incomplete_runs(&mut self) {
+        let output_dir = match self.parameters.workspace.as_ref() {
+            Some(ws) => match ws.get_output_dir() {
+                Ok(dir) => dir,
+                Err(_) => return,
+            },
+            None => return,
+        };
+        self.incomplete_runs = find_incomplete_runs(&output_dir);
+        if self.incomplete_runs.is_empty() {
+            info!("No incomplete runs in {}", output_dir.display());
+        }
+        for run in self.incomplete_runs.iter() {
+            warn!(
+                "Run {} was not built completely: {}",
+                run.run_number,
+                run.describe_problems()
+            );
+        }
+    }
+
+    fn incomplete_runs_ui(&mut self, ctx: &egui::Context) {
+        if self.incomplete_runs.is_empty() {
+            return;
+        }
+        let is_idle = self.thread_handle.is_none();
+        //A checkpoint is only used by a build with checkpoints
+        let can_resume = is_idle && self.parameters.checkpoints.enabled;
+        let mut to_clean = Vec::new();
+        let mut to_rebuild = None;
+        let mut to_resume = None;
+        let mut dismissed = false;
+        egui::Window::new("Incomplete Runs")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "These runs were left behind by a build that did not finish. Clean deletes \
+                    the run directory, Rebuild deletes it and builds the run again, Resume builds \
+                    it on from its last checkpoint.",
+                );
+                egui::Grid::new("incomplete_runs_grid")
+                    .striped(true)
+                    .num_columns(3)
+                    .show(ui, |ui| {
+                        for (index, run) in self.incomplete_runs.iter().enumerate() {
+                            ui.label(format!("Run {}", run.run_number));
+                            ui.label(run.describe_problems());
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .add_enabled(is_idle, egui::Button::new("Clean"))
+                                    .clicked()
+                                {
+                                    to_clean.push(index);
+                                }
+                                if ui
+                                    .add_enabled(is_idle, egui::Button::new("Rebuild"))
+                                    .clicked()
+                                {
+                                    to_rebuild = Some(index);
+                                }
+                                if ui
+                                    .add_enabled(
+                                        can_resume && run.has_checkpoint,
+                                        egui::Button::new("Resume"),
+                                    )
+                                    .clicked()
+                                {
+                                    to_resume = Some(index);
+                                }
+                            });
+                            ui.end_row();
                         }
+                    });
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(is_idle, egui::Button::new("Clean All"))
+                        .clicked()
+                    {
+                        to_clean = (0..self.incomplete_runs.len()).collect();
                     }
+                    if ui.button("Dismiss").clicked() {
+                        dismissed = true;
+                    }
+                });
+            });
+
+        if let Some(index) = to_rebuild {
+            let run = self.incomplete_runs.remove(index);
+            match run.clean() {
+                Ok(()) => {
+                    self.parameters.run_min = run.run_number;
+                    self.parameters.run_max = run.run_number;
+                    self.parameters.run_list.clear();
+                    self.start_processing();
                 }
+                Err(e) => error!("Could not clean run {}: {}", run.run_number, e),
             }
+        }
+        if let Some(index) = to_resume {
+            let run = self.incomplete_runs.remove(index);
+            self.parameters.run_min = run.run_number;
+            self.parameters.run_max = run.run_number;
+            self.parameters.run_list.clear();
+            self.start_processing();
+        }
+        for &index in to_clean.iter().rev() {
+            let run = self.incomplete_runs.remove(index);
+            match run.clean() {
+                Ok(()) => info!("Cleaned run {} at {}", run.run_number, run.dir.display()),
+                Err(e) => error!("Could not clean run {}: {}", run.run_number, e),
+            }
+        }
+        if dismissed {
+            self.incomplete_runs.clear();
+        }
+    }
 
-            ui.end_row();
+    //Everything shown from the built runs of the old workspace
+    fn clear_workspace_state(&mut self) {
+        self.channel_map_import = None;
+        self.output_run = self.parameters.run_min;
+        self.diagnostic_columns = None;
+        self.histogram = None;
+        self.progress.reset();
+    }
 
-            ui.label("Coincidence Window (ns)");
-            ui.add(
-                egui::widgets::DragValue::new(&mut self.parameters.coincidence_window)
-                    .speed(100)
-                    .custom_formatter(|n, _| format!("{:e}", n)),
-            );
-            ui.end_row();
+    fn set_dark_mode(&mut self, ctx: &egui::Context, dark_mode: bool) {
+        ctx.set_visuals(if dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
+        self.preferences.dark_mode = dark_mode;
+    }
 
-            ui.label("Run Min");
-            ui.add(egui::widgets::DragValue::new(&mut self.parameters.run_min).speed(1));
-            ui.end_row();
+    fn start_processing(&mut self) {
+        self.start_job(false);
+    }
 
-            ui.label("Run Max");
-            ui.add(egui::widgets::DragValue::new(&mut self.parameters.run_max).speed(1));
-        });
+    fn start_quick_build(&mut self) {
+        self.start_job(true);
     }
 
-    fn ui_tabs(&mut self, ui: &mut egui::Ui) {
-        egui::TopBottomPanel::top("cebra_top_panel").show_inside(ui, |ui| {
-            ui.horizontal(|ui| {
+    fn start_job(&mut self, is_quick_build: bool) {
+        info!("Starting processor...");
+        match self.check_and_startup_processing_thread(is_quick_build) {
+            Ok(_) => (),
+            Err(e) => error!(
+                "Could not start processor, recieved the following error: {}",
+                e
+            ),
+        };
+    }
+
+    //Everything the mouse can do from the menus, tabs and Run button, so a build can be set up and
+    //started from the keyboard. Within a tab, Tab/Shift+Tab move between widgets in reading order,
+    //Space/Enter press buttons and checkboxes, and arrow keys change a focused value.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let consume =
+            |shortcut: &egui::KeyboardShortcut| ctx.input_mut(|i| i.consume_shortcut(shortcut));
+
+        //Shift+O also matches the plain shortcut, so check it first
+        if consume(&OPEN_WORKSPACE_SHORTCUT) {
+            self.open_workspace_dialog();
+        }
+        if consume(&OPEN_CONFIG_SHORTCUT) {
+            self.open_config_dialog();
+        }
+        if consume(&SAVE_CONFIG_SHORTCUT) {
+            self.save_config_dialog();
+        }
+        if consume(&DARK_MODE_SHORTCUT) {
+            self.set_dark_mode(ctx, !self.preferences.dark_mode);
+        }
+        if consume(&RUN_SHORTCUT) && self.thread_handle.is_none() {
+            self.start_processing();
+        }
+        if consume(&NEXT_TAB_SHORTCUT) {
+            self.preferences.active_tab = self.preferences.active_tab.get_step(1);
+        }
+        if consume(&PREVIOUS_TAB_SHORTCUT) {
+            self.preferences.active_tab = self.preferences.active_tab.get_step(-1);
+        }
+        for (key, tab) in TAB_KEYS.iter().zip(ActiveTab::iter()) {
+            if consume(&egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, *key)) {
+                self.preferences.active_tab = tab;
+            }
+        }
+    }
+
+    fn shortcuts_ui(ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        egui::Grid::new("shortcuts_grid")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (shortcut, action) in [
+                    (OPEN_CONFIG_SHORTCUT, "Open config"),
+                    (SAVE_CONFIG_SHORTCUT, "Save config"),
+                    (OPEN_WORKSPACE_SHORTCUT, "Open workspace"),
+                    (RUN_SHORTCUT, "Run"),
+                    (NEXT_TAB_SHORTCUT, "Next tab"),
+                    (PREVIOUS_TAB_SHORTCUT, "Previous tab"),
+                    (DARK_MODE_SHORTCUT, "Toggle dark mode"),
+                ] {
+                    ui.label(ctx.format_shortcut(&shortcut));
+                    ui.label(action);
+                    ui.end_row();
+                }
+                ui.label(format!(
+                    "{} ... {}",
+                    ctx.format_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND,
+                        TAB_KEYS[0]
+                    )),
+                    ctx.format_shortcut(&egui::KeyboardShortcut::new(
+                        egui::Modifiers::COMMAND,
+                        TAB_KEYS[8]
+                    ))
+                ));
+                ui.label("Go to tab");
+                ui.end_row();
+                ui.label("Tab / Shift+Tab");
+                ui.label("Move between fields");
+                ui.end_row();
+                ui.label("Space / Enter");
+                ui.label("Press the focused button");
+                ui.end_row();
+            });
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        self.handle_shortcuts(ui.ctx());
+
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("File", |ui| {
+                if ui
+                    .add(
+                        egui::Button::new("Open Config...")
+                            .shortcut_text(ui.ctx().format_shortcut(&OPEN_CONFIG_SHORTCUT)),
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.open_config_dialog();
+                }
+                if ui
+                    .add(
+                        egui::Button::new("Save Config...")
+                            .shortcut_text(ui.ctx().format_shortcut(&SAVE_CONFIG_SHORTCUT)),
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.save_config_dialog();
+                }
                 if ui
-                    .selectable_label(
-                        matches!(self.active_tab, ActiveTab::MainTab),
-                        "Eventbuilder",
+                    .add(
+                        egui::Button::new("Open Workspace...")
+                            .shortcut_text(ui.ctx().format_shortcut(&OPEN_WORKSPACE_SHORTCUT)),
                     )
                     .clicked()
                 {
-                    self.active_tab = ActiveTab::MainTab;
+                    ui.close_menu();
+                    self.open_workspace_dialog();
                 }
+                ui.menu_button("Recent Workspaces", |ui| {
+                    let mut chosen = None;
+                    for recent in self.preferences.recent_workspaces.iter() {
+                        if ui.button(recent.display().to_string()).clicked() {
+                            chosen = Some(recent.clone());
+                        }
+                    }
+                    if self.preferences.recent_workspaces.is_empty() {
+                        ui.label("None");
+                    }
+                    if let Some(path) = chosen {
+                        ui.close_menu();
+                        self.switch_workspace(&path);
+                    }
+                });
                 if ui
-                    .selectable_label(
-                        matches!(self.active_tab, ActiveTab::ChannelMap),
-                        "Channel Map",
+                    .add_enabled(
+                        self.parameters.workspace.is_some() && self.thread_handle.is_none(),
+                        egui::Button::new("Check Built Runs..."),
                     )
+                    .on_hover_text("Look for runs left behind by a build that did not finish")
                     .clicked()
                 {
-                    self.active_tab = ActiveTab::ChannelMap;
+                    ui.close_menu();
+                    self.check_incomplete_runs();
                 }
+                ui.separator();
                 if ui
-                    .selectable_label(matches!(self.active_tab, ActiveTab::ShiftMap), "Shift Map")
+                    .button("Open Build...")
+                    .on_hover_text(
+                        "Look at the reports, spectra and events of runs built before, e.g. on \
+                        another machine, without building them again",
+                    )
                     .clicked()
                 {
-                    self.active_tab = ActiveTab::ShiftMap;
+                    ui.close_menu();
+                    self.open_build_dialog();
                 }
                 if ui
-                    .selectable_label(
-                        matches!(self.active_tab, ActiveTab::ScalerList),
-                        "Scaler List",
+                    .add_enabled(
+                        self.opened_build.is_some(),
+                        egui::Button::new("Close Build"),
                     )
                     .clicked()
                 {
-                    self.active_tab = ActiveTab::ScalerList;
+                    ui.close_menu();
+                    self.close_build();
                 }
             });
-        });
-
-        match self.active_tab {
-            ActiveTab::MainTab => self.main_tab_ui(ui),
-            ActiveTab::ChannelMap => self.channel_map_ui(ui),
-            ActiveTab::ShiftMap => self.shift_map_ui(ui),
-            ActiveTab::ScalerList => self.scaler_list_ui(ui),
-        }
-    }
-
-    fn ui(&mut self, ui: &mut egui::Ui) {
-        ui.menu_button("File", |ui| {
-            if ui.button("Open Config...").clicked() {
-                let result = rfd::FileDialog::new()
-                    .set_directory(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
-                    .add_filter("YAML file", &["yaml"])
-                    .pick_file();
 
-                if let Some(real_path) = result {
-                    self.read_params_from_file(&real_path)
+            ui.menu_button("View", |ui| {
+                let mut dark_mode = ui.ctx().style().visuals.dark_mode;
+                if ui.checkbox(&mut dark_mode, "Dark Mode").changed() {
+                    self.set_dark_mode(ui.ctx(), dark_mode);
                 }
-            }
-            if ui.button("Save Config...").clicked() {
-                let result = rfd::FileDialog::new()
-                    .set_directory(std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
-                    .add_filter("YAML file", &["yaml"])
-                    .save_file();
+            });
 
-                if let Some(real_path) = result {
-                    self.write_params_to_file(&real_path)
+            ui.menu_button("Help", |ui| {
+                if ui.button("Setup Wizard...").clicked() {
+                    ui.close_menu();
+                    self.setup_wizard = Some(WizardStep::Workspace);
                 }
-            }
+                ui.label(RichText::new("Keyboard Shortcuts").strong());
+                Self::shortcuts_ui(ui);
+            });
         });
 
         ui.separator();
 
+        let mut is_closed = false;
+        if let Some(build) = &self.opened_build {
+            ui.horizontal(|ui| {
+                ui.label(
+                    RichText::new(format!(
+                        "Showing the build in {} (read-only)",
+                        build.get_dir().display()
+                    ))
+                    .color(Color32::YELLOW),
+                );
+                is_closed = ui.button("Close").clicked();
+            });
+        }
+        if is_closed {
+            self.close_build();
+        }
+
         self.ui_tabs(ui);
 
         ui.separator();
 
-        ui.add(
-            egui::widgets::ProgressBar::new(match self.progress.lock() {
-                Ok(x) => *x,
-                Err(_) => 0.0,
-            })
-            .show_percentage(),
-        );
+        ui.add(egui::widgets::ProgressBar::new(self.progress.get_fraction()).show_percentage());
+        if self.thread_handle.is_some() {
+            let latest = self.progress.get_latest();
+            if latest.run_index > 0 {
+                ui.label(latest.describe());
+            }
+            self.batch_schedule_ui(ui);
+        }
+
+        if self.paranoid {
+            ui.label(RichText::new("Paranoid checks enabled").color(Color32::YELLOW));
+        }
+
+        self.build_diagnostics_ui(ui);
 
         // Check if the thread handle exists to determine if the process is running
         let is_running = self.thread_handle.is_some();
@@ -528,27 +5028,61 @@ impl EVBApp {
         if ui
             .add_enabled(
                 self.thread_handle.is_none(),
-                egui::widgets::Button::new("Run"),
+                egui::widgets::Button::new("Run")
+                    .shortcut_text(ui.ctx().format_shortcut(&RUN_SHORTCUT)),
             )
             .clicked()
         {
-            info!("Starting processor...");
-            match self.check_and_startup_processing_thread() {
-                Ok(_) => (),
-                Err(e) => error!(
-                    "Could not start processor, recieved the following error: {}",
-                    e
-                ),
-            };
+            self.start_processing();
         } else {
             self.check_and_shutdown_processing_thread();
         }
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.thread_handle.is_none(),
+                    egui::widgets::Button::new("Quick Build"),
+                )
+                .on_hover_text(
+                    "Build only the start of the first run, then show its histograms, to check \
+                     the channel map and gates before the full build",
+                )
+                .clicked()
+            {
+                self.start_quick_build();
+            }
+            ui.label("First");
+            ui.add(
+                egui::widgets::DragValue::new(&mut self.parameters.quick_build.minutes)
+                    .speed(1)
+                    .clamp_range(0.1..=f64::MAX),
+            );
+            ui.label("minutes");
+        });
+
+        self.channel_map_import_ui(ui.ctx());
+        self.setup_wizard_ui(ui.ctx());
+        self.incomplete_runs_ui(ui.ctx());
     }
 }
 
 impl App for EVBApp {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, PREFERENCES_KEY, &self.preferences);
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        //Ctrl-C in the terminal the GUI runs from: the job stops at the next hit and writes what it
+        //has, and the app closes once it is done
+        if is_shutdown_requested() {
+            if self.thread_handle.is_none() {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            } else {
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            }
+        }
         if self.window {
             egui::Window::new("CeBrA Event Builder")
                 .min_width(200.0)
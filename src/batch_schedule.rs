@@ -0,0 +1,153 @@
+use std::path::Path;
+use std::time::Instant;
+
+use super::run_archive::find_run_archive;
+use super::run_list::BatchStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScheduleStatus {
+    Waiting,
+    Building,
+    Finished(BatchStatus),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledRun {
+    pub run_number: i32,
+    //Size of the run archive, the throughput is measured in archive bytes
+    pub bytes: u64,
+    pub status: ScheduleStatus,
+    //Wall time the run took, once finished
+    pub seconds: Option<f64>,
+}
+
+//One line of the schedule as shown, times in seconds from now
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduleEstimate {
+    pub run: ScheduledRun,
+    //None for finished runs, and for all runs before there is a throughput to go by
+    pub start_in: Option<f64>,
+    pub finish_in: Option<f64>,
+}
+
+//The runs of a job in the order they are built, with their sizes, so the time the job will take is
+//estimated from the throughput of the runs built so far and the sizes of those still to come.
+//Unpacking, writing and everything else a run does counts towards its time.
+#[derive(Debug, Clone, Default)]
+pub struct BatchSchedule {
+    runs: Vec<ScheduledRun>,
+    //Of the run being built
+    run_start: Option<Instant>,
+}
+
+impl BatchSchedule {
+    //Runs without an archive are finished as missing from the start
+    pub fn new(archive_dir: &Path, runs: &[i32]) -> Self {
+        BatchSchedule {
+            runs: runs
+                .iter()
+                .map(|run| match find_run_archive(archive_dir, *run) {
+                    Some(path) => ScheduledRun {
+                        run_number: *run,
+                        bytes: path.metadata().map_or(0, |metadata| metadata.len()),
+                        status: ScheduleStatus::Waiting,
+                        seconds: None,
+                    },
+                    None => ScheduledRun {
+                        run_number: *run,
+                        bytes: 0,
+                        status: ScheduleStatus::Finished(BatchStatus::Missing),
+                        seconds: None,
+                    },
+                })
+                .collect(),
+            run_start: None,
+        }
+    }
+
+    pub fn start_run(&mut self, run_number: i32) {
+        if let Some(run) = self
+            .runs
+            .iter_mut()
+            .find(|run| run.run_number == run_number)
+        {
+            run.status = ScheduleStatus::Building;
+            self.run_start = Some(Instant::now());
+        }
+    }
+
+    pub fn finish_run(&mut self, status: BatchStatus) {
+        let seconds = self
+            .run_start
+            .take()
+            .map(|start| start.elapsed().as_secs_f64());
+        if let Some(run) = self
+            .runs
+            .iter_mut()
+            .find(|run| run.status == ScheduleStatus::Building)
+        {
+            run.status = ScheduleStatus::Finished(status);
+            run.seconds = seconds;
+        }
+    }
+
+    //Archive bytes per second over the runs built so far and the part of the current run built,
+    //given as a fraction. Failed and interrupted runs stopped early, so they are left out.
+    pub fn get_throughput(&self, current_fraction: f64) -> Option<f64> {
+        let (mut bytes, mut seconds) = (0.0, 0.0);
+        for run in self.runs.iter() {
+            match (run.status, run.seconds) {
+                (ScheduleStatus::Finished(BatchStatus::Built), Some(run_seconds)) => {
+                    bytes += run.bytes as f64;
+                    seconds += run_seconds;
+                }
+                (ScheduleStatus::Building, _) => {
+                    bytes += run.bytes as f64 * current_fraction.clamp(0.0, 1.0);
+                    seconds += self
+                        .run_start
+                        .map_or(0.0, |start| start.elapsed().as_secs_f64());
+                }
+                _ => (),
+            }
+        }
+        (bytes > 0.0 && seconds > 0.0).then_some(bytes / seconds)
+    }
+
+    //Every run with its estimated start and finish, the runs left built one after the other at
+    //the throughput so far
+    pub fn get_estimates(&self, current_fraction: f64) -> Vec<ScheduleEstimate> {
+        let throughput = self.get_throughput(current_fraction);
+        let mut elapsed = 0.0;
+        self.runs
+            .iter()
+            .map(|run| {
+                let (start_in, finish_in) = match (run.status, throughput) {
+                    (ScheduleStatus::Building, Some(throughput)) => {
+                        let left = 1.0 - current_fraction.clamp(0.0, 1.0);
+                        elapsed += run.bytes as f64 * left / throughput;
+                        (Some(0.0), Some(elapsed))
+                    }
+                    (ScheduleStatus::Waiting, Some(throughput)) => {
+                        let start = elapsed;
+                        elapsed += run.bytes as f64 / throughput;
+                        (Some(start), Some(elapsed))
+                    }
+                    _ => (None, None),
+                };
+                ScheduleEstimate {
+                    run: run.clone(),
+                    start_in,
+                    finish_in,
+                }
+            })
+            .collect()
+    }
+
+    //Until the last run is built
+    pub fn get_remaining_seconds(&self, current_fraction: f64) -> Option<f64> {
+        self.get_estimates(current_fraction)
+            .iter()
+            .filter_map(|estimate| estimate.finish_in)
+            .last()
+    }
+}
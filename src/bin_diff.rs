@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use super::compass_data::{CompassDataType, RawCompassData};
+use super::compass_file::CompassFile;
+use super::error::EVBError;
+
+//Only the first few differing records are listed in full, the rest are only counted
+const MAX_LISTED_DIFFERENCES: usize = 10;
+
+//One record that is not the same in both files, None where a file has already ended
+#[derive(Debug, Clone)]
+pub struct RecordDifference {
+    pub record: u64,
+    pub first: Option<RawCompassData>,
+    pub second: Option<RawCompassData>,
+}
+
+//Result of comparing two CoMPASS binary files record by record, e.g. a copy on the long-term
+//storage against the file on the DAQ machine. Records are compared decoded, with no time shifts or
+//dither, so two files only differ where the data does.
+#[derive(Debug, Clone)]
+pub struct BinDiff {
+    pub first_path: PathBuf,
+    pub second_path: PathBuf,
+    pub first_type: CompassDataType,
+    pub second_type: CompassDataType,
+    pub first_records: u64,
+    pub second_records: u64,
+    pub differing_records: u64,
+    //The first MAX_LISTED_DIFFERENCES of them
+    pub differences: Vec<RecordDifference>,
+}
+
+fn describe_type(data_type: CompassDataType) -> String {
+    let names: Vec<&str> = data_type.iter_names().map(|(name, _)| name).collect();
+    if names.is_empty() {
+        String::from("NONE")
+    } else {
+        names.join(" | ")
+    }
+}
+
+//Field name and value of every decoded field of a record
+fn get_fields(record: &Option<RawCompassData>) -> [(&'static str, Option<u64>); 7] {
+    let value = |get: fn(&RawCompassData) -> u64| record.as_ref().map(get);
+    [
+        ("Board", value(|r| r.board as u64)),
+        ("Channel", value(|r| r.channel as u64)),
+        ("Timestamp", value(|r| r.timestamp)),
+        ("Energy", value(|r| r.energy)),
+        ("EnergyShort", value(|r| r.energy_short as u64)),
+        ("EnergyCalibrated", value(|r| r.energy_calibrated)),
+        ("Flags", value(|r| r.flags as u64)),
+    ]
+}
+
+fn format_value(value: Option<u64>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => String::from("(end of file)"),
+    }
+}
+
+impl BinDiff {
+    pub fn is_identical(&self) -> bool {
+        self.first_type == self.second_type
+            && self.first_records == self.second_records
+            && self.differing_records == 0
+    }
+
+    //Each listed difference shows all fields of the record in both files, with the fields that
+    //differ marked
+    pub fn format_report(&self) -> String {
+        let mut report = format!(
+            "1: {}\n2: {}\n",
+            self.first_path.display(),
+            self.second_path.display()
+        );
+        if self.first_type == self.second_type {
+            report.push_str(&format!("Header: {}\n", describe_type(self.first_type)));
+        } else {
+            report.push_str(&format!(
+                "Header differs: {} in 1, {} in 2\n",
+                describe_type(self.first_type),
+                describe_type(self.second_type)
+            ));
+        }
+        report.push_str(&format!(
+            "Records: {} in 1, {} in 2\n",
+            self.first_records, self.second_records
+        ));
+
+        for difference in self.differences.iter() {
+            report.push_str(&format!("\nRecord {} differs:\n", difference.record));
+            report.push_str(&format!("  {:<18}{:>22}{:>22}\n", "Field", "1", "2"));
+            let first = get_fields(&difference.first);
+            let second = get_fields(&difference.second);
+            for ((name, first_value), (_, second_value)) in first.iter().zip(second.iter()) {
+                report.push_str(&format!(
+                    "  {:<18}{:>22}{:>22}{}\n",
+                    name,
+                    format_value(*first_value),
+                    format_value(*second_value),
+                    if first_value == second_value {
+                        ""
+                    } else {
+                        "  <<"
+                    }
+                ));
+            }
+        }
+        let unlisted = self.differing_records - self.differences.len() as u64;
+        if unlisted > 0 {
+            report.push_str(&format!("\n... and {} more differing records\n", unlisted));
+        }
+
+        if self.is_identical() {
+            report.push_str("\nFiles are identical\n");
+        } else if let Some(first) = self.differences.first() {
+            report.push_str(&format!(
+                "\n{} of {} records differ, first at record {}\n",
+                self.differing_records,
+                self.first_records.max(self.second_records),
+                first.record
+            ));
+        } else {
+            report.push_str("\nRecords are identical, the headers differ\n");
+        }
+        report
+    }
+}
+
+//Reads both files to the end. Records are counted from 0; a record only one file has counts as
+//differing.
+pub fn diff_compass_files(first_path: &Path, second_path: &Path) -> Result<BinDiff, EVBError> {
+    let no_shifts = None;
+    let mut first_file = CompassFile::new(first_path, &no_shifts, &[])?;
+    let mut second_file = CompassFile::new(second_path, &no_shifts, &[])?;
+    let mut diff = BinDiff {
+        first_path: first_path.to_path_buf(),
+        second_path: second_path.to_path_buf(),
+        first_type: first_file.get_data_type(),
+        second_type: second_file.get_data_type(),
+        first_records: 0,
+        second_records: 0,
+        differing_records: 0,
+        differences: vec![],
+    };
+
+    let mut record: u64 = 0;
+    loop {
+        let first = first_file.read_raw_hit()?;
+        let second = second_file.read_raw_hit()?;
+        if first.is_none() && second.is_none() {
+            break;
+        }
+        diff.first_records += first.is_some() as u64;
+        diff.second_records += second.is_some() as u64;
+        if first != second {
+            diff.differing_records += 1;
+            if diff.differences.len() < MAX_LISTED_DIFFERENCES {
+                diff.differences.push(RecordDifference {
+                    record,
+                    first,
+                    second,
+                });
+            }
+        }
+        record += 1;
+    }
+    Ok(diff)
+}
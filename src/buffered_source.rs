@@ -0,0 +1,165 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::hit_source::{HitSource, Waiting};
+use super::run_boundary::ResetTimes;
+use super::used_size::UsedSize;
+
+//What a buffered source does to the hits it reads, e.g. move their times (see time_jitter,
+//time_walk and timestamp_sanitizer)
+pub trait HitTransform: Send {
+    //The hit to buffer, or None to leave it out
+    fn transform(&mut self, hit: CompassData) -> Option<CompassData>;
+
+    //The hit is passed on, every hit buffered later has to come after it
+    fn pass(&mut self, _hit: &CompassData) {}
+
+    fn start_next_run(&mut self) {}
+}
+
+//Earliest hit on top of the heap
+#[derive(Debug)]
+struct BufferedHit(CompassData);
+
+impl PartialEq for BufferedHit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BufferedHit {}
+
+impl PartialOrd for BufferedHit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BufferedHit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.timestamp.total_cmp(&other.0.timestamp)
+    }
+}
+
+//Transformed hits are no longer in time order, so they are held until the hits read are the hold
+//window later, and passed on in time order
+pub struct BufferedSource<'a, T: HitTransform> {
+    inner: Box<dyn HitSource + 'a>,
+    transform: T,
+    //ns
+    hold: f64,
+    buffer: BinaryHeap<Reverse<BufferedHit>>,
+    current_hit: CompassData,
+    is_used: bool,
+    is_eof: bool,
+}
+
+impl<'a, T: HitTransform> BufferedSource<'a, T> {
+    pub fn new(mut inner: Box<dyn HitSource + 'a>, transform: T, hold: f64) -> Self {
+        //The first call of get_top_hit should read the first hit of the inner source
+        inner.set_hit_used();
+        BufferedSource {
+            inner,
+            transform,
+            hold,
+            buffer: BinaryHeap::new(),
+            current_hit: CompassData::default(),
+            is_used: false,
+            is_eof: false,
+        }
+    }
+
+    fn fill_buffer(&mut self) -> Result<(), EVBError> {
+        loop {
+            let next = self.inner.get_top_hit()?.clone();
+            //At a run boundary the buffer is emptied before the next run is read. A file still being
+            //written is read on once it has grown.
+            if self.inner.is_eof()
+                || self.inner.get_boundary().is_some()
+                || self.inner.get_waiting().is_some()
+            {
+                return Ok(());
+            }
+            if let Some(Reverse(earliest)) = self.buffer.peek() {
+                if earliest.0.timestamp <= next.timestamp - self.hold {
+                    return Ok(());
+                }
+            }
+            self.inner.set_hit_used();
+            if next.is_default() {
+                continue;
+            }
+            if let Some(hit) = self.transform.transform(next) {
+                self.buffer.push(Reverse(BufferedHit(hit)));
+            }
+        }
+    }
+}
+
+impl<T: HitTransform> HitSource for BufferedSource<'_, T> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            self.fill_buffer()?;
+            //Hits are held while the file may still write earlier ones
+            if self.inner.get_waiting() == Some(Waiting::Growing) {
+                self.current_hit = CompassData::default();
+                return Ok(&self.current_hit);
+            }
+            match self.buffer.pop() {
+                Some(Reverse(hit)) => {
+                    self.transform.pass(&hit.0);
+                    self.current_hit = hit.0;
+                }
+                None => {
+                    self.current_hit = CompassData::default();
+                    self.is_eof = self.inner.is_eof();
+                }
+            }
+            //A source waiting for its file to grow is read again on the next call
+            self.is_used = self.current_hit.is_default() && self.inner.get_waiting().is_some();
+        }
+        Ok(&self.current_hit)
+    }
+
+    fn set_hit_used(&mut self) {
+        self.is_used = true;
+    }
+
+    fn is_eof(&self) -> bool {
+        self.is_eof
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.inner.get_number_of_hits()
+    }
+
+    fn get_boundary(&self) -> Option<ResetTimes> {
+        if self.current_hit.is_default() && self.buffer.is_empty() {
+            self.inner.get_boundary()
+        } else {
+            None
+        }
+    }
+
+    fn start_next_run(&mut self) {
+        self.inner.start_next_run();
+        self.transform.start_next_run();
+        self.is_used = true;
+    }
+
+    fn get_waiting(&self) -> Option<Waiting> {
+        if self.is_used {
+            self.inner.get_waiting()
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: HitTransform> UsedSize for BufferedSource<'_, T> {
+    fn get_used_size(&self) -> usize {
+        self.inner.get_used_size() + self.buffer.capacity() * std::mem::size_of::<CompassData>()
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::{Arc, Mutex};
+use std::thread::ThreadId;
+
+use log::Level;
+use serde::Serialize;
+use strum_macros::AsRefStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsRefStr, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+//A warning or error of a build, with a fixed code (e.g. skip_list_unused) to tell the kinds apart
+//and the context it was found in (an input file, a channel, ...)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildDiagnostic {
+    pub severity: Severity,
+    pub code: String,
+    //None outside of a run, e.g. for the summed calibration spectra
+    pub run: Option<i32>,
+    pub context: String,
+    pub message: String,
+}
+
+impl BuildDiagnostic {
+    //The same line in the log, the GUI and on the command line, e.g.
+    //warning[skip_list_file] run 12, CH0@V1730_89_Data_run_12.BIN: Skipping input file ...
+    pub fn render(&self) -> String {
+        let mut location: Vec<String> = vec![];
+        if let Some(run) = self.run {
+            location.push(format!("run {}", run));
+        }
+        if !self.context.is_empty() {
+            location.push(self.context.clone());
+        }
+        if location.is_empty() {
+            format!(
+                "{}[{}]: {}",
+                self.severity.as_ref(),
+                self.code,
+                self.message
+            )
+        } else {
+            format!(
+                "{}[{}] {}: {}",
+                self.severity.as_ref(),
+                self.code,
+                location.join(", "),
+                self.message
+            )
+        }
+    }
+}
+
+//Every diagnostic of a job, shared between the thread building the runs and the frontend that
+//started it
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticLog {
+    diagnostics: Arc<Mutex<Vec<BuildDiagnostic>>>,
+}
+
+impl DiagnosticLog {
+    fn push(&self, diagnostic: BuildDiagnostic) {
+        if let Ok(mut diagnostics) = self.diagnostics.lock() {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    pub fn get_all(&self) -> Vec<BuildDiagnostic> {
+        match self.diagnostics.lock() {
+            Ok(diagnostics) => diagnostics.clone(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn get_run(&self, run_number: i32) -> Vec<BuildDiagnostic> {
+        self.get_all()
+            .into_iter()
+            .filter(|diagnostic| diagnostic.run == Some(run_number))
+            .collect()
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        match self.diagnostics.lock() {
+            Ok(diagnostics) => diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == severity)
+                .count(),
+            Err(_) => 0,
+        }
+    }
+}
+
+struct ActiveDiagnostics {
+    threads: Vec<ThreadId>,
+    log: DiagnosticLog,
+    run: Option<i32>,
+}
+
+//As with the run log, only diagnostics of the thread building the runs go to the job
+static ACTIVE_DIAGNOSTICS: Mutex<Option<ActiveDiagnostics>> = Mutex::new(None);
+
+//Collects the diagnostics of the current thread into the log until dropped
+pub struct DiagnosticGuard;
+
+impl DiagnosticGuard {
+    pub fn new(log: &DiagnosticLog) -> Self {
+        if let Ok(mut guard) = ACTIVE_DIAGNOSTICS.lock() {
+            *guard = Some(ActiveDiagnostics {
+                threads: vec![std::thread::current().id()],
+                log: log.clone(),
+                run: None,
+            });
+        }
+        DiagnosticGuard
+    }
+
+    //The run the following diagnostics belong to
+    pub fn set_run(&self, run: Option<i32>) {
+        if let Ok(mut guard) = ACTIVE_DIAGNOSTICS.lock() {
+            if let Some(active) = guard.as_mut() {
+                active.run = run;
+            }
+        }
+    }
+}
+
+//Also collects the diagnostics of the current thread into the log of the job, for the threads
+//helping to build a run (see hit_decoder)
+pub fn join_diagnostics() {
+    if let Ok(mut guard) = ACTIVE_DIAGNOSTICS.lock() {
+        if let Some(active) = guard.as_mut() {
+            active.threads.push(std::thread::current().id());
+        }
+    }
+}
+
+impl Drop for DiagnosticGuard {
+    fn drop(&mut self) {
+        if let Ok(mut guard) = ACTIVE_DIAGNOSTICS.lock() {
+            *guard = None;
+        }
+    }
+}
+
+//Logs the diagnostic and adds it to the log of the job being built on this thread, if any
+fn emit(severity: Severity, code: &str, context: String, message: String) {
+    let mut diagnostic = BuildDiagnostic {
+        severity,
+        code: code.to_string(),
+        run: None,
+        context,
+        message,
+    };
+    let mut job_log = None;
+    if let Ok(guard) = ACTIVE_DIAGNOSTICS.lock() {
+        if let Some(active) = guard.as_ref() {
+            if active.threads.contains(&std::thread::current().id()) {
+                diagnostic.run = active.run;
+                job_log = Some(active.log.clone());
+            }
+        }
+    }
+    let level = match severity {
+        Severity::Warning => Level::Warn,
+        Severity::Error => Level::Error,
+    };
+    log::log!(level, "{}", diagnostic.render());
+    if let Some(job_log) = job_log {
+        job_log.push(diagnostic);
+    }
+}
+
+pub fn emit_warning(code: &str, context: String, message: String) {
+    emit(Severity::Warning, code, context, message);
+}
+
+pub fn emit_error(code: &str, context: String, message: String) {
+    emit(Severity::Error, code, context, message);
+}
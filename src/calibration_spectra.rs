@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::ChannelMap;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::error::EVBError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationSpectraParams {
+    pub enabled: bool,
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for CalibrationSpectraParams {
+    fn default() -> Self {
+        CalibrationSpectraParams {
+            enabled: false,
+            bins: 4096,
+            min: 0.0,
+            max: 4096.0,
+        }
+    }
+}
+
+//One file for the whole batch, next to the run directories
+pub fn get_calibration_spectra_path(output_dir: &Path, run_min: i32, run_max: i32) -> PathBuf {
+    output_dir.join(format!("calibration_spectra_{}-{}.csv", run_min, run_max))
+}
+
+//Energy spectrum of every channel, summed over all runs of a batch, which is what a campaign-wide
+//calibration is fit to. Each run is filled on its own and only added once it was built, so a run
+//that is built again in earliest hit mode is not counted twice.
+#[derive(Debug, Clone)]
+pub struct CalibrationSpectra {
+    params: CalibrationSpectraParams,
+    counts: BTreeMap<u32, Vec<u64>>,
+    runs: Vec<i32>,
+}
+
+impl CalibrationSpectra {
+    pub fn new(params: &CalibrationSpectraParams) -> Self {
+        CalibrationSpectra {
+            params: params.clone(),
+            counts: BTreeMap::new(),
+            runs: vec![],
+        }
+    }
+
+    //Empty spectra with the same binning, to fill one run into
+    pub fn new_run(&self) -> Self {
+        CalibrationSpectra::new(&self.params)
+    }
+
+    fn get_bin(&self, energy: f64) -> Option<usize> {
+        if energy < self.params.min || energy >= self.params.max {
+            return None;
+        }
+        let width = (self.params.max - self.params.min) / (self.params.bins as f64);
+        Some((((energy - self.params.min) / width) as usize).min(self.params.bins - 1))
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData) {
+        if let Some(bin) = self.get_bin(hit.energy) {
+            let bins = self.params.bins;
+            self.counts.entry(hit.uuid).or_insert_with(|| vec![0; bins])[bin] += 1;
+        }
+    }
+
+    pub fn add_run(&mut self, run_number: i32, run: CalibrationSpectra) {
+        for (uuid, spectrum) in run.counts {
+            let total = self
+                .counts
+                .entry(uuid)
+                .or_insert_with(|| vec![0; spectrum.len()]);
+            for (count, run_count) in total.iter_mut().zip(spectrum) {
+                *count += run_count;
+            }
+        }
+        self.runs.push(run_number);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    //One row per bin, one column per channel with hits, named <detector>_<board>_<channel>
+    pub fn write_spectra(&self, filepath: &Path, map: &ChannelMap) -> Result<(), EVBError> {
+        info!(
+            "Writing calibration spectra of {} channels summed over runs {:?} to {}",
+            self.counts.len(),
+            self.runs,
+            filepath.display()
+        );
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .counts
+            .keys()
+            .map(|uuid| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let name = match map.get_channel_data(uuid) {
+                    Some(data) => data.channel_type.to_string(),
+                    None => String::from("Unmapped"),
+                };
+                format!("{}_{}_{}", name, board, channel)
+            })
+            .collect();
+        writeln!(writer, "bin_low,bin_high,{}", headers.join(","))?;
+
+        let width = (self.params.max - self.params.min) / (self.params.bins as f64);
+        for bin in 0..self.params.bins {
+            let low = self.params.min + width * (bin as f64);
+            let counts: Vec<String> = self
+                .counts
+                .values()
+                .map(|spectrum| spectrum[bin].to_string())
+                .collect();
+            writeln!(writer, "{},{},{}", low, low + width, counts.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+use super::parquet_writer::ParquetMetadata;
+
+//Added to the event tables of tagged runs when columns are enabled
+pub const CAMPAIGN_COLUMN: &str = "CampaignConfiguration";
+
+//The physics configuration of a range of runs of the campaign, e.g. the runs on one target
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CampaignEntry {
+    pub first_run: i32,
+    pub last_run: i32,
+    pub target: String,
+    pub beam: String,
+    #[serde(default)]
+    pub label: String,
+}
+
+impl CampaignEntry {
+    pub fn contains(&self, run_number: i32) -> bool {
+        run_number >= self.first_run && run_number <= self.last_run
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CampaignParams {
+    pub entries: Vec<CampaignEntry>,
+    //Also write the configuration number of every event as a column
+    #[serde(default)]
+    pub add_columns: bool,
+}
+
+//The tag of one run, written to its report and to the metadata of its event tables
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CampaignTag {
+    pub target: String,
+    pub beam: String,
+    pub label: String,
+    //Entries with the same target and beam share a number, counted from 0 in the order they are
+    //first listed, so the runs of one configuration group together however their ranges are split
+    pub configuration: usize,
+}
+
+impl CampaignParams {
+    //The first entry containing the run, None for runs outside the campaign
+    pub fn get_tag(&self, run_number: i32) -> Option<CampaignTag> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.contains(run_number))?;
+        let mut configurations: Vec<(&str, &str)> = vec![];
+        for other in self.entries.iter() {
+            let key = (other.target.as_str(), other.beam.as_str());
+            if !configurations.contains(&key) {
+                configurations.push(key);
+            }
+        }
+        let configuration = configurations
+            .iter()
+            .position(|key| *key == (entry.target.as_str(), entry.beam.as_str()))
+            .unwrap_or(0);
+        Some(CampaignTag {
+            target: entry.target.clone(),
+            beam: entry.beam.clone(),
+            label: entry.label.clone(),
+            configuration,
+        })
+    }
+}
+
+impl CampaignTag {
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("campaign_target", self.target.clone());
+        metadata.insert_file("campaign_beam", self.beam.clone());
+        if !self.label.is_empty() {
+            metadata.insert_file("campaign_label", self.label.clone());
+        }
+        metadata.insert_file("campaign_configuration", self.configuration.to_string());
+    }
+}
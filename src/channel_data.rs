@@ -1,54 +1,316 @@
-use super::channel_map::{ChannelMap, ChannelType};
+use super::channel_map::{ChannelMap, ChannelType, DetectorGroup};
+use super::column_naming::ColumnNaming;
 #[allow(unused_imports)]
-use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData, HitConditions};
+use super::efficiency::{get_weight_field, EfficiencyEntry};
+use super::energy_calibration::{EnergyCalibration, EnergyCalibrationEntry};
+use super::energy_rank::{EnergyRankParams, EnergyRankedColumns};
+use super::event_filter::{EventFilterEntry, EventFilters};
+use super::focal_plane::{FocalPlaneHits, SpsParams};
+use super::invalid_values::{ColumnClass, InvalidValues, INVALID_VALUE_KEY};
+use super::multi_hit::{MultiHitEntry, MultiHitPolicies};
+use super::parquet_writer::ParquetMetadata;
+use super::passthrough::{PassthroughColumns, PassthroughEntry};
 use super::used_size::UsedSize;
 use std::collections::BTreeMap;
 use std::hash::Hash;
 
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
-use strum_macros::{AsRefStr, EnumCount, EnumIter};
+use strum_macros::{AsRefStr, EnumIter};
 
 use polars::prelude::*;
 
-const INVALID_VALUE: f64 = -1.0e6;
+pub const INVALID_VALUE: f64 = -1.0e6;
 
-#[derive(Debug, Clone, Hash, Eq, PartialOrd, Ord, PartialEq, EnumIter, EnumCount, AsRefStr)]
+//An f64 holds every integer of up to 15 digits exactly (2^53 has 16), so a HitOrder with more
+//digits, more than 7 detectors fired with two digits each, is written as the invalid value
+const HIT_ORDER_MAX_DIGITS: usize = 15;
+
+//Unit of the Time columns in the output. Event building itself always works in ns.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Default, AsRefStr, EnumIter, Serialize, Deserialize,
+)]
+pub enum TimeUnit {
+    #[strum(serialize = "ps")]
+    Picoseconds,
+    #[default]
+    #[strum(serialize = "ns")]
+    Nanoseconds,
+    #[strum(serialize = "us")]
+    Microseconds,
+}
+
+impl TimeUnit {
+    pub fn convert_nanoseconds(&self, time: f64) -> f64 {
+        match self {
+            TimeUnit::Picoseconds => time * 1.0e3,
+            TimeUnit::Nanoseconds => time,
+            TimeUnit::Microseconds => time * 1.0e-3,
+        }
+    }
+
+    //A time given in this unit, e.g. a coincidence window, in ns
+    pub fn get_nanoseconds(&self, time: f64) -> f64 {
+        match self {
+            TimeUnit::Picoseconds => time * 1.0e-3,
+            TimeUnit::Nanoseconds => time,
+            TimeUnit::Microseconds => time * 1.0e3,
+        }
+    }
+}
+
+//Variants with a detector number are one column per detector of the channel map, named
+//Cebra<n><variant suffix>. The derived order (all energies, then all shorts, ...) is the column
+//order.
+#[derive(Debug, Clone, Hash, Eq, PartialOrd, Ord, PartialEq, EnumIter, AsRefStr)]
 pub enum ChannelDataField {
-    Cebra0Energy,
-    Cebra1Energy,
-    Cebra2Energy,
-    Cebra3Energy,
-    Cebra4Energy,
-    Cebra5Energy,
-    Cebra6Energy,
-    Cebra7Energy,
-    Cebra8Energy,
-
-    Cebra0Short,
-    Cebra1Short,
-    Cebra2Short,
-    Cebra3Short,
-    Cebra4Short,
-    Cebra5Short,
-    Cebra6Short,
-    Cebra7Short,
-    Cebra8Short,
-
-    Cebra0Time,
-    Cebra1Time,
-    Cebra2Time,
-    Cebra3Time,
-    Cebra4Time,
-    Cebra5Time,
-    Cebra6Time,
-    Cebra7Time,
-    Cebra8Time,
+    CebraEnergy(usize),
+    CebraShort(usize),
+    CebraTime(usize),
+
+    //Only present when the raw times are kept: the time before time shifts and jitter
+    CebraRawTime(usize),
+
+    //Only present when an efficiency curve is given for the detector
+    CebraEffWeight(usize),
+
+    //Only present when an energy calibration is given, in keV
+    CebraEnergyCal(usize),
+
+    //Only present when PSD columns are written: (energy - short) / energy
+    CebraPsd(usize),
+
+    //Only present when waveforms are reduced to derived quantities
+    CebraBaseline(usize),
+    CebraAmplitude(usize),
+    CebraRiseTime(usize),
+
+    //Hits of the detector in the event; above 1, only the last one is in its other columns
+    CebraHits(usize),
+
+    //Only present when flag columns are written: the HitConditions of the hit in the columns
+    CebraFlags(usize),
+
+    HitOrder,
+    //Hits in the event, mapped or not, and those of them in CeBrA detectors
+    Multiplicity,
+    CebraMultiplicity,
+
+    //Only present when flag columns are written: the HitConditions of every hit of the event, mapped
+    //or not, combined
+    EventFlags,
+
+    //Only present when the events are split by class: 1 for a coincidence (two or more hits, or a
+    //hit of the trigger channel), 0 for a single
+    Coincidence,
+
+    //Only present when an RF channel is mapped: time of the event from the last RF hit
+    TimeToRF,
+
+    //Only present when event time columns are written: time of the first hit from that of the
+    //event built before, and its wall-clock time in seconds since 1970-01-01 UTC
+    TimeSincePrevious,
+    WallClock,
+
+    //Only present when building with a delayed window
+    EventID,
+    PromptEventID,
+    PromptDelayTime,
+
+    //Only present when SPS focal plane channels are in the channel map: the hit of each channel,
+    //then the position from the delay lines
+    ScintLeftEnergy,
+    ScintLeftShort,
+    ScintLeftTime,
+    ScintRightEnergy,
+    ScintRightShort,
+    ScintRightTime,
+    AnodeFrontEnergy,
+    AnodeFrontShort,
+    AnodeFrontTime,
+    AnodeBackEnergy,
+    AnodeBackShort,
+    AnodeBackTime,
+    CathodeEnergy,
+    CathodeShort,
+    CathodeTime,
+    DelayFrontLeftEnergy,
+    DelayFrontLeftShort,
+    DelayFrontLeftTime,
+    DelayFrontRightEnergy,
+    DelayFrontRightShort,
+    DelayFrontRightTime,
+    DelayBackLeftEnergy,
+    DelayBackLeftShort,
+    DelayBackLeftTime,
+    DelayBackRightEnergy,
+    DelayBackRightShort,
+    DelayBackRightTime,
+    X1,
+    X2,
+    Xavg,
+    StateId,
 }
 
 impl ChannelDataField {
-    //Returns a list of fields for iterating over
-    pub fn get_field_vec() -> Vec<ChannelDataField> {
-        ChannelDataField::iter().collect()
+    //Returns a list of fields for iterating over, with the detector fields of every detector
+    pub fn get_field_vec(detector_count: usize) -> Vec<ChannelDataField> {
+        ChannelDataField::iter()
+            .flat_map(|field| match field.get_detector() {
+                Some(_) => (0..detector_count)
+                    .map(|detector| field.with_detector(detector))
+                    .collect(),
+                None => vec![field],
+            })
+            .collect()
+    }
+
+    pub fn get_detector(&self) -> Option<usize> {
+        match self {
+            ChannelDataField::CebraEnergy(detector)
+            | ChannelDataField::CebraShort(detector)
+            | ChannelDataField::CebraTime(detector)
+            | ChannelDataField::CebraRawTime(detector)
+            | ChannelDataField::CebraEffWeight(detector)
+            | ChannelDataField::CebraEnergyCal(detector)
+            | ChannelDataField::CebraPsd(detector)
+            | ChannelDataField::CebraBaseline(detector)
+            | ChannelDataField::CebraAmplitude(detector)
+            | ChannelDataField::CebraRiseTime(detector)
+            | ChannelDataField::CebraHits(detector)
+            | ChannelDataField::CebraFlags(detector) => Some(*detector),
+            _ => None,
+        }
+    }
+
+    //The same kind of field for another detector, fields without one are returned as they are
+    fn with_detector(&self, detector: usize) -> ChannelDataField {
+        match self {
+            ChannelDataField::CebraEnergy(_) => ChannelDataField::CebraEnergy(detector),
+            ChannelDataField::CebraShort(_) => ChannelDataField::CebraShort(detector),
+            ChannelDataField::CebraTime(_) => ChannelDataField::CebraTime(detector),
+            ChannelDataField::CebraRawTime(_) => ChannelDataField::CebraRawTime(detector),
+            ChannelDataField::CebraEffWeight(_) => ChannelDataField::CebraEffWeight(detector),
+            ChannelDataField::CebraEnergyCal(_) => ChannelDataField::CebraEnergyCal(detector),
+            ChannelDataField::CebraPsd(_) => ChannelDataField::CebraPsd(detector),
+            ChannelDataField::CebraBaseline(_) => ChannelDataField::CebraBaseline(detector),
+            ChannelDataField::CebraAmplitude(_) => ChannelDataField::CebraAmplitude(detector),
+            ChannelDataField::CebraRiseTime(_) => ChannelDataField::CebraRiseTime(detector),
+            ChannelDataField::CebraHits(_) => ChannelDataField::CebraHits(detector),
+            ChannelDataField::CebraFlags(_) => ChannelDataField::CebraFlags(detector),
+            _ => self.clone(),
+        }
+    }
+
+    //Column name in the output
+    pub fn get_name(&self) -> String {
+        match self.get_detector() {
+            Some(detector) => format!(
+                "Cebra{}{}",
+                detector,
+                self.as_ref().trim_start_matches("Cebra")
+            ),
+            None => self.as_ref().to_string(),
+        }
+    }
+
+    //The field of a column of this name, for any number of detectors
+    pub fn from_name(name: &str) -> Option<ChannelDataField> {
+        ChannelDataField::iter().find_map(|field| match field.get_detector() {
+            Some(_) => name
+                .strip_prefix("Cebra")
+                .and_then(|rest| rest.strip_suffix(field.as_ref().trim_start_matches("Cebra")))
+                .filter(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+                .and_then(|number| number.parse().ok())
+                .map(|detector| field.with_detector(detector)),
+            None => Some(field).filter(|field| field.as_ref() == name),
+        })
+    }
+
+    //Whether a column of this name could be one of the fields, for any number of detectors
+    pub fn is_field_name(name: &str) -> bool {
+        ChannelDataField::from_name(name).is_some()
+    }
+
+    pub fn is_time(&self) -> bool {
+        self.as_ref().ends_with("Time")
+            || matches!(
+                self,
+                ChannelDataField::TimeToRF | ChannelDataField::TimeSincePrevious
+            )
+    }
+
+    //Energy columns are the raw, short and calibrated energies of the detectors and focal plane
+    //channels
+    pub fn get_class(&self) -> ColumnClass {
+        if self.is_time() || *self == ChannelDataField::WallClock {
+            ColumnClass::Time
+        } else if matches!(self, ChannelDataField::CebraEnergyCal(_))
+            || self.as_ref().ends_with("Energy")
+            || self.as_ref().ends_with("Short")
+        {
+            ColumnClass::Energy
+        } else {
+            ColumnClass::Other
+        }
+    }
+
+    //The CeBrA energies, not those of the focal plane channels
+    pub fn is_energy(&self) -> bool {
+        matches!(self, ChannelDataField::CebraEnergy(_))
+    }
+
+    pub fn is_eff_weight(&self) -> bool {
+        self.as_ref().ends_with("EffWeight")
+    }
+
+    pub fn is_focal_plane(&self) -> bool {
+        ChannelType::get_types(0, 0)
+            .into_iter()
+            .filter_map(get_focal_plane_fields)
+            .any(|fields| fields.contains(self))
+            || matches!(
+                self,
+                ChannelDataField::X1
+                    | ChannelDataField::X2
+                    | ChannelDataField::Xavg
+                    | ChannelDataField::StateId
+            )
+    }
+
+    pub fn is_waveform(&self) -> bool {
+        matches!(
+            self,
+            ChannelDataField::CebraBaseline(_)
+                | ChannelDataField::CebraAmplitude(_)
+                | ChannelDataField::CebraRiseTime(_)
+        )
+    }
+
+    //Optional fields are only added to ChannelData when the feature filling them is enabled
+    pub fn is_optional(&self) -> bool {
+        self.is_eff_weight()
+            || self.is_waveform()
+            || matches!(
+                self,
+                ChannelDataField::CebraEnergyCal(_)
+                    | ChannelDataField::CebraRawTime(_)
+                    | ChannelDataField::CebraPsd(_)
+                    | ChannelDataField::CebraFlags(_)
+                    | ChannelDataField::EventFlags
+                    | ChannelDataField::Coincidence
+            )
+            || matches!(
+                self,
+                ChannelDataField::TimeToRF
+                    | ChannelDataField::TimeSincePrevious
+                    | ChannelDataField::WallClock
+                    | ChannelDataField::EventID
+                    | ChannelDataField::PromptEventID
+                    | ChannelDataField::PromptDelayTime
+            )
+            || self.is_focal_plane()
     }
 }
 
@@ -58,34 +320,460 @@ impl UsedSize for ChannelDataField {
     }
 }
 
+//Summed energy and multiplicity of a detector group, named by the user so they cannot be
+//ChannelDataFields. They come after the fields, in the order of the groups.
+#[derive(Debug, Clone)]
+pub struct GroupColumns {
+    pub group: DetectorGroup,
+    pub energy_name: String,
+    pub multiplicity_name: String,
+    pub energy: Vec<f64>,
+    pub multiplicity: Vec<f64>,
+}
+
+impl GroupColumns {
+    fn new(group: &DetectorGroup) -> Self {
+        GroupColumns {
+            group: group.clone(),
+            energy_name: group.get_energy_column(),
+            multiplicity_name: group.get_multiplicity_column(),
+            energy: vec![],
+            multiplicity: vec![],
+        }
+    }
+
+    fn append_event(&mut self, event: &[CompassData], map: &ChannelMap) {
+        let mut energy = 0.0;
+        let mut multiplicity = 0;
+        for hit in event.iter() {
+            let is_member = map
+                .get_channel_data(&hit.uuid)
+                .is_some_and(|data| self.group.detectors.contains(&data.channel_type));
+            if is_member {
+                //Hits with a nulled energy still count towards the multiplicity
+                if hit.energy != INVALID_VALUE {
+                    energy += hit.energy;
+                }
+                multiplicity += 1;
+            }
+        }
+        self.energy.push(if multiplicity > 0 {
+            energy
+        } else {
+            INVALID_VALUE
+        });
+        self.multiplicity.push(multiplicity as f64);
+    }
+}
+
+//Fraction of the charge outside the short gate. Hits without a positive energy, nulled ones
+//included, have none.
+fn get_psd(hit: &CompassData) -> Option<f64> {
+    if hit.energy <= 0.0 || hit.energy_short == INVALID_VALUE {
+        return None;
+    }
+    Some((hit.energy - hit.energy_short) / hit.energy)
+}
+
+//Energy, short and time columns of an SPS focal plane channel
+fn get_focal_plane_fields(channel_type: ChannelType) -> Option<[ChannelDataField; 3]> {
+    let fields = match channel_type {
+        ChannelType::ScintLeft => [
+            ChannelDataField::ScintLeftEnergy,
+            ChannelDataField::ScintLeftShort,
+            ChannelDataField::ScintLeftTime,
+        ],
+        ChannelType::ScintRight => [
+            ChannelDataField::ScintRightEnergy,
+            ChannelDataField::ScintRightShort,
+            ChannelDataField::ScintRightTime,
+        ],
+        ChannelType::AnodeFront => [
+            ChannelDataField::AnodeFrontEnergy,
+            ChannelDataField::AnodeFrontShort,
+            ChannelDataField::AnodeFrontTime,
+        ],
+        ChannelType::AnodeBack => [
+            ChannelDataField::AnodeBackEnergy,
+            ChannelDataField::AnodeBackShort,
+            ChannelDataField::AnodeBackTime,
+        ],
+        ChannelType::Cathode => [
+            ChannelDataField::CathodeEnergy,
+            ChannelDataField::CathodeShort,
+            ChannelDataField::CathodeTime,
+        ],
+        ChannelType::DelayFrontLeft => [
+            ChannelDataField::DelayFrontLeftEnergy,
+            ChannelDataField::DelayFrontLeftShort,
+            ChannelDataField::DelayFrontLeftTime,
+        ],
+        ChannelType::DelayFrontRight => [
+            ChannelDataField::DelayFrontRightEnergy,
+            ChannelDataField::DelayFrontRightShort,
+            ChannelDataField::DelayFrontRightTime,
+        ],
+        ChannelType::DelayBackLeft => [
+            ChannelDataField::DelayBackLeftEnergy,
+            ChannelDataField::DelayBackLeftShort,
+            ChannelDataField::DelayBackLeftTime,
+        ],
+        ChannelType::DelayBackRight => [
+            ChannelDataField::DelayBackRightEnergy,
+            ChannelDataField::DelayBackRightShort,
+            ChannelDataField::DelayBackRightTime,
+        ],
+        _ => return None,
+    };
+    Some(fields)
+}
+
+//Name of the list column of every hit of an expanded detector, e.g. Cebra3EnergyList
+pub fn get_list_name(field: &ChannelDataField) -> String {
+    format!("{}List", field.get_name())
+}
+
 #[derive(Debug, Clone)]
 pub struct ChannelData {
     //Columns must always come in same order, so use sorted map
     pub fields: BTreeMap<ChannelDataField, Vec<f64>>,
+    pub groups: Vec<GroupColumns>,
+    pub passthrough: Vec<PassthroughColumns>,
     pub rows: usize,
+    pub time_unit: TimeUnit,
+    pub detector_count: usize,
+    pub efficiency: BTreeMap<ChannelDataField, EfficiencyEntry>,
+    pub calibration: Option<EnergyCalibration>,
+    pub sps: Option<SpsParams>,
+    pub multi_hit: MultiHitPolicies,
+    //Every hit of the detectors with the Expand policy, one list per event, named <field>List.
+    //Only in the DataFrame, since the other outputs and iter_columns are one f64 per event.
+    pub lists: BTreeMap<ChannelDataField, Vec<Vec<f64>>>,
+    pub ranked: Option<EnergyRankedColumns>,
+    //Shift map entries of the channels of each detector, for the metadata of its Time column
+    pub time_shifts: BTreeMap<usize, String>,
+    pub filters: EventFilters,
+    //Applied to the names of the columns only when converting to series, everything before works
+    //with the default names
+    pub naming: ColumnNaming,
+    //Likewise only put in when converting to series, everything before uses INVALID_VALUE
+    pub invalid: InvalidValues,
+    //Channel type whose hits make an event a coincidence on their own, see with_event_class
+    pub trigger: Option<ChannelType>,
 }
 
+//Without any detector columns, see ChannelData::new
 impl Default for ChannelData {
     fn default() -> Self {
-        let fields = ChannelDataField::get_field_vec();
-        let mut data = ChannelData {
-            fields: BTreeMap::new(),
-            rows: 0,
-        };
-        fields.into_iter().for_each(|f| {
-            data.fields.insert(f, vec![]);
-        });
-        data
+        ChannelData::new(TimeUnit::default(), 0, &[])
     }
 }
 
 impl UsedSize for ChannelData {
     fn get_used_size(&self) -> usize {
         self.fields.get_used_size()
+            + self
+                .groups
+                .iter()
+                .map(|group| {
+                    (group.energy.capacity() + group.multiplicity.capacity())
+                        * std::mem::size_of::<f64>()
+                })
+                .sum::<usize>()
+            + self
+                .passthrough
+                .iter()
+                .map(|columns| {
+                    (columns.energy.capacity() + columns.time.capacity())
+                        * std::mem::size_of::<f64>()
+                })
+                .sum::<usize>()
+            + self
+                .lists
+                .values()
+                .flat_map(|list| list.iter())
+                .map(|hits| hits.capacity() * std::mem::size_of::<f64>())
+                .sum::<usize>()
+            + self
+                .ranked
+                .as_ref()
+                .map_or(0, |ranked| ranked.get_used_size())
     }
 }
 
 impl ChannelData {
+    //The detector columns are made for detectors 0 to detector_count - 1, usually the detector
+    //count of the channel map
+    pub fn new(
+        time_unit: TimeUnit,
+        detector_count: usize,
+        efficiency_entries: &[EfficiencyEntry],
+    ) -> Self {
+        let mut data = ChannelData {
+            fields: BTreeMap::new(),
+            groups: vec![],
+            passthrough: vec![],
+            rows: 0,
+            time_unit,
+            detector_count,
+            efficiency: BTreeMap::new(),
+            calibration: None,
+            sps: None,
+            multi_hit: MultiHitPolicies::default(),
+            lists: BTreeMap::new(),
+            ranked: None,
+            time_shifts: BTreeMap::new(),
+            filters: EventFilters::default(),
+            naming: ColumnNaming::default(),
+            invalid: InvalidValues::default(),
+            trigger: None,
+        };
+        ChannelDataField::get_field_vec(detector_count)
+            .into_iter()
+            .filter(|f| !f.is_optional())
+            .for_each(|f| {
+                data.fields.insert(f, vec![]);
+            });
+        for entry in efficiency_entries.iter() {
+            let detector = entry.detector.get_detector();
+            if detector.map_or(true, |detector| detector >= detector_count) {
+                continue;
+            }
+            if let Some(field) = get_weight_field(&entry.detector) {
+                data.fields.insert(field.clone(), vec![]);
+                data.efficiency.insert(field, entry.clone());
+            }
+        }
+        data
+    }
+
+    //Add the columns linking prompt and delayed events
+    pub fn with_event_ids(mut self) -> Self {
+        self.fields.insert(ChannelDataField::EventID, vec![]);
+        self
+    }
+
+    pub fn with_prompt_reference(mut self) -> Self {
+        self.fields.insert(ChannelDataField::PromptEventID, vec![]);
+        self.fields
+            .insert(ChannelDataField::PromptDelayTime, vec![]);
+        self
+    }
+
+    //Add the calibrated energy columns of every detector, filled for the calibrated channels
+    pub fn with_energy_calibration(mut self, entries: &[EnergyCalibrationEntry]) -> Self {
+        for detector in 0..self.detector_count {
+            self.fields
+                .insert(ChannelDataField::CebraEnergyCal(detector), vec![]);
+        }
+        self.calibration = Some(EnergyCalibration::new(entries));
+        self
+    }
+
+    //Add the waveform columns of every detector
+    pub fn with_waveforms(mut self) -> Self {
+        ChannelDataField::get_field_vec(self.detector_count)
+            .into_iter()
+            .filter(|f| f.is_waveform())
+            .for_each(|f| {
+                self.fields.insert(f, vec![]);
+            });
+        self
+    }
+
+    //Add the raw time columns of every detector, so time shifts and jitter can be checked and undone
+    pub fn with_raw_times(mut self) -> Self {
+        for detector in 0..self.detector_count {
+            self.fields
+                .insert(ChannelDataField::CebraRawTime(detector), vec![]);
+        }
+        self
+    }
+
+    //Add the column of the time since the last RF hit, filled with set_event_value
+    pub fn with_time_to_rf(mut self) -> Self {
+        self.fields.insert(ChannelDataField::TimeToRF, vec![]);
+        self
+    }
+
+    //Add the columns of the time since the previous event and the wall-clock time, filled with
+    //set_event_value
+    pub fn with_event_times(mut self) -> Self {
+        self.fields
+            .insert(ChannelDataField::TimeSincePrevious, vec![]);
+        self.fields.insert(ChannelDataField::WallClock, vec![]);
+        self
+    }
+
+    //Add the pulse shape columns of every detector, for telling particles apart by their short
+    //gate fraction
+    pub fn with_psd(mut self) -> Self {
+        for detector in 0..self.detector_count {
+            self.fields
+                .insert(ChannelDataField::CebraPsd(detector), vec![]);
+        }
+        self
+    }
+
+    //Add the flag columns of every detector and of the event, for gating on clean hits
+    pub fn with_flags(mut self) -> Self {
+        for detector in 0..self.detector_count {
+            self.fields
+                .insert(ChannelDataField::CebraFlags(detector), vec![]);
+        }
+        self.fields.insert(ChannelDataField::EventFlags, vec![]);
+        self
+    }
+
+    //Add the column telling coincidences from singles, for splitting the events by class. Events
+    //with a hit of the trigger channel are coincidences whatever their multiplicity.
+    pub fn with_event_class(mut self, trigger: Option<ChannelType>) -> Self {
+        self.fields.insert(ChannelDataField::Coincidence, vec![]);
+        self.trigger = trigger;
+        self
+    }
+
+    //Record the shifts applied to the hits of each detector in the metadata of its Time column
+    pub fn with_time_shifts(mut self, time_shifts: BTreeMap<usize, String>) -> Self {
+        self.time_shifts = time_shifts;
+        self
+    }
+
+    //Only events passing every filter are appended
+    pub fn with_event_filters(mut self, entries: &[EventFilterEntry]) -> Self {
+        self.filters = EventFilters::new(entries);
+        self
+    }
+
+    pub fn with_column_naming(mut self, naming: &ColumnNaming) -> Self {
+        self.naming = naming.clone();
+        self
+    }
+
+    pub fn with_invalid_values(mut self, invalid: &InvalidValues) -> Self {
+        self.invalid = *invalid;
+        self
+    }
+
+    pub fn with_detector_groups(mut self, groups: &[DetectorGroup]) -> Self {
+        self.groups = groups.iter().map(GroupColumns::new).collect();
+        self
+    }
+
+    //Add the energy and time columns of passthrough channels 0 to passthrough_count - 1, usually
+    //the passthrough count of the channel map
+    pub fn with_passthrough(
+        mut self,
+        passthrough_count: usize,
+        entries: &[PassthroughEntry],
+    ) -> Self {
+        self.passthrough = (0..passthrough_count)
+            .map(|number| PassthroughColumns::new(entries, number))
+            .collect();
+        self
+    }
+
+    //Add the energy and time columns of every channel the map has as None, for the Columns policy
+    //for unmapped channels
+    pub fn with_unmapped_columns(mut self, map: &ChannelMap) -> Self {
+        self.passthrough.extend(
+            map.get_channels_of_type(ChannelType::None)
+                .into_iter()
+                .map(|mapped| PassthroughColumns::unmapped(mapped.board, mapped.channel)),
+        );
+        self
+    }
+
+    //Which hit fills the columns of a channel type that fired more than once, with list columns of
+    //every hit for the expanded detectors
+    pub fn with_multi_hit(mut self, entries: &[MultiHitEntry]) -> Self {
+        self.multi_hit = MultiHitPolicies::new(entries);
+        for detector in self
+            .multi_hit
+            .get_expanded()
+            .filter_map(|channel_type| channel_type.get_detector())
+            .filter(|detector| *detector < self.detector_count)
+        {
+            for field in [
+                ChannelDataField::CebraEnergy(detector),
+                ChannelDataField::CebraShort(detector),
+                ChannelDataField::CebraTime(detector),
+            ] {
+                self.lists.insert(field, vec![]);
+            }
+        }
+        self
+    }
+
+    //Add the energy ranked columns after the group columns. Call after with_energy_calibration, the
+    //ranking uses the calibrated energies when there are any.
+    pub fn with_energy_rank(mut self, params: &EnergyRankParams) -> Self {
+        self.ranked = Some(EnergyRankedColumns::new(params, self.calibration.is_some()));
+        self
+    }
+
+    //Add the columns of every focal plane channel and the focal plane position
+    pub fn with_focal_plane(mut self, params: SpsParams) -> Self {
+        ChannelDataField::iter()
+            .filter(|f| f.is_focal_plane())
+            .for_each(|f| {
+                self.fields.insert(f, vec![]);
+            });
+        self.sps = Some(params);
+        self
+    }
+
+    fn set_focal_plane(&mut self, hits: &FocalPlaneHits) {
+        let params = match &self.sps {
+            Some(params) => params,
+            None => return,
+        };
+        let xavg = hits.get_xavg(params);
+        let state = xavg.and_then(|x| params.get_state_id(x));
+        if let Some(x1) = hits.get_x1() {
+            self.set_value(&ChannelDataField::X1, x1);
+        }
+        if let Some(x2) = hits.get_x2() {
+            self.set_value(&ChannelDataField::X2, x2);
+        }
+        if let Some(x) = xavg {
+            self.set_value(&ChannelDataField::Xavg, x);
+        }
+        if let Some(id) = state {
+            self.set_value(&ChannelDataField::StateId, id as f64);
+        }
+    }
+
+    //Value of the most recently appended event, None if the field is missing or invalid
+    pub fn get_last_value(&self, field: &ChannelDataField) -> Option<f64> {
+        self.fields
+            .get(field)
+            .and_then(|list| list.last())
+            .copied()
+            .filter(|value| *value != INVALID_VALUE)
+    }
+
+    //Set a value of the most recently appended event, for fields not derived from the hits
+    pub fn set_event_value(&mut self, field: &ChannelDataField, value: f64) {
+        self.set_value(field, value);
+    }
+
+    //Set a value of any row, for fields filled after the rows were appended
+    pub fn set_row_value(&mut self, field: &ChannelDataField, row: usize, value: f64) {
+        let value = if field.is_time() {
+            self.time_unit.convert_nanoseconds(value)
+        } else {
+            value
+        };
+        if let Some(entry) = self
+            .fields
+            .get_mut(field)
+            .and_then(|list| list.get_mut(row))
+        {
+            *entry = value;
+        }
+    }
+
     //To keep columns all same length, push invalid values as necessary
     fn push_defaults(&mut self) {
         for field in self.fields.iter_mut() {
@@ -93,10 +781,32 @@ impl ChannelData {
                 field.1.push(INVALID_VALUE)
             }
         }
+        for list in self.lists.values_mut() {
+            if list.len() < self.rows {
+                list.push(vec![])
+            }
+        }
+    }
+
+    //Add a hit to the list of the most recently appended event, if the field has one
+    fn push_list_value(&mut self, field: &ChannelDataField, value: f64) {
+        let value = if field.is_time() {
+            self.time_unit.convert_nanoseconds(value)
+        } else {
+            value
+        };
+        if let Some(back) = self.lists.get_mut(field).and_then(|list| list.last_mut()) {
+            back.push(value);
+        }
     }
 
     //Update the last element to the given value
     fn set_value(&mut self, field: &ChannelDataField, value: f64) {
+        let value = if field.is_time() {
+            self.time_unit.convert_nanoseconds(value)
+        } else {
+            value
+        };
         if let Some(list) = self.fields.get_mut(field) {
             if let Some(back) = list.last_mut() {
                 *back = value;
@@ -104,82 +814,576 @@ impl ChannelData {
         }
     }
 
-    pub fn append_event(&mut self, event: Vec<CompassData>, map: &ChannelMap) {
+    fn set_weight(&mut self, field: &ChannelDataField, energy: f64) {
+        let weight = match self.efficiency.get(field) {
+            Some(entry) => entry.weight(energy),
+            None => return,
+        };
+        if let Some(value) = weight {
+            self.set_value(field, value);
+        }
+    }
+
+    //Each detector that fired is one digit of HitOrder, or two when there are more than 9 detectors
+    fn get_hit_order_digits(&self) -> usize {
+        if self.detector_count > 9 {
+            2
+        } else {
+            1
+        }
+    }
+
+    //Encode the detectors that fired, earliest first, as the digits of one number. Each digit is
+    //the detector number + 1 so that Cebra0 can lead, e.g. Cebra3 then Cebra0 then Cebra5 -> 416.
+    //Uses the stored Time columns, so the order always agrees with them.
+    fn set_hit_order(&mut self) {
+        let mut hits: Vec<(usize, f64)> = (0..self.detector_count)
+            .filter_map(|detector| {
+                self.fields
+                    .get(&ChannelDataField::CebraTime(detector))
+                    .and_then(|list| list.last())
+                    .filter(|time| **time != INVALID_VALUE)
+                    .map(|time| (detector, *time))
+            })
+            .collect();
+        if hits.is_empty() {
+            return;
+        }
+        let digits = self.get_hit_order_digits();
+        if hits.len() * digits > HIT_ORDER_MAX_DIGITS {
+            self.set_value(&ChannelDataField::HitOrder, INVALID_VALUE);
+            return;
+        }
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let base = 10_f64.powi(digits as i32);
+        let order = hits.iter().fold(0.0, |acc, (detector, _)| {
+            acc * base + (*detector as f64 + 1.0)
+        });
+        self.set_value(&ChannelDataField::HitOrder, order);
+    }
+
+    //Whether the event passed the event filters and was appended
+    pub fn append_event(&mut self, event: &[CompassData], map: &ChannelMap) -> bool {
+        let channel_types: Vec<Option<ChannelType>> = event
+            .iter()
+            .map(|hit| {
+                map.get_channel_data(&hit.uuid)
+                    .map(|data| data.channel_type)
+            })
+            .collect();
+        if !self.filters.accept_hits(event, &channel_types) {
+            return false;
+        }
         self.rows += 1;
         self.push_defaults();
+        let mut focal_plane = FocalPlaneHits::default();
+        let mut detector_hits = vec![0usize; self.detector_count];
+        let selected = self.multi_hit.select_hits(event, &channel_types);
 
-        for hit in event.iter() {
+        for (index, hit) in event.iter().enumerate() {
             //Fill out detector fields using channel map
-            let channel_data = match map.get_channel_data(&hit.uuid) {
-                Some(data) => data,
+            let channel_type = match channel_types[index] {
+                Some(channel_type) => channel_type,
                 None => continue,
             };
-            match channel_data.channel_type {
-                ChannelType::Cebra0 => {
-                    self.set_value(&ChannelDataField::Cebra0Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra0Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra0Time, hit.timestamp);
+            if let ChannelType::Cebra(detector) = channel_type {
+                if let Some(count) = detector_hits.get_mut(detector) {
+                    *count += 1;
                 }
-
-                ChannelType::Cebra1 => {
-                    self.set_value(&ChannelDataField::Cebra1Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra1Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra1Time, hit.timestamp);
+                if !self.lists.is_empty() {
+                    self.push_list_value(&ChannelDataField::CebraEnergy(detector), hit.energy);
+                    self.push_list_value(&ChannelDataField::CebraShort(detector), hit.energy_short);
+                    self.push_list_value(&ChannelDataField::CebraTime(detector), hit.timestamp);
                 }
-
-                ChannelType::Cebra2 => {
-                    self.set_value(&ChannelDataField::Cebra2Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra2Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra2Time, hit.timestamp);
+            }
+            //Only one hit of each channel type fills its columns
+            if selected.get(&channel_type) != Some(&index) {
+                continue;
+            }
+            match channel_type {
+                ChannelType::Cebra(detector) => {
+                    self.set_value(&ChannelDataField::CebraEnergy(detector), hit.energy);
+                    self.set_value(&ChannelDataField::CebraShort(detector), hit.energy_short);
+                    self.set_value(&ChannelDataField::CebraTime(detector), hit.timestamp);
+                    self.set_value(&ChannelDataField::CebraRawTime(detector), hit.raw_timestamp);
+                    if let Some(psd) = get_psd(hit) {
+                        self.set_value(&ChannelDataField::CebraPsd(detector), psd);
+                    }
+                    self.set_value(
+                        &ChannelDataField::CebraFlags(detector),
+                        HitConditions::from_flags(hit.flags).bits() as f64,
+                    );
+                    self.set_weight(&ChannelDataField::CebraEffWeight(detector), hit.energy);
+                    //Nulled energies stay invalid
+                    let calibrated = self
+                        .calibration
+                        .as_ref()
+                        .filter(|_| hit.energy != INVALID_VALUE)
+                        .and_then(|calibration| calibration.calibrate(&hit.uuid, hit.energy));
+                    if let Some(energy) = calibrated {
+                        self.set_value(&ChannelDataField::CebraEnergyCal(detector), energy);
+                    }
+                    if let Some(waveform) = &hit.waveform {
+                        self.set_value(
+                            &ChannelDataField::CebraBaseline(detector),
+                            waveform.baseline,
+                        );
+                        self.set_value(
+                            &ChannelDataField::CebraAmplitude(detector),
+                            waveform.amplitude,
+                        );
+                        self.set_value(
+                            &ChannelDataField::CebraRiseTime(detector),
+                            waveform.rise_time,
+                        );
+                    }
                 }
 
-                ChannelType::Cebra3 => {
-                    self.set_value(&ChannelDataField::Cebra3Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra3Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra3Time, hit.timestamp);
-                }
+                ChannelType::DelayFrontLeft => focal_plane.delay_front_left = Some(hit.timestamp),
+                ChannelType::DelayFrontRight => focal_plane.delay_front_right = Some(hit.timestamp),
+                ChannelType::DelayBackLeft => focal_plane.delay_back_left = Some(hit.timestamp),
+                ChannelType::DelayBackRight => focal_plane.delay_back_right = Some(hit.timestamp),
 
-                ChannelType::Cebra4 => {
-                    self.set_value(&ChannelDataField::Cebra4Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra4Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra4Time, hit.timestamp);
-                }
+                _ => (),
+            }
+            if let Some([energy, short, time]) = get_focal_plane_fields(channel_type) {
+                self.set_value(&energy, hit.energy);
+                self.set_value(&short, hit.energy_short);
+                self.set_value(&time, hit.timestamp);
+            }
+        }
 
-                ChannelType::Cebra5 => {
-                    self.set_value(&ChannelDataField::Cebra5Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra5Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra5Time, hit.timestamp);
-                }
+        for (detector, count) in detector_hits.iter().enumerate() {
+            self.set_value(&ChannelDataField::CebraHits(detector), *count as f64);
+        }
+        self.set_value(&ChannelDataField::Multiplicity, event.len() as f64);
+        self.set_value(
+            &ChannelDataField::CebraMultiplicity,
+            detector_hits.iter().sum::<usize>() as f64,
+        );
+        if self.fields.contains_key(&ChannelDataField::EventFlags) {
+            let flags = event.iter().fold(HitConditions::empty(), |flags, hit| {
+                flags | HitConditions::from_flags(hit.flags)
+            });
+            self.set_value(&ChannelDataField::EventFlags, flags.bits() as f64);
+        }
+        if self.is_split_by_class() {
+            let is_coincidence = event.len() > 1
+                || self
+                    .trigger
+                    .is_some_and(|trigger| channel_types.contains(&Some(trigger)));
+            self.set_value(
+                &ChannelDataField::Coincidence,
+                f64::from(is_coincidence as u8),
+            );
+        }
+        self.set_hit_order();
+        self.set_focal_plane(&focal_plane);
+        for group in self.groups.iter_mut() {
+            group.append_event(event, map);
+        }
+        for columns in self.passthrough.iter_mut() {
+            let hit = match columns.unmapped_uuid {
+                Some(uuid) => event.iter().find(|hit| hit.uuid == uuid),
+                None => selected
+                    .get(&columns.entry.channel)
+                    .map(|index| &event[*index]),
+            };
+            columns.append_event(hit, self.time_unit);
+        }
+        if let Some(ranked) = &mut self.ranked {
+            ranked.append_event(event, map, self.calibration.as_ref(), self.time_unit);
+        }
+        if self.filters.has_column_gates()
+            && !self
+                .filters
+                .accept_row(|name| self.get_last_column_value(name))
+        {
+            self.remove_last_event();
+            return false;
+        }
+        true
+    }
 
-                ChannelType::Cebra6 => {
-                    self.set_value(&ChannelDataField::Cebra6Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra6Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra6Time, hit.timestamp);
-                }
+    //Value of a column of the most recently appended event by name, None if it is invalid
+    fn get_last_column_value(&self, name: &str) -> Option<f64> {
+        self.iter_columns()
+            .find(|(column, _)| column == name)
+            .and_then(|(_, values)| values.last().copied())
+            .filter(|value| *value != INVALID_VALUE)
+    }
 
-                ChannelType::Cebra7 => {
-                    self.set_value(&ChannelDataField::Cebra7Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra7Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra7Time, hit.timestamp);
-                }
+    //Undo the most recent append_event, for an event rejected by its filled columns
+    fn remove_last_event(&mut self) {
+        self.rows -= 1;
+        for column in self.fields.values_mut() {
+            column.truncate(self.rows);
+        }
+        for list in self.lists.values_mut() {
+            list.truncate(self.rows);
+        }
+        for group in self.groups.iter_mut() {
+            group.energy.truncate(self.rows);
+            group.multiplicity.truncate(self.rows);
+        }
+        for columns in self.passthrough.iter_mut() {
+            columns.truncate(self.rows);
+        }
+        if let Some(ranked) = &mut self.ranked {
+            for rank in ranked.ranks.iter_mut() {
+                rank.energy.truncate(self.rows);
+                rank.time.truncate(self.rows);
+                rank.id.truncate(self.rows);
+            }
+        }
+    }
 
-                ChannelType::Cebra8 => {
-                    self.set_value(&ChannelDataField::Cebra8Energy, hit.energy);
-                    self.set_value(&ChannelDataField::Cebra8Short, hit.energy_short);
-                    self.set_value(&ChannelDataField::Cebra8Time, hit.timestamp);
-                }
+    //Appends the rows of a table made from the same template, for the chunks of a parallel build
+    pub fn append_table(&mut self, other: ChannelData) {
+        self.rows += other.rows;
+        for (field, values) in other.fields {
+            if let Some(column) = self.fields.get_mut(&field) {
+                column.extend(values);
+            }
+        }
+        for (field, values) in other.lists {
+            if let Some(list) = self.lists.get_mut(&field) {
+                list.extend(values);
+            }
+        }
+        for (group, other) in self.groups.iter_mut().zip(other.groups) {
+            group.energy.extend(other.energy);
+            group.multiplicity.extend(other.multiplicity);
+        }
+        for (columns, other) in self.passthrough.iter_mut().zip(other.passthrough) {
+            columns.energy.extend(other.energy);
+            columns.time.extend(other.time);
+        }
+        if let (Some(ranked), Some(other)) = (&mut self.ranked, other.ranked) {
+            for (rank, other) in ranked.ranks.iter_mut().zip(other.ranks) {
+                rank.energy.extend(other.energy);
+                rank.time.extend(other.time);
+                rank.id.extend(other.id);
+            }
+        }
+    }
 
-                _ => continue,
+    //Keeps only the rows whose entry of keep is true, keep has one entry per row
+    pub fn retain_rows(&mut self, keep: &[bool]) {
+        fn retain<T>(values: &mut Vec<T>, keep: &[bool]) {
+            let mut keep = keep.iter();
+            values.retain(|_| keep.next().copied().unwrap_or(true));
+        }
+        self.rows = keep.iter().filter(|is_kept| **is_kept).count();
+        for column in self.fields.values_mut() {
+            retain(column, keep);
+        }
+        for list in self.lists.values_mut() {
+            retain(list, keep);
+        }
+        for group in self.groups.iter_mut() {
+            retain(&mut group.energy, keep);
+            retain(&mut group.multiplicity, keep);
+        }
+        for columns in self.passthrough.iter_mut() {
+            retain(&mut columns.energy, keep);
+            retain(&mut columns.time, keep);
+        }
+        if let Some(ranked) = &mut self.ranked {
+            for rank in ranked.ranks.iter_mut() {
+                retain(&mut rank.energy, keep);
+                retain(&mut rank.time, keep);
+                retain(&mut rank.id, keep);
             }
         }
     }
 
+    pub fn is_split_by_class(&self) -> bool {
+        self.fields.contains_key(&ChannelDataField::Coincidence)
+    }
+
+    //Moves the singles to a table of their own, leaving the coincidences. None unless the events
+    //are split by class.
+    pub fn split_singles(&mut self) -> Option<ChannelData> {
+        let is_coincidence: Vec<bool> = self
+            .fields
+            .get(&ChannelDataField::Coincidence)?
+            .iter()
+            .map(|value| *value == 1.0)
+            .collect();
+        let is_single: Vec<bool> = is_coincidence.iter().map(|value| !value).collect();
+        let mut singles = self.clone();
+        singles.retain_rows(&is_single);
+        self.retain_rows(&is_coincidence);
+        Some(singles)
+    }
+
+    pub fn get_metadata(&self) -> ParquetMetadata {
+        let mut metadata = ParquetMetadata::default();
+        metadata.insert_file("time_unit", self.time_unit.as_ref().to_string());
+        for field in self.fields.keys().filter(|field| field.is_time()) {
+            metadata.insert_column(
+                &field.get_name(),
+                "unit",
+                self.time_unit.as_ref().to_string(),
+            );
+        }
+        for field in self
+            .fields
+            .keys()
+            .filter(|field| matches!(field, ChannelDataField::CebraPsd(_)))
+        {
+            metadata.insert_column(
+                &field.get_name(),
+                "definition",
+                String::from("(energy - energy_short) / energy"),
+            );
+        }
+        if self.fields.contains_key(&ChannelDataField::WallClock) {
+            metadata.insert_column(
+                &ChannelDataField::WallClock.get_name(),
+                "unit",
+                String::from("s since 1970-01-01 UTC"),
+            );
+        }
+        for field in self.fields.keys().filter(|field| {
+            matches!(
+                field,
+                ChannelDataField::CebraFlags(_) | ChannelDataField::EventFlags
+            )
+        }) {
+            metadata.insert_column(&field.get_name(), "encoding", HitConditions::describe());
+        }
+        if self.is_split_by_class() {
+            metadata.insert_column(
+                &ChannelDataField::Coincidence.get_name(),
+                "definition",
+                match self.trigger {
+                    Some(trigger) => {
+                        format!("multiplicity >= 2 or a {} hit", String::from(trigger))
+                    }
+                    None => String::from("multiplicity >= 2"),
+                },
+            );
+        }
+        for (detector, shifts) in self.time_shifts.iter() {
+            metadata.insert_column(
+                &ChannelDataField::CebraTime(*detector).get_name(),
+                "time_shift_ns",
+                shifts.clone(),
+            );
+        }
+        metadata.insert_column(
+            &ChannelDataField::HitOrder.get_name(),
+            "encoding",
+            if self.get_hit_order_digits() > 1 {
+                String::from("detector number + 1 per two digits, earliest first")
+            } else {
+                String::from("detector number + 1 per digit, earliest first")
+            },
+        );
+        if let Some(calibration) = &self.calibration {
+            for detector in 0..self.detector_count {
+                let name = ChannelDataField::CebraEnergyCal(detector).get_name();
+                metadata.insert_column(&name, "unit", String::from("keV"));
+            }
+            metadata.insert_file("energy_calibration", calibration.describe());
+        }
+        for field in self
+            .fields
+            .keys()
+            .filter(|field| field.is_waveform() && !field.is_time())
+        {
+            metadata.insert_column(&field.get_name(), "unit", String::from("ADC channels"));
+        }
+        if let Some(params) = &self.sps {
+            for field in [
+                ChannelDataField::X1,
+                ChannelDataField::X2,
+                ChannelDataField::Xavg,
+            ] {
+                metadata.insert_column(&field.get_name(), "unit", String::from("mm"));
+            }
+            metadata.insert_column(
+                &ChannelDataField::StateId.get_name(),
+                "states",
+                params.describe_states(),
+            );
+        }
+        for (field, entry) in self.efficiency.iter() {
+            let name = field.get_name();
+            metadata.insert_column(&name, "efficiency_model", String::from("loglog"));
+            metadata.insert_column(&name, "efficiency_coefficients", entry.describe());
+        }
+        for group in self.groups.iter() {
+            for name in [&group.energy_name, &group.multiplicity_name] {
+                metadata.insert_column(name, "detectors", group.group.describe_detectors());
+            }
+        }
+        for columns in self.passthrough.iter() {
+            for name in [&columns.energy_name, &columns.time_name] {
+                metadata.insert_column(name, "channel", columns.describe_channel());
+            }
+            metadata.insert_column(
+                &columns.time_name,
+                "unit",
+                self.time_unit.as_ref().to_string(),
+            );
+        }
+        if let Some(ranked) = &self.ranked {
+            ranked.insert_metadata(&mut metadata, self.time_unit);
+        }
+        metadata.insert_file("multi_hit", self.multi_hit.describe());
+        if !self.filters.is_empty() {
+            metadata.insert_file("event_filters", self.filters.describe());
+        }
+        metadata.insert_file("invalid_values", self.invalid.describe());
+        for (name, class) in self.iter_column_classes() {
+            metadata.insert_column(&name, INVALID_VALUE_KEY, self.invalid.describe_class(class));
+        }
+        metadata.columns = std::mem::take(&mut metadata.columns)
+            .into_iter()
+            .map(|(name, entries)| (self.naming.get_column_name(&name), entries))
+            .collect();
+        for field in self.lists.keys().filter(|field| field.is_time()) {
+            metadata.insert_column(
+                &self.naming.get_list_name(field),
+                "unit",
+                self.time_unit.as_ref().to_string(),
+            );
+        }
+        metadata
+    }
+
+    //Every column in output order: the fields, then the detector group columns, then the
+    //passthrough columns, then the energy ranked columns
+    pub fn iter_columns(&self) -> impl Iterator<Item = (String, &Vec<f64>)> {
+        self.fields
+            .iter()
+            .map(|(field, column)| (field.get_name(), column))
+            .chain(self.groups.iter().flat_map(|group| {
+                [
+                    (group.energy_name.clone(), &group.energy),
+                    (group.multiplicity_name.clone(), &group.multiplicity),
+                ]
+            }))
+            .chain(self.passthrough.iter().flat_map(|columns| {
+                [
+                    (columns.energy_name.clone(), &columns.energy),
+                    (columns.time_name.clone(), &columns.time),
+                ]
+            }))
+            .chain(self.ranked.iter().flat_map(|ranked| ranked.iter_columns()))
+    }
+
+    //Class of every column, in the order of iter_columns
+    pub fn iter_column_classes(&self) -> impl Iterator<Item = (String, ColumnClass)> + '_ {
+        self.fields
+            .keys()
+            .map(|field| (field.get_name(), field.get_class()))
+            .chain(self.groups.iter().flat_map(|group| {
+                [
+                    (group.energy_name.clone(), ColumnClass::Energy),
+                    (group.multiplicity_name.clone(), ColumnClass::Other),
+                ]
+            }))
+            .chain(self.passthrough.iter().flat_map(|columns| {
+                [
+                    (columns.energy_name.clone(), ColumnClass::Energy),
+                    (columns.time_name.clone(), ColumnClass::Time),
+                ]
+            }))
+            .chain(self.ranked.iter().flat_map(|ranked| {
+                ranked.ranks.iter().flat_map(|rank| {
+                    [
+                        (rank.energy_name.clone(), ColumnClass::Energy),
+                        (rank.time_name.clone(), ColumnClass::Time),
+                        (rank.id_name.clone(), ColumnClass::Other),
+                    ]
+                })
+            }))
+    }
+
+    //The invalid value of a column by its name in the output
+    pub fn get_invalid_value(&self, name: &str) -> f64 {
+        self.iter_column_classes()
+            .find(|(column, _)| self.naming.get_column_name(column) == name)
+            .map_or(INVALID_VALUE, |(_, class)| self.invalid.get_fill(class))
+    }
+
+    //Of every column, in the order of iter_columns, for outputs that cannot hold nulls
+    pub fn get_invalid_values(&self) -> Vec<f64> {
+        self.iter_column_classes()
+            .map(|(_, class)| self.invalid.get_fill(class))
+            .collect()
+    }
+
+    pub fn get_field_names(&self) -> Vec<String> {
+        self.iter_columns().map(|(name, _)| name).collect()
+    }
+
+    //Columns in output order, named by the naming scheme and with the invalid value of their class,
+    //or nulls
     pub fn convert_to_series(self) -> Vec<Series> {
-        let sps_cols: Vec<Series> = self
+        let naming = self.naming;
+        let invalid = self.invalid;
+        let mut sps_cols: Vec<Series> = self
             .fields
             .into_iter()
-            .map(|field| -> Series { Series::new(field.0.as_ref(), field.1) })
+            .map(|field| -> Series {
+                invalid.get_series(
+                    &naming.get_field_name(&field.0),
+                    field.0.get_class(),
+                    field.1,
+                )
+            })
             .collect();
+        for group in self.groups.into_iter() {
+            sps_cols.push(invalid.get_series(
+                &naming.get_column_name(&group.energy_name),
+                ColumnClass::Energy,
+                group.energy,
+            ));
+            sps_cols.push(Series::new(
+                &naming.get_column_name(&group.multiplicity_name),
+                group.multiplicity,
+            ));
+        }
+        for columns in self.passthrough.into_iter() {
+            sps_cols.push(invalid.get_series(
+                &naming.get_column_name(&columns.energy_name),
+                ColumnClass::Energy,
+                columns.energy,
+            ));
+            sps_cols.push(invalid.get_series(
+                &naming.get_column_name(&columns.time_name),
+                ColumnClass::Time,
+                columns.time,
+            ));
+        }
+        if let Some(ranked) = self.ranked {
+            for rank in ranked.ranks.into_iter() {
+                sps_cols.push(invalid.get_series(
+                    &naming.get_column_name(&rank.energy_name),
+                    ColumnClass::Energy,
+                    rank.energy,
+                ));
+                sps_cols.push(invalid.get_series(
+                    &naming.get_column_name(&rank.time_name),
+                    ColumnClass::Time,
+                    rank.time,
+                ));
+                sps_cols.push(invalid.get_series(
+                    &naming.get_column_name(&rank.id_name),
+                    ColumnClass::Other,
+                    rank.id,
+                ));
+            }
+        }
+        for (field, list) in self.lists.into_iter() {
+            let events: Vec<Series> = list.into_iter().map(|hits| Series::new("", hits)).collect();
+            sps_cols.push(Series::new(&naming.get_list_name(&field), events));
+        }
 
         sps_cols
     }
@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use super::channel_map::{Board, ChannelMap, ChannelType};
+use super::compass_data::decompose_uuid_to_board_channel;
+use super::compass_file::{group_segments, is_time_merged, CompassHits};
+use super::error::EVBError;
+use super::run_archive::unpack_run_archive;
+
+//A board/channel that has data in a run, for assigning channel types without guessing which inputs
+//were cabled
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredChannel {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub hits: u64,
+}
+
+impl DiscoveredChannel {
+    //None for boards not in the channel map
+    pub fn get_channel_type(&self, boards: &[Board]) -> ChannelType {
+        boards
+            .get(self.board_number as usize)
+            .and_then(|board| board.channels.get(self.channel_number as usize))
+            .copied()
+            .unwrap_or(ChannelType::None)
+    }
+
+    //Adds empty boards up to that of the channel if the map has fewer
+    pub fn set_channel_type(&self, boards: &mut Vec<Board>, channel_type: ChannelType) {
+        let board_index = self.board_number as usize;
+        if boards.len() <= board_index {
+            boards.resize_with(board_index + 1, Board::default);
+        }
+        if let Some(channel) = boards[board_index]
+            .channels
+            .get_mut(self.channel_number as usize)
+        {
+            *channel = channel_type;
+        }
+    }
+}
+
+fn is_bin_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("bin"))
+}
+
+//The channels with data in a directory of CoMPASS files, e.g. the UNFILTERED directory of a run.
+//CoMPASS writes one file per channel, so only the first hit of each is read and its hits are
+//counted from the size of the file. A time-merged file of several channels is read through instead. The energy
+//widths are those of the channel map, boards not in it are read as 16 bit.
+pub fn discover_channels(dir: &Path, map: &ChannelMap) -> Result<Vec<DiscoveredChannel>, EVBError> {
+    let paths: Vec<PathBuf> = dir
+        .read_dir()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_bin_path(path))
+        .collect();
+    let groups = group_segments(paths);
+    let mut hits: BTreeMap<u32, u64> = BTreeMap::new();
+    for segments in groups.iter() {
+        let is_merged = is_time_merged(segments, map.get_energy_widths())?;
+        let mut file = CompassHits::new(segments, map)?;
+        if is_merged {
+            for hit in file {
+                *hits.entry(hit?.uuid).or_default() += 1;
+            }
+        } else {
+            let count = file.get_number_of_hits();
+            if let Some(hit) = file.next() {
+                *hits.entry(hit?.uuid).or_default() += count;
+            }
+        }
+    }
+    Ok(hits
+        .into_iter()
+        .map(|(uuid, hits)| {
+            let (board_number, channel_number) = decompose_uuid_to_board_channel(&uuid);
+            DiscoveredChannel {
+                board_number,
+                channel_number,
+                hits,
+            }
+        })
+        .collect())
+}
+
+//The same for a run archive, unpacked into a scratch directory so a build using the unpack
+//directory of the workspace is not disturbed
+pub fn discover_run_channels(
+    archive_path: &Path,
+    map: &ChannelMap,
+) -> Result<Vec<DiscoveredChannel>, EVBError> {
+    let unpack_dir =
+        std::env::temp_dir().join(format!("cebra_evb_discovery_{}", std::process::id()));
+    if unpack_dir.exists() {
+        std::fs::remove_dir_all(&unpack_dir)?;
+    }
+    std::fs::create_dir_all(&unpack_dir)?;
+    info!("Scanning {} for channels with data", archive_path.display());
+    let result = unpack_run_archive(archive_path, &unpack_dir)
+        .and_then(|_| discover_channels(&unpack_dir, map));
+    std::fs::remove_dir_all(&unpack_dir)?;
+    result
+}
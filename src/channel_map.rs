@@ -1,47 +1,354 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::num::ParseIntError;
 use strum_macros::{AsRefStr, EnumIter};
 
-use super::compass_data::generate_board_channel_uuid;
+use super::compass_data::{
+    decompose_uuid_to_board_channel, generate_board_channel_uuid, EnergyWidth, TimestampUnit,
+};
 
-//Channels to be mapped in the ChannelMap, each variant is the verbatim keyword in the channel map
-#[derive(Debug, Clone, Copy, PartialEq, AsRefStr, EnumIter, Serialize, Deserialize)]
+//Channels to be mapped in the ChannelMap, written in the channel map as Cebra<n> for detector n,
+//Passthrough<n> for passthrough channel n and as the variant name otherwise. Any number of CeBrA
+//detectors and passthrough channels can be mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum ChannelType {
     //Detector fields -> can be channel mapped
-    Cebra0,
-    Cebra1,
-    Cebra2,
-    Cebra3,
-    Cebra4,
-    Cebra5,
-    Cebra6,
-    Cebra7,
-    Cebra8,
-
-    // make sure to update app.rs so the channel map combo box are updated
+    Cebra(usize),
+
+    //SPS focal plane delay lines, used for the focal plane position
+    DelayFrontLeft,
+    DelayFrontRight,
+    DelayBackLeft,
+    DelayBackRight,
+
+    //The other SPS focal plane channels: the anode wires of the front and back proportional
+    //counters, both ends of the scintillator and the cathode
+    AnodeFront,
+    AnodeBack,
+    ScintLeft,
+    ScintRight,
+    Cathode,
+
+    //Pulser reference fanned into all channels, used to derive per-channel time offsets
+    TimeCalibrator,
+
+    //Counted in time bins (e.g. a beam current integrator), never part of an event
+    Scaler,
+
+    //Beam pulse reference of a pulsed beam, sets the TimeToRF of the events and is never part of
+    //one
+    Rf,
+
+    //Any other signal (a target wheel encoder, a beam pulser, ...), its energy and time copied
+    //into <name>Energy and <name>Time as they are, see PassthroughEntry
+    Passthrough(usize),
 
     //Invalid channel
     None,
 }
 
+//The channel types offered in the GUI when the channel map has fewer (or no) detectors
+pub const DEFAULT_DETECTOR_COUNT: usize = 9;
+
 impl ChannelType {
     fn default() -> Self {
         ChannelType::None // Default type
     }
+
+    //Detectors 0 to detector_count - 1, then every other type, None last
+    //Every channel type with the detectors and passthrough channels numbered from 0, None last
+    pub fn get_types(detector_count: usize, passthrough_count: usize) -> Vec<ChannelType> {
+        (0..detector_count)
+            .map(ChannelType::Cebra)
+            .chain([
+                ChannelType::DelayFrontLeft,
+                ChannelType::DelayFrontRight,
+                ChannelType::DelayBackLeft,
+                ChannelType::DelayBackRight,
+                ChannelType::AnodeFront,
+                ChannelType::AnodeBack,
+                ChannelType::ScintLeft,
+                ChannelType::ScintRight,
+                ChannelType::Cathode,
+                ChannelType::TimeCalibrator,
+                ChannelType::Scaler,
+                ChannelType::Rf,
+            ])
+            .chain((0..passthrough_count).map(ChannelType::Passthrough))
+            .chain([ChannelType::None])
+            .collect()
+    }
+
+    pub fn get_detector(&self) -> Option<usize> {
+        match self {
+            ChannelType::Cebra(detector) => Some(*detector),
+            _ => None,
+        }
+    }
+
+    pub fn get_passthrough(&self) -> Option<usize> {
+        match self {
+            ChannelType::Passthrough(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn get_group(&self) -> ChannelGroup {
+        match self {
+            ChannelType::Cebra(_) => ChannelGroup::Cebra,
+            ChannelType::DelayFrontLeft
+            | ChannelType::DelayFrontRight
+            | ChannelType::DelayBackLeft
+            | ChannelType::DelayBackRight
+            | ChannelType::AnodeFront
+            | ChannelType::AnodeBack
+            | ChannelType::ScintLeft
+            | ChannelType::ScintRight
+            | ChannelType::Cathode => ChannelGroup::Sps,
+            ChannelType::TimeCalibrator => ChannelGroup::Calibration,
+            ChannelType::Scaler => ChannelGroup::Scalers,
+            ChannelType::Rf => ChannelGroup::Beam,
+            ChannelType::Passthrough(_) => ChannelGroup::Auxiliary,
+            ChannelType::None => ChannelGroup::Unmapped,
+        }
+    }
+}
+
+//Padded like a str, so the names line up in tables
+impl std::fmt::Display for ChannelType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ChannelType::Cebra(detector) => return f.pad(&format!("Cebra{}", detector)),
+            ChannelType::Passthrough(number) => return f.pad(&format!("Passthrough{}", number)),
+            ChannelType::DelayFrontLeft => "DelayFrontLeft",
+            ChannelType::DelayFrontRight => "DelayFrontRight",
+            ChannelType::DelayBackLeft => "DelayBackLeft",
+            ChannelType::DelayBackRight => "DelayBackRight",
+            ChannelType::AnodeFront => "AnodeFront",
+            ChannelType::AnodeBack => "AnodeBack",
+            ChannelType::ScintLeft => "ScintLeft",
+            ChannelType::ScintRight => "ScintRight",
+            ChannelType::Cathode => "Cathode",
+            ChannelType::TimeCalibrator => "TimeCalibrator",
+            ChannelType::Scaler => "Scaler",
+            ChannelType::Rf => "RF",
+            ChannelType::None => "None",
+        };
+        f.pad(name)
+    }
+}
+
+impl TryFrom<String> for ChannelType {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let get_number = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .filter(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+                .map(|number| {
+                    number
+                        .parse::<usize>()
+                        .map_err(|_| format!("number of {} is too large", name))
+                })
+        };
+        if let Some(detector) = get_number("Cebra") {
+            return detector.map(ChannelType::Cebra);
+        }
+        if let Some(number) = get_number("Passthrough") {
+            return number.map(ChannelType::Passthrough);
+        }
+        ChannelType::get_types(0, 0)
+            .into_iter()
+            .find(|channel_type| channel_type.to_string() == name)
+            .ok_or_else(|| format!("unknown channel type {}", name))
+    }
+}
+
+impl From<ChannelType> for String {
+    fn from(channel_type: ChannelType) -> Self {
+        channel_type.to_string()
+    }
+}
+
+//Detectors are numbered from 0, so a map up to Cebra<n> has n + 1 detectors even if some numbers
+//are not mapped
+pub fn get_detector_count(boards: &[Board]) -> usize {
+    boards
+        .iter()
+        .flat_map(|board| board.channels.iter())
+        .filter_map(|channel| channel.get_detector())
+        .map(|detector| detector + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+//Passthrough channels are numbered from 0 like the detectors
+pub fn get_passthrough_count(boards: &[Board]) -> usize {
+    boards
+        .iter()
+        .flat_map(|board| board.channels.iter())
+        .filter_map(|channel| channel.get_passthrough())
+        .map(|number| number + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+//Detectors offered in the channel and detector selections of the GUI: at least the default
+//number, and always one more than the map has so the array can grow. The same for the passthrough
+//channels, which go before None.
+pub fn get_selectable_types(boards: &[Board]) -> Vec<ChannelType> {
+    ChannelType::get_types(
+        (get_detector_count(boards) + 1).max(DEFAULT_DETECTOR_COUNT),
+        get_passthrough_count(boards) + 1,
+    )
+}
+
+//Subsystems of the setup, for summaries that should read at the physics level rather than per
+//channel. Scalers are either channels mapped as Scaler or files identified by the scaler list.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    AsRefStr,
+    EnumIter,
+    Serialize,
+    Deserialize,
+)]
+pub enum ChannelGroup {
+    #[strum(serialize = "CeBrA")]
+    Cebra,
+    #[strum(serialize = "SPS")]
+    Sps,
+    Beam,
+    Calibration,
+    Auxiliary,
+    Unmapped,
+    Scalers,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Board {
     pub channels: [ChannelType; 16], // Each board has 16 channels
+    #[serde(default)]
+    pub energy_width: EnergyWidth,
+    #[serde(default)]
+    pub timestamp_unit: TimestampUnit,
 }
 
 impl Default for Board {
     fn default() -> Self {
         Board {
             channels: [ChannelType::default(); 16], // Initialize all channels with the default type
+            energy_width: EnergyWidth::default(),
+            timestamp_unit: TimestampUnit::default(),
+        }
+    }
+}
+
+//Detectors whose energies are summed into <name>Energy, with the number of their hits in
+//<name>Mult, e.g. the crystals of a clover. Any set of detectors can form a group, and a detector
+//can be in more than one group.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct DetectorGroup {
+    pub name: String,
+    pub detectors: Vec<ChannelType>,
+}
+
+impl DetectorGroup {
+    pub fn get_energy_column(&self) -> String {
+        format!("{}Energy", self.name)
+    }
+
+    pub fn get_multiplicity_column(&self) -> String {
+        format!("{}Mult", self.name)
+    }
+
+    pub fn describe_detectors(&self) -> String {
+        self.detectors
+            .iter()
+            .map(|detector| detector.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+//A board/channel assigned different detectors by two channel maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelConflict {
+    pub board: usize,
+    pub channel: usize,
+    pub current: ChannelType,
+    pub imported: ChannelType,
+    pub use_imported: bool,
+}
+
+pub fn find_channel_conflicts(current: &[Board], imported: &[Board]) -> Vec<ChannelConflict> {
+    let mut conflicts = vec![];
+    for (board_index, (current_board, imported_board)) in
+        current.iter().zip(imported.iter()).enumerate()
+    {
+        for (channel_index, (current_type, imported_type)) in current_board
+            .channels
+            .iter()
+            .zip(imported_board.channels.iter())
+            .enumerate()
+        {
+            if *current_type != ChannelType::None
+                && *imported_type != ChannelType::None
+                && current_type != imported_type
+            {
+                conflicts.push(ChannelConflict {
+                    board: board_index,
+                    channel: channel_index,
+                    current: *current_type,
+                    imported: *imported_type,
+                    use_imported: false,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+//Channels only mapped on one side are always taken, the conflicts decide the rest
+pub fn merge_channel_maps(
+    current: &[Board],
+    imported: &[Board],
+    conflicts: &[ChannelConflict],
+) -> Vec<Board> {
+    let board_count = current.len().max(imported.len());
+    let mut merged: Vec<Board> = Vec::with_capacity(board_count);
+    for board_index in 0..board_count {
+        //A board only in the imported map keeps its energy width too
+        let mut board = current
+            .get(board_index)
+            .or(imported.get(board_index))
+            .cloned()
+            .unwrap_or_default();
+        if let Some(imported_board) = imported.get(board_index) {
+            for (channel, imported_type) in board
+                .channels
+                .iter_mut()
+                .zip(imported_board.channels.iter())
+            {
+                if *channel == ChannelType::None {
+                    *channel = *imported_type;
+                }
+            }
         }
+        merged.push(board);
+    }
+
+    for conflict in conflicts.iter().filter(|c| c.use_imported) {
+        merged[conflict.board].channels[conflict.channel] = conflict.imported;
     }
+    merged
 }
 
 #[derive(Debug)]
@@ -94,15 +401,46 @@ impl Default for ChannelData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MappedChannel {
+    pub board: u32,
+    pub channel: u32,
+}
+
+//The channels serving one detector, for reading the map by detector rather than by board
+#[derive(Debug, Clone, Serialize)]
+pub struct DetectorChannels {
+    pub detector: ChannelType,
+    pub channels: Vec<MappedChannel>,
+}
+
+impl DetectorChannels {
+    pub fn describe_channels(&self) -> String {
+        self.channels
+            .iter()
+            .map(|mapped| format!("{}/{}", mapped.board, mapped.channel))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ChannelMap {
     map: HashMap<u32, ChannelData>,
+    energy_widths: Vec<EnergyWidth>,
+    timestamp_units: Vec<TimestampUnit>,
+    detector_count: usize,
+    passthrough_count: usize,
 }
 
 impl ChannelMap {
     pub fn new(boards: &[Board]) -> ChannelMap {
         let mut cmap = ChannelMap {
             map: HashMap::new(),
+            energy_widths: boards.iter().map(|board| board.energy_width).collect(),
+            timestamp_units: boards.iter().map(|board| board.timestamp_unit).collect(),
+            detector_count: get_detector_count(boards),
+            passthrough_count: get_passthrough_count(boards),
         };
         for (board_index, board) in boards.iter().enumerate() {
             for (channel_index, channel) in board.channels.iter().enumerate() {
@@ -122,4 +460,128 @@ impl ChannelMap {
     pub fn get_channel_data(&self, uuid: &u32) -> Option<&ChannelData> {
         return self.map.get(uuid);
     }
+
+    //Indexed by board number, boards not in the map use the standard width
+    pub fn get_energy_widths(&self) -> &[EnergyWidth] {
+        &self.energy_widths
+    }
+
+    //Indexed by board number, boards not in the map are in ps
+    pub fn get_timestamp_units(&self) -> &[TimestampUnit] {
+        &self.timestamp_units
+    }
+
+    //Number of CeBrA detectors, which sets the detector columns of the output
+    pub fn get_detector_count(&self) -> usize {
+        self.detector_count
+    }
+
+    //Number of passthrough channels, which sets the passthrough columns of the output
+    pub fn get_passthrough_count(&self) -> usize {
+        self.passthrough_count
+    }
+
+    pub fn has_channel_type(&self, channel_type: ChannelType) -> bool {
+        self.map
+            .values()
+            .any(|data| data.channel_type == channel_type)
+    }
+
+    pub fn get_uuids_of_type(&self, channel_type: ChannelType) -> HashSet<u32> {
+        self.map
+            .iter()
+            .filter(|(_, data)| data.channel_type == channel_type)
+            .map(|(uuid, _)| *uuid)
+            .collect()
+    }
+
+    //Sorted by board, then channel
+    pub fn get_channels_of_type(&self, channel_type: ChannelType) -> Vec<MappedChannel> {
+        let mut channels: Vec<MappedChannel> = self
+            .get_uuids_of_type(channel_type)
+            .iter()
+            .map(|uuid| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                MappedChannel { board, channel }
+            })
+            .collect();
+        channels.sort();
+        channels
+    }
+
+    //Every mapped detector in ChannelType order, passthrough channels last, unmapped channels are
+    //left out
+    pub fn get_detector_channels(&self) -> Vec<DetectorChannels> {
+        ChannelType::get_types(self.detector_count, self.passthrough_count)
+            .into_iter()
+            .filter(|detector| *detector != ChannelType::None)
+            .map(|detector| DetectorChannels {
+                detector,
+                channels: self.get_channels_of_type(detector),
+            })
+            .filter(|entry| !entry.channels.is_empty())
+            .collect()
+    }
+
+    //Every channel with a type, sorted by board and channel
+    pub fn get_mapped_channels(&self) -> Vec<(MappedChannel, ChannelType)> {
+        let mut mapped: Vec<(MappedChannel, ChannelType)> = self
+            .map
+            .iter()
+            .filter(|(_, data)| data.channel_type != ChannelType::None)
+            .map(|(uuid, data)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                (MappedChannel { board, channel }, data.channel_type)
+            })
+            .collect();
+        mapped.sort_by_key(|(mapped, _)| *mapped);
+        mapped
+    }
+
+    //The full mapping as a plain text table, one row per mapped channel, then the same mapping by
+    //detector
+    pub fn format_table(&self) -> String {
+        let mapped = self.get_mapped_channels();
+
+        let mut table = format!(
+            "{:<6} {:<8} {:<16} {:<8} {}\n",
+            "Board", "Channel", "Detector", "Energy", "Timestamps"
+        );
+        for (mapped, detector) in mapped.iter() {
+            let width = self
+                .energy_widths
+                .get(mapped.board as usize)
+                .copied()
+                .unwrap_or_default();
+            table.push_str(&format!(
+                "{:<6} {:<8} {:<16} {:<8} {}\n",
+                mapped.board,
+                mapped.channel,
+                detector,
+                width.as_ref(),
+                TimestampUnit::of_board(&self.timestamp_units, mapped.board).as_ref()
+            ));
+        }
+        table.push_str(&format!(
+            "{} of {} channels mapped\n\n",
+            mapped.len(),
+            self.map.len()
+        ));
+
+        table.push_str(&format!("{:<16} {}\n", "Detector", "Board/Channel"));
+        for entry in self.get_detector_channels() {
+            table.push_str(&format!(
+                "{:<16} {}\n",
+                entry.detector,
+                entry.describe_channels()
+            ));
+        }
+        table
+    }
+
+    pub fn has_sps_channels(&self) -> bool {
+        self.map
+            .values()
+            .any(|data| data.channel_type.get_group() == ChannelGroup::Sps)
+    }
 }
@@ -0,0 +1,290 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::{
+    decompose_uuid_to_board_channel, generate_board_channel_uuid, CompassData,
+};
+use super::error::EVBError;
+use super::formatting::format_count;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelRateParams {
+    pub enabled: bool,
+    //Width of the time bins (s)
+    pub interval: f64,
+    //A bin below this fraction of the median rate of the channel is reported as a drop
+    pub drop_fraction: f64,
+}
+
+impl Default for ChannelRateParams {
+    fn default() -> Self {
+        ChannelRateParams {
+            enabled: false,
+            interval: 10.0,
+            drop_fraction: 0.2,
+        }
+    }
+}
+
+//Written to the report of every run built with channel rates
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelRateSummary {
+    pub board: u32,
+    pub channel: u32,
+    pub channel_type: ChannelType,
+    pub counts: u64,
+    //Counts over the span of the run, and the median and highest rate of the full bins (Hz)
+    pub mean_rate: f64,
+    pub median_rate: f64,
+    pub peak_rate: f64,
+    //Shortest time between two hits of the channel (ns), None with fewer than two hits
+    pub min_spacing: Option<f64>,
+    //Fraction of the run the channel was dead, taking min_spacing as its dead time per hit
+    pub dead_fraction: f64,
+    //Full bins below the drop fraction of the median, and the start of the first one (s)
+    pub low_bins: usize,
+    pub first_low_time: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct ChannelRate {
+    channel_type: ChannelType,
+    counts: Vec<u64>,
+    //Of the latest hit of the current run segment
+    last_time: Option<f64>,
+    min_spacing: Option<f64>,
+}
+
+//Hits of every channel going into the event builder in time bins over the run, so a channel that
+//drops out part way through, or whose rate sags, is seen without histogramming its Time column.
+//Mapped channels are listed even when they never fire; scaler channels have their own rates.
+pub struct ChannelRates {
+    interval_ns: f64,
+    drop_fraction: f64,
+    //By uuid
+    channels: BTreeMap<u32, ChannelRate>,
+    first_time: Option<f64>,
+    last_time: f64,
+    //Summed span of the runs before the last run boundary (ns)
+    finished_span: f64,
+}
+
+impl ChannelRates {
+    pub fn new(params: &ChannelRateParams, channel_map: &ChannelMap) -> Self {
+        let channels = channel_map
+            .get_detector_channels()
+            .into_iter()
+            .filter(|detector| detector.detector != ChannelType::Scaler)
+            .flat_map(|detector| {
+                let channel_type = detector.detector;
+                detector.channels.into_iter().map(move |mapped| {
+                    (
+                        generate_board_channel_uuid(&mapped.board, &mapped.channel),
+                        ChannelRate {
+                            channel_type,
+                            counts: vec![],
+                            last_time: None,
+                            min_spacing: None,
+                        },
+                    )
+                })
+            })
+            .collect();
+        ChannelRates {
+            interval_ns: params.interval * 1.0e9,
+            drop_fraction: params.drop_fraction,
+            channels,
+            first_time: None,
+            last_time: 0.0,
+            finished_span: 0.0,
+        }
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData, channel_map: &ChannelMap) {
+        let first_time = *self.first_time.get_or_insert(hit.timestamp);
+        self.last_time = self.last_time.max(hit.timestamp);
+        let position = self.finished_span + (hit.timestamp - first_time).max(0.0);
+        let bin = (position / self.interval_ns) as usize;
+        let rate = self
+            .channels
+            .entry(hit.uuid)
+            .or_insert_with(|| ChannelRate {
+                channel_type: channel_map
+                    .get_channel_data(&hit.uuid)
+                    .map_or(ChannelType::None, |data| data.channel_type),
+                counts: vec![],
+                last_time: None,
+                min_spacing: None,
+            });
+        if bin >= rate.counts.len() {
+            rate.counts.resize(bin + 1, 0);
+        }
+        rate.counts[bin] += 1;
+        if let Some(last_time) = rate.last_time {
+            let spacing = hit.timestamp - last_time;
+            if spacing >= 0.0 && rate.min_spacing.map_or(true, |min| spacing < min) {
+                rate.min_spacing = Some(spacing);
+            }
+        }
+        rate.last_time = Some(hit.timestamp);
+    }
+
+    //The timestamps start over at a run boundary, so the runs follow each other in time and no
+    //spacing spans it
+    pub fn end_run_segment(&mut self) {
+        self.finished_span += self.get_current_span();
+        self.first_time = None;
+        self.last_time = 0.0;
+        for rate in self.channels.values_mut() {
+            rate.last_time = None;
+        }
+    }
+
+    fn get_current_span(&self) -> f64 {
+        match self.first_time {
+            Some(first) => self.last_time - first,
+            None => 0.0,
+        }
+    }
+
+    fn get_bins(&self) -> usize {
+        ((self.finished_span + self.get_current_span()) / self.interval_ns) as usize + 1
+    }
+
+    pub fn get_summary(&self) -> Vec<ChannelRateSummary> {
+        let bins = self.get_bins();
+        let interval = self.interval_ns * 1.0e-9;
+        let duration = (self.finished_span + self.get_current_span()) * 1.0e-9;
+        self.channels
+            .iter()
+            .map(|(uuid, rate)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let counts: u64 = rate.counts.iter().sum();
+                //The last bin is left out of the rates, the run usually ends inside it
+                let full_rates: Vec<f64> = (0..bins - 1)
+                    .map(|bin| rate.counts.get(bin).copied().unwrap_or(0) as f64 / interval)
+                    .collect();
+                let median_rate = get_median(&full_rates);
+                let low_bins: Vec<usize> = full_rates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| {
+                        median_rate > 0.0 && **value < self.drop_fraction * median_rate
+                    })
+                    .map(|(bin, _)| bin)
+                    .collect();
+                let mean_rate = if duration > 0.0 {
+                    counts as f64 / duration
+                } else {
+                    0.0
+                };
+                ChannelRateSummary {
+                    board,
+                    channel,
+                    channel_type: rate.channel_type,
+                    counts,
+                    mean_rate,
+                    median_rate,
+                    peak_rate: full_rates.iter().copied().fold(0.0, f64::max),
+                    min_spacing: rate.min_spacing,
+                    dead_fraction: rate
+                        .min_spacing
+                        .map_or(0.0, |spacing| (mean_rate * spacing * 1.0e-9).min(1.0)),
+                    low_bins: low_bins.len(),
+                    first_low_time: low_bins.first().map(|bin| *bin as f64 * interval),
+                }
+            })
+            .collect()
+    }
+
+    //Mapped channels without hits and channels with drops are warnings of the build, the others
+    //only go to the log
+    pub fn log_summary(&self) {
+        for summary in self.get_summary() {
+            let context = format!(
+                "board {} channel {} ({})",
+                summary.board, summary.channel, summary.channel_type
+            );
+            if summary.counts == 0 {
+                emit_warning("channel_rates", context, String::from("No hits in the run"));
+                continue;
+            }
+            match summary.first_low_time {
+                Some(time) => emit_warning(
+                    "channel_rates",
+                    context,
+                    format!(
+                        "Rate fell below {:.0}% of the median {:.2} Hz in {} bins, first at {:.0} s",
+                        100.0 * self.drop_fraction,
+                        summary.median_rate,
+                        summary.low_bins,
+                        time
+                    ),
+                ),
+                None => info!(
+                    "Board {} channel {} ({}): {} hits, {:.2} Hz, {:.2}% dead",
+                    summary.board,
+                    summary.channel,
+                    summary.channel_type,
+                    format_count(summary.counts),
+                    summary.mean_rate,
+                    100.0 * summary.dead_fraction
+                ),
+            }
+        }
+    }
+
+    //One row per time bin, the counts and the rate (Hz) of every channel. The last bin is usually
+    //only partly covered by the run, so its rate reads low.
+    pub fn write_rates(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!("Writing channel rates to {}", filepath.display());
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .channels
+            .iter()
+            .map(|(uuid, rate)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let name = format!("{}_{}_{}", rate.channel_type, board, channel);
+                format!("{}_counts,{}_rate", name, name)
+            })
+            .collect();
+        writeln!(writer, "time_low,time_high,{}", headers.join(","))?;
+
+        let interval = self.interval_ns * 1.0e-9;
+        for bin in 0..self.get_bins() {
+            let low = interval * (bin as f64);
+            let columns: Vec<String> = self
+                .channels
+                .values()
+                .map(|rate| {
+                    let count = rate.counts.get(bin).copied().unwrap_or(0);
+                    format!("{},{}", count, count as f64 / interval)
+                })
+                .collect();
+            writeln!(writer, "{},{},{}", low, low + interval, columns.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn get_median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        0.5 * (sorted[middle - 1] + sorted[middle])
+    } else {
+        sorted[middle]
+    }
+}
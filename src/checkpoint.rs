@@ -0,0 +1,250 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::checksum::Checksum;
+use super::error::EVBError;
+use super::event_builder::BuilderCheckpoint;
+use super::formatting::format_count;
+use super::hdf5_writer::get_hdf5_path;
+use super::hit_source::HitSource;
+use super::ipc_writer::get_ipc_path;
+use super::root_writer::get_root_path;
+use super::run_layout::RunLayout;
+use super::time_calibration::CampaignTimeOffsets;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointParams {
+    pub enabled: bool,
+    //Wall time between checkpoints (s)
+    pub interval: f64,
+}
+
+impl Default for CheckpointParams {
+    fn default() -> Self {
+        CheckpointParams {
+            enabled: false,
+            interval: 300.0,
+        }
+    }
+}
+
+//What a build has to share with the one that took a checkpoint to resume from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointKey {
+    pub run_number: i32,
+    //See get_config_hash, checkpoints of older builds have none and are never resumed
+    #[serde(default)]
+    pub config_hash: String,
+    //Name and size of every hit source, in the order they are merged
+    pub inputs: Vec<(String, u64)>,
+}
+
+//Settings that pick the runs to build, where they go and how they are logged, but leave the events
+//of a run as they are
+const UNKEYED_SETTINGS: [&str; 5] = [
+    "workspace",
+    "run_min",
+    "run_max",
+    "log_level",
+    "checkpoints",
+];
+
+//Checksum of the whole config a run is built with, but for the settings above, and of the campaign
+//time offsets that replace part of its shift map. Hooks and hit filters added by a program are
+//not in the config, so a program has to resume with the same ones.
+pub fn get_config_hash(config_text: &str, time_offsets: Option<&CampaignTimeOffsets>) -> String {
+    let mut config = serde_yaml::from_str(config_text).unwrap_or(serde_yaml::Value::Null);
+    if let serde_yaml::Value::Mapping(settings) = &mut config {
+        for key in UNKEYED_SETTINGS.iter() {
+            settings.remove(*key);
+        }
+    }
+    let mut checksum = Checksum::new();
+    checksum.update(
+        serde_yaml::to_string(&config)
+            .unwrap_or_default()
+            .as_bytes(),
+    );
+    if let Some(offsets) = time_offsets {
+        checksum.update(
+            serde_yaml::to_string(offsets)
+                .unwrap_or_default()
+                .as_bytes(),
+        );
+    }
+    checksum.to_hex()
+}
+
+//Where the build of a run stood when its tables were last written out. The events before it are in
+//the fragments on disk, the hits read but not yet in an event are in the open window of the
+//builder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub key: CheckpointKey,
+    //Hits taken from each source, in the order of the inputs
+    pub hits_read: Vec<u64>,
+    pub builder: BuilderCheckpoint,
+    pub events: u64,
+    pub filtered_events: u64,
+    pub events_fragments: i32,
+    pub hits_fragments: i32,
+}
+
+impl Checkpoint {
+    pub fn read(path: &Path) -> Result<Self, EVBError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    //Written next to the checkpoint and moved over it, so a build killed while writing it still
+    //has the one before
+    pub fn write(&self, path: &Path) -> Result<(), EVBError> {
+        let temporary = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        drop(writer);
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn get_hits(&self) -> u64 {
+        self.hits_read.iter().sum()
+    }
+}
+
+//Written to the report of a run resumed from a checkpoint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResumeSummary {
+    //Built before the checkpoint. The event counts of the report are of the whole run, its
+    //statistics, spectra and rates only of the hits read after the checkpoint.
+    pub hits: u64,
+    pub events: u64,
+    pub events_fragments: i32,
+}
+
+//The checkpoint of an interrupted build of the run, if it was taken with the same inputs and
+//settings. Any other checkpoint is warned about and left to be replaced.
+pub fn find_checkpoint(layout: &RunLayout, key: &CheckpointKey) -> Option<Checkpoint> {
+    let path = layout.get_checkpoint_path();
+    if !path.exists() {
+        return None;
+    }
+    match Checkpoint::read(&path) {
+        Ok(checkpoint) if checkpoint.key == *key => Some(checkpoint),
+        Ok(_) => {
+            emit_warning(
+                "checkpoint_mismatch",
+                path.display().to_string(),
+                String::from(
+                    "Checkpoint was taken with other inputs or settings, building the run from \
+                     the start",
+                ),
+            );
+            None
+        }
+        Err(e) => {
+            emit_warning(
+                "checkpoint_unreadable",
+                path.display().to_string(),
+                format!(
+                    "Could not read the checkpoint ({}), building the run from the start",
+                    e
+                ),
+            );
+            None
+        }
+    }
+}
+
+//Once a run is built to its end its checkpoint is of no more use
+pub fn remove_checkpoint(layout: &RunLayout) -> Result<(), EVBError> {
+    let path = layout.get_checkpoint_path();
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+//The parquet file of a table and its ROOT, HDF5 and Arrow IPC copies
+fn get_table_files(path: PathBuf) -> [PathBuf; 4] {
+    [
+        get_root_path(&path),
+        get_hdf5_path(&path),
+        get_ipc_path(&path),
+        path,
+    ]
+}
+
+//Tables written after the checkpoint by the interrupted build, the last one possibly unfinished,
+//are built again. Their files are removed so none is left over if the rebuild has fewer.
+pub fn remove_later_fragments(layout: &RunLayout, stem: &str, first: i32) -> Result<(), EVBError> {
+    let later = (first..)
+        .map(|fragment| get_table_files(layout.get_table_path(stem, Some(fragment))))
+        .take_while(|files| files.iter().any(|file| file.exists()));
+    let tables: Vec<[PathBuf; 4]> =
+        std::iter::once(get_table_files(layout.get_table_path(stem, None)))
+            .chain(later)
+            .collect();
+    for file in tables.iter().flatten().filter(|file| file.exists()) {
+        info!("Removing {} written after the checkpoint", file.display());
+        std::fs::remove_file(file)?;
+    }
+    Ok(())
+}
+
+//Reads past the hits each source gave before the checkpoint
+pub fn skip_read_hits(
+    files: &mut [Box<dyn HitSource + '_>],
+    hits_read: &[u64],
+) -> Result<(), EVBError> {
+    for (file, hits) in files.iter_mut().zip(hits_read.iter()) {
+        for _ in 0..*hits {
+            file.get_top_hit()?;
+            file.set_hit_used();
+        }
+    }
+    Ok(())
+}
+
+//Takes a checkpoint of the run every interval of wall time
+pub struct Checkpointer {
+    interval: f64,
+    path: PathBuf,
+    last: Instant,
+}
+
+impl Checkpointer {
+    pub fn new(params: &CheckpointParams, layout: &RunLayout) -> Self {
+        info!(
+            "Taking a checkpoint of the run every {} s at {}",
+            params.interval,
+            layout.get_checkpoint_path().display()
+        );
+        Checkpointer {
+            interval: params.interval,
+            path: layout.get_checkpoint_path(),
+            last: Instant::now(),
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last.elapsed().as_secs_f64() >= self.interval
+    }
+
+    pub fn write(&mut self, checkpoint: &Checkpoint) -> Result<(), EVBError> {
+        checkpoint.write(&self.path)?;
+        info!(
+            "Checkpoint taken at {} hits, {} events",
+            format_count(checkpoint.get_hits()),
+            format_count(checkpoint.events)
+        );
+        self.last = Instant::now();
+        Ok(())
+    }
+}
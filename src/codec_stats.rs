@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use polars::prelude::*;
+
+use super::column_codec::{ColumnCodec, ColumnCompression, ColumnEncoding};
+use super::error::EVBError;
+use super::formatting::format_bytes;
+use super::invalid_values::ColumnInvalidValues;
+use super::parquet_writer::{write_parquet, ParquetMetadata};
+
+//Rows of a table the codecs are tried on
+pub const DEFAULT_SAMPLE_ROWS: usize = 100_000;
+
+//A codec is only recommended over the default if it saves at least this fraction of the column
+const MIN_SAVING: f64 = 0.05;
+
+//Tried on every column, the default first. Lz4 and snappy are only worth it for their speed, so
+//they are kept for comparison.
+const CANDIDATES: [(ColumnEncoding, ColumnCompression, Option<i32>); 6] = [
+    (ColumnEncoding::Plain, ColumnCompression::Zstd, None),
+    (ColumnEncoding::Plain, ColumnCompression::Zstd, Some(19)),
+    (ColumnEncoding::Dictionary, ColumnCompression::Zstd, None),
+    (
+        ColumnEncoding::Dictionary,
+        ColumnCompression::Zstd,
+        Some(19),
+    ),
+    (ColumnEncoding::Plain, ColumnCompression::Lz4, None),
+    (ColumnEncoding::Plain, ColumnCompression::Snappy, None),
+];
+
+fn describe_codec(codec: &ColumnCodec) -> String {
+    match codec.level {
+        Some(level) => format!(
+            "{} {} {}",
+            codec.encoding.as_ref(),
+            codec.compression.as_ref(),
+            level
+        ),
+        None => format!("{} {}", codec.encoding.as_ref(), codec.compression.as_ref()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnCodecStats {
+    pub column: String,
+    pub distinct: usize,
+    pub invalid_fraction: f64,
+    //Shannon entropy of the values in bits per value, the size they could shrink to if the rows
+    //were independent. None for columns that are not numbers.
+    pub entropy_bits: Option<f64>,
+    //Size of the sample with the default codec, and with the best one tried
+    pub default_bytes: usize,
+    pub best: ColumnCodec,
+    pub best_bytes: usize,
+}
+
+impl ColumnCodecStats {
+    pub fn get_saving(&self) -> f64 {
+        if self.default_bytes == 0 {
+            return 0.0;
+        }
+        1.0 - self.best_bytes as f64 / self.default_bytes as f64
+    }
+
+    pub fn is_worth_changing(&self) -> bool {
+        self.get_saving() >= MIN_SAVING
+    }
+}
+
+//How well each column of a table compresses, found by writing a sample of it with every candidate
+//codec
+#[derive(Debug, Clone, Default)]
+pub struct CodecReport {
+    pub rows: usize,
+    pub columns: Vec<ColumnCodecStats>,
+}
+
+impl CodecReport {
+    //Codec entries for the columns where a codec other than the default pays off
+    pub fn get_recommended_codecs(&self) -> Vec<ColumnCodec> {
+        self.columns
+            .iter()
+            .filter(|stats| stats.is_worth_changing())
+            .map(|stats| stats.best.clone())
+            .collect()
+    }
+
+    //The recommended codecs as the column_codecs entry of a config file
+    pub fn format_recommended_config(&self) -> String {
+        let codecs = self.get_recommended_codecs();
+        if codecs.is_empty() {
+            return String::from("column_codecs: []\n");
+        }
+        let yaml = serde_yaml::to_string(&codecs).unwrap_or_default();
+        format!("column_codecs:\n{}", yaml)
+    }
+
+    pub fn get_total_bytes(&self) -> (usize, usize) {
+        self.columns.iter().fold((0, 0), |(default, best), stats| {
+            let chosen = if stats.is_worth_changing() {
+                stats.best_bytes
+            } else {
+                stats.default_bytes
+            };
+            (default + stats.default_bytes, best + chosen)
+        })
+    }
+
+    //Largest columns first, they are where the file size goes
+    pub fn format_table(&self) -> String {
+        let mut columns: Vec<&ColumnCodecStats> = self.columns.iter().collect();
+        columns.sort_by(|a, b| b.default_bytes.cmp(&a.default_bytes));
+        let mut table = format!(
+            "{:<28} {:>9} {:>8} {:>12} {:>10}  {:<20} {:>10} {:>7}\n",
+            "column", "distinct", "invalid", "bits/value", "default", "best", "size", "saving"
+        );
+        for stats in columns {
+            table.push_str(&format!(
+                "{:<28} {:>9} {:>7.1}% {:>12} {:>10}  {:<20} {:>10} {:>6.1}%\n",
+                stats.column,
+                stats.distinct,
+                100.0 * stats.invalid_fraction,
+                stats
+                    .entropy_bits
+                    .map_or(String::from("-"), |bits| format!("{:.2}", bits)),
+                format_bytes(stats.default_bytes),
+                describe_codec(&stats.best),
+                format_bytes(stats.best_bytes),
+                100.0 * stats.get_saving()
+            ));
+        }
+        let (default, best) = self.get_total_bytes();
+        table.push_str(&format!(
+            "{} rows sampled: {} with the default codecs, {} with the recommended ones\n",
+            self.rows,
+            format_bytes(default),
+            format_bytes(best)
+        ));
+        table
+    }
+}
+
+//Distinct values, fraction of invalid or null values and entropy of a numeric column
+fn get_value_stats(column: &Series, invalid_value: f64) -> (usize, f64, Option<f64>) {
+    let height = column.len().max(1) as f64;
+    let values = match column.cast(&DataType::Float64) {
+        Ok(values) if column.dtype().is_numeric() => values,
+        _ => {
+            let distinct = column.n_unique().unwrap_or_default();
+            return (distinct, column.null_count() as f64 / height, None);
+        }
+    };
+    let mut counts: HashMap<Option<u64>, usize> = HashMap::new();
+    let mut invalid: usize = 0;
+    for value in values.f64().into_iter().flatten() {
+        if value.map_or(true, |value| value == invalid_value) {
+            invalid += 1;
+        }
+        *counts.entry(value.map(f64::to_bits)).or_default() += 1;
+    }
+    let entropy = counts
+        .values()
+        .map(|count| {
+            let probability = *count as f64 / height;
+            -probability * probability.log2()
+        })
+        .sum();
+    (counts.len(), invalid as f64 / height, Some(entropy))
+}
+
+fn get_written_size(column: &Series, codec: &ColumnCodec) -> PolarsResult<usize> {
+    let mut df = DataFrame::new(vec![column.clone()])?;
+    let mut buffer: Vec<u8> = vec![];
+    write_parquet(
+        &mut buffer,
+        &mut df,
+        &ParquetMetadata::default(),
+        std::slice::from_ref(codec),
+    )?;
+    Ok(buffer.len())
+}
+
+fn analyze_column(column: &Series, invalid_value: f64) -> PolarsResult<ColumnCodecStats> {
+    let (distinct, invalid_fraction, entropy_bits) = get_value_stats(column, invalid_value);
+    let mut sizes = vec![];
+    for (encoding, compression, level) in CANDIDATES {
+        let codec = ColumnCodec {
+            column: column.name().to_string(),
+            encoding,
+            compression,
+            level,
+        };
+        let size = get_written_size(column, &codec)?;
+        sizes.push((codec, size));
+    }
+    let default_bytes = sizes[0].1;
+    //The first of the smallest, so the default wins a tie
+    let (best, best_bytes) = sizes
+        .into_iter()
+        .reduce(|best, next| if next.1 < best.1 { next } else { best })
+        .unwrap_or_default();
+    Ok(ColumnCodecStats {
+        column: column.name().to_string(),
+        distinct,
+        invalid_fraction,
+        entropy_bits,
+        default_bytes,
+        best,
+        best_bytes,
+    })
+}
+
+//Tries the codecs on the first sample_rows rows of a parquet table
+pub fn analyze_codecs(path: &Path, sample_rows: usize) -> Result<CodecReport, EVBError> {
+    let df = ParquetReader::new(File::open(path)?)
+        .with_n_rows(Some(sample_rows))
+        .finish()?;
+    let invalid = ColumnInvalidValues::read(path)?;
+    let columns = df
+        .get_columns()
+        .iter()
+        .map(|column| analyze_column(column, invalid.get(column.name())))
+        .collect::<PolarsResult<Vec<ColumnCodecStats>>>()?;
+    Ok(CodecReport {
+        rows: df.height(),
+        columns,
+    })
+}
@@ -0,0 +1,327 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::histogram::Histogram;
+use super::plot_export::write_png;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoincidenceTrendParams {
+    pub enabled: bool,
+    //Every detector is counted in coincidence with this channel type
+    pub reference: ChannelType,
+    //Width of the time bins (s)
+    pub interval: f64,
+    //Prompt gate on the detector time minus the reference time (ns)
+    pub prompt_min: f64,
+    pub prompt_max: f64,
+    //A bin below this fraction of the median rate of the detector is reported as a drop
+    pub drop_fraction: f64,
+}
+
+impl Default for CoincidenceTrendParams {
+    fn default() -> Self {
+        CoincidenceTrendParams {
+            enabled: false,
+            reference: ChannelType::ScintLeft,
+            interval: 60.0,
+            prompt_min: -100.0,
+            prompt_max: 100.0,
+            drop_fraction: 0.5,
+        }
+    }
+}
+
+//Written to the report of every run built with the coincidence trend
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectorTrendSummary {
+    pub detector: ChannelType,
+    pub coincidences: u64,
+    //Prompt coincidences per second in every bin, the last bin usually only partly covered
+    pub rates: Vec<f64>,
+    //Of the full bins (Hz)
+    pub median_rate: f64,
+    //Full bins below the drop fraction of the median, and the start of the first one (s)
+    pub low_bins: usize,
+    pub first_low_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoincidenceTrendSummary {
+    pub reference: ChannelType,
+    //s
+    pub interval: f64,
+    pub detectors: Vec<DetectorTrendSummary>,
+}
+
+struct DetectorTrend {
+    detector: ChannelType,
+    counts: Vec<u64>,
+    //Of the latest hit not yet in coincidence, it may still be with the next reference hit
+    pending_time: Option<f64>,
+}
+
+//Prompt coincidences of every detector with a reference channel in time bins over the run, so a
+//detector drifting out of the prompt gate shows up as its rate falling while the others hold. Like
+//the time differences it is taken from the hit stream: a detector hit is in coincidence if the
+//reference hit before or after it is inside the gate.
+pub struct CoincidenceTrend {
+    reference: ChannelType,
+    interval_ns: f64,
+    prompt_min: f64,
+    prompt_max: f64,
+    drop_fraction: f64,
+    last_reference_time: Option<f64>,
+    detectors: Vec<DetectorTrend>,
+    first_time: Option<f64>,
+    last_time: f64,
+    //Summed span of the runs before the last run boundary (ns)
+    finished_span: f64,
+}
+
+impl CoincidenceTrend {
+    pub fn new(params: &CoincidenceTrendParams, channel_map: &ChannelMap) -> Self {
+        let detectors = (0..channel_map.get_detector_count())
+            .map(ChannelType::Cebra)
+            .filter(|detector| {
+                *detector != params.reference && channel_map.has_channel_type(*detector)
+            })
+            .map(|detector| DetectorTrend {
+                detector,
+                counts: vec![],
+                pending_time: None,
+            })
+            .collect();
+        CoincidenceTrend {
+            reference: params.reference,
+            interval_ns: params.interval * 1.0e9,
+            prompt_min: params.prompt_min,
+            prompt_max: params.prompt_max,
+            drop_fraction: params.drop_fraction,
+            last_reference_time: None,
+            detectors,
+            first_time: None,
+            last_time: 0.0,
+            finished_span: 0.0,
+        }
+    }
+
+    fn get_bin(&self, time: f64) -> usize {
+        let first_time = self.first_time.unwrap_or(time);
+        let position = self.finished_span + (time - first_time).max(0.0);
+        (position / self.interval_ns) as usize
+    }
+
+    fn is_prompt(&self, difference: f64) -> bool {
+        difference >= self.prompt_min && difference <= self.prompt_max
+    }
+
+    fn count(detector: &mut DetectorTrend, bin: usize) {
+        if bin >= detector.counts.len() {
+            detector.counts.resize(bin + 1, 0);
+        }
+        detector.counts[bin] += 1;
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData, channel_map: &ChannelMap) {
+        let channel_type = match channel_map.get_channel_data(&hit.uuid) {
+            Some(data) => data.channel_type,
+            None => return,
+        };
+        self.first_time.get_or_insert(hit.timestamp);
+        self.last_time = self.last_time.max(hit.timestamp);
+        if channel_type == self.reference {
+            for index in 0..self.detectors.len() {
+                if let Some(time) = self.detectors[index].pending_time.take() {
+                    if self.is_prompt(time - hit.timestamp) {
+                        let bin = self.get_bin(time);
+                        Self::count(&mut self.detectors[index], bin);
+                    }
+                }
+            }
+            self.last_reference_time = Some(hit.timestamp);
+        } else if let Some(index) = self
+            .detectors
+            .iter()
+            .position(|detector| detector.detector == channel_type)
+        {
+            let is_prompt = self
+                .last_reference_time
+                .is_some_and(|reference_time| self.is_prompt(hit.timestamp - reference_time));
+            if is_prompt {
+                let bin = self.get_bin(hit.timestamp);
+                Self::count(&mut self.detectors[index], bin);
+                self.detectors[index].pending_time = None;
+            } else {
+                self.detectors[index].pending_time = Some(hit.timestamp);
+            }
+        }
+    }
+
+    //The timestamps start over at a run boundary, so the runs follow each other in time and no
+    //coincidence spans it
+    pub fn end_run_segment(&mut self) {
+        if let Some(first) = self.first_time.take() {
+            self.finished_span += self.last_time - first;
+        }
+        self.last_time = 0.0;
+        self.last_reference_time = None;
+        for detector in self.detectors.iter_mut() {
+            detector.pending_time = None;
+        }
+    }
+
+    fn get_bins(&self) -> usize {
+        let span = self.finished_span
+            + self
+                .first_time
+                .map_or(0.0, |first| (self.last_time - first).max(0.0));
+        (span / self.interval_ns) as usize + 1
+    }
+
+    pub fn get_summary(&self) -> CoincidenceTrendSummary {
+        let bins = self.get_bins();
+        let interval = self.interval_ns * 1.0e-9;
+        let detectors = self
+            .detectors
+            .iter()
+            .map(|detector| {
+                let rates: Vec<f64> = (0..bins)
+                    .map(|bin| detector.counts.get(bin).copied().unwrap_or(0) as f64 / interval)
+                    .collect();
+                //The last bin is left out of the drops, the run usually ends inside it
+                let full_rates = &rates[..bins - 1];
+                let median_rate = get_median(full_rates);
+                let low_bins: Vec<usize> = full_rates
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rate)| {
+                        median_rate > 0.0 && **rate < self.drop_fraction * median_rate
+                    })
+                    .map(|(bin, _)| bin)
+                    .collect();
+                DetectorTrendSummary {
+                    detector: detector.detector,
+                    coincidences: detector.counts.iter().sum(),
+                    rates,
+                    median_rate,
+                    low_bins: low_bins.len(),
+                    first_low_time: low_bins.first().map(|bin| *bin as f64 * interval),
+                }
+            })
+            .collect();
+        CoincidenceTrendSummary {
+            reference: self.reference,
+            interval,
+            detectors,
+        }
+    }
+
+    //A detector with drops is a warning of the build, the others only go to the log
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        for detector in summary.detectors.iter() {
+            match detector.first_low_time {
+                Some(time) => emit_warning(
+                    "coincidence_trend",
+                    detector.detector.to_string(),
+                    format!(
+                        "Prompt coincidences with {} fell below {:.0}% of the median {:.2} Hz in \
+                         {} bins, first at {:.0} s",
+                        summary.reference,
+                        100.0 * self.drop_fraction,
+                        detector.median_rate,
+                        detector.low_bins,
+                        time
+                    ),
+                ),
+                None => info!(
+                    "{} - {}: {} prompt coincidences, median {:.2} Hz",
+                    detector.detector,
+                    summary.reference,
+                    detector.coincidences,
+                    detector.median_rate
+                ),
+            }
+        }
+    }
+
+    //One row per time bin, the prompt coincidences and their rate (Hz) of every detector
+    pub fn write_trend(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!("Writing coincidence trend to {}", filepath.display());
+        let summary = self.get_summary();
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = summary
+            .detectors
+            .iter()
+            .map(|detector| format!("{}_counts,{}_rate", detector.detector, detector.detector))
+            .collect();
+        writeln!(writer, "time_low,time_high,{}", headers.join(","))?;
+        for bin in 0..self.get_bins() {
+            let low = summary.interval * (bin as f64);
+            let columns: Vec<String> = self
+                .detectors
+                .iter()
+                .zip(summary.detectors.iter())
+                .map(|(detector, trend)| {
+                    format!(
+                        "{},{}",
+                        detector.counts.get(bin).copied().unwrap_or(0),
+                        trend.rates[bin]
+                    )
+                })
+                .collect();
+            writeln!(
+                writer,
+                "{},{},{}",
+                low,
+                low + summary.interval,
+                columns.join(",")
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    //The rate against time of every detector, one image each in spectra_dir
+    pub fn write_plots(&self, spectra_dir: &Path) -> Result<(), EVBError> {
+        let summary = self.get_summary();
+        for detector in summary.detectors.iter() {
+            let histogram = Histogram {
+                title: format!(
+                    "{} - {} Prompt Coincidences",
+                    detector.detector, summary.reference
+                ),
+                x_label: String::from("Time (s)"),
+                y_label: String::from("Rate (Hz)"),
+                min: 0.0,
+                max: summary.interval * detector.rates.len() as f64,
+                counts: detector.rates.clone(),
+            };
+            let filepath = spectra_dir.join(format!("coincidence_trend_{}.png", detector.detector));
+            write_png(&histogram, &filepath, false)?;
+        }
+        Ok(())
+    }
+}
+
+fn get_median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        0.5 * (sorted[middle - 1] + sorted[middle])
+    } else {
+        sorted[middle]
+    }
+}
@@ -0,0 +1,69 @@
+use polars_parquet::write::{CompressionOptions, Encoding, ZstdLevel};
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+//zstd levels accepted by the writer
+pub const ZSTD_LEVEL_RANGE: std::ops::RangeInclusive<i32> = 1..=22;
+
+//Every output column is a float column. Parquet only defines delta encodings for integer columns,
+//so the choice here is plain values or a dictionary, which pays off for columns with few distinct
+//values (HitOrder, StateId, ...). Columns with too many distinct values stay plain.
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum ColumnEncoding {
+    #[default]
+    Plain,
+    Dictionary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum ColumnCompression {
+    Uncompressed,
+    Snappy,
+    Lz4,
+    #[default]
+    Zstd,
+}
+
+//Encoding and compression for the columns matching column, either an exact column name or a
+//prefix ending in * (e.g. "Cebra0*" for every column of one detector)
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ColumnCodec {
+    pub column: String,
+    pub encoding: ColumnEncoding,
+    pub compression: ColumnCompression,
+    //zstd level, the zstd default when not given
+    #[serde(default)]
+    pub level: Option<i32>,
+}
+
+impl ColumnCodec {
+    pub fn matches(&self, column_name: &str) -> bool {
+        match self.column.strip_suffix('*') {
+            Some(prefix) => column_name.starts_with(prefix),
+            None => column_name == self.column,
+        }
+    }
+
+    pub fn get_encoding(&self) -> Encoding {
+        match self.encoding {
+            ColumnEncoding::Plain => Encoding::Plain,
+            ColumnEncoding::Dictionary => Encoding::RleDictionary,
+        }
+    }
+
+    pub fn get_compression(&self) -> CompressionOptions {
+        match self.compression {
+            ColumnCompression::Uncompressed => CompressionOptions::Uncompressed,
+            ColumnCompression::Snappy => CompressionOptions::Snappy,
+            ColumnCompression::Lz4 => CompressionOptions::Lz4Raw,
+            ColumnCompression::Zstd => CompressionOptions::Zstd(
+                self.level.and_then(|level| ZstdLevel::try_new(level).ok()),
+            ),
+        }
+    }
+}
+
+//The first codec matching the column wins
+pub fn find_codec<'a>(codecs: &'a [ColumnCodec], column_name: &str) -> Option<&'a ColumnCodec> {
+    codecs.iter().find(|codec| codec.matches(column_name))
+}
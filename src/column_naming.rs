@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_data::{get_list_name, ChannelDataField};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum ColumnNamingScheme {
+    //Cebra0Energy, EventID, ...
+    #[default]
+    Default,
+    //cebra0_energy, event_id, ...
+    SnakeCase,
+    //The CeBrA detector columns named by the template, the others as by default
+    Template,
+}
+
+//How the columns of the event tables are named in the output files. The event filters and the sort
+//column of the config still name columns by their default names, the column codecs by their names
+//in the output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnNaming {
+    pub scheme: ColumnNamingScheme,
+    //For the Template scheme: {n} is the detector number, {field} the field (Energy, Time, ...)
+    //and {f} its short form (E, T, ...), e.g. det{n}_{f} for det0_E
+    #[serde(default = "default_template")]
+    pub template: String,
+}
+
+fn default_template() -> String {
+    String::from("det{n}_{f}")
+}
+
+impl Default for ColumnNaming {
+    fn default() -> Self {
+        ColumnNaming {
+            scheme: ColumnNamingScheme::Default,
+            template: default_template(),
+        }
+    }
+}
+
+impl ColumnNaming {
+    pub fn get_field_name(&self, field: &ChannelDataField) -> String {
+        match (self.scheme, field.get_detector()) {
+            (ColumnNamingScheme::SnakeCase, _) => to_snake_case(&field.get_name()),
+            (ColumnNamingScheme::Template, Some(detector)) => self
+                .template
+                .replace("{n}", &detector.to_string())
+                .replace("{field}", get_detector_suffix(field))
+                .replace("{f}", get_short_suffix(field)),
+            _ => field.get_name(),
+        }
+    }
+
+    //Name in the output of a column whose default name is name. Only the snake case scheme renames
+    //columns that are not fields, e.g. those of detector groups.
+    pub fn get_column_name(&self, name: &str) -> String {
+        match self.scheme {
+            ColumnNamingScheme::Default => name.to_string(),
+            ColumnNamingScheme::SnakeCase => to_snake_case(name),
+            ColumnNamingScheme::Template => match ChannelDataField::from_name(name) {
+                Some(field) => self.get_field_name(&field),
+                None => name.to_string(),
+            },
+        }
+    }
+
+    //Name of the list column of every hit of an expanded detector, e.g. det3_EList
+    pub fn get_list_name(&self, field: &ChannelDataField) -> String {
+        match self.scheme {
+            ColumnNamingScheme::Default => get_list_name(field),
+            ColumnNamingScheme::SnakeCase => to_snake_case(&get_list_name(field)),
+            ColumnNamingScheme::Template => format!("{}List", self.get_field_name(field)),
+        }
+    }
+}
+
+//Energy for Cebra0Energy, as in the {field} placeholder
+fn get_detector_suffix(field: &ChannelDataField) -> &str {
+    field.as_ref().trim_start_matches("Cebra")
+}
+
+//Short form of the detector fields, for the {f} placeholder
+fn get_short_suffix(field: &ChannelDataField) -> &'static str {
+    match field {
+        ChannelDataField::CebraEnergy(_) => "E",
+        ChannelDataField::CebraShort(_) => "S",
+        ChannelDataField::CebraTime(_) => "T",
+        ChannelDataField::CebraRawTime(_) => "RawT",
+        ChannelDataField::CebraEffWeight(_) => "W",
+        ChannelDataField::CebraEnergyCal(_) => "ECal",
+        ChannelDataField::CebraPsd(_) => "PSD",
+        ChannelDataField::CebraBaseline(_) => "Base",
+        ChannelDataField::CebraAmplitude(_) => "Amp",
+        ChannelDataField::CebraRiseTime(_) => "Rise",
+        ChannelDataField::CebraHits(_) => "N",
+        _ => "",
+    }
+}
+
+//An underscore goes before every capital that starts a word: after a lower case letter or digit,
+//or before a lower case letter after a run of capitals, so TimeToRF is time_to_rf
+fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_ascii_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let is_word_start = previous.is_ascii_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_ascii_uppercase()
+                    && chars
+                        .get(index + 1)
+                        .is_some_and(|next| next.is_ascii_lowercase()));
+            if is_word_start {
+                snake.push('_');
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+    snake
+}
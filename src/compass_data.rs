@@ -1,9 +1,16 @@
+use std::cell::RefCell;
+
 use super::shift_map::ShiftMap;
+use super::used_size::UsedSize;
+use super::waveform::WaveformSummary;
 use bitflags::bitflags;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
 
 bitflags! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
     pub struct CompassDataType: u16 {
         const ENERGY = 0x0001;
         const ENERGY_SHORT = 0x0004;
@@ -14,14 +21,149 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone)]
+//Bits of the flag word CoMPASS writes with every hit
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct CompassFlags: u32 {
+        const DEAD_TIME = 0x00000004;
+        const TIMESTAMP_ROLLOVER = 0x00000008;
+        const TIMESTAMP_RESET = 0x00000020;
+        const FAKE_EVENT = 0x00000080;
+        const MEMORY_FULL = 0x00000400;
+        const TRIGGER_LOST = 0x00001000;
+        const N_TRIGGERS_LOST = 0x00008000;
+        const GATE_SATURATION = 0x00010000;
+        const TRIGGERS_1024_COUNTED = 0x00020000;
+        const INPUT_SATURATING = 0x00080000;
+        const N_TRIGGERS_COUNTED = 0x00100000;
+        const NOT_MATCHED = 0x00200000;
+        const FINE_TIMESTAMP = 0x00400000;
+        const PILEUP = 0x00800000;
+        const PLL_LOCK_LOSS = 0x01000000;
+        const OVER_TEMPERATURE = 0x02000000;
+        const ADC_SHUTDOWN = 0x04000000;
+    }
+}
+
+//What the flags of a hit say about its energy, packed into the Flags columns so clean hits are
+//those with 0
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct HitConditions: u32 {
+        const PILEUP = 0x1;
+        //In the gate or at the input
+        const SATURATED = 0x2;
+        //Triggers were lost, or the board was dead, just before the hit
+        const LOST_EVENTS = 0x4;
+    }
+}
+
+impl HitConditions {
+    pub fn from_flags(flags: u32) -> Self {
+        let flags = CompassFlags::from_bits_retain(flags);
+        let mut conditions = HitConditions::empty();
+        conditions.set(HitConditions::PILEUP, flags.contains(CompassFlags::PILEUP));
+        conditions.set(
+            HitConditions::SATURATED,
+            flags.intersects(CompassFlags::GATE_SATURATION | CompassFlags::INPUT_SATURATING),
+        );
+        conditions.set(
+            HitConditions::LOST_EVENTS,
+            flags.intersects(
+                CompassFlags::DEAD_TIME
+                    | CompassFlags::TRIGGER_LOST
+                    | CompassFlags::N_TRIGGERS_LOST,
+            ),
+        );
+        conditions
+    }
+
+    //e.g. "1 pileup, 2 saturated, 4 lost events", for the metadata of the Flags columns
+    pub fn describe() -> String {
+        HitConditions::all()
+            .iter_names()
+            .map(|(name, condition)| {
+                format!(
+                    "{} {}",
+                    condition.bits(),
+                    name.to_lowercase().replace('_', " ")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+//Width of the energy field in a board's binary records. Some HEX firmware writes extended
+//precision 64-bit energies in place of the usual 16-bit ones.
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum EnergyWidth {
+    #[default]
+    #[strum(serialize = "16 bit")]
+    Standard,
+    #[strum(serialize = "64 bit")]
+    Extended,
+}
+
+impl EnergyWidth {
+    pub fn get_size_bytes(&self) -> usize {
+        match self {
+            EnergyWidth::Standard => 2,
+            EnergyWidth::Extended => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct RawCompassData {
     pub board: u16,
     pub channel: u16,
     pub timestamp: u64,
-    pub energy: u16,
+    pub energy: u64,
     pub energy_calibrated: u64,
     pub energy_short: u16,
+    pub flags: u32,
+    //Empty unless the file was written with wave recording enabled
+    pub samples: Vec<u16>,
+}
+
+//Unit of the raw timestamps of a board. CoMPASS writes picoseconds whatever the digitizer, while
+//other readouts write ticks of the sampling clock, which differ between models (4 ns for a V1725 at
+//250 MS/s, 2 ns for a V1730 at 500 MS/s). Every timestamp is normalized to ns as it is decoded, so
+//boards with different clocks are merged and built on one time axis.
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum TimestampUnit {
+    #[default]
+    #[strum(serialize = "ps")]
+    Picoseconds,
+    #[strum(serialize = "1 ns ticks (1 GS/s)")]
+    Ticks1GSps,
+    #[strum(serialize = "2 ns ticks (500 MS/s)")]
+    Ticks500MSps,
+    #[strum(serialize = "4 ns ticks (250 MS/s)")]
+    Ticks250MSps,
+    #[strum(serialize = "8 ns ticks (125 MS/s)")]
+    Ticks125MSps,
+    #[strum(serialize = "10 ns ticks (100 MS/s)")]
+    Ticks100MSps,
+}
+
+impl TimestampUnit {
+    pub fn get_ns_per_unit(&self) -> f64 {
+        match self {
+            TimestampUnit::Picoseconds => 1.0e-3,
+            TimestampUnit::Ticks1GSps => 1.0,
+            TimestampUnit::Ticks500MSps => 2.0,
+            TimestampUnit::Ticks250MSps => 4.0,
+            TimestampUnit::Ticks125MSps => 8.0,
+            TimestampUnit::Ticks100MSps => 10.0,
+        }
+    }
+
+    //Indexed by board number, boards without a unit are in ps
+    pub fn of_board(units: &[TimestampUnit], board: u32) -> Self {
+        units.get(board as usize).copied().unwrap_or_default()
+    }
 }
 
 pub const fn generate_board_channel_uuid(board: &u32, channel: &u32) -> u32 {
@@ -45,28 +187,73 @@ pub fn decompose_uuid_to_board_channel(uuid: &u32) -> (u32, u32) {
     }
 }
 
-#[derive(Debug, Clone)]
+thread_local! {
+    //Only seeded by the regression build, which needs reproducible energies
+    static DITHER_RNG: RefCell<Option<StdRng>> = RefCell::new(None);
+}
+
+//Seeds the dither of hits decoded on this thread, None goes back to unseeded dithering
+pub fn set_dither_seed(seed: Option<u64>) {
+    DITHER_RNG.with(|rng| *rng.borrow_mut() = seed.map(StdRng::seed_from_u64));
+}
+
+//A seeded dither for hits decoded on another thread, drawn from the dither of this thread so a
+//seeded build stays reproducible. None when this thread is unseeded.
+pub fn fork_dither() -> Option<StdRng> {
+    DITHER_RNG.with(|rng| {
+        rng.borrow_mut()
+            .as_mut()
+            .map(|seeded| StdRng::seed_from_u64(seeded.gen()))
+    })
+}
+
+//Exchanges the dither of this thread with the given one, so a thread decoding several sources
+//gives each of them its own
+pub fn swap_dither(dither: &mut Option<StdRng>) {
+    DITHER_RNG.with(|rng| std::mem::swap(&mut *rng.borrow_mut(), dither));
+}
+
+//Uniform in [0, 1), added to the integer energies so they can be histogrammed without binning
+//artifacts
+pub fn get_dither() -> f64 {
+    DITHER_RNG.with(|rng| match rng.borrow_mut().as_mut() {
+        Some(seeded) => seeded.gen(),
+        None => rand::thread_rng().gen(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompassData {
     pub uuid: u32,
     pub energy: f64,
     pub energy_short: f64,
     pub timestamp: f64,
+    //The timestamp as the digitizer wrote it (ns), before time shifts, time walk and jitter
+    pub raw_timestamp: f64,
+    //Only filled when waveforms are reduced to derived quantities
+    pub waveform: Option<WaveformSummary>,
+    //The CoMPASS flag word, see CompassFlags. 0 for hits without one.
+    #[serde(default)]
+    pub flags: u32,
 }
 
 impl CompassData {
-    pub fn new(raw: &RawCompassData, shifts: &Option<ShiftMap>) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new(raw: &RawCompassData, shifts: &Option<ShiftMap>, unit: TimestampUnit) -> Self {
         let board = raw.board as u32;
         let channel = raw.channel as u32;
         let id = generate_board_channel_uuid(&board, &channel);
+        let timestamp = raw.timestamp as f64 * unit.get_ns_per_unit();
         CompassData {
             uuid: id,
-            energy: raw.energy as f64 + rng.gen::<f64>(),
-            energy_short: raw.energy_short as f64 + rng.gen::<f64>(),
+            energy: raw.energy as f64 + get_dither(),
+            energy_short: raw.energy_short as f64 + get_dither(),
             timestamp: match shifts {
-                Some(map) => raw.timestamp as f64 * 1.0e-3 + map.get_timeshift(&id),
-                None => raw.timestamp as f64 * 1.0e-3,
+                Some(map) => timestamp + map.get_timeshift(&id),
+                None => timestamp,
             },
+            raw_timestamp: timestamp,
+            waveform: None,
+            flags: raw.flags,
         }
     }
 
@@ -80,6 +267,12 @@ impl CompassData {
     }
 }
 
+impl UsedSize for CompassData {
+    fn get_used_size(&self) -> usize {
+        std::mem::size_of::<CompassData>()
+    }
+}
+
 impl Default for CompassData {
     fn default() -> Self {
         CompassData {
@@ -87,6 +280,9 @@ impl Default for CompassData {
             energy: 0.0,
             energy_short: 0.0,
             timestamp: 0.0,
+            raw_timestamp: 0.0,
+            waveform: None,
+            flags: 0,
         }
     }
 }
@@ -1,152 +1,835 @@
-use super::compass_data::{CompassData, CompassDataType, RawCompassData};
+use super::channel_map::ChannelMap;
+use super::compass_data::{
+    CompassData, CompassDataType, EnergyWidth, RawCompassData, TimestampUnit,
+};
+use super::corrupt_file::{CompassFileError, CorruptFile, CorruptionLog};
 use super::error::EVBError;
+use super::hit_source::{HitSource, Waiting};
 use super::shift_map::ShiftMap;
+use super::used_size::UsedSize;
+use super::waveform::WaveformHandler;
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
-use std::path;
+use std::io::{BufReader, SeekFrom};
+use std::path::{self, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use nom::number::complete::*;
+use log::debug;
+use memmap2::Mmap;
 
-const BUFFER_SIZE_HITS: usize = 24000; // Size in Compass hits of the buffer for each binary data file
+const BUFFER_SIZE_HITS: usize = 24000; // Size in Compass hits of the buffer of a buffered segment
 
-fn parse_u16(buffer: &[u8]) -> Result<(&[u8], u16), EVBError> {
-    match le_u16::<&[u8], nom::error::Error<&[u8]>>(buffer) {
-        Err(_x) => Err(EVBError::Parser),
-        Ok(x) => Ok(x),
+//Enough of the start of a file for its header, the first record's board and its sample count
+const HEAD_BYTES: u64 = 64;
+
+//CoMPASS writes every field little endian, whatever the machine reading the file. Each parser takes
+//its field off the front of the slice, without copying the record.
+fn take_bytes<const N: usize>(buffer: &[u8]) -> Result<(&[u8], [u8; N]), EVBError> {
+    match buffer
+        .get(..N)
+        .and_then(|bytes| <[u8; N]>::try_from(bytes).ok())
+    {
+        Some(bytes) => Ok((&buffer[N..], bytes)),
+        None => Err(EVBError::Parser),
     }
 }
 
+fn parse_u16(buffer: &[u8]) -> Result<(&[u8], u16), EVBError> {
+    take_bytes(buffer).map(|(rest, bytes)| (rest, u16::from_le_bytes(bytes)))
+}
+
 fn parse_u32(buffer: &[u8]) -> Result<(&[u8], u32), EVBError> {
-    match le_u32::<&[u8], nom::error::Error<&[u8]>>(buffer) {
-        Err(_x) => Err(EVBError::Parser),
-        Ok(x) => Ok(x),
-    }
+    take_bytes(buffer).map(|(rest, bytes)| (rest, u32::from_le_bytes(bytes)))
 }
 
 fn parse_u64(buffer: &[u8]) -> Result<(&[u8], u64), EVBError> {
-    match le_u64::<&[u8], nom::error::Error<&[u8]>>(buffer) {
-        Err(_x) => Err(EVBError::Parser),
-        Ok(x) => Ok(x),
+    take_bytes(buffer).map(|(rest, bytes)| (rest, u64::from_le_bytes(bytes)))
+}
+
+//How the segments of a file are read. Mapped, the records are decoded in place without going
+//through a read buffer, but a mapped file cut short while it is read faults the whole process. Files
+//that can still be written to or truncated, e.g. those of a run that is followed, are read through a
+//buffer instead, where a file cut short just ends early.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SegmentAccess {
+    #[default]
+    Mapped,
+    Buffered,
+}
+
+//How a file that is still being written is read, e.g. one of a run that is followed. At the end of
+//what was written it waits for more (see Waiting), and finds the next segment once CoMPASS starts
+//it. Only once the run is closed is the end of the file its end.
+#[derive(Debug, Clone)]
+pub struct GrowingFile {
+    //How often the size of the file is looked at while waiting
+    pub check_interval: Duration,
+    //A file that has not grown for this long is idle
+    pub idle_timeout: Duration,
+    pub is_closed: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+enum OpenSegment {
+    Mapped(Mmap),
+    //With the bytes of the record being decoded
+    Buffered(BufReader<File>, Vec<u8>),
+}
+
+impl OpenSegment {
+    //The bytes from offset to offset + length, right after those of the last read
+    fn read(&mut self, offset: usize, length: usize) -> Result<&[u8], EVBError> {
+        match self {
+            OpenSegment::Mapped(map) => map.get(offset..offset + length).ok_or(EVBError::Parser),
+            OpenSegment::Buffered(reader, record) => {
+                record.resize(length, 0);
+                reader.read_exact(record)?;
+                Ok(record)
+            }
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct CompassFile<'a> {
-    file_handle: BufReader<File>,
+    //The open segment, read record by record
+    segment: Option<OpenSegment>,
+    access: SegmentAccess,
+    pending_segments: VecDeque<PathBuf>,
     size_bytes: u64,
     data_type: CompassDataType,
     data_size_bytes: usize,
+    energy_width: EnergyWidth,
+    wave_samples: usize,
     current_hit: CompassData,
-    shift_map: &'a Option<ShiftMap>,
+    //Owned, so a file can outlive the settings it was opened with, as the files of a followed run do
+    shift_map: Option<ShiftMap>,
+    energy_widths: Vec<EnergyWidth>,
+    timestamp_units: Vec<TimestampUnit>,
+    waveforms: Option<WaveformHandler<'a>>,
     is_used: bool,
     is_eof: bool,
+    corruption: CorruptionLog,
+    //Where reading stands in the open segment, to find the offset of a bad record
+    segment_name: String,
+    segment_size: u64,
+    segment_offset: u64,
+    segment_hits: u64,
+    //Only for files still being written, with the first segment the next ones are named after
+    growth: Option<GrowingFile>,
+    first_segment: PathBuf,
+    segment_path: PathBuf,
+    segments_opened: usize,
+    last_growth: Instant,
+    last_check: Instant,
+    waiting: Option<Waiting>,
 }
 
-impl<'a> CompassFile<'a> {
-    pub fn new(
-        path: &path::Path,
-        shifts: &'a Option<ShiftMap>,
-    ) -> Result<CompassFile<'a>, EVBError> {
-        let mut file: File = File::open(path)?;
-        let total_size = file.metadata()?.len();
+//Decoded header of one file: the data type and the size of one hit. With waves the size is that of
+//the fields before the samples, and the number of samples is the one of the first record.
+struct SegmentFormat {
+    data_type: CompassDataType,
+    data_size_bytes: usize,
+    energy_width: EnergyWidth,
+    wave_samples: usize,
+    size_bytes: u64,
+}
 
-        let mut header: [u8; 2] = [0; 2];
-        file.read_exact(&mut header)?;
-        let header_word = u16::from_le_bytes(header);
+//The data type of the first word of a file, and the size of the fields of each record before any
+//samples
+pub fn parse_header(header_word: u16, energy_width: EnergyWidth) -> (CompassDataType, usize) {
+    let mut datatype = CompassDataType::NONE;
+    let mut datasize: usize = 16; //minimum 16 bytes for board, channel, timestamp, flags
 
-        let mut datatype = CompassDataType::NONE;
-        let mut datasize: usize = 16; //minimum 16 bytes for board, channel, timestamp, flags
+    if header_word & CompassDataType::ENERGY.bits() != 0 {
+        datatype |= CompassDataType::ENERGY;
+        datasize += energy_width.get_size_bytes();
+    }
+    if header_word & CompassDataType::ENERGY_SHORT.bits() != 0 {
+        datatype |= CompassDataType::ENERGY_SHORT;
+        datasize += 2;
+    }
+    if header_word & CompassDataType::ENERGY_CALIBRATED.bits() != 0 {
+        datatype |= CompassDataType::ENERGY_CALIBRATED;
+        datasize += 8;
+    }
+    //Each record ends with a wave code byte and the number of samples, followed by the samples
+    if header_word & CompassDataType::WAVES.bits() != 0 {
+        datatype |= CompassDataType::WAVES;
+        datasize += 5;
+    }
+    (datatype, datasize)
+}
 
-        if header_word & CompassDataType::ENERGY.bits() != 0 {
-            datatype |= CompassDataType::ENERGY;
-            datasize += 2;
+//Decode the header of a file from its first bytes. The energy width is set per board, so the board
+//of the first record is read before sizing the records.
+fn parse_format(
+    head: &[u8],
+    size_bytes: u64,
+    energy_widths: &[EnergyWidth],
+) -> Result<SegmentFormat, EVBError> {
+    let header_word = match parse_u16(head) {
+        Ok((_, word)) => word,
+        Err(_) => return Err(CompassFileError::MissingHeader.into()),
+    };
+    let energy_width = match parse_u16(head.get(2..).unwrap_or_default()) {
+        Ok((_, board)) => energy_widths
+            .get(board as usize)
+            .copied()
+            .unwrap_or_default(),
+        Err(_) => EnergyWidth::default(),
+    };
+
+    let (datatype, datasize) = parse_header(header_word, energy_width);
+    let mut wave_samples: usize = 0;
+    if datatype.bits() & CompassDataType::WAVES.bits() != 0 {
+        wave_samples = match parse_u32(head.get(datasize - 2..).unwrap_or_default()) {
+            Ok((_, samples)) => samples as usize,
+            Err(_) => 0,
+        };
+    }
+
+    Ok(SegmentFormat {
+        data_type: datatype,
+        data_size_bytes: datasize,
+        energy_width,
+        wave_samples,
+        size_bytes,
+    })
+}
+
+fn map_segment(path: &path::Path) -> Result<Mmap, EVBError> {
+    let file = File::open(path)?;
+    // Safety: the files are only read, and mapped only when the DAQ is done writing them (see
+    // SegmentAccess). A file cut short while mapped would fault.
+    let map = unsafe { Mmap::map(&file)? };
+    //The records are read front to back once, so the kernel can read ahead and drop what is read
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential)?;
+    Ok(map)
+}
+
+//Open a single file, positioned at its first record, and decode its header
+fn open_segment(
+    path: &path::Path,
+    energy_widths: &[EnergyWidth],
+    access: SegmentAccess,
+) -> Result<(OpenSegment, SegmentFormat), EVBError> {
+    match access {
+        SegmentAccess::Mapped => {
+            let map = map_segment(path)?;
+            let format = parse_format(&map, map.len() as u64, energy_widths)?;
+            Ok((OpenSegment::Mapped(map), format))
         }
-        if header_word & CompassDataType::ENERGY_SHORT.bits() != 0 {
-            datatype |= CompassDataType::ENERGY_SHORT;
-            datasize += 2;
+        SegmentAccess::Buffered => {
+            let mut file = File::open(path)?;
+            let size_bytes = file.metadata()?.len();
+            let mut head = vec![];
+            (&mut file).take(HEAD_BYTES).read_to_end(&mut head)?;
+            let format = parse_format(&head, size_bytes, energy_widths)?;
+            file.seek(SeekFrom::Start(2))?;
+            let capacity = (format.data_size_bytes + 2 * format.wave_samples) * BUFFER_SIZE_HITS;
+            Ok((
+                OpenSegment::Buffered(BufReader::with_capacity(capacity, file), vec![]),
+                format,
+            ))
         }
-        if header_word & CompassDataType::ENERGY_CALIBRATED.bits() != 0 {
-            datatype |= CompassDataType::ENERGY_CALIBRATED;
-            datasize += 8;
+    }
+}
+
+//CoMPASS splits long runs into segments named <stem>_1.BIN, <stem>_2.BIN, ... next to <stem>.BIN.
+//Group the segments of each channel together, in order, so they can be read as one file.
+pub fn group_segments(paths: Vec<PathBuf>) -> Vec<Vec<PathBuf>> {
+    let stems: HashSet<String> = paths
+        .iter()
+        .filter_map(|p| p.file_stem().and_then(|s| s.to_str()).map(String::from))
+        .collect();
+
+    let mut groups: BTreeMap<String, Vec<(u32, PathBuf)>> = BTreeMap::new();
+    for path in paths {
+        let stem = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(s) => s.to_string(),
+            None => continue,
+        };
+        let segment = stem.rsplit_once('_').and_then(|(base, suffix)| {
+            match (suffix.parse::<u32>(), stems.contains(base)) {
+                (Ok(number), true) => Some((base.to_string(), number)),
+                _ => None,
+            }
+        });
+        match segment {
+            Some((base, number)) => groups.entry(base).or_default().push((number, path)),
+            None => groups.entry(stem).or_default().push((0, path)),
+        }
+    }
+
+    groups
+        .into_values()
+        .map(|mut segments| {
+            segments.sort_by_key(|(number, _)| *number);
+            segments.into_iter().map(|(_, path)| path).collect()
+        })
+        .collect()
+}
+
+//What the header and the size of one segment tell without reading it through
+#[derive(Debug, Clone)]
+pub struct SegmentSummary {
+    pub data_type: CompassDataType,
+    pub hits: u64,
+    //Raw records, without the samples
+    pub first: Option<RawCompassData>,
+    pub last: Option<RawCompassData>,
+    //Bytes after the last whole record, a file cut short by the DAQ
+    pub trailing_bytes: u64,
+    //More than one board/channel among the first records, a time-merged file
+    pub is_merged: bool,
+}
+
+//Records decoded from the start of a segment to tell a time-merged file from that of one channel
+const MERGE_PROBE_RECORDS: u64 = 256;
+
+//Only the first and last record are decoded, the others are counted from the size of the segment.
+//With waves every record is taken to have the samples of the first, as CoMPASS writes them.
+pub fn summarize_segment(
+    path: &path::Path,
+    energy_widths: &[EnergyWidth],
+) -> Result<SegmentSummary, EVBError> {
+    let map = map_segment(path)?;
+    let format = parse_format(&map, map.len() as u64, energy_widths)?;
+    let record_bytes = (format.data_size_bytes + 2 * format.wave_samples) as u64;
+    let data_bytes = format.size_bytes.saturating_sub(2);
+    let hits = data_bytes / record_bytes;
+    let read_record = |index: u64| -> Result<RawCompassData, EVBError> {
+        let start = (2 + index * record_bytes) as usize;
+        let fields = map
+            .get(start..start + format.data_size_bytes)
+            .ok_or(EVBError::Parser)?;
+        parse_record_fields(fields, format.data_type, format.energy_width).map(|(raw, _)| raw)
+    };
+    let (first, last) = match hits {
+        0 => (None, None),
+        _ => (Some(read_record(0)?), Some(read_record(hits - 1)?)),
+    };
+    let is_other_channel = |record: &RawCompassData| {
+        first
+            .as_ref()
+            .is_some_and(|first| record.board != first.board || record.channel != first.channel)
+    };
+    let mut is_merged = last.as_ref().is_some_and(is_other_channel);
+    for index in 1..hits.min(MERGE_PROBE_RECORDS) {
+        if is_merged {
+            break;
+        }
+        is_merged = is_other_channel(&read_record(index)?);
+    }
+    Ok(SegmentSummary {
+        data_type: format.data_type,
+        hits,
+        first,
+        last,
+        trailing_bytes: data_bytes % record_bytes,
+        is_merged,
+    })
+}
+
+//Whether the segments of a file are a time-merged file of several channels, as CoMPASS writes when
+//configured to, rather than the file of one channel. Told from the records, as a run of a single
+//channel has a single file too.
+pub fn is_time_merged(
+    segments: &[PathBuf],
+    energy_widths: &[EnergyWidth],
+) -> Result<bool, EVBError> {
+    match segments.first() {
+        Some(path) => Ok(summarize_segment(path, energy_widths)?.is_merged),
+        None => Ok(false),
+    }
+}
+
+impl<'a> CompassFile<'a> {
+    pub fn new(
+        path: &path::Path,
+        shifts: &Option<ShiftMap>,
+        energy_widths: &[EnergyWidth],
+    ) -> Result<CompassFile<'a>, EVBError> {
+        Self::new_segmented(
+            &[path.to_path_buf()],
+            shifts,
+            energy_widths,
+            &CorruptionLog::default(),
+            SegmentAccess::Mapped,
+        )
+    }
+
+    //Only the active segment is kept open, so runs with hundreds of segments don't exhaust the
+    //open file limit. Corrupted segments are recorded in the log and read up to the bad record.
+    pub fn new_segmented(
+        paths: &[PathBuf],
+        shifts: &Option<ShiftMap>,
+        energy_widths: &[EnergyWidth],
+        corruption: &CorruptionLog,
+        access: SegmentAccess,
+    ) -> Result<CompassFile<'a>, EVBError> {
+        if paths.is_empty() {
+            return Err(EVBError::Parser);
         }
-        if header_word & CompassDataType::WAVES.bits() != 0 {
-            return Err(EVBError::Waves);
+        let mut total_size: u64 = 0;
+        for path in paths.iter() {
+            total_size += std::fs::metadata(path)?.len();
         }
 
-        Ok(CompassFile {
-            file_handle: BufReader::with_capacity(datasize * BUFFER_SIZE_HITS, file),
+        let mut file = CompassFile {
+            segment: None,
+            access,
+            pending_segments: paths.iter().cloned().collect(),
             size_bytes: total_size,
-            data_type: datatype,
-            data_size_bytes: datasize,
+            data_type: CompassDataType::NONE,
+            data_size_bytes: parse_header(0, EnergyWidth::default()).1,
+            energy_width: EnergyWidth::default(),
+            wave_samples: 0,
             current_hit: CompassData::default(),
-            shift_map: shifts,
+            shift_map: shifts.clone(),
+            energy_widths: energy_widths.to_vec(),
+            timestamp_units: vec![],
+            waveforms: None,
             is_used: false,
             is_eof: false,
-        })
+            corruption: corruption.clone(),
+            segment_name: String::new(),
+            segment_size: 0,
+            segment_offset: 0,
+            segment_hits: 0,
+            growth: None,
+            first_segment: paths[0].clone(),
+            segment_path: PathBuf::new(),
+            segments_opened: 0,
+            last_growth: Instant::now(),
+            last_check: Instant::now(),
+            waiting: None,
+        };
+        file.open_next_segment()?;
+        Ok(file)
     }
 
-    pub fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
-        if self.is_used {
-            self.current_hit = match self.parse_top_hit() {
-                Err(EVBError::File(e)) => match e.kind() {
-                    std::io::ErrorKind::UnexpectedEof => {
-                        self.is_eof = true;
-                        CompassData::default()
-                    }
-                    _ => return Err(EVBError::File(e)),
-                },
-                Ok(data) => {
-                    self.is_used = false;
-                    data
+    //Close the exhausted segment and move to the next one that has a header, returns false if
+    //there is none
+    fn open_next_segment(&mut self) -> Result<bool, EVBError> {
+        self.segment = None;
+        while let Some(path) = self.pending_segments.pop_front() {
+            self.segments_opened += 1;
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match open_segment(&path, &self.energy_widths, self.access) {
+                Ok((segment, format)) => {
+                    self.segment = Some(segment);
+                    self.segment_path = path;
+                    self.data_type = format.data_type;
+                    self.data_size_bytes = format.data_size_bytes;
+                    self.energy_width = format.energy_width;
+                    self.wave_samples = format.wave_samples;
+                    self.segment_name = name;
+                    self.segment_size = format.size_bytes;
+                    self.segment_offset = 2;
+                    self.segment_hits = 0;
+                    debug!(
+                        "Reading {}: {} records of {} bytes",
+                        self.segment_name,
+                        self.segment_size.saturating_sub(2) / self.get_record_size_bytes() as u64,
+                        self.get_record_size_bytes()
+                    );
+                    return Ok(true);
                 }
+                Err(EVBError::CompassFile(error)) => self.corruption.add(CorruptFile {
+                    file: name,
+                    offset: error.get_offset(),
+                    reason: error.to_string(),
+                    hits_recovered: 0,
+                    hits_dropped: 0,
+                }),
                 Err(x) => return Err(x),
             }
         }
+        Ok(false)
+    }
 
-        Ok(&self.current_hit)
+    //Keeps the hits read from the open segment and drops the rest of it
+    fn drop_corrupted_segment(&mut self, error: CompassFileError) {
+        let record_bytes = self.get_record_size_bytes() as u64;
+        let remaining = self.segment_size.saturating_sub(error.get_offset());
+        self.corruption.add(CorruptFile {
+            file: self.segment_name.clone(),
+            offset: error.get_offset(),
+            reason: error.to_string(),
+            hits_recovered: self.segment_hits,
+            hits_dropped: (remaining + record_bytes - 1) / record_bytes,
+        });
+    }
+
+    fn get_record_size_bytes(&self) -> usize {
+        self.data_size_bytes + 2 * self.wave_samples
+    }
+
+    //Indexed by board number, without units every board is taken to write ps as CoMPASS does
+    pub fn with_timestamp_units(mut self, timestamp_units: &[TimestampUnit]) -> Self {
+        self.timestamp_units = timestamp_units.to_vec();
+        self
+    }
+
+    //Only for files read with SegmentAccess::Buffered, a mapped file must not grow
+    pub fn with_growth(mut self, growth: GrowingFile) -> Self {
+        self.growth = Some(growth);
+        self
+    }
+
+    //Without a handler the samples of files with waves are read past and dropped
+    pub fn with_waveforms(mut self, waveforms: Option<WaveformHandler<'a>>) -> Self {
+        self.waveforms = waveforms;
+        self
+    }
+
+    pub fn get_data_type(&self) -> CompassDataType {
+        self.data_type
+    }
+
+    //The next record as it is in the file, without time shifts or dither. None once every segment
+    //is exhausted.
+    pub fn read_raw_hit(&mut self) -> Result<Option<RawCompassData>, EVBError> {
+        match self.parse_raw_hit() {
+            Err(EVBError::File(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => (),
+            Err(EVBError::CompassFile(error)) => self.drop_corrupted_segment(error),
+            Err(x) => return Err(x),
+            Ok(raw) => return Ok(Some(raw)),
+        }
+        if self.open_next_segment()? {
+            self.read_raw_hit()
+        } else {
+            Ok(None)
+        }
     }
 
     fn parse_top_hit(&mut self) -> Result<CompassData, EVBError> {
-        let mut raw_data = RawCompassData {
-            board: 0,
-            channel: 0,
-            timestamp: 0,
-            energy: 0,
-            energy_calibrated: 0,
-            energy_short: 0,
+        let raw = self.parse_raw_hit()?;
+        let unit = TimestampUnit::of_board(&self.timestamp_units, raw.board as u32);
+        let hit = CompassData::new(&raw, &self.shift_map, unit);
+        match &mut self.waveforms {
+            Some(handler) => handler.process(&raw, hit),
+            None => Ok(hit),
+        }
+    }
+
+    //A record that does not fit in what is left of the segment is a CompassFileError, checked
+    //before reading so a bad sample count never reads past the segment
+    fn parse_raw_hit(&mut self) -> Result<RawCompassData, EVBError> {
+        let offset = self.segment_offset;
+        let remaining = self.segment_size.saturating_sub(offset);
+        let segment = match &mut self.segment {
+            Some(segment) if remaining > 0 => segment,
+            _ => return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
         };
+        if remaining < self.data_size_bytes as u64 {
+            return Err(CompassFileError::TruncatedRecord {
+                offset,
+                bytes: remaining,
+            }
+            .into());
+        }
+        let fields_end = offset as usize + self.data_size_bytes;
+        let (mut raw_data, samples) = parse_record_fields(
+            segment.read(offset as usize, self.data_size_bytes)?,
+            self.data_type,
+            self.energy_width,
+        )?;
+
+        let sample_bytes = 2 * samples as u64;
+        if sample_bytes > remaining - self.data_size_bytes as u64 {
+            return Err(CompassFileError::BadSampleCount { offset, samples }.into());
+        }
+        if samples > 0 {
+            raw_data.samples = parse_samples(segment.read(fields_end, 2 * samples)?);
+        }
+
+        self.segment_offset += self.data_size_bytes as u64 + sample_bytes;
+        self.segment_hits += 1;
+        Ok(raw_data)
+    }
+}
+
+//The fields of one record before its samples, which are data_size_bytes long, and the number of
+//samples that follow them
+pub fn parse_record_fields(
+    dataword: &[u8],
+    data_type: CompassDataType,
+    energy_width: EnergyWidth,
+) -> Result<(RawCompassData, usize), EVBError> {
+    let mut raw_data = RawCompassData {
+        board: 0,
+        channel: 0,
+        timestamp: 0,
+        energy: 0,
+        energy_calibrated: 0,
+        energy_short: 0,
+        flags: 0,
+        samples: vec![],
+    };
+    let mut dataslice = dataword;
+
+    (dataslice, raw_data.board) = parse_u16(dataslice)?;
+    (dataslice, raw_data.channel) = parse_u16(dataslice)?;
+    (dataslice, raw_data.timestamp) = parse_u64(dataslice)?;
+    if data_type.bits() & CompassDataType::ENERGY.bits() != 0 {
+        (dataslice, raw_data.energy) = match energy_width {
+            EnergyWidth::Standard => {
+                let (rest, energy) = parse_u16(dataslice)?;
+                (rest, energy as u64)
+            }
+            EnergyWidth::Extended => parse_u64(dataslice)?,
+        };
+    }
+    if data_type.bits() & CompassDataType::ENERGY_CALIBRATED.bits() != 0 {
+        (dataslice, raw_data.energy_calibrated) = parse_u64(dataslice)?;
+    }
+    if data_type.bits() & CompassDataType::ENERGY_SHORT.bits() != 0 {
+        (dataslice, raw_data.energy_short) = parse_u16(dataslice)?;
+    }
+    (dataslice, raw_data.flags) = parse_u32(dataslice)?;
+
+    let mut samples: usize = 0;
+    if data_type.bits() & CompassDataType::WAVES.bits() != 0 {
+        //The wave code byte only says which probes were recorded
+        samples = parse_u32(dataslice.get(1..).ok_or(EVBError::Parser)?)?.1 as usize;
+    }
+    Ok((raw_data, samples))
+}
 
-        let mut dataword: Vec<u8> = vec![0; self.data_size_bytes];
-        self.file_handle.read_exact(&mut dataword)?;
-        let mut dataslice = dataword.as_slice();
+pub fn parse_samples(sampleword: &[u8]) -> Vec<u16> {
+    sampleword
+        .chunks_exact(2)
+        .map(|sample| u16::from_le_bytes([sample[0], sample[1]]))
+        .collect()
+}
 
-        (dataslice, raw_data.board) = parse_u16(dataslice)?;
-        (dataslice, raw_data.channel) = parse_u16(dataslice)?;
-        (dataslice, raw_data.timestamp) = parse_u64(dataslice)?;
-        if self.data_type.bits() & CompassDataType::ENERGY.bits() != 0 {
-            (dataslice, raw_data.energy) = parse_u16(dataslice)?;
+impl CompassFile<'_> {
+    //Moves on to the next segment, or marks the file as done after the last one
+    fn end_segment(&mut self) -> Result<&CompassData, EVBError> {
+        if self.open_next_segment()? {
+            return self.get_top_hit();
         }
-        if self.data_type.bits() & CompassDataType::ENERGY_CALIBRATED.bits() != 0 {
-            (dataslice, raw_data.energy_calibrated) = parse_u64(dataslice)?;
+        self.is_eof = true;
+        if let Some(handler) = &mut self.waveforms {
+            handler.finish()?;
+        }
+        self.current_hit = CompassData::default();
+        Ok(&self.current_hit)
+    }
+
+    //A file still being written is only read as far as whole records were written. The end of its
+    //last segment is the end of the file once the run is closed, or CoMPASS has started the next.
+    fn is_record_written(&mut self) -> Result<bool, EVBError> {
+        let growth = match &self.growth {
+            Some(growth) => growth.clone(),
+            None => return Ok(true),
+        };
+        if self.segment.is_none() || !self.pending_segments.is_empty() {
+            return Ok(true);
         }
-        if self.data_type.bits() & CompassDataType::ENERGY_SHORT.bits() != 0 {
-            (dataslice, raw_data.energy_short) = parse_u16(dataslice)?;
+        let record_bytes = self.get_record_size_bytes() as u64;
+        if self.segment_size.saturating_sub(self.segment_offset) >= record_bytes {
+            return Ok(true);
+        }
+        let is_closed = growth.is_closed.load(Ordering::Relaxed);
+        if !is_closed && self.last_check.elapsed() < growth.check_interval {
+            return Ok(false);
+        }
+        self.last_check = Instant::now();
+        let size = std::fs::metadata(&self.segment_path)?.len();
+        if size > self.segment_size {
+            self.size_bytes += size - self.segment_size;
+            self.segment_size = size;
+            self.last_growth = Instant::now();
+            if size - self.segment_offset >= record_bytes {
+                return Ok(true);
+            }
+        }
+        let next = get_segment_path(&self.first_segment, self.segments_opened);
+        if is_segment_started(&next, &self.energy_widths)? {
+            self.pending_segments.push_back(next);
+            return Ok(true);
+        }
+        Ok(is_closed)
+    }
+
+    //The top hit is a default hit until more is written
+    fn wait_for_growth(&mut self) -> &CompassData {
+        let is_idle = self
+            .growth
+            .as_ref()
+            .is_some_and(|growth| self.last_growth.elapsed() >= growth.idle_timeout);
+        self.waiting = Some(if is_idle {
+            Waiting::Idle
+        } else {
+            Waiting::Growing
+        });
+        self.current_hit = CompassData::default();
+        &self.current_hit
+    }
+}
+
+//The segment numbered after the first one, as CoMPASS names them (see group_segments)
+fn get_segment_path(first_segment: &path::Path, number: usize) -> PathBuf {
+    let stem = first_segment
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut name = format!("{}_{}", stem, number);
+    if let Some(extension) = first_segment.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+    }
+    first_segment.with_file_name(name)
+}
+
+//Whether a segment still being written has its header and first record, which set its format, so
+//it can be opened. False for a segment not created yet.
+pub fn is_segment_started(
+    path: &path::Path,
+    energy_widths: &[EnergyWidth],
+) -> Result<bool, EVBError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    let mut head = vec![];
+    file.take(HEAD_BYTES).read_to_end(&mut head)?;
+    match parse_format(&head, head.len() as u64, energy_widths) {
+        Ok(format) => Ok(head.len() >= 2 + format.data_size_bytes),
+        Err(_) => Ok(false),
+    }
+}
+
+impl HitSource for CompassFile<'_> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            self.waiting = None;
+            if !self.is_record_written()? {
+                return Ok(self.wait_for_growth());
+            }
+            match self.parse_top_hit() {
+                Ok(data) => {
+                    self.is_used = false;
+                    self.current_hit = data;
+                }
+                Err(EVBError::File(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return self.end_segment();
+                }
+                Err(EVBError::CompassFile(error)) => {
+                    self.drop_corrupted_segment(error);
+                    return self.end_segment();
+                }
+                Err(x) => return Err(x),
+            }
         }
-        let (_dataslice, _flags) = parse_u32(dataslice)?;
 
-        Ok(CompassData::new(&raw_data, self.shift_map))
+        Ok(&self.current_hit)
     }
 
-    pub fn is_eof(&self) -> bool {
+    fn is_eof(&self) -> bool {
         self.is_eof
     }
 
-    pub fn set_hit_used(&mut self) {
+    fn set_hit_used(&mut self) {
         self.is_used = true;
     }
 
+    fn get_number_of_hits(&self) -> u64 {
+        self.size_bytes / self.get_record_size_bytes() as u64
+    }
+
+    fn get_waiting(&self) -> Option<Waiting> {
+        self.waiting
+    }
+}
+
+impl UsedSize for CompassFile<'_> {
+    //A mapped segment counts as far as it has been read, the pages of it the kernel may still hold.
+    //A buffered one counts its read buffer.
+    fn get_used_size(&self) -> usize {
+        let segment = match &self.segment {
+            Some(OpenSegment::Mapped(_)) => self.segment_offset as usize,
+            Some(OpenSegment::Buffered(reader, record)) => reader.capacity() + record.capacity(),
+            None => 0,
+        };
+        segment + std::mem::size_of::<CompassFile<'_>>()
+    }
+}
+
+//Files read on their own are not time shifted
+static NO_SHIFTS: Option<ShiftMap> = None;
+
+//The hits of one CoMPASS channel file, or of the segments of one channel in order, for programs
+//using the crate as a library. The energy width of each board is that of the channel map. A
+//corrupted segment is read up to the bad record, as in a build.
+pub struct CompassHits<'a> {
+    file: CompassFile<'a>,
+    is_done: bool,
+}
+
+impl<'a> CompassHits<'a> {
+    pub fn new(paths: &[PathBuf], map: &'a ChannelMap) -> Result<Self, EVBError> {
+        Self::with_access(paths, map, SegmentAccess::Mapped)
+    }
+
+    //Files still being written to should be read buffered, see SegmentAccess
+    pub fn with_access(
+        paths: &[PathBuf],
+        map: &'a ChannelMap,
+        access: SegmentAccess,
+    ) -> Result<Self, EVBError> {
+        Ok(CompassHits {
+            file: CompassFile::new_segmented(
+                paths,
+                &NO_SHIFTS,
+                map.get_energy_widths(),
+                &CorruptionLog::default(),
+                access,
+            )?
+            .with_timestamp_units(map.get_timestamp_units()),
+            is_done: false,
+        })
+    }
+
+    //From the size of the files, so only exact for files without waves
     pub fn get_number_of_hits(&self) -> u64 {
-        self.size_bytes / (self.data_size_bytes as u64)
+        self.file.get_number_of_hits()
+    }
+
+    //The hits not yet read, as a source to merge with the hits of other files (see HitMerge)
+    pub fn into_source(mut self) -> Box<dyn HitSource + 'a> {
+        self.file.set_hit_used();
+        Box::new(self.file)
+    }
+}
+
+impl Iterator for CompassHits<'_> {
+    type Item = Result<CompassData, EVBError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+        self.file.set_hit_used();
+        let hit = match self.file.get_top_hit() {
+            Ok(hit) => hit.clone(),
+            Err(e) => {
+                self.is_done = true;
+                return Some(Err(e));
+            }
+        };
+        self.is_done = self.file.is_eof();
+        (!self.is_done).then_some(Ok(hit))
     }
 }
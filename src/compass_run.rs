@@ -1,35 +1,177 @@
+use std::collections::HashSet;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use flate2::read::GzDecoder;
-use log::info;
+use log::{debug, info};
 use polars::prelude::*;
-use std::sync::{Arc, Mutex};
-use tar::Archive;
 
-use super::channel_data::ChannelData;
-use super::channel_map::{Board, ChannelMap};
-use super::compass_file::CompassFile;
+use super::batch_schedule::BatchSchedule;
+use super::build_diagnostics::{emit_error, emit_warning, DiagnosticGuard, DiagnosticLog};
+use super::calibration_spectra::{
+    get_calibration_spectra_path, CalibrationSpectra, CalibrationSpectraParams,
+};
+use super::campaign::{CampaignParams, CAMPAIGN_COLUMN};
+use super::channel_data::{ChannelData, ChannelDataField, TimeUnit, INVALID_VALUE};
+use super::channel_map::{Board, ChannelMap, ChannelType, DetectorGroup};
+use super::channel_rates::{ChannelRateParams, ChannelRates};
+use super::checkpoint::{
+    find_checkpoint, get_config_hash, remove_checkpoint, remove_later_fragments, skip_read_hits,
+    Checkpoint, CheckpointKey, CheckpointParams, Checkpointer, ResumeSummary,
+};
+use super::coincidence_trend::{CoincidenceTrend, CoincidenceTrendParams};
+use super::column_codec::ColumnCodec;
+use super::column_naming::ColumnNaming;
+use super::compass_data::CompassData;
+use super::compass_file::{group_segments, is_time_merged, CompassFile, SegmentAccess};
+use super::compass_settings::{is_settings_path, CompassSettings};
+use super::config_validation::find_channel_map_gaps;
+use super::corrupt_file::CorruptionLog;
+use super::csv_file::{is_csv_path, CsvFile};
+use super::data_reduction::{DataReductionParams, EventReducer};
+use super::delayed_window::{DelayedTagger, DelayedWindowParams, TaggedEvent};
+use super::detector_spectra::{DetectorSpectra, DetectorSpectraParams};
+use super::efficiency::EfficiencyEntry;
+use super::energy_calibration::EnergyCalibrationEntry;
+use super::energy_policy::{BadEnergyFilter, BadEnergyParams, BadEnergyPolicy};
+use super::energy_rank::EnergyRankParams;
+use super::energy_smearing::{EnergySmearer, EnergySmearingParams};
+use super::energy_threshold::{EnergyThresholdEntry, EnergyThresholds};
 use super::error::EVBError;
-use super::event_builder::EventBuilder;
+use super::event_builder::{BuildStrategy, EventBuilder, MissingTriggerBehavior, TriggerParams};
+use super::event_clock::{is_run_info_path, EventClock};
+use super::event_filter::EventFilterEntry;
+use super::focal_plane::SpsParams;
+use super::formatting::{format_bytes, format_clock_time, format_count, format_duration};
+use super::gain_drift::{GainAnchor, GainMap};
+use super::hdf5_writer::Hdf5Params;
+use super::histogram_only::{ChannelSpectra, HistogramOnlyParams};
+use super::hit_decoder::{get_decoder_threads, start_decoders};
+use super::hit_filter::{HitFilter, HitFilterStage, HitFilters};
+use super::hit_merge::HitMerge;
+use super::hit_source::HitSource;
+use super::hit_table::HitTable;
+use super::invalid_values::InvalidValues;
+use super::memory_profile::MemoryProfile;
+use super::multi_hit::MultiHitEntry;
+use super::network_sink::{NetworkSink, NetworkSinkParams};
+use super::output_format::{MemoryOutput, OutputFormats, OutputWriter, ParquetOutput};
+use super::output_sink::{JobSinks, OutputSink, OutputSinks, SinkFeed};
+use super::output_sort::RowOrder;
+use super::pair_window::{PairWindowEntry, PairWindows};
+use super::parallel_build::{get_builder_threads, BuiltChunk, ChunkBuilder};
+use super::paranoid::ParanoidChecker;
+use super::parquet_writer::{EventParquetStream, ParquetMetadata, StreamingParams};
+use super::passthrough::PassthroughEntry;
+use super::post_batch::{apply_post_batch_hooks, PostBatchHook, PostBatchHooks};
+use super::progress::{InputFile, JobProgress, ProgressReporter};
+use super::quick_build::{QuickBuildParams, TimeLimit};
+use super::quick_look::{QuickLookFilter, QuickLookParams};
+use super::rate_limit::{RateLimitParams, RateLimiter};
+use super::resource_limits::{EncodePoolGuard, ResourceLimits};
+use super::rf_reference::RfReference;
+use super::run_archive::{find_run_archive, unpack_run_archive};
+use super::run_boundary::{get_reset_times, BoundarySource, RunBoundary, RunBoundaryParams};
+use super::run_layout::{
+    RunLayout, DELAYED_STEM, EVENTS_STEM, HITS_STEM, QUICK_LOOK_STEM, SINGLES_STEM,
+};
+use super::run_list::{get_batch_summary_path, BatchStatus, BatchSummary};
+use super::run_log::{LogLevel, RunLogGuard};
+use super::run_merge::{get_merged_path, merge_runs};
+use super::run_metadata::{get_channel_map_hash, RunMetadata};
+use super::run_qa::{check_run, QaParams};
+use super::run_report::{RunReport, RunReportLabels};
+use super::run_statistics::{log_largest_gaps, RunStatistics};
+use super::run_stitching::{RunStitcher, RunStitchingParams};
 use super::scaler_list::{ScalerEntryUI, ScalerList};
+use super::scaler_rates::{ScalerRateParams, ScalerRates};
+use super::shared_memory::{SharedMemoryParams, SharedMemoryWriter};
 use super::shift_map::{ShiftMap, ShiftMapEntry};
+use super::shutdown::is_shutdown_requested;
+use super::sim_file::{is_sim_path, SimFile};
+use super::skip_list::{SkipEntry, SkipList};
+use super::slow_control::{is_slow_control_path, SlowControlTable};
+use super::state_spectra::StateSpectra;
+use super::time_calibration::{CampaignTimeOffsets, PulserParams, TimeCalibrator};
+use super::time_differences::{TimeDifferenceParams, TimeDifferences};
+use super::time_jitter::{TimeJitter, TimeJitterParams};
+use super::time_reversal::ReversalCheck;
+use super::time_walk::{TimeWalk, TimeWalkParams};
+use super::timestamp_sanitizer::{TimestampSanitizer, TimestampSanitizerParams};
+use super::unmapped::{get_unmapped_summary, UnmappedPolicy};
 use super::used_size::UsedSize;
-
-//Maximum allowed size for a single dataframe: 8GB
-const MAX_USED_SIZE: usize = 8_000_000_000;
+use super::waveform::{WaveformHandler, WaveformMode, WaveformParams};
+use super::write_limit::{ThrottledWriter, WriteLimitParams};
 
 #[derive(Debug)]
 struct RunParams<'a> {
     pub run_archive_path: PathBuf,
     pub unpack_dir_path: PathBuf,
-    pub output_file_path: PathBuf,
+    pub layout: RunLayout,
     pub scalerlist: Vec<ScalerEntryUI>,
-    pub scalerout_file_path: PathBuf,
+    pub slow_control_pattern: &'a str,
     pub channel_map: &'a ChannelMap,
     pub shift_map: &'a Option<ShiftMap>,
     pub coincidence_window: f64,
+    //Empty for a single coincidence window
+    pub pair_windows: &'a [PairWindowEntry],
+    pub channel_map_hash: &'a str,
+    pub data_template: &'a ChannelData,
+    pub delayed_template: &'a ChannelData,
     pub run_number: i32,
+    pub output_sinks: &'a mut [Box<dyn OutputSink>],
+    pub post_batch_hooks: &'a mut [Box<dyn PostBatchHook>],
+    pub hit_filters: &'a mut [Box<dyn HitFilter>],
+    pub delayed_window: &'a DelayedWindowParams,
+    pub gain_map: &'a Option<GainMap>,
+    pub trigger: &'a TriggerParams,
+    pub column_codecs: &'a [ColumnCodec],
+    pub is_pulser_run: bool,
+    pub write_hits: bool,
+    pub keep_raw_times: bool,
+    pub sort_column: &'a str,
+    pub quick_build: Option<&'a QuickBuildParams>,
+    pub paranoid: bool,
+    pub data_reduction: &'a DataReductionParams,
+    pub energy_smearing: &'a EnergySmearingParams,
+    pub time_reversed_check: bool,
+    pub write_limit: &'a WriteLimitParams,
+    pub quick_look: &'a QuickLookParams,
+    pub skip_files: &'a [SkipEntry],
+    pub time_jitter: &'a TimeJitterParams,
+    pub time_walk: &'a TimeWalkParams,
+    pub timestamp_sanitizer: &'a TimestampSanitizerParams,
+    pub run_boundaries: &'a RunBoundaryParams,
+    pub calibration_spectra: &'a mut Option<CalibrationSpectra>,
+    //Saved once, by the first run with time calibrator events, in the output directory
+    pub time_offsets: &'a mut Option<CampaignTimeOffsets>,
+    pub output_dir: &'a Path,
+    pub run_stitcher: &'a mut Option<RunStitcher>,
+    pub streaming: &'a StreamingParams,
+    pub waveforms: &'a WaveformParams,
+    pub bad_energies: &'a BadEnergyParams,
+    pub rate_limit: &'a RateLimitParams,
+    pub energy_thresholds: &'a [EnergyThresholdEntry],
+    pub output_formats: OutputFormats,
+    pub hdf5: &'a Hdf5Params,
+    pub memory_output: Option<&'a MemoryOutput>,
+    pub unmapped_channels: UnmappedPolicy,
+    pub time_differences: &'a TimeDifferenceParams,
+    pub scaler_rates: &'a ScalerRateParams,
+    pub coincidence_trend: &'a CoincidenceTrendParams,
+    pub channel_rates: &'a ChannelRateParams,
+    pub checkpoints: &'a CheckpointParams,
+    pub detector_spectra: &'a DetectorSpectraParams,
+    pub histogram_only: &'a HistogramOnlyParams,
+    pub campaign: &'a CampaignParams,
+    pub worker_threads: usize,
+    pub build_threads: usize,
+    pub max_threads: usize,
+    //Bytes a table may hold before it is written out as a fragment
+    pub memory_budget: usize,
+    pub diagnostics: &'a DiagnosticLog,
+    pub log_level: LogLevel,
+    pub config_text: &'a str,
 }
 
 fn clean_up_unpack_dir(unpack_dir: &Path) -> Result<(), EVBError> {
@@ -42,151 +184,1835 @@ fn clean_up_unpack_dir(unpack_dir: &Path) -> Result<(), EVBError> {
     Ok(())
 }
 
-fn write_dataframe(data: ChannelData, filepath: &Path) -> Result<(), PolarsError> {
-    info!("Writing dataframe to disk at {}", filepath.display());
-    let columns: Vec<Series> = data.convert_to_series();
-    let mut df = DataFrame::new(columns)?;
-    let mut output_file = File::create(filepath)?;
-    ParquetWriter::new(&mut output_file).finish(&mut df)?;
-    Ok(())
+//How the event tables of a run are written
+struct TableOutput<'a> {
+    codecs: &'a [ColumnCodec],
+    //How the events of this run were altered, written to every events and delayed file
+    provenance: ParquetMetadata,
+    write_limit: &'a WriteLimitParams,
+    formats: OutputFormats,
+    hdf5: &'a Hdf5Params,
+    memory: Option<&'a MemoryOutput>,
+    //Campaign configuration number, added as a column to every table when enabled
+    campaign_column: Option<f64>,
+    row_order: RowOrder<'a>,
+}
+
+//The columns of a table as they are written, before the post batch hooks
+fn get_table_frame(data: ChannelData, output: &TableOutput<'_>) -> Result<DataFrame, PolarsError> {
+    let rows = data.rows;
+    let invalid = output
+        .row_order
+        .get_column()
+        .map_or(INVALID_VALUE, |column| data.get_invalid_value(column));
+    let mut columns: Vec<Series> = data.convert_to_series();
+    if let Some(configuration) = output.campaign_column {
+        columns.push(Series::new(CAMPAIGN_COLUMN, vec![configuration; rows]));
+    }
+    output.row_order.apply(DataFrame::new(columns)?, invalid)
 }
 
-fn write_dataframe_fragment(
+fn write_dataframe(
     data: ChannelData,
-    out_dir: &Path,
-    run_number: &i32,
-    frag_number: &i32,
-) -> Result<(), PolarsError> {
-    let frag_file_path = out_dir.join(format!("run_{}_{}.parquet", run_number, frag_number));
-    write_dataframe(data, &frag_file_path)?;
+    filepath: &Path,
+    run_number: i32,
+    hooks: &mut [Box<dyn PostBatchHook>],
+    output: &TableOutput<'_>,
+    profile: &mut MemoryProfile,
+) -> Result<(), EVBError> {
+    let baseline = profile.begin_write();
+    let mut metadata = data.get_metadata();
+    metadata.extend(&output.provenance);
+    let event_column = data.naming.get_field_name(&ChannelDataField::EventID);
+    let mut df = apply_post_batch_hooks(hooks, run_number, get_table_frame(data, output)?)?;
+    let parquet = ParquetOutput::new(&event_column)
+        .with_codecs(output.codecs)
+        .with_write_limit(output.write_limit);
+    for writer in output.formats.get_writers(parquet, output.hdf5) {
+        writer.write_table(&mut df, filepath, &metadata)?;
+    }
+    if let Some(memory) = output.memory {
+        memory.write_table(&mut df, filepath, &metadata)?;
+    }
+    drop(df);
+    profile.end_write(baseline);
+    Ok(())
+}
+
+//An events or delayed table written to its file in pieces while the run is built, so the whole
+//table is never held in memory
+struct StreamedTable {
+    filepath: PathBuf,
+    stream: Option<EventParquetStream<ThrottledWriter<File>>>,
+}
+
+impl StreamedTable {
+    fn new(filepath: PathBuf) -> Self {
+        StreamedTable {
+            filepath,
+            stream: None,
+        }
+    }
+
+    //The file is created with the first piece, which also sets the columns
+    fn write_piece(
+        &mut self,
+        data: ChannelData,
+        run_number: i32,
+        hooks: &mut [Box<dyn PostBatchHook>],
+        output: &TableOutput<'_>,
+        profile: &mut MemoryProfile,
+    ) -> Result<(), PolarsError> {
+        let baseline = profile.begin_write();
+        if self.stream.is_none() {
+            info!("Streaming dataframe to disk at {}", self.filepath.display());
+            let mut metadata = data.get_metadata();
+            metadata.extend(&output.provenance);
+            self.stream = Some(EventParquetStream::new(
+                ThrottledWriter::new(File::create(&self.filepath)?, output.write_limit),
+                &metadata,
+                &data.naming.get_field_name(&ChannelDataField::EventID),
+                output.codecs,
+            ));
+        }
+        let mut df = apply_post_batch_hooks(hooks, run_number, get_table_frame(data, output)?)?;
+        if let Some(stream) = &mut self.stream {
+            stream.write_piece(&mut df)?;
+        }
+        drop(df);
+        profile.end_write(baseline);
+        Ok(())
+    }
+
+    //The provenance may have grown since the first piece, e.g. by the hit counts of the run
+    fn finish(self, output: &TableOutput<'_>) -> Result<(), PolarsError> {
+        if let Some(mut stream) = self.stream {
+            stream.insert_file_metadata(&output.provenance);
+            stream.finish()?;
+        }
+        Ok(())
+    }
+}
+
+fn append_tagged_event(data: &mut ChannelData, tagged: TaggedEvent, map: &ChannelMap) {
+    if !data.append_event(&tagged.hits, map) {
+        return;
+    }
+    if let Some((prompt_id, delay)) = tagged.prompt {
+        data.set_event_value(&ChannelDataField::PromptEventID, prompt_id as f64);
+        data.set_event_value(&ChannelDataField::PromptDelayTime, delay);
+    }
+}
+
+//The tables of a run still being filled, with the number of fragments of each already written.
+//When the runs of a concatenated file are split, every run in the file gets its own tables.
+//Streamed tables are written in pieces of chunk_rows events, or less once a piece reaches the
+//memory budget, and never fragmented.
+struct RunTables {
+    layout: RunLayout,
+    events: ChannelData,
+    delayed: ChannelData,
+    events_stream: Option<StreamedTable>,
+    singles_stream: Option<StreamedTable>,
+    delayed_stream: Option<StreamedTable>,
+    hits: Option<HitTable>,
+    quick_look: ChannelData,
+    events_fragments: i32,
+    delayed_fragments: i32,
+    hits_fragments: i32,
+}
+
+impl RunTables {
+    fn new(params: &RunParams<'_>, layout: RunLayout) -> Self {
+        let stream_table = |stem: &str, is_written: bool| {
+            if params.streaming.enabled && !params.histogram_only.enabled && is_written {
+                Some(StreamedTable::new(layout.get_table_path(stem, None)))
+            } else {
+                None
+            }
+        };
+        RunTables {
+            events_stream: stream_table(EVENTS_STEM, true),
+            singles_stream: stream_table(SINGLES_STEM, params.data_template.is_split_by_class()),
+            delayed_stream: stream_table(DELAYED_STEM, params.delayed_window.enabled),
+            layout,
+            events: params.data_template.clone(),
+            delayed: params.delayed_template.clone(),
+            hits: if params.write_hits && !params.histogram_only.enabled {
+                Some(HitTable::new(
+                    params.data_template.time_unit,
+                    params.keep_raw_times,
+                ))
+            } else {
+                None
+            },
+            quick_look: params.data_template.clone(),
+            events_fragments: 0,
+            delayed_fragments: 0,
+            hits_fragments: 0,
+        }
+    }
+
+    //Whether write_full_events will write a fragment of the events, for sampling the memory
+    //before it does
+    fn is_events_fragment_due(&self, params: &RunParams<'_>) -> bool {
+        !params.histogram_only.enabled
+            && self.events_stream.is_none()
+            && self.events.get_used_size() > params.memory_budget
+    }
+
+    //Streamed tables are written a piece at a time, cut early when the piece grows past the memory
+    //budget, the others are fragmented when they grow too large. Without tables to write the
+    //events are simply dropped.
+    fn write_full_events(
+        &mut self,
+        params: &mut RunParams<'_>,
+        output: &TableOutput<'_>,
+        profile: &mut MemoryProfile,
+    ) -> Result<(), EVBError> {
+        if params.histogram_only.enabled {
+            if self.events.get_used_size() > params.memory_budget {
+                self.events = params.data_template.clone();
+            }
+        } else if let Some(stream) = &mut self.events_stream {
+            if self.events.rows >= params.streaming.chunk_rows
+                || self.events.get_used_size() > params.memory_budget
+            {
+                write_event_pieces(
+                    std::mem::replace(&mut self.events, params.data_template.clone()),
+                    stream,
+                    &mut self.singles_stream,
+                    params,
+                    output,
+                    profile,
+                )?;
+            }
+        } else if self.events.get_used_size() > params.memory_budget {
+            self.write_events_fragment(params, output, profile)?;
+        }
+        Ok(())
+    }
+
+    fn write_events_fragment(
+        &mut self,
+        params: &mut RunParams<'_>,
+        output: &TableOutput<'_>,
+        profile: &mut MemoryProfile,
+    ) -> Result<(), EVBError> {
+        write_events_table(
+            std::mem::replace(&mut self.events, params.data_template.clone()),
+            &self.layout,
+            Some(self.events_fragments),
+            params,
+            output,
+            profile,
+        )?;
+        self.events_fragments += 1;
+        Ok(())
+    }
+
+    fn write_hits_fragment(&mut self, params: &RunParams<'_>) -> Result<(), EVBError> {
+        if let Some(table) = self.hits.replace(HitTable::new(
+            params.data_template.time_unit,
+            params.keep_raw_times,
+        )) {
+            table.write_table(
+                &self
+                    .layout
+                    .get_table_path(HITS_STEM, Some(self.hits_fragments)),
+                params.column_codecs,
+                params.write_limit,
+            )?;
+            self.hits_fragments += 1;
+        }
+        Ok(())
+    }
+
+    //Everything built so far is written out, so a checkpoint only needs what is still open. Empty
+    //tables are left for later.
+    fn write_checkpoint_fragments(
+        &mut self,
+        params: &mut RunParams<'_>,
+        output: &TableOutput<'_>,
+        profile: &mut MemoryProfile,
+    ) -> Result<(), EVBError> {
+        if self.events.rows > 0 {
+            self.write_events_fragment(params, output, profile)?;
+        }
+        if self.hits.as_ref().is_some_and(|table| !table.is_empty()) {
+            self.write_hits_fragment(params)?;
+        }
+        Ok(())
+    }
+
+    //Once write_checkpoint_fragments has written the tables
+    fn get_checkpoint(
+        &self,
+        key: &CheckpointKey,
+        evb: &EventBuilder,
+        hits_read: &[u64],
+        events: u64,
+        filtered_events: u64,
+    ) -> Checkpoint {
+        Checkpoint {
+            key: key.clone(),
+            hits_read: hits_read.to_vec(),
+            builder: evb.get_checkpoint(),
+            events,
+            filtered_events,
+            events_fragments: self.events_fragments,
+            hits_fragments: self.hits_fragments,
+        }
+    }
+}
+
+//Fills the event time columns of the rows of the chunks. The chunks are in build order, so the
+//first event of each still gets its time from the last of the chunk before.
+fn set_chunk_event_times(
+    mut chunks: Vec<BuiltChunk>,
+    event_clock: &mut Option<EventClock>,
+) -> Vec<BuiltChunk> {
+    if let Some(clock) = event_clock {
+        for chunk in chunks.iter_mut() {
+            let mut row = 0;
+            for (event, is_accepted) in chunk.events.iter().zip(chunk.accepted.iter()) {
+                let times = clock.next_event(event);
+                if *is_accepted {
+                    times.set_row_values(&mut chunk.table, row);
+                    row += 1;
+                }
+            }
+        }
+    }
+    chunks
+}
+
+//Adds the events of chunks built on the builder threads to the events table, in order. The chunk
+//tables are already filled, so only the statistics and the data reduction are left to do for each
+//event. Gives the events added and those rejected by the event filters.
+fn add_built_chunks(
+    chunks: Vec<BuiltChunk>,
+    events: &mut ChannelData,
+    map: &ChannelMap,
+    statistics: &mut RunStatistics,
+    reversal_check: &mut Option<ReversalCheck>,
+    time_calibrator: &mut Option<TimeCalibrator>,
+    reducer: &mut EventReducer,
+) -> (u64, u64) {
+    let mut added: u64 = 0;
+    let mut filtered: u64 = 0;
+    for mut chunk in chunks {
+        let mut rows_kept = vec![];
+        for (event, is_accepted) in chunk.events.iter().zip(chunk.accepted.iter()) {
+            statistics.add_event(event, map);
+            if let Some(check) = reversal_check {
+                check.add_forward_event(event);
+            }
+            if let Some(calibrator) = time_calibrator {
+                calibrator.add_event(event, map);
+            }
+            let is_kept = reducer.keep_event();
+            if *is_accepted {
+                rows_kept.push(is_kept);
+            } else if is_kept {
+                filtered += 1;
+            }
+            added += 1;
+        }
+        if rows_kept.contains(&false) {
+            chunk.table.retain_rows(&rows_kept);
+        }
+        events.append_table(chunk.table);
+    }
+    (added, filtered)
+}
+
+//With the events split by class the singles go to a table of their own, fragmented along with
+//the coincidences
+fn write_events_table(
+    mut events: ChannelData,
+    layout: &RunLayout,
+    fragment: Option<i32>,
+    params: &mut RunParams<'_>,
+    output: &TableOutput<'_>,
+    profile: &mut MemoryProfile,
+) -> Result<(), EVBError> {
+    if let Some(singles) = events.split_singles() {
+        write_dataframe(
+            singles,
+            &layout.get_table_path(SINGLES_STEM, fragment),
+            params.run_number,
+            params.post_batch_hooks,
+            output,
+            profile,
+        )?;
+    }
+    write_dataframe(
+        events,
+        &layout.get_table_path(EVENTS_STEM, fragment),
+        params.run_number,
+        params.post_batch_hooks,
+        output,
+        profile,
+    )
+}
+
+//The same for a piece of streamed events
+fn write_event_pieces(
+    mut events: ChannelData,
+    stream: &mut StreamedTable,
+    singles_stream: &mut Option<StreamedTable>,
+    params: &mut RunParams<'_>,
+    output: &TableOutput<'_>,
+    profile: &mut MemoryProfile,
+) -> Result<(), EVBError> {
+    if let (Some(singles), Some(singles_stream)) = (events.split_singles(), singles_stream) {
+        singles_stream.write_piece(
+            singles,
+            params.run_number,
+            params.post_batch_hooks,
+            output,
+            profile,
+        )?;
+    }
+    stream.write_piece(
+        events,
+        params.run_number,
+        params.post_batch_hooks,
+        output,
+        profile,
+    )?;
+    Ok(())
+}
+
+//The last piece of a table is only numbered if the table was fragmented
+fn get_last_fragment(fragments: i32) -> Option<i32> {
+    if fragments == 0 {
+        None
+    } else {
+        Some(fragments)
+    }
+}
+
+//Writes what is left of the tables, at the end of a run or of one run of a concatenated file
+fn write_run_tables(
+    tables: RunTables,
+    params: &mut RunParams<'_>,
+    output: &TableOutput<'_>,
+    quick_look_output: &TableOutput<'_>,
+    profile: &mut MemoryProfile,
+) -> Result<(), EVBError> {
+    if params.histogram_only.enabled {
+        return Ok(());
+    }
+    match tables.events_stream {
+        Some(mut stream) => {
+            let mut singles_stream = tables.singles_stream;
+            write_event_pieces(
+                tables.events,
+                &mut stream,
+                &mut singles_stream,
+                params,
+                output,
+                profile,
+            )?;
+            stream.finish(output)?;
+            if let Some(singles) = singles_stream {
+                singles.finish(output)?;
+            }
+        }
+        None => write_events_table(
+            tables.events,
+            &tables.layout,
+            get_last_fragment(tables.events_fragments),
+            params,
+            output,
+            profile,
+        )?,
+    }
+    if let Some(mut stream) = tables.delayed_stream {
+        stream.write_piece(
+            tables.delayed,
+            params.run_number,
+            params.post_batch_hooks,
+            output,
+            profile,
+        )?;
+        stream.finish(output)?;
+    } else if params.delayed_window.enabled {
+        write_dataframe(
+            tables.delayed,
+            &tables
+                .layout
+                .get_table_path(DELAYED_STEM, get_last_fragment(tables.delayed_fragments)),
+            params.run_number,
+            params.post_batch_hooks,
+            output,
+            profile,
+        )?;
+    }
+    if let Some(table) = tables.hits {
+        table.write_table(
+            &tables
+                .layout
+                .get_table_path(HITS_STEM, get_last_fragment(tables.hits_fragments)),
+            params.column_codecs,
+            params.write_limit,
+        )?;
+    }
+    if params.quick_look.enabled {
+        write_dataframe(
+            tables.quick_look,
+            &tables.layout.get_table_path(QUICK_LOOK_STEM, None),
+            params.run_number,
+            params.post_batch_hooks,
+            quick_look_output,
+            profile,
+        )?;
+    }
     Ok(())
 }
 
+//Builder memory covers everything held between reading and writing, for both windows
+fn sample_memory(
+    profile: &mut MemoryProfile,
+    files: &[Box<dyn HitSource + '_>],
+    builders: &[&dyn UsedSize],
+) {
+    let reader_bytes: usize = files.iter().map(|file| file.get_used_size()).sum();
+    let builder_bytes: usize = builders.iter().map(|builder| builder.get_used_size()).sum();
+    profile.sample(reader_bytes, builder_bytes);
+}
+
+//Trigger channels that can open events in this run. Fails if the channel map does not have the
+//trigger channel, or if the run has one file per channel and none of them is a trigger channel.
+//Time-merged and CSV inputs can only be checked once all hits were read.
+fn get_trigger_uuids(
+    params: &RunParams<'_>,
+    files: &mut [Box<dyn HitSource + '_>],
+    is_per_channel_input: bool,
+) -> Result<Option<HashSet<u32>>, EVBError> {
+    let channel_type = match params.trigger.channel {
+        Some(channel_type) => channel_type,
+        None => return Ok(None),
+    };
+    let uuids = params.channel_map.get_uuids_of_type(channel_type);
+    if uuids.is_empty() {
+        return Err(EVBError::MissingTrigger(format!(
+            "{} is not in the channel map",
+            channel_type
+        )));
+    }
+    if is_per_channel_input {
+        let mut has_trigger_file = false;
+        for file in files.iter_mut() {
+            let hit = file.get_top_hit()?;
+            has_trigger_file |= !hit.is_default() && uuids.contains(&hit.uuid);
+        }
+        if !has_trigger_file {
+            return Err(EVBError::MissingTrigger(format!(
+                "run {} has no hits in {}",
+                params.run_number, channel_type
+            )));
+        }
+    }
+    Ok(Some(uuids))
+}
+
 //Main function which processes a single run archive and writes the resulting event built data to parquet file
-fn process_run(params: RunParams<'_>, progress: Arc<Mutex<f32>>) -> Result<(), EVBError> {
+//Returns whether the run was cut short by SIGINT or SIGTERM
+fn process_run(mut params: RunParams<'_>, progress: JobProgress) -> Result<bool, EVBError> {
+    params.layout.create_dirs()?;
+    let _run_log = RunLogGuard::new(&params.layout.get_log_path(), params.log_level)?;
+    info!(
+        "Building run {} into {}",
+        params.run_number,
+        params.layout.get_dir().display()
+    );
+    info!("Config:\n{}", params.config_text.trim_end());
+    match build_run(&mut params, true, progress.clone()) {
+        Err(EVBError::MissingTrigger(reason))
+            if params.trigger.on_missing == MissingTriggerBehavior::EarliestHit =>
+        {
+            emit_warning(
+                "missing_trigger",
+                String::new(),
+                format!(
+                    "!!! No trigger hits ({}), building the run in earliest hit mode instead !!!",
+                    reason
+                ),
+            );
+            build_run(&mut params, false, progress)
+        }
+        result => result,
+    }
+}
+
+fn build_run(
+    params: &mut RunParams<'_>,
+    use_trigger: bool,
+    progress: JobProgress,
+) -> Result<bool, EVBError> {
     //Protective, ensure no loose files
     clean_up_unpack_dir(&params.unpack_dir_path)?;
 
-    let archive_file = File::open(&params.run_archive_path)?;
-    let mut decompressed_archive = Archive::new(GzDecoder::new(archive_file));
-    decompressed_archive.unpack(&params.unpack_dir_path)?;
+    unpack_run_archive(&params.run_archive_path, &params.unpack_dir_path)?;
 
-    let mut scaler_list = Some(ScalerList::new(params.scalerlist));
+    let mut scaler_list = Some(ScalerList::new(params.scalerlist.clone()));
+    let memory_profile = MemoryProfile::new(params.memory_budget);
 
-    //Collect all files from unpack, separate scalers from normal files
-    let mut files: Vec<CompassFile<'_>> = vec![];
-    let mut total_count: u64 = 0;
+    //Collect all files from unpack, separate scalers and slow control logs from normal files
+    let mut data_paths: Vec<PathBuf> = vec![];
+    let mut slow_control_paths: Vec<PathBuf> = vec![];
+    let mut run_info_path: Option<PathBuf> = None;
+    let mut settings_path: Option<PathBuf> = None;
+    let mut skip_list = SkipList::new(params.skip_files, params.run_number);
     for item in params.unpack_dir_path.read_dir()? {
-        let filepath = &item?.path();
+        let filepath = item?.path();
+        if skip_list.check_file(&filepath) {
+            continue;
+        }
+        if is_slow_control_path(&filepath, params.slow_control_pattern) {
+            debug!("Slow control log {}", filepath.display());
+            slow_control_paths.push(filepath);
+            continue;
+        }
+        if is_run_info_path(&filepath) {
+            debug!("Run info {}", filepath.display());
+            run_info_path = Some(filepath);
+            continue;
+        }
+        if is_settings_path(&filepath) {
+            debug!("CoMPASS settings {}", filepath.display());
+            settings_path = Some(filepath);
+            continue;
+        }
         match &mut scaler_list {
             Some(list) => {
-                if list.read_scaler(filepath) {
+                if list.read_scaler(&filepath, params.channel_map.get_energy_widths()) {
+                    debug!("Scaler file {}", filepath.display());
                     continue;
                 }
             }
             None => (),
         };
+        debug!(
+            "Data file {} ({})",
+            filepath.display(),
+            format_bytes(std::fs::metadata(&filepath)?.len() as usize)
+        );
+        data_paths.push(filepath);
+    }
+
+    skip_list.warn_unused();
+
+    //Segments of the same channel are chained, so only one file per channel is open at a time.
+    //Legacy CSV exports and simulated hits are read as their own hit streams.
+    let (sim_paths, data_paths): (Vec<PathBuf>, Vec<PathBuf>) =
+        data_paths.into_iter().partition(|path| is_sim_path(path));
+    let (csv_paths, binary_paths): (Vec<PathBuf>, Vec<PathBuf>) =
+        data_paths.into_iter().partition(|path| is_csv_path(path));
+    let mut files: Vec<Box<dyn HitSource + '_>> = vec![];
+    //Names and sizes of the sources, in the same order, for the progress reports
+    let mut inputs: Vec<InputFile> = vec![];
+    let corruption = CorruptionLog::default();
+    for path in sim_paths.iter() {
+        info!("Reading simulated hits from {}", path.display());
+        files.push(Box::new(SimFile::new(path, params.channel_map)?));
+        inputs.push(InputFile::new(std::slice::from_ref(path)));
+    }
+    for path in csv_paths.iter() {
+        info!("Reading CSV hits from {}", path.display());
+        files.push(Box::new(
+            CsvFile::new(path, params.shift_map)?
+                .with_timestamp_units(params.channel_map.get_timestamp_units()),
+        ));
+        inputs.push(InputFile::new(std::slice::from_ref(path)));
+    }
+    //Positions of the time-merged files among the sources, files that cannot be read are reported
+    //when they are opened
+    let mut merged_inputs: Vec<usize> = vec![];
+    for segments in group_segments(binary_paths) {
+        if is_time_merged(&segments, params.channel_map.get_energy_widths()).unwrap_or(false) {
+            info!(
+                "{} is a time-merged file of several channels",
+                segments[0].display()
+            );
+            merged_inputs.push(files.len());
+        }
+        inputs.push(InputFile::new(&segments));
+        files.push(Box::new(
+            CompassFile::new_segmented(
+                &segments,
+                params.shift_map,
+                params.channel_map.get_energy_widths(),
+                &corruption,
+                SegmentAccess::Mapped,
+            )?
+            .with_timestamp_units(params.channel_map.get_timestamp_units())
+            .with_waveforms(WaveformHandler::new(
+                params.waveforms,
+                params.channel_map,
+                params.layout.get_waveform_dir(),
+            )),
+        ));
+    }
+    let thresholds = EnergyThresholds::new(params.energy_thresholds);
+    if let Some(thresholds) = &thresholds {
+        files = files
+            .into_iter()
+            .map(|file| thresholds.apply(file))
+            .collect();
+    }
+    let unpacked = UnpackedRun {
+        scaler_list,
+        memory_profile,
+        slow_control_paths,
+        run_info_path,
+        settings_path,
+        skip_list,
+        thresholds,
+        has_text_input: !csv_paths.is_empty() || !sim_paths.is_empty(),
+        inputs,
+        merged_inputs,
+        corruption,
+    };
+    let threads = get_decoder_threads(params.worker_threads, params.max_threads);
+    let result = if threads > 1 {
+        info!("Decoding the data files on {} threads", threads);
+        std::thread::scope(|scope| {
+            let files = start_decoders(scope, files, threads);
+            merge_run(params, files, unpacked, use_trigger, progress)
+        })
+    } else {
+        merge_run(params, files, unpacked, use_trigger, progress)
+    };
+
+    let is_partial = result?;
+
+    //To be safe, all files in the unpack dir are dropped (and decoders joined) before deleting them
+    clean_up_unpack_dir(&params.unpack_dir_path)?;
+    Ok(is_partial)
+}
 
-        files.push(CompassFile::new(filepath, params.shift_map)?);
-        files.last_mut().unwrap().set_hit_used();
-        files.last_mut().unwrap().get_top_hit()?;
-        total_count += files.last().unwrap().get_number_of_hits();
+//What is left of unpacking a run archive besides the data files
+struct UnpackedRun<'a> {
+    scaler_list: Option<ScalerList>,
+    memory_profile: MemoryProfile,
+    slow_control_paths: Vec<PathBuf>,
+    //The CoMPASS run.info, with the start time of the run
+    run_info_path: Option<PathBuf>,
+    //The CoMPASS settings.xml, with the settings of the boards
+    settings_path: Option<PathBuf>,
+    skip_list: SkipList<'a>,
+    //Applied to the sources as they are read, for the counts of suppressed hits
+    thresholds: Option<EnergyThresholds>,
+    //CSV exports and simulated hits, in which trigger hits cannot be checked up front
+    has_text_input: bool,
+    inputs: Vec<InputFile>,
+    merged_inputs: Vec<usize>,
+    corruption: CorruptionLog,
+}
+
+fn is_rf_hit(rf_reference: &mut Option<RfReference>, hit: &CompassData) -> bool {
+    rf_reference
+        .as_mut()
+        .is_some_and(|reference| reference.add_hit(hit))
+}
+
+fn is_scaler_hit(scaler_rates: &mut Option<ScalerRates>, hit: &CompassData) -> bool {
+    scaler_rates
+        .as_mut()
+        .is_some_and(|rates| rates.add_hit(hit))
+}
+
+//Merges the hits of the data files into events and writes the tables and report of the run
+fn merge_run<'a>(
+    params: &mut RunParams<'a>,
+    mut files: Vec<Box<dyn HitSource + 'a>>,
+    unpacked: UnpackedRun<'a>,
+    use_trigger: bool,
+    progress: JobProgress,
+) -> Result<bool, EVBError> {
+    let UnpackedRun {
+        scaler_list,
+        mut memory_profile,
+        slow_control_paths,
+        run_info_path,
+        settings_path,
+        skip_list,
+        thresholds,
+        has_text_input,
+        inputs,
+        merged_inputs,
+        corruption,
+    } = unpacked;
+    //CoMPASS can write all channels to one already time-merged file, which time shifts put out of
+    //order by up to the spread of the shifts
+    let is_merged_input = !merged_inputs.is_empty();
+    let merged_shift_spread = match params.shift_map {
+        Some(map) if is_merged_input => map.get_shift_spread(),
+        _ => 0.0,
+    };
+    if merged_shift_spread > 0.0 {
+        info!(
+            "Re-sorting the time-merged input over {} ns after the time shifts",
+            merged_shift_spread
+        );
+    }
+    let timestamp_sanitizer =
+        TimestampSanitizer::new(params.timestamp_sanitizer, merged_shift_spread);
+    if let Some(sanitizer) = &timestamp_sanitizer {
+        files = files
+            .into_iter()
+            .map(|file| sanitizer.correct_rollovers(file))
+            .collect();
+    }
+    //Boundaries are found in the raw hits of each file, before any jitter re-sorts them
+    if params.run_boundaries.enabled {
+        files = files
+            .into_iter()
+            .map(|file| -> Box<dyn HitSource + '_> {
+                Box::new(BoundarySource::new(file, params.run_boundaries))
+            })
+            .collect();
+    }
+    if let Some(sanitizer) = &timestamp_sanitizer {
+        files = files
+            .into_iter()
+            .enumerate()
+            .map(|(index, file)| sanitizer.reorder(file, merged_inputs.contains(&index)))
+            .collect();
+    }
+    //Corrected from the raw energies, after the time shifts and before any jitter
+    let time_walk = TimeWalk::new(params.time_walk, params.channel_map)?;
+    if let Some(walk) = &time_walk {
+        files = files.into_iter().map(|file| walk.wrap(file)).collect();
+    }
+    let time_jitter = TimeJitter::new(params.time_jitter, params.channel_map, params.run_number);
+    if let Some(jitter) = &time_jitter {
+        files = files
+            .into_iter()
+            .enumerate()
+            .map(|(index, file)| jitter.wrap(file, index))
+            .collect();
     }
+    let mut file_hits: Vec<u64> = vec![];
+    for file in files.iter_mut() {
+        file.set_hit_used();
+        file.get_top_hit()?;
+        file_hits.push(file.get_number_of_hits());
+    }
+    let total_count: u64 = file_hits.iter().sum();
 
-    let mut evb = EventBuilder::new(&params.coincidence_window);
-    let mut analyzed_data = ChannelData::default();
+    //Rejected when the config is checked, but runs can be built from params never checked
+    let is_fixed_window = params.trigger.strategy == BuildStrategy::FixedWindow;
+    if let (true, Some(channel_type)) = (is_fixed_window, params.trigger.channel) {
+        emit_warning(
+            "fixed_window_trigger",
+            channel_type.to_string(),
+            format!(
+                "Trigger channel {} is ignored, fixed window events are opened by any hit",
+                channel_type
+            ),
+        );
+    }
+    let trigger_uuids = if use_trigger && !is_fixed_window {
+        get_trigger_uuids(params, &mut files, !is_merged_input && !has_text_input)?
+    } else {
+        None
+    };
+    let is_trigger_mode = trigger_uuids.is_some();
+    let mut reversal_check = if params.time_reversed_check {
+        info!("Time-reversed check is enabled, every hit is kept until the end of the run");
+        Some(ReversalCheck::new(
+            trigger_uuids.clone(),
+            params.trigger.strategy,
+        ))
+    } else {
+        None
+    };
+    let mut evb =
+        EventBuilder::new(&params.coincidence_window).with_strategy(params.trigger.strategy);
+    if let Some(uuids) = trigger_uuids {
+        evb = evb.with_trigger(uuids);
+    }
+    if !params.pair_windows.is_empty() {
+        let pair_windows = PairWindows::new(
+            params.pair_windows,
+            params.channel_map,
+            params.coincidence_window,
+        );
+        info!("Pair windows: {}", pair_windows.describe());
+        evb = evb.with_pair_windows(pair_windows);
+    }
+    //Events are still built, for the statistics and the histograms filled from them, but only kept
+    //in a table if something reads it
+    let is_histogram_only = params.histogram_only.enabled;
+    if is_histogram_only {
+        info!("Histogram only build, no event tables will be written");
+    }
+    let mut delayed_tagger = if params.delayed_window.enabled && !is_histogram_only {
+        Some(DelayedTagger::new(params.delayed_window))
+    } else {
+        None
+    };
+    let mut state_spectra = match &params.data_template.sps {
+        Some(sps) if sps.spectra.enabled && !sps.state_gates.is_empty() => {
+            if sps.spectra.bins > 0 && sps.spectra.max > sps.spectra.min {
+                Some(StateSpectra::new(sps))
+            } else {
+                emit_warning(
+                    "state_spectra_binning",
+                    String::new(),
+                    String::from(
+                        "State spectra binning is invalid, no gated spectra will be written",
+                    ),
+                );
+                None
+            }
+        }
+        _ => None,
+    };
+    let is_split = params.run_boundaries.enabled && params.run_boundaries.split_output;
+    let mut tables = if is_split {
+        RunTables::new(params, params.layout.with_part(1))
+    } else {
+        RunTables::new(params, params.layout.clone())
+    };
+    let mut run_boundaries: Vec<RunBoundary> = vec![];
+    let mut part_first_event_id: u64 = 0;
+    let mut statistics = RunStatistics::default();
+    let mut run_spectra = params
+        .calibration_spectra
+        .as_ref()
+        .map(|total| total.new_run());
+    let mut paranoid = if params.paranoid {
+        info!("Paranoid checks are enabled, expect the run to be slower");
+        Some(ParanoidChecker::new())
+    } else {
+        None
+    };
+    let mut reducer = EventReducer::new(params.data_reduction, params.run_number);
+    let mut smearer = EnergySmearer::new(
+        params.energy_smearing,
+        params.channel_map,
+        params.run_number,
+    );
+    let row_order = RowOrder::new(params.sort_column);
+    let mut output = TableOutput {
+        codecs: params.column_codecs,
+        provenance: ParquetMetadata::default(),
+        write_limit: params.write_limit,
+        formats: params.output_formats,
+        hdf5: params.hdf5,
+        memory: params.memory_output,
+        campaign_column: None,
+        row_order,
+    };
+    let mut quick_look = if params.quick_look.enabled && !is_histogram_only {
+        Some(QuickLookFilter::new(
+            params.quick_look,
+            params.channel_map,
+            params.run_number,
+        ))
+    } else {
+        None
+    };
+    let mut quick_look_output = TableOutput {
+        codecs: params.column_codecs,
+        provenance: ParquetMetadata::default(),
+        write_limit: params.write_limit,
+        formats: OutputFormats::parquet_only(),
+        hdf5: params.hdf5,
+        memory: None,
+        campaign_column: None,
+        row_order,
+    };
+    let mut bad_energies = BadEnergyFilter::new(params.bad_energies);
+    let mut rate_limiter = RateLimiter::new(params.rate_limit);
+    let mut time_differences = if params.time_differences.enabled {
+        Some(TimeDifferences::new(
+            params.time_differences,
+            params.channel_map,
+        ))
+    } else {
+        None
+    };
+    let mut coincidence_trend = if params.coincidence_trend.enabled {
+        Some(CoincidenceTrend::new(
+            params.coincidence_trend,
+            params.channel_map,
+        ))
+    } else {
+        None
+    };
+    let mut channel_rates = if params.channel_rates.enabled {
+        Some(ChannelRates::new(params.channel_rates, params.channel_map))
+    } else {
+        None
+    };
+    let mut detector_spectra = if params.detector_spectra.enabled {
+        Some(DetectorSpectra::new(
+            params.detector_spectra,
+            params.channel_map,
+        ))
+    } else {
+        None
+    };
+    let mut scaler_rates = if params.channel_map.has_channel_type(ChannelType::Scaler) {
+        Some(ScalerRates::new(params.scaler_rates, params.channel_map))
+    } else {
+        None
+    };
+    let mut rf_reference = RfReference::new(params.channel_map, params.coincidence_window);
+    let mut event_clock = params
+        .data_template
+        .fields
+        .contains_key(&ChannelDataField::TimeSincePrevious)
+        .then(|| EventClock::new(run_info_path.as_deref()));
+    let mut channel_spectra = if is_histogram_only {
+        Some(ChannelSpectra::new(params.histogram_only))
+    } else {
+        None
+    };
+    let is_events_filled =
+        !is_histogram_only || state_spectra.is_some() || !params.output_sinks.is_empty();
+    let mut sink_feed = SinkFeed::new(params.data_template);
+    let mut hit_filter_stage = HitFilterStage::new(params.hit_filters);
+    let campaign = params.campaign.get_tag(params.run_number);
+    if let Some(tag) = &campaign {
+        info!(
+            "Run is tagged as target {}, beam {} (configuration {})",
+            tag.target, tag.beam, tag.configuration
+        );
+        tag.insert_metadata(&mut output.provenance);
+        tag.insert_metadata(&mut quick_look_output.provenance);
+        if params.campaign.add_columns {
+            output.campaign_column = Some(tag.configuration as f64);
+            quick_look_output.campaign_column = Some(tag.configuration as f64);
+        }
+    }
+    let mut run_metadata = RunMetadata::new(
+        params.run_number,
+        params.coincidence_window,
+        params.channel_map_hash,
+    );
+    match settings_path.as_deref().map(CompassSettings::read) {
+        Some(Ok(settings)) => run_metadata.daq_settings = settings.get_daq_settings(),
+        Some(Err(e)) => emit_warning("daq_settings", String::new(), e.to_string()),
+        None => (),
+    }
+    run_metadata.time_walk = time_walk.as_ref().map(|walk| walk.get_provenance());
+    run_metadata.insert_metadata(&mut output.provenance);
+    run_metadata.insert_metadata(&mut quick_look_output.provenance);
+    if let Some(pair_windows) = evb.get_pair_windows() {
+        output
+            .provenance
+            .insert_file("pair_windows", pair_windows.describe());
+        quick_look_output
+            .provenance
+            .insert_file("pair_windows", pair_windows.describe());
+    }
+    reducer.insert_metadata(&mut output.provenance);
+    bad_energies.insert_metadata(&mut output.provenance);
+    bad_energies.insert_metadata(&mut quick_look_output.provenance);
+    if let Some(limiter) = &rate_limiter {
+        limiter.insert_metadata(&mut output.provenance);
+        limiter.insert_metadata(&mut quick_look_output.provenance);
+    }
+    if let Some(thresholds) = &thresholds {
+        thresholds.insert_metadata(&mut output.provenance);
+        thresholds.insert_metadata(&mut quick_look_output.provenance);
+    }
+    row_order.insert_metadata(&mut output.provenance);
+    row_order.insert_metadata(&mut quick_look_output.provenance);
+    if let Some(filter) = &quick_look {
+        filter.insert_metadata(&mut quick_look_output.provenance);
+    }
+    if let Some(smearer) = &smearer {
+        smearer.insert_metadata(&mut output.provenance);
+        smearer.insert_metadata(&mut quick_look_output.provenance);
+    }
+    if let Some(jitter) = &time_jitter {
+        jitter.insert_metadata(&mut output.provenance);
+        jitter.insert_metadata(&mut quick_look_output.provenance);
+    }
+    if let Some(sanitizer) = &timestamp_sanitizer {
+        sanitizer.insert_metadata(&mut output.provenance);
+        sanitizer.insert_metadata(&mut quick_look_output.provenance);
+    }
+    let seam = params
+        .run_stitcher
+        .as_ref()
+        .and_then(|stitcher| stitcher.get_seam(params.run_number));
+    if let Some(seam) = seam {
+        seam.insert_metadata(&mut output.provenance);
+        seam.insert_metadata(&mut quick_look_output.provenance);
+        evb.resume_window(seam.window);
+    }
+    let mut time_limit = params.quick_build.map(TimeLimit::new);
+    if let Some(limit) = &time_limit {
+        limit.insert_metadata(&mut output.provenance);
+        limit.insert_metadata(&mut quick_look_output.provenance);
+    }
+    let mut event_id: u64 = 0;
+    let mut filtered_events: u64 = 0;
+    let mut time_calibrator = if params
+        .channel_map
+        .has_channel_type(ChannelType::TimeCalibrator)
+    {
+        Some(TimeCalibrator::default())
+    } else {
+        if params.is_pulser_run {
+            emit_warning(
+                "pulser_channel",
+                String::new(),
+                String::from(
+                    "Pulser run has no TimeCalibrator channel, no pulser summary will be written",
+                ),
+            );
+        }
+        None
+    };
 
-    let mut earliest_file_index: Option<usize>;
+    //Events are built on builder threads unless something needs each event on the build thread
+    //as soon as it is built, in step with the hits
+    let builder_threads = get_builder_threads(params.build_threads, params.max_threads);
+    let mut chunk_builder = None;
+    if builder_threads > 1 {
+        let blocker = [
+            (is_histogram_only, "Histograms Only"),
+            (delayed_tagger.is_some(), "the delayed window"),
+            (quick_look.is_some(), "the quick-look file"),
+            (state_spectra.is_some(), "state spectra"),
+            (!params.output_sinks.is_empty(), "the output sinks"),
+            (paranoid.is_some(), "paranoid mode"),
+            (rf_reference.is_some(), "the RF reference"),
+            (params.run_stitcher.is_some(), "run stitching"),
+        ]
+        .into_iter()
+        .find(|(is_used, _)| *is_used);
+        match blocker {
+            Some((_, name)) => info!(
+                "Building the events on the build thread, {} needs them in order",
+                name
+            ),
+            None => {
+                info!("Building the events on {} threads", builder_threads);
+                chunk_builder = Some(ChunkBuilder::new(
+                    builder_threads,
+                    &evb,
+                    params.data_template,
+                    params.channel_map,
+                ));
+            }
+        }
+    }
 
-    let mut count: u64 = 0;
-    let mut flush_count: u64 = 0;
-    let flush_percent = 0.01;
-    let flush_val: u64 = ((total_count as f64) * flush_percent) as u64;
+    //A checkpoint holds the positions in the files and the open window, so anything else that has
+    //to carry on across the run as it was cannot be resumed from one
+    let checkpoint_key = CheckpointKey {
+        run_number: params.run_number,
+        config_hash: get_config_hash(
+            params.config_text,
+            params
+                .time_offsets
+                .as_ref()
+                .filter(|offsets| offsets.applies_to(params.run_number)),
+        ),
+        inputs: inputs
+            .iter()
+            .map(|input| (input.name.clone(), input.bytes))
+            .collect(),
+    };
+    let mut checkpointer = None;
+    if params.checkpoints.enabled {
+        let blocker = [
+            (is_histogram_only, "Histograms Only"),
+            (params.streaming.enabled, "Stream Output"),
+            (delayed_tagger.is_some(), "the delayed window"),
+            (quick_look.is_some(), "the quick-look file"),
+            (reversal_check.is_some(), "the time-reversed check"),
+            (params.run_boundaries.enabled, "splitting concatenated runs"),
+            (params.run_stitcher.is_some(), "run stitching"),
+            (time_limit.is_some(), "a quick build"),
+            (chunk_builder.is_some(), "building on several threads"),
+        ]
+        .into_iter()
+        .find(|(is_used, _)| *is_used);
+        match blocker {
+            Some((_, name)) => emit_warning(
+                "checkpoints_off",
+                String::new(),
+                format!(
+                    "No checkpoints are taken, a build with {} cannot be resumed",
+                    name
+                ),
+            ),
+            None => checkpointer = Some(Checkpointer::new(params.checkpoints, &params.layout)),
+        }
+    }
 
-    let mut frag_number = 0;
+    let mut reporter = ProgressReporter::new(progress, params.run_number, inputs, file_hits);
+    let mut resumed = None;
+    if let Some(checkpoint) = checkpointer
+        .as_ref()
+        .and_then(|_| find_checkpoint(&params.layout, &checkpoint_key))
+    {
+        info!(
+            "Resuming the run from its checkpoint, after {} hits and {} events",
+            format_count(checkpoint.get_hits()),
+            format_count(checkpoint.events)
+        );
+        skip_read_hits(&mut files, &checkpoint.hits_read)?;
+        remove_later_fragments(&tables.layout, EVENTS_STEM, checkpoint.events_fragments)?;
+        remove_later_fragments(&tables.layout, HITS_STEM, checkpoint.hits_fragments)?;
+        tables.events_fragments = checkpoint.events_fragments;
+        tables.hits_fragments = checkpoint.hits_fragments;
+        event_id = checkpoint.events;
+        filtered_events = checkpoint.filtered_events;
+        reporter.skip_hits(&checkpoint.hits_read, event_id);
+        resumed = Some(ResumeSummary {
+            hits: checkpoint.get_hits(),
+            events: checkpoint.events,
+            events_fragments: checkpoint.events_fragments,
+        });
+        evb.resume_checkpoint(checkpoint.builder);
+    }
+    let mut is_partial = false;
 
+    let mut merge = HitMerge::new(&mut files)?;
     loop {
         //Bulk of the work ... look for the earliest hit in the file collection
-        earliest_file_index = Option::None;
-        for i in 0..files.len() {
-            if !files[i].is_eof() {
-                let hit = files[i].get_top_hit()?;
-                if hit.is_default() {
-                    continue;
-                }
+        let earliest_file_index = merge.get_earliest_file(&mut files)?;
 
-                earliest_file_index = match earliest_file_index {
-                    None => Some(i),
-                    Some(index) => {
-                        if hit.timestamp < files[index].get_top_hit()?.timestamp {
-                            Some(i)
-                        } else {
-                            Some(index)
-                        }
-                    }
-                };
+        //A quick build ends at the first hit past its run time, as if the files ended there
+        if let (Some(limit), Some(i)) = (&mut time_limit, earliest_file_index) {
+            if limit.is_past(files[i].get_top_hit()?.timestamp) {
+                emit_warning(
+                    "quick_build",
+                    String::new(),
+                    format!(
+                        "Quick build, only the first {} minutes of the run were built",
+                        limit.get_minutes()
+                    ),
+                );
+                break;
+            }
+        }
+
+        //On SIGINT or SIGTERM the run ends here as well, and is written as far as it was read. With
+        //checkpoints a rerun picks it up from here.
+        if is_shutdown_requested() {
+            emit_warning(
+                "interrupted",
+                String::new(),
+                String::from("Build interrupted, the run is only built up to here"),
+            );
+            if let Some(checkpointer) = &mut checkpointer {
+                tables.write_checkpoint_fragments(params, &output, &mut memory_profile)?;
+                checkpointer.write(&tables.get_checkpoint(
+                    &checkpoint_key,
+                    &evb,
+                    reporter.get_file_hits_read(),
+                    event_id,
+                    filtered_events,
+                ))?;
             }
+            is_partial = true;
+            break;
         }
 
         match earliest_file_index {
-            None => break, //This is how we exit, no more hits to be found
+            //This is how we exit, no more hits to be found. Unless the files stopped at a run
+            //boundary, in which case the run ends here and the next one in the files is started.
+            None => {
+                let reset = match get_reset_times(&files) {
+                    Some(reset) => reset,
+                    None => break,
+                };
+                let part = run_boundaries.len() + 1;
+                emit_warning(
+                    "run_boundary",
+                    format!("part {}", part),
+                    format!(
+                        "Timestamps reset from {} ns to {} ns",
+                        reset.last_time, reset.next_time
+                    ),
+                );
+                evb.end_run_segment();
+                //Every event of the run is in the tables before they are written
+                if let Some(builder) = &mut chunk_builder {
+                    builder.end_run_segment()?;
+                    let (added, filtered) = add_built_chunks(
+                        set_chunk_event_times(builder.take_all()?, &mut event_clock),
+                        &mut tables.events,
+                        params.channel_map,
+                        &mut statistics,
+                        &mut reversal_check,
+                        &mut time_calibrator,
+                        &mut reducer,
+                    );
+                    event_id += added;
+                    filtered_events += filtered;
+                }
+                if let Some(tagger) = &mut delayed_tagger {
+                    for tagged in tagger
+                        .end_run_segment()
+                        .into_iter()
+                        .filter(|tagged| tagged.is_prompt_kept)
+                    {
+                        append_tagged_event(&mut tables.delayed, tagged, params.channel_map);
+                        if let Some(checker) = &paranoid {
+                            checker.check_columns(&tables.delayed)?;
+                        }
+                    }
+                }
+                if let Some(checker) = &mut paranoid {
+                    checker.end_run_segment();
+                }
+                if let Some(check) = &mut reversal_check {
+                    check.end_run_segment(params.coincidence_window);
+                }
+                statistics.end_run_segment();
+                if let Some(differences) = &mut time_differences {
+                    differences.end_run_segment();
+                }
+                if let Some(trend) = &mut coincidence_trend {
+                    trend.end_run_segment();
+                }
+                if let Some(rates) = &mut channel_rates {
+                    rates.end_run_segment();
+                }
+                if let Some(rates) = &mut scaler_rates {
+                    rates.end_run_segment();
+                }
+                if let Some(reference) = &mut rf_reference {
+                    reference.end_run_segment();
+                }
+                if let Some(clock) = &mut event_clock {
+                    clock.end_run_segment();
+                }
+                if let Some(limiter) = &mut rate_limiter {
+                    limiter.end_run_segment();
+                }
+                run_boundaries.push(RunBoundary {
+                    part,
+                    events: event_id - part_first_event_id,
+                    last_time: reset.last_time,
+                    next_time: reset.next_time,
+                });
+                part_first_event_id = event_id;
+                if is_split {
+                    let next_tables = RunTables::new(params, params.layout.with_part(part + 1));
+                    write_run_tables(
+                        std::mem::replace(&mut tables, next_tables),
+                        params,
+                        &output,
+                        &quick_look_output,
+                        &mut memory_profile,
+                    )?;
+                }
+                for file in files.iter_mut() {
+                    file.start_next_run();
+                }
+                merge.refill(&mut files)?;
+                continue;
+            }
+            Some(i) if is_scaler_hit(&mut scaler_rates, files[i].get_top_hit()?) => {
+                //Scaler hits are only counted, they never reach the event builder
+                files[i].set_hit_used();
+            }
+            Some(i) if is_rf_hit(&mut rf_reference, files[i].get_top_hit()?) => {
+                //As are RF hits, which only time the events
+                statistics.add_hit(files[i].get_top_hit()?);
+                files[i].set_hit_used();
+            }
             Some(i) => {
                 //else we pop the earliest hit off to the event builder
-                let hit = files[i].get_top_hit()?;
-                evb.push_hit(hit);
+                let raw_energy = files[i].get_top_hit()?.energy;
+                let hit = match params.gain_map {
+                    Some(gains) => gains.apply(files[i].get_top_hit()?),
+                    None => files[i].get_top_hit()?.clone(),
+                };
+                let hit = match &mut smearer {
+                    Some(smearer) => smearer.apply(hit),
+                    None => hit,
+                };
+                let hit = bad_energies
+                    .apply(raw_energy, hit)
+                    .and_then(|hit| match &mut rate_limiter {
+                        Some(limiter) => limiter.apply(hit),
+                        None => Some(hit),
+                    })
+                    .and_then(|hit| {
+                        hit_filter_stage.apply(params.hit_filters, params.run_number, hit)
+                    });
+                if let Some(hit) = hit {
+                    if let Some(checker) = &mut paranoid {
+                        checker.check_hit(&hit, params.channel_map)?;
+                    }
+                    match &mut chunk_builder {
+                        Some(builder) => builder.push_hit(&hit)?,
+                        None => evb.push_hit(&hit),
+                    }
+                    statistics.add_hit(&hit);
+                    if let Some(differences) = &mut time_differences {
+                        differences.add_hit(&hit, params.channel_map);
+                    }
+                    if let Some(trend) = &mut coincidence_trend {
+                        trend.add_hit(&hit, params.channel_map);
+                    }
+                    if let Some(rates) = &mut channel_rates {
+                        rates.add_hit(&hit, params.channel_map);
+                    }
+                    if let Some(spectra) = &mut detector_spectra {
+                        spectra.add_hit(&hit, params.channel_map);
+                    }
+                    if let Some(spectra) = &mut run_spectra {
+                        spectra.add_hit(&hit);
+                    }
+                    if let Some(spectra) = &mut channel_spectra {
+                        spectra.add_hit(&hit);
+                    }
+                    if let Some(check) = &mut reversal_check {
+                        check.add_hit(&hit);
+                    }
+                    if let Some(tagger) = &mut delayed_tagger {
+                        tagger.push_hit(&hit);
+                    }
+                    if let Some(table) = &mut tables.hits {
+                        table.push_hit(&hit);
+                    }
+                }
                 files[i].set_hit_used();
             }
         }
 
-        if evb.is_event_ready() {
-            analyzed_data.append_event(evb.get_ready_event(), params.channel_map);
-            //Check to see if we need to fragment
-            if analyzed_data.get_used_size() > MAX_USED_SIZE {
-                write_dataframe_fragment(
-                    analyzed_data,
-                    params.output_file_path.parent().unwrap(),
-                    &params.run_number,
-                    &frag_number,
-                )?;
-                //allocate new vector
-                analyzed_data = ChannelData::default();
-                frag_number += 1;
+        if tables
+            .hits
+            .as_ref()
+            .is_some_and(|table| table.get_used_size() > params.memory_budget)
+        {
+            tables.write_hits_fragment(params)?;
+        }
+
+        while evb.is_event_ready() {
+            let event = evb.get_ready_event();
+            let time_to_rf = rf_reference
+                .as_ref()
+                .zip(event.first())
+                .and_then(|(reference, hit)| reference.get_time_to_rf(hit.timestamp));
+            let event_times = event_clock.as_mut().map(|clock| clock.next_event(&event));
+            if let Some(checker) = &mut paranoid {
+                checker.check_event(&event, evb.get_max_event_span())?;
+            }
+            statistics.add_event(&event, params.channel_map);
+            if let Some(check) = &mut reversal_check {
+                check.add_forward_event(&event);
+            }
+            if let Some(calibrator) = &mut time_calibrator {
+                calibrator.add_event(&event, params.channel_map);
             }
+            //Dropped events still take an id, so kept events have the same ids as in a full build
+            let mut is_kept = reducer.keep_event();
+            if quick_look
+                .as_mut()
+                .is_some_and(|filter| filter.accept(&event))
+                && tables.quick_look.append_event(&event, params.channel_map)
+            {
+                if let Some(time) = time_to_rf {
+                    tables
+                        .quick_look
+                        .set_event_value(&ChannelDataField::TimeToRF, time);
+                }
+                if let Some(times) = &event_times {
+                    times.set_event_values(&mut tables.quick_look);
+                }
+                if delayed_tagger.is_some() {
+                    tables
+                        .quick_look
+                        .set_event_value(&ChannelDataField::EventID, event_id as f64);
+                }
+            }
+            if is_kept && is_events_filled {
+                is_kept = tables.events.append_event(&event, params.channel_map);
+                if !is_kept {
+                    filtered_events += 1;
+                }
+            }
+            if let Some(tagger) = &mut delayed_tagger {
+                tagger.add_prompt_event(&event, event_id, is_kept);
+            }
+            if is_kept && is_events_filled {
+                if let Some(time) = time_to_rf {
+                    tables
+                        .events
+                        .set_event_value(&ChannelDataField::TimeToRF, time);
+                }
+                if let Some(times) = &event_times {
+                    times.set_event_values(&mut tables.events);
+                }
+                if delayed_tagger.is_some() {
+                    tables
+                        .events
+                        .set_event_value(&ChannelDataField::EventID, event_id as f64);
+                }
+                if let Some(checker) = &paranoid {
+                    checker.check_columns(&tables.events)?;
+                }
+                if !params.output_sinks.is_empty() {
+                    sink_feed.send(params.output_sinks, params.run_number, &tables.events)?;
+                }
+                if let Some(spectra) = &mut state_spectra {
+                    spectra.fill(&tables.events);
+                }
+            }
+            event_id += 1;
+            if tables.is_events_fragment_due(params) {
+                sample_memory(
+                    &mut memory_profile,
+                    &files,
+                    &[
+                        &evb,
+                        &tables.events,
+                        &delayed_tagger,
+                        &tables.delayed,
+                        &tables.hits,
+                        &reversal_check,
+                        &tables.quick_look,
+                    ],
+                );
+            }
+            tables.write_full_events(params, &output, &mut memory_profile)?;
         }
 
-        //Progress report
-        count += 1;
-        if count == flush_val {
-            flush_count += 1;
-            count = 0;
+        if let Some(builder) = &mut chunk_builder {
+            let (added, filtered) = add_built_chunks(
+                set_chunk_event_times(builder.take_built()?, &mut event_clock),
+                &mut tables.events,
+                params.channel_map,
+                &mut statistics,
+                &mut reversal_check,
+                &mut time_calibrator,
+                &mut reducer,
+            );
+            event_id += added;
+            filtered_events += filtered;
+            if tables.is_events_fragment_due(params) {
+                sample_memory(
+                    &mut memory_profile,
+                    &files,
+                    &[&*builder, &tables.events, &tables.hits, &reversal_check],
+                );
+            }
+            tables.write_full_events(params, &output, &mut memory_profile)?;
+        }
+
+        if let Some(tagger) = &mut delayed_tagger {
+            for tagged in tagger
+                .take_tagged_events(false)
+                .into_iter()
+                .filter(|tagged| tagged.is_prompt_kept)
+            {
+                append_tagged_event(&mut tables.delayed, tagged, params.channel_map);
+                if let Some(checker) = &paranoid {
+                    checker.check_columns(&tables.delayed)?;
+                }
+            }
+            if let Some(stream) = &mut tables.delayed_stream {
+                if tables.delayed.rows >= params.streaming.chunk_rows
+                    || tables.delayed.get_used_size() > params.memory_budget
+                {
+                    stream.write_piece(
+                        std::mem::replace(&mut tables.delayed, params.delayed_template.clone()),
+                        params.run_number,
+                        params.post_batch_hooks,
+                        &output,
+                        &mut memory_profile,
+                    )?;
+                }
+            } else if tables.delayed.get_used_size() > params.memory_budget {
+                write_dataframe(
+                    tables.delayed,
+                    &tables
+                        .layout
+                        .get_table_path(DELAYED_STEM, Some(tables.delayed_fragments)),
+                    params.run_number,
+                    params.post_batch_hooks,
+                    &output,
+                    &mut memory_profile,
+                )?;
+                tables.delayed = params.delayed_template.clone();
+                tables.delayed_fragments += 1;
+            }
+        }
 
-            match progress.lock() {
-                Ok(mut prog) => *prog = (flush_count as f64 * flush_percent) as f32,
-                Err(_) => return Err(EVBError::Sync),
-            };
+        //Progress report, every pass that gets here took one hit of the earliest file
+        if reporter.add_hit(earliest_file_index.unwrap_or_default(), event_id)? {
+            sample_memory(
+                &mut memory_profile,
+                &files,
+                &[
+                    &evb,
+                    &tables.events,
+                    &delayed_tagger,
+                    &tables.delayed,
+                    &tables.hits,
+                    &reversal_check,
+                    &tables.quick_look,
+                ],
+            );
+            if let Some(checkpointer) = checkpointer
+                .as_mut()
+                .filter(|checkpointer| checkpointer.is_due())
+            {
+                tables.write_checkpoint_fragments(params, &output, &mut memory_profile)?;
+                checkpointer.write(&tables.get_checkpoint(
+                    &checkpoint_key,
+                    &evb,
+                    reporter.get_file_hits_read(),
+                    event_id,
+                    filtered_events,
+                ))?;
+            }
         }
     }
 
-    if frag_number == 0 {
-        write_dataframe(analyzed_data, &params.output_file_path)?;
-    } else {
-        write_dataframe_fragment(
-            analyzed_data,
-            params.output_file_path.parent().unwrap(),
-            &params.run_number,
-            &frag_number,
-        )?;
+    let mut trigger_hits = evb.get_trigger_hits();
+    if let Some(builder) = &mut chunk_builder {
+        let (added, filtered) = add_built_chunks(
+            set_chunk_event_times(builder.finish()?, &mut event_clock),
+            &mut tables.events,
+            params.channel_map,
+            &mut statistics,
+            &mut reversal_check,
+            &mut time_calibrator,
+            &mut reducer,
+        );
+        filtered_events += filtered;
+        trigger_hits += builder.get_trigger_hits();
+        reporter.set_events(event_id + added);
+    }
+
+    //Checked before anything is written, so a fallback run simply replaces this one
+    if is_trigger_mode && trigger_hits == 0 {
+        return Err(EVBError::MissingTrigger(format!(
+            "run {} has no hits in the trigger channel",
+            params.run_number
+        )));
+    }
+    if let Some(stitcher) = params.run_stitcher.as_mut() {
+        stitcher.carry(params.run_number, evb.take_open_window());
+    }
+
+    sample_memory(
+        &mut memory_profile,
+        &files,
+        &[
+            &evb,
+            &tables.events,
+            &delayed_tagger,
+            &tables.delayed,
+            &tables.hits,
+            &reversal_check,
+            &tables.quick_look,
+        ],
+    );
+    if let Some(tagger) = &mut delayed_tagger {
+        for tagged in tagger
+            .end_run_segment()
+            .into_iter()
+            .filter(|tagged| tagged.is_prompt_kept)
+        {
+            append_tagged_event(&mut tables.delayed, tagged, params.channel_map);
+            if let Some(checker) = &paranoid {
+                checker.check_columns(&tables.delayed)?;
+            }
+        }
+    }
+    //The tables written from here on have the hit times and counts of the whole run
+    run_metadata.partial = is_partial;
+    run_metadata.set_hits(
+        statistics.get_hit_times(),
+        &statistics.get_channel_stats(params.channel_map),
+    );
+    run_metadata.insert_metadata(&mut output.provenance);
+    run_metadata.insert_metadata(&mut quick_look_output.provenance);
+    write_run_tables(
+        tables,
+        params,
+        &output,
+        &quick_look_output,
+        &mut memory_profile,
+    )?;
+    run_metadata.write(&params.layout.get_metadata_path())?;
+    let (file_scalers, file_counts) = scaler_list
+        .as_ref()
+        .map_or((0, 0), |list| list.get_total_counts());
+    let (channel_scalers, channel_counts) = scaler_rates
+        .as_ref()
+        .map_or((0, 0), |rates| rates.get_total_counts());
+    let scaler_counts = (file_scalers + channel_scalers, file_counts + channel_counts);
+    if let Some(rates) = &scaler_rates {
+        rates.write_rates(&params.layout.get_scaler_rates_path())?;
+        rates.log_summary();
     }
     if let Some(list) = scaler_list {
-        list.write_scalers(&params.scalerout_file_path)?
+        list.write_scalers(&params.layout.get_scalers_path())?
+    }
+    if let Some(calibrator) = time_calibrator {
+        calibrator.update_campaign_offsets(
+            params.time_offsets,
+            params.output_dir,
+            params.run_number,
+            params.shift_map,
+        )?;
+        if params.is_pulser_run {
+            calibrator
+                .write_summary(&params.layout.get_pulser_summary_path(), params.channel_map)?;
+        }
     }
 
-    //To be safe, manually drop all files in unpack dir before deleting all the files
-    drop(files);
+    if !slow_control_paths.is_empty() {
+        let table = SlowControlTable::read_files(&slow_control_paths)?;
+        if !table.is_empty() {
+            table.write_table(
+                &params.layout.get_slow_control_path(),
+                params.data_template.time_unit,
+            )?;
+        }
+    }
+    if let Some(spectra) = state_spectra {
+        spectra.write_spectra(&params.layout.get_state_spectra_path())?;
+    }
+    if let Some(differences) = &time_differences {
+        differences.write_histograms(&params.layout.get_time_differences_path())?;
+        differences.log_summary();
+    }
+    if let Some(spectra) = &detector_spectra {
+        spectra.write_spectra(&params.layout.get_detector_spectra_path())?;
+        spectra.log_summary();
+    }
+    if let Some(trend) = &coincidence_trend {
+        trend.write_trend(&params.layout.get_coincidence_trend_path())?;
+        trend.write_plots(&params.layout.get_spectra_dir())?;
+        trend.log_summary();
+    }
+    if let Some(rates) = &channel_rates {
+        rates.write_rates(&params.layout.get_channel_rates_path())?;
+        rates.log_summary();
+    }
+    if let Some(spectra) = &channel_spectra {
+        spectra.write_spectra(
+            &params.layout.get_channel_spectra_path(),
+            params.channel_map,
+        )?;
+    }
 
-    clean_up_unpack_dir(&params.unpack_dir_path)?;
+    if let Some(checker) = &paranoid {
+        checker.log_summary();
+    }
+    reporter.finish()?;
+    let mut builder_stats = evb.get_stats();
+    if let Some(builder) = &chunk_builder {
+        builder_stats.add(&builder.get_stats());
+    }
+    builder_stats.log_summary();
+    let time_reversal = reversal_check.map(|check| check.compare(params.coincidence_window));
+    if let Some(comparison) = &time_reversal {
+        comparison.log_summary();
+    }
+    reducer.log_summary();
+    if filtered_events > 0 {
+        info!(
+            "Event filters rejected {} events",
+            format_count(filtered_events)
+        );
+    }
+    bad_energies.log_summary();
+    if let Some(limiter) = &rate_limiter {
+        limiter.log_summary();
+    }
+    if let Some(thresholds) = &thresholds {
+        thresholds.log_summary();
+    }
+    hit_filter_stage.log_summary(params.hit_filters);
+    let channel_groups = statistics.get_group_stats(params.channel_map, scaler_counts);
+    RunStatistics::log_summary(&channel_groups);
+    let channels = statistics.get_channel_stats(params.channel_map);
+    log_largest_gaps(&channels);
+    let unmapped_channels =
+        get_unmapped_summary(&channels, params.channel_map, params.unmapped_channels);
+    if let Some(reference) = &rf_reference {
+        reference.log_summary();
+    }
+    if let Some(sanitizer) = &timestamp_sanitizer {
+        sanitizer.log_summary();
+    }
+    if let Some(walk) = &time_walk {
+        walk.log_summary();
+    }
+    merge.report();
+    memory_profile.log_summary();
+    corruption.log_summary();
+    let processing_seconds = reporter.get_elapsed_seconds();
+    let report = RunReport {
+        run_number: params.run_number,
+        total_hits: total_count,
+        processing_seconds,
+        partial: is_partial,
+        labels: RunReportLabels::new(total_count, processing_seconds, &memory_profile),
+        memory: memory_profile,
+        event_builder: builder_stats,
+        channel_groups,
+        channels,
+        detectors: params.channel_map.get_detector_channels(),
+        data_reduction: reducer.get_provenance(),
+        filtered_events,
+        bad_energies: bad_energies.get_summary(),
+        rate_limit: rate_limiter.map(|limiter| limiter.get_summary()),
+        energy_thresholds: thresholds.map(|thresholds| thresholds.get_summary()),
+        hit_filters: hit_filter_stage.get_summary(params.hit_filters),
+        energy_smearing: smearer.map(|smearer| smearer.get_provenance()),
+        time_jitter: time_jitter.map(|jitter| jitter.get_provenance()),
+        time_walk: time_walk.map(|walk| walk.get_summary()),
+        timestamps: timestamp_sanitizer.map(|sanitizer| sanitizer.get_summary()),
+        time_reversal,
+        quick_look: quick_look.map(|filter| filter.get_summary()),
+        skipped_files: skip_list.get_skipped(),
+        corrupted_files: corruption.get_files(),
+        run_boundaries,
+        out_of_order_hits: merge.get_out_of_order(),
+        time_differences: time_differences
+            .map(|differences| differences.get_summary())
+            .unwrap_or_default(),
+        coincidence_trend: coincidence_trend.map(|trend| trend.get_summary()),
+        channel_rates: channel_rates
+            .map(|rates| rates.get_summary())
+            .unwrap_or_default(),
+        detector_spectra: detector_spectra
+            .map(|spectra| spectra.get_summary())
+            .unwrap_or_default(),
+        campaign,
+        scaler_channels: scaler_rates
+            .map(|rates| rates.get_summary())
+            .unwrap_or_default(),
+        unmapped_channels,
+        diagnostics: params.diagnostics.get_run(params.run_number),
+        resumed,
+    };
+    report.write(&params.layout.get_report_path())?;
+    if !is_partial {
+        remove_checkpoint(&params.layout)?;
+    }
 
-    Ok(())
+    if let (Some(total), Some(run)) = (params.calibration_spectra.as_mut(), run_spectra) {
+        total.add_run(params.run_number, run);
+    }
+
+    Ok(is_partial)
 }
 
 pub struct ProcessParams {
@@ -196,39 +2022,433 @@ pub struct ProcessParams {
     pub channel_map: Vec<Board>,
     pub scaler_list: Vec<ScalerEntryUI>,
     pub shift_map: Vec<ShiftMapEntry>,
+    //In ns
     pub coincidence_window: f64,
-    pub run_min: i32,
-    pub run_max: i32,
+    pub pair_windows: Vec<PairWindowEntry>,
+    pub time_unit: TimeUnit,
+    pub efficiency_entries: Vec<EfficiencyEntry>,
+    //In the order they are built
+    pub runs: Vec<i32>,
+    pub shared_memory: SharedMemoryParams,
+    pub network_sink: NetworkSinkParams,
+    pub output_sinks: OutputSinks,
+    pub post_batch_hooks: PostBatchHooks,
+    pub hit_filters: HitFilters,
+    pub delayed_window: DelayedWindowParams,
+    pub sps: SpsParams,
+    pub gain_anchors: Vec<GainAnchor>,
+    pub slow_control_pattern: String,
+    pub trigger: TriggerParams,
+    pub pulser: PulserParams,
+    pub column_codecs: Vec<ColumnCodec>,
+    pub write_hits: bool,
+    pub keep_raw_times: bool,
+    pub psd_columns: bool,
+    pub flag_columns: bool,
+    pub event_time_columns: bool,
+    //Singles to their own table, the events table keeping the coincidences
+    pub split_singles: bool,
+    //The tables of every run built into one file once the job is done
+    pub merge_runs: bool,
+    pub unmapped_channels: UnmappedPolicy,
+    //Column the event tables are sorted by, by its name in the output, empty for build order
+    pub sort_column: String,
+    pub column_naming: ColumnNaming,
+    pub invalid_values: InvalidValues,
+    //Only for quick builds, which stop after the first minutes of each run
+    pub quick_build: Option<QuickBuildParams>,
+    pub paranoid: bool,
+    pub data_reduction: DataReductionParams,
+    pub energy_smearing: EnergySmearingParams,
+    pub time_reversed_check: bool,
+    pub write_limit: WriteLimitParams,
+    pub quick_look: QuickLookParams,
+    pub skip_files: Vec<SkipEntry>,
+    pub time_jitter: TimeJitterParams,
+    pub time_walk: TimeWalkParams,
+    pub timestamp_sanitizer: TimestampSanitizerParams,
+    pub detector_groups: Vec<DetectorGroup>,
+    pub passthrough: Vec<PassthroughEntry>,
+    pub multi_hit: Vec<MultiHitEntry>,
+    pub event_filters: Vec<EventFilterEntry>,
+    pub energy_rank: EnergyRankParams,
+    pub run_boundaries: RunBoundaryParams,
+    pub calibration_spectra: CalibrationSpectraParams,
+    pub run_stitching: RunStitchingParams,
+    pub streaming: StreamingParams,
+    pub waveforms: WaveformParams,
+    pub bad_energies: BadEnergyParams,
+    pub rate_limit: RateLimitParams,
+    pub energy_calibration: Vec<EnergyCalibrationEntry>,
+    pub energy_thresholds: Vec<EnergyThresholdEntry>,
+    pub output_formats: OutputFormats,
+    pub hdf5: Hdf5Params,
+    //Also keeps the events and delayed tables in memory, see build_run_dataframe
+    pub memory_output: Option<MemoryOutput>,
+    pub time_differences: TimeDifferenceParams,
+    pub scaler_rates: ScalerRateParams,
+    pub coincidence_trend: CoincidenceTrendParams,
+    pub channel_rates: ChannelRateParams,
+    pub qa: QaParams,
+    pub checkpoints: CheckpointParams,
+    pub detector_spectra: DetectorSpectraParams,
+    pub histogram_only: HistogramOnlyParams,
+    pub campaign: CampaignParams,
+    pub worker_threads: usize,
+    pub build_threads: usize,
+    pub resource_limits: ResourceLimits,
+    //Warnings and errors of the job, for the frontend that started it
+    pub diagnostics: DiagnosticLog,
+    pub log_level: LogLevel,
+    //The config of the job as yaml, written at the top of the log of every run
+    pub config_text: String,
+}
+
+//The columns depend only on the job settings, so every run and fragment starts from the same
+//empty ChannelData
+fn build_data_templates(
+    params: &ProcessParams,
+    map: &ChannelMap,
+    shift_map: &ShiftMap,
+) -> (ChannelData, ChannelData) {
+    let mut data = ChannelData::new(
+        params.time_unit,
+        map.get_detector_count(),
+        &params.efficiency_entries,
+    )
+    .with_detector_groups(&params.detector_groups)
+    .with_passthrough(map.get_passthrough_count(), &params.passthrough)
+    .with_multi_hit(&params.multi_hit)
+    .with_event_filters(&params.event_filters)
+    .with_column_naming(&params.column_naming)
+    .with_invalid_values(&params.invalid_values)
+    .with_time_shifts(shift_map.describe_detector_shifts(map));
+    if params.keep_raw_times {
+        data = data.with_raw_times();
+    }
+    if params.psd_columns {
+        data = data.with_psd();
+    }
+    if params.flag_columns {
+        data = data.with_flags();
+    }
+    if params.unmapped_channels == UnmappedPolicy::Columns {
+        data = data.with_unmapped_columns(map);
+    }
+    if !params.energy_calibration.is_empty() {
+        data = data.with_energy_calibration(&params.energy_calibration);
+    }
+    if params.energy_rank.enabled {
+        data = data.with_energy_rank(&params.energy_rank);
+    }
+    if params.waveforms.mode == WaveformMode::Derived {
+        data = data.with_waveforms();
+    }
+    if map.has_sps_channels() {
+        data = data.with_focal_plane(params.sps.clone());
+    }
+    let delayed = data.clone().with_prompt_reference();
+    //Delayed events are never split
+    if params.split_singles {
+        data = data.with_event_class(params.trigger.channel);
+    }
+    if params.delayed_window.enabled {
+        data = data.with_event_ids();
+    }
+    //Delayed events are tagged long after their RF hits are gone
+    if map.has_channel_type(ChannelType::Rf) {
+        data = data.with_time_to_rf();
+    }
+    //And out of build order
+    if params.event_time_columns {
+        data = data.with_event_times();
+    }
+    (data, delayed)
 }
 
 //Function which handles processing multiple runs, this is what the UI actually calls
-pub fn process_runs(params: ProcessParams, progress: Arc<Mutex<f32>>) -> Result<(), EVBError> {
+pub fn process_runs(params: ProcessParams, progress: JobProgress) -> Result<(), EVBError> {
     let channel_map = ChannelMap::new(&params.channel_map);
-    let shift_map = ShiftMap::new(params.shift_map);
+    let channel_map_hash = get_channel_map_hash(&params.channel_map);
+    let shift_map = ShiftMap::new(params.shift_map.clone());
+    let (data_template, delayed_template) = build_data_templates(&params, &channel_map, &shift_map);
+    //Summed over every run of the job, written once all runs are built
+    let mut calibration_spectra = if params.calibration_spectra.enabled {
+        Some(CalibrationSpectra::new(&params.calibration_spectra))
+    } else {
+        None
+    };
+    //The offsets of the campaign's time calibrator run go into the shift map of every run after it
+    let mut time_offsets = CampaignTimeOffsets::read(&params.output_dir)?;
+    //The open window of each run is carried on to the next, with the runs built in order
+    let mut run_stitcher = RunStitcher::new(&params.run_stitching);
 
-    for run in params.run_min..params.run_max {
+    //Held for the whole job, the GUI never touches the hooks while the job runs
+    let mut hooks = match params.post_batch_hooks.lock() {
+        Ok(hooks) => hooks,
+        Err(_) => return Err(EVBError::Sync),
+    };
+    let mut hit_filters = match params.hit_filters.lock() {
+        Ok(filters) => filters,
+        Err(_) => return Err(EVBError::Sync),
+    };
+    //The sinks outlive individual runs so a consumer only has to attach once
+    let mut config_sinks: Vec<Box<dyn OutputSink>> = vec![];
+    if params.shared_memory.enabled {
+        config_sinks.push(Box::new(SharedMemoryWriter::new(
+            &params.shared_memory,
+            &data_template.get_field_names(),
+        )?));
+    }
+    if params.network_sink.enabled {
+        config_sinks.push(Box::new(NetworkSink::new(&params.network_sink)?));
+    }
+    let mut sinks = JobSinks::new(
+        &params.output_sinks,
+        config_sinks,
+        &data_template.get_field_names(),
+    )?;
+
+    //Pulser runs are about timing only: every hit goes into a wide window, without trigger
+    //selection or energy corrections. No hit is dropped for its energy or rate, bad energies are
+    //kept as they are but still counted.
+    let no_trigger = TriggerParams::default();
+    let no_rate_limit = RateLimitParams::default();
+    let kept_bad_energies = BadEnergyParams {
+        policy: BadEnergyPolicy::Keep,
+        wrap_threshold: params.bad_energies.wrap_threshold,
+    };
+    let (coincidence_window, pair_windows, trigger) = if params.pulser.enabled {
+        info!(
+            "Building pulser runs with a {} ns coincidence window",
+            params.pulser.coincidence_window
+        );
+        (params.pulser.coincidence_window, &[][..], &no_trigger)
+    } else {
+        (
+            params.coincidence_window,
+            params.pair_windows.as_slice(),
+            &params.trigger,
+        )
+    };
+    let (energy_thresholds, bad_energies, rate_limit) = if params.pulser.enabled {
+        (&[][..], &kept_bad_energies, &no_rate_limit)
+    } else {
+        (
+            params.energy_thresholds.as_slice(),
+            &params.bad_energies,
+            &params.rate_limit,
+        )
+    };
+
+    params.resource_limits.log_summary();
+    let _encode_pool = EncodePoolGuard::new(params.resource_limits.get_threads())?;
+
+    //Runs are built one after the other: they share the unpack directory, and the run log and
+    //diagnostics follow one run at a time. A failed run does not stop the others.
+    let mut batch = BatchSummary::default();
+    let mut first_error = None;
+    let diagnostic_guard = DiagnosticGuard::new(&params.diagnostics);
+    //A valid map that still looks wrong is built anyway, with a warning for each doubt
+    for issue in find_channel_map_gaps("", &params.channel_map) {
+        emit_warning("channel_map", String::new(), issue.message);
+    }
+    progress.set_schedule(BatchSchedule::new(&params.archive_dir, &params.runs))?;
+    for (index, &run) in params.runs.iter().enumerate() {
+        if is_shutdown_requested() {
+            diagnostic_guard.set_run(None);
+            emit_warning(
+                "interrupted",
+                String::new(),
+                format!(
+                    "Build interrupted, {} runs were not started",
+                    params.runs.len() - index
+                ),
+            );
+            break;
+        }
+        diagnostic_guard.set_run(Some(run));
+        let gain_map = if params.pulser.enabled {
+            None
+        } else {
+            GainMap::for_run(&params.gain_anchors, run)
+        };
+        //Skip over run if it doesnt exist
+        let run_archive_path = match find_run_archive(&params.archive_dir, run) {
+            Some(path) => path,
+            None => {
+                batch.add(run, BatchStatus::Missing, 0.0, String::new());
+                continue;
+            }
+        };
+        let (run_shift_map, run_templates) = match &time_offsets {
+            Some(offsets) if offsets.applies_to(run) => {
+                let run_shift_map = ShiftMap::new(offsets.apply(&params.shift_map));
+                let templates = build_data_templates(&params, &channel_map, &run_shift_map);
+                (run_shift_map, Some(templates))
+            }
+            _ => (shift_map.clone(), None),
+        };
         let local_params = RunParams {
-            run_archive_path: params.archive_dir.join(format!("run_{}.tar.gz", run)),
+            run_archive_path,
             unpack_dir_path: params.unpack_dir.clone(),
-            output_file_path: params.output_dir.join(format!("run_{}.parquet", run)),
+            layout: RunLayout::new(&params.output_dir, run),
             scalerlist: params.scaler_list.clone(),
-            scalerout_file_path: params.output_dir.join(format!("run_{}_scalers.txt", run)),
+            slow_control_pattern: &params.slow_control_pattern,
             channel_map: &channel_map,
-            shift_map: &Some(shift_map.clone()),
-            coincidence_window: params.coincidence_window,
+            shift_map: &Some(run_shift_map),
+            coincidence_window,
+            pair_windows,
+            channel_map_hash: &channel_map_hash,
+            data_template: run_templates
+                .as_ref()
+                .map_or(&data_template, |(data, _)| data),
+            delayed_template: run_templates
+                .as_ref()
+                .map_or(&delayed_template, |(_, delayed)| delayed),
             run_number: run,
+            output_sinks: sinks.as_mut_slice(),
+            post_batch_hooks: hooks.as_mut_slice(),
+            hit_filters: hit_filters.as_mut_slice(),
+            delayed_window: &params.delayed_window,
+            gain_map: &gain_map,
+            trigger,
+            column_codecs: &params.column_codecs,
+            is_pulser_run: params.pulser.enabled,
+            write_hits: params.write_hits,
+            keep_raw_times: params.keep_raw_times,
+            sort_column: &params.sort_column,
+            quick_build: params.quick_build.as_ref(),
+            paranoid: params.paranoid,
+            data_reduction: &params.data_reduction,
+            energy_smearing: &params.energy_smearing,
+            time_reversed_check: params.time_reversed_check,
+            write_limit: &params.write_limit,
+            quick_look: &params.quick_look,
+            skip_files: &params.skip_files,
+            time_jitter: &params.time_jitter,
+            time_walk: &params.time_walk,
+            timestamp_sanitizer: &params.timestamp_sanitizer,
+            run_boundaries: &params.run_boundaries,
+            calibration_spectra: &mut calibration_spectra,
+            time_offsets: &mut time_offsets,
+            output_dir: &params.output_dir,
+            run_stitcher: &mut run_stitcher,
+            streaming: &params.streaming,
+            waveforms: &params.waveforms,
+            bad_energies,
+            rate_limit,
+            energy_thresholds,
+            output_formats: params.output_formats,
+            hdf5: &params.hdf5,
+            memory_output: params.memory_output.as_ref(),
+            unmapped_channels: params.unmapped_channels,
+            time_differences: &params.time_differences,
+            scaler_rates: &params.scaler_rates,
+            coincidence_trend: &params.coincidence_trend,
+            channel_rates: &params.channel_rates,
+            checkpoints: &params.checkpoints,
+            detector_spectra: &params.detector_spectra,
+            histogram_only: &params.histogram_only,
+            campaign: &params.campaign,
+            worker_threads: params.worker_threads,
+            build_threads: params.build_threads,
+            max_threads: params.resource_limits.get_threads(),
+            memory_budget: params.resource_limits.get_memory_budget_bytes(),
+            diagnostics: &params.diagnostics,
+            log_level: params.log_level,
+            config_text: &params.config_text,
         };
 
-        match progress.lock() {
-            Ok(mut prog) => *prog = 0.0,
-            Err(_) => return Err(EVBError::Sync),
+        if let Some(seconds) = progress.get_schedule().0.get_remaining_seconds(0.0) {
+            info!(
+                "{} runs left, estimated to be built in {} (at {})",
+                params.runs.len() - index,
+                format_duration(seconds),
+                format_clock_time(seconds)
+            );
+        }
+        progress.start_run(run, index + 1, params.runs.len())?;
+        let start = Instant::now();
+        let (status, message) = match process_run(local_params, progress.clone()) {
+            Ok(true) => (BatchStatus::Interrupted, String::new()),
+            Ok(false) => (BatchStatus::Built, String::new()),
+            Err(x) => {
+                emit_error("run_failed", String::new(), x.to_string());
+                let message = x.to_string();
+                first_error.get_or_insert(x);
+                (BatchStatus::Failed, message)
+            }
         };
+        //Once the tables are written in full, so the run is compared as it will be analysed
+        if params.qa.enabled && status == BatchStatus::Built {
+            if let Err(x) = check_run(&params.qa, &params.output_dir, run) {
+                emit_warning(
+                    "qa",
+                    String::new(),
+                    format!("Unable to check the run: {}", x),
+                );
+            }
+        }
+        sinks.flush();
+        batch.add(run, status, start.elapsed().as_secs_f64(), message);
+        progress.finish_run(status)?;
+    }
+    diagnostic_guard.set_run(None);
+    sinks.finalize();
 
-        //Skip over run if it doesnt exist
-        if local_params.run_archive_path.exists() {
-            process_run(local_params, progress.clone())?;
+    let (first_run, last_run) = match (params.runs.first(), params.runs.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => {
+            emit_warning("run_list", String::new(), String::from("No runs to build"));
+            return Ok(());
         }
+    };
+    if params.runs.len() > 1 {
+        batch.log_summary();
+        batch.write_summary(&get_batch_summary_path(
+            &params.output_dir,
+            first_run,
+            last_run,
+        ))?;
     }
 
-    Ok(())
+    if let Some(spectra) = calibration_spectra.filter(|spectra| !spectra.is_empty()) {
+        spectra.write_spectra(
+            &get_calibration_spectra_path(&params.output_dir, first_run, last_run),
+            &channel_map,
+        )?;
+    }
+
+    //From the files of the runs built in full, so a failed or interrupted run is left out
+    if params.merge_runs {
+        let built: Vec<i32> = batch
+            .entries
+            .iter()
+            .filter(|entry| entry.status == BatchStatus::Built)
+            .map(|entry| entry.run_number)
+            .collect();
+        let mut stems = vec![EVENTS_STEM];
+        if data_template.is_split_by_class() {
+            stems.push(SINGLES_STEM);
+        }
+        for stem in stems {
+            if let Err(x) = merge_runs(
+                &params.output_dir,
+                &built,
+                stem,
+                &get_merged_path(&params.output_dir, stem, first_run, last_run),
+                &params.column_codecs,
+            ) {
+                emit_error("merge_runs", String::new(), x.to_string());
+                first_error.get_or_insert(x);
+            }
+        }
+    }
+
+    match first_error {
+        Some(x) => Err(x),
+        None if is_shutdown_requested() => Err(EVBError::Interrupted),
+        None => Ok(()),
+    }
 }
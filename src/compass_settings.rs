@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use log::info;
+
+use super::channel_map::{Board, ChannelType};
+use super::error::EVBError;
+
+//CoMPASS saves the settings of the acquisition next to the data of every run
+const SETTINGS_NAME: &str = "settings.xml";
+const ENABLED_KEY: &str = "SRV_PARAM_CH_ENABLED";
+const LABEL_KEY: &str = "SW_PARAMETER_CH_LABEL";
+//Parameters recorded in the run metadata, any whose key contains one of these: the energy
+//filter (trapezoid rise, flat top, pole zero, peaking), gains and input ranges, the charge gates,
+//thresholds, polarity and DC offset
+const RECORDED_PARAMETERS: [&str; 7] = [
+    "TRAP",
+    "GAIN",
+    "INDYN",
+    "GATE",
+    "THRESHOLD",
+    "POLARITY",
+    "DCOFFSET",
+];
+//Channels of the boards without a <channel> list
+const BOARD_CHANNELS: u32 = 16;
+
+pub fn is_settings_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.eq_ignore_ascii_case(SETTINGS_NAME))
+}
+
+//An element of an XML document with its text, attributes are dropped since CoMPASS keeps the
+//settings in the elements
+#[derive(Debug, Clone, Default)]
+struct XmlElement {
+    name: String,
+    text: String,
+    children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    fn get_children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn get_child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn get_text(&self, name: &str) -> Option<&str> {
+        self.get_child(name).map(|child| child.text.trim())
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+//Index of the > closing a tag that starts at the beginning of text, skipping quoted attributes
+fn find_tag_end(text: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(index),
+            _ => (),
+        }
+    }
+    None
+}
+
+//Index just past the first end in text
+fn find_end(text: &str, end: &str) -> Result<usize, String> {
+    text.find(end)
+        .map(|index| index + end.len())
+        .ok_or_else(|| {
+            format!(
+                "{} is never closed",
+                text.chars().take(20).collect::<String>()
+            )
+        })
+}
+
+//The document as an element holding the root element. Enough XML for the files CoMPASS writes:
+//declarations, comments and doctypes are skipped and CDATA is kept as text.
+fn parse_xml(text: &str) -> Result<XmlElement, String> {
+    let mut stack = vec![XmlElement::default()];
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        if let Some(element) = stack.last_mut() {
+            element.text.push_str(&decode_entities(&rest[..start]));
+        }
+        rest = &rest[start..];
+        if rest.starts_with("<![CDATA[") {
+            let end = find_end(rest, "]]>")?;
+            if let Some(element) = stack.last_mut() {
+                element
+                    .text
+                    .push_str(&rest["<![CDATA[".len()..end - "]]>".len()]);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        if rest.starts_with("<?") {
+            rest = &rest[find_end(rest, "?>")?..];
+            continue;
+        }
+        if rest.starts_with("<!--") {
+            rest = &rest[find_end(rest, "-->")?..];
+            continue;
+        }
+        let end = find_tag_end(rest).ok_or_else(|| String::from("unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack
+                .pop()
+                .filter(|_| !stack.is_empty())
+                .ok_or_else(|| format!("</{}> closes no element", name.trim()))?;
+            if element.name != name.trim() {
+                return Err(format!(
+                    "<{}> is closed by </{}>",
+                    element.name,
+                    name.trim()
+                ));
+            }
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(element);
+            }
+            continue;
+        }
+        let (tag, is_empty) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+        let element = XmlElement {
+            name: tag
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            ..Default::default()
+        };
+        if is_empty {
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(element);
+            }
+        } else {
+            stack.push(element);
+        }
+    }
+    match stack.pop() {
+        Some(document) if stack.is_empty() => Ok(document),
+        Some(element) => Err(format!("<{}> is never closed", element.name)),
+        None => Err(String::from("the document is empty")),
+    }
+}
+
+//key -> value of the <entry> elements of a <parameters> or <values> list. Board parameters nest
+//the value in a second <value> next to its descriptor.
+fn read_entries(list: Option<&XmlElement>, entries: &mut BTreeMap<String, String>) {
+    for entry in list.iter().flat_map(|list| list.get_children("entry")) {
+        let key = match entry.get_text("key") {
+            Some(key) => key.to_string(),
+            None => continue,
+        };
+        let value = match entry.get_child("value") {
+            Some(value) => value.get_text("value").unwrap_or(value.text.trim()),
+            None => continue,
+        };
+        entries.insert(key, value.to_string());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelSettings {
+    pub channel_number: u32,
+    pub enabled: bool,
+    pub label: String,
+    //Every parameter of the channel, the board parameters with the channel values over them
+    pub parameters: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardSettings {
+    pub model: String,
+    pub serial_number: String,
+    pub label: String,
+    pub channels: Vec<ChannelSettings>,
+}
+
+//The boards and channels of a CoMPASS settings.xml. Boards are numbered in the order of the file,
+//which is the board number CoMPASS writes with their hits.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompassSettings {
+    pub boards: Vec<BoardSettings>,
+}
+
+impl CompassSettings {
+    pub fn read(path: &Path) -> Result<Self, EVBError> {
+        let text = std::fs::read_to_string(path)?;
+        let document = parse_xml(&text)
+            .map_err(|x| EVBError::Settings(format!("{}: {}", path.display(), x)))?;
+        let root = document
+            .children
+            .first()
+            .ok_or_else(|| EVBError::Settings(format!("{} has no root element", path.display())))?;
+        let boards: Vec<BoardSettings> = root.get_children("board").map(read_board).collect();
+        if boards.is_empty() {
+            return Err(EVBError::Settings(format!(
+                "{} has no boards",
+                path.display()
+            )));
+        }
+        Ok(CompassSettings { boards })
+    }
+
+    //A board for every board of the settings, with the enabled channels whose label is the name of
+    //a channel type (Cebra0, AnodeFront, ...) mapped to it. The rest are None.
+    pub fn get_channel_map(&self) -> Vec<Board> {
+        let mut boards = vec![];
+        for (board_number, settings) in self.boards.iter().enumerate() {
+            let mut board = Board::default();
+            for channel in settings.channels.iter().filter(|channel| channel.enabled) {
+                match (
+                    board.channels.get_mut(channel.channel_number as usize),
+                    ChannelType::try_from(channel.label.clone()),
+                ) {
+                    (Some(entry), Ok(channel_type)) => *entry = channel_type,
+                    _ => info!(
+                        "Board {} channel {} is enabled with label '{}', which is not a channel \
+                        type, so it is left as None",
+                        board_number, channel.channel_number, channel.label
+                    ),
+                }
+            }
+            boards.push(board);
+        }
+        boards
+    }
+
+    //The recorded parameters of every enabled channel by board/channel, for the run metadata
+    pub fn get_daq_settings(&self) -> BTreeMap<String, BTreeMap<String, String>> {
+        let mut settings = BTreeMap::new();
+        for (board_number, board) in self.boards.iter().enumerate() {
+            for channel in board.channels.iter().filter(|channel| channel.enabled) {
+                let mut entries: BTreeMap<String, String> = channel
+                    .parameters
+                    .iter()
+                    .filter(|(key, _)| RECORDED_PARAMETERS.iter().any(|part| key.contains(part)))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+                entries.insert(String::from("board_model"), board.model.clone());
+                entries.insert(String::from("label"), channel.label.clone());
+                settings.insert(
+                    format!("{}/{}", board_number, channel.channel_number),
+                    entries,
+                );
+            }
+        }
+        settings
+    }
+}
+
+fn read_board(board: &XmlElement) -> BoardSettings {
+    let mut defaults = BTreeMap::new();
+    read_entries(board.get_child("parameters"), &mut defaults);
+    let mut channels: Vec<ChannelSettings> = board
+        .get_children("channel")
+        .filter_map(|channel| {
+            let channel_number = channel.get_text("index")?.parse::<u32>().ok()?;
+            let mut parameters = defaults.clone();
+            read_entries(channel.get_child("values"), &mut parameters);
+            Some(get_channel_settings(channel_number, parameters))
+        })
+        .collect();
+    if channels.is_empty() {
+        channels = (0..BOARD_CHANNELS)
+            .map(|channel_number| get_channel_settings(channel_number, defaults.clone()))
+            .collect();
+    }
+    channels.sort_by_key(|channel| channel.channel_number);
+    BoardSettings {
+        model: board.get_text("modelName").unwrap_or_default().to_string(),
+        serial_number: board
+            .get_text("serialNumber")
+            .unwrap_or_default()
+            .to_string(),
+        label: board.get_text("label").unwrap_or_default().to_string(),
+        channels,
+    }
+}
+
+fn get_channel_settings(
+    channel_number: u32,
+    parameters: BTreeMap<String, String>,
+) -> ChannelSettings {
+    ChannelSettings {
+        channel_number,
+        enabled: parameters
+            .get(ENABLED_KEY)
+            .is_some_and(|value| value.eq_ignore_ascii_case("true")),
+        label: parameters.get(LABEL_KEY).cloned().unwrap_or_default(),
+        parameters,
+    }
+}
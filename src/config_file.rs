@@ -0,0 +1,142 @@
+use std::path::Path;
+
+use log::info;
+use serde_yaml::{Mapping, Value};
+
+use super::app::EvbAppParams;
+use super::config_validation::{describe_parse_error, ConfigIssue};
+use super::error::EVBError;
+
+//Version of the layout of the config files written by this event builder. When a setting is
+//renamed, moved or changes meaning, raise it and add the step from the old layout to UPGRADES, so
+//configs saved before the change still load as they were meant. Settings that are only added need
+//neither, missing settings take their defaults.
+pub const CONFIG_VERSION: u32 = 1;
+
+const VERSION_KEY: &str = "config_version";
+
+//The steps from each version of the layout to the next, by the version they upgrade from
+const UPGRADES: [fn(&mut Mapping); CONFIG_VERSION as usize] = [upgrade_unversioned];
+
+//Configs saved before versioning only lack the settings added since
+fn upgrade_unversioned(_config: &mut Mapping) {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    //By the extension of the file, anything but .toml is YAML
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+}
+
+fn describe_toml_error(text: &str, err: &toml::de::Error) -> ConfigIssue {
+    ConfigIssue {
+        line: err
+            .span()
+            .map(|span| text[..span.start].matches('\n').count() + 1),
+        message: err.message().trim_end().to_string(),
+    }
+}
+
+fn parse_text<T: serde::de::DeserializeOwned>(
+    text: &str,
+    format: ConfigFormat,
+) -> Result<T, ConfigIssue> {
+    match format {
+        ConfigFormat::Yaml => {
+            serde_yaml::from_str(text).map_err(|x| describe_parse_error(text, &x))
+        }
+        ConfigFormat::Toml => toml::from_str(text).map_err(|x| describe_toml_error(text, &x)),
+    }
+}
+
+//A config in either format, upgraded to the current layout if it was saved by an older event
+//builder. A config saved by a newer one is refused rather than read with its new settings lost.
+pub fn parse_config(text: &str, format: ConfigFormat) -> Result<EvbAppParams, ConfigIssue> {
+    let value: Value = parse_text(text, format)?;
+    let version = value
+        .get(VERSION_KEY)
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+    if version > CONFIG_VERSION as u64 {
+        return Err(ConfigIssue {
+            line: None,
+            message: format!(
+                "saved with config version {} by a newer event builder, this one reads up to \
+                 version {}, update it to load the config",
+                version, CONFIG_VERSION
+            ),
+        });
+    }
+
+    let mut params = if version == CONFIG_VERSION as u64 {
+        //Straight from the text, so errors point at their line
+        parse_text::<EvbAppParams>(text, format)?
+    } else {
+        let mut config = match value {
+            Value::Mapping(config) => config,
+            _ => {
+                return Err(ConfigIssue {
+                    line: None,
+                    message: String::from("a config is a list of settings"),
+                })
+            }
+        };
+        for upgrade in UPGRADES[version as usize..].iter() {
+            upgrade(&mut config);
+        }
+        //An error also in the text as saved is pointed at in the text
+        let params =
+            serde_yaml::from_value::<EvbAppParams>(Value::Mapping(config)).map_err(|x| {
+                parse_text::<EvbAppParams>(text, format)
+                    .err()
+                    .unwrap_or_else(|| ConfigIssue {
+                        line: None,
+                        message: describe_parse_error("", &x).message,
+                    })
+            })?;
+        info!(
+            "Upgraded a config of version {} to version {}",
+            version, CONFIG_VERSION
+        );
+        params
+    };
+    params.config_version = CONFIG_VERSION;
+    Ok(params)
+}
+
+//The config with the text the checks of the config point into. Those only find lines in YAML as
+//Save Config writes it, so TOML configs are checked without lines.
+pub fn read_config_file(path: &Path) -> Result<(EvbAppParams, String), EVBError> {
+    let text = std::fs::read_to_string(path)?;
+    let format = ConfigFormat::of_path(path);
+    let params = parse_config(&text, format)
+        .map_err(|x| EVBError::Config(format!("{}, {}", path.display(), x)))?;
+    match format {
+        ConfigFormat::Yaml => Ok((params, text)),
+        ConfigFormat::Toml => Ok((params, String::new())),
+    }
+}
+
+pub fn format_config(params: &EvbAppParams, format: ConfigFormat) -> Result<String, EVBError> {
+    match format {
+        ConfigFormat::Yaml => Ok(serde_yaml::to_string(params)?),
+        ConfigFormat::Toml => {
+            toml::to_string_pretty(params).map_err(|x| EVBError::Config(x.to_string()))
+        }
+    }
+}
+
+//In the format of its extension
+pub fn write_config_file(params: &EvbAppParams, path: &Path) -> Result<(), EVBError> {
+    std::fs::write(path, format_config(params, ConfigFormat::of_path(path))?)?;
+    Ok(())
+}
@@ -0,0 +1,1878 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use super::calibration_spectra::CalibrationSpectraParams;
+use super::campaign::CampaignParams;
+use super::channel_data::ChannelDataField;
+use super::channel_map::{Board, ChannelType, DetectorGroup};
+use super::channel_rates::ChannelRateParams;
+use super::checkpoint::CheckpointParams;
+use super::coincidence_trend::CoincidenceTrendParams;
+use super::column_codec::{ColumnCodec, ColumnCompression, ZSTD_LEVEL_RANGE};
+use super::column_naming::{ColumnNaming, ColumnNamingScheme};
+use super::data_reduction::{DataReductionParams, ReductionMode};
+use super::detector_spectra::DetectorSpectraParams;
+use super::efficiency::{get_weight_field, EfficiencyEntry};
+use super::energy_calibration::EnergyCalibrationEntry;
+use super::energy_policy::{BadEnergyParams, BadEnergyPolicy};
+use super::energy_rank::{EnergyRankParams, MAX_ENERGY_RANKS};
+use super::energy_smearing::EnergySmearingParams;
+use super::energy_threshold::EnergyThresholdEntry;
+use super::event_builder::{BuildStrategy, MissingTriggerBehavior, TriggerParams};
+use super::event_filter::{EventFilterEntry, EventFilterKind};
+use super::focal_plane::StateGate;
+use super::gain_drift::GainAnchor;
+use super::hdf5_writer::{is_hdf5_available, Hdf5Params};
+use super::histogram_only::{HistogramOnlyParams, MAX_HISTOGRAM_ONLY_BINS};
+use super::invalid_values::{ColumnClass, InvalidValues};
+use super::multi_hit::{MultiHitEntry, MultiHitPolicy};
+use super::network_sink::NetworkSinkParams;
+use super::output_format::OutputFormats;
+use super::pair_window::PairWindowEntry;
+use super::parquet_writer::StreamingParams;
+use super::passthrough::PassthroughEntry;
+use super::quick_build::QuickBuildParams;
+use super::quick_look::QuickLookParams;
+use super::rate_limit::RateLimitParams;
+use super::resource_limits::ResourceLimits;
+use super::root_writer::is_root_available;
+use super::run_boundary::RunBoundaryParams;
+use super::run_list::parse_run_list;
+use super::run_qa::{QaParams, QaReference};
+use super::run_stitching::RunStitchingParams;
+use super::scaler_list::ScalerEntryUI;
+use super::scaler_rates::ScalerRateParams;
+use super::shift_map::ShiftMapEntry;
+use super::skip_list::SkipEntry;
+use super::state_spectra::StateSpectraParams;
+use super::time_calibration::PulserParams;
+use super::time_differences::TimeDifferenceParams;
+use super::time_jitter::TimeJitterParams;
+use super::time_walk::TimeWalkParams;
+use super::timestamp_sanitizer::TimestampSanitizerParams;
+use super::waveform::{WaveformMode, WaveformParams};
+use super::write_limit::WriteLimitParams;
+
+//Channels per board, see channel_map::Board
+const CHANNELS_PER_BOARD: u32 = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+//Find the (1-based) line of the index-th list item under a top level key, so semantic errors
+//can point at the offending entry. Only handles the block style written by Save Config.
+fn find_list_item_line(yaml: &str, key: &str, index: usize) -> Option<usize> {
+    let header = format!("{}:", key);
+    let mut lines = yaml.lines().enumerate();
+    lines.find(|(_, line)| line.starts_with(&header))?;
+
+    let mut count = 0;
+    let mut item_indent: Option<usize> = None;
+    for (line_number, line) in lines {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if indent == 0 && !trimmed.is_empty() && !trimmed.starts_with('-') {
+            break; //Reached the next top level key
+        }
+        if trimmed.starts_with("- ") || trimmed == "-" {
+            //Items of nested lists (e.g. the channels of a board) sit deeper than the first item
+            if *item_indent.get_or_insert(indent) != indent {
+                continue;
+            }
+            if count == index {
+                return Some(line_number + 1);
+            }
+            count += 1;
+        }
+    }
+    None
+}
+
+fn find_key_line(yaml: &str, key: &str) -> Option<usize> {
+    let header = format!("{}:", key);
+    yaml.lines()
+        .position(|line| line.starts_with(&header))
+        .map(|index| index + 1)
+}
+
+//The name of an unknown channel type in a parse error: ChannelType reports its own, and serde the
+//unknown variants of enums whose variants are all channel types. Unknown variants of other
+//settings are left to serde's message, which lists the expected variants.
+fn get_unknown_channel_type(message: &str) -> Option<&str> {
+    const CHANNEL_TYPE: &str = "unknown channel type ";
+    const VARIANT: &str = "unknown variant `";
+    if let Some(start) = message.find(CHANNEL_TYPE) {
+        return Some(message[start + CHANNEL_TYPE.len()..].trim());
+    }
+    let rest = &message[message.find(VARIANT)? + VARIANT.len()..];
+    let (name, expected) = rest.split_once('`')?;
+    let variants: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+    let is_channel_type = !variants.is_empty()
+        && variants
+            .iter()
+            .all(|variant| ChannelType::try_from(variant.to_string()).is_ok());
+    is_channel_type.then_some(name)
+}
+
+//Turn serde's terse failure into something a shifter can act on
+pub fn describe_parse_error(yaml: &str, err: &serde_yaml::Error) -> ConfigIssue {
+    let line = err.location().map(|loc| loc.line());
+    let full_message = err.to_string();
+    let message = match full_message.find(" at line ") {
+        Some(pos) => &full_message[..pos],
+        None => full_message.as_str(),
+    };
+
+    let mut explained = if let Some(name) = get_unknown_channel_type(message) {
+        format!(
+            "unknown detector name '{}', expected one of the channel types shown in the Channel Map tab",
+            name
+        )
+    } else if let Some(start) = message.find("unknown field `") {
+        let rest = &message[start + 15..];
+        let name = rest.split('`').next().unwrap_or_default();
+        format!("unknown setting '{}', check the spelling", name)
+    } else if let Some(start) = message.find("invalid length ") {
+        //The channels of a board are a fixed size list
+        let length = message[start + 15..].split(',').next().unwrap_or_default();
+        format!(
+            "a board has {} channels, boards have channels 0-{}",
+            length,
+            CHANNELS_PER_BOARD - 1
+        )
+    } else if message.contains("missing field") {
+        format!("{}, add it or re-save the config from the GUI", message)
+    } else {
+        message.to_string()
+    };
+
+    if let Some(content) = line.and_then(|l| yaml.lines().nth(l.saturating_sub(1))) {
+        explained = format!("{} (in '{}')", explained, content.trim());
+    }
+
+    ConfigIssue {
+        line,
+        message: explained,
+    }
+}
+
+//A detector may only be served by one board/channel, otherwise the second hit silently wins.
+//Any number of channels can be unmapped or counted as scalers.
+pub fn validate_channel_map(yaml: &str, boards: &[Board]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let mut seen: HashMap<String, (usize, usize)> = HashMap::new();
+    for (board_index, board) in boards.iter().enumerate() {
+        for (channel_index, channel) in board.channels.iter().enumerate() {
+            if matches!(channel, ChannelType::None | ChannelType::Scaler) {
+                continue;
+            }
+            let name = channel.to_string();
+            if let Some((first_board, first_channel)) = seen.get(&name) {
+                issues.push(ConfigIssue {
+                    line: find_list_item_line(yaml, "channel_map_entries", board_index),
+                    message: format!(
+                        "detector {} is mapped to both board {} channel {} and board {} channel {}",
+                        name, first_board, first_channel, board_index, channel_index
+                    ),
+                });
+            } else {
+                seen.insert(name, (board_index, channel_index));
+            }
+        }
+    }
+    issues
+}
+
+//Likely mistakes that still give a valid map, so they are only warned about: a CeBrA detector
+//number left out, a board with nothing mapped before the last board in use (the boards after it
+//may be numbered one off), and a delay line without its partner (the position is never filled)
+pub fn find_channel_map_gaps(yaml: &str, boards: &[Board]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let line = find_key_line(yaml, "channel_map_entries");
+    let mapped: Vec<ChannelType> = boards
+        .iter()
+        .flat_map(|board| board.channels.iter().copied())
+        .filter(|channel| *channel != ChannelType::None)
+        .collect();
+
+    let detectors: Vec<usize> = mapped
+        .iter()
+        .filter_map(|channel| channel.get_detector())
+        .collect();
+    if let Some(last) = detectors.iter().max() {
+        for missing in (0..*last).filter(|detector| !detectors.contains(detector)) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} is not mapped, though {} is",
+                    ChannelType::Cebra(missing),
+                    ChannelType::Cebra(*last)
+                ),
+            });
+        }
+    }
+
+    let last_used = boards.iter().rposition(|board| {
+        board
+            .channels
+            .iter()
+            .any(|channel| *channel != ChannelType::None)
+    });
+    for (board_index, board) in boards.iter().enumerate().take(last_used.unwrap_or(0)) {
+        if board
+            .channels
+            .iter()
+            .all(|channel| *channel == ChannelType::None)
+        {
+            issues.push(ConfigIssue {
+                line: find_list_item_line(yaml, "channel_map_entries", board_index),
+                message: format!(
+                    "board {} has no channels mapped, check that the boards after it are not numbered one off",
+                    board_index
+                ),
+            });
+        }
+    }
+
+    for (first, second, position) in [
+        (
+            ChannelType::DelayFrontLeft,
+            ChannelType::DelayFrontRight,
+            "X1",
+        ),
+        (
+            ChannelType::DelayBackLeft,
+            ChannelType::DelayBackRight,
+            "X2",
+        ),
+    ] {
+        for (channel, partner) in [(first, second), (second, first)] {
+            if mapped.contains(&channel) && !mapped.contains(&partner) {
+                issues.push(ConfigIssue {
+                    line,
+                    message: format!(
+                        "{} is mapped without {}, {} is never filled",
+                        channel, partner, position
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+pub fn validate_shift_map(yaml: &str, entries: &[ShiftMapEntry]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let mut seen: HashMap<(u32, u32), usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "shift_map_entries", index);
+        if entry.channel_number >= CHANNELS_PER_BOARD {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "shift for board {} channel {} is out of range, boards have channels 0-{}",
+                    entry.board_number,
+                    entry.channel_number,
+                    CHANNELS_PER_BOARD - 1
+                ),
+            });
+        }
+        match seen.get(&(entry.board_number, entry.channel_number)) {
+            Some(first) => issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "board {} channel {} already has a shift (entry {}), the entries overlap",
+                    entry.board_number, entry.channel_number, first
+                ),
+            }),
+            None => {
+                seen.insert((entry.board_number, entry.channel_number), index);
+            }
+        }
+    }
+    issues
+}
+
+pub fn validate_scaler_list(yaml: &str, entries: &[ScalerEntryUI]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "scaler_list_entries", index);
+        if entry.file_pattern.is_empty() {
+            issues.push(ConfigIssue {
+                line,
+                message: String::from(
+                    "scaler has an empty file pattern, it would match every file",
+                ),
+            });
+        }
+        for other in entries.iter().take(index) {
+            if !other.file_pattern.is_empty()
+                && (entry.file_pattern.starts_with(&other.file_pattern)
+                    || other.file_pattern.starts_with(&entry.file_pattern))
+            {
+                issues.push(ConfigIssue {
+                    line,
+                    message: format!(
+                        "scaler pattern '{}' overlaps with '{}'",
+                        entry.file_pattern, other.file_pattern
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+pub fn validate_detector_groups(yaml: &str, groups: &[DetectorGroup]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, group) in groups.iter().enumerate() {
+        let line = find_list_item_line(yaml, "detector_groups", index);
+        if group.name.is_empty() || !group.name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "detector group name '{}' must be letters and digits only",
+                    group.name
+                ),
+            });
+        }
+        let columns = [group.get_energy_column(), group.get_multiplicity_column()];
+        if columns.iter().any(|c| ChannelDataField::is_field_name(c)) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "detector group {} has the same column names as a detector",
+                    group.name
+                ),
+            });
+        }
+        if groups
+            .iter()
+            .take(index)
+            .any(|other| other.name == group.name)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("detector group {} is defined more than once", group.name),
+            });
+        }
+        if group.detectors.is_empty() || group.detectors.contains(&ChannelType::None) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "detector group {} needs at least one detector and cannot contain None",
+                    group.name
+                ),
+            });
+        }
+    }
+    issues
+}
+
+//Named channels must be mapped as passthrough, and their columns must not clash with the others
+pub fn validate_passthrough(
+    yaml: &str,
+    entries: &[PassthroughEntry],
+    boards: &[Board],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "passthrough", index);
+        if entry.name.is_empty() || !entry.name.chars().all(|c| c.is_ascii_alphanumeric()) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "passthrough name '{}' must be letters and digits only",
+                    entry.name
+                ),
+            });
+        }
+        let columns = [entry.get_energy_column(), entry.get_time_column()];
+        if columns.iter().any(|c| ChannelDataField::is_field_name(c)) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "passthrough {} has the same column names as a detector",
+                    entry.name
+                ),
+            });
+        }
+        if entry.channel.get_passthrough().is_none() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} is not a passthrough channel", entry.channel),
+            });
+        } else if !boards
+            .iter()
+            .any(|board| board.channels.contains(&entry.channel))
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("passthrough {} is not in the channel map", entry.channel),
+            });
+        }
+        if entries
+            .iter()
+            .take(index)
+            .any(|other| other.channel == entry.channel)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} is named more than once", entry.channel),
+            });
+        }
+        if entries
+            .iter()
+            .take(index)
+            .any(|other| other.name == entry.name)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("passthrough name {} is used more than once", entry.name),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_multi_hit(yaml: &str, entries: &[MultiHitEntry]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "multi_hit", index);
+        if entry.detector == ChannelType::None {
+            issues.push(ConfigIssue {
+                line,
+                message: String::from("multiple hit policy is set for detector None"),
+            });
+        }
+        //List columns are only made for the CeBrA detectors
+        if entry.policy == MultiHitPolicy::Expand && entry.detector.get_detector().is_none() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} cannot be expanded, only CeBrA detectors have list columns",
+                    entry.detector
+                ),
+            });
+        }
+        if entries
+            .iter()
+            .take(index)
+            .any(|other| other.detector == entry.detector)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} has more than one multiple hit policy", entry.detector),
+            });
+        }
+    }
+    issues
+}
+
+//Each pair of channel groups has one window, and only the leading edge builder opens its windows
+//by the group of their first hit
+pub fn validate_pair_windows(
+    yaml: &str,
+    entries: &[PairWindowEntry],
+    strategy: BuildStrategy,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !entries.is_empty() && strategy != BuildStrategy::LeadingEdge {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "pair_windows"),
+            message: String::from("pair windows need the leading edge strategy"),
+        });
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "pair_windows", index);
+        if !entry.window.is_finite() || entry.window <= 0.0 {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "pair window {}-{} must be positive, found {}",
+                    entry.first.as_ref(),
+                    entry.second.as_ref(),
+                    entry.window
+                ),
+            });
+        }
+        if entries
+            .iter()
+            .take(index)
+            .any(|other| other.is_pair(entry.first, entry.second))
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{}-{} has more than one pair window",
+                    entry.first.as_ref(),
+                    entry.second.as_ref()
+                ),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_energy_rank(yaml: &str, params: &EnergyRankParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if params.enabled && !(1..=MAX_ENERGY_RANKS).contains(&params.ranks) {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "energy_rank"),
+            message: format!(
+                "energy ranked columns need 1 to {} ranks, found {}",
+                MAX_ENERGY_RANKS, params.ranks
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_quick_build(yaml: &str, params: &QuickBuildParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !params.minutes.is_finite() || params.minutes <= 0.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "quick_build"),
+            message: format!(
+                "quick build needs a positive number of minutes, found {}",
+                params.minutes
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_skip_list(yaml: &str, entries: &[SkipEntry]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.file_pattern.is_empty() {
+            issues.push(ConfigIssue {
+                line: find_list_item_line(yaml, "skip_files", index),
+                message: format!(
+                    "skipped file of run {} has an empty file pattern, it would skip every file",
+                    entry.run
+                ),
+            });
+        }
+    }
+    issues
+}
+
+//A run in two entries only gets the first, so the later one is wrong for it
+pub fn validate_campaign(yaml: &str, campaign: &CampaignParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in campaign.entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "campaign", index);
+        if entry.first_run > entry.last_run {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "campaign entry first_run ({}) is after last_run ({})",
+                    entry.first_run, entry.last_run
+                ),
+            });
+        }
+        if entry.target.trim().is_empty() || entry.beam.trim().is_empty() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "campaign entry of runs {}-{} needs both a target and a beam",
+                    entry.first_run, entry.last_run
+                ),
+            });
+        }
+        if let Some(earlier) = campaign.entries[..index].iter().find(|earlier| {
+            earlier.first_run <= entry.last_run && entry.first_run <= earlier.last_run
+        }) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "campaign entry of runs {}-{} overlaps runs {}-{}, the overlap is tagged with the earlier entry",
+                    entry.first_run, entry.last_run, earlier.first_run, earlier.last_run
+                ),
+            });
+        }
+    }
+    issues
+}
+
+//Curves are only applied to detectors with columns, which the channel map's detector count sets
+pub fn validate_efficiency(
+    yaml: &str,
+    entries: &[EfficiencyEntry],
+    detector_count: usize,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "efficiency_entries", index);
+        if get_weight_field(&entry.detector).is_none() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} has no energy, an efficiency curve can't be applied to it",
+                    entry.detector
+                ),
+            });
+        }
+        if entry
+            .detector
+            .get_detector()
+            .is_some_and(|detector| detector >= detector_count)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} is beyond the detectors of the channel map, its efficiency curve is not used",
+                    entry.detector
+                ),
+            });
+        }
+        if entry.coefficients.is_empty() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "efficiency curve for {} has no coefficients",
+                    entry.detector
+                ),
+            });
+        }
+        if entries
+            .iter()
+            .take(index)
+            .any(|other| other.detector == entry.detector)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} has more than one efficiency curve", entry.detector),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_energy_smearing(yaml: &str, smearing: &EnergySmearingParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !smearing.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "energy_smearing");
+    for (index, entry) in smearing.entries.iter().enumerate() {
+        if entry.detector == ChannelType::None {
+            issues.push(ConfigIssue {
+                line,
+                message: String::from("energy smearing is set for detector None"),
+            });
+        }
+        if !(entry.resolution > 0.0 && entry.reference_energy > 0.0) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} smearing needs a positive resolution and reference energy, found {} at {}",
+                    entry.detector, entry.resolution, entry.reference_energy
+                ),
+            });
+        }
+        if smearing
+            .entries
+            .iter()
+            .take(index)
+            .any(|other| other.detector == entry.detector)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} has more than one energy smearing entry", entry.detector),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_state_gates(yaml: &str, gates: &[StateGate]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, gate) in gates.iter().enumerate() {
+        //state_gates is nested one level down, under sps
+        let line = find_list_item_line(yaml, "  state_gates", index);
+        if gate.x_min >= gate.x_max {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "state gate '{}' has x_min ({}) not below x_max ({})",
+                    gate.name, gate.x_min, gate.x_max
+                ),
+            });
+        }
+        for other in gates.iter().take(index) {
+            if gate.x_min < other.x_max && other.x_min < gate.x_max {
+                issues.push(ConfigIssue {
+                    line,
+                    message: format!(
+                        "state gate '{}' overlaps with '{}', events in the overlap get the first",
+                        gate.name, other.name
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+pub fn validate_state_spectra(yaml: &str, spectra: &StateSpectraParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !spectra.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "  spectra");
+    if spectra.bins == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("state spectra need at least one bin"),
+        });
+    }
+    if spectra.min >= spectra.max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "state spectra min ({}) is not below max ({})",
+                spectra.min, spectra.max
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_calibration_spectra(
+    yaml: &str,
+    spectra: &CalibrationSpectraParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !spectra.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "calibration_spectra");
+    if spectra.bins == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("calibration spectra need at least one bin"),
+        });
+    }
+    if spectra.min >= spectra.max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "calibration spectra min ({}) is not below max ({})",
+                spectra.min, spectra.max
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_detector_spectra(yaml: &str, spectra: &DetectorSpectraParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !spectra.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "detector_spectra");
+    if spectra.bins == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("detector spectra need at least one bin"),
+        });
+    }
+    if spectra.min >= spectra.max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "detector spectra min ({}) is not below max ({})",
+                spectra.min, spectra.max
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_gain_anchors(yaml: &str, anchors: &[GainAnchor]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, anchor) in anchors.iter().enumerate() {
+        let line = find_list_item_line(yaml, "gain_anchors", index);
+        if anchors
+            .iter()
+            .take(index)
+            .any(|other| other.run_number == anchor.run_number)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("run {} has more than one gain anchor", anchor.run_number),
+            });
+        }
+        if anchor.entries.iter().any(|entry| entry.gain <= 0.0) {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "gain anchor for run {} has a gain that is not positive",
+                    anchor.run_number
+                ),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_energy_calibration(
+    yaml: &str,
+    entries: &[EnergyCalibrationEntry],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let is_repeated = entries.iter().take(index).any(|other| {
+            other.board_number == entry.board_number && other.channel_number == entry.channel_number
+        });
+        if is_repeated {
+            issues.push(ConfigIssue {
+                line: find_list_item_line(yaml, "energy_calibration", index),
+                message: format!(
+                    "board {} channel {} has more than one energy calibration",
+                    entry.board_number, entry.channel_number
+                ),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_energy_thresholds(
+    yaml: &str,
+    entries: &[EnergyThresholdEntry],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, entry) in entries.iter().enumerate() {
+        let line = find_list_item_line(yaml, "energy_thresholds", index);
+        let is_repeated = entries.iter().take(index).any(|other| {
+            other.board_number == entry.board_number && other.channel_number == entry.channel_number
+        });
+        if is_repeated {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "board {} channel {} has more than one energy threshold",
+                    entry.board_number, entry.channel_number
+                ),
+            });
+        }
+        if !entry.threshold.is_finite() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "energy threshold of board {} channel {} must be a finite number, found {}",
+                    entry.board_number, entry.channel_number, entry.threshold
+                ),
+            });
+        }
+    }
+    issues
+}
+
+fn get_mapped_type(boards: &[Board], board: u32, channel: u32) -> ChannelType {
+    boards
+        .get(board as usize)
+        .and_then(|board| board.channels.get(channel as usize))
+        .copied()
+        .unwrap_or(ChannelType::None)
+}
+
+//For --lint-map: the channel map, shift map and energy calibration of a config checked against
+//each other, on top of the checks of each made whenever a config is loaded. Every CeBrA channel
+//should be calibrated, shifts and calibrations should belong to mapped channels, and no shift
+//should move a channel by a coincidence window or more.
+pub fn lint_channel_map(
+    yaml: &str,
+    boards: &[Board],
+    shifts: &[ShiftMapEntry],
+    calibration: &[EnergyCalibrationEntry],
+    coincidence_window: f64,
+) -> Vec<ConfigIssue> {
+    let mut issues = validate_channel_map(yaml, boards);
+    issues.extend(find_channel_map_gaps(yaml, boards));
+    issues.extend(validate_shift_map(yaml, shifts));
+    issues.extend(validate_energy_calibration(yaml, calibration));
+
+    for (board_index, board) in boards.iter().enumerate() {
+        for (channel_index, channel) in board.channels.iter().enumerate() {
+            if channel.get_detector().is_none() {
+                continue;
+            }
+            let is_calibrated = calibration.iter().any(|entry| {
+                entry.board_number as usize == board_index
+                    && entry.channel_number as usize == channel_index
+            });
+            if !is_calibrated {
+                issues.push(ConfigIssue {
+                    line: find_list_item_line(yaml, "channel_map_entries", board_index),
+                    message: format!(
+                        "{} on board {} channel {} has no energy calibration",
+                        channel, board_index, channel_index
+                    ),
+                });
+            }
+        }
+    }
+    for (index, entry) in calibration.iter().enumerate() {
+        if get_mapped_type(boards, entry.board_number, entry.channel_number) == ChannelType::None {
+            issues.push(ConfigIssue {
+                line: find_list_item_line(yaml, "energy_calibration", index),
+                message: format!(
+                    "board {} channel {} has an energy calibration but is not mapped",
+                    entry.board_number, entry.channel_number
+                ),
+            });
+        }
+    }
+    for (index, entry) in shifts.iter().enumerate() {
+        let line = find_list_item_line(yaml, "shift_map_entries", index);
+        if get_mapped_type(boards, entry.board_number, entry.channel_number) == ChannelType::None {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "board {} channel {} has a time shift but is not mapped",
+                    entry.board_number, entry.channel_number
+                ),
+            });
+        }
+        if !entry.time_shift.is_finite() || entry.time_shift.abs() >= coincidence_window {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "board {} channel {} is shifted by {} ns, not within the {} ns coincidence window",
+                    entry.board_number, entry.channel_number, entry.time_shift, coincidence_window
+                ),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_column_codecs(yaml: &str, codecs: &[ColumnCodec]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, codec) in codecs.iter().enumerate() {
+        let line = find_list_item_line(yaml, "column_codecs", index);
+        if codec.column.trim_end_matches('*').is_empty() {
+            issues.push(ConfigIssue {
+                line,
+                message: String::from("column codec has no column name"),
+            });
+        }
+        if let Some(level) = codec.level {
+            if codec.compression != ColumnCompression::Zstd {
+                issues.push(ConfigIssue {
+                    line,
+                    message: format!(
+                        "column {} has a level, but levels only apply to Zstd",
+                        codec.column
+                    ),
+                });
+            } else if !ZSTD_LEVEL_RANGE.contains(&level) {
+                issues.push(ConfigIssue {
+                    line,
+                    message: format!(
+                        "column {} has zstd level {}, it must be between {} and {}",
+                        codec.column,
+                        level,
+                        ZSTD_LEVEL_RANGE.start(),
+                        ZSTD_LEVEL_RANGE.end()
+                    ),
+                });
+            }
+        }
+        if codecs
+            .iter()
+            .take(index)
+            .any(|other| other.column == codec.column)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("column {} has more than one codec", codec.column),
+            });
+        }
+    }
+    issues
+}
+
+//Offsets are measured against the pulser reference, without one a pulser run gives nothing
+pub fn validate_pulser(yaml: &str, pulser: &PulserParams, boards: &[Board]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !pulser.enabled {
+        return issues;
+    }
+    let has_calibrator = boards
+        .iter()
+        .any(|board| board.channels.contains(&ChannelType::TimeCalibrator));
+    if !has_calibrator {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "pulser"),
+            message: String::from(
+                "pulser run is enabled but no channel is mapped as TimeCalibrator",
+            ),
+        });
+    }
+    if pulser.coincidence_window <= 0.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "pulser"),
+            message: format!(
+                "pulser coincidence_window must be positive, found {}",
+                pulser.coincidence_window
+            ),
+        });
+    }
+    issues
+}
+
+fn check_reduction(yaml: &str, key: &str, reduction: &DataReductionParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    match reduction.mode {
+        ReductionMode::None => (),
+        ReductionMode::Prescale => {
+            if reduction.prescale_factor == 0 {
+                issues.push(ConfigIssue {
+                    line: find_key_line(yaml, "prescale_factor"),
+                    message: format!("{} prescale_factor must be at least 1", key),
+                });
+            }
+        }
+        ReductionMode::Random => {
+            if !(reduction.fraction > 0.0 && reduction.fraction <= 1.0) {
+                issues.push(ConfigIssue {
+                    line: find_key_line(yaml, "fraction"),
+                    message: format!(
+                        "{} fraction must be in (0, 1], found {}",
+                        key, reduction.fraction
+                    ),
+                });
+            }
+        }
+    }
+    issues
+}
+
+pub fn validate_data_reduction(yaml: &str, reduction: &DataReductionParams) -> Vec<ConfigIssue> {
+    check_reduction(yaml, "data_reduction", reduction)
+}
+
+pub fn validate_quick_look(yaml: &str, quick_look: &QuickLookParams) -> Vec<ConfigIssue> {
+    if !quick_look.enabled {
+        return vec![];
+    }
+    let mut issues = check_reduction(yaml, "quick_look reduction", &quick_look.reduction);
+    if quick_look.min_multiplicity == 0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "min_multiplicity"),
+            message: String::from("quick_look min_multiplicity must be at least 1"),
+        });
+    }
+    if quick_look.required_detectors.contains(&ChannelType::None) {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "required_detectors"),
+            message: String::from("quick_look required_detectors cannot contain None"),
+        });
+    }
+    issues
+}
+
+pub fn validate_time_jitter(yaml: &str, jitter: &TimeJitterParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !jitter.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "time_jitter");
+    for (index, entry) in jitter.entries.iter().enumerate() {
+        if entry.detector == ChannelType::None {
+            issues.push(ConfigIssue {
+                line,
+                message: String::from("time jitter is set for detector None"),
+            });
+        }
+        if entry.sigma < 0.0 {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "{} time jitter sigma cannot be negative, found {}",
+                    entry.detector, entry.sigma
+                ),
+            });
+        }
+        if jitter
+            .entries
+            .iter()
+            .take(index)
+            .any(|other| other.detector == entry.detector)
+        {
+            issues.push(ConfigIssue {
+                line,
+                message: format!("{} has more than one time jitter entry", entry.detector),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_time_walk(yaml: &str, walk: &TimeWalkParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !walk.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "time_walk");
+    if walk.file.as_os_str().is_empty() {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("time_walk is enabled without a correction file"),
+        });
+    }
+    if !(walk.max_correction.is_finite() && walk.max_correction >= 0.0) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "time_walk max_correction cannot be negative, found {}",
+                walk.max_correction
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_write_limit(yaml: &str, limit: &WriteLimitParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if limit.enabled && limit.get_bytes_per_second().is_none() {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "write_limit"),
+            message: format!(
+                "write_limit megabytes_per_second must be positive, found {}",
+                limit.megabytes_per_second
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_time_differences(
+    yaml: &str,
+    differences: &TimeDifferenceParams,
+    boards: &[Board],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !differences.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "time_differences");
+    if differences.bins == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("time differences need at least one bin"),
+        });
+    }
+    if differences.min >= differences.max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "time differences min ({}) is not below max ({})",
+                differences.min, differences.max
+            ),
+        });
+    }
+    let is_mapped = boards
+        .iter()
+        .any(|board| board.channels.contains(&differences.reference));
+    if !is_mapped {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "time difference reference {} is not in the channel map",
+                differences.reference
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_histogram_only(
+    yaml: &str,
+    histogram_only: &HistogramOnlyParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !histogram_only.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "histogram_only");
+    if histogram_only.bins == 0 || histogram_only.bins > MAX_HISTOGRAM_ONLY_BINS {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "histogram only spectra need between 1 and {} bins, found {}",
+                MAX_HISTOGRAM_ONLY_BINS, histogram_only.bins
+            ),
+        });
+    }
+    if histogram_only.min >= histogram_only.max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "histogram only spectra min ({}) is not below max ({})",
+                histogram_only.min, histogram_only.max
+            ),
+        });
+    }
+    issues
+}
+
+//The merged tables are read from the parquet files of the runs
+pub fn validate_merge_runs(
+    yaml: &str,
+    merge_runs: bool,
+    formats: &OutputFormats,
+    histogram_only: &HistogramOnlyParams,
+    run_boundaries: &RunBoundaryParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !merge_runs {
+        return issues;
+    }
+    let line = find_key_line(yaml, "merge_runs");
+    if histogram_only.enabled {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("merge_runs needs the event tables, turn histogram_only off"),
+        });
+    } else if !formats.parquet {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("merge_runs needs parquet in output_formats"),
+        });
+    }
+    if run_boundaries.enabled && run_boundaries.split_output {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "merge_runs cannot merge the parts of split concatenated runs, turn split_output \
+                off",
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_scaler_rates(yaml: &str, scaler_rates: &ScalerRateParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !scaler_rates.interval.is_finite() || scaler_rates.interval <= 0.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "scaler_rates"),
+            message: format!(
+                "scaler rates need an interval above 0 s, found {}",
+                scaler_rates.interval
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_channel_rates(yaml: &str, rates: &ChannelRateParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !rates.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "channel_rates");
+    if !rates.interval.is_finite() || rates.interval <= 0.0 {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "channel rates need an interval above 0 s, found {}",
+                rates.interval
+            ),
+        });
+    }
+    if !(0.0..1.0).contains(&rates.drop_fraction) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "channel rates drop fraction must be from 0 up to 1, found {}",
+                rates.drop_fraction
+            ),
+        });
+    }
+    issues
+}
+
+//The statistics are read back from the parquet events tables
+pub fn validate_qa(
+    yaml: &str,
+    qa: &QaParams,
+    formats: &OutputFormats,
+    histogram_only: &HistogramOnlyParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !qa.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "qa");
+    if !qa.threshold.is_finite() || qa.threshold <= 0.0 {
+        issues.push(ConfigIssue {
+            line,
+            message: format!("QA needs a threshold above 0, found {}", qa.threshold),
+        });
+    }
+    if qa.reference == QaReference::RollingAverage && qa.rolling_runs == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("QA rolling average needs at least 1 run"),
+        });
+    }
+    if !formats.parquet || histogram_only.enabled {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "QA reads the statistics from the parquet events tables, which are not written",
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_checkpoints(yaml: &str, checkpoints: &CheckpointParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if checkpoints.enabled && (!checkpoints.interval.is_finite() || checkpoints.interval <= 0.0) {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "checkpoints"),
+            message: format!(
+                "checkpoints need an interval above 0 s, found {}",
+                checkpoints.interval
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_coincidence_trend(
+    yaml: &str,
+    trend: &CoincidenceTrendParams,
+    boards: &[Board],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !trend.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "coincidence_trend");
+    if !trend.interval.is_finite() || trend.interval <= 0.0 {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "coincidence trend needs an interval above 0 s, found {}",
+                trend.interval
+            ),
+        });
+    }
+    if trend.prompt_min >= trend.prompt_max {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "coincidence trend prompt gate min ({}) is not below max ({})",
+                trend.prompt_min, trend.prompt_max
+            ),
+        });
+    }
+    if !(0.0..1.0).contains(&trend.drop_fraction) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "coincidence trend drop fraction must be from 0 up to 1, found {}",
+                trend.drop_fraction
+            ),
+        });
+    }
+    let is_mapped = boards
+        .iter()
+        .any(|board| board.channels.contains(&trend.reference));
+    if !is_mapped {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "coincidence trend reference {} is not in the channel map",
+                trend.reference
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_resource_limits(yaml: &str, limits: &ResourceLimits) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if limits.memory_budget_mb == 0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "resource_limits"),
+            message: String::from("resource limits need a memory budget of at least 1 MB"),
+        });
+    }
+    issues
+}
+
+pub fn validate_streaming(yaml: &str, streaming: &StreamingParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if streaming.enabled && streaming.chunk_rows == 0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "streaming"),
+            message: String::from("streaming chunk_rows must be at least 1"),
+        });
+    }
+    issues
+}
+
+pub fn validate_network_sink(yaml: &str, network_sink: &NetworkSinkParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !network_sink.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "network_sink");
+    if let Err(x) = network_sink.get_address() {
+        issues.push(ConfigIssue {
+            line,
+            message: format!("network_sink address: {}", x),
+        });
+    }
+    if network_sink.batch_events == 0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("network_sink batch_events must be at least 1"),
+        });
+    }
+    issues
+}
+
+pub fn validate_output_formats(
+    yaml: &str,
+    formats: &OutputFormats,
+    hdf5: &Hdf5Params,
+    streaming: &StreamingParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let line = find_key_line(yaml, "output_formats");
+    if formats.is_empty() {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("output_formats has no format, the events would not be written"),
+        });
+    }
+    if formats.root && !is_root_available() {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "output_formats root needs the event builder built with the root feature",
+            ),
+        });
+    }
+    if formats.hdf5 && !is_hdf5_available() {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "output_formats hdf5 needs the event builder built with the hdf5 feature",
+            ),
+        });
+    }
+    if !formats.is_streamable() && streaming.enabled {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from("ROOT and HDF5 output cannot be streamed, turn streaming off"),
+        });
+    }
+    if formats.hdf5 {
+        if hdf5.compression_level > 9 {
+            issues.push(ConfigIssue {
+                line: find_key_line(yaml, "hdf5"),
+                message: format!(
+                    "hdf5 compression_level must be 0 to 9, found {}",
+                    hdf5.compression_level
+                ),
+            });
+        }
+        if hdf5.chunk_rows == 0 {
+            issues.push(ConfigIssue {
+                line: find_key_line(yaml, "hdf5"),
+                message: String::from("hdf5 chunk_rows must be at least 1"),
+            });
+        }
+    }
+    issues
+}
+
+pub fn validate_waveforms(yaml: &str, waveforms: &WaveformParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if waveforms.mode != WaveformMode::Derived {
+        return issues;
+    }
+    if waveforms.baseline_samples == 0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "waveforms"),
+            message: String::from("waveforms baseline_samples must be at least 1"),
+        });
+    }
+    if waveforms.sample_period.is_nan() || waveforms.sample_period <= 0.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "waveforms"),
+            message: format!(
+                "waveforms sample_period must be positive, found {}",
+                waveforms.sample_period
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_bad_energies(yaml: &str, bad_energies: &BadEnergyParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let threshold = bad_energies.wrap_threshold;
+    if threshold.is_nan() || threshold < 1.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "bad_energies"),
+            message: format!(
+                "bad_energies wrap_threshold must be at least 1, found {}",
+                bad_energies.wrap_threshold
+            ),
+        });
+    }
+    issues
+}
+
+//Filters compare against the sentinels, so they must be numbers that compare equal to themselves.
+//Clamped bad energies are built as 0, which a sentinel of 0 would make invalid. Neither matters
+//when nulls are written instead.
+pub fn validate_invalid_values(
+    yaml: &str,
+    invalid: &InvalidValues,
+    bad_energies: &BadEnergyParams,
+) -> Vec<ConfigIssue> {
+    let line = find_key_line(yaml, "invalid_values");
+    let mut issues = vec![];
+    if invalid.nulls {
+        return issues;
+    }
+    for class in [ColumnClass::Energy, ColumnClass::Time, ColumnClass::Other] {
+        let value = invalid.get(class);
+        if !value.is_finite() {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "invalid value of the {} columns must be a finite number, found {}",
+                    class.as_ref(),
+                    value
+                ),
+            });
+        }
+    }
+    if bad_energies.policy == BadEnergyPolicy::Clamp && invalid.energy == 0.0 {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "invalid value of the Energy columns cannot be 0 when bad energies are clamped to 0",
+            ),
+        });
+    }
+    issues
+}
+
+//The limit must allow at least one hit per window, or every hit would be dropped
+pub fn validate_timestamp_sanitizer(
+    yaml: &str,
+    sanitizer: &TimestampSanitizerParams,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !sanitizer.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "timestamp_sanitizer");
+    if !(sanitizer.rollover_seconds.is_finite() && sanitizer.rollover_seconds >= 0.0) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "timestamp_sanitizer rollover_seconds cannot be negative, found {}",
+                sanitizer.rollover_seconds
+            ),
+        });
+    }
+    if !(sanitizer.reorder_window.is_finite() && sanitizer.reorder_window >= 0.0) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "timestamp_sanitizer reorder_window cannot be negative, found {}",
+                sanitizer.reorder_window
+            ),
+        });
+    } else if sanitizer.rollover_seconds > 0.0
+        && sanitizer.reorder_window >= 0.5 * sanitizer.rollover_seconds * 1.0e9
+    {
+        issues.push(ConfigIssue {
+            line,
+            message: String::from(
+                "timestamp_sanitizer reorder_window must be shorter than half the rollover period",
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_rate_limit(yaml: &str, rate_limit: &RateLimitParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if !rate_limit.enabled {
+        return issues;
+    }
+    let line = find_key_line(yaml, "rate_limit");
+    let window = rate_limit.window_seconds;
+    if !(window.is_finite() && window > 0.0) {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "rate_limit window_seconds must be positive, found {}",
+                window
+            ),
+        });
+    } else if (rate_limit.max_rate_hz * window).is_nan() || rate_limit.max_rate_hz * window < 1.0 {
+        issues.push(ConfigIssue {
+            line,
+            message: format!(
+                "rate_limit of {} Hz allows no hit in a window of {} s",
+                rate_limit.max_rate_hz, window
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_run_boundaries(yaml: &str, boundaries: &RunBoundaryParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let reset = boundaries.min_reset_seconds;
+    if boundaries.enabled && (reset.is_nan() || reset <= 0.0) {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "run_boundaries"),
+            message: format!(
+                "run_boundaries min_reset_seconds must be positive, found {}",
+                boundaries.min_reset_seconds
+            ),
+        });
+    }
+    issues
+}
+
+pub fn validate_run_stitching(yaml: &str, stitching: &RunStitchingParams) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    let gap = stitching.restart_gap_seconds;
+    if stitching.enabled && !(gap.is_finite() && gap >= 0.0) {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "run_stitching"),
+            message: format!(
+                "run_stitching restart_gap_seconds must be zero or more, found {}",
+                gap
+            ),
+        });
+    }
+    issues
+}
+
+//A field, detector group or passthrough column, the ranked and multi hit columns cannot be named
+fn is_named_column(column: &str, groups: &[DetectorGroup], passthrough: &[String]) -> bool {
+    ChannelDataField::is_field_name(column)
+        || groups.iter().any(|group| {
+            group.get_energy_column() == column || group.get_multiplicity_column() == column
+        })
+        || passthrough.iter().any(|name| name == column)
+}
+
+pub fn validate_event_filters(
+    yaml: &str,
+    filters: &[EventFilterEntry],
+    groups: &[DetectorGroup],
+    passthrough: &[String],
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    for (index, filter) in filters.iter().enumerate() {
+        let line = find_list_item_line(yaml, "event_filters", index);
+        match filter.kind {
+            EventFilterKind::CebraEnergyAbove => (),
+            EventFilterKind::DetectorPresent => {
+                if filter.detector == ChannelType::None {
+                    issues.push(ConfigIssue {
+                        line,
+                        message: String::from("event filter requires detector None"),
+                    });
+                }
+            }
+            EventFilterKind::ColumnGate => {
+                if !is_named_column(&filter.column, groups, passthrough) {
+                    issues.push(ConfigIssue {
+                        line,
+                        message: format!(
+                            "event filter gates on {}, which is not a column of the events",
+                            filter.column
+                        ),
+                    });
+                }
+                if filter.min >= filter.max {
+                    issues.push(ConfigIssue {
+                        line,
+                        message: format!(
+                            "event filter gate on {} has min ({}) not below max ({})",
+                            filter.column, filter.min, filter.max
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+//Rows can be sorted by a field, detector group or passthrough column, not by the ranked or multi
+//hit columns
+pub fn validate_sort_column(
+    yaml: &str,
+    column: &str,
+    groups: &[DetectorGroup],
+    passthrough: &[String],
+) -> Vec<ConfigIssue> {
+    if column.is_empty() || is_named_column(column, groups, passthrough) {
+        return vec![];
+    }
+    vec![ConfigIssue {
+        line: find_key_line(yaml, "sort_column"),
+        message: format!("sort column {} is not a column of the events", column),
+    }]
+}
+
+//A template needs the detector number and the field in it to give every detector column a name of
+//its own, and no two columns of the events may end up with the same name
+pub fn validate_column_naming(
+    yaml: &str,
+    naming: &ColumnNaming,
+    detector_count: usize,
+    groups: &[DetectorGroup],
+    passthrough: &[String],
+) -> Vec<ConfigIssue> {
+    let line = find_key_line(yaml, "column_naming");
+    let mut issues = vec![];
+    if naming.scheme == ColumnNamingScheme::Template {
+        let has_field = naming.template.contains("{field}") || naming.template.contains("{f}");
+        if !naming.template.contains("{n}") || !has_field {
+            issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "column naming template {} needs {{n}} and one of {{field}} or {{f}}",
+                    naming.template
+                ),
+            });
+            return issues;
+        }
+    }
+    let names = ChannelDataField::get_field_vec(detector_count)
+        .into_iter()
+        .map(|field| field.get_name())
+        .chain(
+            groups
+                .iter()
+                .flat_map(|group| [group.get_energy_column(), group.get_multiplicity_column()]),
+        )
+        .chain(passthrough.iter().cloned());
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for name in names {
+        let output_name = naming.get_column_name(&name);
+        match seen.get(&output_name) {
+            Some(other) if *other != name => issues.push(ConfigIssue {
+                line,
+                message: format!(
+                    "columns {} and {} would both be named {}",
+                    other, name, output_name
+                ),
+            }),
+            _ => {
+                seen.insert(output_name, name);
+            }
+        }
+    }
+    issues
+}
+
+//A trigger that is not mapped is only an error if the run would fail because of it
+pub fn validate_trigger(yaml: &str, trigger: &TriggerParams, boards: &[Board]) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if let Some(channel_type) = trigger.channel {
+        let is_mapped = boards
+            .iter()
+            .any(|board| board.channels.contains(&channel_type));
+        if !is_mapped && trigger.on_missing == MissingTriggerBehavior::Fail {
+            issues.push(ConfigIssue {
+                line: find_key_line(yaml, "trigger"),
+                message: format!("trigger channel {} is not in the channel map", channel_type),
+            });
+        }
+    }
+    match (trigger.strategy, trigger.channel) {
+        (BuildStrategy::FixedWindow, Some(channel_type)) => issues.push(ConfigIssue {
+            line: find_key_line(yaml, "strategy"),
+            message: format!(
+                "fixed window events cannot have a trigger channel, found {}",
+                channel_type
+            ),
+        }),
+        (BuildStrategy::ReferenceTrigger, None) => issues.push(ConfigIssue {
+            line: find_key_line(yaml, "strategy"),
+            message: String::from("the reference trigger strategy needs a trigger channel"),
+        }),
+        _ => {}
+    }
+    issues
+}
+
+pub fn validate_run_range(
+    yaml: &str,
+    run_min: i32,
+    run_max: i32,
+    run_list: &str,
+    coincidence_window: f64,
+) -> Vec<ConfigIssue> {
+    let mut issues = vec![];
+    if run_list.trim().is_empty() {
+        if run_min > run_max {
+            issues.push(ConfigIssue {
+                line: find_key_line(yaml, "run_min"),
+                message: format!("run_min ({}) is larger than run_max ({})", run_min, run_max),
+            });
+        }
+    } else {
+        let line = find_key_line(yaml, "run_list");
+        match parse_run_list(run_list) {
+            Ok(runs) if runs.is_empty() => issues.push(ConfigIssue {
+                line,
+                message: String::from("run_list leaves no runs to build"),
+            }),
+            Ok(_) => (),
+            Err(e) => issues.push(ConfigIssue {
+                line,
+                message: format!("run_list is invalid, {}", e),
+            }),
+        }
+    }
+    if coincidence_window <= 0.0 {
+        issues.push(ConfigIssue {
+            line: find_key_line(yaml, "coincidence_window"),
+            message: format!(
+                "coincidence_window must be positive, found {}",
+                coincidence_window
+            ),
+        });
+    }
+    issues
+}
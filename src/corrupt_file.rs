@@ -0,0 +1,115 @@
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use serde::Serialize;
+
+use super::build_diagnostics::emit_warning;
+use super::formatting::format_count;
+
+//What is wrong with a CoMPASS file, e.g. one cut short by a DAQ crash. Offsets are in bytes from
+//the start of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompassFileError {
+    //Too short to hold the header word
+    MissingHeader,
+    //The file ends partway into a record
+    TruncatedRecord { offset: u64, bytes: u64 },
+    //A record claims more wave samples than are left in the file
+    BadSampleCount { offset: u64, samples: usize },
+}
+
+impl CompassFileError {
+    pub fn get_offset(&self) -> u64 {
+        match self {
+            CompassFileError::MissingHeader => 0,
+            CompassFileError::TruncatedRecord { offset, .. } => *offset,
+            CompassFileError::BadSampleCount { offset, .. } => *offset,
+        }
+    }
+}
+
+impl Display for CompassFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompassFileError::MissingHeader => write!(f, "File has no header"),
+            CompassFileError::TruncatedRecord { offset, bytes } => write!(
+                f,
+                "File ends {} bytes into the record at byte {}",
+                bytes, offset
+            ),
+            CompassFileError::BadSampleCount { offset, samples } => write!(
+                f,
+                "Record at byte {} has {} wave samples, more than are left in the file",
+                offset, samples
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CompassFileError {}
+
+//A corrupted input file of a run. Reading stops at the first bad record and goes on with the next
+//file, so the hits before it are kept and the rest of the file is dropped.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CorruptFile {
+    pub file: String,
+    pub offset: u64,
+    pub reason: String,
+    pub hits_recovered: u64,
+    //Estimated from the bytes left after the bad record
+    pub hits_dropped: u64,
+}
+
+//The corrupted files of a run, shared with the threads decoding them (see hit_decoder)
+#[derive(Debug, Clone, Default)]
+pub struct CorruptionLog {
+    files: Arc<Mutex<Vec<CorruptFile>>>,
+}
+
+impl CorruptionLog {
+    pub fn add(&self, file: CorruptFile) {
+        emit_warning(
+            "corrupt_file",
+            file.file.clone(),
+            format!(
+                "{}, skipping the rest of the file ({} hits read, about {} dropped)",
+                file.reason,
+                format_count(file.hits_recovered),
+                format_count(file.hits_dropped)
+            ),
+        );
+        if let Ok(mut files) = self.files.lock() {
+            files.push(file);
+        }
+    }
+
+    pub fn get_files(&self) -> Vec<CorruptFile> {
+        match self.files.lock() {
+            Ok(files) => files.clone(),
+            Err(_) => vec![],
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let files = self.get_files();
+        if files.is_empty() {
+            return;
+        }
+        info!(
+            "{} corrupted input files: {} hits recovered, about {} dropped",
+            files.len(),
+            format_count(files.iter().map(|file| file.hits_recovered).sum()),
+            format_count(files.iter().map(|file| file.hits_dropped).sum())
+        );
+        for file in files.iter() {
+            info!(
+                "  {}: bad at byte {}, {} hits recovered, about {} dropped",
+                file.file,
+                file.offset,
+                format_count(file.hits_recovered),
+                format_count(file.hits_dropped)
+            );
+        }
+    }
+}
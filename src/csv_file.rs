@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+use super::compass_data::{generate_board_channel_uuid, get_dither, CompassData, TimestampUnit};
+use super::error::EVBError;
+use super::hit_source::HitSource;
+use super::shift_map::ShiftMap;
+use super::used_size::UsedSize;
+
+const BUFFER_SIZE_BYTES: usize = 1_000_000;
+
+//Accepted header names for each hit value, compared case-insensitively. The CoMPASS CSV names
+//come first; the others are what the older analysis exports used.
+const BOARD_NAMES: [&str; 1] = ["board"];
+const CHANNEL_NAMES: [&str; 2] = ["channel", "ch"];
+const TIMETAG_NAMES: [&str; 3] = ["timetag", "timestamp", "time"];
+const ENERGY_NAMES: [&str; 2] = ["energy", "energy_long"];
+const ENERGY_SHORT_NAMES: [&str; 3] = ["energyshort", "energy_short", "short"];
+const FLAGS_NAMES: [&str; 1] = ["flags"];
+
+pub fn is_csv_path(path: &Path) -> bool {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(n) => n.to_lowercase(),
+        None => return false,
+    };
+    name.ends_with(".csv") || name.ends_with(".csv.gz")
+}
+
+pub fn open_reader(path: &Path) -> Result<BufReader<Box<dyn Read + Send>>, EVBError> {
+    let file = File::open(path)?;
+    let is_gzip = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("gz"));
+    let reader: Box<dyn Read + Send> = if is_gzip {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(BufReader::with_capacity(BUFFER_SIZE_BYTES, reader))
+}
+
+#[derive(Debug, Clone)]
+struct CsvColumns {
+    delimiter: char,
+    board: usize,
+    channel: usize,
+    timetag: usize,
+    energy: usize,
+    energy_short: Option<usize>,
+    flags: Option<usize>,
+}
+
+impl CsvColumns {
+    fn from_header(header: &str) -> Result<Self, EVBError> {
+        let delimiter = if header.contains(';') { ';' } else { ',' };
+        let names: Vec<String> = header
+            .split(delimiter)
+            .map(|name| name.trim().to_lowercase())
+            .collect();
+        let find = |candidates: &[&str]| {
+            names
+                .iter()
+                .position(|name| candidates.contains(&name.as_str()))
+        };
+        let require = |candidates: &[&str]| {
+            find(candidates).ok_or_else(|| {
+                EVBError::Csv(format!(
+                    "header '{}' has no {} column",
+                    header.trim(),
+                    candidates[0]
+                ))
+            })
+        };
+
+        Ok(CsvColumns {
+            delimiter,
+            board: require(&BOARD_NAMES)?,
+            channel: require(&CHANNEL_NAMES)?,
+            timetag: require(&TIMETAG_NAMES)?,
+            energy: require(&ENERGY_NAMES)?,
+            energy_short: find(&ENERGY_SHORT_NAMES),
+            flags: find(&FLAGS_NAMES),
+        })
+    }
+}
+
+fn parse_column<T: std::str::FromStr>(
+    values: &[&str],
+    index: usize,
+    line_number: u64,
+) -> Result<T, EVBError> {
+    let value = values.get(index).map(|v| v.trim()).unwrap_or_default();
+    value.parse::<T>().map_err(|_| {
+        EVBError::Csv(format!(
+            "line {}: could not parse '{}' in column {}",
+            line_number,
+            value,
+            index + 1
+        ))
+    })
+}
+
+//CoMPASS writes the flags in hex, e.g. 0x4000
+fn parse_flags(values: &[&str], index: usize, line_number: u64) -> Result<u32, EVBError> {
+    let value = values.get(index).map(|v| v.trim()).unwrap_or_default();
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    };
+    parsed.map_err(|_| {
+        EVBError::Csv(format!(
+            "line {}: could not parse the flags '{}' in column {}",
+            line_number,
+            value,
+            index + 1
+        ))
+    })
+}
+
+//Binary energies are integers and get dithered in CompassData::new; do the same here, but leave
+//energies that were already calibrated/dithered by the old analysis alone
+fn dither(energy: f64) -> f64 {
+    if energy.fract() == 0.0 {
+        energy + get_dither()
+    } else {
+        energy
+    }
+}
+
+//Hits exported to CSV (plain or gzip-compressed) by CoMPASS or an older analysis. Timetags are in
+//the timestamp unit of their board, ps like the binary files by default. The hits must be in time
+//order, as with any other hit file.
+pub struct CsvFile<'a> {
+    reader: BufReader<Box<dyn Read + Send>>,
+    columns: CsvColumns,
+    line: String,
+    line_number: u64,
+    number_of_hits: u64,
+    current_hit: CompassData,
+    shift_map: &'a Option<ShiftMap>,
+    timestamp_units: &'a [TimestampUnit],
+    is_used: bool,
+    is_eof: bool,
+}
+
+impl<'a> CsvFile<'a> {
+    pub fn new(path: &Path, shifts: &'a Option<ShiftMap>) -> Result<CsvFile<'a>, EVBError> {
+        //Gzip doesn't record the uncompressed size, so count the hits up front for the progress bar
+        let number_of_hits = open_reader(path)?
+            .lines()
+            .skip(1)
+            .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+            .count() as u64;
+
+        let mut reader = open_reader(path)?;
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let columns = CsvColumns::from_header(&header)?;
+
+        Ok(CsvFile {
+            reader,
+            columns,
+            line: String::new(),
+            line_number: 1,
+            number_of_hits,
+            current_hit: CompassData::default(),
+            shift_map: shifts,
+            timestamp_units: &[],
+            is_used: false,
+            is_eof: false,
+        })
+    }
+
+    //Indexed by board number, without units every board is in ps
+    pub fn with_timestamp_units(mut self, timestamp_units: &'a [TimestampUnit]) -> Self {
+        self.timestamp_units = timestamp_units;
+        self
+    }
+
+    //Returns None at the end of the file
+    fn parse_next_hit(&mut self) -> Result<Option<CompassData>, EVBError> {
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            if !self.line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let values: Vec<&str> = self.line.split(self.columns.delimiter).collect();
+        let board: u32 = parse_column(&values, self.columns.board, self.line_number)?;
+        let channel: u32 = parse_column(&values, self.columns.channel, self.line_number)?;
+        let timetag: f64 = parse_column(&values, self.columns.timetag, self.line_number)?;
+        let energy: f64 = parse_column(&values, self.columns.energy, self.line_number)?;
+        let energy_short: f64 = match self.columns.energy_short {
+            Some(index) => parse_column(&values, index, self.line_number)?,
+            None => 0.0,
+        };
+        let flags = match self.columns.flags {
+            Some(index) => parse_flags(&values, index, self.line_number)?,
+            None => 0,
+        };
+
+        let id = generate_board_channel_uuid(&board, &channel);
+        let timestamp =
+            timetag * TimestampUnit::of_board(self.timestamp_units, board).get_ns_per_unit();
+        Ok(Some(CompassData {
+            uuid: id,
+            energy: dither(energy),
+            energy_short: dither(energy_short),
+            timestamp: match self.shift_map {
+                Some(map) => timestamp + map.get_timeshift(&id),
+                None => timestamp,
+            },
+            raw_timestamp: timestamp,
+            waveform: None,
+            flags,
+        }))
+    }
+}
+
+impl HitSource for CsvFile<'_> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            self.current_hit = match self.parse_next_hit()? {
+                Some(hit) => {
+                    self.is_used = false;
+                    hit
+                }
+                None => {
+                    self.is_eof = true;
+                    CompassData::default()
+                }
+            }
+        }
+
+        Ok(&self.current_hit)
+    }
+
+    fn is_eof(&self) -> bool {
+        self.is_eof
+    }
+
+    fn set_hit_used(&mut self) {
+        self.is_used = true;
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.number_of_hits
+    }
+}
+
+impl UsedSize for CsvFile<'_> {
+    fn get_used_size(&self) -> usize {
+        self.reader.capacity() + self.line.capacity() + std::mem::size_of::<CsvFile<'_>>()
+    }
+}
@@ -0,0 +1,181 @@
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::formatting::format_count;
+use super::parquet_writer::ParquetMetadata;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum ReductionMode {
+    #[default]
+    None,
+    //Keep one event in every prescale_factor, starting at a seeded offset
+    Prescale,
+    //Keep each event with probability fraction
+    Random,
+}
+
+//Subsampling of the built events, for quick looks at large runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataReductionParams {
+    pub mode: ReductionMode,
+    pub prescale_factor: u64,
+    pub fraction: f64,
+    //Base seed, each run gets its own seed from it. Without one a fresh seed is drawn per run.
+    pub seed: Option<u64>,
+}
+
+impl Default for DataReductionParams {
+    fn default() -> Self {
+        DataReductionParams {
+            mode: ReductionMode::default(),
+            prescale_factor: 10,
+            fraction: 0.1,
+            seed: None,
+        }
+    }
+}
+
+impl DataReductionParams {
+    pub fn is_enabled(&self) -> bool {
+        self.mode != ReductionMode::None
+    }
+
+    //Fraction of the events kept
+    pub fn get_fraction(&self) -> f64 {
+        match self.mode {
+            ReductionMode::None => 1.0,
+            ReductionMode::Prescale => 1.0 / self.prescale_factor.max(1) as f64,
+            ReductionMode::Random => self.fraction,
+        }
+    }
+}
+
+//SplitMix64 of the base seed and run number, so neighbouring runs do not get related sequences
+pub fn get_run_seed(base_seed: u64, run_number: i32) -> u64 {
+    let mut z = (base_seed ^ (run_number as u64)).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+//What was done to a run's events, written to the report and to every parquet file of the run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReductionProvenance {
+    pub mode: ReductionMode,
+    pub fraction: f64,
+    pub seed: Option<u64>,
+    pub events_built: u64,
+    pub events_kept: u64,
+}
+
+impl Default for ReductionProvenance {
+    fn default() -> Self {
+        ReductionProvenance {
+            mode: ReductionMode::None,
+            fraction: 1.0,
+            seed: None,
+            events_built: 0,
+            events_kept: 0,
+        }
+    }
+}
+
+//Decides, event by event, which built events of a run are kept
+#[derive(Debug)]
+pub struct EventReducer {
+    params: DataReductionParams,
+    seed: Option<u64>,
+    rng: Option<StdRng>,
+    prescale_offset: u64,
+    events_built: u64,
+    events_kept: u64,
+}
+
+impl EventReducer {
+    pub fn new(params: &DataReductionParams, run_number: i32) -> Self {
+        let seed = if params.is_enabled() {
+            Some(match params.seed {
+                Some(base_seed) => get_run_seed(base_seed, run_number),
+                None => rand::random(),
+            })
+        } else {
+            None
+        };
+        let mut rng = seed.map(StdRng::seed_from_u64);
+        let prescale_offset = match (&mut rng, params.mode) {
+            (Some(rng), ReductionMode::Prescale) => rng.gen_range(0..params.prescale_factor.max(1)),
+            _ => 0,
+        };
+        if let Some(seed) = seed {
+            info!(
+                "Reducing events of run {} with {} (kept fraction {}, seed {})",
+                run_number,
+                params.mode.as_ref(),
+                params.get_fraction(),
+                seed
+            );
+        }
+        EventReducer {
+            params: params.clone(),
+            seed,
+            rng,
+            prescale_offset,
+            events_built: 0,
+            events_kept: 0,
+        }
+    }
+
+    pub fn keep_event(&mut self) -> bool {
+        let index = self.events_built;
+        self.events_built += 1;
+        let is_kept = match (&mut self.rng, self.params.mode) {
+            (Some(_), ReductionMode::Prescale) => {
+                index % self.params.prescale_factor.max(1) == self.prescale_offset
+            }
+            (Some(rng), ReductionMode::Random) => {
+                rng.gen_bool(self.params.fraction.clamp(0.0, 1.0))
+            }
+            _ => true,
+        };
+        if is_kept {
+            self.events_kept += 1;
+        }
+        is_kept
+    }
+
+    pub fn get_provenance(&self) -> ReductionProvenance {
+        ReductionProvenance {
+            mode: self.params.mode,
+            fraction: self.params.get_fraction(),
+            seed: self.seed,
+            events_built: self.events_built,
+            events_kept: self.events_kept,
+        }
+    }
+
+    //The mode is always written, so a file without reduction says so explicitly
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("data_reduction_mode", self.params.mode.as_ref().to_string());
+        if let Some(seed) = self.seed {
+            metadata.insert_file(
+                "data_reduction_fraction",
+                self.params.get_fraction().to_string(),
+            );
+            metadata.insert_file("data_reduction_seed", seed.to_string());
+        }
+    }
+
+    pub fn log_summary(&self) {
+        if self.seed.is_some() {
+            info!(
+                "Kept {} of {} built events ({})",
+                format_count(self.events_kept),
+                format_count(self.events_built),
+                self.params.mode.as_ref()
+            );
+        }
+    }
+}
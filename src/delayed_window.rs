@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::compass_data::CompassData;
+use super::event_builder::EventBuilder;
+use super::used_size::UsedSize;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DelayedWindowParams {
+    pub enabled: bool,
+    //Coincidence window used to build the delayed events (ns)
+    pub coincidence_window: f64,
+    //A delayed event is only tagged with a prompt event that started at most this long before it (ns)
+    pub max_delay: f64,
+}
+
+impl Default for DelayedWindowParams {
+    fn default() -> Self {
+        DelayedWindowParams {
+            enabled: false,
+            coincidence_window: 3.0e3,
+            max_delay: 1.0e6,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TaggedEvent {
+    pub hits: Vec<CompassData>,
+    //Id of the prompt event and the time since it started (ns)
+    pub prompt: Option<(u64, f64)>,
+    //False when the prompt event was dropped by the data reduction
+    pub is_prompt_kept: bool,
+}
+
+//Builds the same hit stream a second time with the delayed window, and tags each delayed event
+//with the latest prompt event that started before it. Delayed events can complete before the
+//prompt events that precede them, so they wait until the prompt side has caught up.
+#[derive(Debug)]
+pub struct DelayedTagger {
+    evb: EventBuilder,
+    max_delay: f64,
+    prompt_starts: VecDeque<(f64, u64, bool)>,
+    pending: VecDeque<Vec<CompassData>>,
+}
+
+impl DelayedTagger {
+    pub fn new(params: &DelayedWindowParams) -> Self {
+        DelayedTagger {
+            evb: EventBuilder::new(&params.coincidence_window),
+            max_delay: params.max_delay,
+            prompt_starts: VecDeque::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn push_hit(&mut self, hit: &CompassData) {
+        self.evb.push_hit(hit);
+        while self.evb.is_event_ready() {
+            self.pending.push_back(self.evb.get_ready_event());
+        }
+    }
+
+    pub fn add_prompt_event(&mut self, prompt_event: &[CompassData], id: u64, is_kept: bool) {
+        if let Some(first) = prompt_event.first() {
+            self.prompt_starts.push_back((first.timestamp, id, is_kept));
+        }
+    }
+
+    //Returns the delayed events whose prompt event is known. At the end of a run pass finished
+    //so that everything still waiting is returned.
+    pub fn take_tagged_events(&mut self, finished: bool) -> Vec<TaggedEvent> {
+        let mut tagged = vec![];
+        let latest_prompt = self.prompt_starts.back().map(|(start, _, _)| *start);
+        while let Some(start) = self
+            .pending
+            .front()
+            .and_then(|event| event.first())
+            .map(|hit| hit.timestamp)
+        {
+            //Until a prompt event starts at or after this one, a closer prompt event may still come
+            let is_resolved = finished || latest_prompt.is_some_and(|latest| latest >= start);
+            if !is_resolved {
+                break;
+            }
+
+            while self
+                .prompt_starts
+                .front()
+                .is_some_and(|(prompt_start, _, _)| start - prompt_start > self.max_delay)
+            {
+                self.prompt_starts.pop_front();
+            }
+            let closest = self
+                .prompt_starts
+                .iter()
+                .rev()
+                .find(|(prompt_start, _, _)| *prompt_start < start);
+            let prompt = closest.map(|(prompt_start, id, _)| (*id, start - prompt_start));
+            let is_prompt_kept = closest.map_or(true, |(_, _, is_kept)| *is_kept);
+
+            if let Some(hits) = self.pending.pop_front() {
+                tagged.push(TaggedEvent {
+                    hits,
+                    prompt,
+                    is_prompt_kept,
+                });
+            }
+        }
+        tagged
+    }
+
+    //Everything still waiting, then starts over for the next run in the file
+    pub fn end_run_segment(&mut self) -> Vec<TaggedEvent> {
+        let tagged = self.take_tagged_events(true);
+        self.evb.end_run_segment();
+        self.prompt_starts.clear();
+        tagged
+    }
+}
+
+impl UsedSize for DelayedTagger {
+    fn get_used_size(&self) -> usize {
+        let pending: usize = self.pending.iter().map(|event| event.get_used_size()).sum();
+        self.evb.get_used_size()
+            + pending
+            + self.prompt_starts.len() * std::mem::size_of::<(f64, u64, bool)>()
+    }
+}
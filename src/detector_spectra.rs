@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::histogram::Histogram;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectorSpectraParams {
+    pub enabled: bool,
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for DetectorSpectraParams {
+    fn default() -> Self {
+        DetectorSpectraParams {
+            enabled: false,
+            bins: 4096,
+            min: 0.0,
+            max: 4096.0,
+        }
+    }
+}
+
+//Written to the report of every run built with detector spectra
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectorSpectrumSummary {
+    pub detector: ChannelType,
+    pub entries: u64,
+    //Hits outside min to max
+    pub out_of_range: u64,
+    //Energy of the highest bin, None without entries
+    pub peak: Option<f64>,
+}
+
+struct DetectorSpectrum {
+    detector: ChannelType,
+    histogram: Histogram,
+    entries: u64,
+    out_of_range: u64,
+}
+
+//Energy spectrum of every CeBrA detector of a run, filled from the hits going into the event
+//builder, so a shift can look at the spectra without reading the events table
+pub struct DetectorSpectra {
+    detectors: Vec<DetectorSpectrum>,
+}
+
+impl DetectorSpectra {
+    pub fn new(params: &DetectorSpectraParams, channel_map: &ChannelMap) -> Self {
+        let detectors = (0..channel_map.get_detector_count())
+            .map(ChannelType::Cebra)
+            .filter(|detector| channel_map.has_channel_type(*detector))
+            .map(|detector| DetectorSpectrum {
+                detector,
+                histogram: Histogram::new(
+                    detector.to_string(),
+                    String::from("Energy"),
+                    String::from("Counts"),
+                    params.bins,
+                    params.min,
+                    params.max,
+                ),
+                entries: 0,
+                out_of_range: 0,
+            })
+            .collect();
+        DetectorSpectra { detectors }
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData, channel_map: &ChannelMap) {
+        let channel_type = match channel_map.get_channel_data(&hit.uuid) {
+            Some(data) => data.channel_type,
+            None => return,
+        };
+        let spectrum = match self
+            .detectors
+            .iter_mut()
+            .find(|spectrum| spectrum.detector == channel_type)
+        {
+            Some(spectrum) => spectrum,
+            None => return,
+        };
+        if hit.energy >= spectrum.histogram.min && hit.energy < spectrum.histogram.max {
+            spectrum.histogram.fill(hit.energy);
+            spectrum.entries += 1;
+        } else {
+            spectrum.out_of_range += 1;
+        }
+    }
+
+    pub fn get_summary(&self) -> Vec<DetectorSpectrumSummary> {
+        self.detectors
+            .iter()
+            .map(|spectrum| DetectorSpectrumSummary {
+                detector: spectrum.detector,
+                entries: spectrum.entries,
+                out_of_range: spectrum.out_of_range,
+                peak: spectrum.histogram.get_peak(),
+            })
+            .collect()
+    }
+
+    pub fn log_summary(&self) {
+        for summary in self.get_summary() {
+            info!(
+                "{} spectrum: {} entries, {} out of range",
+                summary.detector,
+                format_count(summary.entries),
+                format_count(summary.out_of_range)
+            );
+        }
+    }
+
+    //One row per bin, one column per detector
+    pub fn write_spectra(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!(
+            "Writing energy spectra of {} detectors to {}",
+            self.detectors.len(),
+            filepath.display()
+        );
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .detectors
+            .iter()
+            .map(|spectrum| spectrum.detector.to_string())
+            .collect();
+        writeln!(writer, "bin_low,bin_high,{}", headers.join(","))?;
+
+        if let Some(first) = self.detectors.first() {
+            let width = first.histogram.get_bin_width();
+            for bin in 0..first.histogram.counts.len() {
+                let low = first.histogram.min + width * (bin as f64);
+                let counts: Vec<String> = self
+                    .detectors
+                    .iter()
+                    .map(|spectrum| spectrum.histogram.counts[bin].to_string())
+                    .collect();
+                writeln!(writer, "{},{},{}", low, low + width, counts.join(","))?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::ChannelDataField;
+use super::channel_map::ChannelType;
+
+//Efficiency curve of one detector, as fit to a source calibration. The curve is a polynomial in
+//log-log space: ln(eff) = c0 + c1*ln(E) + c2*ln(E)^2 + ..., with E in the same units as the
+//Energy column (i.e. raw channel unless the fit was done against calibrated energies).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EfficiencyEntry {
+    pub detector: ChannelType,
+    pub coefficients: Vec<f64>,
+}
+
+impl EfficiencyEntry {
+    pub fn evaluate(&self, energy: f64) -> Option<f64> {
+        if energy <= 0.0 || self.coefficients.is_empty() {
+            return None;
+        }
+        let log_energy = energy.ln();
+        let log_eff = self
+            .coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |acc, coeff| acc * log_energy + coeff);
+        let efficiency = log_eff.exp();
+        if efficiency.is_finite() && efficiency > 0.0 {
+            Some(efficiency)
+        } else {
+            None
+        }
+    }
+
+    //Weight to apply to a hit so that a filled spectrum is efficiency corrected
+    pub fn weight(&self, energy: f64) -> Option<f64> {
+        self.evaluate(energy).map(|efficiency| 1.0 / efficiency)
+    }
+
+    pub fn describe(&self) -> String {
+        self.coefficients
+            .iter()
+            .map(|coeff| coeff.to_string())
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+//Only detectors with an Energy column can be weighted
+pub fn get_weight_field(detector: &ChannelType) -> Option<ChannelDataField> {
+    match detector {
+        ChannelType::Cebra(detector) => Some(ChannelDataField::CebraEffWeight(*detector)),
+        _ => None,
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::compass_data::generate_board_channel_uuid;
+
+//Quadratic energy calibration of one channel to keV, E_cal = a + b * E + c * E^2, where E is the
+//value of the Energy column (i.e. after gain drift correction when there is one)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyCalibrationEntry {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub a: f64,
+    pub b: f64,
+    #[serde(default)]
+    pub c: f64,
+}
+
+impl EnergyCalibrationEntry {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}.{}:{},{},{}",
+            self.board_number, self.channel_number, self.a, self.b, self.c
+        )
+    }
+}
+
+//The calibrations by channel, for filling the EnergyCal columns
+#[derive(Debug, Clone, Default)]
+pub struct EnergyCalibration {
+    map: HashMap<u32, (f64, f64, f64)>,
+    entries: Vec<EnergyCalibrationEntry>,
+}
+
+impl EnergyCalibration {
+    pub fn new(entries: &[EnergyCalibrationEntry]) -> Self {
+        EnergyCalibration {
+            map: entries
+                .iter()
+                .map(|entry| {
+                    (
+                        generate_board_channel_uuid(&entry.board_number, &entry.channel_number),
+                        (entry.a, entry.b, entry.c),
+                    )
+                })
+                .collect(),
+            entries: entries.to_vec(),
+        }
+    }
+
+    //None for channels without a calibration
+    pub fn calibrate(&self, uuid: &u32, energy: f64) -> Option<f64> {
+        self.map
+            .get(uuid)
+            .map(|(a, b, c)| a + b * energy + c * energy * energy)
+    }
+
+    pub fn describe(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| entry.describe())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
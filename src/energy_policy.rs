@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_data::INVALID_VALUE;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::formatting::format_count;
+use super::parquet_writer::ParquetMetadata;
+
+//What is done with a hit whose energy is zero or wrapped
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum BadEnergyPolicy {
+    //Build the hit as it is, the bad energies are only counted
+    #[default]
+    Keep,
+    //Leave the hit out of the build altogether
+    Drop,
+    //Build the hit with an energy of 0
+    Clamp,
+    //Build the hit for its time, with the invalid value as its energy
+    Null,
+}
+
+//CAEN boards occasionally write an energy of 0, or a value wrapped around the top of the energy
+//range where the true energy was negative. Both are found in the energies as read from the file,
+//before gain drift correction or smearing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BadEnergyParams {
+    pub policy: BadEnergyPolicy,
+    //Raw energies at or above this are wrapped, 32767 is the top of the 15 bit range of the V1730
+    pub wrap_threshold: f64,
+}
+
+impl Default for BadEnergyParams {
+    fn default() -> Self {
+        BadEnergyParams {
+            policy: BadEnergyPolicy::Keep,
+            wrap_threshold: 32767.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BadEnergyChannel {
+    pub board: u32,
+    pub channel: u32,
+    pub zero: u64,
+    pub wrapped: u64,
+}
+
+//Written to the report of every run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BadEnergySummary {
+    pub policy: String,
+    pub zero: u64,
+    pub wrapped: u64,
+    //Only the channels with bad energies
+    pub channels: Vec<BadEnergyChannel>,
+}
+
+#[derive(Debug)]
+pub struct BadEnergyFilter {
+    params: BadEnergyParams,
+    counts: BTreeMap<u32, BadEnergyChannel>,
+}
+
+impl BadEnergyFilter {
+    pub fn new(params: &BadEnergyParams) -> Self {
+        BadEnergyFilter {
+            params: params.clone(),
+            counts: BTreeMap::new(),
+        }
+    }
+
+    //raw_energy is the energy of the hit as read, hit the same hit after any corrections. Integer
+    //energies are dithered by less than 1, so a raw energy of 0 is anything below 1. Returns None
+    //for a dropped hit.
+    pub fn apply(&mut self, raw_energy: f64, hit: CompassData) -> Option<CompassData> {
+        let is_zero = raw_energy < 1.0;
+        let is_wrapped = raw_energy >= self.params.wrap_threshold;
+        if !is_zero && !is_wrapped {
+            return Some(hit);
+        }
+        let counts = self.counts.entry(hit.uuid).or_insert_with(|| {
+            let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+            BadEnergyChannel {
+                board,
+                channel,
+                ..Default::default()
+            }
+        });
+        if is_zero {
+            counts.zero += 1;
+        } else {
+            counts.wrapped += 1;
+        }
+        match self.params.policy {
+            BadEnergyPolicy::Keep => Some(hit),
+            BadEnergyPolicy::Drop => None,
+            BadEnergyPolicy::Clamp => Some(CompassData { energy: 0.0, ..hit }),
+            BadEnergyPolicy::Null => Some(CompassData {
+                energy: INVALID_VALUE,
+                ..hit
+            }),
+        }
+    }
+
+    pub fn get_summary(&self) -> BadEnergySummary {
+        BadEnergySummary {
+            policy: self.params.policy.as_ref().to_string(),
+            zero: self.counts.values().map(|counts| counts.zero).sum(),
+            wrapped: self.counts.values().map(|counts| counts.wrapped).sum(),
+            channels: self.counts.values().cloned().collect(),
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        if summary.zero + summary.wrapped == 0 {
+            info!("No zero or wrapped energies");
+            return;
+        }
+        emit_warning(
+            "bad_energies",
+            String::new(),
+            format!(
+                "Found {} zero and {} wrapped energies in {} channels ({})",
+                format_count(summary.zero),
+                format_count(summary.wrapped),
+                summary.channels.len(),
+                summary.policy
+            ),
+        );
+        for counts in summary.channels.iter() {
+            info!(
+                "  board {} channel {}: {} zero, {} wrapped",
+                counts.board,
+                counts.channel,
+                format_count(counts.zero),
+                format_count(counts.wrapped)
+            );
+        }
+    }
+
+    //Only when the energies were changed
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        if self.params.policy != BadEnergyPolicy::Keep {
+            metadata.insert_file(
+                "bad_energies",
+                format!(
+                    "{} below 1 or at least {}",
+                    self.params.policy.as_ref(),
+                    self.params.wrap_threshold
+                ),
+            );
+        }
+    }
+}
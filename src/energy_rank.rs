@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::{TimeUnit, INVALID_VALUE};
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::energy_calibration::EnergyCalibration;
+use super::parquet_writer::ParquetMetadata;
+
+pub const MAX_ENERGY_RANKS: usize = 16;
+
+//The CeBrA hits of each event ordered by energy, highest first: E1st, T1st, Id1st, E2nd, ...
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyRankParams {
+    pub enabled: bool,
+    //Number of ranks with columns, hits past the last rank are left out
+    pub ranks: usize,
+}
+
+impl Default for EnergyRankParams {
+    fn default() -> Self {
+        EnergyRankParams {
+            enabled: false,
+            ranks: 4,
+        }
+    }
+}
+
+//1st, 2nd, 3rd, 4th, ..., 11th, 12th, 13th, ..., 21st
+pub fn get_rank_suffix(rank: usize) -> String {
+    let suffix = match (rank % 10, rank % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", rank, suffix)
+}
+
+//One rank of the ranked columns, named so they cannot be ChannelDataFields or group columns
+#[derive(Debug, Clone)]
+pub struct RankColumns {
+    pub energy_name: String,
+    pub time_name: String,
+    pub id_name: String,
+    pub energy: Vec<f64>,
+    pub time: Vec<f64>,
+    pub id: Vec<f64>,
+}
+
+impl RankColumns {
+    fn new(rank: usize) -> Self {
+        let suffix = get_rank_suffix(rank);
+        RankColumns {
+            energy_name: format!("E{}", suffix),
+            time_name: format!("T{}", suffix),
+            id_name: format!("Id{}", suffix),
+            energy: vec![],
+            time: vec![],
+            id: vec![],
+        }
+    }
+}
+
+//With an energy calibration, the hits are ranked by their calibrated energy and E holds it in keV,
+//hits of uncalibrated channels are left out. Otherwise E is the raw Energy. Hits with a nulled
+//energy are left out either way.
+#[derive(Debug, Clone)]
+pub struct EnergyRankedColumns {
+    pub ranks: Vec<RankColumns>,
+    pub is_calibrated: bool,
+}
+
+impl EnergyRankedColumns {
+    pub fn new(params: &EnergyRankParams, is_calibrated: bool) -> Self {
+        EnergyRankedColumns {
+            ranks: (1..=params.ranks).map(RankColumns::new).collect(),
+            is_calibrated,
+        }
+    }
+
+    pub fn append_event(
+        &mut self,
+        event: &[CompassData],
+        map: &ChannelMap,
+        calibration: Option<&EnergyCalibration>,
+        time_unit: TimeUnit,
+    ) {
+        let mut hits: Vec<(f64, f64, usize)> = event
+            .iter()
+            .filter(|hit| hit.energy != INVALID_VALUE)
+            .filter_map(|hit| {
+                let detector = match map.get_channel_data(&hit.uuid)?.channel_type {
+                    ChannelType::Cebra(detector) => detector,
+                    _ => return None,
+                };
+                let energy = match calibration {
+                    Some(calibration) => calibration.calibrate(&hit.uuid, hit.energy)?,
+                    None => hit.energy,
+                };
+                Some((energy, hit.timestamp, detector))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (index, rank) in self.ranks.iter_mut().enumerate() {
+            match hits.get(index) {
+                Some((energy, time, detector)) => {
+                    rank.energy.push(*energy);
+                    rank.time.push(time_unit.convert_nanoseconds(*time));
+                    rank.id.push(*detector as f64);
+                }
+                None => {
+                    rank.energy.push(INVALID_VALUE);
+                    rank.time.push(INVALID_VALUE);
+                    rank.id.push(INVALID_VALUE);
+                }
+            }
+        }
+    }
+
+    pub fn iter_columns(&self) -> impl Iterator<Item = (String, &Vec<f64>)> {
+        self.ranks.iter().flat_map(|rank| {
+            [
+                (rank.energy_name.clone(), &rank.energy),
+                (rank.time_name.clone(), &rank.time),
+                (rank.id_name.clone(), &rank.id),
+            ]
+        })
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata, time_unit: TimeUnit) {
+        for rank in self.ranks.iter() {
+            if self.is_calibrated {
+                metadata.insert_column(&rank.energy_name, "unit", String::from("keV"));
+            }
+            metadata.insert_column(&rank.time_name, "unit", time_unit.as_ref().to_string());
+            metadata.insert_column(
+                &rank.id_name,
+                "encoding",
+                String::from("CeBrA detector number"),
+            );
+        }
+    }
+
+    pub fn get_used_size(&self) -> usize {
+        self.ranks
+            .iter()
+            .map(|rank| {
+                (rank.energy.capacity() + rank.time.capacity() + rank.id.capacity())
+                    * std::mem::size_of::<f64>()
+            })
+            .sum()
+    }
+}
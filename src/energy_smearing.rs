@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::data_reduction::get_run_seed;
+use super::parquet_writer::ParquetMetadata;
+
+//Gaussian sigma to FWHM
+const FWHM_PER_SIGMA: f64 = 2.354_820_045;
+
+//Resolution of one detector, as a fraction FWHM/E at reference_energy. The FWHM scales with
+//sqrt(E), as for a statistics limited scintillator. Energies are in the units of the Energy
+//column, i.e. after gain drift correction when there is one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmearingEntry {
+    pub detector: ChannelType,
+    pub resolution: f64,
+    pub reference_energy: f64,
+}
+
+impl Default for SmearingEntry {
+    fn default() -> Self {
+        SmearingEntry {
+            detector: ChannelType::Cebra(0),
+            resolution: 0.04,
+            reference_energy: 662.0,
+        }
+    }
+}
+
+impl SmearingEntry {
+    pub fn get_sigma(&self, energy: f64) -> f64 {
+        let fwhm = self.resolution * (energy.max(0.0) * self.reference_energy).sqrt();
+        fwhm / FWHM_PER_SIGMA
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}:{}@{}",
+            self.detector, self.resolution, self.reference_energy
+        )
+    }
+}
+
+//Simulation support: smear the energies of simulated hits to the detector resolution before they
+//are built, so they can be compared with the data
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct EnergySmearingParams {
+    pub enabled: bool,
+    //Base seed, each run gets its own seed from it. Without one a fresh seed is drawn per run.
+    pub seed: Option<u64>,
+    pub entries: Vec<SmearingEntry>,
+}
+
+//Written to the report, so a smeared build can be repeated
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmearingProvenance {
+    pub seed: u64,
+    pub entries: Vec<SmearingEntry>,
+}
+
+#[derive(Debug)]
+pub struct EnergySmearer {
+    entries: Vec<SmearingEntry>,
+    channels: HashMap<u32, usize>,
+    seed: u64,
+    rng: StdRng,
+}
+
+impl EnergySmearer {
+    pub fn new(params: &EnergySmearingParams, map: &ChannelMap, run_number: i32) -> Option<Self> {
+        if !params.enabled || params.entries.is_empty() {
+            return None;
+        }
+        let seed = match params.seed {
+            Some(base_seed) => get_run_seed(base_seed, run_number),
+            None => rand::random(),
+        };
+        let mut channels = HashMap::new();
+        for (index, entry) in params.entries.iter().enumerate() {
+            for uuid in map.get_uuids_of_type(entry.detector) {
+                channels.insert(uuid, index);
+            }
+        }
+        info!(
+            "Smearing energies of run {} for {} channels, seed {}",
+            run_number,
+            channels.len(),
+            seed
+        );
+        Some(EnergySmearer {
+            entries: params.entries.clone(),
+            channels,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        })
+    }
+
+    //Box-Muller, one standard normal number per call
+    fn get_normal(&mut self) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+
+    //Only the long gate energy is smeared, smeared energies below zero are set to zero
+    pub fn apply(&mut self, hit: CompassData) -> CompassData {
+        let index = match self.channels.get(&hit.uuid) {
+            Some(index) => *index,
+            None => return hit,
+        };
+        let sigma = self.entries[index].get_sigma(hit.energy);
+        let energy = (hit.energy + sigma * self.get_normal()).max(0.0);
+        CompassData { energy, ..hit }
+    }
+
+    pub fn get_provenance(&self) -> SmearingProvenance {
+        SmearingProvenance {
+            seed: self.seed,
+            entries: self.entries.clone(),
+        }
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file(
+            "energy_smearing",
+            self.entries
+                .iter()
+                .map(|entry| entry.describe())
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+        metadata.insert_file("energy_smearing_seed", self.seed.to_string());
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::compass_data::{
+    decompose_uuid_to_board_channel, generate_board_channel_uuid, CompassData,
+};
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::hit_source::{HitSource, Waiting};
+use super::parquet_writer::ParquetMetadata;
+use super::run_boundary::ResetTimes;
+use super::used_size::UsedSize;
+
+//Software threshold of one channel: hits with a raw energy below it are dropped as they are read
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EnergyThresholdEntry {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub threshold: f64,
+}
+
+impl EnergyThresholdEntry {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}.{}:{}",
+            self.board_number, self.channel_number, self.threshold
+        )
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SuppressedChannel {
+    pub board: u32,
+    pub channel: u32,
+    pub threshold: f64,
+    pub suppressed: u64,
+}
+
+//Written to the report of every run built with thresholds
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EnergyThresholdSummary {
+    pub suppressed: u64,
+    //Every channel with a threshold
+    pub channels: Vec<SuppressedChannel>,
+}
+
+#[derive(Debug)]
+struct ChannelThreshold {
+    threshold: f64,
+    suppressed: AtomicU64,
+}
+
+//The thresholds of one run. The sources of the run are read on the decoder threads, so each
+//channel counts its suppressed hits in its own atomic, shared by every source.
+#[derive(Debug, Clone)]
+pub struct EnergyThresholds {
+    channels: Arc<BTreeMap<u32, ChannelThreshold>>,
+}
+
+impl EnergyThresholds {
+    pub fn new(entries: &[EnergyThresholdEntry]) -> Option<Self> {
+        if entries.is_empty() {
+            return None;
+        }
+        Some(EnergyThresholds {
+            channels: Arc::new(
+                entries
+                    .iter()
+                    .map(|entry| {
+                        (
+                            generate_board_channel_uuid(&entry.board_number, &entry.channel_number),
+                            ChannelThreshold {
+                                threshold: entry.threshold,
+                                suppressed: AtomicU64::new(0),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    //Hits below the threshold of their channel are skipped as the source is read, so they never
+    //reach the merge or the event builder
+    pub fn apply<'a>(&self, source: Box<dyn HitSource + 'a>) -> Box<dyn HitSource + 'a> {
+        Box::new(ThresholdSource {
+            inner: source,
+            channels: self.channels.clone(),
+        })
+    }
+
+    pub fn get_summary(&self) -> EnergyThresholdSummary {
+        let channels: Vec<SuppressedChannel> = self
+            .channels
+            .iter()
+            .map(|(uuid, channel)| {
+                let (board, channel_number) = decompose_uuid_to_board_channel(uuid);
+                SuppressedChannel {
+                    board,
+                    channel: channel_number,
+                    threshold: channel.threshold,
+                    suppressed: channel.suppressed.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        EnergyThresholdSummary {
+            suppressed: channels.iter().map(|channel| channel.suppressed).sum(),
+            channels,
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        info!(
+            "Suppressed {} hits below the energy thresholds",
+            format_count(summary.suppressed)
+        );
+        for channel in summary
+            .channels
+            .iter()
+            .filter(|channel| channel.suppressed > 0)
+        {
+            info!(
+                "  board {} channel {}: {} hits below {}",
+                channel.board,
+                channel.channel,
+                format_count(channel.suppressed),
+                channel.threshold
+            );
+        }
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        let thresholds: Vec<String> = self
+            .channels
+            .iter()
+            .map(|(uuid, channel)| {
+                let (board, channel_number) = decompose_uuid_to_board_channel(uuid);
+                EnergyThresholdEntry {
+                    board_number: board,
+                    channel_number,
+                    threshold: channel.threshold,
+                }
+                .describe()
+            })
+            .collect();
+        metadata.insert_file("energy_thresholds", thresholds.join(" "));
+    }
+}
+
+struct ThresholdSource<'a> {
+    inner: Box<dyn HitSource + 'a>,
+    channels: Arc<BTreeMap<u32, ChannelThreshold>>,
+}
+
+impl HitSource for ThresholdSource<'_> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        loop {
+            let hit = self.inner.get_top_hit()?;
+            //Default hits mark the end of the source or a run boundary
+            let channel = match self.channels.get(&hit.uuid) {
+                Some(channel) if !hit.is_default() && hit.energy < channel.threshold => channel,
+                _ => break,
+            };
+            channel.suppressed.fetch_add(1, Ordering::Relaxed);
+            self.inner.set_hit_used();
+        }
+        self.inner.get_top_hit()
+    }
+
+    fn set_hit_used(&mut self) {
+        self.inner.set_hit_used();
+    }
+
+    fn is_eof(&self) -> bool {
+        self.inner.is_eof()
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.inner.get_number_of_hits()
+    }
+
+    fn get_boundary(&self) -> Option<ResetTimes> {
+        self.inner.get_boundary()
+    }
+
+    fn start_next_run(&mut self) {
+        self.inner.start_next_run();
+    }
+
+    fn get_waiting(&self) -> Option<Waiting> {
+        self.inner.get_waiting()
+    }
+}
+
+impl UsedSize for ThresholdSource<'_> {
+    fn get_used_size(&self) -> usize {
+        self.inner.get_used_size()
+    }
+}
@@ -1,4 +1,5 @@
 use super::channel_map::ChannelMapError;
+use super::corrupt_file::CompassFileError;
 use super::shift_map::ShiftError;
 use flate2::DecompressError;
 use polars::error::PolarsError;
@@ -8,13 +9,33 @@ use std::fmt::Display;
 #[derive(Debug)]
 pub enum EVBError {
     Compressor(DecompressError),
-    Waves,
     File(std::io::Error),
     Parser,
+    CompassFile(CompassFileError),
     Channel(ChannelMapError),
     DataFrame(PolarsError),
     ShiftMap(ShiftError),
+    Yaml(serde_yaml::Error),
+    Json(serde_json::Error),
+    Csv(String),
+    Simulation(String),
+    MissingTrigger(String),
+    Plot(String),
+    Paranoid(String),
+    Regression(String),
+    Root(String),
+    Hdf5(String),
+    Follow(String),
+    Slice(String),
+    Merge(String),
+    Archive(String),
+    Settings(String),
+    Bundle(String),
+    Resource(String),
+    Config(String),
+    TimeWalk(String),
     Sync,
+    Interrupted,
 }
 
 impl From<std::io::Error> for EVBError {
@@ -29,6 +50,12 @@ impl From<DecompressError> for EVBError {
     }
 }
 
+impl From<CompassFileError> for EVBError {
+    fn from(err: CompassFileError) -> EVBError {
+        EVBError::CompassFile(err)
+    }
+}
+
 impl From<ChannelMapError> for EVBError {
     fn from(err: ChannelMapError) -> EVBError {
         EVBError::Channel(err)
@@ -47,22 +74,59 @@ impl From<ShiftError> for EVBError {
     }
 }
 
+impl From<serde_yaml::Error> for EVBError {
+    fn from(value: serde_yaml::Error) -> Self {
+        EVBError::Yaml(value)
+    }
+}
+
+impl From<serde_json::Error> for EVBError {
+    fn from(value: serde_json::Error) -> Self {
+        EVBError::Json(value)
+    }
+}
+
 impl Display for EVBError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             EVBError::Compressor(x) => write!(f, "Run had a decompression error: {}", x),
-            EVBError::Waves => write!(
-                f,
-                "Run found a file with waveform data, which is not supported!"
-            ),
             EVBError::File(x) => write!(f, "Run had a file I/O error: {}", x),
             EVBError::Parser => write!(f, "Run had an error parsing the data from files"),
+            EVBError::CompassFile(x) => write!(f, "Run had a corrupted CoMPASS file: {}", x),
             EVBError::Channel(x) => {
                 write!(f, "Run had an error occur with the channel map: {}", x)
             }
             EVBError::DataFrame(x) => write!(f, "Run had an error using polars: {}", x),
             EVBError::ShiftMap(x) => write!(f, "Run had an error with the shift map: {}", x),
+            EVBError::Yaml(x) => write!(f, "Run had an error with a YAML file: {}", x),
+            EVBError::Json(x) => write!(f, "Run had an error with a JSON file: {}", x),
+            EVBError::Csv(x) => write!(f, "Run had an error in a CSV hit file: {}", x),
+            EVBError::Simulation(x) => {
+                write!(f, "Run had an error in a simulated hit file: {}", x)
+            }
+            EVBError::MissingTrigger(x) => write!(f, "Run has no trigger hits: {}", x),
+            EVBError::Plot(x) => write!(f, "Unable to export plot: {}", x),
+            EVBError::Paranoid(x) => write!(f, "Run failed a paranoid check: {}", x),
+            EVBError::Regression(x) => write!(f, "Unable to build the reference run: {}", x),
+            EVBError::Root(x) => write!(f, "Unable to write a ROOT file: {}", x),
+            EVBError::Hdf5(x) => write!(f, "Unable to write an HDF5 file: {}", x),
+            EVBError::Follow(x) => write!(f, "Unable to follow the run: {}", x),
+            EVBError::Slice(x) => write!(f, "Unable to slice the run: {}", x),
+            EVBError::Merge(x) => write!(f, "Unable to merge the runs: {}", x),
+            EVBError::Archive(x) => write!(f, "Unable to unpack the run archive: {}", x),
+            EVBError::Settings(x) => write!(f, "Unable to read the CoMPASS settings: {}", x),
+            EVBError::Bundle(x) => write!(f, "Unable to use the workspace bundle: {}", x),
+            EVBError::Resource(x) => write!(f, "Unable to set up the build threads: {}", x),
+            EVBError::Config(x) => write!(f, "Config is invalid: {}", x),
+            EVBError::TimeWalk(x) => {
+                write!(
+                    f,
+                    "Run had an error with the time walk correction file: {}",
+                    x
+                )
+            }
             EVBError::Sync => write!(f, "Run was unable to access shared progress resource"),
+            EVBError::Interrupted => write!(f, "Build interrupted by a signal"),
         }
     }
 }
@@ -1,11 +1,127 @@
+use std::collections::{HashSet, VecDeque};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_map::ChannelType;
 use super::compass_data::CompassData;
+use super::formatting::format_count;
+use super::pair_window::PairWindows;
+use super::used_size::UsedSize;
+
+//What to do with a run in which the trigger channel has no hits (or is not in the channel map)
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum MissingTriggerBehavior {
+    #[default]
+    #[strum(serialize = "Fail")]
+    Fail,
+    #[strum(serialize = "Earliest Hit")]
+    EarliestHit,
+}
+
+//How the coincidence windows are placed on the hits
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum BuildStrategy {
+    //A window opens on the earliest hit (or trigger hit) and covers the window after it
+    #[default]
+    #[strum(serialize = "Leading Edge")]
+    LeadingEdge,
+    //Windows are fixed slices of the window width, counted from timestamp 0, whatever the hits
+    #[strum(serialize = "Fixed Window")]
+    FixedWindow,
+    //A window opens on a trigger hit and covers the window before and after it
+    #[strum(serialize = "Reference Trigger")]
+    ReferenceTrigger,
+}
+
+//With a trigger channel, events are only opened by a hit in that channel, and hits outside the
+//window of a trigger hit are dropped. Without one, the earliest hit opens the event.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TriggerParams {
+    pub channel: Option<ChannelType>,
+    pub on_missing: MissingTriggerBehavior,
+    #[serde(default)]
+    pub strategy: BuildStrategy,
+}
+
+//What the builder did with the hits it was given. Windows have no size limit, they are only closed
+//by the first hit past the end of the window, so every closed window is one event.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventBuilderStats {
+    pub windows_opened: u64,
+    //Hits added to an already open window
+    pub hits_coalesced: u64,
+    pub windows_closed: u64,
+    //Trigger mode only: hits that arrived with no window open and could not open one (with a
+    //reference trigger, no trigger hit within a window after them either)
+    pub hits_dropped: u64,
+    //Hits of the window still open when the run ended, which is never built into an event
+    pub hits_left_open: u64,
+    //Online only: windows closed before their end because they were open too long in wall time
+    pub windows_flushed: u64,
+    //Pair windows only: hits taken out of a closed window for being outside the window of their
+    //pair with its first hit, and built again into the events after it. A hit is counted each time
+    //it is taken out.
+    #[serde(default)]
+    pub hits_rebuilt: u64,
+}
+
+//Hits of the window still open at the end of a run and of the reference trigger look back, to be
+//carried on into the next run (see run_stitching)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenWindow {
+    event: Vec<CompassData>,
+    lookback: Vec<CompassData>,
+}
+
+impl OpenWindow {
+    pub fn len(&self) -> usize {
+        self.event.len() + self.lookback.len()
+    }
 
-#[derive(Debug)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get_last_time(&self) -> Option<f64> {
+        self.event
+            .iter()
+            .chain(self.lookback.iter())
+            .map(|hit| hit.timestamp)
+            .reduce(f64::max)
+    }
+
+    pub fn shift(&mut self, offset: f64) {
+        for hit in self.event.iter_mut().chain(self.lookback.iter_mut()) {
+            hit.timestamp += offset;
+        }
+    }
+}
+
+//The open window and the counts of a builder, kept in the checkpoint of a run (see checkpoint)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BuilderCheckpoint {
+    window: OpenWindow,
+    stats: EventBuilderStats,
+    trigger_hits: u64,
+}
+
+#[derive(Debug, Clone)]
 pub struct EventBuilder {
     coincidence_window: f64,
     event: Vec<CompassData>,
-    ready_event: Vec<CompassData>,
-    is_event_ready: bool,
+    //A hit closes one window at a time, but with pair windows the hits built again after it can
+    //close more
+    ready_events: VecDeque<Vec<CompassData>>,
+    trigger_uuids: Option<HashSet<u32>>,
+    trigger_hits: u64,
+    strategy: BuildStrategy,
+    //Reference trigger only: hits of the last window that are in no event yet, for the next
+    //trigger hit to look back on
+    lookback: VecDeque<CompassData>,
+    stats: EventBuilderStats,
+    pair_windows: Option<PairWindows>,
 }
 
 impl EventBuilder {
@@ -13,33 +129,334 @@ impl EventBuilder {
         EventBuilder {
             coincidence_window: *window,
             event: vec![],
-            ready_event: vec![],
-            is_event_ready: false,
+            ready_events: VecDeque::new(),
+            trigger_uuids: None,
+            trigger_hits: 0,
+            strategy: BuildStrategy::default(),
+            lookback: VecDeque::new(),
+            stats: EventBuilderStats::default(),
+            pair_windows: None,
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger_uuids: HashSet<u32>) -> Self {
+        self.trigger_uuids = Some(trigger_uuids);
+        self
+    }
+
+    //A reference trigger strategy without trigger channels builds in leading edge mode
+    pub fn with_strategy(mut self, strategy: BuildStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    //Only for the leading edge strategy, see push_leading_edge
+    pub fn with_pair_windows(mut self, pair_windows: PairWindows) -> Self {
+        self.pair_windows = Some(pair_windows);
+        self
+    }
+
+    //Largest time between the first and last hit of an event
+    pub fn get_max_event_span(&self) -> f64 {
+        if self.is_reference_trigger() {
+            2.0 * self.coincidence_window
+        } else if let Some(pair_windows) = self.get_pair_windows() {
+            pair_windows.get_longest_window()
+        } else {
+            self.coincidence_window
         }
     }
 
+    pub fn get_pair_windows(&self) -> Option<&PairWindows> {
+        self.pair_windows
+            .as_ref()
+            .filter(|_| self.strategy == BuildStrategy::LeadingEdge)
+    }
+
+    pub fn get_strategy(&self) -> BuildStrategy {
+        self.strategy
+    }
+
+    fn is_reference_trigger(&self) -> bool {
+        self.strategy == BuildStrategy::ReferenceTrigger && self.trigger_uuids.is_some()
+    }
+
+    pub fn get_trigger_hits(&self) -> u64 {
+        self.trigger_hits
+    }
+
+    pub fn get_windows_opened(&self) -> u64 {
+        self.stats.windows_opened
+    }
+
+    pub fn has_open_window(&self) -> bool {
+        !self.event.is_empty()
+    }
+
     pub fn is_event_ready(&self) -> bool {
-        self.is_event_ready
+        !self.ready_events.is_empty()
+    }
+
+    fn can_open(&self, hit: &CompassData) -> bool {
+        match &self.trigger_uuids {
+            Some(uuids) => uuids.contains(&hit.uuid),
+            None => true,
+        }
     }
 
     pub fn push_hit(&mut self, hit: &CompassData) {
-        if self.event.is_empty() {
-            self.event.push(hit.clone());
+        //Fixed windows have no trigger, so none of their hits count as trigger hits
+        if self.strategy == BuildStrategy::FixedWindow {
+            self.push_fixed_window(hit);
             return;
         }
+        let can_open = self.can_open(hit);
+        if can_open && self.trigger_uuids.is_some() {
+            self.trigger_hits += 1;
+        }
 
-        if hit.timestamp - self.event[0].timestamp < self.coincidence_window {
+        if self.is_reference_trigger() {
+            self.push_reference_trigger(hit, can_open);
+            return;
+        }
+        self.push_leading_edge(hit, can_open);
+    }
+
+    //With pair windows, a window stays open for the longest window of the group of its first hit
+    //with any group. Hits in it but outside the window of their own pair with the first hit are
+    //built again once it closes.
+    fn push_leading_edge(&mut self, hit: &CompassData, can_open: bool) {
+        let first = match self.event.first() {
+            Some(first) => first,
+            None => {
+                self.open_window(hit, can_open);
+                return;
+            }
+        };
+        let (open_window, is_coincident) = match self.get_pair_windows() {
+            Some(pair_windows) => (
+                pair_windows.get_open_window(first),
+                pair_windows.is_coincident(first, hit),
+            ),
+            None => (self.coincidence_window, true),
+        };
+        if hit.timestamp - first.timestamp < open_window {
             self.event.push(hit.clone());
+            if is_coincident {
+                self.stats.hits_coalesced += 1;
+            }
         } else {
-            self.ready_event.clone_from(&self.event);
-            self.is_event_ready = true;
-            self.event.clear();
+            self.close_window();
+            self.push_leading_edge(hit, can_open);
+        }
+    }
+
+    //Hits in the same slice of the time axis make up an event
+    fn push_fixed_window(&mut self, hit: &CompassData) {
+        let slice = |timestamp: f64| (timestamp / self.coincidence_window).floor();
+        if !self.event.is_empty() && slice(hit.timestamp) != slice(self.event[0].timestamp) {
+            self.close_window();
+        }
+        if self.event.is_empty() {
+            self.stats.windows_opened += 1;
+        } else {
+            self.stats.hits_coalesced += 1;
+        }
+        self.event.push(hit.clone());
+    }
+
+    //The window of an event is centered on its first trigger hit, so the hits that came before the
+    //trigger are kept back until they are more than a window older than the latest hit
+    fn push_reference_trigger(&mut self, hit: &CompassData, can_open: bool) {
+        let trigger_time = self
+            .event
+            .iter()
+            .find(|event_hit| self.is_trigger(event_hit))
+            .map(|trigger| trigger.timestamp);
+        if let Some(trigger_time) = trigger_time {
+            if hit.timestamp - trigger_time < self.coincidence_window {
+                self.event.push(hit.clone());
+                self.stats.hits_coalesced += 1;
+                return;
+            }
+            self.close_window();
+        }
+
+        while self
+            .lookback
+            .front()
+            .is_some_and(|old| hit.timestamp - old.timestamp >= self.coincidence_window)
+        {
+            self.lookback.pop_front();
+            self.stats.hits_dropped += 1;
+        }
+        if can_open {
+            self.stats.hits_coalesced += self.lookback.len() as u64;
+            self.event.extend(self.lookback.drain(..));
             self.event.push(hit.clone());
+            self.stats.windows_opened += 1;
+        } else {
+            self.lookback.push_back(hit.clone());
+        }
+    }
+
+    fn is_trigger(&self, hit: &CompassData) -> bool {
+        self.trigger_uuids
+            .as_ref()
+            .is_some_and(|uuids| uuids.contains(&hit.uuid))
+    }
+
+    fn close_window(&mut self) {
+        let mut event = std::mem::take(&mut self.event);
+        let mut rebuilt = vec![];
+        if let Some((pair_windows, first)) = self.get_pair_windows().zip(event.first()) {
+            let first = first.clone();
+            (event, rebuilt) = event
+                .into_iter()
+                .partition(|hit| pair_windows.is_coincident(&first, hit));
+        }
+        self.ready_events.push_back(event);
+        self.stats.windows_closed += 1;
+        self.stats.hits_rebuilt += rebuilt.len() as u64;
+        for hit in rebuilt.iter() {
+            self.push_leading_edge(hit, self.can_open(hit));
+        }
+    }
+
+    fn open_window(&mut self, hit: &CompassData, can_open: bool) {
+        if can_open {
+            self.event.push(hit.clone());
+            self.stats.windows_opened += 1;
+        } else {
+            self.stats.hits_dropped += 1;
+        }
+    }
+
+    //Closes the open window as it is, so the next hit opens a new one even if it would have been
+    //inside this window
+    pub fn flush_window(&mut self) {
+        if self.event.is_empty() {
+            return;
+        }
+        self.close_window();
+        self.stats.windows_flushed += 1;
+    }
+
+    //Closes the open window at the end of a chunk of a parallel build, which is only cut where no
+    //later hit can be in the window (see parallel_build). Hits waiting for a reference trigger get
+    //none, and are dropped as a single builder drops them at the next hit.
+    pub fn close_chunk(&mut self) {
+        while !self.event.is_empty() {
+            self.close_window();
+        }
+        self.stats.hits_dropped += self.lookback.len() as u64;
+        self.lookback.clear();
+    }
+
+    //At a run boundary inside a file the open window is dropped, as at the end of a run
+    pub fn end_run_segment(&mut self) {
+        self.stats.hits_left_open += self.event.len() as u64;
+        self.event.clear();
+        self.stats.hits_dropped += self.lookback.len() as u64;
+        self.lookback.clear();
+    }
+
+    //Takes the open window at the end of a run instead of dropping it
+    pub fn take_open_window(&mut self) -> OpenWindow {
+        OpenWindow {
+            event: std::mem::take(&mut self.event),
+            lookback: self.lookback.drain(..).collect(),
+        }
+    }
+
+    //Starts a run with the window carried from the run before, as if its hits had just been pushed.
+    //The window was opened in that run, so it is only counted as closed in this one.
+    pub fn resume_window(&mut self, window: OpenWindow) {
+        self.event = window.event;
+        self.lookback = window.lookback.into();
+    }
+
+    //Taken between hits, with no event waiting to be taken
+    pub fn get_checkpoint(&self) -> BuilderCheckpoint {
+        BuilderCheckpoint {
+            window: OpenWindow {
+                event: self.event.clone(),
+                lookback: self.lookback.iter().cloned().collect(),
+            },
+            stats: self.stats.clone(),
+            trigger_hits: self.trigger_hits,
+        }
+    }
+
+    //Picks the build up where the checkpoint was taken, as if the hits before it had been pushed
+    pub fn resume_checkpoint(&mut self, checkpoint: BuilderCheckpoint) {
+        self.resume_window(checkpoint.window);
+        self.stats = checkpoint.stats;
+        self.trigger_hits = checkpoint.trigger_hits;
+    }
+
+    pub fn get_stats(&self) -> EventBuilderStats {
+        EventBuilderStats {
+            hits_dropped: self.stats.hits_dropped + self.lookback.len() as u64,
+            hits_left_open: self.stats.hits_left_open + self.event.len() as u64,
+            ..self.stats.clone()
         }
     }
 
     pub fn get_ready_event(&mut self) -> Vec<CompassData> {
-        self.is_event_ready = false;
-        self.ready_event.clone()
+        self.ready_events.pop_front().unwrap_or_default()
+    }
+}
+
+impl EventBuilderStats {
+    //Counts of the chunks of a parallel build are summed
+    pub fn add(&mut self, other: &EventBuilderStats) {
+        self.windows_opened += other.windows_opened;
+        self.hits_coalesced += other.hits_coalesced;
+        self.windows_closed += other.windows_closed;
+        self.hits_dropped += other.hits_dropped;
+        self.hits_left_open += other.hits_left_open;
+        self.windows_flushed += other.windows_flushed;
+        self.hits_rebuilt += other.hits_rebuilt;
+    }
+
+    pub fn log_summary(&self) {
+        info!(
+            "Event builder opened {} windows, closed {}, coalesced {} hits into open windows",
+            format_count(self.windows_opened),
+            format_count(self.windows_closed),
+            format_count(self.hits_coalesced)
+        );
+        if self.hits_dropped > 0 {
+            info!(
+                "{} hits were outside of any trigger window",
+                format_count(self.hits_dropped)
+            );
+        }
+        if self.hits_rebuilt > 0 {
+            info!(
+                "Hits were outside the pair window with the first hit of their window {} times and \
+                 built again",
+                format_count(self.hits_rebuilt)
+            );
+        }
+        if self.windows_flushed > 0 {
+            info!(
+                "{} windows were flushed for being open too long",
+                format_count(self.windows_flushed)
+            );
+        }
+    }
+}
+
+impl UsedSize for EventBuilder {
+    fn get_used_size(&self) -> usize {
+        self.event.get_used_size()
+            + self
+                .ready_events
+                .iter()
+                .map(|event| event.get_used_size())
+                .sum::<usize>()
+            + self.lookback.len() * std::mem::size_of::<CompassData>()
     }
 }
@@ -0,0 +1,204 @@
+use std::path::Path;
+
+use log::info;
+
+use super::build_diagnostics::emit_warning;
+use super::channel_data::{ChannelData, ChannelDataField};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+
+//CoMPASS writes run.info next to the data of every run, with the start of the acquisition as e.g.
+//time.start=2023/05/17 15:32:47.123-0400
+const RUN_INFO_NAME: &str = "run.info";
+const TIME_START_KEY: &str = "time.start";
+
+pub fn is_run_info_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.eq_ignore_ascii_case(RUN_INFO_NAME))
+}
+
+//Start of the run in seconds since 1970-01-01 UTC, from the time.start line of a run.info file
+pub fn read_run_start(path: &Path) -> Result<f64, EVBError> {
+    let text = std::fs::read_to_string(path)?;
+    let value = text
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(key, _)| key.trim() == TIME_START_KEY)
+        .map(|(_, value)| value.trim())
+        .ok_or_else(|| {
+            get_run_info_error(format!("{} has no {} line", path.display(), TIME_START_KEY))
+        })?;
+    parse_start_time(value).ok_or_else(|| {
+        get_run_info_error(format!(
+            "{}: could not read the run start {}",
+            path.display(),
+            value
+        ))
+    })
+}
+
+fn get_run_info_error(message: String) -> EVBError {
+    EVBError::File(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message,
+    ))
+}
+
+//"yyyy/mm/dd hh:mm:ss[.fff][+hhmm]", dashes are taken in the date too. Without a zone the time is
+//taken as UTC.
+fn parse_start_time(value: &str) -> Option<f64> {
+    let (date, time) = value.split_once(' ')?;
+    let date: Vec<i64> = date
+        .split(['/', '-'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<i64>>>()?;
+    let [year, month, day] = date[..] else {
+        return None;
+    };
+    let time = time.trim();
+    let (clock, offset) = match time.find(['+', '-', 'Z']) {
+        Some(index) => (&time[..index], parse_zone(&time[index..])?),
+        None => (time, 0),
+    };
+    let clock: Vec<f64> = clock
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    let [hours, minutes, seconds] = clock[..] else {
+        return None;
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = get_days_from_epoch(year, month, day);
+    Some((days * 86400 - offset) as f64 + hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+//Offset of the zone from UTC in seconds, from "Z", "+hhmm" or "+hh:mm"
+fn parse_zone(zone: &str) -> Option<i64> {
+    let (sign, digits) = match zone.split_at(1) {
+        ("Z", "") => return Some(0),
+        ("+", digits) => (1, digits.replace(':', "")),
+        ("-", digits) => (-1, digits.replace(':', "")),
+        _ => return None,
+    };
+    if digits.len() != 4 {
+        return None;
+    }
+    let hours: i64 = digits[..2].parse().ok()?;
+    let minutes: i64 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+//Days from 1970-01-01 to a date of the proleptic Gregorian calendar
+fn get_days_from_epoch(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+//The time of every built event from the one built before it, and its wall-clock time from the
+//start of the run plus the timestamp of its first hit. Every event built counts, whether it is
+//kept in the tables or not, so dropping events does not change the times of the others.
+#[derive(Debug, Clone)]
+pub struct EventClock {
+    //Seconds since 1970-01-01 UTC
+    run_start: Option<f64>,
+    //ns
+    previous: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventTimes {
+    //ns
+    since_previous: Option<f64>,
+    //Seconds since 1970-01-01 UTC
+    wall_clock: Option<f64>,
+}
+
+impl EventClock {
+    //run_info is the run.info file of the run, if there is one
+    pub fn new(run_info: Option<&Path>) -> Self {
+        let run_start = match run_info.map(read_run_start) {
+            Some(Ok(start)) => {
+                info!("Run started {} s after 1970-01-01 UTC", start);
+                Some(start)
+            }
+            Some(Err(e)) => {
+                emit_warning(
+                    "run_start",
+                    String::new(),
+                    format!("{}, every WallClock is invalid", e),
+                );
+                None
+            }
+            None => {
+                emit_warning(
+                    "run_start",
+                    String::new(),
+                    String::from("Run has no run.info file, every WallClock is invalid"),
+                );
+                None
+            }
+        };
+        EventClock {
+            run_start,
+            previous: None,
+        }
+    }
+
+    //Call for every event in the order they are built
+    pub fn next_event(&mut self, event: &[CompassData]) -> EventTimes {
+        let time = match event.first() {
+            Some(hit) => hit.timestamp,
+            None => return EventTimes::default(),
+        };
+        let since_previous = self.previous.map(|previous| time - previous);
+        self.previous = Some(time);
+        EventTimes {
+            since_previous,
+            wall_clock: self.run_start.map(|start| start + time * 1.0e-9),
+        }
+    }
+
+    //Times after a run boundary are on a new clock, started at a time the run.info does not give
+    pub fn end_run_segment(&mut self) {
+        if self.run_start.is_some() {
+            emit_warning(
+                "run_start",
+                String::new(),
+                String::from(
+                    "Start of the next run in the files is unknown, its WallClock is invalid",
+                ),
+            );
+        }
+        self.run_start = None;
+        self.previous = None;
+    }
+}
+
+impl EventTimes {
+    //Fills the columns of the most recently appended event
+    pub fn set_event_values(&self, data: &mut ChannelData) {
+        if let Some(time) = self.since_previous {
+            data.set_event_value(&ChannelDataField::TimeSincePrevious, time);
+        }
+        if let Some(time) = self.wall_clock {
+            data.set_event_value(&ChannelDataField::WallClock, time);
+        }
+    }
+
+    //Fills the columns of one row, for the events of a chunk built on another thread
+    pub fn set_row_values(&self, data: &mut ChannelData, row: usize) {
+        if let Some(time) = self.since_previous {
+            data.set_row_value(&ChannelDataField::TimeSincePrevious, row, time);
+        }
+        if let Some(time) = self.wall_clock {
+            data.set_row_value(&ChannelDataField::WallClock, row, time);
+        }
+    }
+}
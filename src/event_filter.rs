@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_map::ChannelType;
+use super::compass_data::CompassData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum EventFilterKind {
+    //A CeBrA detector has a hit with an energy of at least min
+    #[default]
+    #[strum(serialize = "CeBrA Energy Above")]
+    CebraEnergyAbove,
+    //The detector has a hit in the event
+    #[strum(serialize = "Detector Present")]
+    DetectorPresent,
+    //The value of a column of the event is in [min, max)
+    #[strum(serialize = "Column Gate")]
+    ColumnGate,
+}
+
+//One condition an event must meet to be written. Only the values of its kind are used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventFilterEntry {
+    pub kind: EventFilterKind,
+    pub detector: ChannelType,
+    pub column: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for EventFilterEntry {
+    fn default() -> Self {
+        EventFilterEntry {
+            kind: EventFilterKind::default(),
+            detector: ChannelType::Cebra(0),
+            column: String::new(),
+            min: 0.0,
+            max: 0.0,
+        }
+    }
+}
+
+impl EventFilterEntry {
+    pub fn describe(&self) -> String {
+        match self.kind {
+            EventFilterKind::CebraEnergyAbove => format!("CeBrA energy >= {}", self.min),
+            EventFilterKind::DetectorPresent => format!("{} present", self.detector),
+            EventFilterKind::ColumnGate => {
+                format!("{} <= {} < {}", self.min, self.column, self.max)
+            }
+        }
+    }
+}
+
+//Every filter must pass for an event to be appended. The filters on the hits are checked before
+//the event is filled, the column gates on its filled row.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilters {
+    entries: Vec<EventFilterEntry>,
+}
+
+impl EventFilters {
+    pub fn new(entries: &[EventFilterEntry]) -> Self {
+        EventFilters {
+            entries: entries.to_vec(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn has_column_gates(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.kind == EventFilterKind::ColumnGate)
+    }
+
+    //Nulled energies are below any threshold
+    pub fn accept_hits(
+        &self,
+        event: &[CompassData],
+        channel_types: &[Option<ChannelType>],
+    ) -> bool {
+        self.entries.iter().all(|entry| match entry.kind {
+            EventFilterKind::CebraEnergyAbove => {
+                event
+                    .iter()
+                    .zip(channel_types.iter())
+                    .any(|(hit, channel_type)| {
+                        matches!(channel_type, Some(ChannelType::Cebra(_)))
+                            && hit.energy >= entry.min
+                    })
+            }
+            EventFilterKind::DetectorPresent => channel_types.contains(&Some(entry.detector)),
+            EventFilterKind::ColumnGate => true,
+        })
+    }
+
+    //get_value gives the value of a column of the filled row, None if it is invalid or there is
+    //no such column
+    pub fn accept_row(&self, get_value: impl Fn(&str) -> Option<f64>) -> bool {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == EventFilterKind::ColumnGate)
+            .all(|entry| {
+                get_value(&entry.column)
+                    .is_some_and(|value| value >= entry.min && value < entry.max)
+            })
+    }
+
+    pub fn describe(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| entry.describe())
+            .collect::<Vec<String>>()
+            .join(" and ")
+    }
+}
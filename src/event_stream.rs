@@ -0,0 +1,184 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use polars::prelude::*;
+
+use super::app::EvbAppParams;
+use super::channel_data::{ChannelData, ChannelDataField, TimeUnit};
+use super::channel_map::{Board, ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::event_builder::{BuildStrategy, EventBuilder, EventBuilderStats};
+use super::focal_plane::SpsParams;
+use super::parquet_writer::ParquetMetadata;
+use super::unmapped::UnmappedPolicy;
+
+//Event building without files or the GUI, for programs using the crate as a library (e.g. the
+//online analysis): hits are pushed in time order, and every event they complete is returned and
+//added to a table that can be taken as a DataFrame at any time. The hits are used as given, so any
+//time shifts or gain corrections must already be applied.
+pub struct EventStream {
+    map: ChannelMap,
+    evb: EventBuilder,
+    template: ChannelData,
+    data: ChannelData,
+    max_latency: Option<Duration>,
+    //Wall time the open window was opened at
+    window_opened: Option<Instant>,
+}
+
+impl EventStream {
+    pub fn new(boards: &[Board], coincidence_window: f64) -> Self {
+        let map = ChannelMap::new(boards);
+        let mut template = ChannelData::new(TimeUnit::default(), map.get_detector_count(), &[])
+            .with_passthrough(map.get_passthrough_count(), &[]);
+        if map.has_sps_channels() {
+            template = template.with_focal_plane(SpsParams::default());
+        }
+        EventStream {
+            evb: EventBuilder::new(&coincidence_window),
+            data: template.clone(),
+            template,
+            map,
+            max_latency: None,
+            window_opened: None,
+        }
+    }
+
+    //Same channel map, window, trigger and columns as the GUI would build with the config file
+    pub fn from_config(path: &Path) -> Result<Self, EVBError> {
+        Self::from_params(&EvbAppParams::read(path)?)
+    }
+
+    //The same from a config that was already read
+    pub fn from_params(config: &EvbAppParams) -> Result<Self, EVBError> {
+        let map = ChannelMap::new(&config.channel_map_entries);
+        let mut template = ChannelData::new(
+            config.time_unit,
+            map.get_detector_count(),
+            &config.efficiency_entries,
+        )
+        .with_detector_groups(&config.detector_groups)
+        .with_passthrough(map.get_passthrough_count(), &config.passthrough)
+        .with_multi_hit(&config.multi_hit)
+        .with_event_filters(&config.event_filters)
+        .with_column_naming(&config.column_naming)
+        .with_invalid_values(&config.invalid_values);
+        if config.unmapped_channels == UnmappedPolicy::Columns {
+            template = template.with_unmapped_columns(&map);
+        }
+        if !config.energy_calibration.is_empty() {
+            template = template.with_energy_calibration(&config.energy_calibration);
+        }
+        if config.energy_rank.enabled {
+            template = template.with_energy_rank(&config.energy_rank);
+        }
+        if map.has_sps_channels() {
+            template = template.with_focal_plane(config.sps.clone());
+        }
+        let stream = EventStream {
+            evb: EventBuilder::new(&config.get_coincidence_window())
+                .with_strategy(config.trigger.strategy),
+            data: template.clone(),
+            template,
+            map,
+            max_latency: None,
+            window_opened: None,
+        };
+        match config.trigger.channel {
+            Some(channel) => stream.with_trigger(channel),
+            None => Ok(stream),
+        }
+    }
+
+    //Only hits of this detector open events, as with the Trigger Channel of a build. Fixed window
+    //events are opened by any hit, so they cannot have one.
+    pub fn with_trigger(mut self, channel: ChannelType) -> Result<Self, EVBError> {
+        if self.evb.get_strategy() == BuildStrategy::FixedWindow {
+            return Err(EVBError::Config(format!(
+                "fixed window events cannot have a trigger channel, found {}",
+                channel
+            )));
+        }
+        let uuids = self.map.get_uuids_of_type(channel);
+        if uuids.is_empty() {
+            return Err(EVBError::MissingTrigger(format!(
+                "{} is not in the channel map",
+                channel
+            )));
+        }
+        self.evb = self.evb.with_trigger(uuids);
+        Ok(self)
+    }
+
+    //A window is closed once it has been open this long in wall time, even if its end has not been
+    //reached by the hits yet, so the events never lag behind the data by more than about this.
+    //Windows closed early can cut an event in two. The limit is checked by push_hit and poll.
+    pub fn with_max_latency(mut self, latency: Duration) -> Self {
+        self.max_latency = Some(latency);
+        self
+    }
+
+    //Returns the event this hit completed, if any. Hits must come in time order.
+    pub fn push_hit(&mut self, hit: &CompassData) -> Option<Vec<CompassData>> {
+        let flushed = self.poll();
+        let windows_opened = self.evb.get_windows_opened();
+        self.evb.push_hit(hit);
+        if self.evb.get_windows_opened() != windows_opened {
+            self.window_opened = Some(Instant::now());
+        }
+        //A flushed window leaves the builder empty, so this hit cannot complete another event
+        flushed.or_else(|| self.take_ready_event())
+    }
+
+    //Closes the open window if it is older than the max latency and returns its event. Call this
+    //regularly, since with no hits coming in push_hit is not called to check.
+    pub fn poll(&mut self) -> Option<Vec<CompassData>> {
+        let is_late = match (self.max_latency, self.window_opened) {
+            (Some(latency), Some(opened)) => opened.elapsed() >= latency,
+            _ => false,
+        };
+        if is_late && self.evb.has_open_window() {
+            self.evb.flush_window();
+            self.window_opened = None;
+        }
+        self.take_ready_event()
+    }
+
+    fn take_ready_event(&mut self) -> Option<Vec<CompassData>> {
+        if !self.evb.is_event_ready() {
+            return None;
+        }
+        let event = self.evb.get_ready_event();
+        self.data.append_event(&event, &self.map);
+        Some(event)
+    }
+
+    //Events completed since the last DataFrame was taken
+    pub fn get_pending_events(&self) -> usize {
+        self.data.rows
+    }
+
+    //The completed events since the last call, with the same columns as events.parquet. The event
+    //still open is not included.
+    pub fn take_dataframe(&mut self) -> Result<DataFrame, EVBError> {
+        let data = std::mem::replace(&mut self.data, self.template.clone());
+        Ok(DataFrame::new(data.convert_to_series())?)
+    }
+
+    //The file metadata of the tables of this stream, e.g. their time unit
+    pub fn get_metadata(&self) -> ParquetMetadata {
+        self.template.get_metadata()
+    }
+
+    //Name of the EventID column in the tables of this stream
+    pub fn get_event_column(&self) -> String {
+        self.template
+            .naming
+            .get_field_name(&ChannelDataField::EventID)
+    }
+
+    pub fn get_stats(&self) -> EventBuilderStats {
+        self.evb.get_stats()
+    }
+}
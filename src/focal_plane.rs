@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use super::state_spectra::StateSpectraParams;
+
+//Delay line propagation, ns per mm, of the front and back wires of the SPS focal plane detector
+const FRONT_DELAY_NS_PER_MM: f64 = 2.1;
+const BACK_DELAY_NS_PER_MM: f64 = 1.98;
+
+//An x-position (mm) window on the focal plane selecting one populated state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateGate {
+    pub name: String,
+    pub x_min: f64,
+    pub x_max: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpsParams {
+    //Xavg = front_weight * X1 + (1 - front_weight) * X2, 0.5 puts the focal plane midway between the wires
+    pub front_weight: f64,
+    pub state_gates: Vec<StateGate>,
+    #[serde(default)]
+    pub spectra: StateSpectraParams,
+}
+
+impl Default for SpsParams {
+    fn default() -> Self {
+        SpsParams {
+            front_weight: 0.5,
+            state_gates: vec![],
+            spectra: StateSpectraParams::default(),
+        }
+    }
+}
+
+impl SpsParams {
+    //Index of the first gate containing the position
+    pub fn get_state_id(&self, xavg: f64) -> Option<usize> {
+        self.state_gates
+            .iter()
+            .position(|gate| xavg >= gate.x_min && xavg < gate.x_max)
+    }
+
+    pub fn describe_states(&self) -> String {
+        self.state_gates
+            .iter()
+            .enumerate()
+            .map(|(id, gate)| format!("{}={}", id, gate.name))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+//Delay line hit times (ns) of one event
+#[derive(Debug, Clone, Default)]
+pub struct FocalPlaneHits {
+    pub delay_front_left: Option<f64>,
+    pub delay_front_right: Option<f64>,
+    pub delay_back_left: Option<f64>,
+    pub delay_back_right: Option<f64>,
+}
+
+impl FocalPlaneHits {
+    pub fn get_x1(&self) -> Option<f64> {
+        match (self.delay_front_left, self.delay_front_right) {
+            (Some(left), Some(right)) => Some((left - right) * 0.5 / FRONT_DELAY_NS_PER_MM),
+            _ => None,
+        }
+    }
+
+    pub fn get_x2(&self) -> Option<f64> {
+        match (self.delay_back_left, self.delay_back_right) {
+            (Some(left), Some(right)) => Some((left - right) * 0.5 / BACK_DELAY_NS_PER_MM),
+            _ => None,
+        }
+    }
+
+    pub fn get_xavg(&self, params: &SpsParams) -> Option<f64> {
+        match (self.get_x1(), self.get_x2()) {
+            (Some(x1), Some(x2)) => {
+                Some(params.front_weight * x1 + (1.0 - params.front_weight) * x2)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use polars::prelude::*;
+
+use super::app::EvbAppParams;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::column_codec::ColumnCodec;
+use super::compass_data::{CompassData, EnergyWidth, TimestampUnit};
+use super::compass_file::{
+    group_segments, is_segment_started, CompassFile, GrowingFile, SegmentAccess,
+};
+use super::corrupt_file::CorruptionLog;
+use super::energy_threshold::EnergyThresholds;
+use super::error::EVBError;
+use super::event_builder::EventBuilderStats;
+use super::event_stream::EventStream;
+use super::formatting::format_count;
+use super::hit_merge::HitMerge;
+use super::hit_source::HitSource;
+use super::output_format::{OutputWriter, ParquetOutput};
+use super::run_layout::{RunLayout, EVENTS_STEM};
+use super::shift_map::ShiftMap;
+use super::time_walk::TimeWalk;
+use super::timestamp_sanitizer::TimestampSanitizer;
+
+//Built in one poll at most, so a follower started late in a run catches up in steps
+const MAX_POLL_HITS: usize = 2_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowParams {
+    //How often the run directory is looked at for new data
+    pub poll_interval: Duration,
+    //A file that has not grown for this long no longer holds back the other files, so a quiet
+    //channel does not stop the event building
+    pub idle_timeout: Duration,
+    //How often the events built so far are written out
+    pub flush_interval: Duration,
+    //The run is taken as closed once no file has grown for this long
+    pub stop_after: Duration,
+}
+
+impl Default for FollowParams {
+    fn default() -> Self {
+        FollowParams {
+            poll_interval: Duration::from_millis(500),
+            idle_timeout: Duration::from_secs(2),
+            flush_interval: Duration::from_secs(10),
+            stop_after: Duration::from_secs(60),
+        }
+    }
+}
+
+//Events built from a CoMPASS run directory while the run is still being written. Every channel
+//file is read as a CompassFile that waits at the end of what was written (see GrowingFile), through
+//the same energy thresholds, timestamp sanitizer and time walk correction as a build. The hits of
+//the files are merged in time order as far as every file has been written: a hit is only built
+//once each other file either has a later hit waiting or has not grown for the idle timeout. Hits
+//of a file that come in after it was idle, earlier than hits already built, are dropped and
+//counted as late.
+pub struct RunFollower {
+    run_dir: PathBuf,
+    params: FollowParams,
+    stream: EventStream,
+    shift_map: Option<ShiftMap>,
+    energy_widths: Vec<EnergyWidth>,
+    timestamp_units: Vec<TimestampUnit>,
+    thresholds: Option<EnergyThresholds>,
+    sanitizer: Option<TimestampSanitizer>,
+    time_walk: Option<TimeWalk>,
+    corruption: CorruptionLog,
+    //Shared with every file, set once the run is closed
+    is_run_closed: Arc<AtomicBool>,
+    //Scaler files start with one of the patterns of the scaler list, slow control logs contain
+    //the slow control pattern; neither is hit data
+    scaler_patterns: Vec<String>,
+    slow_control_pattern: String,
+    //Channels mapped as Scaler or RF are left out of the events
+    excluded_uuids: HashSet<u32>,
+    //The first segment of each channel followed, and its hits in the order the channels were found
+    channel_paths: HashSet<PathBuf>,
+    channels: Vec<Box<dyn HitSource>>,
+    merge: HitMerge,
+    last_time: f64,
+    late_hits: u64,
+    written_bytes: u64,
+    last_growth: Instant,
+}
+
+impl RunFollower {
+    pub fn new(
+        config: &EvbAppParams,
+        run_dir: &Path,
+        params: FollowParams,
+    ) -> Result<Self, EVBError> {
+        let map = ChannelMap::new(&config.channel_map_entries);
+        Ok(RunFollower {
+            run_dir: run_dir.to_path_buf(),
+            params,
+            stream: EventStream::from_params(config)?,
+            shift_map: Some(ShiftMap::new(config.shift_map_entries.clone())),
+            energy_widths: map.get_energy_widths().to_vec(),
+            timestamp_units: map.get_timestamp_units().to_vec(),
+            thresholds: EnergyThresholds::new(&config.energy_thresholds),
+            //Every file is followed as the file of one channel, not as a time-merged file
+            sanitizer: TimestampSanitizer::new(&config.timestamp_sanitizer, 0.0),
+            time_walk: TimeWalk::new(&config.time_walk, &map)?,
+            corruption: CorruptionLog::default(),
+            is_run_closed: Arc::new(AtomicBool::new(false)),
+            scaler_patterns: config
+                .scaler_list_entries
+                .iter()
+                .map(|entry| entry.file_pattern.clone())
+                .filter(|pattern| !pattern.is_empty())
+                .collect(),
+            slow_control_pattern: config.slow_control_pattern.clone(),
+            excluded_uuids: map
+                .get_uuids_of_type(ChannelType::Scaler)
+                .union(&map.get_uuids_of_type(ChannelType::Rf))
+                .copied()
+                .collect(),
+            channel_paths: HashSet::new(),
+            channels: vec![],
+            merge: HitMerge::default(),
+            last_time: f64::MIN,
+            late_hits: 0,
+            written_bytes: 0,
+            last_growth: Instant::now(),
+        })
+    }
+
+    //Channel map, shift map, window and columns as the GUI would build with the config file
+    pub fn from_config(
+        config_path: &Path,
+        run_dir: &Path,
+        params: FollowParams,
+    ) -> Result<Self, EVBError> {
+        Self::new(&EvbAppParams::read(config_path)?, run_dir, params)
+    }
+
+    fn is_hit_file(&self, path: &Path) -> bool {
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+        let is_bin = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("bin"));
+        let is_slow_control =
+            !self.slow_control_pattern.is_empty() && name.contains(&self.slow_control_pattern);
+        is_bin
+            && !is_slow_control
+            && !self
+                .scaler_patterns
+                .iter()
+                .any(|pattern| name.starts_with(pattern))
+    }
+
+    //The BIN files of the run directory and of its subdirectories, e.g. RAW
+    fn find_files(&self) -> Result<Vec<PathBuf>, EVBError> {
+        let mut paths = vec![];
+        for entry in self.run_dir.read_dir()?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                for inner in path.read_dir()?.flatten() {
+                    paths.push(inner.path());
+                }
+            } else {
+                paths.push(path);
+            }
+        }
+        paths.retain(|path| path.is_file() && self.is_hit_file(path));
+        Ok(paths)
+    }
+
+    //The hits of a channel go through the wrappers in the order of a build
+    fn open_channel(&self, first_segment: &Path) -> Result<Box<dyn HitSource>, EVBError> {
+        let growth = GrowingFile {
+            check_interval: self.params.poll_interval,
+            idle_timeout: self.params.idle_timeout,
+            is_closed: self.is_run_closed.clone(),
+        };
+        //Later segments are found by the file as CoMPASS starts them
+        let mut source: Box<dyn HitSource> = Box::new(
+            CompassFile::new_segmented(
+                &[first_segment.to_path_buf()],
+                &self.shift_map,
+                &self.energy_widths,
+                &self.corruption,
+                SegmentAccess::Buffered,
+            )?
+            .with_timestamp_units(&self.timestamp_units)
+            .with_growth(growth),
+        );
+        if let Some(thresholds) = &self.thresholds {
+            source = thresholds.apply(source);
+        }
+        if let Some(sanitizer) = &self.sanitizer {
+            source = sanitizer.correct_rollovers(source);
+            source = sanitizer.reorder(source, false);
+        }
+        if let Some(walk) = &self.time_walk {
+            source = walk.wrap(source);
+        }
+        source.set_hit_used();
+        Ok(source)
+    }
+
+    //Opens the channels whose first segment has been started, and notes whether any file grew
+    fn update_channels(&mut self) -> Result<(), EVBError> {
+        let paths = self.find_files()?;
+        let mut written_bytes: u64 = 0;
+        for path in paths.iter() {
+            written_bytes += std::fs::metadata(path)?.len();
+        }
+        if written_bytes != self.written_bytes {
+            self.written_bytes = written_bytes;
+            self.last_growth = Instant::now();
+        }
+        for segments in group_segments(paths) {
+            if self.channel_paths.contains(&segments[0])
+                || !is_segment_started(&segments[0], &self.energy_widths)?
+            {
+                continue;
+            }
+            info!("Following {}", segments[0].display());
+            let source = self.open_channel(&segments[0])?;
+            self.channels.push(source);
+            let channel = self.channels.len() - 1;
+            self.merge.add_file(&mut self.channels, channel)?;
+            self.channel_paths.insert(segments[0].clone());
+        }
+        Ok(())
+    }
+
+    fn build_hits(&mut self, max_hits: usize) -> Result<Vec<Vec<CompassData>>, EVBError> {
+        let mut events = vec![];
+        let mut hits: usize = 0;
+        while hits < max_hits {
+            //Held while a channel still growing may yet write an earlier hit
+            let channel = match self.merge.get_earliest_file(&mut self.channels)? {
+                Some(channel) => channel,
+                None => break,
+            };
+            hits += 1;
+            let source = &mut self.channels[channel];
+            let hit = source.get_top_hit()?.clone();
+            source.set_hit_used();
+            if self.excluded_uuids.contains(&hit.uuid) {
+                continue;
+            }
+            if hit.timestamp < self.last_time {
+                self.late_hits += 1;
+                continue;
+            }
+            self.last_time = hit.timestamp;
+            if let Some(event) = self.stream.push_hit(&hit) {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    //Reads what was written since the last call and returns the events it completed
+    pub fn poll(&mut self) -> Result<Vec<Vec<CompassData>>, EVBError> {
+        self.update_channels()?;
+        self.build_hits(MAX_POLL_HITS)
+    }
+
+    //Builds every hit still waiting, for when the run is closed. The window still open is never
+    //built, as at the end of a run.
+    pub fn finish(&mut self) -> Result<Vec<Vec<CompassData>>, EVBError> {
+        self.is_run_closed.store(true, Ordering::Relaxed);
+        self.update_channels()?;
+        self.build_hits(usize::MAX)
+    }
+
+    //True once no file has grown for the stop time
+    pub fn is_closed(&self) -> bool {
+        self.last_growth.elapsed() >= self.params.stop_after
+    }
+
+    pub fn get_pending_events(&self) -> usize {
+        self.stream.get_pending_events()
+    }
+
+    //The completed events since the last call, with the same columns as events.parquet
+    pub fn take_dataframe(&mut self) -> Result<DataFrame, EVBError> {
+        self.stream.take_dataframe()
+    }
+
+    pub fn get_stats(&self) -> EventBuilderStats {
+        self.stream.get_stats()
+    }
+
+    pub fn get_late_hits(&self) -> u64 {
+        self.late_hits
+    }
+
+    //Hits suppressed and corrected on the way, as at the end of a build
+    pub fn log_summary(&self) {
+        self.get_stats().log_summary();
+        if let Some(thresholds) = &self.thresholds {
+            thresholds.log_summary();
+        }
+        if let Some(sanitizer) = &self.sanitizer {
+            sanitizer.log_summary();
+        }
+        if let Some(walk) = &self.time_walk {
+            walk.log_summary();
+        }
+        self.corruption.log_summary();
+        if self.late_hits > 0 {
+            warn!(
+                "{} hits came in after later hits had been built and were dropped",
+                format_count(self.late_hits)
+            );
+        }
+    }
+
+    fn write_fragment(
+        &mut self,
+        layout: &RunLayout,
+        fragment: i32,
+        codecs: &[ColumnCodec],
+    ) -> Result<(), EVBError> {
+        let path = layout.get_table_path(EVENTS_STEM, Some(fragment));
+        info!(
+            "Writing {} events to {}",
+            format_count(self.get_pending_events() as u64),
+            path.display()
+        );
+        let mut metadata = self.stream.get_metadata();
+        if let Some(thresholds) = &self.thresholds {
+            thresholds.insert_metadata(&mut metadata);
+        }
+        if let Some(sanitizer) = &self.sanitizer {
+            sanitizer.insert_metadata(&mut metadata);
+        }
+        if let Some(walk) = &self.time_walk {
+            walk.get_provenance().insert_metadata(&mut metadata);
+        }
+        let parquet = ParquetOutput::new(&self.stream.get_event_column()).with_codecs(codecs);
+        let mut df = self.take_dataframe()?;
+        parquet.write_table(&mut df, &path, &metadata)
+    }
+}
+
+//The number at the end of a CoMPASS run directory name, e.g. 12 for run_12
+fn get_run_number(run_dir: &Path) -> Option<i32> {
+    run_dir
+        .file_name()?
+        .to_str()?
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+//Follows a run directory until no file has grown for the stop time, writing the events built so
+//far to output_dir/run_<num>/events_<frag>.parquet every flush interval, as the fragments of a
+//fragmented build
+pub fn follow_run(
+    config_path: &Path,
+    run_dir: &Path,
+    output_dir: &Path,
+    params: FollowParams,
+) -> Result<(), EVBError> {
+    let config = EvbAppParams::read(config_path)?;
+    let run_number = get_run_number(run_dir).ok_or_else(|| {
+        EVBError::Follow(format!(
+            "{} does not end in a run number",
+            run_dir.display()
+        ))
+    })?;
+    let layout = RunLayout::new(output_dir, run_number);
+    layout.create_dirs()?;
+    info!(
+        "Following run {} in {}, writing events to {}",
+        run_number,
+        run_dir.display(),
+        layout.get_dir().display()
+    );
+
+    let mut follower = RunFollower::new(&config, run_dir, params.clone())?;
+    let mut fragment: i32 = 0;
+    let mut last_flush = Instant::now();
+    loop {
+        follower.poll()?;
+        if follower.is_closed() {
+            follower.finish()?;
+            break;
+        }
+        if last_flush.elapsed() >= params.flush_interval && follower.get_pending_events() > 0 {
+            follower.write_fragment(&layout, fragment, &config.column_codecs)?;
+            fragment += 1;
+            last_flush = Instant::now();
+        }
+        std::thread::sleep(params.poll_interval);
+    }
+    if follower.get_pending_events() > 0 {
+        follower.write_fragment(&layout, fragment, &config.column_codecs)?;
+    }
+
+    info!(
+        "No file of run {} has grown for {:?}, stopping",
+        run_number, params.stop_after
+    );
+    follower.log_summary();
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//Shared formatting of counts, sizes, rates and durations, so the GUI, the logs and the run report
+//all read the same way: three significant figures with an SI prefix, durations as HH:MM:SS.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const PREFIXES: [(f64, &str); 5] = [
+    (1.0e12, "T"),
+    (1.0e9, "G"),
+    (1.0e6, "M"),
+    (1.0e3, "k"),
+    (1.0, ""),
+];
+
+//e.g. 1234567.0 "B" -> "1.23 MB". Values below 1000 are printed as they are.
+pub fn format_si(value: f64, unit: &str) -> String {
+    if !value.is_finite() {
+        return format!("{} {}", value, unit).trim_end().to_string();
+    }
+    let (scale, prefix) = PREFIXES
+        .iter()
+        //Values that would round up to 1000 move to the next prefix
+        .find(|(scale, _)| value.abs() >= *scale * 0.9995)
+        .unwrap_or(&(1.0, ""));
+    let scaled = value / scale;
+    let number = if prefix.is_empty() && scaled.fract() == 0.0 {
+        format!("{}", scaled)
+    } else if scaled.abs() >= 100.0 {
+        format!("{:.0}", scaled)
+    } else if scaled.abs() >= 10.0 {
+        format!("{:.1}", scaled)
+    } else {
+        format!("{:.2}", scaled)
+    };
+    format!("{} {}{}", number, prefix, unit)
+        .trim_end()
+        .to_string()
+}
+
+pub fn format_count(count: u64) -> String {
+    format_si(count as f64, "")
+}
+
+pub fn format_bytes(bytes: usize) -> String {
+    format_si(bytes as f64, "B")
+}
+
+//unit is what is being counted, e.g. "hits" -> "12.3 k hits/s"
+pub fn format_rate(per_second: f64, unit: &str) -> String {
+    format!("{} {}/s", format_si(per_second, ""), unit)
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        total / 3600,
+        (total / 60) % 60,
+        total % 60
+    )
+}
+
+//Time of day seconds from now, e.g. "07:42", with the days ahead when it is not today, e.g.
+//"07:42 (+1 day)". Local time where the platform gives it, UTC otherwise.
+pub fn format_clock_time(seconds_from_now: f64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64);
+    let today = get_local_seconds(now).div_euclid(86400);
+    let local = get_local_seconds(now + seconds_from_now.max(0.0).round() as i64);
+    let time = format!(
+        "{:02}:{:02}",
+        local.rem_euclid(86400) / 3600,
+        local.rem_euclid(3600) / 60
+    );
+    match local.div_euclid(86400) - today {
+        0 => time,
+        1 => format!("{} (+1 day)", time),
+        days => format!("{} (+{} days)", time, days),
+    }
+}
+
+//Seconds since the epoch shifted by the offset of the local time zone
+#[cfg(unix)]
+fn get_local_seconds(time: i64) -> i64 {
+    let time_t = time as libc::time_t;
+    let mut local: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time_t, &mut local) }.is_null() {
+        return time;
+    }
+    time + local.tm_gmtoff
+}
+
+#[cfg(not(unix))]
+fn get_local_seconds(time: i64) -> i64 {
+    time
+}
@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::compass_data::{generate_board_channel_uuid, CompassData};
+
+//Linear energy calibration of one channel, E' = gain * E + offset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GainEntry {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub gain: f64,
+    pub offset: f64,
+}
+
+//Calibration measured in one of the campaign's calibration runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GainAnchor {
+    pub run_number: i32,
+    pub entries: Vec<GainEntry>,
+}
+
+//Per-channel calibration for one run, interpolated between the anchors around it
+#[derive(Debug, Clone)]
+pub struct GainMap {
+    map: HashMap<u32, (f64, f64)>,
+}
+
+fn to_map(anchor: &GainAnchor) -> HashMap<u32, (f64, f64)> {
+    anchor
+        .entries
+        .iter()
+        .map(|entry| {
+            (
+                generate_board_channel_uuid(&entry.board_number, &entry.channel_number),
+                (entry.gain, entry.offset),
+            )
+        })
+        .collect()
+}
+
+impl GainMap {
+    //Runs before the first anchor or after the last use the nearest anchor as-is. A channel missing
+    //from one of the two anchors takes the calibration of the other.
+    pub fn for_run(anchors: &[GainAnchor], run_number: i32) -> Option<GainMap> {
+        let mut sorted: Vec<&GainAnchor> = anchors.iter().collect();
+        sorted.sort_by_key(|anchor| anchor.run_number);
+
+        let after_index = sorted
+            .iter()
+            .position(|anchor| anchor.run_number >= run_number);
+        let (before, after) = match after_index {
+            None => (sorted.last()?, sorted.last()?),
+            Some(0) => (&sorted[0], &sorted[0]),
+            Some(index) => (&sorted[index - 1], &sorted[index]),
+        };
+
+        info!(
+            "Run {} gains interpolated between anchor runs {} and {}",
+            run_number, before.run_number, after.run_number
+        );
+        let before_map = to_map(before);
+        let after_map = to_map(after);
+        let span = (after.run_number - before.run_number) as f64;
+        let fraction = if span > 0.0 {
+            (run_number - before.run_number) as f64 / span
+        } else {
+            0.0
+        };
+
+        let mut map = before_map.clone();
+        for (uuid, (after_gain, after_offset)) in after_map.iter() {
+            let value = match before_map.get(uuid) {
+                Some((before_gain, before_offset)) => (
+                    before_gain + (after_gain - before_gain) * fraction,
+                    before_offset + (after_offset - before_offset) * fraction,
+                ),
+                None => (*after_gain, *after_offset),
+            };
+            map.insert(*uuid, value);
+        }
+        Some(GainMap { map })
+    }
+
+    pub fn apply(&self, hit: &CompassData) -> CompassData {
+        let mut corrected = hit.clone();
+        if let Some((gain, offset)) = self.map.get(&hit.uuid) {
+            corrected.energy = hit.energy * gain + offset;
+            corrected.energy_short = hit.energy_short * gain + offset;
+        }
+        corrected
+    }
+}
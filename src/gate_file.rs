@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::ChannelDataField;
+use super::error::EVBError;
+use super::focal_plane::StateGate;
+
+//A range of one column, [min, max)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlotterGate {
+    pub name: String,
+    pub column: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+//A polygon in the plane of two columns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlotterCut {
+    pub name: String,
+    pub x_column: String,
+    pub y_column: String,
+    pub vertices: Vec<[f64; 2]>,
+}
+
+//Gates in the JSON file format of the spectrum plotter, so the gates drawn there can be used as
+//state gates without converting them by hand
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GateFile {
+    #[serde(default)]
+    pub gates: Vec<PlotterGate>,
+    #[serde(default)]
+    pub cuts: Vec<PlotterCut>,
+}
+
+impl GateFile {
+    pub fn read(path: &Path) -> Result<Self, EVBError> {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), EVBError> {
+        Ok(serde_json::to_writer_pretty(
+            BufWriter::new(File::create(path)?),
+            self,
+        )?)
+    }
+
+    pub fn from_state_gates(gates: &[StateGate]) -> Self {
+        GateFile {
+            gates: gates
+                .iter()
+                .map(|gate| PlotterGate {
+                    name: gate.name.clone(),
+                    column: ChannelDataField::Xavg.get_name(),
+                    min: gate.x_min,
+                    max: gate.x_max,
+                })
+                .collect(),
+            cuts: vec![],
+        }
+    }
+
+    //State gates are ranges of Xavg, so only the gates on Xavg can be used. The names of all other
+    //gates and cuts are returned with them.
+    pub fn get_state_gates(&self) -> (Vec<StateGate>, Vec<String>) {
+        let (xavg_gates, other_gates): (Vec<&PlotterGate>, Vec<&PlotterGate>) = self
+            .gates
+            .iter()
+            .partition(|gate| gate.column == ChannelDataField::Xavg.get_name());
+        let state_gates = xavg_gates
+            .into_iter()
+            .map(|gate| StateGate {
+                name: gate.name.clone(),
+                x_min: gate.min,
+                x_max: gate.max,
+            })
+            .collect();
+        let unused = other_gates
+            .into_iter()
+            .map(|gate| gate.name.clone())
+            .chain(self.cuts.iter().map(|cut| cut.name.clone()))
+            .collect();
+        (state_gates, unused)
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::error::EVBError;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hdf5Params {
+    //Deflate (gzip) level of the datasets, 0 to 9 with 0 for no compression
+    pub compression_level: u8,
+    //Rows per chunk of the datasets, the unit h5py reads and decompresses
+    pub chunk_rows: usize,
+}
+
+impl Default for Hdf5Params {
+    fn default() -> Self {
+        Hdf5Params {
+            compression_level: 4,
+            chunk_rows: 65536,
+        }
+    }
+}
+
+pub fn is_hdf5_available() -> bool {
+    cfg!(feature = "hdf5")
+}
+
+//The HDF5 file of the table written to filepath, events.parquet gives events.h5
+pub fn get_hdf5_path(filepath: &Path) -> PathBuf {
+    filepath.with_extension("h5")
+}
+
+//One f64 dataset per column at the root of the file, named and ordered as the parquet columns, so
+//h5py reads a column with file["Cebra0Energy"][:]. Nulls left by post batch hooks become the
+//invalid value.
+//List columns of expanded detectors are left out, they are only in the parquet file.
+#[cfg(feature = "hdf5")]
+pub fn write_event_hdf5(
+    df: &DataFrame,
+    filepath: &Path,
+    params: &Hdf5Params,
+) -> Result<(), EVBError> {
+    use super::channel_data::INVALID_VALUE;
+
+    let to_error = |x: hdf5::Error| EVBError::Hdf5(format!("{}: {}", filepath.display(), x));
+    let file = hdf5::File::create(filepath).map_err(to_error)?;
+    //Chunks cannot be larger than a dataset of fixed size, and an empty one cannot be chunked
+    let chunk_rows = params.chunk_rows.min(df.height());
+    for series in df.get_columns() {
+        if matches!(series.dtype(), DataType::List(_)) {
+            continue;
+        }
+        let values: Vec<f64> = series
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .map(|value| value.unwrap_or(INVALID_VALUE))
+            .collect();
+        let mut builder = file.new_dataset_builder();
+        if chunk_rows > 0 {
+            builder = builder.chunk(chunk_rows);
+            if params.compression_level > 0 {
+                builder = builder.deflate(params.compression_level);
+            }
+        }
+        builder
+            .with_data(values.as_slice())
+            .create(series.name())
+            .map_err(to_error)?;
+    }
+    file.close().map_err(to_error)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "hdf5"))]
+pub fn write_event_hdf5(
+    _df: &DataFrame,
+    filepath: &Path,
+    _params: &Hdf5Params,
+) -> Result<(), EVBError> {
+    Err(EVBError::Hdf5(format!(
+        "{}: the event builder was built without the hdf5 feature",
+        filepath.display()
+    )))
+}
@@ -0,0 +1,266 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use log::info;
+use polars::prelude::*;
+
+use super::app::{read_channel_map_file, validate_params, EvbAppParams};
+use super::channel_data::TimeUnit;
+use super::compass_run::process_runs;
+use super::config_file::read_config_file;
+use super::error::EVBError;
+use super::hit_filter::HitFilters;
+use super::output_format::{MemoryOutput, OutputFormats};
+use super::output_sink::OutputSinks;
+use super::post_batch::PostBatchHooks;
+use super::progress::{JobProgress, ProgressEvent, ProgressListener};
+use super::resource_limits::ResourceOverrides;
+use super::run_archive::find_run_archive;
+use super::run_layout::EVENTS_STEM;
+use super::run_log::LogLevel;
+use super::run_scan::{find_output_density, scan_run, RunScan};
+use super::ws::{Workspace, WorkspaceError};
+
+//What --build can set on top of the config file, each None keeps the value of the config
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessOptions {
+    pub workspace: Option<PathBuf>,
+    //A run list, as typed in the GUI, e.g. "120-147, !133"
+    pub runs: Option<String>,
+    pub coincidence_window: Option<f64>,
+    //Config file the channel map is taken from instead
+    pub channel_map: Option<PathBuf>,
+    //e.g. "parquet,root"
+    pub output_formats: Option<String>,
+    pub paranoid: bool,
+    pub resource_overrides: ResourceOverrides,
+    pub log_level: Option<LogLevel>,
+}
+
+impl HeadlessOptions {
+    fn apply(&self, params: &mut EvbAppParams) -> Result<(), EVBError> {
+        if let Some(workspace) = &self.workspace {
+            params.workspace = Some(
+                Workspace::new(workspace)
+                    .map_err(|e| EVBError::Config(format!("{}: {}", workspace.display(), e)))?,
+            );
+        }
+        if let Some(runs) = &self.runs {
+            params.run_list = runs.clone();
+        }
+        if let Some(window) = self.coincidence_window {
+            params.coincidence_window = window;
+            params.coincidence_window_unit = TimeUnit::Nanoseconds;
+        }
+        if let Some(path) = &self.channel_map {
+            params.channel_map_entries = read_channel_map_file(path)?;
+        }
+        if let Some(formats) = &self.output_formats {
+            params.output_formats = OutputFormats::parse_list(formats).map_err(EVBError::Config)?;
+        }
+        if let Some(log_level) = self.log_level {
+            params.log_level = log_level;
+        }
+        Ok(())
+    }
+}
+
+//Seconds between progress lines while a run is built
+const PRINT_PERIOD: f64 = 5.0;
+
+//Prints a line when a run starts and finishes, and the progress in between every PRINT_PERIOD
+#[derive(Debug, Default)]
+struct StderrProgress {
+    last_print: Option<Instant>,
+}
+
+impl ProgressListener for StderrProgress {
+    fn on_progress(&mut self, event: &ProgressEvent) {
+        match event {
+            ProgressEvent::RunStarted {
+                run_number,
+                run_index,
+                run_count,
+            } => {
+                eprintln!("Building run {} ({}/{})", run_number, run_index, run_count);
+                self.last_print = Some(Instant::now());
+            }
+            ProgressEvent::Update(run) => {
+                if self
+                    .last_print
+                    .map_or(true, |last| last.elapsed().as_secs_f64() >= PRINT_PERIOD)
+                {
+                    eprintln!("{}", run.describe());
+                    self.last_print = Some(Instant::now());
+                }
+            }
+            ProgressEvent::RunFinished(run) => eprintln!("{}", run.describe()),
+            ProgressEvent::FileFinished { .. } => (),
+        }
+    }
+}
+
+//Builds the runs of a config file on this thread, the same job the Run button of the GUI starts,
+//for shell scripts and machines without a display. The overrides are checked along with the rest
+//of the config, and the warnings and errors of the build are printed to stderr when it is done.
+pub fn build_headless(config_path: &Path, options: &HeadlessOptions) -> Result<(), EVBError> {
+    let (params, yaml_str) = read_config_file(config_path)?;
+    build_params(
+        &yaml_str,
+        params,
+        options,
+        &config_path.display().to_string(),
+        None,
+    )
+}
+
+//The same for a config made or changed in code by a program using the crate as a library. The
+//lines of the config issues are those of the config written out as yaml.
+pub fn build_runs(config: EvbAppParams, options: &HeadlessOptions) -> Result<(), EVBError> {
+    let yaml_str = serde_yaml::to_string(&config)?;
+    build_params(&yaml_str, config, options, "the config", None)
+}
+
+//Builds one run of a config and returns its events, with the columns of events.parquet, instead of
+//writing them, e.g. for a notebook that would otherwise read the table back from disk. Only the
+//tables are kept in memory, the report, spectra and the rest of the run directory are written to
+//the workspace as usual. A fragmented table is joined back together.
+pub fn build_run_dataframe(
+    config: EvbAppParams,
+    run_number: i32,
+    options: &HeadlessOptions,
+) -> Result<DataFrame, EVBError> {
+    let options = HeadlessOptions {
+        runs: Some(run_number.to_string()),
+        ..options.clone()
+    };
+    let memory = MemoryOutput::default();
+    let yaml_str = serde_yaml::to_string(&config)?;
+    build_params(
+        &yaml_str,
+        config,
+        &options,
+        "the config",
+        Some(memory.clone()),
+    )?;
+    let mut events: Option<DataFrame> = None;
+    for (path, df) in memory.take_tables()? {
+        let is_events = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| {
+                stem == EVENTS_STEM || stem.starts_with(&format!("{}_", EVENTS_STEM))
+            });
+        match (is_events, events.as_mut()) {
+            (false, _) => (),
+            (true, Some(events)) => {
+                events.vstack_mut(&df)?;
+            }
+            (true, None) => events = Some(df),
+        }
+    }
+    events.ok_or_else(|| {
+        EVBError::File(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("run {} built no events", run_number),
+        ))
+    })
+}
+
+//Scans the runs of a config file instead of building them, see RunScan. Only the workspace and
+//the runs of the options are used.
+pub fn scan_headless(
+    config_path: &Path,
+    options: &HeadlessOptions,
+) -> Result<Vec<RunScan>, EVBError> {
+    let mut params = EvbAppParams::read(config_path)?;
+    options.apply(&mut params)?;
+    let workspace = match &params.workspace {
+        Some(workspace) => workspace.clone(),
+        None => {
+            return Err(EVBError::Config(String::from(
+                "no workspace, set one in the config or with --workspace",
+            )))
+        }
+    };
+    let to_config_error = |e: WorkspaceError| EVBError::Config(e.to_string());
+    let archive_dir = workspace.get_archive_dir().map_err(to_config_error)?;
+    let density = find_output_density(&workspace.get_output_dir().map_err(to_config_error)?);
+    params
+        .get_runs()
+        .into_iter()
+        .map(|run| {
+            let archive_path = find_run_archive(&archive_dir, run);
+            scan_run(archive_path.as_deref(), run, &params, density)
+        })
+        .collect()
+}
+
+//yaml_str is the text params were read from, for the lines of the config issues. With memory, the
+//tables are kept there instead of written in the output formats.
+fn build_params(
+    yaml_str: &str,
+    mut params: EvbAppParams,
+    options: &HeadlessOptions,
+    source: &str,
+    memory: Option<MemoryOutput>,
+) -> Result<(), EVBError> {
+    options.apply(&mut params)?;
+
+    let issues = validate_params(yaml_str, &params);
+    if !issues.is_empty() {
+        return Err(EVBError::Config(
+            issues
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<String>>()
+                .join("; "),
+        ));
+    }
+    let workspace = match &params.workspace {
+        Some(workspace) => workspace.clone(),
+        None => {
+            return Err(EVBError::Config(String::from(
+                "no workspace, set one in the config or with --workspace",
+            )))
+        }
+    };
+    if params.channel_map_entries.is_empty() {
+        return Err(EVBError::Config(String::from("the channel map is empty")));
+    }
+
+    let mut process_params = params
+        .get_process_params(
+            &workspace,
+            PostBatchHooks::default(),
+            OutputSinks::default(),
+            HitFilters::default(),
+            options.paranoid,
+        )
+        .map_err(|e| EVBError::Config(e.to_string()))?;
+    options
+        .resource_overrides
+        .apply(&mut process_params.resource_limits);
+    if let Some(memory) = memory {
+        process_params.output_formats = OutputFormats::none();
+        process_params.streaming.enabled = false;
+        process_params.memory_output = Some(memory);
+        process_params.qa.enabled = false;
+    }
+    info!(
+        "Building {} runs of {} into {}",
+        process_params.runs.len(),
+        source,
+        process_params.output_dir.display()
+    );
+
+    let diagnostics = process_params.diagnostics.clone();
+    let result = process_runs(
+        process_params,
+        JobProgress::default().with_listener(Box::<StderrProgress>::default()),
+    );
+    for diagnostic in diagnostics.get_all() {
+        eprintln!("{}", diagnostic.render());
+    }
+    result
+}
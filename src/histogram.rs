@@ -0,0 +1,247 @@
+use std::fs::File;
+use std::path::Path;
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_data::TimeUnit;
+use super::error::EVBError;
+use super::invalid_values::ColumnInvalidValues;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum DiagnosticKind {
+    #[default]
+    Spectrum,
+    #[strum(serialize = "Time Difference")]
+    TimeDifference,
+    #[strum(serialize = "Rate vs Time")]
+    RateVsTime,
+}
+
+//What to histogram from a built run's events. Rate vs time always uses 1 s bins over the whole run,
+//so bins/min/max only apply to the other kinds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticParams {
+    pub kind: DiagnosticKind,
+    pub column: String,
+    //Time difference only: the column subtracted from column
+    pub reference_column: String,
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for DiagnosticParams {
+    fn default() -> Self {
+        DiagnosticParams {
+            kind: DiagnosticKind::default(),
+            column: String::from("Cebra0Energy"),
+            reference_column: String::from("ScintLeftTime"),
+            bins: 4096,
+            min: 0.0,
+            max: 4096.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub min: f64,
+    pub max: f64,
+    pub counts: Vec<f64>,
+}
+
+impl Histogram {
+    pub fn new(
+        title: String,
+        x_label: String,
+        y_label: String,
+        bins: usize,
+        min: f64,
+        max: f64,
+    ) -> Self {
+        Histogram {
+            title,
+            x_label,
+            y_label,
+            min,
+            max,
+            counts: vec![0.0; bins],
+        }
+    }
+
+    pub fn get_bin_width(&self) -> f64 {
+        (self.max - self.min) / (self.counts.len() as f64)
+    }
+
+    pub fn fill(&mut self, value: f64) {
+        if value < self.min || value >= self.max || self.counts.is_empty() {
+            return;
+        }
+        let last_bin = self.counts.len() - 1;
+        let bin = (((value - self.min) / self.get_bin_width()) as usize).min(last_bin);
+        self.counts[bin] += 1.0;
+    }
+
+    pub fn get_max_count(&self) -> f64 {
+        self.counts.iter().cloned().fold(0.0, f64::max)
+    }
+
+    fn get_bin_center(&self, bin: usize) -> f64 {
+        self.min + (bin as f64 + 0.5) * self.get_bin_width()
+    }
+
+    //The highest bin and its count
+    fn get_peak_bin(&self) -> (usize, f64) {
+        self.counts
+            .iter()
+            .cloned()
+            .enumerate()
+            .fold(
+                (0, 0.0),
+                |best, (bin, count)| {
+                    if count > best.1 {
+                        (bin, count)
+                    } else {
+                        best
+                    }
+                },
+            )
+    }
+
+    //Center of the highest bin, None for an empty histogram
+    pub fn get_peak(&self) -> Option<f64> {
+        match self.get_peak_bin() {
+            (_, count) if count <= 0.0 => None,
+            (bin, _) => Some(self.get_bin_center(bin)),
+        }
+    }
+
+    //Full width at half maximum of the highest peak, interpolated between the bin centers on
+    //either side of each half maximum crossing. None for an empty histogram or a peak that does
+    //not fall below half maximum before the edges.
+    pub fn get_fwhm(&self) -> Option<f64> {
+        let (peak, max_count) = self.get_peak_bin();
+        if max_count <= 0.0 {
+            return None;
+        }
+        let half = max_count / 2.0;
+        let crossing = |inside: usize, outside: usize| {
+            let (inside_count, outside_count) = (self.counts[inside], self.counts[outside]);
+            let fraction = (inside_count - half) / (inside_count - outside_count);
+            self.get_bin_center(inside)
+                + fraction * (self.get_bin_center(outside) - self.get_bin_center(inside))
+        };
+        let left = (0..peak).rev().find(|bin| self.counts[*bin] < half)?;
+        let right = (peak + 1..self.counts.len()).find(|bin| self.counts[*bin] < half)?;
+        Some(crossing(right - 1, right) - crossing(left + 1, left))
+    }
+}
+
+//The events file records the unit of its Time columns, which can differ from the current setting
+pub fn read_time_unit(filepath: &Path) -> Result<TimeUnit, EVBError> {
+    let mut file = File::open(filepath)?;
+    let metadata = polars_parquet::read::read_metadata(&mut file)?;
+    let unit = metadata
+        .key_value_metadata
+        .unwrap_or_default()
+        .into_iter()
+        .find(|kv| kv.key == "time_unit")
+        .and_then(|kv| kv.value);
+    Ok(TimeUnit::iter()
+        .find(|time_unit| Some(time_unit.as_ref()) == unit.as_deref())
+        .unwrap_or_default())
+}
+
+pub fn read_columns(filepath: &Path) -> Result<Vec<String>, EVBError> {
+    let schema = LazyFrame::scan_parquet(filepath, ScanArgsParquet::default())?.schema()?;
+    Ok(schema.iter_names().map(|name| name.to_string()).collect())
+}
+
+fn read_values(filepath: &Path, expr: Expr) -> Result<Vec<f64>, EVBError> {
+    let df = LazyFrame::scan_parquet(filepath, ScanArgsParquet::default())?
+        .select([expr.alias("value")])
+        .collect()?;
+    Ok(df.column("value")?.f64()?.into_iter().flatten().collect())
+}
+
+pub fn build_histogram(
+    params: &DiagnosticParams,
+    filepath: &Path,
+    run_number: i32,
+) -> Result<Histogram, EVBError> {
+    let time_unit = read_time_unit(filepath)?;
+    let invalid = ColumnInvalidValues::read(filepath)?;
+    match params.kind {
+        DiagnosticKind::Spectrum => {
+            let mut histogram = Histogram::new(
+                format!("Run {} {}", run_number, params.column),
+                params.column.clone(),
+                String::from("Counts"),
+                params.bins,
+                params.min,
+                params.max,
+            );
+            let values = read_values(
+                filepath,
+                col(&params.column).filter(invalid.get_valid(&params.column)),
+            )?;
+            values.into_iter().for_each(|value| histogram.fill(value));
+            Ok(histogram)
+        }
+        DiagnosticKind::TimeDifference => {
+            let mut histogram = Histogram::new(
+                format!(
+                    "Run {} {} - {}",
+                    run_number, params.column, params.reference_column
+                ),
+                format!(
+                    "{} - {} ({})",
+                    params.column,
+                    params.reference_column,
+                    time_unit.as_ref()
+                ),
+                String::from("Counts"),
+                params.bins,
+                params.min,
+                params.max,
+            );
+            let values = read_values(
+                filepath,
+                (col(&params.column) - col(&params.reference_column)).filter(
+                    invalid
+                        .get_valid(&params.column)
+                        .and(invalid.get_valid(&params.reference_column)),
+                ),
+            )?;
+            values.into_iter().for_each(|value| histogram.fill(value));
+            Ok(histogram)
+        }
+        DiagnosticKind::RateVsTime => {
+            let units_per_second = time_unit.convert_nanoseconds(1.0e9);
+            let values: Vec<f64> = read_values(
+                filepath,
+                col(&params.column).filter(invalid.get_valid(&params.column)),
+            )?
+            .into_iter()
+            .map(|time| time / units_per_second)
+            .collect();
+            let last_second = values.iter().cloned().fold(0.0, f64::max).floor() + 1.0;
+            let mut histogram = Histogram::new(
+                format!("Run {} {} Rate", run_number, params.column),
+                String::from("Time (s)"),
+                String::from("Rate (Hz)"),
+                last_second as usize,
+                0.0,
+                last_second,
+            );
+            values.into_iter().for_each(|value| histogram.fill(value));
+            Ok(histogram)
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::ChannelMap;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::error::EVBError;
+
+//Energies are turned into fixed-point numbers with this many fraction bits, enough to keep the
+//dither of the integer ADC values apart
+const ENERGY_FRACTION_BITS: u32 = 8;
+//Fraction bits of the fixed-point bins per energy step
+const SCALE_FRACTION_BITS: u32 = 40;
+//Keeps the fixed-point product of an energy in range and the scale below 2^64
+pub const MAX_HISTOGRAM_ONLY_BINS: usize = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramOnlyParams {
+    pub enabled: bool,
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for HistogramOnlyParams {
+    fn default() -> Self {
+        HistogramOnlyParams {
+            enabled: false,
+            bins: 4096,
+            min: 0.0,
+            max: 4096.0,
+        }
+    }
+}
+
+//Energy spectrum of every channel of one run, for histogram only builds where no events table is
+//written. The bin of a hit is found with integer arithmetic only: the energy is taken as a
+//fixed-point number and multiplied by a fixed-point bins per energy step, so each hit costs one
+//multiply and a shift instead of a division.
+pub struct ChannelSpectra {
+    params: HistogramOnlyParams,
+    min_fixed: i64,
+    range_fixed: u64,
+    scale_fixed: u64,
+    //Indexed by the uuid of the channel, allocated on its first hit
+    counts: Vec<Option<Vec<u64>>>,
+}
+
+impl ChannelSpectra {
+    pub fn new(params: &HistogramOnlyParams) -> Self {
+        let to_fixed = |energy: f64| (energy * (1u64 << ENERGY_FRACTION_BITS) as f64) as i64;
+        let min_fixed = to_fixed(params.min);
+        let range_fixed = (to_fixed(params.max) - min_fixed).max(1) as u64;
+        ChannelSpectra {
+            params: params.clone(),
+            min_fixed,
+            range_fixed,
+            scale_fixed: (((params.bins as u128) << SCALE_FRACTION_BITS) / range_fixed as u128)
+                as u64,
+            counts: vec![],
+        }
+    }
+
+    fn get_bin(&self, energy: f64) -> Option<usize> {
+        let offset = (energy * (1u64 << ENERGY_FRACTION_BITS) as f64) as i64 - self.min_fixed;
+        if offset < 0 || offset as u64 >= self.range_fixed {
+            return None;
+        }
+        let bin = ((offset as u64 * self.scale_fixed) >> SCALE_FRACTION_BITS) as usize;
+        Some(bin.min(self.params.bins - 1))
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData) {
+        let bin = match self.get_bin(hit.energy) {
+            Some(bin) => bin,
+            None => return,
+        };
+        let index = hit.uuid as usize;
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, None);
+        }
+        let bins = self.params.bins;
+        self.counts[index].get_or_insert_with(|| vec![0; bins])[bin] += 1;
+    }
+
+    //One row per bin, one column per channel with hits, named <detector>_<board>_<channel> as in
+    //the calibration spectra
+    pub fn write_spectra(&self, filepath: &Path, map: &ChannelMap) -> Result<(), EVBError> {
+        let channels: Vec<(u32, &Vec<u64>)> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter_map(|(uuid, spectrum)| spectrum.as_ref().map(|counts| (uuid as u32, counts)))
+            .collect();
+        info!(
+            "Writing energy spectra of {} channels to {}",
+            channels.len(),
+            filepath.display()
+        );
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = channels
+            .iter()
+            .map(|(uuid, _)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let name = match map.get_channel_data(uuid) {
+                    Some(data) => data.channel_type.to_string(),
+                    None => String::from("Unmapped"),
+                };
+                format!("{}_{}_{}", name, board, channel)
+            })
+            .collect();
+        writeln!(writer, "bin_low,bin_high,{}", headers.join(","))?;
+
+        let width = (self.params.max - self.params.min) / (self.params.bins as f64);
+        for bin in 0..self.params.bins {
+            let low = self.params.min + width * (bin as f64);
+            let counts: Vec<String> = channels
+                .iter()
+                .map(|(_, spectrum)| spectrum[bin].to_string())
+                .collect();
+            writeln!(writer, "{},{},{}", low, low + width, counts.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
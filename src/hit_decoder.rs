@@ -0,0 +1,232 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{Scope, Thread};
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+
+use super::build_diagnostics::join_diagnostics;
+use super::compass_data::{fork_dither, swap_dither, CompassData};
+use super::error::EVBError;
+use super::hit_source::HitSource;
+use super::run_log::join_run_log;
+use super::used_size::UsedSize;
+
+//Hits handed from a decoder thread to the build thread at once
+const BATCH_HITS: usize = 4096;
+//Batches decoded ahead of the build thread per source, bounding the memory of a fast decoder
+const BATCHES_AHEAD: usize = 4;
+//A decoder with every queue full waits for the build thread to take a batch, this long at most
+const IDLE_WAIT: Duration = Duration::from_millis(5);
+
+struct Batch {
+    hits: Vec<CompassData>,
+    is_last: bool,
+}
+
+//One source being decoded on a decoder thread
+struct DecodeSlot<'a> {
+    source: Box<dyn HitSource + 'a>,
+    dither: Option<StdRng>,
+    sender: SyncSender<Result<Batch, EVBError>>,
+    pending: Option<Result<Batch, EVBError>>,
+    used_size: Arc<AtomicUsize>,
+    is_done: bool,
+}
+
+impl DecodeSlot<'_> {
+    fn read_batch(&mut self) -> Result<Batch, EVBError> {
+        let mut hits = Vec::with_capacity(BATCH_HITS);
+        while hits.len() < BATCH_HITS {
+            let hit = self.source.get_top_hit()?.clone();
+            if self.source.is_eof() {
+                return Ok(Batch {
+                    hits,
+                    is_last: true,
+                });
+            }
+            hits.push(hit);
+            self.source.set_hit_used();
+        }
+        Ok(Batch {
+            hits,
+            is_last: false,
+        })
+    }
+
+    //Hands the next batch to the build thread if its queue has room, true if it did
+    fn try_send(&mut self) -> bool {
+        let batch = match self.pending.take() {
+            Some(batch) => batch,
+            None => {
+                swap_dither(&mut self.dither);
+                let batch = self.read_batch();
+                swap_dither(&mut self.dither);
+                self.used_size
+                    .store(self.source.get_used_size(), Ordering::Relaxed);
+                batch
+            }
+        };
+        let is_last = batch.as_ref().map_or(true, |batch| batch.is_last);
+        match self.sender.try_send(batch) {
+            Ok(()) => {
+                self.is_done = is_last;
+                true
+            }
+            Err(TrySendError::Full(batch)) => {
+                self.pending = Some(batch);
+                false
+            }
+            //The build thread stopped reading, e.g. after an error in another source
+            Err(TrySendError::Disconnected(_)) => {
+                self.is_done = true;
+                false
+            }
+        }
+    }
+}
+
+fn run_decoder(mut slots: Vec<DecodeSlot<'_>>) {
+    join_run_log();
+    join_diagnostics();
+    while slots.iter().any(|slot| !slot.is_done) {
+        let mut is_idle = true;
+        for slot in slots.iter_mut().filter(|slot| !slot.is_done) {
+            is_idle &= !slot.try_send();
+        }
+        if is_idle {
+            std::thread::park_timeout(IDLE_WAIT);
+        }
+    }
+}
+
+//The build thread end of a source decoded on a decoder thread. The hits come in the order of the
+//source, so merging them gives exactly the stream of a sequential build.
+struct DecodedSource {
+    batches: Receiver<Result<Batch, EVBError>>,
+    decoder: Option<Thread>,
+    hits: std::vec::IntoIter<CompassData>,
+    is_last_batch: bool,
+    current_hit: CompassData,
+    is_used: bool,
+    is_eof: bool,
+    number_of_hits: u64,
+    decoder_size: Arc<AtomicUsize>,
+}
+
+impl DecodedSource {
+    fn next_hit(&mut self) -> Result<Option<CompassData>, EVBError> {
+        loop {
+            if let Some(hit) = self.hits.next() {
+                return Ok(Some(hit));
+            }
+            if self.is_last_batch {
+                return Ok(None);
+            }
+            //Only fails if the decoder thread panicked
+            let batch = self.batches.recv().map_err(|_| EVBError::Sync)??;
+            if let Some(decoder) = &self.decoder {
+                decoder.unpark();
+            }
+            self.is_last_batch = batch.is_last;
+            self.hits = batch.hits.into_iter();
+        }
+    }
+}
+
+impl HitSource for DecodedSource {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            self.current_hit = match self.next_hit()? {
+                Some(hit) => hit,
+                None => {
+                    self.is_eof = true;
+                    CompassData::default()
+                }
+            };
+            self.is_used = false;
+        }
+        Ok(&self.current_hit)
+    }
+
+    fn set_hit_used(&mut self) {
+        self.is_used = true;
+    }
+
+    fn is_eof(&self) -> bool {
+        self.is_eof
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.number_of_hits
+    }
+}
+
+impl UsedSize for DecodedSource {
+    //The reader of the decoder plus the batches, counting the queue as full
+    fn get_used_size(&self) -> usize {
+        self.decoder_size.load(Ordering::Relaxed)
+            + (self.hits.len() + BATCHES_AHEAD * BATCH_HITS) * std::mem::size_of::<CompassData>()
+    }
+}
+
+//The worker_threads option, 0 for one thread per core, capped by the thread limit of the build.
+//One keeps the decoding on the build thread.
+pub fn get_decoder_threads(worker_threads: usize, max_threads: usize) -> usize {
+    match worker_threads {
+        0 => max_threads,
+        threads => threads.min(max_threads),
+    }
+}
+
+//Decodes the sources on up to threads decoder threads of the scope, each source with its own
+//dither forked from the build thread. The returned sources are in the same order and are merged
+//on the build thread as usual; they end when the scope ends, joining the decoders.
+pub fn start_decoders<'scope, 'a: 'scope>(
+    scope: &'scope Scope<'scope, '_>,
+    sources: Vec<Box<dyn HitSource + 'a>>,
+    threads: usize,
+) -> Vec<Box<dyn HitSource + 'a>> {
+    let threads = threads.clamp(1, sources.len().max(1));
+    let mut thread_slots: Vec<Vec<DecodeSlot<'a>>> = (0..threads).map(|_| vec![]).collect();
+    let mut decoded: Vec<DecodedSource> = vec![];
+    for (index, mut source) in sources.into_iter().enumerate() {
+        let (sender, batches) = sync_channel(BATCHES_AHEAD);
+        let used_size = Arc::new(AtomicUsize::new(source.get_used_size()));
+        let number_of_hits = source.get_number_of_hits();
+        //The first call of get_top_hit should read the first hit of the source
+        source.set_hit_used();
+        thread_slots[index % threads].push(DecodeSlot {
+            source,
+            dither: fork_dither(),
+            sender,
+            pending: None,
+            used_size: used_size.clone(),
+            is_done: false,
+        });
+        decoded.push(DecodedSource {
+            batches,
+            decoder: None,
+            hits: vec![].into_iter(),
+            is_last_batch: false,
+            current_hit: CompassData::default(),
+            is_used: true,
+            is_eof: false,
+            number_of_hits,
+            decoder_size: used_size,
+        });
+    }
+    let decoders: Vec<Thread> = thread_slots
+        .into_iter()
+        .map(|slots| scope.spawn(move || run_decoder(slots)).thread().clone())
+        .collect();
+    decoded
+        .into_iter()
+        .enumerate()
+        .map(|(index, mut source)| -> Box<dyn HitSource + 'a> {
+            source.decoder = Some(decoders[index % threads].clone());
+            Box::new(source)
+        })
+        .collect()
+}
@@ -0,0 +1,89 @@
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use serde::Serialize;
+
+use super::compass_data::CompassData;
+use super::formatting::format_count;
+
+//Experiment specific processing of each hit, run on the merged hit stream just before the hit goes
+//into the event builder (after time shifts, gain drift correction, smearing and the bad energy
+//policy). A filter returns the hit to build, possibly with a changed energy or timestamp, or None
+//to drop it, e.g. to remove a known pickup pattern on one channel. Filters run in the order they
+//were added, each receiving the previous filter's output.
+//
+//As for post batch hooks, build your own binary against this crate and register the filter on the
+//app, e.g. `EVBApp::new(cc, false).with_hit_filter(Box::new(MyFilter))`.
+pub trait HitFilter: Send {
+    fn name(&self) -> &str;
+
+    fn process(&mut self, run_number: i32, hit: CompassData) -> Option<CompassData>;
+}
+
+impl std::fmt::Debug for dyn HitFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HitFilter({})", self.name())
+    }
+}
+
+//Shared between the GUI, which owns the filters, and the processing thread
+pub type HitFilters = Arc<Mutex<Vec<Box<dyn HitFilter>>>>;
+
+//Written to the report of every run built with filters
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HitFilterSummary {
+    pub name: String,
+    pub dropped: u64,
+}
+
+//The filters of one run, with the number of hits each of them dropped
+pub struct HitFilterStage {
+    dropped: Vec<u64>,
+}
+
+impl HitFilterStage {
+    pub fn new(filters: &[Box<dyn HitFilter>]) -> Self {
+        HitFilterStage {
+            dropped: vec![0; filters.len()],
+        }
+    }
+
+    pub fn apply(
+        &mut self,
+        filters: &mut [Box<dyn HitFilter>],
+        run_number: i32,
+        mut hit: CompassData,
+    ) -> Option<CompassData> {
+        for (filter, dropped) in filters.iter_mut().zip(self.dropped.iter_mut()) {
+            hit = match filter.process(run_number, hit) {
+                Some(hit) => hit,
+                None => {
+                    *dropped += 1;
+                    return None;
+                }
+            };
+        }
+        Some(hit)
+    }
+
+    pub fn get_summary(&self, filters: &[Box<dyn HitFilter>]) -> Vec<HitFilterSummary> {
+        filters
+            .iter()
+            .zip(self.dropped.iter())
+            .map(|(filter, dropped)| HitFilterSummary {
+                name: filter.name().to_string(),
+                dropped: *dropped,
+            })
+            .collect()
+    }
+
+    pub fn log_summary(&self, filters: &[Box<dyn HitFilter>]) {
+        for summary in self.get_summary(filters) {
+            info!(
+                "Hit filter {} dropped {} hits",
+                summary.name,
+                format_count(summary.dropped)
+            );
+        }
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::app::EvbAppParams;
+use super::channel_map::{ChannelGroup, ChannelMap, MappedChannel};
+use super::compass_data::{
+    generate_board_channel_uuid, CompassDataType, CompassFlags, EnergyWidth, RawCompassData,
+    TimestampUnit,
+};
+use super::error::EVBError;
+use super::headless::{build_run_dataframe, HeadlessOptions};
+use super::shift_map::ShiftMap;
+use super::ws::Workspace;
+
+//Hit times start here (ns), so hits moved earlier by the jitter or a time shift stay above zero
+const START_TIME: f64 = 1.0e6;
+const GENERATED_RUN: i32 = 1;
+//Raw energies of the generated hits are uniform in this range
+const ENERGY_RANGE: (u64, u64) = (100, 4000);
+//Each validation builds in a scratch workspace of its own, so validations can run side by side
+static NEXT_WORKSPACE: AtomicUsize = AtomicUsize::new(0);
+
+//A synthetic run with a known number of events, for checking the coincidence logic and the window
+//settings of a config. Events come at random times at the event rate, each firing a random set of
+//the mapped detector channels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HitGeneratorParams {
+    pub seed: u64,
+    //s
+    pub duration: f64,
+    //Events per second, on average
+    pub event_rate: f64,
+    //Relative weights of events with 1, 2, 3, ... channels, at most one hit per channel
+    pub multiplicity_weights: Vec<f64>,
+    //Gaussian spread of the hit times of an event around the time of the event, ns
+    pub timestamp_jitter: f64,
+    //Share of hits followed by a second hit in the same channel, flagged as pileup
+    pub pileup_fraction: f64,
+    //Longest time from a hit to its pileup hit, ns
+    pub pileup_window: f64,
+    //Channel files cut short part way through their last record
+    pub corrupt_files: usize,
+}
+
+impl Default for HitGeneratorParams {
+    fn default() -> Self {
+        HitGeneratorParams {
+            seed: 1,
+            duration: 10.0,
+            event_rate: 100.0,
+            multiplicity_weights: vec![1.0, 1.0, 0.5],
+            timestamp_jitter: 5.0,
+            pileup_fraction: 0.0,
+            pileup_window: 500.0,
+            corrupt_files: 0,
+        }
+    }
+}
+
+//What a perfect event builder would find in the readable hits of a generated run: hits cut off by
+//a corrupted file are left out of the events they belonged to
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GeneratorTruth {
+    pub events: u64,
+    pub hits: u64,
+    pub pileup_hits: u64,
+    pub corrupted_hits: u64,
+    //Hits of the last event, in the window still open at the end of the run, which the event
+    //builder drops
+    pub hits_left_open: u64,
+    //Events by their number of hits, pileup hits included
+    pub multiplicities: BTreeMap<usize, u64>,
+}
+
+struct GeneratedHit {
+    event: u64,
+    raw: RawCompassData,
+}
+
+//The hits of every channel in time order, ready to be written as CoMPASS files
+pub struct GeneratedRun {
+    channels: Vec<(MappedChannel, Vec<GeneratedHit>)>,
+    energy_widths: Vec<EnergyWidth>,
+    corrupt_files: usize,
+    pub truth: GeneratorTruth,
+}
+
+fn get_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
+
+//Index of the weight drawn, in proportion to the weights
+fn draw_weighted(rng: &mut StdRng, weights: &[f64]) -> usize {
+    let total: f64 = weights.iter().map(|weight| weight.max(0.0)).sum();
+    let mut draw = rng.gen::<f64>() * total;
+    for (index, weight) in weights.iter().enumerate() {
+        draw -= weight.max(0.0);
+        if draw < 0.0 {
+            return index;
+        }
+    }
+    weights.len().saturating_sub(1)
+}
+
+//Hits go to the channels of detectors, SPS channels and passthrough channels, which all end up in
+//the events. Scalers, the RF and the time calibrator never do.
+pub fn generate_run(
+    params: &HitGeneratorParams,
+    config: &EvbAppParams,
+) -> Result<GeneratedRun, EVBError> {
+    let map = ChannelMap::new(&config.channel_map_entries);
+    let channels: Vec<MappedChannel> = map
+        .get_mapped_channels()
+        .into_iter()
+        .filter(|(_, channel_type)| {
+            matches!(
+                channel_type.get_group(),
+                ChannelGroup::Cebra | ChannelGroup::Sps | ChannelGroup::Auxiliary
+            )
+        })
+        .map(|(mapped, _)| mapped)
+        .collect();
+    if channels.is_empty() {
+        return Err(EVBError::Config(String::from(
+            "the channel map has no channels to generate hits for",
+        )));
+    }
+    if params.event_rate <= 0.0 || params.multiplicity_weights.iter().all(|w| *w <= 0.0) {
+        return Err(EVBError::Config(String::from(
+            "the generator needs an event rate and a multiplicity weight above 0",
+        )));
+    }
+    let shifts = ShiftMap::new(config.shift_map_entries.clone());
+    let units = map.get_timestamp_units().to_vec();
+
+    let mut rng = StdRng::seed_from_u64(params.seed);
+    let mut hits: Vec<Vec<GeneratedHit>> = channels.iter().map(|_| vec![]).collect();
+    let mut add_hit = |rng: &mut StdRng, event: u64, index: usize, time: f64, flags: u32| {
+        let mapped = channels[index];
+        let uuid = generate_board_channel_uuid(&mapped.board, &mapped.channel);
+        //The time shift of the channel is taken back off, so the shifted hits line up again
+        let unshifted = time - shifts.get_timeshift(&uuid);
+        let unit = TimestampUnit::of_board(&units, mapped.board);
+        let energy = rng.gen_range(ENERGY_RANGE.0..ENERGY_RANGE.1);
+        hits[index].push(GeneratedHit {
+            event,
+            raw: RawCompassData {
+                board: mapped.board as u16,
+                channel: mapped.channel as u16,
+                timestamp: (unshifted.max(0.0) / unit.get_ns_per_unit()).round() as u64,
+                energy,
+                energy_calibrated: 0,
+                energy_short: (energy * 4 / 5) as u16,
+                flags,
+                samples: vec![],
+            },
+        });
+    };
+
+    let end = START_TIME + params.duration * 1.0e9;
+    let mut time = START_TIME;
+    let mut event: u64 = 0;
+    loop {
+        time += -(1.0 - rng.gen::<f64>()).ln() / params.event_rate * 1.0e9;
+        if time >= end {
+            break;
+        }
+        let multiplicity =
+            (draw_weighted(&mut rng, &params.multiplicity_weights) + 1).min(channels.len());
+        let mut fired: Vec<usize> = (0..channels.len()).collect();
+        for slot in 0..multiplicity {
+            let pick = rng.gen_range(slot..fired.len());
+            fired.swap(slot, pick);
+            let hit_time = time + params.timestamp_jitter * get_normal(&mut rng);
+            add_hit(&mut rng, event, fired[slot], hit_time, 0);
+            if rng.gen::<f64>() < params.pileup_fraction {
+                let pileup_time = hit_time + rng.gen::<f64>() * params.pileup_window;
+                add_hit(
+                    &mut rng,
+                    event,
+                    fired[slot],
+                    pileup_time,
+                    CompassFlags::PILEUP.bits(),
+                );
+            }
+        }
+        event += 1;
+    }
+
+    let mut run = GeneratedRun {
+        channels: channels.into_iter().zip(hits).collect(),
+        energy_widths: map.get_energy_widths().to_vec(),
+        corrupt_files: params.corrupt_files,
+        truth: GeneratorTruth::default(),
+    };
+    for (_, channel_hits) in run.channels.iter_mut() {
+        channel_hits.sort_by_key(|hit| hit.raw.timestamp);
+    }
+    run.set_truth();
+    Ok(run)
+}
+
+impl GeneratedRun {
+    //The files to be corrupted lose their last hit, and the last event is left out as the builder
+    //never closes its window
+    fn set_truth(&mut self) {
+        let mut event_hits: BTreeMap<u64, usize> = BTreeMap::new();
+        let mut truth = GeneratorTruth::default();
+        let mut corrupted = 0;
+        for (_, hits) in self.channels.iter() {
+            let readable = match hits.len() {
+                0 => 0,
+                hits if corrupted < self.corrupt_files => {
+                    corrupted += 1;
+                    truth.corrupted_hits += 1;
+                    hits - 1
+                }
+                hits => hits,
+            };
+            for hit in hits[..readable].iter() {
+                *event_hits.entry(hit.event).or_default() += 1;
+                truth.hits += 1;
+                if hit.raw.flags != 0 {
+                    truth.pileup_hits += 1;
+                }
+            }
+        }
+        if let Some((_, hits)) = event_hits.pop_last() {
+            truth.hits -= hits as u64;
+            truth.hits_left_open = hits as u64;
+        }
+        truth.events = event_hits.len() as u64;
+        for multiplicity in event_hits.into_values() {
+            *truth.multiplicities.entry(multiplicity).or_default() += 1;
+        }
+        self.truth = truth;
+    }
+
+    //One CoMPASS file per channel with energy and short gate energy, as CoMPASS writes them
+    fn get_file_bytes(&self, hits: &[GeneratedHit], board: u32) -> Vec<u8> {
+        let width = self
+            .energy_widths
+            .get(board as usize)
+            .copied()
+            .unwrap_or_default();
+        let header = CompassDataType::ENERGY.bits() | CompassDataType::ENERGY_SHORT.bits();
+        let mut bytes = header.to_le_bytes().to_vec();
+        for hit in hits.iter() {
+            bytes.extend(hit.raw.board.to_le_bytes());
+            bytes.extend(hit.raw.channel.to_le_bytes());
+            bytes.extend(hit.raw.timestamp.to_le_bytes());
+            match width {
+                EnergyWidth::Standard => bytes.extend((hit.raw.energy as u16).to_le_bytes()),
+                EnergyWidth::Extended => bytes.extend(hit.raw.energy.to_le_bytes()),
+            }
+            bytes.extend(hit.raw.energy_short.to_le_bytes());
+            bytes.extend(hit.raw.flags.to_le_bytes());
+        }
+        bytes
+    }
+
+    //A tar archive as the DAQ leaves a run, to put into the archive directory of a workspace
+    pub fn write_archive(&self, path: &Path) -> Result<(), EVBError> {
+        let mut archive = tar::Builder::new(std::fs::File::create(path)?);
+        let mut corrupted = 0;
+        for (mapped, hits) in self.channels.iter() {
+            let mut bytes = self.get_file_bytes(hits, mapped.board);
+            if !hits.is_empty() && corrupted < self.corrupt_files {
+                //Half of the last record, as a DAQ stopped while writing it would leave
+                let record_bytes = (bytes.len() - 2) / hits.len();
+                bytes.truncate(bytes.len() - record_bytes / 2);
+                corrupted += 1;
+            }
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive.append_data(
+                &mut header,
+                format!(
+                    "Data_CH{}@board{}_run_{}.BIN",
+                    mapped.channel, mapped.board, GENERATED_RUN
+                ),
+                bytes.as_slice(),
+            )?;
+        }
+        archive.finish()?;
+        Ok(())
+    }
+}
+
+//The events built from a generated run next to what was generated
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct GeneratorValidation {
+    pub truth: GeneratorTruth,
+    pub built_events: u64,
+    pub built_hits: u64,
+    pub built_multiplicities: BTreeMap<usize, u64>,
+}
+
+impl GeneratorValidation {
+    pub fn is_matched(&self) -> bool {
+        self.built_events == self.truth.events
+            && self.built_hits == self.truth.hits
+            && self.built_multiplicities == self.truth.multiplicities
+    }
+
+    pub fn format_report(&self) -> String {
+        let truth = &self.truth;
+        let mut report = format!("{:<22}{:>12}{:>12}\n", "", "Generated", "Built");
+        report.push_str(&format!(
+            "{:<22}{:>12}{:>12}\n",
+            "Events", truth.events, self.built_events
+        ));
+        report.push_str(&format!(
+            "{:<22}{:>12}{:>12}\n",
+            "Hits in events", truth.hits, self.built_hits
+        ));
+        let largest = truth
+            .multiplicities
+            .keys()
+            .chain(self.built_multiplicities.keys())
+            .max()
+            .copied()
+            .unwrap_or(0);
+        for multiplicity in 1..=largest {
+            let count = |counts: &BTreeMap<usize, u64>| counts.get(&multiplicity).copied();
+            report.push_str(&format!(
+                "{:<22}{:>12}{:>12}\n",
+                format!("  with {} hits", multiplicity),
+                count(&truth.multiplicities).unwrap_or(0),
+                count(&self.built_multiplicities).unwrap_or(0)
+            ));
+        }
+        report.push_str(&format!(
+            "\n{} pileup hits, {} hits cut off by corrupted files, {} hits left open at the end\n",
+            truth.pileup_hits, truth.corrupted_hits, truth.hits_left_open
+        ));
+        if self.is_matched() {
+            report.push_str("Every generated event was built as generated\n");
+        } else if self.built_events > truth.events {
+            report.push_str(&format!(
+                "{} more events than generated, events are split: the window is shorter than \
+                 their hits are apart\n",
+                self.built_events - truth.events
+            ));
+        } else if self.built_events < truth.events {
+            report.push_str(&format!(
+                "{} fewer events than generated, events are merged or dropped: the window is \
+                 longer than the events are apart, or hits are filtered\n",
+                truth.events - self.built_events
+            ));
+        } else {
+            report.push_str("As many events as generated, but not with the same hits\n");
+        }
+        report
+    }
+}
+
+//Generates a run, builds it with the config in a scratch workspace and counts the events built.
+//The events are not split into singles and coincidences, everything else of the config applies,
+//so hit filters, thresholds and event filters show up as missing hits and events.
+pub fn validate_generated_run(
+    mut config: EvbAppParams,
+    params: &HitGeneratorParams,
+) -> Result<GeneratorValidation, EVBError> {
+    let run = generate_run(params, &config)?;
+    config.split_singles = false;
+    let column = config.column_naming.get_column_name("Multiplicity");
+    let workspace_dir = std::env::temp_dir().join(format!(
+        "cebra_evb_generated_{}_{}",
+        std::process::id(),
+        NEXT_WORKSPACE.fetch_add(1, Ordering::Relaxed)
+    ));
+    if workspace_dir.exists() {
+        std::fs::remove_dir_all(&workspace_dir)?;
+    }
+    let result = Workspace::new(&workspace_dir)
+        .map_err(|e| EVBError::Config(e.to_string()))
+        .and_then(|workspace| {
+            let archive_dir = workspace
+                .get_archive_dir()
+                .map_err(|e| EVBError::Config(e.to_string()))?;
+            run.write_archive(&archive_dir.join(format!("run_{}.tar", GENERATED_RUN)))?;
+            let options = HeadlessOptions {
+                workspace: Some(workspace_dir.clone()),
+                ..Default::default()
+            };
+            build_run_dataframe(config, GENERATED_RUN, &options)
+        });
+    std::fs::remove_dir_all(&workspace_dir)?;
+    let df = result?;
+
+    let mut validation = GeneratorValidation {
+        truth: run.truth,
+        built_events: df.height() as u64,
+        ..Default::default()
+    };
+    for multiplicity in df.column(&column)?.f64()?.into_iter().flatten() {
+        let multiplicity = multiplicity as usize;
+        validation.built_hits += multiplicity as u64;
+        *validation
+            .built_multiplicities
+            .entry(multiplicity)
+            .or_default() += 1;
+    }
+    Ok(validation)
+}
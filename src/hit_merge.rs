@@ -0,0 +1,155 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use super::build_diagnostics::emit_warning;
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::hit_source::{HitSource, Waiting};
+
+//The top hit of a file, by time and then by file, so hits at the same time are merged in the order
+//of the files
+#[derive(Debug, Clone, Copy)]
+struct MergeKey {
+    timestamp: f64,
+    file: usize,
+}
+
+impl PartialEq for MergeKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeKey {}
+
+impl PartialOrd for MergeKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MergeKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.timestamp
+            .total_cmp(&other.timestamp)
+            .then(self.file.cmp(&other.file))
+    }
+}
+
+//Merges the files of a run into one stream in time order, with a heap of the top hit of each file.
+//Only the top hit of every file is held, so memory does not grow with the run, and finding the next
+//hit takes log(files) rather than a look at every file. The merge is only in order if every file is
+//(see HitSource), a hit earlier than the one merged before it is counted as out of order. Files
+//still being written are merged as far as they were read (see follow).
+#[derive(Debug, Default)]
+pub struct HitMerge {
+    heap: BinaryHeap<Reverse<MergeKey>>,
+    //Files waiting to grow, without a hit to merge until they read on
+    waiting: Vec<usize>,
+    //Returned last, its top hit may have been used since
+    taken: Option<usize>,
+    last_time: Option<f64>,
+    out_of_order: u64,
+    //ns
+    largest_step_back: f64,
+}
+
+impl HitMerge {
+    pub fn new(files: &mut [Box<dyn HitSource + '_>]) -> Result<Self, EVBError> {
+        let mut merge = HitMerge::default();
+        merge.refill(files)?;
+        Ok(merge)
+    }
+
+    fn push(&mut self, files: &mut [Box<dyn HitSource + '_>], file: usize) -> Result<(), EVBError> {
+        if files[file].is_eof() {
+            return Ok(());
+        }
+        let hit = files[file].get_top_hit()?;
+        if !hit.is_default() {
+            self.heap.push(Reverse(MergeKey {
+                timestamp: hit.timestamp,
+                file,
+            }));
+        } else if files[file].get_waiting().is_some() {
+            self.waiting.push(file);
+        }
+        Ok(())
+    }
+
+    //A file added after the merge started, e.g. a channel that started writing during the run
+    pub fn add_file(
+        &mut self,
+        files: &mut [Box<dyn HitSource + '_>],
+        file: usize,
+    ) -> Result<(), EVBError> {
+        self.push(files, file)
+    }
+
+    //Starts again from the top hits of the files, after they moved on to the next run of a
+    //concatenated file (see run_boundary)
+    pub fn refill(&mut self, files: &mut [Box<dyn HitSource + '_>]) -> Result<(), EVBError> {
+        self.heap.clear();
+        self.waiting.clear();
+        self.taken = None;
+        self.last_time = None;
+        for file in 0..files.len() {
+            self.push(files, file)?;
+        }
+        Ok(())
+    }
+
+    //The file with the earliest top hit, None once every file is read or stopped at a boundary, or
+    //while a file still growing may yet write an earlier hit. The file returned before goes back
+    //into the heap with its top hit, a new one if its hit was used.
+    pub fn get_earliest_file(
+        &mut self,
+        files: &mut [Box<dyn HitSource + '_>],
+    ) -> Result<Option<usize>, EVBError> {
+        if let Some(file) = self.taken.take() {
+            self.push(files, file)?;
+        }
+        for file in std::mem::take(&mut self.waiting) {
+            self.push(files, file)?;
+        }
+        if self
+            .waiting
+            .iter()
+            .any(|file| files[*file].get_waiting() == Some(Waiting::Growing))
+        {
+            return Ok(None);
+        }
+        let key = match self.heap.pop() {
+            Some(Reverse(key)) => key,
+            None => return Ok(None),
+        };
+        match self.last_time {
+            Some(last_time) if key.timestamp < last_time => {
+                self.out_of_order += 1;
+                self.largest_step_back = self.largest_step_back.max(last_time - key.timestamp);
+            }
+            _ => self.last_time = Some(key.timestamp),
+        }
+        self.taken = Some(key.file);
+        Ok(Some(key.file))
+    }
+
+    pub fn get_out_of_order(&self) -> u64 {
+        self.out_of_order
+    }
+
+    pub fn report(&self) {
+        if self.out_of_order > 0 {
+            emit_warning(
+                "merge_order",
+                String::new(),
+                format!(
+                    "{} hits were merged out of time order, up to {:.1} ns before the hit merged \
+                     before them, their files are not sorted in time (see Timestamp Sanitizer)",
+                    format_count(self.out_of_order),
+                    self.largest_step_back
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::run_boundary::ResetTimes;
+use super::used_size::UsedSize;
+
+//Where a source of a file that is still being written stands once it has read all that was written
+//so far. Its top hit is then a default hit, and it reads on once the file has grown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waiting {
+    //The file grew recently, so hits that come before those read may still be written
+    Growing,
+    //The file has not grown for a while, the hits read so far can be passed on
+    Idle,
+}
+
+//A time ordered stream of hits, e.g. one CoMPASS channel file or a legacy CSV export. The run
+//merges the top hit of every source, so each source must already be sorted in time. Sources are
+//Send so they can be decoded on a worker thread (see hit_decoder).
+pub trait HitSource: UsedSize + Send {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError>;
+    fn set_hit_used(&mut self);
+    fn is_eof(&self) -> bool;
+    fn get_number_of_hits(&self) -> u64;
+
+    //Only sources that split concatenated runs stop at a boundary (see run_boundary). While they
+    //are stopped the top hit is a default hit, until the next run is started.
+    fn get_boundary(&self) -> Option<ResetTimes> {
+        None
+    }
+
+    fn start_next_run(&mut self) {}
+
+    //Only sources of files still being written wait. Sources wrapping another pass its waiting on,
+    //and hold back the hits they buffer while it is Growing.
+    fn get_waiting(&self) -> Option<Waiting> {
+        None
+    }
+}
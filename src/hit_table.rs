@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::path::Path;
+
+use log::info;
+use polars::prelude::*;
+
+use super::channel_data::TimeUnit;
+use super::column_codec::ColumnCodec;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::parquet_writer::{write_parquet, ParquetMetadata};
+use super::used_size::UsedSize;
+use super::write_limit::{ThrottledWriter, WriteLimitParams};
+
+//Raw hits in the order they went into the event builder (after shifts and gain correction), one
+//row per hit, with the unshifted times as well when the raw times are kept. Useful for checking the event building itself, but as large as the raw data.
+#[derive(Debug, Clone, Default)]
+pub struct HitTable {
+    board: Vec<f64>,
+    channel: Vec<f64>,
+    energy: Vec<f64>,
+    energy_short: Vec<f64>,
+    time: Vec<f64>,
+    //Only kept with the raw times: the time before time shifts and jitter
+    raw_time: Option<Vec<f64>>,
+    time_unit: TimeUnit,
+}
+
+impl HitTable {
+    pub fn new(time_unit: TimeUnit, keep_raw_times: bool) -> Self {
+        HitTable {
+            time_unit,
+            raw_time: keep_raw_times.then(Vec::new),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    pub fn push_hit(&mut self, hit: &CompassData) {
+        let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+        self.board.push(board as f64);
+        self.channel.push(channel as f64);
+        self.energy.push(hit.energy);
+        self.energy_short.push(hit.energy_short);
+        self.time
+            .push(self.time_unit.convert_nanoseconds(hit.timestamp));
+        if let Some(raw_time) = &mut self.raw_time {
+            raw_time.push(self.time_unit.convert_nanoseconds(hit.raw_timestamp));
+        }
+    }
+
+    pub fn write_table(
+        self,
+        filepath: &Path,
+        codecs: &[ColumnCodec],
+        write_limit: &WriteLimitParams,
+    ) -> Result<(), PolarsError> {
+        info!("Writing raw hits to {}", filepath.display());
+        let mut columns = vec![
+            Series::new("Board", self.board),
+            Series::new("Channel", self.channel),
+            Series::new("Energy", self.energy),
+            Series::new("EnergyShort", self.energy_short),
+            Series::new("Time", self.time),
+        ];
+        let mut metadata = ParquetMetadata::default();
+        metadata.insert_file("time_unit", self.time_unit.as_ref().to_string());
+        metadata.insert_column("Time", "unit", self.time_unit.as_ref().to_string());
+        if let Some(raw_time) = self.raw_time {
+            columns.push(Series::new("RawTime", raw_time));
+            metadata.insert_column("RawTime", "unit", self.time_unit.as_ref().to_string());
+        }
+        let mut df = DataFrame::new(columns)?;
+
+        let file = ThrottledWriter::new(File::create(filepath)?, write_limit);
+        write_parquet(file, &mut df, &metadata, codecs)?;
+        Ok(())
+    }
+}
+
+impl UsedSize for HitTable {
+    fn get_used_size(&self) -> usize {
+        (self.board.capacity()
+            + self.channel.capacity()
+            + self.energy.capacity()
+            + self.energy_short.capacity()
+            + self.time.capacity()
+            + self
+                .raw_time
+                .as_ref()
+                .map_or(0, |raw_time| raw_time.capacity()))
+            * std::mem::size_of::<f64>()
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::AsRefStr;
+
+use super::channel_data::INVALID_VALUE;
+use super::error::EVBError;
+use super::parquet_writer::ParquetMetadata;
+
+//Column metadata entry holding the invalid value of the column
+pub const INVALID_VALUE_KEY: &str = "invalid_value";
+//Recorded as the invalid value of the columns of tables written with nulls
+const NULL_VALUE: &str = "null";
+
+#[derive(Debug, Clone, Copy, PartialEq, AsRefStr)]
+pub enum ColumnClass {
+    Energy,
+    Time,
+    //Multiplicities, weights, positions, ids, ...
+    Other,
+}
+
+//Value written in place of the invalid value in the columns of each class. Events are built with
+//INVALID_VALUE everywhere, the sentinels are only put in as the columns are written, and recorded
+//in the metadata of every column.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InvalidValues {
+    pub energy: f64,
+    pub time: f64,
+    pub other: f64,
+    //Write nulls instead of the sentinels, to the outputs that can hold them (parquet and Arrow
+    //IPC). The others (ROOT, HDF5, the shared memory ring) get INVALID_VALUE.
+    #[serde(default)]
+    pub nulls: bool,
+}
+
+impl Default for InvalidValues {
+    fn default() -> Self {
+        InvalidValues {
+            energy: INVALID_VALUE,
+            time: INVALID_VALUE,
+            other: INVALID_VALUE,
+            nulls: false,
+        }
+    }
+}
+
+impl InvalidValues {
+    pub fn get(&self, class: ColumnClass) -> f64 {
+        match class {
+            ColumnClass::Energy => self.energy,
+            ColumnClass::Time => self.time,
+            ColumnClass::Other => self.other,
+        }
+    }
+
+    //Value of the columns of a class in outputs that cannot hold nulls
+    pub fn get_fill(&self, class: ColumnClass) -> f64 {
+        if self.nulls {
+            INVALID_VALUE
+        } else {
+            self.get(class)
+        }
+    }
+
+    //A column with INVALID_VALUE replaced by the sentinel of its class, or by nulls
+    pub fn get_series(&self, name: &str, class: ColumnClass, mut values: Vec<f64>) -> Series {
+        if self.nulls {
+            let values: Vec<Option<f64>> = values
+                .into_iter()
+                .map(|value| Some(value).filter(|value| *value != INVALID_VALUE))
+                .collect();
+            return Series::new(name, values);
+        }
+        let sentinel = self.get(class);
+        if sentinel != INVALID_VALUE {
+            values
+                .iter_mut()
+                .filter(|value| **value == INVALID_VALUE)
+                .for_each(|value| *value = sentinel);
+        }
+        Series::new(name, values)
+    }
+
+    //For the invalid_value metadata of a column
+    pub fn describe_class(&self, class: ColumnClass) -> String {
+        if self.nulls {
+            NULL_VALUE.to_string()
+        } else {
+            self.get(class).to_string()
+        }
+    }
+
+    //e.g. "energy=0,time=-1000000,other=-1000000", or "null", for the metadata of the tables
+    pub fn describe(&self) -> String {
+        if self.nulls {
+            return NULL_VALUE.to_string();
+        }
+        format!(
+            "energy={},time={},other={}",
+            self.energy, self.time, self.other
+        )
+    }
+}
+
+//The invalid value of every column of a built table, as recorded in its column metadata. Tables
+//built before the sentinels were configurable have INVALID_VALUE in every column. Columns written
+//with nulls hold no sentinel, they are read as INVALID_VALUE, which they never have.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnInvalidValues {
+    columns: HashMap<String, f64>,
+}
+
+impl ColumnInvalidValues {
+    pub fn read(filepath: &Path) -> Result<Self, EVBError> {
+        let mut file = File::open(filepath)?;
+        let metadata = polars_parquet::read::read_metadata(&mut file)?;
+        let schema = polars_parquet::read::infer_schema(&metadata)?;
+        Ok(ColumnInvalidValues {
+            columns: schema
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    field
+                        .metadata
+                        .get(INVALID_VALUE_KEY)
+                        .and_then(|value| value.parse::<f64>().ok())
+                        .map(|value| (field.name.clone(), value))
+                })
+                .collect(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> f64 {
+        self.columns.get(name).copied().unwrap_or(INVALID_VALUE)
+    }
+
+    //Rows where the column has a value, neither null nor the sentinel
+    pub fn get_valid(&self, name: &str) -> Expr {
+        col(name)
+            .is_not_null()
+            .and(col(name).neq(lit(self.get(name))))
+    }
+
+    //For a table made from the built one, e.g. a time slice
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        for (name, value) in self.columns.iter() {
+            metadata.insert_column(name, INVALID_VALUE_KEY, value.to_string());
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+use super::error::EVBError;
+
+//The Arrow IPC (Feather v2) file of the table written to filepath, events.parquet gives
+//events.arrow
+pub fn get_ipc_path(filepath: &Path) -> PathBuf {
+    filepath.with_extension("arrow")
+}
+
+//The columns as they are, list columns included, LZ4 compressed so reading stays fast, for
+//pyarrow.feather.read_table or polars.read_ipc
+pub fn write_event_ipc(df: &mut DataFrame, filepath: &Path) -> Result<(), EVBError> {
+    IpcWriter::new(File::create(filepath)?)
+        .with_compression(Some(IpcCompression::LZ4))
+        .finish(df)?;
+    Ok(())
+}
@@ -4,29 +4,316 @@
 #[cfg(not(target_arch = "wasm32"))]
 mod app;
 #[cfg(not(target_arch = "wasm32"))]
+mod batch_schedule;
+#[cfg(not(target_arch = "wasm32"))]
+mod bin_diff;
+#[cfg(not(target_arch = "wasm32"))]
+mod buffered_source;
+#[cfg(not(target_arch = "wasm32"))]
+mod build_diagnostics;
+#[cfg(not(target_arch = "wasm32"))]
+mod calibration_spectra;
+#[cfg(not(target_arch = "wasm32"))]
+mod campaign;
+#[cfg(not(target_arch = "wasm32"))]
 mod channel_data;
 #[cfg(not(target_arch = "wasm32"))]
+mod channel_discovery;
+#[cfg(not(target_arch = "wasm32"))]
 mod channel_map;
 #[cfg(not(target_arch = "wasm32"))]
+mod channel_rates;
+#[cfg(not(target_arch = "wasm32"))]
+mod checkpoint;
+#[cfg(not(target_arch = "wasm32"))]
+mod checksum;
+#[cfg(not(target_arch = "wasm32"))]
+mod codec_stats;
+#[cfg(not(target_arch = "wasm32"))]
+mod coincidence_trend;
+#[cfg(not(target_arch = "wasm32"))]
+mod column_codec;
+#[cfg(not(target_arch = "wasm32"))]
+mod column_naming;
+#[cfg(not(target_arch = "wasm32"))]
 mod compass_data;
 #[cfg(not(target_arch = "wasm32"))]
 mod compass_file;
 #[cfg(not(target_arch = "wasm32"))]
 mod compass_run;
 #[cfg(not(target_arch = "wasm32"))]
+mod compass_settings;
+#[cfg(not(target_arch = "wasm32"))]
+mod config_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod config_validation;
+#[cfg(not(target_arch = "wasm32"))]
+mod corrupt_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod csv_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod data_reduction;
+#[cfg(not(target_arch = "wasm32"))]
+mod delayed_window;
+#[cfg(not(target_arch = "wasm32"))]
+mod detector_spectra;
+#[cfg(not(target_arch = "wasm32"))]
+mod efficiency;
+#[cfg(not(target_arch = "wasm32"))]
+mod energy_calibration;
+#[cfg(not(target_arch = "wasm32"))]
+mod energy_policy;
+#[cfg(not(target_arch = "wasm32"))]
+mod energy_rank;
+#[cfg(not(target_arch = "wasm32"))]
+mod energy_smearing;
+#[cfg(not(target_arch = "wasm32"))]
+mod energy_threshold;
+#[cfg(not(target_arch = "wasm32"))]
 mod error;
 #[cfg(not(target_arch = "wasm32"))]
 mod event_builder;
 #[cfg(not(target_arch = "wasm32"))]
+mod event_clock;
+#[cfg(not(target_arch = "wasm32"))]
+mod event_filter;
+#[cfg(not(target_arch = "wasm32"))]
+mod event_stream;
+#[cfg(not(target_arch = "wasm32"))]
+mod focal_plane;
+#[cfg(not(target_arch = "wasm32"))]
+mod follow;
+#[cfg(not(target_arch = "wasm32"))]
+mod formatting;
+#[cfg(not(target_arch = "wasm32"))]
+mod gain_drift;
+#[cfg(not(target_arch = "wasm32"))]
+mod gate_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod hdf5_writer;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+#[cfg(not(target_arch = "wasm32"))]
+mod histogram;
+#[cfg(not(target_arch = "wasm32"))]
+mod histogram_only;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_decoder;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_filter;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_generator;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_merge;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_source;
+#[cfg(not(target_arch = "wasm32"))]
+mod hit_table;
+#[cfg(not(target_arch = "wasm32"))]
+mod invalid_values;
+#[cfg(not(target_arch = "wasm32"))]
+mod ipc_writer;
+#[cfg(not(target_arch = "wasm32"))]
+mod memory_profile;
+#[cfg(not(target_arch = "wasm32"))]
+mod multi_hit;
+#[cfg(not(target_arch = "wasm32"))]
+mod network_sink;
+#[cfg(not(target_arch = "wasm32"))]
+mod opened_build;
+#[cfg(not(target_arch = "wasm32"))]
+mod output_format;
+#[cfg(not(target_arch = "wasm32"))]
+mod output_recovery;
+#[cfg(not(target_arch = "wasm32"))]
+mod output_sink;
+#[cfg(not(target_arch = "wasm32"))]
+mod output_sort;
+#[cfg(not(target_arch = "wasm32"))]
+mod pair_window;
+#[cfg(not(target_arch = "wasm32"))]
+mod parallel_build;
+#[cfg(not(target_arch = "wasm32"))]
+mod paranoid;
+#[cfg(not(target_arch = "wasm32"))]
+mod parquet_writer;
+#[cfg(not(target_arch = "wasm32"))]
+mod passthrough;
+#[cfg(not(target_arch = "wasm32"))]
+mod plot_export;
+#[cfg(not(target_arch = "wasm32"))]
+mod post_batch;
+#[cfg(not(target_arch = "wasm32"))]
+mod progress;
+#[cfg(not(target_arch = "wasm32"))]
+mod quick_build;
+#[cfg(not(target_arch = "wasm32"))]
+mod quick_look;
+#[cfg(not(target_arch = "wasm32"))]
+mod rate_limit;
+#[cfg(not(target_arch = "wasm32"))]
+mod regression;
+#[cfg(not(target_arch = "wasm32"))]
+mod resource_limits;
+#[cfg(not(target_arch = "wasm32"))]
+mod rf_reference;
+#[cfg(not(target_arch = "wasm32"))]
+mod root_writer;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_archive;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_boundary;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_comparison;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_layout;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_list;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_log;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_merge;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_metadata;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_notes;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_qa;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_report;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_scan;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_statistics;
+#[cfg(not(target_arch = "wasm32"))]
+mod run_stitching;
+#[cfg(not(target_arch = "wasm32"))]
 mod scaler_list;
 #[cfg(not(target_arch = "wasm32"))]
+mod scaler_rates;
+#[cfg(not(target_arch = "wasm32"))]
+mod shared_memory;
+#[cfg(not(target_arch = "wasm32"))]
 mod shift_map;
 #[cfg(not(target_arch = "wasm32"))]
+mod shutdown;
+#[cfg(not(target_arch = "wasm32"))]
+mod sim_file;
+#[cfg(not(target_arch = "wasm32"))]
+mod skip_list;
+#[cfg(not(target_arch = "wasm32"))]
+mod slow_control;
+#[cfg(not(target_arch = "wasm32"))]
+mod state_spectra;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_calibration;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_differences;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_jitter;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_reversal;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_slice;
+#[cfg(not(target_arch = "wasm32"))]
+mod time_walk;
+#[cfg(not(target_arch = "wasm32"))]
+mod timestamp_sanitizer;
+#[cfg(not(target_arch = "wasm32"))]
+mod unmapped;
+#[cfg(not(target_arch = "wasm32"))]
 mod used_size;
 #[cfg(not(target_arch = "wasm32"))]
+mod waveform;
+#[cfg(not(target_arch = "wasm32"))]
+mod workspace_bundle;
+#[cfg(not(target_arch = "wasm32"))]
+mod write_limit;
+#[cfg(not(target_arch = "wasm32"))]
 mod ws;
 #[cfg(not(target_arch = "wasm32"))]
-pub use app::EVBApp;
+pub use app::{format_channel_map_file, lint_channel_map_file, EVBApp, EvbAppParams as RunConfig};
+#[cfg(not(target_arch = "wasm32"))]
+pub use bin_diff::diff_compass_files;
+#[cfg(not(target_arch = "wasm32"))]
+pub use channel_discovery::{discover_channels, discover_run_channels, DiscoveredChannel};
+#[cfg(not(target_arch = "wasm32"))]
+pub use channel_map::{Board, ChannelMap, ChannelType};
+#[cfg(not(target_arch = "wasm32"))]
+pub use codec_stats::{analyze_codecs, CodecReport, DEFAULT_SAMPLE_ROWS};
+#[cfg(not(target_arch = "wasm32"))]
+pub use column_codec::ColumnCodec;
+#[cfg(not(target_arch = "wasm32"))]
+pub use compass_data::{generate_board_channel_uuid, CompassData};
+#[cfg(not(target_arch = "wasm32"))]
+pub use compass_file::{CompassHits, SegmentAccess};
+#[cfg(not(target_arch = "wasm32"))]
+pub use compass_settings::{BoardSettings, ChannelSettings, CompassSettings};
+#[cfg(not(target_arch = "wasm32"))]
+pub use config_file::{
+    format_config, parse_config, read_config_file, write_config_file, ConfigFormat, CONFIG_VERSION,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use error::EVBError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use event_builder::{EventBuilder, EventBuilderStats};
+#[cfg(not(target_arch = "wasm32"))]
+pub use event_stream::EventStream;
+#[cfg(not(target_arch = "wasm32"))]
+pub use follow::{follow_run, FollowParams, RunFollower};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hdf5_writer::Hdf5Params;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::{
+    build_headless, build_run_dataframe, build_runs, scan_headless, HeadlessOptions,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hit_filter::HitFilter;
+#[cfg(not(target_arch = "wasm32"))]
+pub use hit_generator::{
+    generate_run, validate_generated_run, GeneratedRun, GeneratorTruth, GeneratorValidation,
+    HitGeneratorParams,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use hit_merge::HitMerge;
+#[cfg(not(target_arch = "wasm32"))]
+pub use hit_source::HitSource;
+#[cfg(not(target_arch = "wasm32"))]
+pub use memory_profile::TrackingAllocator;
+#[cfg(not(target_arch = "wasm32"))]
+pub use network_sink::{NetworkSink, NetworkSinkParams};
+#[cfg(not(target_arch = "wasm32"))]
+pub use output_format::{
+    Hdf5Output, IpcOutput, MemoryOutput, OutputWriter, ParquetOutput, RootOutput,
+};
+#[cfg(not(target_arch = "wasm32"))]
+pub use output_sink::OutputSink;
+#[cfg(not(target_arch = "wasm32"))]
+pub use parquet_writer::ParquetMetadata;
+#[cfg(not(target_arch = "wasm32"))]
+pub use post_batch::PostBatchHook;
+#[cfg(not(target_arch = "wasm32"))]
+pub use regression::{get_reference_counts, run_regression, write_example_workspace};
+#[cfg(not(target_arch = "wasm32"))]
+pub use resource_limits::ResourceOverrides;
+#[cfg(not(target_arch = "wasm32"))]
+pub use run_archive::unpack_run_archive;
+#[cfg(not(target_arch = "wasm32"))]
+pub use run_log::{init_logger, LogLevel};
+#[cfg(not(target_arch = "wasm32"))]
+pub use run_merge::{get_merged_path, merge_runs, MergedRun, RUN_NUMBER_COLUMN};
+#[cfg(not(target_arch = "wasm32"))]
+pub use run_scan::{scan_run, OutputDensity, RunScan, ScannedChannel};
+#[cfg(not(target_arch = "wasm32"))]
+pub use shutdown::{install_shutdown_handler, is_shutdown_requested};
+#[cfg(not(target_arch = "wasm32"))]
+pub use time_slice::{export_time_slice, parse_run_time, TimeSliceParams};
+#[cfg(not(target_arch = "wasm32"))]
+pub use time_walk::{read_time_walk_file, TimeWalkParams};
+#[cfg(not(target_arch = "wasm32"))]
+pub use waveform::WaveformSummary;
+#[cfg(not(target_arch = "wasm32"))]
+pub use workspace_bundle::{export_workspace_bundle, import_workspace_bundle};
 
 #[cfg(target_arch = "wasm32")]
 mod app_web;
@@ -1,10 +1,515 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+//Tracks the heap for the peak memory of the run reports
+#[cfg(not(target_arch = "wasm32"))]
+#[global_allocator]
+static GLOBAL: cebra_eventbuilder::TrackingAllocator = cebra_eventbuilder::TrackingAllocator;
+
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    let args: Vec<String> = std::env::args().collect();
+
+    //--log-level <warn|info|debug> sets the level of the log of every run built, and of stderr
+    //unless RUST_LOG is set
+    let log_level = args
+        .iter()
+        .position(|arg| arg == "--log-level")
+        .map(|index| {
+            match args
+                .get(index + 1)
+                .and_then(|value| cebra_eventbuilder::LogLevel::parse(value))
+            {
+                Some(log_level) => log_level,
+                None => {
+                    eprintln!("--log-level needs one of warn, info or debug");
+                    std::process::exit(1);
+                }
+            }
+        });
+
+    //Log to stderr (if you run with `RUST_LOG=debug`), and to each run's logs/build.log
+    cebra_eventbuilder::init_logger(log_level);
+    //Ctrl-C and SIGTERM let the run being built finish writing its tables, marked partial
+    cebra_eventbuilder::install_shutdown_handler();
+
+    //--paranoid turns on expensive consistency checks, for validating a new config on a small run
+    let paranoid = std::env::args().any(|arg| arg == "--paranoid");
+
+    //--threads <N> and --memory-budget <MB> replace the resource limits of the config
+    let parse_limit = |flag: &str| -> Option<usize> {
+        let index = args.iter().position(|arg| arg == flag)?;
+        match args
+            .get(index + 1)
+            .and_then(|value| value.parse::<usize>().ok())
+        {
+            Some(value) => Some(value),
+            None => {
+                eprintln!("{} needs a whole number", flag);
+                std::process::exit(1);
+            }
+        }
+    };
+    let threads = parse_limit("--threads");
+    let memory_budget_mb = parse_limit("--memory-budget");
+    if memory_budget_mb == Some(0) {
+        eprintln!("--memory-budget needs at least 1 MB");
+        std::process::exit(1);
+    }
+    //The thread pool of polars is set up on its first use and read from the environment
+    if let Some(threads) = threads.filter(|threads| *threads > 0) {
+        std::env::set_var("POLARS_MAX_THREADS", threads.to_string());
+    }
+
+    //--print-map <config.yaml> prints the channel map of a config file and exits
+    if let Some(index) = args.iter().position(|arg| arg == "--print-map") {
+        let path = match args.get(index + 1) {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                eprintln!("--print-map needs the path of a config file");
+                std::process::exit(1);
+            }
+        };
+        match cebra_eventbuilder::format_channel_map_file(&path) {
+            Ok(table) => print!("{}", table),
+            Err(x) => {
+                eprintln!(
+                    "Unable to read the channel map of {}: {}",
+                    path.display(),
+                    x
+                );
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--lint-map <config.yaml> checks the channel map, shift map and energy calibration of a config
+    //file against each other, exits with 1 if there are problems
+    if let Some(index) = args.iter().position(|arg| arg == "--lint-map") {
+        let path = match args.get(index + 1) {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                eprintln!("--lint-map needs the path of a config file");
+                std::process::exit(1);
+            }
+        };
+        match cebra_eventbuilder::lint_channel_map_file(&path) {
+            Ok(problems) if problems.is_empty() => {
+                println!("No problems found in the channel map of {}", path.display());
+            }
+            Ok(problems) => {
+                for problem in problems.iter() {
+                    println!("{}", problem);
+                }
+                println!("{} problems found in {}", problems.len(), path.display());
+                std::process::exit(1);
+            }
+            Err(x) => {
+                eprintln!("Unable to read {}: {}", path.display(), x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--diff-bin <a.BIN> <b.BIN> compares two CoMPASS files record by record, exits with 1 if they
+    //differ
+    if let Some(index) = args.iter().position(|arg| arg == "--diff-bin") {
+        let (first, second) = match (args.get(index + 1), args.get(index + 2)) {
+            (Some(first), Some(second)) => (
+                std::path::PathBuf::from(first),
+                std::path::PathBuf::from(second),
+            ),
+            _ => {
+                eprintln!("--diff-bin needs the paths of two BIN files");
+                std::process::exit(1);
+            }
+        };
+        match cebra_eventbuilder::diff_compass_files(&first, &second) {
+            Ok(diff) => {
+                print!("{}", diff.format_report());
+                if !diff.is_identical() {
+                    std::process::exit(1);
+                }
+            }
+            Err(x) => {
+                eprintln!(
+                    "Unable to compare {} and {}: {}",
+                    first.display(),
+                    second.display(),
+                    x
+                );
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--codec-stats <file.parquet> [rows] writes a sample of a table with every codec and prints
+    //how much each column shrinks, with the column_codecs to put in the config
+    if let Some(index) = args.iter().position(|arg| arg == "--codec-stats") {
+        let path = match args.get(index + 1) {
+            Some(path) => std::path::PathBuf::from(path),
+            None => {
+                eprintln!("--codec-stats needs the path of a parquet file");
+                std::process::exit(1);
+            }
+        };
+        let rows = match args.get(index + 2).map(|rows| rows.parse::<usize>()) {
+            Some(Ok(rows)) if rows > 0 => rows,
+            None => cebra_eventbuilder::DEFAULT_SAMPLE_ROWS,
+            _ => {
+                eprintln!("--codec-stats needs a number of rows to sample");
+                std::process::exit(1);
+            }
+        };
+        match cebra_eventbuilder::analyze_codecs(&path, rows) {
+            Ok(report) => {
+                print!("{}", report.format_table());
+                println!();
+                print!("{}", report.format_recommended_config());
+            }
+            Err(x) => {
+                eprintln!("Unable to analyze {}: {}", path.display(), x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--slice <output_dir> <run> <start> <stop> <file.parquet> copies the events of a built run
+    //between two times since the start of the run (seconds or h:mm:ss) into a small file
+    if let Some(index) = args.iter().position(|arg| arg == "--slice") {
+        let values: Vec<&String> = args.iter().skip(index + 1).take(5).collect();
+        let (output_dir, run, start, stop, file) = match values.as_slice() {
+            [output_dir, run, start, stop, file] => (output_dir, run, start, stop, file),
+            _ => {
+                eprintln!(
+                    "--slice needs an output directory, a run number, a start and stop time and \
+                     the file to write"
+                );
+                std::process::exit(1);
+            }
+        };
+        let run_number = match run.parse::<i32>() {
+            Ok(run_number) => run_number,
+            Err(_) => {
+                eprintln!("--slice needs a run number, not '{}'", run);
+                std::process::exit(1);
+            }
+        };
+        let (start_seconds, stop_seconds) = match (
+            cebra_eventbuilder::parse_run_time(start),
+            cebra_eventbuilder::parse_run_time(stop),
+        ) {
+            (Ok(start_seconds), Ok(stop_seconds)) => (start_seconds, stop_seconds),
+            (Err(x), _) | (_, Err(x)) => {
+                eprintln!("{}", x);
+                std::process::exit(1);
+            }
+        };
+        let params = cebra_eventbuilder::TimeSliceParams {
+            run_number,
+            start_seconds,
+            stop_seconds,
+        };
+        match cebra_eventbuilder::export_time_slice(
+            std::path::Path::new(output_dir),
+            &params,
+            std::path::Path::new(file),
+        ) {
+            Ok(events) => println!("Wrote {} events to {}", events, file),
+            Err(x) => {
+                eprintln!("Unable to slice run {}: {}", run_number, x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--follow <config.yaml> <run_dir> <output_dir> builds the events of a run while CoMPASS is
+    //still writing it, until its files stop growing
+    if let Some(index) = args.iter().position(|arg| arg == "--follow") {
+        let (config, run_dir, output_dir) = match (
+            args.get(index + 1),
+            args.get(index + 2),
+            args.get(index + 3),
+        ) {
+            (Some(config), Some(run_dir), Some(output_dir)) => (
+                std::path::PathBuf::from(config),
+                std::path::PathBuf::from(run_dir),
+                std::path::PathBuf::from(output_dir),
+            ),
+            _ => {
+                eprintln!("--follow needs a config file, a run directory and an output directory");
+                std::process::exit(1);
+            }
+        };
+        if let Err(x) = cebra_eventbuilder::follow_run(
+            &config,
+            &run_dir,
+            &output_dir,
+            cebra_eventbuilder::FollowParams::default(),
+        ) {
+            eprintln!("Unable to follow {}: {}", run_dir.display(), x);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    //--export-workspace <workspace_dir> <bundle.tar.gz> bundles the config, run notes, scaler
+    //lists and reports of a workspace, without the data, to set up the analysis elsewhere
+    if let Some(index) = args.iter().position(|arg| arg == "--export-workspace") {
+        let (workspace_dir, bundle) = match (args.get(index + 1), args.get(index + 2)) {
+            (Some(workspace_dir), Some(bundle)) => (
+                std::path::PathBuf::from(workspace_dir),
+                std::path::PathBuf::from(bundle),
+            ),
+            _ => {
+                eprintln!("--export-workspace needs a workspace directory and a bundle file");
+                std::process::exit(1);
+            }
+        };
+        match cebra_eventbuilder::export_workspace_bundle(&workspace_dir, &bundle) {
+            Ok(count) => println!("Bundled {} files into {}", count, bundle.display()),
+            Err(x) => {
+                eprintln!("Unable to bundle {}: {}", workspace_dir.display(), x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--import-workspace <bundle.tar.gz> <workspace_dir> unpacks a bundle into a new workspace
+    if let Some(index) = args.iter().position(|arg| arg == "--import-workspace") {
+        let (bundle, workspace_dir) = match (args.get(index + 1), args.get(index + 2)) {
+            (Some(bundle), Some(workspace_dir)) => (
+                std::path::PathBuf::from(bundle),
+                std::path::PathBuf::from(workspace_dir),
+            ),
+            _ => {
+                eprintln!("--import-workspace needs a bundle file and a workspace directory");
+                std::process::exit(1);
+            }
+        };
+        if let Err(x) = cebra_eventbuilder::import_workspace_bundle(&bundle, &workspace_dir) {
+            eprintln!("Unable to import {}: {}", bundle.display(), x);
+            std::process::exit(1);
+        }
+        println!(
+            "Imported {} into {}",
+            bundle.display(),
+            workspace_dir.display()
+        );
+        return Ok(());
+    }
+
+    //--build <config.yaml> builds the runs of a config file without the GUI, e.g. over SSH. The
+    //options replace what the config sets:
+    //  --workspace <dir>          workspace with the run archives
+    //  --runs <list>              runs to build, e.g. "120-147, !133"
+    //  --window <ns>              coincidence window
+    //  --channel-map <config>     config file to take the channel map from
+    //  --formats <list>           output formats, e.g. parquet,root
+    //Exits with 1 if the config is invalid or a run failed.
+    if let Some(index) = args.iter().position(|arg| arg == "--build") {
+        let config = match args.get(index + 1) {
+            Some(config) => std::path::PathBuf::from(config),
+            None => {
+                eprintln!("--build needs the path of a config file");
+                std::process::exit(1);
+            }
+        };
+        let get_value = |flag: &str| -> Option<String> {
+            let index = args.iter().position(|arg| arg == flag)?;
+            match args.get(index + 1) {
+                Some(value) => Some(value.clone()),
+                None => {
+                    eprintln!("{} needs a value", flag);
+                    std::process::exit(1);
+                }
+            }
+        };
+        let coincidence_window = get_value("--window").map(|value| match value.parse::<f64>() {
+            Ok(window) => window,
+            Err(_) => {
+                eprintln!("--window needs a number of ns");
+                std::process::exit(1);
+            }
+        });
+        let options = cebra_eventbuilder::HeadlessOptions {
+            workspace: get_value("--workspace").map(std::path::PathBuf::from),
+            runs: get_value("--runs"),
+            coincidence_window,
+            channel_map: get_value("--channel-map").map(std::path::PathBuf::from),
+            output_formats: get_value("--formats"),
+            paranoid,
+            resource_overrides: cebra_eventbuilder::ResourceOverrides {
+                threads,
+                memory_budget_mb,
+            },
+            log_level,
+        };
+        if let Err(x) = cebra_eventbuilder::build_headless(&config, &options) {
+            eprintln!("Unable to build {}: {}", config.display(), x);
+            //The usual exit code of a process ended by Ctrl-C
+            if cebra_eventbuilder::is_shutdown_requested() {
+                std::process::exit(130);
+            }
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    //--scan <config.yaml> inventories the runs of a config file from the headers and sizes of
+    //their files without building them, with the same --workspace and --runs as --build. Exits
+    //with 1 if a run has no archive, a mapped channel without data or a damaged file.
+    if let Some(index) = args.iter().position(|arg| arg == "--scan") {
+        let config = match args.get(index + 1) {
+            Some(config) => std::path::PathBuf::from(config),
+            None => {
+                eprintln!("--scan needs the path of a config file");
+                std::process::exit(1);
+            }
+        };
+        let get_value = |flag: &str| -> Option<String> {
+            let index = args.iter().position(|arg| arg == flag)?;
+            match args.get(index + 1) {
+                Some(value) => Some(value.clone()),
+                None => {
+                    eprintln!("{} needs a value", flag);
+                    std::process::exit(1);
+                }
+            }
+        };
+        let options = cebra_eventbuilder::HeadlessOptions {
+            workspace: get_value("--workspace").map(std::path::PathBuf::from),
+            runs: get_value("--runs"),
+            ..Default::default()
+        };
+        match cebra_eventbuilder::scan_headless(&config, &options) {
+            Ok(scans) => {
+                for scan in scans.iter() {
+                    println!("{}", scan.format_report());
+                }
+                let incomplete = scans.iter().filter(|scan| !scan.is_complete()).count();
+                if incomplete > 0 {
+                    println!("{} of {} runs have problems", incomplete, scans.len());
+                    std::process::exit(1);
+                }
+            }
+            Err(x) => {
+                eprintln!("Unable to scan {}: {}", config.display(), x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--validate-window <config.yaml> [generator.yaml] builds a generated run with the windows and
+    //channel map of a config and compares the events built with those generated. The generator
+    //settings default to HitGeneratorParams::default(). Exits with 1 if they differ.
+    if let Some(index) = args.iter().position(|arg| arg == "--validate-window") {
+        let config = match args.get(index + 1) {
+            Some(config) => std::path::PathBuf::from(config),
+            None => {
+                eprintln!("--validate-window needs the path of a config file");
+                std::process::exit(1);
+            }
+        };
+        let generator = match args.get(index + 2).filter(|arg| !arg.starts_with("--")) {
+            Some(path) => match std::fs::read_to_string(path)
+                .map_err(|x| x.to_string())
+                .and_then(|yaml| serde_yaml::from_str(&yaml).map_err(|x| x.to_string()))
+            {
+                Ok(generator) => generator,
+                Err(x) => {
+                    eprintln!("Unable to read generator settings {}: {}", path, x);
+                    std::process::exit(1);
+                }
+            },
+            None => cebra_eventbuilder::HitGeneratorParams::default(),
+        };
+        let validation = cebra_eventbuilder::RunConfig::read(&config)
+            .and_then(|params| cebra_eventbuilder::validate_generated_run(params, &generator));
+        match validation {
+            Ok(validation) => {
+                print!("{}", validation.format_report());
+                if !validation.is_matched() {
+                    std::process::exit(1);
+                }
+            }
+            Err(x) => {
+                eprintln!("Unable to validate {}: {}", config.display(), x);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    //--example <dir> writes the bundled reference run and its config into a workspace, to build as
+    //a check of a new install
+    if let Some(index) = args.iter().position(|arg| arg == "--example") {
+        let dir = match args.get(index + 1) {
+            Some(dir) => std::path::PathBuf::from(dir),
+            None => {
+                eprintln!("--example needs a workspace directory");
+                std::process::exit(1);
+            }
+        };
+        let config_path = match cebra_eventbuilder::write_example_workspace(&dir) {
+            Ok(config_path) => config_path,
+            Err(x) => {
+                eprintln!("Unable to write the example workspace: {}", x);
+                std::process::exit(1);
+            }
+        };
+        println!(
+            "Wrote the example run to {}, build it with\n    cebra_eventbuilder --build {}\nor open the workspace in the GUI and press Run. built/run_1 should then have:",
+            dir.display(),
+            config_path.display()
+        );
+        for (name, count) in cebra_eventbuilder::get_reference_counts().unwrap_or_default() {
+            println!("    {:<24}{}", name, count);
+        }
+        return Ok(());
+    }
+
+    //--regression builds the bundled reference run and compares it against the golden checksums,
+    //--regression --bless stores the new outputs as golden instead
+    if args.iter().any(|arg| arg == "--regression") {
+        let bless = args.iter().any(|arg| arg == "--bless");
+        match cebra_eventbuilder::run_regression(bless) {
+            Ok(true) => {
+                if !bless {
+                    println!("Regression build passed");
+                }
+                return Ok(());
+            }
+            Ok(false) => {
+                println!("Regression build differs from the golden checksums");
+                std::process::exit(1);
+            }
+            Err(x) => {
+                eprintln!("Regression build failed: {}", x);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    //--config <config.yaml|config.toml> opens the GUI with the settings of a config file
+    let config = match args.iter().position(|arg| arg == "--config") {
+        Some(index) => match args.get(index + 1) {
+            Some(path) => Some(std::path::PathBuf::from(path)),
+            None => {
+                eprintln!("--config needs the path of a config file");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -15,7 +520,15 @@ fn main() -> eframe::Result<()> {
     eframe::run_native(
         "CeBrA Eventbuilder",
         native_options,
-        Box::new(|cc| Box::new(cebra_eventbuilder::EVBApp::new(cc, false))),
+        Box::new(move |cc| {
+            Box::new(
+                cebra_eventbuilder::EVBApp::new(cc, false)
+                    .with_paranoid(paranoid)
+                    .with_resource_limits(threads, memory_budget_mb)
+                    .with_log_level(log_level)
+                    .with_config(config.as_deref()),
+            )
+        }),
     )
 }
 
@@ -0,0 +1,145 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::info;
+use serde::Serialize;
+
+use super::formatting::format_bytes;
+
+//Thin wrapper around the system allocator which keeps a running total of live heap bytes and
+//the high water mark. Two relaxed atomics per allocation is cheap next to the allocation itself.
+//The library does not install it, so programs using the crate keep their own allocator; the
+//eventbuilder binary makes it the global allocator, otherwise the heap is not tracked.
+pub struct TrackingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn record_alloc(size: usize) {
+    let current = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                record_alloc(new_size - layout.size());
+            } else {
+                record_dealloc(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+//Any program that got this far has allocated, so nothing allocated means another allocator
+pub fn is_heap_tracked() -> bool {
+    get_allocated_bytes() > 0
+}
+
+pub fn get_allocated_bytes() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+pub fn get_peak_bytes() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+//Start a new high water mark from the current usage
+pub fn reset_peak_bytes() {
+    PEAK.store(get_allocated_bytes(), Ordering::Relaxed);
+}
+
+//Peak memory of each stage of a run. The reader and builder are sampled through UsedSize as the
+//run progresses, the writer (dataframe conversion + parquet encoding) is measured with the
+//allocator since its buffers live inside polars.
+//Without the TrackingAllocator the writer and heap peaks stay 0 and heap_tracked is false.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MemoryProfile {
+    pub reader_peak_bytes: usize,
+    pub builder_peak_bytes: usize,
+    pub writer_peak_bytes: usize,
+    pub heap_peak_bytes: usize,
+    pub heap_tracked: bool,
+    pub flush_threshold_bytes: usize,
+    pub fragments_written: usize,
+}
+
+impl MemoryProfile {
+    pub fn new(flush_threshold: usize) -> Self {
+        reset_peak_bytes();
+        MemoryProfile {
+            flush_threshold_bytes: flush_threshold,
+            heap_tracked: is_heap_tracked(),
+            ..Default::default()
+        }
+    }
+
+    pub fn sample(&mut self, reader_bytes: usize, builder_bytes: usize) {
+        self.reader_peak_bytes = self.reader_peak_bytes.max(reader_bytes);
+        self.builder_peak_bytes = self.builder_peak_bytes.max(builder_bytes);
+        self.heap_peak_bytes = self.heap_peak_bytes.max(get_peak_bytes());
+    }
+
+    //Returns the baseline to hand back to end_write
+    pub fn begin_write(&mut self) -> usize {
+        self.heap_peak_bytes = self.heap_peak_bytes.max(get_peak_bytes());
+        reset_peak_bytes();
+        get_allocated_bytes()
+    }
+
+    pub fn end_write(&mut self, baseline: usize) {
+        let peak = get_peak_bytes();
+        self.writer_peak_bytes = self.writer_peak_bytes.max(peak.saturating_sub(baseline));
+        self.heap_peak_bytes = self.heap_peak_bytes.max(peak);
+        self.fragments_written += 1;
+    }
+
+    pub fn log_summary(&self) {
+        if !self.heap_tracked {
+            info!(
+                "Peak memory: reader {}, builder {} (flush threshold {}), writer and total heap not \
+                 tracked without the TrackingAllocator",
+                format_bytes(self.reader_peak_bytes),
+                format_bytes(self.builder_peak_bytes),
+                format_bytes(self.flush_threshold_bytes)
+            );
+            return;
+        }
+        info!(
+            "Peak memory: reader {}, builder {}, writer {}, total heap {} (flush threshold {})",
+            format_bytes(self.reader_peak_bytes),
+            format_bytes(self.builder_peak_bytes),
+            format_bytes(self.writer_peak_bytes),
+            format_bytes(self.heap_peak_bytes),
+            format_bytes(self.flush_threshold_bytes)
+        );
+    }
+}
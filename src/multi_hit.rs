@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_map::ChannelType;
+use super::compass_data::CompassData;
+
+//Which hit fills the columns of a channel type that fired more than once in an event
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum MultiHitPolicy {
+    #[strum(serialize = "Keep First")]
+    KeepFirst,
+    #[default]
+    #[strum(serialize = "Keep Last")]
+    KeepLast,
+    #[strum(serialize = "Keep Highest Energy")]
+    KeepHighestEnergy,
+    //The first hit fills the columns, and every hit is listed in the list columns of the detector
+    #[strum(serialize = "Expand")]
+    Expand,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MultiHitEntry {
+    pub detector: ChannelType,
+    pub policy: MultiHitPolicy,
+}
+
+impl Default for MultiHitEntry {
+    fn default() -> Self {
+        MultiHitEntry {
+            detector: ChannelType::Cebra(0),
+            policy: MultiHitPolicy::KeepFirst,
+        }
+    }
+}
+
+//The policy of every channel type, channel types without an entry keep their last hit
+#[derive(Debug, Clone, Default)]
+pub struct MultiHitPolicies {
+    entries: Vec<MultiHitEntry>,
+}
+
+impl MultiHitPolicies {
+    pub fn new(entries: &[MultiHitEntry]) -> Self {
+        MultiHitPolicies {
+            entries: entries.to_vec(),
+        }
+    }
+
+    pub fn get_policy(&self, channel_type: &ChannelType) -> MultiHitPolicy {
+        self.entries
+            .iter()
+            .find(|entry| entry.detector == *channel_type)
+            .map(|entry| entry.policy)
+            .unwrap_or_default()
+    }
+
+    //Detectors whose hits are all listed in list columns
+    pub fn get_expanded(&self) -> impl Iterator<Item = ChannelType> + '_ {
+        self.entries
+            .iter()
+            .filter(|entry| entry.policy == MultiHitPolicy::Expand)
+            .map(|entry| entry.detector)
+    }
+
+    //Index in the event of the hit each channel type is filled from
+    pub fn select_hits(
+        &self,
+        event: &[CompassData],
+        channel_types: &[Option<ChannelType>],
+    ) -> HashMap<ChannelType, usize> {
+        let mut selected: HashMap<ChannelType, usize> = HashMap::new();
+        for (index, channel_type) in channel_types.iter().enumerate() {
+            let channel_type = match channel_type {
+                Some(channel_type) => *channel_type,
+                None => continue,
+            };
+            let is_better = match selected.get(&channel_type) {
+                None => true,
+                Some(current) => match self.get_policy(&channel_type) {
+                    MultiHitPolicy::KeepFirst | MultiHitPolicy::Expand => false,
+                    MultiHitPolicy::KeepLast => true,
+                    MultiHitPolicy::KeepHighestEnergy => {
+                        event[index].energy > event[*current].energy
+                    }
+                },
+            };
+            if is_better {
+                selected.insert(channel_type, index);
+            }
+        }
+        selected
+    }
+
+    //For the metadata of the tables, e.g. "default Keep Last; Cebra0 Keep First"
+    pub fn describe(&self) -> String {
+        std::iter::once(format!("default {}", MultiHitPolicy::default().as_ref()))
+            .chain(
+                self.entries
+                    .iter()
+                    .map(|entry| format!("{} {}", entry.detector, entry.policy.as_ref())),
+            )
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
@@ -0,0 +1,224 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{info, warn};
+use polars::prelude::*;
+use polars_arrow::io::ipc::write::{StreamWriter, WriteOptions};
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::output_sink::OutputSink;
+use super::run_merge::RUN_NUMBER_COLUMN;
+
+//Batches waiting for the network thread before new ones are dropped
+const QUEUED_BATCHES: usize = 16;
+//How often the network thread looks for new clients when no batch comes
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(200);
+//A client that takes longer than this to take a batch is dropped, so it cannot stall the others
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSinkParams {
+    pub enabled: bool,
+    //Address the event builder listens on for clients, e.g. 0.0.0.0:5557 for every interface
+    pub address: String,
+    //Events sent in each record batch, fewer at the end of a run
+    pub batch_events: usize,
+}
+
+impl Default for NetworkSinkParams {
+    fn default() -> Self {
+        NetworkSinkParams {
+            enabled: false,
+            address: String::from("0.0.0.0:5557"),
+            batch_events: 1000,
+        }
+    }
+}
+
+impl NetworkSinkParams {
+    pub fn get_address(&self) -> Result<SocketAddr, String> {
+        self.address
+            .trim()
+            .parse::<SocketAddr>()
+            .map_err(|_| format!("'{}' is not an address, e.g. 0.0.0.0:5557", self.address))
+    }
+}
+
+//A connected client gets its own Arrow IPC stream, which starts with the schema
+struct Client {
+    address: SocketAddr,
+    writer: StreamWriter<TcpStream>,
+    is_started: bool,
+}
+
+impl Client {
+    fn write_batch(&mut self, batch: &DataFrame) -> PolarsResult<()> {
+        if !self.is_started {
+            self.writer.start(&batch.schema().to_arrow(), None)?;
+            self.is_started = true;
+        }
+        for chunk in batch.iter_chunks() {
+            self.writer.write(&chunk, None)?;
+        }
+        Ok(())
+    }
+}
+
+//Takes on clients and sends every batch to each, until the sink is finalized
+fn serve(listener: TcpListener, batches: Receiver<DataFrame>) {
+    let mut clients: Vec<Client> = vec![];
+    loop {
+        let batch = batches.recv_timeout(ACCEPT_INTERVAL);
+        while let Ok((stream, address)) = listener.accept() {
+            let is_ready = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(CLIENT_TIMEOUT)))
+                .and_then(|_| stream.set_nodelay(true));
+            match is_ready {
+                Ok(()) => {
+                    info!("Streaming built events to {}", address);
+                    clients.push(Client {
+                        address,
+                        writer: StreamWriter::new(stream, WriteOptions { compression: None }),
+                        is_started: false,
+                    });
+                }
+                Err(x) => warn!("Unable to stream built events to {}: {}", address, x),
+            }
+        }
+        match batch {
+            Ok(batch) => clients.retain_mut(|client| match client.write_batch(&batch) {
+                Ok(()) => true,
+                Err(x) => {
+                    info!(
+                        "Stopped streaming built events to {}: {}",
+                        client.address, x
+                    );
+                    false
+                }
+            }),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    for mut client in clients {
+        if client.is_started {
+            let _ = client.writer.finish();
+        }
+    }
+}
+
+//Streams the built events over TCP as Arrow IPC record batches, for an online monitor on another
+//machine: every client that connects gets a stream of its own, starting with the next batch. The
+//events are batched on the build thread and written on a thread of their own, so a slow network
+//or client never holds up the build; when the network falls behind, batches are dropped.
+pub struct NetworkSink {
+    batch_events: usize,
+    columns: Vec<String>,
+    values: Vec<Vec<f64>>,
+    run_numbers: Vec<i32>,
+    sender: Option<SyncSender<DataFrame>>,
+    thread: Option<JoinHandle<()>>,
+    dropped_events: u64,
+}
+
+impl NetworkSink {
+    pub fn new(params: &NetworkSinkParams) -> Result<Self, EVBError> {
+        let address = params.get_address().map_err(EVBError::Config)?;
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        info!(
+            "Listening for clients of the built events on {}",
+            listener.local_addr()?
+        );
+        let (sender, batches) = sync_channel(QUEUED_BATCHES);
+        Ok(NetworkSink {
+            batch_events: params.batch_events.max(1),
+            columns: vec![],
+            values: vec![],
+            run_numbers: vec![],
+            sender: Some(sender),
+            thread: Some(std::thread::spawn(move || serve(listener, batches))),
+            dropped_events: 0,
+        })
+    }
+
+    fn send_batch(&mut self) -> Result<(), EVBError> {
+        if self.run_numbers.is_empty() {
+            return Ok(());
+        }
+        let mut series: Vec<Series> = self
+            .columns
+            .iter()
+            .zip(self.values.iter_mut())
+            .map(|(name, values)| Series::new(name, std::mem::take(values)))
+            .collect();
+        let events = self.run_numbers.len() as u64;
+        series.push(Series::new(
+            RUN_NUMBER_COLUMN,
+            std::mem::take(&mut self.run_numbers),
+        ));
+        let batch = DataFrame::new(series)?;
+        if let Some(sender) = &self.sender {
+            match sender.try_send(batch) {
+                Ok(()) | Err(TrySendError::Disconnected(_)) => (),
+                Err(TrySendError::Full(_)) => self.dropped_events += events,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for NetworkSink {
+    fn name(&self) -> &str {
+        "network"
+    }
+
+    fn start(&mut self, columns: &[String]) -> Result<(), EVBError> {
+        self.columns = columns.to_vec();
+        self.values = vec![vec![]; columns.len()];
+        Ok(())
+    }
+
+    fn receive(&mut self, run_number: i32, event: &[f64]) -> Result<(), EVBError> {
+        for (column, value) in self.values.iter_mut().zip(event.iter()) {
+            column.push(*value);
+        }
+        self.run_numbers.push(run_number);
+        if self.run_numbers.len() >= self.batch_events {
+            self.send_batch()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EVBError> {
+        self.send_batch()
+    }
+
+    fn finalize(&mut self) -> Result<(), EVBError> {
+        self.send_batch()?;
+        //Closing the channel ends the network thread once it has sent what is queued
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                return Err(EVBError::Sync);
+            }
+        }
+        if self.dropped_events > 0 {
+            emit_warning(
+                "network_output",
+                String::new(),
+                format!(
+                    "The network output fell behind and dropped {} events",
+                    format_count(self.dropped_events)
+                ),
+            );
+        }
+        Ok(())
+    }
+}
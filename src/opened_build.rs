@@ -0,0 +1,155 @@
+use std::path::{Path, PathBuf};
+
+use super::error::EVBError;
+use super::histogram::Histogram;
+use super::run_layout::RunLayout;
+use super::run_notes::{get_built_run, RunNotes};
+
+const BUILT: &str = "built";
+const RUN_NOTES: &str = "run_notes.yaml";
+
+//The built runs of another workspace, e.g. last night's runs copied from the DAQ machine, opened to
+//look at on the diagnostics tabs without building anything. Nothing is written into it.
+#[derive(Debug, Clone)]
+pub struct OpenedBuild {
+    dir: PathBuf,
+    //Sorted
+    runs: Vec<i32>,
+    //Of the workspace the build is in, if it is there
+    notes: RunNotes,
+}
+
+impl OpenedBuild {
+    //path is a built directory, the workspace it is in or a single run_<num> directory in it. The
+    //run is that of the run directory, if one was opened.
+    pub fn open(path: &Path) -> Result<(Self, Option<i32>), EVBError> {
+        let (dir, run) = match get_built_run(path) {
+            Some(run) => match path.parent() {
+                Some(parent) => (parent.to_path_buf(), Some(run)),
+                None => (path.to_path_buf(), Some(run)),
+            },
+            None if path.join(BUILT).is_dir() => (path.join(BUILT), None),
+            None => (path.to_path_buf(), None),
+        };
+        let mut runs: Vec<i32> = dir
+            .read_dir()?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| get_built_run(&entry.path()))
+            .collect();
+        runs.sort();
+        if runs.is_empty() {
+            return Err(EVBError::File(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} has no built runs", dir.display()),
+            )));
+        }
+        let notes = match dir.parent().map(|workspace| workspace.join(RUN_NOTES)) {
+            Some(notes_path) => RunNotes::read(&notes_path).unwrap_or_default(),
+            None => RunNotes::default(),
+        };
+        Ok((OpenedBuild { dir, runs, notes }, run))
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get_runs(&self) -> &[i32] {
+        &self.runs
+    }
+
+    pub fn get_notes(&self) -> &RunNotes {
+        &self.notes
+    }
+}
+
+//The spectra and rate tables of a built run: the CSV files of its spectra directory and those next
+//to its tables, sorted by name
+pub fn list_spectra_files(layout: &RunLayout) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = [layout.get_spectra_dir(), layout.get_dir().to_path_buf()]
+        .iter()
+        .filter_map(|dir| dir.read_dir().ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "csv"))
+        .collect();
+    files.sort_by_key(|path| path.file_name().map(|name| name.to_os_string()));
+    files
+}
+
+//The spectra files all have the edges of a bin in their first two columns (bin_low,bin_high or
+//time_low,time_high for the rates against time) and the counts of one spectrum in each column
+//after, so every column is read as a histogram of equal bins over the range of the file
+pub fn read_spectra_file(path: &Path) -> Result<Vec<Histogram>, EVBError> {
+    let text = std::fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    if header.len() < 3 {
+        return Err(EVBError::Csv(format!(
+            "{} is not a spectra file, it has no columns of counts",
+            path.display()
+        )));
+    }
+    let x_label = match header[0] {
+        "time_low" => "Time (s)",
+        _ => "Bin",
+    };
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+
+    let mut edges: Vec<f64> = vec![];
+    let mut columns: Vec<Vec<f64>> = vec![vec![]; header.len() - 2];
+    for (index, line) in lines.enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = line
+            .split(',')
+            .map(|value| match value.trim() {
+                "" => Ok(0.0),
+                value => value.parse::<f64>(),
+            })
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| EVBError::Csv(format!("{} line {}: {}", path.display(), index + 2, e)))?;
+        if values.len() != header.len() {
+            return Err(EVBError::Csv(format!(
+                "{} line {} has {} values for {} columns",
+                path.display(),
+                index + 2,
+                values.len(),
+                header.len()
+            )));
+        }
+        if edges.is_empty() {
+            edges.push(values[0]);
+        }
+        edges.push(values[1]);
+        for (column, value) in columns.iter_mut().zip(values.iter().skip(2)) {
+            column.push(*value);
+        }
+    }
+
+    let (min, max) = match (edges.first(), edges.last()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return Ok(vec![]),
+    };
+    Ok(header
+        .iter()
+        .skip(2)
+        .zip(columns)
+        .map(|(name, counts)| Histogram {
+            title: format!("{}: {}", stem, name),
+            x_label: x_label.to_string(),
+            y_label: if name.ends_with("rate") {
+                String::from("Rate (Hz)")
+            } else {
+                String::from("Counts")
+            },
+            min,
+            max,
+            counts,
+        })
+        .collect())
+}
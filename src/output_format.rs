@@ -0,0 +1,248 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use log::info;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::column_codec::ColumnCodec;
+use super::error::EVBError;
+use super::hdf5_writer::{get_hdf5_path, write_event_hdf5, Hdf5Params};
+use super::ipc_writer::{get_ipc_path, write_event_ipc};
+use super::parquet_writer::{write_event_parquet, ParquetMetadata};
+use super::root_writer::{get_root_path, write_event_root};
+use super::write_limit::{ThrottledWriter, WriteLimitParams};
+
+//File formats the events and delayed tables are written in, any combination of them. The ROOT
+//and HDF5 files are only written by builds with the root and hdf5 features.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputFormats {
+    pub parquet: bool,
+    pub root: bool,
+    pub hdf5: bool,
+    //Arrow IPC, also known as Feather v2
+    pub ipc: bool,
+}
+
+impl Default for OutputFormats {
+    fn default() -> Self {
+        OutputFormats {
+            parquet: true,
+            root: false,
+            hdf5: false,
+            ipc: false,
+        }
+    }
+}
+
+impl OutputFormats {
+    //The quick-look file and the hit table are always parquet only
+    pub fn parquet_only() -> Self {
+        OutputFormats::default()
+    }
+
+    //No files at all, for builds that keep their tables in memory
+    pub fn none() -> Self {
+        OutputFormats {
+            parquet: false,
+            root: false,
+            hdf5: false,
+            ipc: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.parquet && !self.root && !self.hdf5 && !self.ipc
+    }
+
+    //ROOT, HDF5 and Arrow IPC files are written whole at the end of a table
+    pub fn is_streamable(&self) -> bool {
+        !self.root && !self.hdf5 && !self.ipc
+    }
+
+    //The writers of the chosen formats, parquet first
+    pub fn get_writers(
+        &self,
+        parquet: ParquetOutput,
+        hdf5: &Hdf5Params,
+    ) -> Vec<Box<dyn OutputWriter>> {
+        let mut writers: Vec<Box<dyn OutputWriter>> = vec![];
+        if self.parquet {
+            writers.push(Box::new(parquet));
+        }
+        if self.root {
+            writers.push(Box::new(RootOutput));
+        }
+        if self.hdf5 {
+            writers.push(Box::new(Hdf5Output::new(hdf5)));
+        }
+        if self.ipc {
+            writers.push(Box::new(IpcOutput));
+        }
+        writers
+    }
+
+    //From the command line, e.g. "parquet,root"
+    pub fn parse_list(list: &str) -> Result<Self, String> {
+        let mut formats = OutputFormats::none();
+        for format in list.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+            match format.to_lowercase().as_str() {
+                "parquet" => formats.parquet = true,
+                "root" => formats.root = true,
+                "hdf5" | "h5" => formats.hdf5 = true,
+                "ipc" | "arrow" | "feather" => formats.ipc = true,
+                _ => {
+                    return Err(format!(
+                        "unknown output format '{}', use parquet, root, hdf5 or ipc",
+                        format
+                    ))
+                }
+            }
+        }
+        Ok(formats)
+    }
+}
+
+//Writes a whole table of built events in one format, e.g. the DataFrame taken from an EventStream.
+//path is where the parquet file of the table goes, the other formats are written next to it.
+pub trait OutputWriter {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError>;
+}
+
+//Row groups are only cut where the event column changes, see write_event_parquet
+#[derive(Debug, Clone, Default)]
+pub struct ParquetOutput {
+    event_column: String,
+    codecs: Vec<ColumnCodec>,
+    write_limit: WriteLimitParams,
+}
+
+impl ParquetOutput {
+    pub fn new(event_column: &str) -> Self {
+        ParquetOutput {
+            event_column: event_column.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_codecs(mut self, codecs: &[ColumnCodec]) -> Self {
+        self.codecs = codecs.to_vec();
+        self
+    }
+
+    pub fn with_write_limit(mut self, write_limit: &WriteLimitParams) -> Self {
+        self.write_limit = write_limit.clone();
+        self
+    }
+}
+
+impl OutputWriter for ParquetOutput {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError> {
+        info!("Writing dataframe to disk at {}", path.display());
+        let file = ThrottledWriter::new(File::create(path)?, &self.write_limit);
+        write_event_parquet(file, df, metadata, &self.event_column, &self.codecs)?;
+        Ok(())
+    }
+}
+
+//The metadata is only written to parquet
+#[derive(Debug, Clone, Default)]
+pub struct RootOutput;
+
+impl OutputWriter for RootOutput {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        _metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError> {
+        let root_path = get_root_path(path);
+        info!("Writing dataframe to disk at {}", root_path.display());
+        write_event_root(df, &root_path)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Hdf5Output {
+    params: Hdf5Params,
+}
+
+impl Hdf5Output {
+    pub fn new(params: &Hdf5Params) -> Self {
+        Hdf5Output {
+            params: params.clone(),
+        }
+    }
+}
+
+impl OutputWriter for Hdf5Output {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        _metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError> {
+        let hdf5_path = get_hdf5_path(path);
+        info!("Writing dataframe to disk at {}", hdf5_path.display());
+        write_event_hdf5(df, &hdf5_path, &self.params)
+    }
+}
+
+//The metadata is only written to parquet
+#[derive(Debug, Clone, Default)]
+pub struct IpcOutput;
+
+impl OutputWriter for IpcOutput {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        _metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError> {
+        let ipc_path = get_ipc_path(path);
+        info!("Writing dataframe to disk at {}", ipc_path.display());
+        write_event_ipc(df, &ipc_path)
+    }
+}
+
+//Keeps the tables in memory instead of writing them, for builds that hand their events back to
+//the program that started them (see build_run_dataframe). Clones share the tables.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryOutput {
+    tables: Arc<Mutex<Vec<(PathBuf, DataFrame)>>>,
+}
+
+impl MemoryOutput {
+    //In the order they were written, each with the path its parquet file would have
+    pub fn take_tables(&self) -> Result<Vec<(PathBuf, DataFrame)>, EVBError> {
+        let mut tables = self.tables.lock().map_err(|_| EVBError::Sync)?;
+        Ok(std::mem::take(&mut *tables))
+    }
+}
+
+impl OutputWriter for MemoryOutput {
+    fn write_table(
+        &self,
+        df: &mut DataFrame,
+        path: &Path,
+        _metadata: &ParquetMetadata,
+    ) -> Result<(), EVBError> {
+        self.tables
+            .lock()
+            .map_err(|_| EVBError::Sync)?
+            .push((path.to_path_buf(), df.clone()));
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::run_layout::RunLayout;
+
+//Every parquet file starts and ends with this, the end only once the footer has been written
+const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+
+//A run directory left behind by a build that did not finish: the report is written last, so a run
+//without one was aborted, and a parquet file without its footer cannot be read at all. A run
+//stopped by SIGINT or SIGTERM is complete on disk, but its report marks it partial.
+#[derive(Debug, Clone)]
+pub struct IncompleteRun {
+    pub run_number: i32,
+    pub dir: PathBuf,
+    pub problems: Vec<String>,
+    //Built with checkpoints, so building it again picks up from the last one
+    pub has_checkpoint: bool,
+}
+
+impl IncompleteRun {
+    pub fn describe_problems(&self) -> String {
+        self.problems.join(", ")
+    }
+
+    //Stale fragments of the aborted build would otherwise sit next to the tables of a rebuild
+    pub fn clean(&self) -> std::io::Result<()> {
+        std::fs::remove_dir_all(&self.dir)
+    }
+}
+
+fn has_parquet_footer(path: &Path) -> std::io::Result<bool> {
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < 2 * PARQUET_MAGIC.len() as u64 {
+        return Ok(false);
+    }
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != PARQUET_MAGIC {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::End(-(PARQUET_MAGIC.len() as i64)))?;
+    file.read_exact(&mut magic)?;
+    Ok(&magic == PARQUET_MAGIC)
+}
+
+fn is_partial(report_path: &Path) -> bool {
+    std::fs::read_to_string(report_path)
+        .ok()
+        .and_then(|report| serde_json::from_str::<serde_json::Value>(&report).ok())
+        .and_then(|report| report.get("partial").and_then(|partial| partial.as_bool()))
+        .unwrap_or(false)
+}
+
+fn check_run(output_dir: &Path, run_number: i32) -> Option<IncompleteRun> {
+    let layout = RunLayout::new(output_dir, run_number);
+    let mut problems = vec![];
+    if !layout.get_report_path().exists() {
+        problems.push(String::from("no report.json"));
+    } else if is_partial(&layout.get_report_path()) {
+        problems.push(String::from("build was interrupted"));
+    }
+    let mut files = layout.list_files().unwrap_or_default();
+    files.retain(|(path, _)| path.extension().is_some_and(|e| e == "parquet"));
+    for (path, _) in files.iter() {
+        if !has_parquet_footer(&layout.get_dir().join(path)).unwrap_or(false) {
+            problems.push(format!("{} is unreadable", path.display()));
+        }
+    }
+    if problems.is_empty() {
+        None
+    } else {
+        Some(IncompleteRun {
+            run_number,
+            dir: layout.get_dir().to_path_buf(),
+            problems,
+            has_checkpoint: layout.get_checkpoint_path().exists(),
+        })
+    }
+}
+
+//Run directories of the output directory with a missing report or an unfinished parquet file,
+//sorted by run number. Only meaningful while nothing is being built into the directory.
+pub fn find_incomplete_runs(output_dir: &Path) -> Vec<IncompleteRun> {
+    let entries = match output_dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut runs: Vec<IncompleteRun> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("run_"))
+                .and_then(|number| number.parse::<i32>().ok())
+        })
+        .filter_map(|run_number| check_run(output_dir, run_number))
+        .collect();
+    runs.sort_by_key(|run| run.run_number);
+    runs
+}
@@ -0,0 +1,139 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_data::{ChannelData, INVALID_VALUE};
+use super::error::EVBError;
+
+//Receives every built event as soon as it is built, for consumers that cannot wait for the tables
+//of a run to be written, e.g. an online histogrammer. The sinks of a job are started once and
+//outlive its runs, so a consumer only has to attach once.
+//
+//The shared memory ring buffer and the network stream are sinks. An experiment's own binary can
+//add its own with `EVBApp::with_output_sink`, as with post batch hooks.
+pub trait OutputSink: Send {
+    fn name(&self) -> &str;
+
+    //Before the first event of the job, with the names of the columns in the order of the values
+    //of every event
+    fn start(&mut self, columns: &[String]) -> Result<(), EVBError>;
+
+    //One built event, a value per column, with the invalid values of the output in place of
+    //missing ones
+    fn receive(&mut self, run_number: i32, event: &[f64]) -> Result<(), EVBError>;
+
+    //At the end of every run, built or not, so no events are held back between runs
+    fn flush(&mut self) -> Result<(), EVBError>;
+
+    //Once the job is done, the last call the sink gets
+    fn finalize(&mut self) -> Result<(), EVBError>;
+}
+
+impl std::fmt::Debug for dyn OutputSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OutputSink({})", self.name())
+    }
+}
+
+//Shared between the GUI, which owns the sinks, and the processing thread
+pub type OutputSinks = Arc<Mutex<Vec<Box<dyn OutputSink>>>>;
+
+//The sinks of a job: those of the app, followed by those of the config, which are taken away
+//again when the job is done, however it ends. Held for the whole job, the GUI never touches the
+//sinks while the job runs.
+pub struct JobSinks<'a> {
+    sinks: MutexGuard<'a, Vec<Box<dyn OutputSink>>>,
+    app_sinks: usize,
+}
+
+impl<'a> JobSinks<'a> {
+    pub fn new(
+        sinks: &'a OutputSinks,
+        config_sinks: Vec<Box<dyn OutputSink>>,
+        columns: &[String],
+    ) -> Result<Self, EVBError> {
+        let sinks = sinks.lock().map_err(|_| EVBError::Sync)?;
+        let mut job_sinks = JobSinks {
+            app_sinks: sinks.len(),
+            sinks,
+        };
+        job_sinks.sinks.extend(config_sinks);
+        for sink in job_sinks.sinks.iter_mut() {
+            sink.start(columns)?;
+        }
+        Ok(job_sinks)
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [Box<dyn OutputSink>] {
+        self.sinks.as_mut_slice()
+    }
+
+    //After every run. A sink that fails is reported and the job goes on.
+    pub fn flush(&mut self) {
+        for sink in self.sinks.iter_mut() {
+            if let Err(x) = sink.flush() {
+                emit_warning(
+                    "output_sink",
+                    String::new(),
+                    format!("Unable to flush the {} output: {}", sink.name(), x),
+                );
+            }
+        }
+    }
+
+    pub fn finalize(&mut self) {
+        for sink in self.sinks.iter_mut() {
+            if let Err(x) = sink.finalize() {
+                emit_warning(
+                    "output_sink",
+                    String::new(),
+                    format!("Unable to finish the {} output: {}", sink.name(), x),
+                );
+            }
+        }
+    }
+}
+
+impl Drop for JobSinks<'_> {
+    fn drop(&mut self) {
+        let app_sinks = self.app_sinks;
+        self.sinks.truncate(app_sinks);
+    }
+}
+
+//Hands the event just appended to a table, its last row, to every sink
+#[derive(Debug, Clone)]
+pub struct SinkFeed {
+    //Written in place of INVALID_VALUE, per column
+    invalid_values: Vec<f64>,
+    event: Vec<f64>,
+}
+
+impl SinkFeed {
+    pub fn new(template: &ChannelData) -> Self {
+        SinkFeed {
+            invalid_values: template.get_invalid_values(),
+            event: vec![],
+        }
+    }
+
+    pub fn send(
+        &mut self,
+        sinks: &mut [Box<dyn OutputSink>],
+        run_number: i32,
+        data: &ChannelData,
+    ) -> Result<(), EVBError> {
+        self.event.clear();
+        for ((_, column), invalid) in data.iter_columns().zip(self.invalid_values.iter()) {
+            let value = column.last().copied().unwrap_or(INVALID_VALUE);
+            self.event.push(if value == INVALID_VALUE {
+                *invalid
+            } else {
+                value
+            });
+        }
+        for sink in sinks.iter_mut() {
+            sink.receive(run_number, &self.event)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use polars::prelude::*;
+
+use super::parquet_writer::ParquetMetadata;
+
+//Rows of the event tables are written in build order unless sorted by one of their columns, e.g.
+//for merges that need the events in anode time order. Each table file (or piece of a streamed
+//table) is sorted on its own, and rows with the invalid value in the column go last.
+#[derive(Debug, Clone, Copy)]
+pub struct RowOrder<'a> {
+    column: Option<&'a str>,
+}
+
+impl<'a> RowOrder<'a> {
+    pub fn new(column: &'a str) -> Self {
+        RowOrder {
+            column: if column.is_empty() {
+                None
+            } else {
+                Some(column)
+            },
+        }
+    }
+
+    pub fn get_column(&self) -> Option<&'a str> {
+        self.column
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        if let Some(column) = self.column {
+            metadata.insert_file("sort_column", column.to_string());
+        }
+    }
+
+    //Tables without the column (e.g. no EventID without a delayed window) keep the build order.
+    //The sort is stable, so rows with the same value stay in build order. invalid is the invalid
+    //value written in the column.
+    pub fn apply(&self, df: DataFrame, invalid: f64) -> Result<DataFrame, PolarsError> {
+        let column = match self.column {
+            Some(column) if df.get_column_names().contains(&column) => column,
+            _ => return Ok(df),
+        };
+        let values = df.column(column)?.cast(&DataType::Float64)?;
+        let keys: Vec<f64> = values
+            .f64()?
+            .into_iter()
+            .map(|value| match value {
+                Some(value) if value != invalid && !value.is_nan() => value,
+                _ => f64::INFINITY,
+            })
+            .collect();
+        let mut order: Vec<IdxSize> = (0..keys.len() as IdxSize).collect();
+        order.sort_by(|a, b| keys[*a as usize].total_cmp(&keys[*b as usize]));
+        df.take(&IdxCa::from_vec("", order))
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::TimeUnit;
+use super::channel_map::{ChannelGroup, ChannelMap};
+use super::compass_data::{generate_board_channel_uuid, CompassData};
+
+//Coincidence window between the hits of two channel groups, in either order, e.g. CeBrA and SPS
+//with the flight time through the spectrograph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PairWindowEntry {
+    pub first: ChannelGroup,
+    pub second: ChannelGroup,
+    pub window: f64,
+    #[serde(default)]
+    pub unit: TimeUnit,
+}
+
+impl Default for PairWindowEntry {
+    fn default() -> Self {
+        PairWindowEntry {
+            first: ChannelGroup::Cebra,
+            second: ChannelGroup::Sps,
+            window: 3.0,
+            unit: TimeUnit::Microseconds,
+        }
+    }
+}
+
+impl PairWindowEntry {
+    pub fn is_pair(&self, first: ChannelGroup, second: ChannelGroup) -> bool {
+        (self.first == first && self.second == second)
+            || (self.first == second && self.second == first)
+    }
+
+    pub fn get_window_ns(&self) -> f64 {
+        self.unit.get_nanoseconds(self.window)
+    }
+}
+
+//The window of every pair of channel groups, the coincidence window for pairs without an entry.
+//Hits of channels not in the map are in the Unmapped group.
+#[derive(Debug, Clone)]
+pub struct PairWindows {
+    entries: Vec<PairWindowEntry>,
+    default_window: f64,
+    groups: HashMap<u32, ChannelGroup>,
+    //Longest window of each group with any other, how long a window opened by one of its hits stays
+    //open
+    longest: HashMap<ChannelGroup, f64>,
+}
+
+impl PairWindows {
+    pub fn new(entries: &[PairWindowEntry], map: &ChannelMap, coincidence_window: f64) -> Self {
+        let groups: HashMap<u32, ChannelGroup> = map
+            .get_detector_channels()
+            .into_iter()
+            .flat_map(|detector| {
+                let group = detector.detector.get_group();
+                detector.channels.into_iter().map(move |mapped| {
+                    (
+                        generate_board_channel_uuid(&mapped.board, &mapped.channel),
+                        group,
+                    )
+                })
+            })
+            .collect();
+        let mut longest = HashMap::new();
+        for entry in entries.iter() {
+            for group in [entry.first, entry.second] {
+                let window = longest.entry(group).or_insert(coincidence_window);
+                *window = f64::max(*window, entry.get_window_ns());
+            }
+        }
+        PairWindows {
+            entries: entries.to_vec(),
+            default_window: coincidence_window,
+            groups,
+            longest,
+        }
+    }
+
+    fn get_group(&self, hit: &CompassData) -> ChannelGroup {
+        self.groups
+            .get(&hit.uuid)
+            .copied()
+            .unwrap_or(ChannelGroup::Unmapped)
+    }
+
+    //Hits further than this after the first hit of an event are never in it
+    pub fn get_open_window(&self, first: &CompassData) -> f64 {
+        self.longest
+            .get(&self.get_group(first))
+            .copied()
+            .unwrap_or(self.default_window)
+    }
+
+    pub fn get_longest_window(&self) -> f64 {
+        self.longest
+            .values()
+            .copied()
+            .fold(self.default_window, f64::max)
+    }
+
+    //Whether hit is in the window of its pair with the first hit of an event
+    pub fn is_coincident(&self, first: &CompassData, hit: &CompassData) -> bool {
+        let (first_group, group) = (self.get_group(first), self.get_group(hit));
+        let window = self
+            .entries
+            .iter()
+            .find(|entry| entry.is_pair(first_group, group))
+            .map_or(self.default_window, |entry| entry.get_window_ns());
+        hit.timestamp - first.timestamp < window
+    }
+
+    //e.g. "CeBrA-SPS 3000 ns, CeBrA-CeBrA 50 ns", for the metadata of the tables
+    pub fn describe(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}-{} {} ns",
+                    entry.first.as_ref(),
+                    entry.second.as_ref(),
+                    entry.get_window_ns()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
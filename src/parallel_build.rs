@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use super::build_diagnostics::join_diagnostics;
+use super::channel_data::ChannelData;
+use super::channel_map::ChannelMap;
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::event_builder::{EventBuilder, EventBuilderStats};
+use super::run_log::join_run_log;
+use super::used_size::UsedSize;
+
+//Hits of a chunk before it may be cut, at the next gap no event can span
+const CHUNK_HITS: usize = 65536;
+//Chunks handed out ahead of the build thread per builder thread, bounding the memory of the
+//chunks waiting for their turn
+const CHUNKS_AHEAD: usize = 2;
+
+//The build_threads option, 0 for one thread per core, capped by the thread limit of the build.
+//One builds the events on the build thread.
+pub fn get_builder_threads(build_threads: usize, max_threads: usize) -> usize {
+    match build_threads {
+        0 => max_threads,
+        threads => threads.min(max_threads),
+    }
+}
+
+struct Chunk {
+    index: usize,
+    hits: Vec<CompassData>,
+    //The last chunk of a run (or of a run in a concatenated file) leaves its last window open, as
+    //a single builder would at the end of the run
+    is_run_end: bool,
+}
+
+//The events of one chunk, filled into a table of their own
+pub struct BuiltChunk {
+    pub events: Vec<Vec<CompassData>>,
+    //Whether each event passed the event filters of the table, and so has a row in it
+    pub accepted: Vec<bool>,
+    pub table: ChannelData,
+    stats: EventBuilderStats,
+    trigger_hits: u64,
+}
+
+fn build_chunk(
+    chunk: Chunk,
+    prototype: &EventBuilder,
+    template: &ChannelData,
+    map: &ChannelMap,
+) -> BuiltChunk {
+    let mut evb = prototype.clone();
+    let mut built = BuiltChunk {
+        events: vec![],
+        accepted: vec![],
+        table: template.clone(),
+        stats: EventBuilderStats::default(),
+        trigger_hits: 0,
+    };
+    let mut add_ready_event = |evb: &mut EventBuilder| {
+        while evb.is_event_ready() {
+            let event = evb.get_ready_event();
+            built.accepted.push(built.table.append_event(&event, map));
+            built.events.push(event);
+        }
+    };
+    for hit in chunk.hits.iter() {
+        evb.push_hit(hit);
+        add_ready_event(&mut evb);
+    }
+    if !chunk.is_run_end {
+        evb.close_chunk();
+        add_ready_event(&mut evb);
+    }
+    built.stats = evb.get_stats();
+    built.trigger_hits = evb.get_trigger_hits();
+    built
+}
+
+fn run_builder(
+    chunks: Arc<Mutex<Receiver<Chunk>>>,
+    results: Sender<(usize, BuiltChunk)>,
+    prototype: EventBuilder,
+    template: ChannelData,
+    map: ChannelMap,
+) {
+    join_run_log();
+    join_diagnostics();
+    loop {
+        let chunk = match chunks.lock() {
+            Ok(receiver) => match receiver.recv() {
+                Ok(chunk) => chunk,
+                Err(_) => return,
+            },
+            Err(_) => return,
+        };
+        let index = chunk.index;
+        if results
+            .send((index, build_chunk(chunk, &prototype, &template, &map)))
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+//Builds the events on several threads. The hits going into the event builder are cut into chunks
+//of about CHUNK_HITS hits, each built into events and filled into a table on a builder thread. A
+//chunk is only cut where the next hit is at least the longest event span past every hit before it:
+//the window open there is complete, and the builder is in the same state as one that has just been
+//made, so each chunk can start with a new builder and the events are exactly those of a single
+//builder. The chunks are handed back in order, a hit stream without such gaps only makes them
+//longer.
+pub struct ChunkBuilder {
+    chunk_sender: Option<Sender<Chunk>>,
+    results: Receiver<(usize, BuiltChunk)>,
+    handles: Vec<JoinHandle<()>>,
+    max_event_span: f64,
+    hits: Vec<CompassData>,
+    //Latest timestamp of the chunk being collected
+    last_time: Option<f64>,
+    chunks_sent: usize,
+    chunks_taken: usize,
+    max_chunks_ahead: usize,
+    //Chunks built out of order, waiting for the ones before them
+    built: BTreeMap<usize, BuiltChunk>,
+    stats: EventBuilderStats,
+    trigger_hits: u64,
+}
+
+impl ChunkBuilder {
+    //prototype is the builder as set up for the run, before any hits. Every chunk starts with a
+    //copy of it, and of the empty template table.
+    pub fn new(
+        threads: usize,
+        prototype: &EventBuilder,
+        template: &ChannelData,
+        map: &ChannelMap,
+    ) -> Self {
+        let (chunk_sender, chunk_receiver) = channel();
+        let (result_sender, results) = channel();
+        let chunk_receiver = Arc::new(Mutex::new(chunk_receiver));
+        let handles = (0..threads.max(1))
+            .map(|_| {
+                let chunks = chunk_receiver.clone();
+                let results = result_sender.clone();
+                let prototype = prototype.clone();
+                let template = template.clone();
+                let map = map.clone();
+                std::thread::spawn(move || run_builder(chunks, results, prototype, template, map))
+            })
+            .collect();
+        ChunkBuilder {
+            chunk_sender: Some(chunk_sender),
+            results,
+            handles,
+            max_event_span: prototype.get_max_event_span(),
+            hits: vec![],
+            last_time: None,
+            chunks_sent: 0,
+            chunks_taken: 0,
+            max_chunks_ahead: threads.max(1) * CHUNKS_AHEAD,
+            built: BTreeMap::new(),
+            stats: EventBuilderStats::default(),
+            trigger_hits: 0,
+        }
+    }
+
+    pub fn push_hit(&mut self, hit: &CompassData) -> Result<(), EVBError> {
+        let is_cut = self.hits.len() >= CHUNK_HITS
+            && self
+                .last_time
+                .is_some_and(|last_time| hit.timestamp - last_time >= self.max_event_span);
+        if is_cut {
+            self.send_chunk(false)?;
+        }
+        self.last_time = Some(
+            self.last_time
+                .map_or(hit.timestamp, |t| t.max(hit.timestamp)),
+        );
+        self.hits.push(hit.clone());
+        Ok(())
+    }
+
+    fn send_chunk(&mut self, is_run_end: bool) -> Result<(), EVBError> {
+        let chunk = Chunk {
+            index: self.chunks_sent,
+            hits: std::mem::take(&mut self.hits),
+            is_run_end,
+        };
+        self.last_time = None;
+        self.chunk_sender
+            .as_ref()
+            .ok_or(EVBError::Sync)?
+            .send(chunk)
+            .map_err(|_| EVBError::Sync)?;
+        self.chunks_sent += 1;
+        Ok(())
+    }
+
+    //The timestamps start over at a run boundary, the open window of the run is dropped there
+    pub fn end_run_segment(&mut self) -> Result<(), EVBError> {
+        self.send_chunk(true)
+    }
+
+    //The next chunks in order that are built. With too many chunks handed out, waits for the
+    //oldest one, so the builder threads never run far ahead of the build thread.
+    pub fn take_built(&mut self) -> Result<Vec<BuiltChunk>, EVBError> {
+        while let Ok((index, chunk)) = self.results.try_recv() {
+            self.built.insert(index, chunk);
+        }
+        if self.chunks_sent - self.chunks_taken >= self.max_chunks_ahead {
+            self.wait_for(self.chunks_taken)?;
+        }
+        Ok(self.take_in_order())
+    }
+
+    //Every chunk handed out so far, e.g. before the tables are written at a run boundary
+    pub fn take_all(&mut self) -> Result<Vec<BuiltChunk>, EVBError> {
+        if self.chunks_sent > 0 {
+            self.wait_for(self.chunks_sent - 1)?;
+        }
+        let chunks = self.take_in_order();
+        if self.chunks_taken < self.chunks_sent {
+            return Err(EVBError::Sync);
+        }
+        Ok(chunks)
+    }
+
+    fn wait_for(&mut self, index: usize) -> Result<(), EVBError> {
+        while (self.chunks_taken..=index).any(|wanted| !self.built.contains_key(&wanted)) {
+            let (built_index, chunk) = self.results.recv().map_err(|_| EVBError::Sync)?;
+            self.built.insert(built_index, chunk);
+        }
+        Ok(())
+    }
+
+    fn take_in_order(&mut self) -> Vec<BuiltChunk> {
+        let mut chunks = vec![];
+        while let Some(chunk) = self.built.remove(&self.chunks_taken) {
+            self.stats.add(&chunk.stats);
+            self.trigger_hits += chunk.trigger_hits;
+            self.chunks_taken += 1;
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    //The hits left are the last chunk of the run, its last window is left open
+    pub fn finish(&mut self) -> Result<Vec<BuiltChunk>, EVBError> {
+        if !self.hits.is_empty() {
+            self.send_chunk(true)?;
+        }
+        let chunks = self.take_all()?;
+        self.chunk_sender = None;
+        for handle in self.handles.drain(..) {
+            handle.join().map_err(|_| EVBError::Sync)?;
+        }
+        Ok(chunks)
+    }
+
+    //Of the chunks taken so far
+    pub fn get_stats(&self) -> EventBuilderStats {
+        self.stats.clone()
+    }
+
+    pub fn get_trigger_hits(&self) -> u64 {
+        self.trigger_hits
+    }
+}
+
+impl Drop for ChunkBuilder {
+    fn drop(&mut self) {
+        self.chunk_sender = None;
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl UsedSize for ChunkBuilder {
+    fn get_used_size(&self) -> usize {
+        self.hits.capacity() * std::mem::size_of::<CompassData>()
+            + self
+                .built
+                .values()
+                .map(|chunk| {
+                    chunk.table.get_used_size()
+                        + chunk
+                            .events
+                            .iter()
+                            .map(|event| event.get_used_size())
+                            .sum::<usize>()
+                })
+                .sum::<usize>()
+    }
+}
@@ -0,0 +1,115 @@
+use log::info;
+
+use super::channel_data::ChannelData;
+use super::channel_map::ChannelMap;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::error::EVBError;
+use super::formatting::format_count;
+
+//Expensive consistency checks for validating a new configuration on a small run (--paranoid).
+//Any failure stops the run, pointing at the first hit/event that broke the assumption.
+#[derive(Debug)]
+pub struct ParanoidChecker {
+    last_hit_time: f64,
+    last_event_time: f64,
+    hits: u64,
+    events: u64,
+}
+
+impl ParanoidChecker {
+    pub fn new() -> Self {
+        ParanoidChecker {
+            last_hit_time: f64::NEG_INFINITY,
+            last_event_time: f64::NEG_INFINITY,
+            hits: 0,
+            events: 0,
+        }
+    }
+
+    //Every hit going into the builder is mapped, has finite values, and comes in time order
+    pub fn check_hit(&mut self, hit: &CompassData, map: &ChannelMap) -> Result<(), EVBError> {
+        self.hits += 1;
+        let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+        if map.get_channel_data(&hit.uuid).is_none() {
+            return Err(EVBError::Paranoid(format!(
+                "hit {} (board {} channel {}) is not in the channel map",
+                self.hits, board, channel
+            )));
+        }
+        if !(hit.timestamp.is_finite() && hit.energy.is_finite() && hit.energy_short.is_finite()) {
+            return Err(EVBError::Paranoid(format!(
+                "hit {} (board {} channel {}) has a non-finite value: {:?}",
+                self.hits, board, channel, hit
+            )));
+        }
+        if hit.timestamp < self.last_hit_time {
+            return Err(EVBError::Paranoid(format!(
+                "hit {} (board {} channel {}) at {} ns is earlier than the previous hit at {} ns",
+                self.hits, board, channel, hit.timestamp, self.last_hit_time
+            )));
+        }
+        self.last_hit_time = hit.timestamp;
+        Ok(())
+    }
+
+    //Events come out in order of their start time, and each one fits in the coincidence window
+    pub fn check_event(&mut self, event: &[CompassData], window: f64) -> Result<(), EVBError> {
+        self.events += 1;
+        let start = match event.first() {
+            Some(hit) => hit.timestamp,
+            None => {
+                return Err(EVBError::Paranoid(format!(
+                    "event {} has no hits",
+                    self.events
+                )))
+            }
+        };
+        if start < self.last_event_time {
+            return Err(EVBError::Paranoid(format!(
+                "event {} starts at {} ns, before the previous event at {} ns",
+                self.events, start, self.last_event_time
+            )));
+        }
+        if let Some(hit) = event.iter().find(|hit| hit.timestamp - start >= window) {
+            return Err(EVBError::Paranoid(format!(
+                "event {} has a hit {} ns after its start, outside the {} ns window",
+                self.events,
+                hit.timestamp - start,
+                window
+            )));
+        }
+        self.last_event_time = start;
+        Ok(())
+    }
+
+    //Time starts over with every run of a concatenated file
+    pub fn end_run_segment(&mut self) {
+        self.last_hit_time = f64::NEG_INFINITY;
+        self.last_event_time = f64::NEG_INFINITY;
+    }
+
+    //Every column has one value per event
+    pub fn check_columns(&self, data: &ChannelData) -> Result<(), EVBError> {
+        match data
+            .iter_columns()
+            .find(|(_, column)| column.len() != data.rows)
+        {
+            Some((name, column)) => Err(EVBError::Paranoid(format!(
+                "column {} has {} values after event {}, expected {}",
+                name,
+                column.len(),
+                self.events,
+                data.rows
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn log_summary(&self) {
+        info!(
+            "Paranoid checks passed for {} hits and {} events",
+            format_count(self.hits),
+            format_count(self.events)
+        );
+    }
+}
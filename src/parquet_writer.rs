@@ -0,0 +1,358 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use polars::prelude::*;
+use polars_arrow::array::Array;
+use polars_arrow::datatypes::ArrowSchema;
+use polars_parquet::write::{
+    array_to_columns, compress, to_parquet_schema, transverse, CompressedPage, CompressionOptions,
+    DynIter, DynStreamingIterator, Encoding, FallibleStreamingIterator, FileWriter, KeyValue,
+    ParquetType, Version, WriteOptions,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::column_codec::{find_codec, ColumnCodec};
+use super::resource_limits::run_on_encode_pool;
+
+//Same default as polars' own ParquetWriter
+const DEFAULT_ROW_GROUP_SIZE: usize = 512 * 512;
+
+//polars' ParquetWriter has no way to attach key-value metadata, so we drive the underlying
+//polars-parquet FileWriter ourselves. Column metadata is stored in the embedded arrow schema,
+//which pyarrow/polars expose as field metadata when reading the file back.
+#[derive(Debug, Clone, Default)]
+pub struct ParquetMetadata {
+    pub file: BTreeMap<String, String>,
+    pub columns: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl ParquetMetadata {
+    pub fn insert_file(&mut self, key: &str, value: String) {
+        self.file.insert(key.to_string(), value);
+    }
+
+    pub fn insert_column(&mut self, column: &str, key: &str, value: String) {
+        self.columns
+            .entry(column.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+    }
+
+    pub fn extend(&mut self, other: &ParquetMetadata) {
+        self.file
+            .extend(other.file.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for (column, entries) in other.columns.iter() {
+            self.columns
+                .entry(column.clone())
+                .or_default()
+                .extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+}
+
+fn get_row_bounds(height: usize) -> Vec<usize> {
+    (DEFAULT_ROW_GROUP_SIZE..height + DEFAULT_ROW_GROUP_SIZE)
+        .step_by(DEFAULT_ROW_GROUP_SIZE)
+        .map(|end| end.min(height))
+        .collect()
+}
+
+//Row groups are only cut where the event changes, so every row group holds whole events and can
+//be processed on its own. Each row is normally one event; if a post batch hook spreads events over
+//several rows, rows with the same value in event_column are kept together.
+fn get_event_bounds(df: &DataFrame, event_column: &str) -> PolarsResult<Vec<usize>> {
+    if !df.get_column_names().contains(&event_column) {
+        return Ok(get_row_bounds(df.height()));
+    }
+    let ids = df.column(event_column)?.f64()?;
+    let height = df.height();
+    let mut bounds = vec![];
+    let mut end: usize = 0;
+    while end < height {
+        end = (end + DEFAULT_ROW_GROUP_SIZE).min(height);
+        while end < height && ids.get(end) == ids.get(end - 1) {
+            end += 1;
+        }
+        bounds.push(end);
+    }
+    Ok(bounds)
+}
+
+pub fn write_parquet<W: Write>(
+    writer: W,
+    df: &mut DataFrame,
+    metadata: &ParquetMetadata,
+    codecs: &[ColumnCodec],
+) -> PolarsResult<u64> {
+    let bounds = get_row_bounds(df.height());
+    write_row_groups(writer, df, metadata, &bounds, codecs)
+}
+
+//Writes a table of built events, recording the row group guarantee in the file metadata
+pub fn write_event_parquet<W: Write>(
+    writer: W,
+    df: &mut DataFrame,
+    metadata: &ParquetMetadata,
+    event_column: &str,
+    codecs: &[ColumnCodec],
+) -> PolarsResult<u64> {
+    let mut stream = EventParquetStream::new(writer, metadata, event_column, codecs);
+    stream.write_piece(df)?;
+    stream.finish()
+}
+
+//How many rows of events are built before they are written as one piece of a streamed table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamingParams {
+    pub enabled: bool,
+    pub chunk_rows: usize,
+}
+
+impl Default for StreamingParams {
+    fn default() -> Self {
+        StreamingParams {
+            enabled: false,
+            chunk_rows: DEFAULT_ROW_GROUP_SIZE,
+        }
+    }
+}
+
+//Writes a table of built events piece by piece while the run is built, so only the piece being
+//filled is held in memory. The columns and their metadata are fixed by the first piece. Each piece
+//is cut into row groups on its own and holds whole events, so the row groups stay event aligned.
+pub struct EventParquetStream<W: Write> {
+    writer: Option<W>,
+    table: Option<TableWriter<W>>,
+    metadata: ParquetMetadata,
+    event_column: String,
+    codecs: Vec<ColumnCodec>,
+}
+
+impl<W: Write> EventParquetStream<W> {
+    pub fn new(
+        writer: W,
+        metadata: &ParquetMetadata,
+        event_column: &str,
+        codecs: &[ColumnCodec],
+    ) -> Self {
+        EventParquetStream {
+            writer: Some(writer),
+            table: None,
+            metadata: metadata.clone(),
+            event_column: event_column.to_string(),
+            codecs: codecs.to_vec(),
+        }
+    }
+
+    pub fn write_piece(&mut self, df: &mut DataFrame) -> PolarsResult<()> {
+        let bounds = get_event_bounds(df, &self.event_column)?;
+        if let Some(writer) = self.writer.take() {
+            let event_key = if df.get_column_names().contains(&self.event_column.as_str()) {
+                self.event_column.clone()
+            } else {
+                String::from("row")
+            };
+            self.metadata
+                .insert_file("event_aligned_row_groups", String::from("true"));
+            self.metadata.insert_file("event_key", event_key);
+            self.metadata
+                .insert_file("row_group_size", DEFAULT_ROW_GROUP_SIZE.to_string());
+            self.table = Some(TableWriter::new(writer, df, &self.metadata, &self.codecs)?);
+        }
+        match &mut self.table {
+            Some(table) => table.write_row_groups(df, &bounds),
+            None => Err(PolarsError::ComputeError(
+                "parquet stream has no file".into(),
+            )),
+        }
+    }
+
+    //File metadata only known once the table is complete, written with the rest at the end
+    pub fn insert_file_metadata(&mut self, metadata: &ParquetMetadata) {
+        self.metadata.file.extend(
+            metadata
+                .file
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+
+    //A stream that was never written to has no columns, and is an error
+    pub fn finish(self) -> PolarsResult<u64> {
+        match self.table {
+            Some(table) => table.end(&self.metadata),
+            None => Err(PolarsError::NoData(
+                "no rows were written to the parquet stream".into(),
+            )),
+        }
+    }
+}
+
+//Pages of one column chunk, already compressed, handed to the FileWriter
+struct CompressedPages {
+    pages: std::vec::IntoIter<CompressedPage>,
+    current: Option<CompressedPage>,
+}
+
+impl FallibleStreamingIterator for CompressedPages {
+    type Item = CompressedPage;
+    type Error = PolarsError;
+
+    fn advance(&mut self) -> Result<(), Self::Error> {
+        self.current = self.pages.next();
+        Ok(())
+    }
+
+    fn get(&self) -> Option<&Self::Item> {
+        self.current.as_ref()
+    }
+}
+
+fn compress_column(
+    array: &dyn Array,
+    parquet_type: &ParquetType,
+    options: WriteOptions,
+    encoding: &[Encoding],
+) -> PolarsResult<Vec<Vec<CompressedPage>>> {
+    array_to_columns(array, parquet_type.clone(), options, encoding)?
+        .into_iter()
+        .map(|pages| {
+            pages
+                .map(|page| Ok(compress(page?, vec![], options.compression)?))
+                .collect::<PolarsResult<Vec<_>>>()
+        })
+        .collect()
+}
+
+//bounds are the (exclusive) last row of each row group
+fn write_row_groups<W: Write>(
+    writer: W,
+    df: &mut DataFrame,
+    metadata: &ParquetMetadata,
+    bounds: &[usize],
+    codecs: &[ColumnCodec],
+) -> PolarsResult<u64> {
+    let mut table = TableWriter::new(writer, df, metadata, codecs)?;
+    table.write_row_groups(df, bounds)?;
+    table.end(metadata)
+}
+
+//The open file with the encoding of every column, set up from the schema of the first DataFrame
+struct TableWriter<W: Write> {
+    file_writer: FileWriter<W>,
+    column_names: Vec<String>,
+    parquet_fields: Vec<ParquetType>,
+    column_settings: Vec<(WriteOptions, Vec<Encoding>)>,
+}
+
+impl<W: Write> TableWriter<W> {
+    fn new(
+        writer: W,
+        df: &DataFrame,
+        metadata: &ParquetMetadata,
+        codecs: &[ColumnCodec],
+    ) -> PolarsResult<Self> {
+        let fields = df
+            .schema()
+            .to_arrow()
+            .fields
+            .into_iter()
+            .map(|field| match metadata.columns.get(&field.name) {
+                Some(column_metadata) => field.with_metadata(column_metadata.clone()),
+                None => field,
+            })
+            .collect::<Vec<_>>();
+        let schema = ArrowSchema::from(fields);
+
+        let options = WriteOptions {
+            write_statistics: false,
+            compression: CompressionOptions::Zstd(None),
+            version: Version::V2,
+            data_pagesize_limit: None,
+        };
+        //Columns without a codec get the defaults above
+        let column_settings: Vec<(WriteOptions, Vec<Encoding>)> = schema
+            .fields
+            .iter()
+            .map(|field| match find_codec(codecs, &field.name) {
+                Some(codec) => (
+                    WriteOptions {
+                        compression: codec.get_compression(),
+                        ..options
+                    },
+                    transverse(&field.data_type, |_| codec.get_encoding()),
+                ),
+                None => (options, transverse(&field.data_type, |_| Encoding::Plain)),
+            })
+            .collect();
+
+        let parquet_schema = to_parquet_schema(&schema)?;
+        Ok(TableWriter {
+            column_names: schema
+                .fields
+                .iter()
+                .map(|field| field.name.clone())
+                .collect(),
+            parquet_fields: parquet_schema.fields().to_vec(),
+            file_writer: FileWriter::try_new(writer, schema, options)?,
+            column_settings,
+        })
+    }
+
+    //The FileWriter compresses pages one at a time as it writes them, which leaves zstd on a
+    //single core. Instead every column of a row group is encoded and compressed on its own
+    //thread, and the writer only copies the finished pages to the file.
+    fn write_row_groups(&mut self, df: &mut DataFrame, bounds: &[usize]) -> PolarsResult<()> {
+        if df.get_column_names() != self.column_names {
+            return Err(PolarsError::SchemaMismatch(
+                format!(
+                    "columns {:?} do not match the columns {:?} of the file",
+                    df.get_column_names(),
+                    self.column_names
+                )
+                .into(),
+            ));
+        }
+        df.align_chunks();
+
+        let mut offset: usize = 0;
+        for end in bounds.iter() {
+            let slice = df.slice(offset as i64, end - offset);
+            offset = *end;
+            for chunk in slice.iter_chunks() {
+                let columns = run_on_encode_pool(|| {
+                    chunk
+                        .arrays()
+                        .par_iter()
+                        .zip(self.parquet_fields.par_iter())
+                        .zip(self.column_settings.par_iter())
+                        .map(|((array, parquet_type), (column_options, encoding))| {
+                            compress_column(array.as_ref(), parquet_type, *column_options, encoding)
+                        })
+                        .collect::<PolarsResult<Vec<_>>>()
+                })?;
+                let row_group = DynIter::new(columns.into_iter().flatten().map(|pages| {
+                    Ok(DynStreamingIterator::new(CompressedPages {
+                        pages: pages.into_iter(),
+                        current: None,
+                    }))
+                }));
+                self.file_writer.write(row_group)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(mut self, metadata: &ParquetMetadata) -> PolarsResult<u64> {
+        let key_values = metadata
+            .file
+            .iter()
+            .map(|(key, value)| KeyValue {
+                key: key.clone(),
+                value: Some(value.clone()),
+            })
+            .collect();
+        self.file_writer.end(Some(key_values))
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::{TimeUnit, INVALID_VALUE};
+use super::channel_map::ChannelType;
+use super::compass_data::{
+    decompose_uuid_to_board_channel, generate_board_channel_uuid, CompassData,
+};
+
+//Name of the columns of a passthrough channel, e.g. TargetWheel for TargetWheelEnergy and
+//TargetWheelTime. Channels without an entry are named after their channel type, Passthrough<n>.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PassthroughEntry {
+    pub channel: ChannelType,
+    pub name: String,
+}
+
+impl Default for PassthroughEntry {
+    fn default() -> Self {
+        PassthroughEntry {
+            channel: ChannelType::Passthrough(0),
+            name: String::new(),
+        }
+    }
+}
+
+impl PassthroughEntry {
+    pub fn get_energy_column(&self) -> String {
+        format!("{}Energy", self.name)
+    }
+
+    pub fn get_time_column(&self) -> String {
+        format!("{}Time", self.name)
+    }
+}
+
+//The entry naming a channel, or the default name
+pub fn get_passthrough_entry(entries: &[PassthroughEntry], number: usize) -> PassthroughEntry {
+    let channel = ChannelType::Passthrough(number);
+    entries
+        .iter()
+        .find(|entry| entry.channel == channel)
+        .cloned()
+        .unwrap_or_else(|| PassthroughEntry {
+            channel,
+            name: channel.to_string(),
+        })
+}
+
+//Energy and time of one passthrough channel in every event, with no detector assumptions: no
+//calibration, weights or multiplicities. Named by the user so they cannot be ChannelDataFields.
+//They come after the detector group columns, in channel order. The columns of unmapped channels,
+//with the Columns policy, are passthrough columns as well, after those of the passthrough channels.
+#[derive(Debug, Clone)]
+pub struct PassthroughColumns {
+    pub entry: PassthroughEntry,
+    //Of an unmapped channel, whose first hit in the event fills the columns
+    pub unmapped_uuid: Option<u32>,
+    pub energy_name: String,
+    pub time_name: String,
+    pub energy: Vec<f64>,
+    pub time: Vec<f64>,
+}
+
+impl PassthroughColumns {
+    pub fn new(entries: &[PassthroughEntry], number: usize) -> Self {
+        let entry = get_passthrough_entry(entries, number);
+        PassthroughColumns {
+            energy_name: entry.get_energy_column(),
+            time_name: entry.get_time_column(),
+            entry,
+            unmapped_uuid: None,
+            energy: vec![],
+            time: vec![],
+        }
+    }
+
+    //UnmappedBoard<b>Chan<c>Energy and UnmappedBoard<b>Chan<c>Time
+    pub fn unmapped(board: u32, channel: u32) -> Self {
+        let entry = PassthroughEntry {
+            channel: ChannelType::None,
+            name: format!("UnmappedBoard{}Chan{}", board, channel),
+        };
+        PassthroughColumns {
+            energy_name: entry.get_energy_column(),
+            time_name: entry.get_time_column(),
+            entry,
+            unmapped_uuid: Some(generate_board_channel_uuid(&board, &channel)),
+            energy: vec![],
+            time: vec![],
+        }
+    }
+
+    //For the metadata of the columns, e.g. Passthrough2 or board 1 channel 5
+    pub fn describe_channel(&self) -> String {
+        match self.unmapped_uuid {
+            Some(uuid) => {
+                let (board, channel) = decompose_uuid_to_board_channel(&uuid);
+                format!("board {} channel {}", board, channel)
+            }
+            None => self.entry.channel.to_string(),
+        }
+    }
+
+    //hit is the one chosen by the multi-hit policy of the channel, None if it did not fire
+    pub fn append_event(&mut self, hit: Option<&CompassData>, time_unit: TimeUnit) {
+        match hit {
+            Some(hit) => {
+                self.energy.push(hit.energy);
+                self.time.push(time_unit.convert_nanoseconds(hit.timestamp));
+            }
+            None => {
+                self.energy.push(INVALID_VALUE);
+                self.time.push(INVALID_VALUE);
+            }
+        }
+    }
+
+    pub fn truncate(&mut self, rows: usize) {
+        self.energy.truncate(rows);
+        self.time.truncate(rows);
+    }
+}
+
+//Energy and time columns of the passthrough channels of a map with count of them
+pub fn get_passthrough_columns(entries: &[PassthroughEntry], count: usize) -> Vec<String> {
+    (0..count)
+        .map(|number| get_passthrough_entry(entries, number))
+        .flat_map(|entry| [entry.get_energy_column(), entry.get_time_column()])
+        .collect()
+}
@@ -0,0 +1,483 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use eframe::egui;
+
+use super::error::EVBError;
+use super::histogram::Histogram;
+
+//Size of exported plots (px)
+pub const EXPORT_WIDTH: f32 = 1000.0;
+pub const EXPORT_HEIGHT: f32 = 600.0;
+
+const MARGIN_LEFT: f32 = 80.0;
+const MARGIN_RIGHT: f32 = 25.0;
+const MARGIN_TOP: f32 = 35.0;
+const MARGIN_BOTTOM: f32 = 55.0;
+const TICK_LENGTH: f32 = 5.0;
+const TICK_TARGET: f64 = 8.0;
+const TITLE_SIZE: f32 = 16.0;
+const LABEL_SIZE: f32 = 14.0;
+const TICK_LABEL_SIZE: f32 = 12.0;
+
+type Rgba = [u8; 4];
+
+#[derive(Debug, Clone, Copy)]
+pub struct PlotColors {
+    pub background: Rgba,
+    pub foreground: Rgba,
+    pub bars: Rgba,
+}
+
+impl PlotColors {
+    //Match the GUI theme, so an exported plot looks like the one on screen
+    pub fn new(dark_mode: bool) -> Self {
+        if dark_mode {
+            PlotColors {
+                background: [27, 27, 27, 255],
+                foreground: [220, 220, 220, 255],
+                bars: [90, 170, 255, 255],
+            }
+        } else {
+            PlotColors {
+                background: [255, 255, 255, 255],
+                foreground: [20, 20, 20, 255],
+                bars: [30, 90, 200, 255],
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    Middle,
+    End,
+}
+
+//Everything on a plot is an axis-aligned rectangle or a line of text. Text is positioned by its
+//vertical center; vertical text reads bottom to top and is anchored on its center.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlotElement {
+    Rect {
+        min: [f32; 2],
+        max: [f32; 2],
+        color: Rgba,
+    },
+    Text {
+        pos: [f32; 2],
+        text: String,
+        size: f32,
+        anchor: TextAnchor,
+        vertical: bool,
+        color: Rgba,
+    },
+}
+
+//Ticks at 1, 2 or 5 times a power of ten, about TICK_TARGET of them over the range
+fn get_ticks(min: f64, max: f64) -> (Vec<f64>, f64) {
+    let range = max - min;
+    if range <= 0.0 || !range.is_finite() {
+        return (vec![min], 1.0);
+    }
+    let raw_step = range / TICK_TARGET;
+    let magnitude = 10.0_f64.powf(raw_step.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= raw_step)
+        .unwrap_or(10.0 * magnitude);
+    let mut ticks = vec![];
+    let mut tick = (min / step).ceil() * step;
+    while tick <= max + step * 1.0e-9 {
+        ticks.push(tick);
+        tick += step;
+    }
+    (ticks, step)
+}
+
+fn format_tick(value: f64, step: f64) -> String {
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    format!("{:.*}", decimals, value)
+}
+
+pub fn get_plot_elements(
+    histogram: &Histogram,
+    width: f32,
+    height: f32,
+    colors: &PlotColors,
+) -> Vec<PlotElement> {
+    let mut elements = vec![PlotElement::Rect {
+        min: [0.0, 0.0],
+        max: [width, height],
+        color: colors.background,
+    }];
+
+    let left = MARGIN_LEFT;
+    let right = (width - MARGIN_RIGHT).max(left + 1.0);
+    let top = MARGIN_TOP;
+    let bottom = (height - MARGIN_BOTTOM).max(top + 1.0);
+
+    let y_max = (histogram.get_max_count() * 1.05).max(1.0);
+    let to_x = |value: f64| {
+        left + ((value - histogram.min) / (histogram.max - histogram.min)) as f32 * (right - left)
+    };
+    let to_y = |value: f64| bottom - (value / y_max) as f32 * (bottom - top);
+
+    let bin_width = histogram.get_bin_width();
+    for (bin, count) in histogram.counts.iter().enumerate() {
+        if *count <= 0.0 {
+            continue;
+        }
+        let low = histogram.min + bin as f64 * bin_width;
+        elements.push(PlotElement::Rect {
+            min: [to_x(low), to_y(*count)],
+            max: [to_x(low + bin_width), bottom],
+            color: colors.bars,
+        });
+    }
+
+    //Frame
+    elements.push(PlotElement::Rect {
+        min: [left, top],
+        max: [right, top + 1.0],
+        color: colors.foreground,
+    });
+    elements.push(PlotElement::Rect {
+        min: [left, bottom - 1.0],
+        max: [right, bottom],
+        color: colors.foreground,
+    });
+    elements.push(PlotElement::Rect {
+        min: [left, top],
+        max: [left + 1.0, bottom],
+        color: colors.foreground,
+    });
+    elements.push(PlotElement::Rect {
+        min: [right - 1.0, top],
+        max: [right, bottom],
+        color: colors.foreground,
+    });
+
+    let (x_ticks, x_step) = get_ticks(histogram.min, histogram.max);
+    for tick in x_ticks {
+        let x = to_x(tick);
+        elements.push(PlotElement::Rect {
+            min: [x - 0.5, bottom],
+            max: [x + 0.5, bottom + TICK_LENGTH],
+            color: colors.foreground,
+        });
+        elements.push(PlotElement::Text {
+            pos: [x, bottom + TICK_LENGTH + TICK_LABEL_SIZE],
+            text: format_tick(tick, x_step),
+            size: TICK_LABEL_SIZE,
+            anchor: TextAnchor::Middle,
+            vertical: false,
+            color: colors.foreground,
+        });
+    }
+
+    let (y_ticks, y_step) = get_ticks(0.0, y_max);
+    for tick in y_ticks {
+        let y = to_y(tick);
+        elements.push(PlotElement::Rect {
+            min: [left - TICK_LENGTH, y - 0.5],
+            max: [left, y + 0.5],
+            color: colors.foreground,
+        });
+        elements.push(PlotElement::Text {
+            pos: [left - TICK_LENGTH - 3.0, y],
+            text: format_tick(tick, y_step),
+            size: TICK_LABEL_SIZE,
+            anchor: TextAnchor::End,
+            vertical: false,
+            color: colors.foreground,
+        });
+    }
+
+    elements.push(PlotElement::Text {
+        pos: [(left + right) / 2.0, top / 2.0],
+        text: histogram.title.clone(),
+        size: TITLE_SIZE,
+        anchor: TextAnchor::Middle,
+        vertical: false,
+        color: colors.foreground,
+    });
+    elements.push(PlotElement::Text {
+        pos: [(left + right) / 2.0, height - LABEL_SIZE],
+        text: histogram.x_label.clone(),
+        size: LABEL_SIZE,
+        anchor: TextAnchor::Middle,
+        vertical: false,
+        color: colors.foreground,
+    });
+    elements.push(PlotElement::Text {
+        pos: [LABEL_SIZE, (top + bottom) / 2.0],
+        text: histogram.y_label.clone(),
+        size: LABEL_SIZE,
+        anchor: TextAnchor::Middle,
+        vertical: true,
+        color: colors.foreground,
+    });
+    elements
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn svg_color(color: &Rgba) -> String {
+    format!("rgb({},{},{})", color[0], color[1], color[2])
+}
+
+pub fn write_svg(histogram: &Histogram, filepath: &Path, dark_mode: bool) -> Result<(), EVBError> {
+    let colors = PlotColors::new(dark_mode);
+    let mut file = BufWriter::new(File::create(filepath)?);
+    writeln!(
+        file,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+        w = EXPORT_WIDTH,
+        h = EXPORT_HEIGHT
+    )?;
+    for element in get_plot_elements(histogram, EXPORT_WIDTH, EXPORT_HEIGHT, &colors) {
+        match element {
+            PlotElement::Rect { min, max, color } => writeln!(
+                file,
+                "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"/>",
+                min[0],
+                min[1],
+                max[0] - min[0],
+                max[1] - min[1],
+                svg_color(&color)
+            )?,
+            PlotElement::Text {
+                pos,
+                text,
+                size,
+                anchor,
+                vertical,
+                color,
+            } => {
+                let anchor = match anchor {
+                    TextAnchor::Middle => "middle",
+                    TextAnchor::End => "end",
+                };
+                let rotate = if vertical {
+                    format!(" transform=\"rotate(-90 {:.2} {:.2})\"", pos[0], pos[1])
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    file,
+                    "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"central\" fill=\"{}\"{}>{}</text>",
+                    pos[0],
+                    pos[1],
+                    size,
+                    anchor,
+                    svg_color(&color),
+                    rotate,
+                    escape_xml(&text)
+                )?
+            }
+        }
+    }
+    writeln!(file, "</svg>")?;
+    Ok(())
+}
+
+fn blend(image: &mut image::RgbaImage, x: i64, y: i64, color: &Rgba, coverage: f32) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let alpha = coverage.clamp(0.0, 1.0);
+    for (value, target) in pixel.0.iter_mut().zip(color.iter()).take(3) {
+        *value = (*target as f32 * alpha + *value as f32 * (1.0 - alpha)) as u8;
+    }
+}
+
+//Renders text into a coverage buffer, returned with its width and height
+fn rasterize_text(font: &FontRef<'_>, text: &str, size: f32) -> (Vec<f32>, usize, usize) {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut glyphs = vec![];
+    let mut caret = 0.0;
+    let mut previous = None;
+    for character in text.chars() {
+        let id = scaled.glyph_id(character);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        glyphs.push(id.with_scale_and_position(size, ab_glyph::point(caret, scaled.ascent())));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    let width = caret.ceil().max(1.0) as usize;
+    let height = (scaled.ascent() - scaled.descent()).ceil().max(1.0) as usize;
+    let mut coverage = vec![0.0; width * height];
+    for glyph in glyphs {
+        if let Some(outline) = font.outline_glyph(glyph) {
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, c| {
+                let px = x as i64 + bounds.min.x as i64;
+                let py = y as i64 + bounds.min.y as i64;
+                if px >= 0 && py >= 0 && (px as usize) < width && (py as usize) < height {
+                    let value = &mut coverage[py as usize * width + px as usize];
+                    *value = (*value + c).min(1.0);
+                }
+            });
+        }
+    }
+    (coverage, width, height)
+}
+
+pub fn write_png(histogram: &Histogram, filepath: &Path, dark_mode: bool) -> Result<(), EVBError> {
+    let colors = PlotColors::new(dark_mode);
+    //Use the font the GUI draws with
+    let definitions = egui::FontDefinitions::default();
+    let font_data = definitions
+        .families
+        .get(&egui::FontFamily::Proportional)
+        .and_then(|names| names.first())
+        .and_then(|name| definitions.font_data.get(name))
+        .ok_or_else(|| EVBError::Plot(String::from("No font available")))?;
+    let font =
+        FontRef::try_from_slice(&font_data.font).map_err(|x| EVBError::Plot(x.to_string()))?;
+
+    let mut image = image::RgbaImage::from_pixel(
+        EXPORT_WIDTH as u32,
+        EXPORT_HEIGHT as u32,
+        image::Rgba(colors.background),
+    );
+    for element in get_plot_elements(histogram, EXPORT_WIDTH, EXPORT_HEIGHT, &colors) {
+        match element {
+            PlotElement::Rect { min, max, color } => {
+                let x0 = min[0].round() as i64;
+                let x1 = (max[0].round() as i64).max(x0 + 1);
+                let y0 = min[1].round() as i64;
+                let y1 = (max[1].round() as i64).max(y0 + 1);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        blend(&mut image, x, y, &color, 1.0);
+                    }
+                }
+            }
+            PlotElement::Text {
+                pos,
+                text,
+                size,
+                anchor,
+                vertical,
+                color,
+            } => {
+                let (coverage, width, height) = rasterize_text(&font, &text, size);
+                let offset = match anchor {
+                    TextAnchor::Middle => width as f32 / 2.0,
+                    TextAnchor::End => width as f32,
+                };
+                for row in 0..height {
+                    for column in 0..width {
+                        let value = coverage[row * width + column];
+                        if value <= 0.0 {
+                            continue;
+                        }
+                        //Vertical text is the horizontal text turned a quarter counterclockwise
+                        let (x, y) = if vertical {
+                            (
+                                pos[0] - height as f32 / 2.0 + row as f32,
+                                pos[1] + offset - column as f32,
+                            )
+                        } else {
+                            (
+                                pos[0] - offset + column as f32,
+                                pos[1] - height as f32 / 2.0 + row as f32,
+                            )
+                        };
+                        blend(&mut image, x as i64, y as i64, &color, value);
+                    }
+                }
+            }
+        }
+    }
+    image
+        .save_with_format(filepath, image::ImageFormat::Png)
+        .map_err(|x| EVBError::Plot(x.to_string()))?;
+    Ok(())
+}
+
+//Draws the same elements on screen
+pub fn paint_plot(ui: &mut egui::Ui, histogram: &Histogram, dark_mode: bool) -> egui::Response {
+    let colors = PlotColors::new(dark_mode);
+    let size = ui.available_size().max(egui::vec2(200.0, 150.0));
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let origin = response.rect.min;
+    let to_color =
+        |color: Rgba| egui::Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]);
+    for element in get_plot_elements(histogram, size.x, size.y, &colors) {
+        match element {
+            PlotElement::Rect { min, max, color } => {
+                let rect = egui::Rect::from_min_max(
+                    origin + egui::vec2(min[0], min[1]),
+                    origin + egui::vec2(max[0], max[1]),
+                );
+                painter.rect_filled(rect, 0.0, to_color(color));
+            }
+            PlotElement::Text {
+                pos,
+                text,
+                size,
+                anchor,
+                vertical,
+                color,
+            } => {
+                let galley =
+                    painter.layout_no_wrap(text, egui::FontId::proportional(size), to_color(color));
+                let offset = match anchor {
+                    TextAnchor::Middle => galley.size().x / 2.0,
+                    TextAnchor::End => galley.size().x,
+                };
+                let pos = origin + egui::vec2(pos[0], pos[1]);
+                let shape = if vertical {
+                    egui::epaint::TextShape::new(
+                        pos + egui::vec2(-galley.size().y / 2.0, offset),
+                        galley,
+                        to_color(color),
+                    )
+                    .with_angle(-std::f32::consts::FRAC_PI_2)
+                } else {
+                    egui::epaint::TextShape::new(
+                        pos - egui::vec2(offset, galley.size().y / 2.0),
+                        galley,
+                        to_color(color),
+                    )
+                };
+                painter.add(shape);
+            }
+        }
+    }
+    response
+}
+
+//Size of each plot in a grid of small multiples (px)
+const SMALL_MULTIPLE_WIDTH: f32 = 340.0;
+const SMALL_MULTIPLE_HEIGHT: f32 = 220.0;
+
+//Plots of the same size side by side, wrapped into as many columns as fit
+pub fn paint_small_multiples(ui: &mut egui::Ui, histograms: &[Histogram], dark_mode: bool) {
+    let cell_width = SMALL_MULTIPLE_WIDTH + ui.spacing().item_spacing.x;
+    let columns = ((ui.available_width() / cell_width).floor() as usize).max(1);
+    for row in histograms.chunks(columns) {
+        ui.horizontal(|ui| {
+            for histogram in row {
+                ui.allocate_ui(
+                    egui::vec2(SMALL_MULTIPLE_WIDTH, SMALL_MULTIPLE_HEIGHT),
+                    |ui| paint_plot(ui, histogram, dark_mode),
+                );
+            }
+        });
+    }
+}
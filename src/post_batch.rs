@@ -0,0 +1,35 @@
+use std::sync::{Arc, Mutex};
+
+use polars::prelude::*;
+
+//Experiment specific processing of each built batch, run after the batch is converted to a
+//DataFrame and before it is written. A hook can add columns, filter rows, or just look at the
+//data. Hooks run in the order they were added, each receiving the previous hook's output.
+//
+//To use one, build your own binary against this crate and register the hook on the app, e.g.
+//`EVBApp::new(cc, false).with_post_batch_hook(Box::new(MyHook))`.
+pub trait PostBatchHook: Send {
+    fn name(&self) -> &str;
+
+    fn process(&mut self, run_number: i32, batch: DataFrame) -> PolarsResult<DataFrame>;
+}
+
+impl std::fmt::Debug for dyn PostBatchHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PostBatchHook({})", self.name())
+    }
+}
+
+//Shared between the GUI, which owns the hooks, and the processing thread
+pub type PostBatchHooks = Arc<Mutex<Vec<Box<dyn PostBatchHook>>>>;
+
+pub fn apply_post_batch_hooks(
+    hooks: &mut [Box<dyn PostBatchHook>],
+    run_number: i32,
+    mut batch: DataFrame,
+) -> PolarsResult<DataFrame> {
+    for hook in hooks.iter_mut() {
+        batch = hook.process(run_number, batch)?;
+    }
+    Ok(batch)
+}
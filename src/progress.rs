@@ -0,0 +1,436 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use log::info;
+
+use super::batch_schedule::BatchSchedule;
+use super::error::EVBError;
+use super::formatting::{format_bytes, format_count, format_duration, format_rate};
+use super::run_list::BatchStatus;
+
+//Aim for a progress update about this often (s). Reports are triggered by hit count, and the count
+//between reports is adjusted from the measured rate, so the clock is only read at report time.
+const REPORT_PERIOD: f64 = 0.5;
+//Rate lines in the log are rarer than progress updates
+const LOG_PERIOD: f64 = 10.0;
+const INITIAL_INTERVAL: u64 = 1000;
+const MIN_INTERVAL: u64 = 100;
+
+//One hit source of a run (a channel file with its segments, a CSV export, ...) and its size
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFile {
+    pub name: String,
+    pub bytes: u64,
+}
+
+impl InputFile {
+    //Named after the first of its files, e.g. the first segment of a channel
+    pub fn new(paths: &[PathBuf]) -> Self {
+        InputFile {
+            name: paths
+                .first()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            bytes: paths
+                .iter()
+                .filter_map(|path| path.metadata().ok())
+                .map(|metadata| metadata.len())
+                .sum(),
+        }
+    }
+}
+
+//Where the build of a run stands
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RunProgress {
+    pub run_number: i32,
+    //Position of the run in the job, from 1
+    pub run_index: usize,
+    pub run_count: usize,
+    //Estimated from the share of the hits of each file read so far
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    //Input files of which every hit has been read
+    pub files_finished: usize,
+    pub file_count: usize,
+    pub hits: u64,
+    pub total_hits: u64,
+    pub events: u64,
+    pub hit_rate: f64,
+    //None until there is a rate to go by
+    pub remaining_seconds: Option<f64>,
+}
+
+impl RunProgress {
+    pub fn get_fraction(&self) -> f64 {
+        if self.total_hits == 0 {
+            return 0.0;
+        }
+        (self.hits as f64 / self.total_hits as f64).min(1.0)
+    }
+
+    //e.g. "Run 12 (2/5): 1.2 M of 3.4 M hits, 380 k events, 12 MB of 34 MB, 3 of 16 files done,
+    //00:01:05 left"
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "Run {} ({}/{}): {} of {} hits, {} events, {} of {}, {} of {} files done",
+            self.run_number,
+            self.run_index,
+            self.run_count,
+            format_count(self.hits),
+            format_count(self.total_hits),
+            format_count(self.events),
+            format_bytes(self.bytes_read as usize),
+            format_bytes(self.total_bytes as usize),
+            self.files_finished,
+            self.file_count
+        );
+        if let Some(seconds) = self.remaining_seconds {
+            text.push_str(&format!(", {} left", format_duration(seconds)));
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    RunStarted {
+        run_number: i32,
+        run_index: usize,
+        run_count: usize,
+    },
+    //Every hit of one input file has been read
+    FileFinished {
+        run_number: i32,
+        file: String,
+        bytes: u64,
+    },
+    //Sent about every REPORT_PERIOD while the hits of a run are built
+    Update(RunProgress),
+    //The last update of a run, sent once its hits are all built
+    RunFinished(RunProgress),
+}
+
+//Called on the thread building the runs, so keep it quick
+pub trait ProgressListener: Send {
+    fn on_progress(&mut self, event: &ProgressEvent);
+}
+
+impl std::fmt::Debug for dyn ProgressListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressListener")
+    }
+}
+
+#[derive(Debug, Default)]
+struct JobState {
+    run_index: usize,
+    run_count: usize,
+    latest: RunProgress,
+    schedule: BatchSchedule,
+}
+
+//Progress of a job, shared between the thread building the runs and the frontend that started it.
+//Frontends either poll the latest state (the GUI, once a frame) or add a listener to be told of
+//every event (the command line).
+#[derive(Debug, Clone, Default)]
+pub struct JobProgress {
+    state: Arc<Mutex<JobState>>,
+    listeners: Arc<Mutex<Vec<Box<dyn ProgressListener>>>>,
+}
+
+impl JobProgress {
+    pub fn with_listener(self, listener: Box<dyn ProgressListener>) -> Self {
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.push(listener);
+        }
+        self
+    }
+
+    //Fraction of the current run built, for the progress bar
+    pub fn get_fraction(&self) -> f32 {
+        match self.state.lock() {
+            Ok(state) => state.latest.get_fraction() as f32,
+            Err(_) => 0.0,
+        }
+    }
+
+    pub fn get_latest(&self) -> RunProgress {
+        match self.state.lock() {
+            Ok(state) => state.latest.clone(),
+            Err(_) => RunProgress::default(),
+        }
+    }
+
+    //The schedule of the runs of the job, with the fraction of the current run built to estimate it
+    pub fn get_schedule(&self) -> (BatchSchedule, f64) {
+        match self.state.lock() {
+            Ok(state) => (state.schedule.clone(), state.latest.get_fraction()),
+            Err(_) => (BatchSchedule::default(), 0.0),
+        }
+    }
+
+    pub fn set_schedule(&self, schedule: BatchSchedule) -> Result<(), EVBError> {
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.schedule = schedule;
+                Ok(())
+            }
+            Err(_) => Err(EVBError::Sync),
+        }
+    }
+
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = JobState::default();
+        }
+    }
+
+    pub fn start_run(
+        &self,
+        run_number: i32,
+        run_index: usize,
+        run_count: usize,
+    ) -> Result<(), EVBError> {
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.run_index = run_index;
+                state.run_count = run_count;
+                state.schedule.start_run(run_number);
+                state.latest = RunProgress {
+                    run_number,
+                    run_index,
+                    run_count,
+                    ..Default::default()
+                };
+            }
+            Err(_) => return Err(EVBError::Sync),
+        }
+        self.notify(&ProgressEvent::RunStarted {
+            run_number,
+            run_index,
+            run_count,
+        })
+    }
+
+    //The run started last is done, however it ended
+    pub fn finish_run(&self, status: BatchStatus) -> Result<(), EVBError> {
+        match self.state.lock() {
+            Ok(mut state) => {
+                state.schedule.finish_run(status);
+                Ok(())
+            }
+            Err(_) => Err(EVBError::Sync),
+        }
+    }
+
+    //Fills in the position of the run in the job and keeps it as the latest state
+    fn update(&self, mut run: RunProgress, is_finished: bool) -> Result<(), EVBError> {
+        match self.state.lock() {
+            Ok(mut state) => {
+                run.run_index = state.run_index;
+                run.run_count = state.run_count;
+                state.latest = run.clone();
+            }
+            Err(_) => return Err(EVBError::Sync),
+        }
+        self.notify(&if is_finished {
+            ProgressEvent::RunFinished(run)
+        } else {
+            ProgressEvent::Update(run)
+        })
+    }
+
+    fn notify(&self, event: &ProgressEvent) -> Result<(), EVBError> {
+        match self.listeners.lock() {
+            Ok(mut listeners) => {
+                for listener in listeners.iter_mut() {
+                    listener.on_progress(event);
+                }
+                Ok(())
+            }
+            Err(_) => Err(EVBError::Sync),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgressReporter {
+    progress: JobProgress,
+    run_number: i32,
+    inputs: Vec<InputFile>,
+    //Hits of each input file, and how many of them have been read
+    file_hits: Vec<u64>,
+    file_hits_read: Vec<u64>,
+    total_hits: u64,
+    hits: u64,
+    //Read before the checkpoint a run was resumed from, left out of the rates
+    hits_skipped: u64,
+    events: u64,
+    interval: u64,
+    next_report: u64,
+    start: Instant,
+    last_report: Instant,
+    last_report_hits: u64,
+    last_log: Instant,
+}
+
+impl ProgressReporter {
+    //inputs and file_hits are in the order of the hit sources of the run
+    pub fn new(
+        progress: JobProgress,
+        run_number: i32,
+        inputs: Vec<InputFile>,
+        file_hits: Vec<u64>,
+    ) -> Self {
+        let now = Instant::now();
+        ProgressReporter {
+            progress,
+            run_number,
+            inputs,
+            file_hits_read: vec![0; file_hits.len()],
+            total_hits: file_hits.iter().sum(),
+            file_hits,
+            hits: 0,
+            hits_skipped: 0,
+            events: 0,
+            interval: INITIAL_INTERVAL,
+            next_report: INITIAL_INTERVAL,
+            start: now,
+            last_report: now,
+            last_report_hits: 0,
+            last_log: now,
+        }
+    }
+
+    //A hit of the given source was read, with events built so far. Returns true when a report was
+    //made, so callers can piggyback other periodic work on it.
+    pub fn add_hit(&mut self, file_index: usize, events: u64) -> Result<bool, EVBError> {
+        self.hits += 1;
+        self.events = events;
+        if let Some(read) = self.file_hits_read.get_mut(file_index) {
+            *read += 1;
+            if *read == self.file_hits[file_index] {
+                if let Some(input) = self.inputs.get(file_index) {
+                    self.progress.notify(&ProgressEvent::FileFinished {
+                        run_number: self.run_number,
+                        file: input.name.clone(),
+                        bytes: input.bytes,
+                    })?;
+                }
+            }
+        }
+        if self.hits < self.next_report {
+            return Ok(false);
+        }
+        self.report()?;
+        Ok(true)
+    }
+
+    //Hits read from each source so far
+    pub fn get_file_hits_read(&self) -> &[u64] {
+        &self.file_hits_read
+    }
+
+    //A run resumed from a checkpoint starts with the hits read before it, so the progress and the
+    //estimate of the time left cover the whole run
+    pub fn skip_hits(&mut self, file_hits_read: &[u64], events: u64) {
+        for (read, skipped) in self.file_hits_read.iter_mut().zip(file_hits_read.iter()) {
+            *read = *skipped;
+        }
+        self.hits = self.file_hits_read.iter().sum();
+        self.hits_skipped = self.hits;
+        self.events = events;
+        self.next_report = self.hits + self.interval;
+        self.last_report_hits = self.hits;
+    }
+
+    //Events built after the last hit was read, by the builder threads of a parallel build
+    pub fn set_events(&mut self, events: u64) {
+        self.events = events;
+    }
+
+    fn report(&mut self) -> Result<(), EVBError> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_report).as_secs_f64();
+        let rate = (self.hits - self.last_report_hits) as f64 / elapsed.max(1.0e-6);
+
+        //Never go more than 1% of the run between updates, so the progress bar keeps moving
+        let max_interval = (self.total_hits / 100).max(MIN_INTERVAL);
+        self.interval = ((rate * REPORT_PERIOD) as u64).clamp(MIN_INTERVAL, max_interval);
+        self.next_report = self.hits + self.interval;
+        self.last_report = now;
+        self.last_report_hits = self.hits;
+
+        let run = self.get_run_progress();
+        if now.duration_since(self.last_log).as_secs_f64() >= LOG_PERIOD {
+            info!(
+                "Processed {} of {} hits ({:.1}%), {}, {} elapsed",
+                format_count(self.hits),
+                format_count(self.total_hits),
+                run.get_fraction() * 100.0,
+                format_rate(rate, "hits"),
+                format_duration(self.get_elapsed_seconds())
+            );
+            self.last_log = now;
+        }
+        self.progress.update(run, false)
+    }
+
+    fn get_run_progress(&self) -> RunProgress {
+        let bytes_read = self
+            .inputs
+            .iter()
+            .zip(self.file_hits.iter().zip(self.file_hits_read.iter()))
+            .filter(|(_, (hits, _))| **hits > 0)
+            .map(|(input, (hits, read))| {
+                (input.bytes as f64 * (*read as f64 / *hits as f64).min(1.0)) as u64
+            })
+            .sum();
+        let hit_rate = self.get_hit_rate();
+        RunProgress {
+            run_number: self.run_number,
+            run_index: 0,
+            run_count: 0,
+            bytes_read,
+            total_bytes: self.inputs.iter().map(|input| input.bytes).sum(),
+            files_finished: self
+                .file_hits
+                .iter()
+                .zip(self.file_hits_read.iter())
+                .filter(|(hits, read)| read >= hits)
+                .count(),
+            file_count: self.file_hits.len(),
+            hits: self.hits,
+            total_hits: self.total_hits,
+            events: self.events,
+            hit_rate,
+            remaining_seconds: (hit_rate > 0.0)
+                .then(|| self.total_hits.saturating_sub(self.hits) as f64 / hit_rate),
+        }
+    }
+
+    pub fn get_elapsed_seconds(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    pub fn get_hit_rate(&self) -> f64 {
+        (self.hits - self.hits_skipped) as f64 / self.get_elapsed_seconds().max(1.0e-6)
+    }
+
+    //Logs the totals of the run and sends its last update
+    pub fn finish(&self) -> Result<(), EVBError> {
+        info!(
+            "Run {} processed {} hits in {} ({})",
+            self.run_number,
+            format_count(self.hits - self.hits_skipped),
+            format_duration(self.get_elapsed_seconds()),
+            format_rate(self.get_hit_rate(), "hits")
+        );
+        let mut run = self.get_run_progress();
+        run.remaining_seconds = Some(0.0);
+        self.progress.update(run, true)
+    }
+}
@@ -0,0 +1,52 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::parquet_writer::ParquetMetadata;
+
+//A build of only the start of a run, to catch channel map, shift and gate mistakes in a few
+//minutes before committing to the full build. It is written apart from the full builds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuickBuildParams {
+    //Run time built, counted from the first hit of the run
+    pub minutes: f64,
+}
+
+impl Default for QuickBuildParams {
+    fn default() -> Self {
+        QuickBuildParams { minutes: 5.0 }
+    }
+}
+
+//Stops the hits of a run once they are past the run time of a quick build
+#[derive(Debug)]
+pub struct TimeLimit {
+    minutes: f64,
+    first_time: Option<f64>,
+}
+
+impl TimeLimit {
+    pub fn new(params: &QuickBuildParams) -> Self {
+        info!(
+            "Quick build, only the first {} minutes of the run are built",
+            params.minutes
+        );
+        TimeLimit {
+            minutes: params.minutes,
+            first_time: None,
+        }
+    }
+
+    //Whether a hit (time in ns) is past the limit. The first hit asked about starts the clock.
+    pub fn is_past(&mut self, time: f64) -> bool {
+        let first_time = *self.first_time.get_or_insert(time);
+        time - first_time > self.minutes * 60.0 * 1.0e9
+    }
+
+    pub fn get_minutes(&self) -> f64 {
+        self.minutes
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("quick_build_minutes", self.minutes.to_string());
+    }
+}
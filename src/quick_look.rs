@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::data_reduction::{DataReductionParams, EventReducer, ReductionProvenance};
+use super::parquet_writer::ParquetMetadata;
+
+//A thinned copy of the events, written next to the full events file in the same pass: only
+//events passing the filter, then subsampled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuickLookParams {
+    pub enabled: bool,
+    //Every one of these detectors must have a hit in the event
+    pub required_detectors: Vec<ChannelType>,
+    pub min_multiplicity: usize,
+    pub reduction: DataReductionParams,
+}
+
+impl Default for QuickLookParams {
+    fn default() -> Self {
+        QuickLookParams {
+            enabled: false,
+            required_detectors: Vec::new(),
+            min_multiplicity: 1,
+            reduction: DataReductionParams::default(),
+        }
+    }
+}
+
+impl QuickLookParams {
+    pub fn describe_filter(&self) -> String {
+        let detectors: Vec<String> = self
+            .required_detectors
+            .iter()
+            .map(|detector| detector.to_string())
+            .collect();
+        format!(
+            "multiplicity >= {}, requires [{}]",
+            self.min_multiplicity,
+            detectors.join(",")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuickLookSummary {
+    pub filter: String,
+    pub events_passed_filter: u64,
+    pub reduction: ReductionProvenance,
+}
+
+#[derive(Debug)]
+pub struct QuickLookFilter {
+    params: QuickLookParams,
+    required_uuids: Vec<HashSet<u32>>,
+    reducer: EventReducer,
+    events_passed_filter: u64,
+}
+
+impl QuickLookFilter {
+    pub fn new(params: &QuickLookParams, map: &ChannelMap, run_number: i32) -> Self {
+        QuickLookFilter {
+            params: params.clone(),
+            required_uuids: params
+                .required_detectors
+                .iter()
+                .map(|detector| map.get_uuids_of_type(*detector))
+                .collect(),
+            reducer: EventReducer::new(&params.reduction, run_number),
+            events_passed_filter: 0,
+        }
+    }
+
+    //The subsample is only drawn from the events that pass the filter
+    pub fn accept(&mut self, event: &[CompassData]) -> bool {
+        let passes = event.len() >= self.params.min_multiplicity
+            && self
+                .required_uuids
+                .iter()
+                .all(|uuids| event.iter().any(|hit| uuids.contains(&hit.uuid)));
+        if !passes {
+            return false;
+        }
+        self.events_passed_filter += 1;
+        self.reducer.keep_event()
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("quick_look_filter", self.params.describe_filter());
+        self.reducer.insert_metadata(metadata);
+    }
+
+    pub fn get_summary(&self) -> QuickLookSummary {
+        QuickLookSummary {
+            filter: self.params.describe_filter(),
+            events_passed_filter: self.events_passed_filter,
+            reduction: self.reducer.get_provenance(),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::formatting::format_count;
+use super::parquet_writer::ParquetMetadata;
+
+//Software throttling of noisy channels, e.g. a sparking PMT: while the rate of a channel over the
+//last window is above the limit, its hits are dropped before they reach the event builder
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitParams {
+    pub enabled: bool,
+    pub max_rate_hz: f64,
+    //The instantaneous rate is the number of hits of the channel in this window, dropped ones
+    //included, over its length
+    pub window_seconds: f64,
+}
+
+impl Default for RateLimitParams {
+    fn default() -> Self {
+        RateLimitParams {
+            enabled: false,
+            max_rate_hz: 1.0e5,
+            window_seconds: 1.0e-3,
+        }
+    }
+}
+
+impl RateLimitParams {
+    pub fn describe(&self) -> String {
+        format!("{} Hz over {} s", self.max_rate_hz, self.window_seconds)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RateLimitedChannel {
+    pub board: u32,
+    pub channel: u32,
+    pub dropped: u64,
+    //Times (ns) of the first and last dropped hit
+    pub first_dropped: f64,
+    pub last_dropped: f64,
+}
+
+//Written to the report of every run built with the rate limit
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RateLimitSummary {
+    pub limit: String,
+    pub dropped: u64,
+    //Only the channels with dropped hits
+    pub channels: Vec<RateLimitedChannel>,
+}
+
+#[derive(Debug, Default)]
+struct ChannelWindow {
+    times: VecDeque<f64>,
+    dropped: Option<RateLimitedChannel>,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    params: RateLimitParams,
+    //Most hits a channel may have in a window
+    max_hits: usize,
+    window: f64,
+    channels: BTreeMap<u32, ChannelWindow>,
+}
+
+impl RateLimiter {
+    pub fn new(params: &RateLimitParams) -> Option<Self> {
+        if !params.enabled {
+            return None;
+        }
+        Some(RateLimiter {
+            params: params.clone(),
+            max_hits: (params.max_rate_hz * params.window_seconds).floor() as usize,
+            window: params.window_seconds * 1.0e9,
+            channels: BTreeMap::new(),
+        })
+    }
+
+    //Hits come in time order. Returns None for a dropped hit.
+    pub fn apply(&mut self, hit: CompassData) -> Option<CompassData> {
+        let channel = self.channels.entry(hit.uuid).or_default();
+        channel.times.push_back(hit.timestamp);
+        while channel
+            .times
+            .front()
+            .is_some_and(|time| *time <= hit.timestamp - self.window)
+        {
+            channel.times.pop_front();
+        }
+        if channel.times.len() <= self.max_hits {
+            return Some(hit);
+        }
+        let dropped = channel.dropped.get_or_insert_with(|| {
+            let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+            RateLimitedChannel {
+                board,
+                channel,
+                first_dropped: hit.timestamp,
+                ..Default::default()
+            }
+        });
+        dropped.dropped += 1;
+        dropped.last_dropped = hit.timestamp;
+        None
+    }
+
+    //Times after a run boundary are on a new clock
+    pub fn end_run_segment(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.times.clear();
+        }
+    }
+
+    pub fn get_summary(&self) -> RateLimitSummary {
+        let channels: Vec<RateLimitedChannel> = self
+            .channels
+            .values()
+            .filter_map(|channel| channel.dropped.clone())
+            .collect();
+        RateLimitSummary {
+            limit: self.params.describe(),
+            dropped: channels.iter().map(|channel| channel.dropped).sum(),
+            channels,
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        if summary.dropped == 0 {
+            info!("No channel above the rate limit of {}", summary.limit);
+            return;
+        }
+        emit_warning(
+            "rate_limited",
+            String::new(),
+            format!(
+                "Dropped {} hits of {} channels above the rate limit of {}",
+                format_count(summary.dropped),
+                summary.channels.len(),
+                summary.limit
+            ),
+        );
+        for channel in summary.channels.iter() {
+            info!(
+                "  board {} channel {}: {} hits dropped between {:.3} s and {:.3} s",
+                channel.board,
+                channel.channel,
+                format_count(channel.dropped),
+                channel.first_dropped * 1.0e-9,
+                channel.last_dropped * 1.0e-9
+            );
+        }
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("rate_limit", self.params.describe());
+    }
+}
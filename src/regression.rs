@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+use super::app::EvbAppParams;
+use super::checksum::Checksum;
+use super::compass_data::set_dither_seed;
+use super::compass_run::process_runs;
+use super::error::EVBError;
+use super::hit_filter::HitFilters;
+use super::output_sink::OutputSinks;
+use super::post_batch::PostBatchHooks;
+use super::progress::JobProgress;
+use super::run_layout::RunLayout;
+use super::ws::Workspace;
+
+//A tiny run with three CeBrA channels, built with the config next to it. Both are compiled in, so
+//--regression works from any directory.
+const REFERENCE_ARCHIVE: &[u8] = include_bytes!("../regression/run_1.tar.gz");
+const REFERENCE_CONFIG: &str = include_str!("../regression/config.yaml");
+const REFERENCE_RUN: i32 = 1;
+const GOLDEN_CHECKSUMS: &str = include_str!("../regression/golden.yaml");
+const GOLDEN_COUNTS: &str = include_str!("../regression/counts.yaml");
+//--bless writes the new checksums and counts back into the source tree
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/regression/golden.yaml");
+const COUNTS_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/regression/counts.yaml");
+const DITHER_SEED: u64 = 1729;
+
+//The report has timings and memory use, the log has timestamps and the run metadata has the
+//eventbuilder version, so none of them can be compared
+fn is_compared(relative_path: &str) -> bool {
+    relative_path != "report.json"
+        && relative_path != "metadata.json"
+        && !relative_path.starts_with("logs/")
+}
+
+//Parquet files are compared by their columns and values, so a change of the writer's own
+//metadata does not show up as a regression
+fn get_parquet_checksum(path: &Path) -> Result<String, EVBError> {
+    let df = ParquetReader::new(File::open(path)?).finish()?;
+    let mut checksum = Checksum::new();
+    for series in df.get_columns() {
+        checksum.update(series.name().as_bytes());
+        for value in series.iter() {
+            checksum.update(format!("{};", value).as_bytes());
+        }
+    }
+    Ok(checksum.to_hex())
+}
+
+fn get_file_checksum(path: &Path) -> Result<String, EVBError> {
+    if path.extension().is_some_and(|e| e == "parquet") {
+        return get_parquet_checksum(path);
+    }
+    let mut checksum = Checksum::new();
+    checksum.update(&std::fs::read(path)?);
+    Ok(checksum.to_hex())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), EVBError> {
+    for item in dir.read_dir()? {
+        let path = item?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+//Relative path with / separators -> checksum, for every compared output of the run
+fn get_output_checksums(run_dir: &Path) -> Result<BTreeMap<String, String>, EVBError> {
+    let mut files = vec![];
+    collect_files(run_dir, &mut files)?;
+    let mut checksums = BTreeMap::new();
+    for path in files {
+        let relative = match path.strip_prefix(run_dir) {
+            Ok(relative) => relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => continue,
+        };
+        if is_compared(&relative) {
+            checksums.insert(relative, get_file_checksum(&path)?);
+        }
+    }
+    Ok(checksums)
+}
+
+//The hits of the run and of each detector, from the report, and the rows of each table. A checksum
+//only tells that something changed, these tell how many events were gained or lost.
+fn get_output_counts(run_dir: &Path) -> Result<BTreeMap<String, u64>, EVBError> {
+    let mut counts = BTreeMap::new();
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(run_dir.join("report.json"))?)?;
+    if let Some(hits) = report["total_hits"].as_u64() {
+        counts.insert(String::from("hits"), hits);
+    }
+    for channel in report["channels"].as_array().into_iter().flatten() {
+        if let (Some(detector), Some(hits)) =
+            (channel["detector"].as_str(), channel["hits"].as_u64())
+        {
+            counts.insert(format!("{} hits", detector), hits);
+        }
+    }
+    for item in run_dir.read_dir()? {
+        let path = item?.path();
+        if path.extension().is_some_and(|e| e == "parquet") {
+            let metadata = polars_parquet::read::read_metadata(&mut File::open(&path)?)?;
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            counts.insert(format!("{} rows", name), metadata.num_rows as u64);
+        }
+    }
+    Ok(counts)
+}
+
+fn write_reference_archive(workspace: &Workspace) -> Result<(), EVBError> {
+    let archive_dir = workspace
+        .get_archive_dir()
+        .map_err(|e| EVBError::Regression(e.to_string()))?;
+    std::fs::write(
+        archive_dir.join(format!("run_{}.tar.gz", REFERENCE_RUN)),
+        REFERENCE_ARCHIVE,
+    )?;
+    Ok(())
+}
+
+type ReferenceOutputs = (BTreeMap<String, String>, BTreeMap<String, u64>);
+
+fn build_reference(workspace_dir: &Path) -> Result<ReferenceOutputs, EVBError> {
+    let workspace = match Workspace::new(workspace_dir) {
+        Ok(ws) => ws,
+        Err(e) => return Err(EVBError::Regression(e.to_string())),
+    };
+    write_reference_archive(&workspace)?;
+
+    let config = serde_yaml::from_str::<EvbAppParams>(REFERENCE_CONFIG)?;
+    let params = config
+        .get_process_params(
+            &workspace,
+            PostBatchHooks::default(),
+            OutputSinks::default(),
+            HitFilters::default(),
+            true,
+        )
+        .map_err(|e| EVBError::Regression(e.to_string()))?;
+    let output_dir = params.output_dir.clone();
+
+    //The build runs on this thread, so seeding here makes the energies reproducible
+    set_dither_seed(Some(DITHER_SEED));
+    let diagnostics = params.diagnostics.clone();
+    let result = process_runs(params, JobProgress::default());
+    set_dither_seed(None);
+    for diagnostic in diagnostics.get_all() {
+        eprintln!("{}", diagnostic.render());
+    }
+    result?;
+
+    let layout = RunLayout::new(&output_dir, REFERENCE_RUN);
+    Ok((
+        get_output_checksums(layout.get_dir())?,
+        get_output_counts(layout.get_dir())?,
+    ))
+}
+
+//Prints a line for every entry, returns false if any differs
+fn compare_golden<T: PartialEq + Display>(
+    golden: &BTreeMap<String, T>,
+    found: &BTreeMap<String, T>,
+) -> bool {
+    let mut is_passed = true;
+    for (name, expected) in golden.iter() {
+        match found.get(name) {
+            Some(value) if value == expected => println!("ok       {}", name),
+            Some(value) => {
+                println!("CHANGED  {} (expected {}, found {})", name, expected, value);
+                is_passed = false;
+            }
+            None => {
+                println!("MISSING  {}", name);
+                is_passed = false;
+            }
+        }
+    }
+    for name in found.keys().filter(|name| !golden.contains_key(*name)) {
+        println!("NEW      {}", name);
+        is_passed = false;
+    }
+    is_passed
+}
+
+//Builds the bundled reference run in a scratch workspace and compares every output against the
+//golden checksums. Returns false if anything differs. With bless the golden checksums are replaced
+//by the new ones instead, for changes that are meant to change the outputs.
+pub fn run_regression(bless: bool) -> Result<bool, EVBError> {
+    let workspace_dir =
+        std::env::temp_dir().join(format!("cebra_evb_regression_{}", std::process::id()));
+    if workspace_dir.exists() {
+        std::fs::remove_dir_all(&workspace_dir)?;
+    }
+    let result = build_reference(&workspace_dir);
+    std::fs::remove_dir_all(&workspace_dir)?;
+    let (checksums, counts) = result?;
+
+    if bless {
+        std::fs::write(GOLDEN_PATH, serde_yaml::to_string(&checksums)?)?;
+        std::fs::write(COUNTS_PATH, serde_yaml::to_string(&counts)?)?;
+        println!(
+            "Wrote {} checksums to {} and {} counts to {}, rebuild to use them",
+            checksums.len(),
+            GOLDEN_PATH,
+            counts.len(),
+            COUNTS_PATH
+        );
+        return Ok(true);
+    }
+
+    let golden = serde_yaml::from_str::<Option<BTreeMap<String, String>>>(GOLDEN_CHECKSUMS)?
+        .unwrap_or_default();
+    let is_passed = compare_golden(&golden, &checksums);
+    //Both are compared whatever the first gives, so a change shows with its counts
+    let is_passed = compare_golden(&get_reference_counts()?, &counts) && is_passed;
+    Ok(is_passed)
+}
+
+//The counts of the reference build, as checked by the regression build
+pub fn get_reference_counts() -> Result<BTreeMap<String, u64>, EVBError> {
+    Ok(serde_yaml::from_str::<Option<BTreeMap<String, u64>>>(GOLDEN_COUNTS)?.unwrap_or_default())
+}
+
+//Writes the reference run into the archive directory of a workspace, and the config it is built
+//with as the config of the workspace, for trying a new install on a run with known results.
+//Building it should give the reference counts, whatever the number of threads. An existing config
+//is never replaced. Returns the path of the config.
+pub fn write_example_workspace(dir: &Path) -> Result<PathBuf, EVBError> {
+    let workspace =
+        Workspace::new(dir).map_err(|e| EVBError::Config(format!("{}: {}", dir.display(), e)))?;
+    let config_path = workspace.get_config_path();
+    if config_path.exists() {
+        return Err(EVBError::Config(format!(
+            "{} already has a config",
+            dir.display()
+        )));
+    }
+    write_reference_archive(&workspace)?;
+    let mut config = serde_yaml::from_str::<EvbAppParams>(REFERENCE_CONFIG)?;
+    config.workspace = Some(workspace);
+    std::fs::write(&config_path, serde_yaml::to_string(&config)?)?;
+    Ok(config_path)
+}
@@ -0,0 +1,105 @@
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use log::info;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::error::EVBError;
+use super::formatting::format_bytes;
+
+const BYTES_PER_MEGABYTE: usize = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    //Most threads a build uses for decoding, building and encoding the parquet columns, 0 for
+    //one per core
+    pub threads: usize,
+    //A table of a run is written out as a fragment (a piece when streamed) once it holds this
+    //much
+    pub memory_budget_mb: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            threads: 0,
+            memory_budget_mb: 8000,
+        }
+    }
+}
+
+impl ResourceLimits {
+    pub fn get_threads(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
+
+    pub fn get_memory_budget_bytes(&self) -> usize {
+        self.memory_budget_mb.saturating_mul(BYTES_PER_MEGABYTE)
+    }
+
+    pub fn log_summary(&self) {
+        info!(
+            "Building with at most {} threads and a memory budget of {} per table",
+            self.get_threads(),
+            format_bytes(self.get_memory_budget_bytes())
+        );
+    }
+}
+
+//Set from the command line, these replace the limits of the config for every build
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceOverrides {
+    pub threads: Option<usize>,
+    pub memory_budget_mb: Option<usize>,
+}
+
+impl ResourceOverrides {
+    pub fn apply(&self, limits: &mut ResourceLimits) {
+        if let Some(threads) = self.threads {
+            limits.threads = threads;
+        }
+        if let Some(memory_budget_mb) = self.memory_budget_mb {
+            limits.memory_budget_mb = memory_budget_mb;
+        }
+    }
+}
+
+thread_local! {
+    //The pool the parquet columns written on this thread are encoded on, None for the global pool
+    static ENCODE_POOL: RefCell<Option<Arc<ThreadPool>>> = RefCell::new(None);
+}
+
+//Encodes the parquet columns written on the current thread on a pool of its own until dropped,
+//instead of the global pool which has one thread per core
+pub struct EncodePoolGuard;
+
+impl EncodePoolGuard {
+    pub fn new(threads: usize) -> Result<Self, EVBError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|index| format!("encode-{}", index))
+            .build()
+            .map_err(|x| EVBError::Resource(x.to_string()))?;
+        ENCODE_POOL.with(|current| *current.borrow_mut() = Some(Arc::new(pool)));
+        Ok(EncodePoolGuard)
+    }
+}
+
+impl Drop for EncodePoolGuard {
+    fn drop(&mut self) {
+        ENCODE_POOL.with(|current| *current.borrow_mut() = None);
+    }
+}
+
+//Runs the operation on the encode pool of this thread
+pub fn run_on_encode_pool<R: Send>(operation: impl FnOnce() -> R + Send) -> R {
+    let pool = ENCODE_POOL.with(|current| current.borrow().clone());
+    match pool {
+        Some(pool) => pool.install(operation),
+        None => operation(),
+    }
+}
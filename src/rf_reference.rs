@@ -0,0 +1,75 @@
+use std::collections::{HashSet, VecDeque};
+
+use log::info;
+
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::formatting::format_count;
+
+//RF hits are kept this many coincidence windows back from the latest one, more than an event can
+//lag behind the hits read when it is built
+const KEEP_WINDOWS: f64 = 10.0;
+
+//The beam pulse reference of a pulsed beam: hits of the channels mapped as RF. As with scalers,
+//they are taken out of the hit stream before the event builder, so they are never part of an
+//event. Each event gets its time since the last RF hit before it instead.
+#[derive(Debug)]
+pub struct RfReference {
+    uuids: HashSet<u32>,
+    //RF hit times (ns), in time order
+    times: VecDeque<f64>,
+    keep: f64,
+    hits: u64,
+}
+
+impl RfReference {
+    pub fn new(channel_map: &ChannelMap, coincidence_window: f64) -> Option<Self> {
+        if !channel_map.has_channel_type(ChannelType::Rf) {
+            return None;
+        }
+        Some(RfReference {
+            uuids: channel_map.get_uuids_of_type(ChannelType::Rf),
+            times: VecDeque::new(),
+            keep: KEEP_WINDOWS * coincidence_window,
+            hits: 0,
+        })
+    }
+
+    //Whether a hit belongs to an RF channel, keeping its time if so
+    pub fn add_hit(&mut self, hit: &CompassData) -> bool {
+        if !self.uuids.contains(&hit.uuid) {
+            return false;
+        }
+        self.hits += 1;
+        self.times.push_back(hit.timestamp);
+        //The last RF hit before the cut is still needed for the events just after it
+        while self.times.len() > 1 && self.times[1] < hit.timestamp - self.keep {
+            self.times.pop_front();
+        }
+        true
+    }
+
+    //Time (ns) from the last RF hit at or before the given time, None before the first RF hit
+    pub fn get_time_to_rf(&self, time: f64) -> Option<f64> {
+        let after = self.times.partition_point(|rf_time| *rf_time <= time);
+        after.checked_sub(1).map(|index| time - self.times[index])
+    }
+
+    //Times after a run boundary are on a new clock
+    pub fn end_run_segment(&mut self) {
+        self.times.clear();
+    }
+
+    pub fn log_summary(&self) {
+        if self.hits == 0 {
+            emit_warning(
+                "rf_missing",
+                String::new(),
+                String::from("RF channel has no hits, every TimeToRF is invalid"),
+            );
+            return;
+        }
+        info!("RF reference: {} hits", format_count(self.hits));
+    }
+}
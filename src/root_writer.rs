@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+use super::error::EVBError;
+
+pub fn is_root_available() -> bool {
+    cfg!(feature = "root")
+}
+
+//The ROOT file of the table written to filepath, events.parquet gives events.root
+pub fn get_root_path(filepath: &Path) -> PathBuf {
+    filepath.with_extension("root")
+}
+
+//One f64 branch per column, in the column order, in a tree named after the file (events,
+//delayed_0, ...). Nulls left by post batch hooks become the invalid value.
+//List columns of expanded detectors are left out, they are only in the parquet file.
+#[cfg(feature = "root")]
+pub fn write_event_root(df: &DataFrame, filepath: &Path) -> Result<(), EVBError> {
+    use super::channel_data::INVALID_VALUE;
+
+    let name = filepath
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("events");
+    let mut tree = oxyroot::WriterTree::new(name);
+    for series in df.get_columns() {
+        if matches!(series.dtype(), DataType::List(_)) {
+            continue;
+        }
+        let values: Vec<f64> = series
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .map(|value| value.unwrap_or(INVALID_VALUE))
+            .collect();
+        tree.new_branch(series.name(), values.into_iter());
+    }
+    let to_error = |x| EVBError::Root(format!("{}: {}", filepath.display(), x));
+    let mut file = oxyroot::RootFile::create(filepath).map_err(to_error)?;
+    tree.write(&mut file).map_err(to_error)?;
+    file.close().map_err(to_error)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "root"))]
+pub fn write_event_root(_df: &DataFrame, filepath: &Path) -> Result<(), EVBError> {
+    Err(EVBError::Root(format!(
+        "{}: the event builder was built without the root feature",
+        filepath.display()
+    )))
+}
@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::{DeflateDecoder, GzDecoder};
+use tar::Archive;
+
+use super::error::EVBError;
+
+//Extensions of a run archive, in the order they are looked for in the archive directory
+const ARCHIVE_EXTENSIONS: [&str; 4] = ["tar.gz", "tgz", "tar", "zip"];
+
+const ZIP_END_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ZIP_CENTRAL_SIGNATURE: u32 = 0x02014b50;
+const ZIP_LOCAL_SIGNATURE: u32 = 0x04034b50;
+//The end record is 22 bytes, followed by a comment of up to 64 kB
+const ZIP_END_SIZE: usize = 22;
+const ZIP_MAX_COMMENT: usize = 65535;
+//Sizes and offsets too large for the classic records are 0xFFFFFFFF there, and in a zip64 field
+const ZIP64_MARKER: u32 = 0xFFFFFFFF;
+const ZIP64_EXTRA_ID: u16 = 0x0001;
+const ZIP_STORED: u16 = 0;
+const ZIP_DEFLATED: u16 = 8;
+
+//The archive of a run, run_<num> with any of the archive extensions, or None if there is none
+pub fn find_run_archive(archive_dir: &Path, run: i32) -> Option<PathBuf> {
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .map(|extension| archive_dir.join(format!("run_{}.{}", run, extension)))
+        .find(|path| path.exists())
+}
+
+//Decompresses the archive straight into the unpack directory as it is read
+pub fn unpack_run_archive(archive_path: &Path, unpack_dir: &Path) -> Result<(), EVBError> {
+    let name = archive_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let file = File::open(archive_path)?;
+    if name.ends_with(".zip") {
+        unpack_zip(file, unpack_dir)
+    } else if name.ends_with(".tar") {
+        Ok(Archive::new(file).unpack(unpack_dir)?)
+    } else {
+        Ok(Archive::new(GzDecoder::new(file)).unpack(unpack_dir)?)
+    }
+}
+
+fn get_bytes<const N: usize>(buffer: &[u8], at: usize) -> Result<[u8; N], EVBError> {
+    buffer
+        .get(at..at + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| EVBError::Archive(String::from("zip record is cut short")))
+}
+
+fn get_u16(buffer: &[u8], at: usize) -> Result<u16, EVBError> {
+    Ok(u16::from_le_bytes(get_bytes(buffer, at)?))
+}
+
+fn get_u32(buffer: &[u8], at: usize) -> Result<u32, EVBError> {
+    Ok(u32::from_le_bytes(get_bytes(buffer, at)?))
+}
+
+fn get_u64(buffer: &[u8], at: usize) -> Result<u64, EVBError> {
+    Ok(u64::from_le_bytes(get_bytes(buffer, at)?))
+}
+
+//A file in a zip archive, as listed in its central directory
+struct ZipEntry {
+    name: String,
+    method: u16,
+    compressed_size: u64,
+    local_offset: u64,
+}
+
+//Where the central directory starts and how many entries it has, from the end record (or its
+//zip64 version for archives over 4 GB)
+fn read_zip_directory_location(file: &mut File) -> Result<(u64, u64), EVBError> {
+    let size = file.metadata()?.len();
+    let tail_size = size.min((ZIP_END_SIZE + ZIP_MAX_COMMENT) as u64);
+    file.seek(SeekFrom::Start(size - tail_size))?;
+    let mut tail = vec![0; tail_size as usize];
+    file.read_exact(&mut tail)?;
+
+    let end = (0..=tail.len().saturating_sub(ZIP_END_SIZE))
+        .rev()
+        .find(|&at| get_u32(&tail, at).is_ok_and(|word| word == ZIP_END_SIGNATURE))
+        .ok_or_else(|| EVBError::Archive(String::from("not a zip archive")))?;
+    let mut entries = get_u16(&tail, end + 10)? as u64;
+    let mut directory_offset = get_u32(&tail, end + 16)? as u64;
+
+    //The zip64 locator is the 20 bytes right before the end record
+    if end >= 20 && get_u32(&tail, end - 20)? == ZIP64_LOCATOR_SIGNATURE {
+        file.seek(SeekFrom::Start(get_u64(&tail, end - 12)?))?;
+        let mut record = [0; 56];
+        file.read_exact(&mut record)?;
+        if get_u32(&record, 0)? != ZIP64_END_SIGNATURE {
+            return Err(EVBError::Archive(String::from(
+                "zip64 end record is missing",
+            )));
+        }
+        entries = get_u64(&record, 32)?;
+        directory_offset = get_u64(&record, 48)?;
+    }
+    Ok((directory_offset, entries))
+}
+
+fn read_zip_entries(file: &mut File) -> Result<Vec<ZipEntry>, EVBError> {
+    let (directory_offset, count) = read_zip_directory_location(file)?;
+    file.seek(SeekFrom::Start(directory_offset))?;
+    let mut reader = BufReader::new(file);
+    let mut entries = vec![];
+    for _ in 0..count {
+        let mut header = [0; 46];
+        reader.read_exact(&mut header)?;
+        if get_u32(&header, 0)? != ZIP_CENTRAL_SIGNATURE {
+            return Err(EVBError::Archive(String::from(
+                "zip central directory is corrupted",
+            )));
+        }
+        let mut name = vec![0; get_u16(&header, 28)? as usize];
+        reader.read_exact(&mut name)?;
+        let mut extra = vec![0; get_u16(&header, 30)? as usize];
+        reader.read_exact(&mut extra)?;
+        let mut comment = vec![0; get_u16(&header, 32)? as usize];
+        reader.read_exact(&mut comment)?;
+
+        let uncompressed_size = get_u32(&header, 24)?;
+        let mut compressed_size = get_u32(&header, 20)? as u64;
+        let mut local_offset = get_u32(&header, 42)? as u64;
+        //The zip64 field has only the values that overflowed, in this order
+        let mut at = 0;
+        while at + 4 <= extra.len() {
+            let id = get_u16(&extra, at)?;
+            let size = get_u16(&extra, at + 2)? as usize;
+            if id == ZIP64_EXTRA_ID {
+                let mut field = at + 4;
+                if uncompressed_size == ZIP64_MARKER {
+                    field += 8;
+                }
+                if compressed_size == ZIP64_MARKER as u64 {
+                    compressed_size = get_u64(&extra, field)?;
+                    field += 8;
+                }
+                if local_offset == ZIP64_MARKER as u64 {
+                    local_offset = get_u64(&extra, field)?;
+                }
+            }
+            at += 4 + size;
+        }
+
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(&name).to_string(),
+            method: get_u16(&header, 10)?,
+            compressed_size,
+            local_offset,
+        });
+    }
+    Ok(entries)
+}
+
+//As with tar, entries must stay inside the unpack directory
+fn get_entry_path(unpack_dir: &Path, name: &str) -> Result<PathBuf, EVBError> {
+    let relative = Path::new(name);
+    if relative
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(EVBError::Archive(format!(
+            "zip entry {} points outside of the archive",
+            name
+        )));
+    }
+    Ok(unpack_dir.join(relative))
+}
+
+//Reads the entries through the central directory, so files written with data descriptors (sizes
+//only after the data) are read as well. Only stored and deflated entries are supported.
+fn unpack_zip(mut file: File, unpack_dir: &Path) -> Result<(), EVBError> {
+    let entries = read_zip_entries(&mut file)?;
+    for entry in entries.iter() {
+        let path = get_entry_path(unpack_dir, &entry.name)?;
+        if entry.name.ends_with('/') {
+            std::fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        file.seek(SeekFrom::Start(entry.local_offset))?;
+        let mut header = [0; 30];
+        file.read_exact(&mut header)?;
+        if get_u32(&header, 0)? != ZIP_LOCAL_SIGNATURE {
+            return Err(EVBError::Archive(format!(
+                "zip entry {} has no local header",
+                entry.name
+            )));
+        }
+        let skip = get_u16(&header, 26)? as i64 + get_u16(&header, 28)? as i64;
+        file.seek(SeekFrom::Current(skip))?;
+
+        let mut data = BufReader::new((&file).take(entry.compressed_size));
+        let mut output = File::create(&path)?;
+        match entry.method {
+            ZIP_STORED => std::io::copy(&mut data, &mut output)?,
+            ZIP_DEFLATED => std::io::copy(&mut DeflateDecoder::new(data), &mut output)?,
+            method => {
+                return Err(EVBError::Archive(format!(
+                    "zip entry {} uses compression method {}, only stored and deflated entries \
+                    are supported",
+                    entry.name, method
+                )))
+            }
+        };
+    }
+    Ok(())
+}
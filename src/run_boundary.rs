@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::hit_source::{HitSource, Waiting};
+use super::used_size::UsedSize;
+
+//Some archived data has several runs concatenated into one file per channel. The timestamps of a
+//file then jump back to near zero wherever a new run starts, and such a reset is taken as a
+//boundary between runs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunBoundaryParams {
+    pub enabled: bool,
+    //A hit this much earlier than the hit before it in the same file starts a new run
+    pub min_reset_seconds: f64,
+    //Write the event, delayed, hit and quick-look tables of every run in the file separately
+    pub split_output: bool,
+}
+
+impl Default for RunBoundaryParams {
+    fn default() -> Self {
+        RunBoundaryParams {
+            enabled: false,
+            min_reset_seconds: 1.0,
+            split_output: false,
+        }
+    }
+}
+
+//Times of the hits either side of a boundary (ns)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResetTimes {
+    pub last_time: f64,
+    pub next_time: f64,
+}
+
+//Written to the report for every boundary found in a run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunBoundary {
+    //The run in the file that ends at this boundary, counted from 1
+    pub part: usize,
+    pub events: u64,
+    pub last_time: f64,
+    pub next_time: f64,
+}
+
+//The latest hit before and the earliest hit after the boundary all stopped sources are waiting at,
+//or None if no source is at a boundary
+pub fn get_reset_times(files: &[Box<dyn HitSource + '_>]) -> Option<ResetTimes> {
+    let mut reset: Option<ResetTimes> = None;
+    let mut sources_at_boundary = 0;
+    for times in files.iter().filter_map(|file| file.get_boundary()) {
+        sources_at_boundary += 1;
+        reset = Some(match reset {
+            Some(found) => ResetTimes {
+                last_time: found.last_time.max(times.last_time),
+                next_time: found.next_time.min(times.next_time),
+            },
+            None => times,
+        });
+    }
+    if reset.is_some() && sources_at_boundary < files.len() {
+        emit_warning(
+            "run_boundary_sources",
+            String::new(),
+            format!(
+                "Only {} of {} input files have a run boundary here, the others have ended",
+                sources_at_boundary,
+                files.len()
+            ),
+        );
+    }
+    reset
+}
+
+//Stops at the first timestamp reset and holds it back, with a default top hit, until the next run
+//is started. The run merges whatever the other sources have left first, so every run of the file
+//is built on its own.
+pub struct BoundarySource<'a> {
+    inner: Box<dyn HitSource + 'a>,
+    min_reset: f64,
+    top_time: Option<f64>,
+    last_time: Option<f64>,
+    boundary: Option<ResetTimes>,
+    boundary_hit: CompassData,
+}
+
+impl<'a> BoundarySource<'a> {
+    pub fn new(inner: Box<dyn HitSource + 'a>, params: &RunBoundaryParams) -> Self {
+        BoundarySource {
+            inner,
+            min_reset: params.min_reset_seconds * 1.0e9,
+            top_time: None,
+            last_time: None,
+            boundary: None,
+            boundary_hit: CompassData::default(),
+        }
+    }
+}
+
+impl HitSource for BoundarySource<'_> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.boundary.is_none() {
+            let hit = self.inner.get_top_hit()?;
+            self.top_time = if hit.is_default() {
+                None
+            } else {
+                Some(hit.timestamp)
+            };
+            if let (Some(next_time), Some(last_time)) = (self.top_time, self.last_time) {
+                if next_time < last_time - self.min_reset {
+                    self.boundary = Some(ResetTimes {
+                        last_time,
+                        next_time,
+                    });
+                }
+            }
+        }
+        match self.boundary {
+            Some(_) => Ok(&self.boundary_hit),
+            None => self.inner.get_top_hit(),
+        }
+    }
+
+    //The held hit stays until the next run is started
+    fn set_hit_used(&mut self) {
+        if self.boundary.is_none() {
+            if self.top_time.is_some() {
+                self.last_time = self.top_time;
+            }
+            self.inner.set_hit_used();
+        }
+    }
+
+    fn is_eof(&self) -> bool {
+        self.inner.is_eof()
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.inner.get_number_of_hits()
+    }
+
+    fn get_boundary(&self) -> Option<ResetTimes> {
+        self.boundary
+    }
+
+    fn start_next_run(&mut self) {
+        self.inner.start_next_run();
+        self.boundary = None;
+        self.last_time = None;
+    }
+
+    fn get_waiting(&self) -> Option<Waiting> {
+        self.inner.get_waiting()
+    }
+}
+
+impl UsedSize for BoundarySource<'_> {
+    fn get_used_size(&self) -> usize {
+        self.inner.get_used_size()
+    }
+}
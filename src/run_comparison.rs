@@ -0,0 +1,367 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+use super::error::EVBError;
+use super::histogram::{
+    build_histogram, read_columns, read_time_unit, DiagnosticKind, DiagnosticParams, Histogram,
+};
+use super::invalid_values::ColumnInvalidValues;
+use super::run_layout::{RunLayout, EVENTS_STEM};
+
+//Which runs to compare, and the time difference whose prompt peak width is tracked
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonParams {
+    pub first_run: i32,
+    pub last_run: i32,
+    pub prompt_column: String,
+    pub prompt_reference: String,
+    pub prompt_bins: usize,
+    pub prompt_min: f64,
+    pub prompt_max: f64,
+}
+
+impl Default for ComparisonParams {
+    fn default() -> Self {
+        ComparisonParams {
+            first_run: 0,
+            last_run: 0,
+            prompt_column: String::from("Cebra0Time"),
+            prompt_reference: String::from("Cebra1Time"),
+            prompt_bins: 400,
+            prompt_min: -200.0,
+            prompt_max: 200.0,
+        }
+    }
+}
+
+//Key numbers of one built run, from its events files
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    pub run_number: i32,
+    pub events: u64,
+    //From the earliest to the latest hit time in the events
+    pub duration_seconds: f64,
+    //Hits of each detector, in the order of the detector names of the comparison
+    pub detector_hits: Vec<u64>,
+    //Events by the number of detectors hit
+    pub multiplicity: Vec<u64>,
+    pub prompt_fwhm: Option<f64>,
+}
+
+impl RunMetrics {
+    fn get_rate(&self, count: u64) -> f64 {
+        if self.duration_seconds > 0.0 {
+            count as f64 / self.duration_seconds
+        } else {
+            0.0
+        }
+    }
+
+    pub fn get_event_rate(&self) -> f64 {
+        self.get_rate(self.events)
+    }
+
+    pub fn get_detector_rate(&self, detector: usize) -> f64 {
+        self.get_rate(self.detector_hits[detector])
+    }
+
+    pub fn get_mean_multiplicity(&self) -> f64 {
+        let hits: u64 = self
+            .multiplicity
+            .iter()
+            .enumerate()
+            .map(|(detectors, events)| detectors as u64 * events)
+            .sum();
+        if self.events > 0 {
+            hits as f64 / self.events as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+//The Cebra<n>Time columns, one per detector of the channel map the run was built with
+pub fn is_detector_time(name: &str) -> bool {
+    name.strip_prefix("Cebra")
+        .and_then(|rest| rest.strip_suffix("Time"))
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+//The whole table of a run, or every fragment of a fragmented one
+pub fn get_table_paths(layout: &RunLayout, stem: &str) -> Vec<PathBuf> {
+    let whole = layout.get_table_path(stem, None);
+    if whole.exists() {
+        return vec![whole];
+    }
+    (0..)
+        .map(|fragment| layout.get_table_path(stem, Some(fragment)))
+        .take_while(|path| path.exists())
+        .collect()
+}
+
+pub fn get_events_paths(layout: &RunLayout) -> Vec<PathBuf> {
+    get_table_paths(layout, EVENTS_STEM)
+}
+
+//Hits per detector, the earliest and latest hit times, and the multiplicity of every event
+fn read_file_counts(
+    filepath: &Path,
+    time_columns: &[String],
+) -> Result<(Vec<u64>, f64, f64, Vec<u64>), EVBError> {
+    if time_columns.is_empty() {
+        return Ok((vec![], f64::MAX, f64::MIN, vec![0]));
+    }
+    let invalid = ColumnInvalidValues::read(filepath)?;
+    let mut exprs: Vec<Expr> = vec![];
+    for (index, name) in time_columns.iter().enumerate() {
+        exprs.push(
+            invalid
+                .get_valid(name)
+                .cast(DataType::UInt64)
+                .sum()
+                .alias(&format!("hits_{}", index)),
+        );
+        exprs.push(
+            col(name)
+                .filter(invalid.get_valid(name))
+                .min()
+                .alias(&format!("min_{}", index)),
+        );
+        exprs.push(
+            col(name)
+                .filter(invalid.get_valid(name))
+                .max()
+                .alias(&format!("max_{}", index)),
+        );
+    }
+    let summary = LazyFrame::scan_parquet(filepath, ScanArgsParquet::default())?
+        .select(exprs)
+        .collect()?;
+    let mut hits = vec![];
+    let mut first_time = f64::MAX;
+    let mut last_time = f64::MIN;
+    for index in 0..time_columns.len() {
+        hits.push(
+            summary
+                .column(&format!("hits_{}", index))?
+                .u64()?
+                .get(0)
+                .unwrap_or(0),
+        );
+        if let Some(time) = summary.column(&format!("min_{}", index))?.f64()?.get(0) {
+            first_time = first_time.min(time);
+        }
+        if let Some(time) = summary.column(&format!("max_{}", index))?.f64()?.get(0) {
+            last_time = last_time.max(time);
+        }
+    }
+
+    let mut multiplicity: Vec<u64> = vec![0; time_columns.len() + 1];
+    if let Some(detectors) = time_columns
+        .iter()
+        .map(|name| invalid.get_valid(name).cast(DataType::UInt32))
+        .reduce(|sum, valid| sum + valid)
+    {
+        let df = LazyFrame::scan_parquet(filepath, ScanArgsParquet::default())?
+            .select([detectors.alias("multiplicity")])
+            .collect()?;
+        for detectors in df.column("multiplicity")?.u32()?.into_iter().flatten() {
+            multiplicity[detectors as usize] += 1;
+        }
+    }
+    Ok((hits, first_time, last_time, multiplicity))
+}
+
+fn read_run_metrics(
+    params: &ComparisonParams,
+    paths: &[PathBuf],
+    run_number: i32,
+    time_columns: &[String],
+) -> Result<RunMetrics, EVBError> {
+    let prompt = DiagnosticParams {
+        kind: DiagnosticKind::TimeDifference,
+        column: params.prompt_column.clone(),
+        reference_column: params.prompt_reference.clone(),
+        bins: params.prompt_bins,
+        min: params.prompt_min,
+        max: params.prompt_max,
+    };
+    let mut metrics = RunMetrics {
+        run_number,
+        events: 0,
+        duration_seconds: 0.0,
+        detector_hits: vec![0; time_columns.len()],
+        multiplicity: vec![0; time_columns.len() + 1],
+        prompt_fwhm: None,
+    };
+    let mut prompt_histogram: Option<Histogram> = None;
+    let mut first_time = f64::MAX;
+    let mut last_time = f64::MIN;
+    for path in paths.iter() {
+        let columns = read_columns(path)?;
+        //Earlier runs can have fewer detectors, their missing columns count as no hits
+        let present: Vec<String> = time_columns
+            .iter()
+            .filter(|name| columns.contains(name))
+            .cloned()
+            .collect();
+        let (hits, first, last, multiplicity) = read_file_counts(path, &present)?;
+        for (name, count) in present.iter().zip(hits) {
+            if let Some(index) = time_columns.iter().position(|column| column == name) {
+                metrics.detector_hits[index] += count;
+            }
+        }
+        for (detectors, events) in multiplicity.into_iter().enumerate() {
+            metrics.multiplicity[detectors] += events;
+        }
+        metrics.events +=
+            polars_parquet::read::read_metadata(&mut File::open(path)?)?.num_rows as u64;
+        first_time = first_time.min(first);
+        last_time = last_time.max(last);
+
+        if columns.contains(&prompt.column) && columns.contains(&prompt.reference_column) {
+            let histogram = build_histogram(&prompt, path, run_number)?;
+            match &mut prompt_histogram {
+                Some(total) => total
+                    .counts
+                    .iter_mut()
+                    .zip(histogram.counts)
+                    .for_each(|(total, count)| *total += count),
+                None => prompt_histogram = Some(histogram),
+            }
+        }
+    }
+    if last_time > first_time {
+        let units_per_second = read_time_unit(&paths[0])?.convert_nanoseconds(1.0e9);
+        metrics.duration_seconds = (last_time - first_time) / units_per_second;
+    }
+    metrics.prompt_fwhm = prompt_histogram.and_then(|histogram| histogram.get_fwhm());
+    Ok(metrics)
+}
+
+//The metrics of every built run in the range, for watching a campaign for changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunComparison {
+    pub first_run: i32,
+    pub last_run: i32,
+    //Cebra0, Cebra1, ... for every detector found in any of the runs
+    pub detectors: Vec<String>,
+    pub runs: Vec<RunMetrics>,
+}
+
+impl RunComparison {
+    //Runs that were not built are left out
+    pub fn new(params: &ComparisonParams, output_dir: &Path) -> Result<Self, EVBError> {
+        let run_paths: Vec<(i32, Vec<PathBuf>)> = (params.first_run..=params.last_run)
+            .map(|run| (run, get_events_paths(&RunLayout::new(output_dir, run))))
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect();
+
+        let mut time_columns: Vec<String> = vec![];
+        for (_, paths) in run_paths.iter() {
+            for name in read_columns(&paths[0])? {
+                if is_detector_time(&name) && !time_columns.contains(&name) {
+                    time_columns.push(name);
+                }
+            }
+        }
+        time_columns.sort_by_key(|name| {
+            name.trim_start_matches("Cebra")
+                .trim_end_matches("Time")
+                .parse::<usize>()
+                .unwrap_or(usize::MAX)
+        });
+
+        let runs = run_paths
+            .iter()
+            .map(|(run, paths)| read_run_metrics(params, paths, *run, &time_columns))
+            .collect::<Result<Vec<RunMetrics>, EVBError>>()?;
+        Ok(RunComparison {
+            first_run: params.first_run,
+            last_run: params.last_run,
+            detectors: time_columns
+                .iter()
+                .map(|name| name.trim_end_matches("Time").to_string())
+                .collect(),
+            runs,
+        })
+    }
+
+    //One bar per run of the range, so runs that were not built show as gaps
+    fn get_run_histogram(
+        &self,
+        title: String,
+        y_label: &str,
+        value: impl Fn(&RunMetrics) -> f64,
+    ) -> Histogram {
+        let mut histogram = Histogram::new(
+            title,
+            String::from("Run"),
+            y_label.to_string(),
+            (self.last_run - self.first_run + 1).max(1) as usize,
+            self.first_run as f64 - 0.5,
+            self.last_run as f64 + 0.5,
+        );
+        for run in self.runs.iter() {
+            let bin = (run.run_number - self.first_run) as usize;
+            if let Some(count) = histogram.counts.get_mut(bin) {
+                *count = value(run);
+            }
+        }
+        histogram
+    }
+
+    //Events, event rate, mean multiplicity, prompt peak width and the rate of every detector, each
+    //against the run number
+    pub fn get_trend_plots(&self) -> Vec<Histogram> {
+        let mut plots = vec![
+            self.get_run_histogram(String::from("Events"), "Events", |run| run.events as f64),
+            self.get_run_histogram(String::from("Event Rate"), "Rate (Hz)", |run| {
+                run.get_event_rate()
+            }),
+            self.get_run_histogram(String::from("Mean Multiplicity"), "Detectors", |run| {
+                run.get_mean_multiplicity()
+            }),
+            self.get_run_histogram(String::from("Prompt Peak FWHM"), "FWHM", |run| {
+                run.prompt_fwhm.unwrap_or(0.0)
+            }),
+        ];
+        for (index, detector) in self.detectors.iter().enumerate() {
+            plots.push(
+                self.get_run_histogram(format!("{} Rate", detector), "Rate (Hz)", |run| {
+                    run.get_detector_rate(index)
+                }),
+            );
+        }
+        plots
+    }
+
+    //The multiplicity distribution of every run, on the same axis
+    pub fn get_multiplicity_plots(&self) -> Vec<Histogram> {
+        let max_detectors = self
+            .runs
+            .iter()
+            .filter_map(|run| run.multiplicity.iter().rposition(|events| *events > 0))
+            .max()
+            .unwrap_or(0);
+        self.runs
+            .iter()
+            .map(|run| {
+                let mut histogram = Histogram::new(
+                    format!("Run {} Multiplicity", run.run_number),
+                    String::from("Detectors"),
+                    String::from("Events"),
+                    max_detectors + 1,
+                    -0.5,
+                    max_detectors as f64 + 0.5,
+                );
+                for (bin, events) in run.multiplicity.iter().take(max_detectors + 1).enumerate() {
+                    histogram.counts[bin] = *events as f64;
+                }
+                histogram
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+const SPECTRA_DIR: &str = "spectra";
+const LOGS_DIR: &str = "logs";
+const WAVEFORMS_DIR: &str = "waveforms";
+
+pub const EVENTS_STEM: &str = "events";
+pub const SINGLES_STEM: &str = "singles";
+pub const DELAYED_STEM: &str = "delayed";
+pub const HITS_STEM: &str = "hits";
+pub const QUICK_LOOK_STEM: &str = "quicklook";
+
+//Everything built from one run goes into built/run_<num>/:
+//  events.parquet          built events (events_<frag>.parquet when fragmented), only the
+//                          coincidences when the events are split by class
+//  singles.parquet         the single hit events, when split by class (singles_<frag>.parquet)
+//  delayed.parquet         delayed window events (delayed_<frag>.parquet)
+//  hits.parquet            raw hits, when enabled (hits_<frag>.parquet)
+//  quicklook.parquet       thinned copy of the events, when enabled
+//  (these five are <stem>_part_<part>[_<frag>].parquet when the runs of concatenated files are
+//  split, parts counted from 1)
+//  slow_control.parquet    temperature/HV logs
+//  report.json             run summary
+//  metadata.json           how the run was built, also in the file metadata of the tables
+//  scalers.txt
+//  scaler_rates.csv        counts and rates vs time of the channels mapped as Scaler
+//  coincidence_trend.csv   prompt coincidence rates vs time of every detector with a reference
+//  channel_rates.csv       hit counts and rates vs time of every channel
+//  qa.json, qa.html        statistics of every detector against a reference, when QA is on
+//  checkpoint.json         where an unfinished build stood, removed once the run is built
+//  pulser_summary.yaml     pulser runs only
+//  spectra/                gated spectra, time differences, detector spectra, histogram only
+//                          spectra and the coincidence trend plots
+//  logs/build.log          log of the build
+//  waveforms/              samples of every hit per channel, when written
+#[derive(Debug, Clone)]
+pub struct RunLayout {
+    dir: PathBuf,
+    part: Option<usize>,
+}
+
+impl RunLayout {
+    pub fn new(output_dir: &Path, run_number: i32) -> Self {
+        RunLayout {
+            dir: output_dir.join(format!("run_{}", run_number)),
+            part: None,
+        }
+    }
+
+    //Same directory, with the tables of one run of a concatenated file
+    pub fn with_part(&self, part: usize) -> Self {
+        RunLayout {
+            dir: self.dir.clone(),
+            part: Some(part),
+        }
+    }
+
+    pub fn create_dirs(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(self.dir.join(SPECTRA_DIR))?;
+        std::fs::create_dir_all(self.dir.join(LOGS_DIR))
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    //stem is events, delayed, hits or quicklook; fragments are numbered from 0 once a run is fragmented
+    pub fn get_table_path(&self, stem: &str, fragment: Option<i32>) -> PathBuf {
+        let stem = match self.part {
+            Some(part) => format!("{}_part_{}", stem, part),
+            None => stem.to_string(),
+        };
+        match fragment {
+            Some(frag) => self.dir.join(format!("{}_{}.parquet", stem, frag)),
+            None => self.dir.join(format!("{}.parquet", stem)),
+        }
+    }
+
+    pub fn get_slow_control_path(&self) -> PathBuf {
+        self.dir.join("slow_control.parquet")
+    }
+
+    pub fn get_report_path(&self) -> PathBuf {
+        self.dir.join("report.json")
+    }
+
+    pub fn get_metadata_path(&self) -> PathBuf {
+        self.dir.join("metadata.json")
+    }
+
+    pub fn get_scalers_path(&self) -> PathBuf {
+        self.dir.join("scalers.txt")
+    }
+
+    pub fn get_scaler_rates_path(&self) -> PathBuf {
+        self.dir.join("scaler_rates.csv")
+    }
+
+    pub fn get_coincidence_trend_path(&self) -> PathBuf {
+        self.dir.join("coincidence_trend.csv")
+    }
+
+    pub fn get_channel_rates_path(&self) -> PathBuf {
+        self.dir.join("channel_rates.csv")
+    }
+
+    pub fn get_qa_report_path(&self) -> PathBuf {
+        self.dir.join("qa.json")
+    }
+
+    pub fn get_qa_html_path(&self) -> PathBuf {
+        self.dir.join("qa.html")
+    }
+
+    pub fn get_checkpoint_path(&self) -> PathBuf {
+        self.dir.join("checkpoint.json")
+    }
+
+    pub fn get_pulser_summary_path(&self) -> PathBuf {
+        self.dir.join("pulser_summary.yaml")
+    }
+
+    pub fn get_spectra_dir(&self) -> PathBuf {
+        self.dir.join(SPECTRA_DIR)
+    }
+
+    pub fn get_state_spectra_path(&self) -> PathBuf {
+        self.dir.join(SPECTRA_DIR).join("state_spectra.csv")
+    }
+
+    pub fn get_time_differences_path(&self) -> PathBuf {
+        self.dir.join(SPECTRA_DIR).join("time_differences.csv")
+    }
+
+    pub fn get_detector_spectra_path(&self) -> PathBuf {
+        self.dir.join(SPECTRA_DIR).join("detector_spectra.csv")
+    }
+
+    pub fn get_channel_spectra_path(&self) -> PathBuf {
+        self.dir.join(SPECTRA_DIR).join("channel_spectra.csv")
+    }
+
+    pub fn get_waveform_dir(&self) -> PathBuf {
+        self.dir.join(WAVEFORMS_DIR)
+    }
+
+    pub fn get_log_path(&self) -> PathBuf {
+        self.dir.join(LOGS_DIR).join("build.log")
+    }
+
+    //Every file in the run directory as (path relative to it, size in bytes), sorted by path
+    pub fn list_files(&self) -> std::io::Result<Vec<(PathBuf, u64)>> {
+        let mut files = Vec::new();
+        let mut to_visit = vec![self.dir.clone()];
+        while let Some(dir) = to_visit.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() {
+                    to_visit.push(path);
+                } else if let Ok(relative) = path.strip_prefix(&self.dir) {
+                    files.push((relative.to_path_buf(), metadata.len()));
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use strum_macros::AsRefStr;
+
+use super::error::EVBError;
+
+//A batch of runs as typed, e.g. "120-147, !133": runs and inclusive ranges separated by commas,
+//with ! in front of those to leave out. Exclusions apply wherever they are in the list.
+pub fn parse_run_list(list: &str) -> Result<Vec<i32>, String> {
+    let mut runs = BTreeSet::new();
+    let mut excluded = BTreeSet::new();
+    for item in list
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let (is_excluded, range) = match item.strip_prefix('!') {
+            Some(range) => (true, range.trim()),
+            None => (false, item),
+        };
+        //An en dash, as in run logbooks, works as well as a hyphen
+        let bounds: Vec<&str> = range.splitn(2, ['-', '\u{2013}']).map(str::trim).collect();
+        let parse = |bound: &str| {
+            bound
+                .parse::<i32>()
+                .map_err(|_| format!("'{}' is not a run or a range of runs", item))
+        };
+        let (first, last) = match bounds.as_slice() {
+            [run] => (parse(run)?, parse(run)?),
+            [first, last] => (parse(first)?, parse(last)?),
+            _ => return Err(format!("'{}' is not a run or a range of runs", item)),
+        };
+        if first > last {
+            return Err(format!("range '{}' ends before it starts", item));
+        }
+        if is_excluded {
+            excluded.extend(first..=last);
+        } else {
+            runs.extend(first..=last);
+        }
+    }
+    Ok(runs.difference(&excluded).copied().collect())
+}
+
+//The summary of a batch is named after its first and last run
+pub fn get_batch_summary_path(output_dir: &Path, first_run: i32, last_run: i32) -> PathBuf {
+    output_dir.join(format!("batch_summary_{}-{}.csv", first_run, last_run))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, AsRefStr)]
+pub enum BatchStatus {
+    #[strum(serialize = "built")]
+    Built,
+    #[strum(serialize = "failed")]
+    Failed,
+    //No archive for the run in the workspace
+    #[strum(serialize = "missing")]
+    Missing,
+    //Stopped by SIGINT or SIGTERM, written up to where it stopped
+    #[strum(serialize = "interrupted")]
+    Interrupted,
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchEntry {
+    pub run_number: i32,
+    pub status: BatchStatus,
+    pub seconds: f64,
+    //The error of a failed run
+    pub message: String,
+}
+
+//What happened to every run of a job, so one bad run does not hide behind the others
+#[derive(Debug, Clone, Default)]
+pub struct BatchSummary {
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchSummary {
+    pub fn add(&mut self, run_number: i32, status: BatchStatus, seconds: f64, message: String) {
+        self.entries.push(BatchEntry {
+            run_number,
+            status,
+            seconds,
+            message,
+        });
+    }
+
+    pub fn count(&self, status: BatchStatus) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == status)
+            .count()
+    }
+
+    pub fn log_summary(&self) {
+        let failed = self.count(BatchStatus::Failed);
+        let interrupted = self.count(BatchStatus::Interrupted);
+        let mut summary = format!(
+            "Built {} of {} runs, {} failed, {} missing",
+            self.count(BatchStatus::Built),
+            self.entries.len(),
+            failed,
+            self.count(BatchStatus::Missing)
+        );
+        if interrupted > 0 {
+            summary.push_str(&format!(", {} interrupted", interrupted));
+        }
+        if failed > 0 || interrupted > 0 {
+            warn!("{}", summary);
+            for entry in self
+                .entries
+                .iter()
+                .filter(|entry| entry.status == BatchStatus::Failed)
+            {
+                warn!("Run {} failed: {}", entry.run_number, entry.message);
+            }
+        } else {
+            info!("{}", summary);
+        }
+    }
+
+    //One row per run: run,status,seconds,message
+    pub fn write_summary(&self, filepath: &Path) -> Result<(), EVBError> {
+        let mut file = BufWriter::new(File::create(filepath)?);
+        writeln!(file, "run,status,seconds,message")?;
+        for entry in self.entries.iter() {
+            writeln!(
+                file,
+                "{},{},{:.1},\"{}\"",
+                entry.run_number,
+                entry.status.as_ref(),
+                entry.seconds,
+                entry.message.replace('"', "'")
+            )?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::Instant;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+//The most detailed records written to the log of each run. Warnings and errors are always kept,
+//so Warn is the least detailed, and Debug adds the files of the run as they are read.
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum LogLevel {
+    //Configs saved with an Error level, which dropped the warnings of the run
+    #[serde(alias = "Error")]
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn get_level(&self) -> Level {
+        match self {
+            LogLevel::Warn => Level::Warn,
+            LogLevel::Info => Level::Info,
+            LogLevel::Debug => Level::Debug,
+        }
+    }
+
+    //e.g. "debug", as given to --log-level or RUST_LOG
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "warn" => Some(LogLevel::Warn),
+            "info" => Some(LogLevel::Info),
+            "debug" => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+struct ActiveRunLog {
+    threads: Vec<ThreadId>,
+    start: Instant,
+    file: BufWriter<File>,
+    level: Level,
+}
+
+//Most detailed level of stderr, as configured in init_logger, so the level of a run is only raised
+//for as long as it is built
+static STDERR_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+fn set_max_level(run_level: Level) {
+    let stderr_level = match STDERR_LEVEL.load(Ordering::Relaxed) {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_max_level(stderr_level.max(run_level.to_level_filter()));
+}
+
+//Only records from the thread building the run end up in its log, so GUI messages stay out of it
+static ACTIVE_RUN_LOG: Mutex<Option<ActiveRunLog>> = Mutex::new(None);
+
+//env_logger for stderr (as configured in init_logger), plus the records up to the log level of the
+//run being built copied to its log
+struct RunLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for RunLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata) || metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+        if record.level() > log::max_level() {
+            return;
+        }
+        if let Ok(mut guard) = ACTIVE_RUN_LOG.lock() {
+            if let Some(run_log) = guard.as_mut() {
+                if record.level() <= run_log.level
+                    && run_log.threads.contains(&std::thread::current().id())
+                {
+                    let _ = writeln!(
+                        run_log.file,
+                        "[{:>10.3}s {:<5}] {}",
+                        run_log.start.elapsed().as_secs_f64(),
+                        record.level(),
+                        record.args()
+                    );
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Ok(mut guard) = ACTIVE_RUN_LOG.lock() {
+            if let Some(run_log) = guard.as_mut() {
+                let _ = run_log.file.flush();
+            }
+        }
+    }
+}
+
+//Use in place of env_logger::init() so that every run gets its logs/build.log. stderr is filtered
+//as configured by RUST_LOG, or else at the level given, or else to errors only.
+pub fn init_logger(stderr_level: Option<LogLevel>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if let Some(level) = stderr_level.filter(|_| std::env::var_os("RUST_LOG").is_none()) {
+        builder.filter_level(level.get_level().to_level_filter());
+    }
+    let inner = builder.build();
+    STDERR_LEVEL.store(inner.filter() as usize, Ordering::Relaxed);
+    if log::set_boxed_logger(Box::new(RunLogger { inner })).is_ok() {
+        set_max_level(LogLevel::default().get_level());
+    }
+}
+
+//Copies the logs of the current thread up to the level to a file until dropped
+pub struct RunLogGuard;
+
+impl RunLogGuard {
+    pub fn new(filepath: &Path, level: LogLevel) -> std::io::Result<Self> {
+        let file = BufWriter::new(File::create(filepath)?);
+        if let Ok(mut guard) = ACTIVE_RUN_LOG.lock() {
+            *guard = Some(ActiveRunLog {
+                threads: vec![std::thread::current().id()],
+                start: Instant::now(),
+                file,
+                level: level.get_level(),
+            });
+        }
+        set_max_level(level.get_level());
+        Ok(RunLogGuard)
+    }
+}
+
+//Also copies the logs of the current thread to the log of the run being built, for the threads
+//helping to build it (see hit_decoder)
+pub fn join_run_log() {
+    if let Ok(mut guard) = ACTIVE_RUN_LOG.lock() {
+        if let Some(run_log) = guard.as_mut() {
+            run_log.threads.push(std::thread::current().id());
+        }
+    }
+}
+
+impl Drop for RunLogGuard {
+    fn drop(&mut self) {
+        if let Ok(mut guard) = ACTIVE_RUN_LOG.lock() {
+            if let Some(mut run_log) = guard.take() {
+                let _ = run_log.file.flush();
+            }
+        }
+        set_max_level(LogLevel::default().get_level());
+    }
+}
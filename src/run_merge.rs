@@ -0,0 +1,228 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use polars::prelude::*;
+
+use super::build_diagnostics::emit_warning;
+use super::channel_data::TimeUnit;
+use super::column_codec::ColumnCodec;
+use super::error::EVBError;
+use super::histogram::{read_columns, read_time_unit};
+use super::invalid_values::ColumnInvalidValues;
+use super::parquet_writer::{EventParquetStream, ParquetMetadata};
+use super::run_comparison::get_table_paths;
+use super::run_layout::RunLayout;
+
+pub const RUN_NUMBER_COLUMN: &str = "RunNumber";
+//File metadata describing a single run, left out of the merged table
+const RUN_KEYS: [&str; 6] = [
+    "run_number",
+    "first_hit_time_ns",
+    "last_hit_time_ns",
+    "channel_hits",
+    "daq_settings",
+    "partial",
+];
+
+//One table of every run of a job, next to the run directories
+pub fn get_merged_path(output_dir: &Path, stem: &str, run_min: i32, run_max: i32) -> PathBuf {
+    output_dir.join(format!("merged_{}_{}-{}.parquet", stem, run_min, run_max))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedRun {
+    pub run_number: i32,
+    pub events: usize,
+    //Added to the times of the run to put them on the clock of the merged table, ns
+    pub time_offset_ns: f64,
+}
+
+//Raw times (ns) of the first and last hit of a built run, None for a run without hits
+fn read_hit_times(layout: &RunLayout) -> Result<Option<(f64, f64)>, EVBError> {
+    let text = std::fs::read_to_string(layout.get_metadata_path())?;
+    let metadata: serde_json::Value = serde_json::from_str(&text)?;
+    Ok(
+        match (
+            metadata["first_hit_time_ns"].as_f64(),
+            metadata["last_hit_time_ns"].as_f64(),
+        ) {
+            (Some(first), Some(last)) => Some((first, last)),
+            _ => None,
+        },
+    )
+}
+
+//The file and column metadata of a table, without the arrow schema, which is written anew
+fn read_table_metadata(filepath: &Path) -> Result<ParquetMetadata, EVBError> {
+    let file_metadata = polars_parquet::read::read_metadata(&mut File::open(filepath)?)?;
+    let schema = polars_parquet::read::infer_schema(&file_metadata)?;
+    let mut metadata = ParquetMetadata::default();
+    for kv in file_metadata.key_value_metadata.unwrap_or_default() {
+        if let Some(value) = kv.value.filter(|_| !kv.key.starts_with("ARROW:")) {
+            metadata.insert_file(&kv.key, value);
+        }
+    }
+    for field in schema.fields.iter() {
+        for (key, value) in field.metadata.iter() {
+            metadata.insert_column(&field.name, key, value.clone());
+        }
+    }
+    Ok(metadata)
+}
+
+//Tracks the clock of the merged table. CoMPASS starts the timestamps of every run from zero, so a
+//run whose first hit comes before the last hit of the run before had its clock reset and is put
+//after it, as if it started when the run before stopped. A clock that kept running is left alone.
+#[derive(Debug, Default)]
+struct MergedClock {
+    offset: f64,
+    end: Option<f64>,
+}
+
+impl MergedClock {
+    fn add_run(&mut self, hit_times: Option<(f64, f64)>) -> f64 {
+        if let Some((first, last)) = hit_times {
+            if let Some(end) = self.end.filter(|end| first + self.offset < *end) {
+                self.offset = end;
+            }
+            self.end = Some(last + self.offset);
+        }
+        self.offset
+    }
+}
+
+//Writes the <stem> tables (events, singles, ...) of the runs into one file at output_path, a run at
+//a time, with a RunNumber column and the times of every run moved onto one clock. Runs without the
+//table, e.g. a split concatenated run, are left out with a warning. The runs must have been built
+//with the same columns.
+pub fn merge_runs(
+    output_dir: &Path,
+    runs: &[i32],
+    stem: &str,
+    output_path: &Path,
+    codecs: &[ColumnCodec],
+) -> Result<Vec<MergedRun>, EVBError> {
+    let mut stream: Option<EventParquetStream<File>> = None;
+    let mut columns: Vec<String> = vec![];
+    let mut time_unit = TimeUnit::default();
+    let mut time_columns: Vec<String> = vec![];
+    let mut clock = MergedClock::default();
+    let mut merged: Vec<MergedRun> = vec![];
+    for run in runs.iter() {
+        let layout = RunLayout::new(output_dir, *run);
+        let paths = get_table_paths(&layout, stem);
+        let first_path = match paths.first() {
+            Some(path) => path,
+            None => {
+                emit_warning(
+                    "merge_runs",
+                    format!("run {}", run),
+                    format!("Run {} has no {} table to merge, it is left out", run, stem),
+                );
+                continue;
+            }
+        };
+        let time_offset_ns = clock.add_run(read_hit_times(&layout)?);
+        if stream.is_none() {
+            let metadata = read_table_metadata(first_path)?;
+            time_unit = read_time_unit(first_path)?;
+            columns = read_columns(first_path)?;
+            //Times in the time unit of the run, not the wall clock in seconds
+            let schema =
+                LazyFrame::scan_parquet(first_path, ScanArgsParquet::default())?.schema()?;
+            time_columns = metadata
+                .columns
+                .iter()
+                .filter(|(name, entries)| {
+                    entries.get("unit").map(String::as_str) == Some(time_unit.as_ref())
+                        && schema.get(name) == Some(&DataType::Float64)
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            let mut file_metadata = metadata.clone();
+            for key in RUN_KEYS {
+                file_metadata.file.remove(key);
+            }
+            let event_key = metadata.file.get("event_key").cloned().unwrap_or_default();
+            stream = Some(EventParquetStream::new(
+                File::create(output_path)?,
+                &file_metadata,
+                &event_key,
+                codecs,
+            ));
+        } else if read_columns(first_path)? != columns || read_time_unit(first_path)? != time_unit {
+            return Err(EVBError::Merge(format!(
+                "run {} was built with other columns than run {}",
+                run, merged[0].run_number
+            )));
+        }
+
+        let invalid = ColumnInvalidValues::read(first_path)?;
+        let shift = time_unit.convert_nanoseconds(time_offset_ns);
+        let frames = paths
+            .iter()
+            .map(|path| LazyFrame::scan_parquet(path, ScanArgsParquet::default()))
+            .collect::<PolarsResult<Vec<LazyFrame>>>()?;
+        let mut frame = concat(frames, UnionArgs::default())?;
+        if shift != 0.0 {
+            frame = frame.with_columns(
+                time_columns
+                    .iter()
+                    .map(|name| {
+                        when(invalid.get_valid(name))
+                            .then(col(name) + lit(shift))
+                            .otherwise(col(name))
+                            .alias(name)
+                    })
+                    .collect::<Vec<Expr>>(),
+            );
+        }
+        let mut df = frame
+            .with_column(lit(*run).alias(RUN_NUMBER_COLUMN))
+            .collect()?;
+        if let Some(stream) = &mut stream {
+            stream.write_piece(&mut df)?;
+        }
+        info!(
+            "Merged {} events of run {}, times moved by {} ns",
+            df.height(),
+            run,
+            time_offset_ns
+        );
+        merged.push(MergedRun {
+            run_number: *run,
+            events: df.height(),
+            time_offset_ns,
+        });
+    }
+
+    let mut stream =
+        stream.ok_or_else(|| EVBError::Merge(format!("none of the runs has a {} table", stem)))?;
+    let mut metadata = ParquetMetadata::default();
+    metadata.insert_file(
+        "merged_runs",
+        merged
+            .iter()
+            .map(|run| run.run_number.to_string())
+            .collect::<Vec<String>>()
+            .join(" "),
+    );
+    metadata.insert_file(
+        "run_time_offsets_ns",
+        merged
+            .iter()
+            .map(|run| format!("{}:{}", run.run_number, run.time_offset_ns))
+            .collect::<Vec<String>>()
+            .join(" "),
+    );
+    stream.insert_file_metadata(&metadata);
+    stream.finish()?;
+    info!(
+        "Wrote {} events of {} runs to {}",
+        merged.iter().map(|run| run.events).sum::<usize>(),
+        merged.len(),
+        output_path.display()
+    );
+    Ok(merged)
+}
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::channel_map::Board;
+use super::checksum::Checksum;
+use super::error::EVBError;
+use super::parquet_writer::ParquetMetadata;
+use super::run_statistics::ChannelStats;
+use super::time_walk::TimeWalkProvenance;
+
+//How a run was built, written to the file metadata of its tables and to metadata.json in the run
+//directory. Tables written before the end of the run (fragments, and the parts of a split run) only
+//get the build settings, since the hit times and counts are only known once the run is read.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunMetadata {
+    pub run_number: i32,
+    pub eventbuilder_version: String,
+    //ns
+    pub coincidence_window: f64,
+    pub channel_map_hash: String,
+    //Raw timestamps (ns) of the first and last hit going into the builder
+    pub first_hit_time_ns: Option<f64>,
+    pub last_hit_time_ns: Option<f64>,
+    //Hits of every channel in the data, by board/channel
+    pub channel_hits: BTreeMap<String, u64>,
+    //Settings of the enabled channels from the CoMPASS settings.xml of the run, by board/channel
+    pub daq_settings: BTreeMap<String, BTreeMap<String, String>>,
+    //The build was interrupted before the end of the files
+    pub partial: bool,
+    //Only for builds with the time walk correction
+    pub time_walk: Option<TimeWalkProvenance>,
+}
+
+//Checksum of the channel map as it is written in the config, to tell builds with different maps
+//apart
+pub fn get_channel_map_hash(boards: &[Board]) -> String {
+    let mut checksum = Checksum::new();
+    for board in boards.iter() {
+        checksum.update(serde_yaml::to_string(board).unwrap_or_default().as_bytes());
+    }
+    checksum.to_hex()
+}
+
+impl RunMetadata {
+    pub fn new(run_number: i32, coincidence_window: f64, channel_map_hash: &str) -> Self {
+        RunMetadata {
+            run_number,
+            eventbuilder_version: env!("CARGO_PKG_VERSION").to_string(),
+            coincidence_window,
+            channel_map_hash: channel_map_hash.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_hits(&mut self, hit_times: Option<(f64, f64)>, channels: &[ChannelStats]) {
+        self.first_hit_time_ns = hit_times.map(|(first, _)| first);
+        self.last_hit_time_ns = hit_times.map(|(_, last)| last);
+        self.channel_hits = channels
+            .iter()
+            .map(|stats| {
+                (
+                    format!("{}/{}", stats.board_number, stats.channel_number),
+                    stats.hits,
+                )
+            })
+            .collect();
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("run_number", self.run_number.to_string());
+        metadata.insert_file("eventbuilder_version", self.eventbuilder_version.clone());
+        metadata.insert_file("coincidence_window", self.coincidence_window.to_string());
+        metadata.insert_file("channel_map_hash", self.channel_map_hash.clone());
+        if let (Some(first), Some(last)) = (self.first_hit_time_ns, self.last_hit_time_ns) {
+            metadata.insert_file("first_hit_time_ns", first.to_string());
+            metadata.insert_file("last_hit_time_ns", last.to_string());
+        }
+        if self.partial {
+            metadata.insert_file("partial", String::from("true"));
+        }
+        if !self.channel_hits.is_empty() {
+            metadata.insert_file(
+                "channel_hits",
+                serde_json::to_string(&self.channel_hits).unwrap_or_default(),
+            );
+        }
+        if !self.daq_settings.is_empty() {
+            metadata.insert_file(
+                "daq_settings",
+                serde_json::to_string(&self.daq_settings).unwrap_or_default(),
+            );
+        }
+        if let Some(time_walk) = &self.time_walk {
+            time_walk.insert_metadata(metadata);
+        }
+    }
+
+    pub fn write(&self, filepath: &Path) -> Result<(), EVBError> {
+        let json_str = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(filepath)?;
+        file.write_all(json_str.as_bytes())?;
+        Ok(())
+    }
+}
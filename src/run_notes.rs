@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::error::EVBError;
+use super::run_layout::RunLayout;
+
+//Free-text notes of the shifters, one per run, kept in run_notes.yaml in the workspace so they
+//stay with the campaign in place of the paper logbook
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunNotes {
+    notes: BTreeMap<i32, String>,
+}
+
+impl RunNotes {
+    //A workspace without notes yet has an empty file
+    pub fn read(path: &Path) -> Result<Self, EVBError> {
+        if !path.exists() {
+            return Ok(RunNotes::default());
+        }
+        Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), EVBError> {
+        std::fs::write(path, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get_note(&self, run_number: i32) -> &str {
+        self.notes.get(&run_number).map_or("", |note| note.as_str())
+    }
+
+    pub fn get_note_mut(&mut self, run_number: i32) -> &mut String {
+        self.notes.entry(run_number).or_default()
+    }
+
+    //Runs whose note was cleared are left out
+    fn clean_up(&mut self) {
+        self.notes.retain(|_, note| !note.trim().is_empty());
+    }
+
+    pub fn save(&mut self, path: &Path) -> Result<(), EVBError> {
+        self.clean_up();
+        self.write(path)
+    }
+}
+
+pub fn get_campaign_summary_path(output_dir: &Path) -> PathBuf {
+    output_dir.join("campaign_summary.csv")
+}
+
+fn quote_csv(text: &str) -> String {
+    let one_line: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+    format!("\"{}\"", one_line.join(" / ").replace('"', "\"\""))
+}
+
+//Number of the run built into a run_<num> directory
+pub fn get_built_run(path: &Path) -> Option<i32> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("run_")?
+        .parse()
+        .ok()
+}
+
+//One row per run that was built or has a note, with the main numbers of its report and its note.
+//Runs with a note that were never built have empty numbers.
+pub fn write_campaign_summary(output_dir: &Path, notes: &RunNotes) -> Result<PathBuf, EVBError> {
+    let mut runs: BTreeSet<i32> = notes.notes.keys().copied().collect();
+    for entry in output_dir.read_dir()?.flatten() {
+        if let Some(run_number) = get_built_run(&entry.path()) {
+            runs.insert(run_number);
+        }
+    }
+
+    let path = get_campaign_summary_path(output_dir);
+    let mut writer = BufWriter::new(File::create(&path)?);
+    writeln!(
+        writer,
+        "run,total_hits,events_built,processing_seconds,target,beam,note"
+    )?;
+    for run_number in runs {
+        let report_path = RunLayout::new(output_dir, run_number).get_report_path();
+        let report: Option<serde_json::Value> = std::fs::read_to_string(report_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok());
+        let get_value = |pointer: &str| {
+            report
+                .as_ref()
+                .and_then(|report| report.pointer(pointer))
+                .map_or(String::new(), |value| value.to_string())
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            run_number,
+            get_value("/total_hits"),
+            get_value("/event_builder/windows_closed"),
+            get_value("/processing_seconds"),
+            get_value("/campaign/target"),
+            get_value("/campaign/beam"),
+            quote_csv(notes.get_note(run_number))
+        )?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
@@ -0,0 +1,597 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use log::info;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::build_diagnostics::emit_warning;
+use super::error::EVBError;
+use super::formatting::format_rate;
+use super::histogram::{read_columns, read_time_unit};
+use super::invalid_values::ColumnInvalidValues;
+use super::run_comparison::{get_events_paths, is_detector_time};
+use super::run_layout::RunLayout;
+use super::run_notes::get_built_run;
+
+//What the statistics of a run are compared against
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum QaReference {
+    //One run known to be good, e.g. from the start of the campaign
+    #[default]
+    Run,
+    //The average of the runs built before it
+    RollingAverage,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QaParams {
+    pub enabled: bool,
+    pub reference: QaReference,
+    pub reference_run: i32,
+    //Built runs averaged for RollingAverage, the latest ones with a lower run number
+    pub rolling_runs: usize,
+    //A detector is flagged when any statistic is off the reference by more than this fraction
+    pub threshold: f64,
+}
+
+impl Default for QaParams {
+    fn default() -> Self {
+        QaParams {
+            enabled: false,
+            reference: QaReference::Run,
+            reference_run: 0,
+            rolling_runs: 5,
+            threshold: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectorQa {
+    pub detector: String,
+    pub hits: u64,
+    //Hz
+    pub hit_rate: f64,
+    //Of the hits with an energy
+    pub mean_energy: Option<f64>,
+    //Events with the detector and at least one other detector (Hz)
+    pub coincidence_rate: f64,
+}
+
+//Key statistics of one built run, from its events table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QaStatistics {
+    pub run_number: i32,
+    pub events: u64,
+    //From the earliest to the latest hit time in the events
+    pub duration_seconds: f64,
+    pub detectors: Vec<DetectorQa>,
+}
+
+//Sums over the files of a run, for one detector
+#[derive(Debug, Clone, Default)]
+struct DetectorSums {
+    detector: String,
+    hits: u64,
+    energy_sum: f64,
+    energy_hits: u64,
+    coincidences: u64,
+}
+
+//The Cebra<n>Time column of each detector found, with its hits, energies and coincidences, and the
+//earliest and latest hit times
+fn read_file_sums(filepath: &Path, sums: &mut Vec<DetectorSums>) -> Result<(f64, f64), EVBError> {
+    let columns = read_columns(filepath)?;
+    let time_columns: Vec<&String> = columns
+        .iter()
+        .filter(|name| is_detector_time(name))
+        .collect();
+    if time_columns.is_empty() {
+        return Ok((f64::MAX, f64::MIN));
+    }
+    let invalid = ColumnInvalidValues::read(filepath)?;
+    let multiplicity = time_columns
+        .iter()
+        .map(|name| invalid.get_valid(name).cast(DataType::UInt32))
+        .reduce(|sum, valid| sum + valid)
+        .unwrap_or(lit(0));
+    let mut exprs: Vec<Expr> = vec![];
+    for (index, name) in time_columns.iter().enumerate() {
+        let valid = invalid.get_valid(name);
+        exprs.push(
+            valid
+                .clone()
+                .cast(DataType::UInt64)
+                .sum()
+                .alias(&format!("hits_{}", index)),
+        );
+        exprs.push(
+            valid
+                .clone()
+                .and(multiplicity.clone().gt_eq(lit(2)))
+                .cast(DataType::UInt64)
+                .sum()
+                .alias(&format!("coincidences_{}", index)),
+        );
+        exprs.push(
+            col(name)
+                .filter(valid.clone())
+                .min()
+                .alias(&format!("min_{}", index)),
+        );
+        exprs.push(
+            col(name)
+                .filter(valid.clone())
+                .max()
+                .alias(&format!("max_{}", index)),
+        );
+        let energy = format!("{}Energy", name.trim_end_matches("Time"));
+        if columns.contains(&energy) {
+            let has_energy = valid.and(invalid.get_valid(&energy));
+            exprs.push(
+                col(&energy)
+                    .filter(has_energy.clone())
+                    .sum()
+                    .alias(&format!("energy_{}", index)),
+            );
+            exprs.push(
+                has_energy
+                    .cast(DataType::UInt64)
+                    .sum()
+                    .alias(&format!("energy_hits_{}", index)),
+            );
+        }
+    }
+    let summary = LazyFrame::scan_parquet(filepath, ScanArgsParquet::default())?
+        .select(exprs)
+        .collect()?;
+
+    let mut first_time = f64::MAX;
+    let mut last_time = f64::MIN;
+    for (index, name) in time_columns.iter().enumerate() {
+        let detector = name.trim_end_matches("Time");
+        let position = match sums.iter().position(|sums| sums.detector == detector) {
+            Some(position) => position,
+            None => {
+                sums.push(DetectorSums {
+                    detector: detector.to_string(),
+                    ..Default::default()
+                });
+                sums.len() - 1
+            }
+        };
+        let get_count = |column: &str| -> Result<u64, EVBError> {
+            Ok(summary.column(column)?.u64()?.get(0).unwrap_or(0))
+        };
+        let detector_sums = &mut sums[position];
+        detector_sums.hits += get_count(&format!("hits_{}", index))?;
+        detector_sums.coincidences += get_count(&format!("coincidences_{}", index))?;
+        if let Ok(energy) = summary.column(&format!("energy_{}", index)) {
+            detector_sums.energy_sum += energy
+                .cast(&DataType::Float64)?
+                .f64()?
+                .get(0)
+                .unwrap_or(0.0);
+            detector_sums.energy_hits += get_count(&format!("energy_hits_{}", index))?;
+        }
+        if let Some(time) = summary.column(&format!("min_{}", index))?.f64()?.get(0) {
+            first_time = first_time.min(time);
+        }
+        if let Some(time) = summary.column(&format!("max_{}", index))?.f64()?.get(0) {
+            last_time = last_time.max(time);
+        }
+    }
+    Ok((first_time, last_time))
+}
+
+impl QaStatistics {
+    //None if the run has no events table
+    pub fn read(output_dir: &Path, run_number: i32) -> Result<Option<Self>, EVBError> {
+        let paths = get_events_paths(&RunLayout::new(output_dir, run_number));
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        let mut sums: Vec<DetectorSums> = vec![];
+        let mut events = 0;
+        let mut first_time = f64::MAX;
+        let mut last_time = f64::MIN;
+        for path in paths.iter() {
+            let (first, last) = read_file_sums(path, &mut sums)?;
+            first_time = first_time.min(first);
+            last_time = last_time.max(last);
+            events += polars_parquet::read::read_metadata(&mut File::open(path)?)?.num_rows as u64;
+        }
+        let duration_seconds = if last_time > first_time {
+            let units_per_second = read_time_unit(&paths[0])?.convert_nanoseconds(1.0e9);
+            (last_time - first_time) / units_per_second
+        } else {
+            0.0
+        };
+        let get_rate = |count: u64| {
+            if duration_seconds > 0.0 {
+                count as f64 / duration_seconds
+            } else {
+                0.0
+            }
+        };
+        sums.sort_by_key(|sums| {
+            sums.detector
+                .trim_start_matches("Cebra")
+                .parse::<usize>()
+                .unwrap_or(usize::MAX)
+        });
+        Ok(Some(QaStatistics {
+            run_number,
+            events,
+            duration_seconds,
+            detectors: sums
+                .iter()
+                .map(|sums| DetectorQa {
+                    detector: sums.detector.clone(),
+                    hits: sums.hits,
+                    hit_rate: get_rate(sums.hits),
+                    mean_energy: (sums.energy_hits > 0)
+                        .then_some(sums.energy_sum / sums.energy_hits as f64),
+                    coincidence_rate: get_rate(sums.coincidences),
+                })
+                .collect(),
+        }))
+    }
+}
+
+//The detectors of the reference runs, each averaged over the runs it is in
+fn average_detectors(runs: &[QaStatistics]) -> Vec<DetectorQa> {
+    let mut detectors: Vec<DetectorQa> = vec![];
+    for run in runs.iter() {
+        for detector in run.detectors.iter() {
+            if !detectors
+                .iter()
+                .any(|known| known.detector == detector.detector)
+            {
+                detectors.push(detector.clone());
+            }
+        }
+    }
+    for average in detectors.iter_mut() {
+        let found: Vec<&DetectorQa> = runs
+            .iter()
+            .filter_map(|run| {
+                run.detectors
+                    .iter()
+                    .find(|detector| detector.detector == average.detector)
+            })
+            .collect();
+        let count = found.len() as f64;
+        average.hits =
+            (found.iter().map(|detector| detector.hits).sum::<u64>() as f64 / count).round() as u64;
+        average.hit_rate = found.iter().map(|detector| detector.hit_rate).sum::<f64>() / count;
+        average.coincidence_rate = found
+            .iter()
+            .map(|detector| detector.coincidence_rate)
+            .sum::<f64>()
+            / count;
+        let energies: Vec<f64> = found
+            .iter()
+            .filter_map(|detector| detector.mean_energy)
+            .collect();
+        average.mean_energy =
+            (!energies.is_empty()).then(|| energies.iter().sum::<f64>() / energies.len() as f64);
+    }
+    detectors
+}
+
+//One statistic of one detector against the reference
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QaCheck {
+    pub detector: String,
+    //hit_rate, mean_energy or coincidence_rate
+    pub statistic: String,
+    pub value: f64,
+    pub reference: f64,
+    //(value - reference) / reference, None with a reference of 0
+    pub deviation: Option<f64>,
+    pub flagged: bool,
+}
+
+impl QaCheck {
+    fn new(detector: &str, statistic: &str, value: f64, reference: f64, threshold: f64) -> Self {
+        let deviation = (reference != 0.0).then_some((value - reference) / reference);
+        QaCheck {
+            detector: detector.to_string(),
+            statistic: statistic.to_string(),
+            value,
+            reference,
+            deviation,
+            flagged: match deviation {
+                Some(deviation) => deviation.abs() > threshold,
+                None => value != 0.0,
+            },
+        }
+    }
+
+    fn format_value(&self, value: f64) -> String {
+        match self.statistic.as_str() {
+            "hit_rate" => format_rate(value, "hits"),
+            "coincidence_rate" => format_rate(value, "events"),
+            _ => format!("{:.1}", value),
+        }
+    }
+
+    fn format_deviation(&self) -> String {
+        match self.deviation {
+            Some(deviation) => format!("{:+.1}%", deviation * 100.0),
+            None => String::from("new"),
+        }
+    }
+
+    //e.g. "hit_rate 120 hits/s is -45.0% off the reference 218 hits/s"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} is {} off the reference {}",
+            self.statistic,
+            self.format_value(self.value),
+            self.format_deviation(),
+            self.format_value(self.reference)
+        )
+    }
+}
+
+//Written to qa.json and qa.html in the run directory of every run built with QA
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QaReport {
+    pub run_number: i32,
+    //e.g. "run 12" or "average of runs 9, 10, 11"
+    pub reference: String,
+    pub reference_runs: Vec<i32>,
+    pub threshold: f64,
+    pub statistics: QaStatistics,
+    pub reference_detectors: Vec<DetectorQa>,
+    //Empty without a reference
+    pub checks: Vec<QaCheck>,
+    //Detectors with any flagged check
+    pub flagged: Vec<String>,
+}
+
+impl QaReport {
+    fn new(params: &QaParams, statistics: QaStatistics, reference_runs: &[QaStatistics]) -> Self {
+        let reference_detectors = average_detectors(reference_runs);
+        let mut checks = vec![];
+        for detector in statistics.detectors.iter() {
+            let reference = match reference_detectors
+                .iter()
+                .find(|reference| reference.detector == detector.detector)
+            {
+                Some(reference) => reference,
+                None => continue,
+            };
+            checks.push(QaCheck::new(
+                &detector.detector,
+                "hit_rate",
+                detector.hit_rate,
+                reference.hit_rate,
+                params.threshold,
+            ));
+            if let (Some(value), Some(reference)) = (detector.mean_energy, reference.mean_energy) {
+                checks.push(QaCheck::new(
+                    &detector.detector,
+                    "mean_energy",
+                    value,
+                    reference,
+                    params.threshold,
+                ));
+            }
+            checks.push(QaCheck::new(
+                &detector.detector,
+                "coincidence_rate",
+                detector.coincidence_rate,
+                reference.coincidence_rate,
+                params.threshold,
+            ));
+        }
+        //A detector of the reference without any hits in the run is off by all of them
+        for reference in reference_detectors.iter().filter(|reference| {
+            !statistics
+                .detectors
+                .iter()
+                .any(|detector| detector.detector == reference.detector)
+        }) {
+            checks.push(QaCheck::new(
+                &reference.detector,
+                "hit_rate",
+                0.0,
+                reference.hit_rate,
+                params.threshold,
+            ));
+        }
+        let mut flagged: Vec<String> = vec![];
+        for check in checks.iter().filter(|check| check.flagged) {
+            if !flagged.contains(&check.detector) {
+                flagged.push(check.detector.clone());
+            }
+        }
+        let runs: Vec<i32> = reference_runs.iter().map(|run| run.run_number).collect();
+        let reference = match runs.as_slice() {
+            [] => String::from("none"),
+            [run] => format!("run {}", run),
+            runs => format!(
+                "average of runs {}",
+                runs.iter()
+                    .map(|run| run.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        };
+        QaReport {
+            run_number: statistics.run_number,
+            reference,
+            reference_runs: runs,
+            threshold: params.threshold,
+            statistics,
+            reference_detectors,
+            checks,
+            flagged,
+        }
+    }
+
+    pub fn write_json(&self, filepath: &Path) -> Result<(), EVBError> {
+        let json_str = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(filepath)?;
+        file.write_all(json_str.as_bytes())?;
+        Ok(())
+    }
+
+    //A single page with the statistics of the run and a table of the checks, flagged rows in red
+    pub fn write_html(&self, filepath: &Path) -> Result<(), EVBError> {
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>QA of run {}</title>\n", self.run_number));
+        html.push_str(
+            "<style>\nbody { font-family: sans-serif; }\ntable { border-collapse: collapse; }\n\
+            th, td { border: 1px solid #999; padding: 2px 8px; text-align: right; }\n\
+            tr.flagged { background: #f4c7c3; }\n</style>\n</head>\n<body>\n",
+        );
+        html.push_str(&format!("<h1>QA of run {}</h1>\n", self.run_number));
+        html.push_str(&format!(
+            "<p>{} events in {:.1} s. Reference: {}, threshold {:.1}%.</p>\n",
+            self.statistics.events,
+            self.statistics.duration_seconds,
+            self.reference,
+            self.threshold * 100.0
+        ));
+        match self.flagged.is_empty() {
+            true if self.checks.is_empty() => html.push_str("<p>Nothing to compare.</p>\n"),
+            true => html.push_str("<p>No detector flagged.</p>\n"),
+            false => html.push_str(&format!(
+                "<p><b>Flagged: {}</b></p>\n",
+                self.flagged.join(", ")
+            )),
+        }
+        html.push_str(
+            "<table>\n<tr><th>Detector</th><th>Statistic</th><th>Value</th><th>Reference</th>\
+            <th>Deviation</th></tr>\n",
+        );
+        for check in self.checks.iter() {
+            html.push_str(&format!(
+                "<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                if check.flagged {
+                    " class=\"flagged\""
+                } else {
+                    ""
+                },
+                check.detector,
+                check.statistic,
+                check.format_value(check.value),
+                check.format_value(check.reference),
+                check.format_deviation()
+            ));
+        }
+        html.push_str("</table>\n</body>\n</html>\n");
+        std::fs::write(filepath, html)?;
+        Ok(())
+    }
+}
+
+//Built runs before run_number, latest first
+fn get_earlier_runs(output_dir: &Path, run_number: i32) -> Vec<i32> {
+    let mut runs: Vec<i32> = match output_dir.read_dir() {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .filter_map(|path| get_built_run(&path))
+            .filter(|run| *run < run_number)
+            .collect(),
+        Err(_) => vec![],
+    };
+    runs.sort_by(|a, b| b.cmp(a));
+    runs
+}
+
+fn read_reference_runs(
+    params: &QaParams,
+    output_dir: &Path,
+    run_number: i32,
+) -> Result<Vec<QaStatistics>, EVBError> {
+    match params.reference {
+        QaReference::Run => Ok(QaStatistics::read(output_dir, params.reference_run)?
+            .into_iter()
+            .collect()),
+        QaReference::RollingAverage => {
+            let mut runs = vec![];
+            for run in get_earlier_runs(output_dir, run_number) {
+                if runs.len() >= params.rolling_runs {
+                    break;
+                }
+                if let Some(statistics) = QaStatistics::read(output_dir, run)? {
+                    runs.push(statistics);
+                }
+            }
+            runs.reverse();
+            Ok(runs)
+        }
+    }
+}
+
+//Compares a built run with its reference, writes qa.json and qa.html into its run directory and
+//warns about every flagged detector. The reference run itself is written without any checks.
+pub fn check_run(params: &QaParams, output_dir: &Path, run_number: i32) -> Result<(), EVBError> {
+    let layout = RunLayout::new(output_dir, run_number);
+    let statistics = match QaStatistics::read(output_dir, run_number)? {
+        Some(statistics) => statistics,
+        None => {
+            return Err(EVBError::File(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("run {} has no events table", run_number),
+            )))
+        }
+    };
+    let is_reference = params.reference == QaReference::Run && params.reference_run == run_number;
+    let reference_runs = match is_reference {
+        true => vec![],
+        false => read_reference_runs(params, output_dir, run_number)?,
+    };
+    if reference_runs.is_empty() && !is_reference {
+        emit_warning(
+            "qa_reference",
+            String::new(),
+            match params.reference {
+                QaReference::Run => format!(
+                    "QA reference run {} has no events table, run {} was not compared",
+                    params.reference_run, run_number
+                ),
+                QaReference::RollingAverage => format!(
+                    "No runs were built before run {} to compare it with",
+                    run_number
+                ),
+            },
+        );
+    }
+    let report = QaReport::new(params, statistics, &reference_runs);
+    for detector in report.flagged.iter() {
+        let description = report
+            .checks
+            .iter()
+            .filter(|check| check.flagged && check.detector == *detector)
+            .map(|check| check.describe())
+            .collect::<Vec<String>>()
+            .join(", ");
+        emit_warning(
+            "qa_deviation",
+            detector.clone(),
+            format!("{} against {}", description, report.reference),
+        );
+    }
+    if !report.checks.is_empty() && report.flagged.is_empty() {
+        info!(
+            "QA: no detector of run {} is off {} by more than {:.1}%",
+            run_number,
+            report.reference,
+            params.threshold * 100.0
+        );
+    }
+    report.write_json(&layout.get_qa_report_path())?;
+    report.write_html(&layout.get_qa_html_path())
+}
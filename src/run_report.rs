@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::build_diagnostics::BuildDiagnostic;
+use super::campaign::CampaignTag;
+use super::channel_map::DetectorChannels;
+use super::channel_rates::ChannelRateSummary;
+use super::checkpoint::ResumeSummary;
+use super::coincidence_trend::CoincidenceTrendSummary;
+use super::corrupt_file::CorruptFile;
+use super::data_reduction::ReductionProvenance;
+use super::detector_spectra::DetectorSpectrumSummary;
+use super::energy_policy::BadEnergySummary;
+use super::energy_smearing::SmearingProvenance;
+use super::energy_threshold::EnergyThresholdSummary;
+use super::error::EVBError;
+use super::event_builder::EventBuilderStats;
+use super::formatting::{format_bytes, format_count, format_duration, format_rate};
+use super::hit_filter::HitFilterSummary;
+use super::memory_profile::MemoryProfile;
+use super::quick_look::QuickLookSummary;
+use super::rate_limit::RateLimitSummary;
+use super::run_boundary::RunBoundary;
+use super::run_statistics::{ChannelStats, GroupStats};
+use super::scaler_rates::ScalerChannelSummary;
+use super::skip_list::SkippedFile;
+use super::time_differences::TimeDifferenceSummary;
+use super::time_jitter::JitterProvenance;
+use super::time_reversal::ReversalComparison;
+use super::time_walk::TimeWalkSummary;
+use super::timestamp_sanitizer::TimestampSummary;
+use super::unmapped::UnmappedChannelSummary;
+
+//Summary of a single built run, written to report.json in the run directory
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunReport {
+    pub run_number: i32,
+    pub total_hits: u64,
+    pub processing_seconds: f64,
+    //The build was interrupted, only the hits read until then are in the tables
+    pub partial: bool,
+    pub memory: MemoryProfile,
+    pub event_builder: EventBuilderStats,
+    pub labels: RunReportLabels,
+    pub channel_groups: Vec<GroupStats>,
+    pub channels: Vec<ChannelStats>,
+    pub detectors: Vec<DetectorChannels>,
+    pub data_reduction: ReductionProvenance,
+    //Events left out of the events table by the event filters
+    pub filtered_events: u64,
+    //Zero and wrapped energies found in the hits as read
+    pub bad_energies: BadEnergySummary,
+    //Hits of noisy channels dropped by the rate limit, only for builds with the rate limit
+    pub rate_limit: Option<RateLimitSummary>,
+    //Hits below the energy threshold of their channel, only for builds with thresholds
+    pub energy_thresholds: Option<EnergyThresholdSummary>,
+    //Hits dropped by each hit filter, empty for builds without filters
+    pub hit_filters: Vec<HitFilterSummary>,
+    //Only for builds with energy smearing
+    pub energy_smearing: Option<SmearingProvenance>,
+    //Only for builds with time jitter
+    pub time_jitter: Option<JitterProvenance>,
+    //Hits corrected for time walk, only for builds with the time walk correction
+    pub time_walk: Option<TimeWalkSummary>,
+    //Only for builds with the timestamp sanitizer
+    pub timestamps: Option<TimestampSummary>,
+    //Only for builds with the time-reversed check
+    pub time_reversal: Option<ReversalComparison>,
+    //Only for builds with a quick-look file
+    pub quick_look: Option<QuickLookSummary>,
+    //Input files left out by the skip list
+    pub skipped_files: Vec<SkippedFile>,
+    //Input files read only up to a bad record, with the hits kept and dropped
+    pub corrupted_files: Vec<CorruptFile>,
+    //Timestamp resets found in concatenated input files
+    pub run_boundaries: Vec<RunBoundary>,
+    //Hits merged earlier than the hit merged before them, from input files not sorted in time
+    pub out_of_order_hits: u64,
+    //Peak and width of every time difference, only for builds with time differences
+    pub time_differences: Vec<TimeDifferenceSummary>,
+    //Entries of the energy spectrum of every detector, only for builds with detector spectra
+    pub detector_spectra: Vec<DetectorSpectrumSummary>,
+    //Prompt coincidence rates vs time, only for builds with the coincidence trend
+    pub coincidence_trend: Option<CoincidenceTrendSummary>,
+    //Hit rates vs time and dead time of every channel, only for builds with channel rates
+    pub channel_rates: Vec<ChannelRateSummary>,
+    //Target and beam of the campaign entry of the run, None for runs outside the campaign
+    pub campaign: Option<CampaignTag>,
+    //Counts and mean rate of every channel mapped as Scaler
+    pub scaler_channels: Vec<ScalerChannelSummary>,
+    //Channels mapped as None or of boards not in the map that had hits
+    pub unmapped_channels: Vec<UnmappedChannelSummary>,
+    //Every warning and error of the build, as shown by the GUI and on the command line
+    pub diagnostics: Vec<BuildDiagnostic>,
+    //Where the run was resumed from the checkpoint of an interrupted build
+    pub resumed: Option<ResumeSummary>,
+}
+
+//The same numbers as they read in the log, for showing the report without reformatting it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunReportLabels {
+    pub total_hits: String,
+    pub processing_time: String,
+    pub hit_rate: String,
+    pub heap_peak: String,
+}
+
+impl RunReportLabels {
+    pub fn new(total_hits: u64, processing_seconds: f64, memory: &MemoryProfile) -> Self {
+        RunReportLabels {
+            total_hits: format_count(total_hits),
+            processing_time: format_duration(processing_seconds),
+            hit_rate: format_rate(total_hits as f64 / processing_seconds.max(1.0e-6), "hits"),
+            heap_peak: match memory.heap_tracked {
+                true => format_bytes(memory.heap_peak_bytes),
+                false => String::from("not tracked"),
+            },
+        }
+    }
+}
+
+impl RunReport {
+    pub fn write(&self, filepath: &Path) -> Result<(), EVBError> {
+        let json_str = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(filepath)?;
+        file.write_all(json_str.as_bytes())?;
+        Ok(())
+    }
+}
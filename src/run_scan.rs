@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use log::info;
+
+use super::app::EvbAppParams;
+use super::channel_map::{ChannelMap, ChannelType, MappedChannel};
+use super::compass_data::{
+    decompose_uuid_to_board_channel, generate_board_channel_uuid, RawCompassData, TimestampUnit,
+};
+use super::compass_file::{group_segments, summarize_segment, CompassHits};
+use super::compass_settings::is_settings_path;
+use super::csv_file::is_csv_path;
+use super::error::EVBError;
+use super::event_clock::is_run_info_path;
+use super::formatting::{format_bytes, format_count};
+use super::run_archive::unpack_run_archive;
+use super::run_layout::RunLayout;
+use super::sim_file::is_sim_path;
+use super::slow_control::is_slow_control_path;
+
+//One board/channel with data in a scanned run. Times are ns since the start of the run, without
+//time shifts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScannedChannel {
+    pub board: u32,
+    pub channel: u32,
+    //None for channels not in the channel map
+    pub detector: ChannelType,
+    pub files: usize,
+    pub hits: u64,
+    pub first_time_ns: Option<f64>,
+    pub last_time_ns: Option<f64>,
+}
+
+impl ScannedChannel {
+    fn add_times(&mut self, first: f64, last: f64) {
+        self.first_time_ns = Some(self.first_time_ns.map_or(first, |time| time.min(first)));
+        self.last_time_ns = Some(self.last_time_ns.map_or(last, |time| time.max(last)));
+    }
+}
+
+//Output bytes per hit of a run built before, for estimating the output of a run from its hits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputDensity {
+    pub run_number: i32,
+    pub bytes_per_hit: f64,
+}
+
+//Of the built run with the most hits in the output directory, None if no run has been built
+pub fn find_output_density(output_dir: &Path) -> Option<OutputDensity> {
+    let mut best: Option<(u64, OutputDensity)> = None;
+    for entry in output_dir.read_dir().ok()?.flatten() {
+        let run_number = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("run_"))
+            .and_then(|number| number.parse::<i32>().ok())
+        {
+            Some(run_number) => run_number,
+            None => continue,
+        };
+        let layout = RunLayout::new(output_dir, run_number);
+        let total_hits = std::fs::read_to_string(layout.get_report_path())
+            .ok()
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+            .and_then(|report| report["total_hits"].as_u64())
+            .unwrap_or(0);
+        if total_hits == 0 || best.is_some_and(|(hits, _)| hits >= total_hits) {
+            continue;
+        }
+        let bytes: u64 = match layout.list_files() {
+            Ok(files) => files.iter().map(|(_, size)| size).sum(),
+            Err(_) => continue,
+        };
+        best = Some((
+            total_hits,
+            OutputDensity {
+                run_number,
+                bytes_per_hit: bytes as f64 / total_hits as f64,
+            },
+        ));
+    }
+    best.map(|(_, density)| density)
+}
+
+//Inventory of one run archive, taken from the headers and sizes of its files and the first and
+//last record of each, without building it
+#[derive(Debug, Clone, Default)]
+pub struct RunScan {
+    pub run_number: i32,
+    //None when the archive directory has no archive for the run
+    pub archive_path: Option<PathBuf>,
+    pub archive_bytes: u64,
+    //CoMPASS files of the channels, segments counted separately
+    pub data_files: usize,
+    //Scaler, slow control, run.info, settings and any other files of the archive
+    pub other_files: usize,
+    pub channels: Vec<ScannedChannel>,
+    //CSV exports and simulated hits, which are only read in a build
+    pub unscanned: Vec<String>,
+    //Mapped channels without hits, not checked when there are unscanned files
+    pub missing: Vec<(MappedChannel, ChannelType)>,
+    //Data files that end part way through a record, with the bytes left over
+    pub truncated: Vec<(String, u64)>,
+    //Data files without a readable header, with the reason
+    pub unreadable: Vec<(String, String)>,
+    pub density: Option<OutputDensity>,
+}
+
+impl RunScan {
+    pub fn get_total_hits(&self) -> u64 {
+        self.channels.iter().map(|channel| channel.hits).sum()
+    }
+
+    //None until a run has been built, or when not every file was scanned
+    pub fn get_estimated_output_bytes(&self) -> Option<u64> {
+        self.density
+            .filter(|_| self.unscanned.is_empty())
+            .map(|density| (self.get_total_hits() as f64 * density.bytes_per_hit) as u64)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.archive_path.is_some()
+            && self.missing.is_empty()
+            && self.truncated.is_empty()
+            && self.unreadable.is_empty()
+    }
+
+    pub fn format_report(&self) -> String {
+        let archive_path = match &self.archive_path {
+            Some(path) => path,
+            None => return format!("Run {}: no archive\n", self.run_number),
+        };
+        let mut report = format!(
+            "Run {}: {} ({})\n",
+            self.run_number,
+            archive_path.display(),
+            format_bytes(self.archive_bytes as usize)
+        );
+        report.push_str(&format!(
+            "{} data files, {} other files\n\n",
+            self.data_files, self.other_files
+        ));
+
+        let format_time = |time: Option<f64>| match time {
+            Some(time) => format!("{:.6}", time * 1.0e-9),
+            None => String::from("-"),
+        };
+        report.push_str(&format!(
+            "{:<6} {:<8} {:<16} {:>6} {:>12} {:>14} {:>14}\n",
+            "Board", "Channel", "Detector", "Files", "Hits", "First (s)", "Last (s)"
+        ));
+        for channel in self.channels.iter() {
+            let detector = match channel.detector {
+                ChannelType::None => String::from("(unmapped)"),
+                detector => detector.to_string(),
+            };
+            report.push_str(&format!(
+                "{:<6} {:<8} {:<16} {:>6} {:>12} {:>14} {:>14}\n",
+                channel.board,
+                channel.channel,
+                detector,
+                channel.files,
+                channel.hits,
+                format_time(channel.first_time_ns),
+                format_time(channel.last_time_ns)
+            ));
+        }
+
+        let first = self
+            .channels
+            .iter()
+            .filter_map(|channel| channel.first_time_ns)
+            .min_by(|a, b| a.total_cmp(b));
+        let last = self
+            .channels
+            .iter()
+            .filter_map(|channel| channel.last_time_ns)
+            .max_by(|a, b| a.total_cmp(b));
+        report.push_str(&format!(
+            "\n{} hits in {} channels, from {} s to {} s\n",
+            format_count(self.get_total_hits()),
+            self.channels.len(),
+            format_time(first),
+            format_time(last)
+        ));
+        match (self.get_estimated_output_bytes(), self.density) {
+            (Some(bytes), Some(density)) => report.push_str(&format!(
+                "Estimated output: {}, at the {:.1} B per hit of run {}\n",
+                format_bytes(bytes as usize),
+                density.bytes_per_hit,
+                density.run_number
+            )),
+            (None, Some(_)) => {
+                report.push_str("Estimated output: unknown, not every file was scanned\n")
+            }
+            _ => report.push_str("Estimated output: unknown until a run has been built\n"),
+        }
+
+        for (mapped, detector) in self.missing.iter() {
+            report.push_str(&format!(
+                "Missing: board {} channel {} ({}) has no hits\n",
+                mapped.board, mapped.channel, detector
+            ));
+        }
+        for file in self.unscanned.iter() {
+            report.push_str(&format!(
+                "Not scanned: {}, its hits are only read in a build\n",
+                file
+            ));
+        }
+        for (file, bytes) in self.truncated.iter() {
+            report.push_str(&format!(
+                "Truncated: {} ends {} bytes into a record\n",
+                file, bytes
+            ));
+        }
+        for (file, reason) in self.unreadable.iter() {
+            report.push_str(&format!("Unreadable: {}: {}\n", file, reason));
+        }
+        if self.is_complete() && self.unscanned.is_empty() {
+            report.push_str("Every mapped channel has data\n");
+        }
+        report
+    }
+}
+
+fn is_bin_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("bin"))
+}
+
+fn get_file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn get_time_ns(raw: &RawCompassData, units: &[TimestampUnit]) -> f64 {
+    raw.timestamp as f64 * TimestampUnit::of_board(units, raw.board as u32).get_ns_per_unit()
+}
+
+fn get_channel<'a>(
+    channels: &'a mut BTreeMap<u32, ScannedChannel>,
+    map: &ChannelMap,
+    board: u32,
+    channel: u32,
+) -> &'a mut ScannedChannel {
+    let uuid = generate_board_channel_uuid(&board, &channel);
+    channels.entry(uuid).or_insert_with(|| ScannedChannel {
+        board,
+        channel,
+        detector: map
+            .get_channel_data(&uuid)
+            .map_or(ChannelType::None, |data| data.channel_type),
+        files: 0,
+        hits: 0,
+        first_time_ns: None,
+        last_time_ns: None,
+    })
+}
+
+//Files are sorted as a build sorts them: scaler files and slow control logs by the patterns of the
+//config, and the rest of the BIN files as the data of the channels. CoMPASS writes one file per
+//channel, so each is summarized from its size and its first and last record; a time-merged file of
+//several channels is read through instead.
+fn scan_dir(dir: &Path, config: &EvbAppParams, scan: &mut RunScan) -> Result<(), EVBError> {
+    let map = ChannelMap::new(&config.channel_map_entries);
+    let mut data_paths: Vec<PathBuf> = vec![];
+    for entry in dir.read_dir()?.flatten() {
+        let path = entry.path();
+        let name = get_file_name(&path);
+        let is_data = path.is_file()
+            && is_bin_path(&path)
+            && !is_slow_control_path(&path, &config.slow_control_pattern)
+            && !is_run_info_path(&path)
+            && !is_settings_path(&path)
+            && !config
+                .scaler_list_entries
+                .iter()
+                .any(|scaler| name.starts_with(&scaler.file_pattern));
+        if is_data {
+            data_paths.push(path);
+        } else if path.is_file() && (is_csv_path(&path) || is_sim_path(&path)) {
+            scan.unscanned.push(name);
+        } else if path.is_file() {
+            scan.other_files += 1;
+        }
+    }
+    scan.data_files = data_paths.len() + scan.unscanned.len();
+
+    let mut channels: BTreeMap<u32, ScannedChannel> = BTreeMap::new();
+    let units = map.get_timestamp_units();
+    for segments in group_segments(data_paths) {
+        let mut is_merged = false;
+        for (index, path) in segments.iter().enumerate() {
+            let summary = match summarize_segment(path, map.get_energy_widths()) {
+                Ok(summary) => summary,
+                Err(x) => {
+                    scan.unreadable.push((get_file_name(path), x.to_string()));
+                    continue;
+                }
+            };
+            if summary.trailing_bytes > 0 {
+                scan.truncated
+                    .push((get_file_name(path), summary.trailing_bytes));
+            }
+            is_merged |= index == 0 && summary.is_merged;
+            if let (Some(first), Some(last), false) = (&summary.first, &summary.last, is_merged) {
+                let scanned = get_channel(
+                    &mut channels,
+                    &map,
+                    first.board as u32,
+                    first.channel as u32,
+                );
+                scanned.files += 1;
+                scanned.hits += summary.hits;
+                scanned.add_times(get_time_ns(first, units), get_time_ns(last, units));
+            }
+        }
+        if is_merged {
+            for hit in CompassHits::new(&segments, &map)? {
+                let hit = hit?;
+                let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+                let scanned = get_channel(&mut channels, &map, board, channel);
+                scanned.hits += 1;
+                scanned.files = segments.len();
+                scanned.add_times(hit.timestamp, hit.timestamp);
+            }
+        }
+    }
+
+    if !scan.unscanned.is_empty() {
+        scan.channels = channels.into_values().collect();
+        return Ok(());
+    }
+    scan.missing = map
+        .get_mapped_channels()
+        .into_iter()
+        .filter(|(mapped, _)| {
+            !channels.contains_key(&generate_board_channel_uuid(&mapped.board, &mapped.channel))
+        })
+        .collect();
+    scan.channels = channels.into_values().collect();
+    Ok(())
+}
+
+//Unpacks the archive into a scratch directory, so a build using the unpack directory of the
+//workspace is not disturbed, and scans it. A run without an archive is scanned as such, not as an
+//error.
+pub fn scan_run(
+    archive_path: Option<&Path>,
+    run_number: i32,
+    config: &EvbAppParams,
+    density: Option<OutputDensity>,
+) -> Result<RunScan, EVBError> {
+    let mut scan = RunScan {
+        run_number,
+        archive_path: archive_path.map(Path::to_path_buf),
+        density,
+        ..Default::default()
+    };
+    let archive_path = match archive_path {
+        Some(path) => path,
+        None => return Ok(scan),
+    };
+    scan.archive_bytes = std::fs::metadata(archive_path)?.len();
+    let unpack_dir = std::env::temp_dir().join(format!("cebra_evb_scan_{}", std::process::id()));
+    if unpack_dir.exists() {
+        std::fs::remove_dir_all(&unpack_dir)?;
+    }
+    std::fs::create_dir_all(&unpack_dir)?;
+    info!("Scanning {}", archive_path.display());
+    let result = unpack_run_archive(archive_path, &unpack_dir)
+        .and_then(|_| scan_dir(&unpack_dir, config, &mut scan));
+    std::fs::remove_dir_all(&unpack_dir)?;
+    result.map(|_| scan)
+}
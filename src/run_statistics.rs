@@ -0,0 +1,262 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use log::info;
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use super::channel_map::{ChannelGroup, ChannelMap, ChannelType};
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::formatting::{format_count, format_duration, format_rate};
+
+//Gaps kept per channel, and listed in the log over all channels
+const MAX_CHANNEL_GAPS: usize = 3;
+const MAX_LOGGED_GAPS: usize = 5;
+
+//A stretch without hits in one channel. Positions are seconds since the first hit of the run, the
+//same clock as the slow control logs, and also count the time before a channel's first hit and
+//after its last, so a channel that dropped out for the rest of the run shows up too.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TimestampGap {
+    pub start_seconds: f64,
+    pub length_seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStats {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub detector: ChannelType,
+    pub hits: u64,
+    //Longest first
+    pub largest_gaps: Vec<TimestampGap>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct ChannelCounts {
+    hits: u64,
+    //Of the latest hit, ns since the first hit of the run
+    last_position: Option<f64>,
+    largest_gaps: Vec<TimestampGap>,
+}
+
+impl ChannelCounts {
+    //Positions in ns
+    fn add_gap(&mut self, start: f64, end: f64) {
+        if end <= start {
+            return;
+        }
+        let gap = TimestampGap {
+            start_seconds: start * 1.0e-9,
+            length_seconds: (end - start) * 1.0e-9,
+        };
+        if self.largest_gaps.len() == MAX_CHANNEL_GAPS
+            && self
+                .largest_gaps
+                .last()
+                .is_some_and(|smallest| smallest.length_seconds >= gap.length_seconds)
+        {
+            return;
+        }
+        self.largest_gaps.push(gap);
+        self.largest_gaps
+            .sort_by(|a, b| b.length_seconds.total_cmp(&a.length_seconds));
+        self.largest_gaps.truncate(MAX_CHANNEL_GAPS);
+    }
+}
+
+//For scalers, hits are the summed scaler counts and they are never part of an event
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupStats {
+    pub group: ChannelGroup,
+    pub channels: usize,
+    pub hits: u64,
+    pub hit_rate: f64,
+    pub events: u64,
+    pub event_fraction: f64,
+}
+
+//Hits per channel and events per channel group of one run, from the hits going into the builder
+#[derive(Debug, Clone, Default)]
+pub struct RunStatistics {
+    channels: HashMap<u32, ChannelCounts>,
+    group_events: BTreeMap<ChannelGroup, u64>,
+    events: u64,
+    first_time: Option<f64>,
+    last_time: f64,
+    //Summed span of the runs before the last run boundary (ns)
+    finished_span: f64,
+    //Raw timestamps of the first hit of the run and of the last hit so far, across run boundaries
+    hit_times: Option<(f64, f64)>,
+}
+
+impl RunStatistics {
+    pub fn add_hit(&mut self, hit: &CompassData) {
+        let first_time = *self.first_time.get_or_insert(hit.timestamp);
+        self.last_time = self.last_time.max(hit.timestamp);
+        let run_first_time = self.hit_times.map_or(hit.timestamp, |(first, _)| first);
+        self.hit_times = Some((run_first_time, self.last_time));
+        let position = self.finished_span + hit.timestamp - first_time;
+        let segment_start = self.finished_span;
+        let counts = self.channels.entry(hit.uuid).or_default();
+        counts.hits += 1;
+        counts.add_gap(counts.last_position.unwrap_or(segment_start), position);
+        counts.last_position = Some(position);
+    }
+
+    pub fn add_event(&mut self, event: &[CompassData], map: &ChannelMap) {
+        self.events += 1;
+        let groups: HashSet<ChannelGroup> =
+            event.iter().map(|hit| get_group(hit.uuid, map)).collect();
+        for group in groups {
+            *self.group_events.entry(group).or_default() += 1;
+        }
+    }
+
+    //The timestamps start over at a run boundary, so the spans of the runs are added up
+    pub fn end_run_segment(&mut self) {
+        let segment_end = self.get_position_of_end();
+        for counts in self.channels.values_mut() {
+            if let Some(last) = counts.last_position.take() {
+                counts.add_gap(last, segment_end);
+            }
+        }
+        self.finished_span += self.get_current_span();
+        self.first_time = None;
+        self.last_time = 0.0;
+    }
+
+    fn get_current_span(&self) -> f64 {
+        match self.first_time {
+            Some(first) => self.last_time - first,
+            None => 0.0,
+        }
+    }
+
+    fn get_position_of_end(&self) -> f64 {
+        self.finished_span + self.get_current_span()
+    }
+
+    pub fn get_hit_times(&self) -> Option<(f64, f64)> {
+        self.hit_times
+    }
+
+    //Span of the hit timestamps (s)
+    fn get_duration(&self) -> f64 {
+        (self.finished_span + self.get_current_span()) * 1.0e-9
+    }
+
+    pub fn get_channel_stats(&self, map: &ChannelMap) -> Vec<ChannelStats> {
+        let end = self.get_position_of_end();
+        let mut stats: Vec<ChannelStats> = self
+            .channels
+            .iter()
+            .map(|(uuid, counts)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                //The channel's trailing gap, to the last hit of the run
+                let mut counts = counts.clone();
+                if let Some(last) = counts.last_position {
+                    counts.add_gap(last, end);
+                }
+                ChannelStats {
+                    board_number: board,
+                    channel_number: channel,
+                    detector: map
+                        .get_channel_data(uuid)
+                        .map_or(ChannelType::None, |data| data.channel_type),
+                    hits: counts.hits,
+                    largest_gaps: counts.largest_gaps,
+                }
+            })
+            .collect();
+        stats.sort_by_key(|entry| (entry.board_number, entry.channel_number));
+        stats
+    }
+
+    //Groups with no channels in the data are left out
+    pub fn get_group_stats(&self, map: &ChannelMap, scalers: (usize, u64)) -> Vec<GroupStats> {
+        let duration = self.get_duration();
+        let get_rate = |hits: u64| {
+            if duration > 0.0 {
+                hits as f64 / duration
+            } else {
+                0.0
+            }
+        };
+        ChannelGroup::iter()
+            .filter_map(|group| {
+                let (channels, hits) = if group == ChannelGroup::Scalers {
+                    scalers
+                } else {
+                    self.channels
+                        .iter()
+                        .filter(|(uuid, _)| get_group(**uuid, map) == group)
+                        .fold((0, 0), |(channels, total), (_, counts)| {
+                            (channels + 1, total + counts.hits)
+                        })
+                };
+                if channels == 0 {
+                    return None;
+                }
+                let events = self.group_events.get(&group).copied().unwrap_or(0);
+                Some(GroupStats {
+                    group,
+                    channels,
+                    hits,
+                    hit_rate: get_rate(hits),
+                    events,
+                    event_fraction: if self.events > 0 {
+                        events as f64 / self.events as f64
+                    } else {
+                        0.0
+                    },
+                })
+            })
+            .collect()
+    }
+
+    pub fn log_summary(groups: &[GroupStats]) {
+        for stats in groups.iter() {
+            if stats.group == ChannelGroup::Scalers {
+                info!(
+                    "{}: {} scalers, {} counts",
+                    stats.group.as_ref(),
+                    stats.channels,
+                    format_count(stats.hits)
+                );
+                continue;
+            }
+            info!(
+                "{}: {} channels, {} hits ({}), in {:.1}% of events",
+                stats.group.as_ref(),
+                stats.channels,
+                format_count(stats.hits),
+                format_rate(stats.hit_rate, "hits"),
+                stats.event_fraction * 100.0
+            );
+        }
+    }
+}
+
+//The longest gaps of any channel, so a dropout of one board or channel is seen in the log
+pub fn log_largest_gaps(channels: &[ChannelStats]) {
+    let mut gaps: Vec<(&ChannelStats, &TimestampGap)> = channels
+        .iter()
+        .flat_map(|stats| stats.largest_gaps.iter().map(move |gap| (stats, gap)))
+        .collect();
+    gaps.sort_by(|a, b| b.1.length_seconds.total_cmp(&a.1.length_seconds));
+    for (stats, gap) in gaps.iter().take(MAX_LOGGED_GAPS) {
+        info!(
+            "Gap of {:.3} s in board {} channel {} ({}) at {} into the run",
+            gap.length_seconds,
+            stats.board_number,
+            stats.channel_number,
+            stats.detector,
+            format_duration(gap.start_seconds)
+        );
+    }
+}
+
+fn get_group(uuid: u32, map: &ChannelMap) -> ChannelGroup {
+    map.get_channel_data(&uuid)
+        .map_or(ChannelGroup::Unmapped, |data| data.channel_type.get_group())
+}
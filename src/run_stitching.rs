@@ -0,0 +1,105 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::event_builder::OpenWindow;
+use super::parquet_writer::ParquetMetadata;
+
+//Back-to-back runs of a DAQ that restarts on its own, with the clock starting from zero each time.
+//The window still open at the end of a run is carried into the next run, so a coincidence across
+//the seam is built instead of dropped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunStitchingParams {
+    pub enabled: bool,
+    //Time from the last hit of a run to the start of the clock of the next, as measured for the
+    //restart of the DAQ
+    pub restart_gap_seconds: f64,
+}
+
+impl Default for RunStitchingParams {
+    fn default() -> Self {
+        RunStitchingParams {
+            enabled: false,
+            restart_gap_seconds: 0.0,
+        }
+    }
+}
+
+//The open window of a run, in the clock of that run
+#[derive(Debug)]
+struct CarriedWindow {
+    run_number: i32,
+    window: OpenWindow,
+}
+
+//Where the window of the run before joins the run being built
+#[derive(Debug, Clone)]
+pub struct Seam {
+    pub from_run: i32,
+    //Start of the clock of this run in the clock of the run before (ns)
+    pub offset: f64,
+    pub window: OpenWindow,
+}
+
+impl Seam {
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("stitched_from_run", self.from_run.to_string());
+        metadata.insert_file("stitch_offset_ns", self.offset.to_string());
+    }
+}
+
+//Carries the open window from one run of a job to the next
+#[derive(Debug)]
+pub struct RunStitcher {
+    restart_gap: f64,
+    carried: Option<CarriedWindow>,
+}
+
+impl RunStitcher {
+    pub fn new(params: &RunStitchingParams) -> Option<Self> {
+        if !params.enabled {
+            return None;
+        }
+        info!(
+            "Stitching consecutive runs, with {} s between the last hit of a run and the next",
+            params.restart_gap_seconds
+        );
+        Some(RunStitcher {
+            restart_gap: params.restart_gap_seconds * 1.0e9,
+            carried: None,
+        })
+    }
+
+    //The window carried from the run right before, moved onto the clock of this run. Only a run
+    //that ends normally replaces it, so a run built a second time gets the same seam.
+    pub fn get_seam(&self, run_number: i32) -> Option<Seam> {
+        let carried = self
+            .carried
+            .as_ref()
+            .filter(|carried| carried.run_number + 1 == run_number)?;
+        let offset = carried.window.get_last_time()? + self.restart_gap;
+        let mut window = carried.window.clone();
+        window.shift(-offset);
+        info!(
+            "Run {} starts with {} hits still open at the end of run {}",
+            run_number,
+            window.len(),
+            carried.run_number
+        );
+        Some(Seam {
+            from_run: carried.run_number,
+            offset,
+            window,
+        })
+    }
+
+    pub fn carry(&mut self, run_number: i32, window: OpenWindow) {
+        if !window.is_empty() {
+            info!(
+                "Carrying {} hits of the open window of run {} into the next run",
+                window.len(),
+                run_number
+            );
+        }
+        self.carried = Some(CarriedWindow { run_number, window });
+    }
+}
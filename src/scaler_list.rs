@@ -2,7 +2,9 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 
+use super::compass_data::EnergyWidth;
 use super::compass_file::CompassFile;
+use super::hit_source::HitSource;
 
 const INVALID_SCALER_PATTERN: &str = "InvalidScalerPattern";
 const INVALID_SCALER_NAME: &str = "InvalidScaler";
@@ -54,7 +56,7 @@ impl ScalerList {
     }
 
     //Check if file is a scaler, read counts if yes
-    pub fn read_scaler(&mut self, filepath: &Path) -> bool {
+    pub fn read_scaler(&mut self, filepath: &Path, energy_widths: &[EnergyWidth]) -> bool {
         for scaler in self.list.iter_mut() {
             match filepath.file_name() {
                 Some(file_name) => {
@@ -63,8 +65,9 @@ impl ScalerList {
                         .expect("Could not parse file name at ScalerList::read_scaler")
                         .starts_with(&scaler.file_pattern)
                     {
-                        if let Ok(compass_rep) = CompassFile::new(filepath, &None) {
-                            scaler.value = compass_rep.get_number_of_hits();
+                        //Long runs are split into segments, which all count toward the scaler
+                        if let Ok(compass_rep) = CompassFile::new(filepath, &None, energy_widths) {
+                            scaler.value += compass_rep.get_number_of_hits();
                             return true;
                         }
                     } else {
@@ -78,6 +81,14 @@ impl ScalerList {
         false
     }
 
+    //Number of scalers and their summed counts
+    pub fn get_total_counts(&self) -> (usize, u64) {
+        (
+            self.list.len(),
+            self.list.iter().map(|scaler| scaler.value).sum(),
+        )
+    }
+
     pub fn write_scalers(&self, filepath: &Path) -> Result<(), std::io::Error> {
         let file = File::create(filepath)?;
         let mut writer = BufWriter::new(file);
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::error::EVBError;
+use super::formatting::format_count;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScalerRateParams {
+    //Width of the time bins the scaler channels are counted in (s)
+    pub interval: f64,
+}
+
+impl Default for ScalerRateParams {
+    fn default() -> Self {
+        ScalerRateParams { interval: 1.0 }
+    }
+}
+
+//Written to the report of every run with scaler channels
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScalerChannelSummary {
+    pub board: u32,
+    pub channel: u32,
+    pub counts: u64,
+    //Counts over the span of the run (Hz)
+    pub mean_rate: f64,
+}
+
+//Counts of the channels mapped as Scaler (e.g. a beam current integrator or a pulser) in time bins
+//over the run. Their hits are taken out of the hit stream before the event builder, so they are
+//never part of an event.
+pub struct ScalerRates {
+    interval_ns: f64,
+    //Counts per bin of every scaler channel, by uuid
+    counts: BTreeMap<u32, Vec<u64>>,
+    first_time: Option<f64>,
+    last_time: f64,
+    //Summed span of the runs before the last run boundary (ns)
+    finished_span: f64,
+}
+
+impl ScalerRates {
+    pub fn new(params: &ScalerRateParams, channel_map: &ChannelMap) -> Self {
+        ScalerRates {
+            interval_ns: params.interval * 1.0e9,
+            counts: channel_map
+                .get_uuids_of_type(ChannelType::Scaler)
+                .into_iter()
+                .map(|uuid| (uuid, vec![]))
+                .collect(),
+            first_time: None,
+            last_time: 0.0,
+            finished_span: 0.0,
+        }
+    }
+
+    //Whether a hit belongs to a scaler channel, counting it if so
+    pub fn add_hit(&mut self, hit: &CompassData) -> bool {
+        let counts = match self.counts.get_mut(&hit.uuid) {
+            Some(counts) => counts,
+            None => return false,
+        };
+        let first_time = *self.first_time.get_or_insert(hit.timestamp);
+        self.last_time = self.last_time.max(hit.timestamp);
+        let position = self.finished_span + (hit.timestamp - first_time).max(0.0);
+        let bin = (position / self.interval_ns) as usize;
+        if bin >= counts.len() {
+            counts.resize(bin + 1, 0);
+        }
+        counts[bin] += 1;
+        true
+    }
+
+    //The timestamps start over at a run boundary, so the runs follow each other in time
+    pub fn end_run_segment(&mut self) {
+        self.finished_span += self.get_current_span();
+        self.first_time = None;
+        self.last_time = 0.0;
+    }
+
+    fn get_current_span(&self) -> f64 {
+        match self.first_time {
+            Some(first) => self.last_time - first,
+            None => 0.0,
+        }
+    }
+
+    //Number of scaler channels and their summed counts
+    pub fn get_total_counts(&self) -> (usize, u64) {
+        (
+            self.counts.len(),
+            self.counts.values().flat_map(|counts| counts.iter()).sum(),
+        )
+    }
+
+    pub fn get_summary(&self) -> Vec<ScalerChannelSummary> {
+        let duration = (self.finished_span + self.get_current_span()) * 1.0e-9;
+        self.counts
+            .iter()
+            .map(|(uuid, counts)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let total: u64 = counts.iter().sum();
+                ScalerChannelSummary {
+                    board,
+                    channel,
+                    counts: total,
+                    mean_rate: if duration > 0.0 {
+                        total as f64 / duration
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+
+    pub fn log_summary(&self) {
+        for summary in self.get_summary() {
+            info!(
+                "Scaler board {} channel {}: {} counts, {:.2} Hz",
+                summary.board,
+                summary.channel,
+                format_count(summary.counts),
+                summary.mean_rate
+            );
+        }
+    }
+
+    //One row per time bin, the counts and the rate (Hz) of every scaler channel. The last bin is
+    //usually only partly covered by the run, so its rate reads low.
+    pub fn write_rates(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!("Writing scaler rates to {}", filepath.display());
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .counts
+            .keys()
+            .map(|uuid| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                format!(
+                    "Scaler_{}_{}_counts,Scaler_{}_{}_rate",
+                    board, channel, board, channel
+                )
+            })
+            .collect();
+        writeln!(writer, "time_low,time_high,{}", headers.join(","))?;
+
+        let bins = self.counts.values().map(Vec::len).max().unwrap_or(0);
+        let interval = self.interval_ns * 1.0e-9;
+        for bin in 0..bins {
+            let low = interval * (bin as f64);
+            let columns: Vec<String> = self
+                .counts
+                .values()
+                .map(|counts| {
+                    let count = counts.get(bin).copied().unwrap_or(0);
+                    format!("{},{}", count, count as f64 / interval)
+                })
+                .collect();
+            writeln!(writer, "{},{},{}", low, low + interval, columns.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//Shared-memory ring buffer of built events for an online histogrammer on the same machine.
+//The layout is documented in the README under "Shared Memory Output", keep the two in sync.
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::sync::atomic::{fence, AtomicU64, Ordering};
+
+use memmap2::MmapMut;
+
+use super::build_diagnostics::emit_warning;
+use super::error::EVBError;
+use super::output_sink::OutputSink;
+
+const SHM_MAGIC: u32 = 0x52424543;
+const SHM_VERSION: u32 = 2;
+const SHM_HEADER_SIZE: usize = 64;
+const SHM_FIELD_NAME_SIZE: usize = 32;
+const SHM_WRITE_INDEX_OFFSET: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedMemoryParams {
+    pub enabled: bool,
+    pub path: PathBuf,
+    pub slots: u32,
+}
+
+impl Default for SharedMemoryParams {
+    fn default() -> Self {
+        SharedMemoryParams {
+            enabled: false,
+            path: PathBuf::from("/dev/shm/cebra_evb"),
+            slots: 65536,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SharedMemoryWriter {
+    mmap: MmapMut,
+    n_slots: u64,
+    n_fields: usize,
+    slot_offset: usize,
+    //The sequence word and the fields
+    slot_size: usize,
+}
+
+impl SharedMemoryWriter {
+    pub fn new(params: &SharedMemoryParams, field_names: &[String]) -> Result<Self, EVBError> {
+        let n_fields = field_names.len();
+        let n_slots = params.slots.max(1) as usize;
+        let slot_offset = SHM_HEADER_SIZE + SHM_FIELD_NAME_SIZE * n_fields;
+        let slot_size = (n_fields + 1) * std::mem::size_of::<f64>();
+        let total_size = slot_offset + n_slots * slot_size;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&params.path)?;
+        file.set_len(total_size as u64)?;
+        // Safety: the file was just created/truncated by us and is only written through this map
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        mmap[0..4].copy_from_slice(&SHM_MAGIC.to_le_bytes());
+        mmap[4..8].copy_from_slice(&SHM_VERSION.to_le_bytes());
+        mmap[8..12].copy_from_slice(&(n_slots as u32).to_le_bytes());
+        mmap[12..16].copy_from_slice(&(n_fields as u32).to_le_bytes());
+        mmap[16..24].copy_from_slice(&0u64.to_le_bytes());
+        let mut cut_names = vec![];
+        for (index, name) in field_names.iter().enumerate() {
+            let start = SHM_HEADER_SIZE + index * SHM_FIELD_NAME_SIZE;
+            let name = get_field_name(name);
+            if name.len() < field_names[index].len() {
+                cut_names.push(format!("{} to {}", field_names[index], name));
+            }
+            mmap[start..start + name.len()].copy_from_slice(name.as_bytes());
+        }
+        if !cut_names.is_empty() {
+            emit_warning(
+                "shared_memory",
+                params.path.display().to_string(),
+                format!(
+                    "Field names longer than {} bytes are cut in the shared memory ring: {}",
+                    SHM_FIELD_NAME_SIZE - 1,
+                    cut_names.join(", ")
+                ),
+            );
+        }
+
+        Ok(SharedMemoryWriter {
+            mmap,
+            n_slots: n_slots as u64,
+            n_fields,
+            slot_offset,
+            slot_size,
+        })
+    }
+
+    fn write_index(&self) -> &AtomicU64 {
+        self.get_atomic(SHM_WRITE_INDEX_OFFSET)
+    }
+
+    fn get_atomic(&self, offset: usize) -> &AtomicU64 {
+        // Safety: the map is page aligned and the header, names and slots are multiples of 8 bytes,
+        // so the index and every sequence word are 8 byte aligned, and they live as long as the map
+        unsafe { &*(self.mmap.as_ptr().add(offset) as *const AtomicU64) }
+    }
+}
+
+//The longest start of the name that fits with its NUL, cut between characters
+fn get_field_name(name: &str) -> &str {
+    let mut len = name.len().min(SHM_FIELD_NAME_SIZE - 1);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    &name[..len]
+}
+
+//Every event is published as it is received, the ring needs no flushing
+impl OutputSink for SharedMemoryWriter {
+    fn name(&self) -> &str {
+        "shared memory"
+    }
+
+    //The field names are written when the ring is created
+    fn start(&mut self, _columns: &[String]) -> Result<(), EVBError> {
+        Ok(())
+    }
+
+    fn receive(&mut self, _run_number: i32, event: &[f64]) -> Result<(), EVBError> {
+        let index = self.write_index().load(Ordering::Relaxed);
+        let slot_start = self.slot_offset + ((index % self.n_slots) as usize) * self.slot_size;
+        //A seqlock: the sequence word is odd while the slot is written, so a consumer that finds
+        //it changed after copying the slot knows the copy may be torn
+        self.get_atomic(slot_start)
+            .store(2 * index + 1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        for (field_index, value) in event.iter().take(self.n_fields).enumerate() {
+            let start = slot_start + (field_index + 1) * 8;
+            self.mmap[start..start + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        self.get_atomic(slot_start)
+            .store(2 * index + 2, Ordering::Release);
+        self.write_index().store(index + 1, Ordering::Release);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EVBError> {
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<(), EVBError> {
+        Ok(())
+    }
+}
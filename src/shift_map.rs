@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::num::ParseFloatError;
 use std::num::ParseIntError;
+use std::path::Path;
 
+use super::channel_map::ChannelMap;
 use super::compass_data::generate_board_channel_uuid;
 
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone, PartialEq)]
@@ -17,6 +19,7 @@ pub enum ShiftError {
     File(std::io::Error),
     Channel(ParseIntError),
     Timeshift(ParseFloatError),
+    Format(usize),
 }
 
 impl Display for ShiftError {
@@ -27,6 +30,11 @@ impl Display for ShiftError {
                 write!(f, "ShiftMap could not parse board/channel: {}", x)
             }
             ShiftError::Timeshift(x) => write!(f, "ShiftMap could not parse timeshift: {}", x),
+            ShiftError::Format(line) => write!(
+                f,
+                "ShiftMap line {} is not <board> <channel> <shift in ns>",
+                line
+            ),
         }
     }
 }
@@ -51,6 +59,39 @@ impl From<ParseFloatError> for ShiftError {
 
 impl std::error::Error for ShiftError {}
 
+//Shift maps written by hand or by other tools, one channel per line: board, channel and time shift
+//(ns), separated by commas or whitespace. Blank lines and everything after a # are ignored, and so
+//is a first line of column names.
+pub fn read_shift_file(path: &Path) -> Result<Vec<ShiftMapEntry>, ShiftError> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut entries = vec![];
+    let mut is_first = true;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .collect();
+        let is_header = is_first && fields[0].parse::<u32>().is_err();
+        is_first = false;
+        if is_header {
+            continue;
+        }
+        if fields.len() != 3 {
+            return Err(ShiftError::Format(index + 1));
+        }
+        entries.push(ShiftMapEntry {
+            board_number: fields[0].parse()?,
+            channel_number: fields[1].parse()?,
+            time_shift: fields[2].parse()?,
+        });
+    }
+    Ok(entries)
+}
+
 #[derive(Debug, Clone)]
 pub struct ShiftMap {
     map: HashMap<u32, f64>,
@@ -66,6 +107,10 @@ impl ShiftMap {
         ShiftMap { map }
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
     pub fn get_timeshift(&self, id: &u32) -> f64 {
         if let Some(value) = self.map.get(id) {
             *value
@@ -73,4 +118,44 @@ impl ShiftMap {
             0.0
         }
     }
+
+    //How far the shifts can move two hits against each other (ns), channels without a shift moving
+    //by 0. Hits of a time-merged file come out of order by no more than this.
+    pub fn get_shift_spread(&self) -> f64 {
+        let largest = self
+            .map
+            .values()
+            .fold(0.0_f64, |largest, shift| largest.max(*shift));
+        let smallest = self
+            .map
+            .values()
+            .fold(0.0_f64, |smallest, shift| smallest.min(*shift));
+        largest - smallest
+    }
+
+    //The shifts of the channels of every CeBrA detector with any, e.g. "0/1: 2.5, 0/2: -1" by board
+    //and channel, keyed by detector number
+    pub fn describe_detector_shifts(&self, channel_map: &ChannelMap) -> BTreeMap<usize, String> {
+        let mut shifts = BTreeMap::new();
+        for entry in channel_map.get_detector_channels() {
+            let detector = match entry.detector.get_detector() {
+                Some(detector) => detector,
+                None => continue,
+            };
+            let described: Vec<String> = entry
+                .channels
+                .iter()
+                .filter_map(|mapped| {
+                    let id = generate_board_channel_uuid(&mapped.board, &mapped.channel);
+                    self.map
+                        .get(&id)
+                        .map(|shift| format!("{}/{}: {}", mapped.board, mapped.channel, shift))
+                })
+                .collect();
+            if !described.is_empty() {
+                shifts.insert(detector, described.join(", "));
+            }
+        }
+        shifts
+    }
 }
@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+//Set by SIGINT or SIGTERM. The run being built stops at its next hit and is written as usual,
+//marked partial, and no further runs are started.
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+//How often the GUI looks at the flag while it is idle
+const GUI_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    //A second Ctrl-C does not wait for the tables to be written
+    if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+//Only on unix, elsewhere a signal still ends the process right away
+pub fn install_shutdown_handler() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, handle_signal as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, handle_signal as libc::sighandler_t);
+    }
+}
+
+pub fn is_shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::Relaxed)
+}
+
+//An idle GUI only updates on input, so it is woken up once a shutdown is requested
+pub fn wake_on_shutdown(ctx: egui::Context) {
+    std::thread::spawn(move || {
+        while !is_shutdown_requested() {
+            std::thread::sleep(GUI_POLL_INTERVAL);
+        }
+        ctx.request_repaint();
+    });
+}
@@ -0,0 +1,251 @@
+use std::io::BufRead;
+use std::path::Path;
+
+use polars::prelude::*;
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::csv_file::open_reader;
+use super::error::EVBError;
+use super::hit_source::HitSource;
+use super::used_size::UsedSize;
+
+//Accepted column names, compared case-insensitively
+const DETECTOR_NAMES: [&str; 2] = ["detector", "det"];
+const ENERGY_NAMES: [&str; 2] = ["energy", "edep"];
+const TIME_NAMES: [&str; 2] = ["time", "t"];
+const ENERGY_SHORT_NAMES: [&str; 2] = ["energyshort", "energy_short"];
+
+const SIM_SUFFIXES: [&str; 3] = [".sim.csv", ".sim.csv.gz", ".sim.parquet"];
+
+pub fn is_sim_path(path: &Path) -> bool {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => {
+            let name = name.to_lowercase();
+            SIM_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        }
+        None => false,
+    }
+}
+
+fn find_column(names: &[String], candidates: &[&str]) -> Option<usize> {
+    names
+        .iter()
+        .position(|name| candidates.contains(&name.to_lowercase().as_str()))
+}
+
+fn require_column(names: &[String], candidates: &[&str]) -> Result<usize, EVBError> {
+    find_column(names, candidates).ok_or_else(|| {
+        EVBError::Simulation(format!(
+            "columns '{}' have no {} column",
+            names.join(","),
+            candidates[0]
+        ))
+    })
+}
+
+//Simulated hits name their detector, which is put on the first channel mapped to it
+fn get_detector_uuids(map: &ChannelMap) -> Vec<(String, u32)> {
+    ChannelType::get_types(map.get_detector_count(), map.get_passthrough_count())
+        .into_iter()
+        .filter_map(|detector| {
+            map.get_uuids_of_type(detector)
+                .into_iter()
+                .min()
+                .map(|uuid| (detector.to_string().to_lowercase(), uuid))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct SimHit {
+    detector: String,
+    energy: f64,
+    energy_short: f64,
+    time: f64,
+}
+
+fn read_csv_hits(path: &Path) -> Result<Vec<SimHit>, EVBError> {
+    let mut lines = open_reader(path)?.lines();
+    let header = match lines.next() {
+        Some(header) => header?,
+        None => return Ok(vec![]),
+    };
+    let delimiter = if header.contains(';') { ';' } else { ',' };
+    let names: Vec<String> = header
+        .split(delimiter)
+        .map(|name| name.trim().to_string())
+        .collect();
+    let detector = require_column(&names, &DETECTOR_NAMES)?;
+    let energy = require_column(&names, &ENERGY_NAMES)?;
+    let time = require_column(&names, &TIME_NAMES)?;
+    let energy_short = find_column(&names, &ENERGY_SHORT_NAMES);
+
+    let mut hits = vec![];
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values: Vec<&str> = line.split(delimiter).map(|value| value.trim()).collect();
+        let parse = |column: usize| -> Result<f64, EVBError> {
+            let value = values.get(column).copied().unwrap_or_default();
+            value.parse::<f64>().map_err(|_| {
+                EVBError::Simulation(format!(
+                    "line {}: could not parse '{}' in column {}",
+                    index + 2,
+                    value,
+                    names[column]
+                ))
+            })
+        };
+        hits.push(SimHit {
+            detector: values
+                .get(detector)
+                .copied()
+                .unwrap_or_default()
+                .to_lowercase(),
+            energy: parse(energy)?,
+            energy_short: match energy_short {
+                Some(column) => parse(column)?,
+                None => 0.0,
+            },
+            time: parse(time)?,
+        });
+    }
+    Ok(hits)
+}
+
+fn read_parquet_hits(path: &Path) -> Result<Vec<SimHit>, EVBError> {
+    let frame = LazyFrame::scan_parquet(path, ScanArgsParquet::default())?;
+    let names: Vec<String> = frame
+        .schema()?
+        .iter_names()
+        .map(|name| name.to_string())
+        .collect();
+    let detector = &names[require_column(&names, &DETECTOR_NAMES)?];
+    let energy = &names[require_column(&names, &ENERGY_NAMES)?];
+    let time = &names[require_column(&names, &TIME_NAMES)?];
+    let energy_short = match find_column(&names, &ENERGY_SHORT_NAMES) {
+        Some(column) => col(&names[column]).cast(DataType::Float64),
+        None => lit(0.0),
+    };
+    let df = frame
+        .select([
+            col(detector).cast(DataType::String).alias("detector"),
+            col(energy).cast(DataType::Float64).alias("energy"),
+            energy_short.alias("energy_short"),
+            col(time).cast(DataType::Float64).alias("time"),
+        ])
+        .collect()?;
+    let hits = df
+        .column("detector")?
+        .str()?
+        .into_iter()
+        .zip(df.column("energy")?.f64()?)
+        .zip(df.column("energy_short")?.f64()?)
+        .zip(df.column("time")?.f64()?)
+        .map(|(((detector, energy), energy_short), time)| SimHit {
+            detector: detector.unwrap_or_default().to_lowercase(),
+            energy: energy.unwrap_or(0.0),
+            energy_short: energy_short.unwrap_or(0.0),
+            time: time.unwrap_or(0.0),
+        })
+        .collect();
+    Ok(hits)
+}
+
+//Hits from a simulation (e.g. GEANT4), one per row with the detector name as in the channel map,
+//the deposited energy and the time in ns. The whole file is read and sorted by time up front,
+//since simulations usually write hits event by event rather than in time order.
+pub struct SimFile {
+    hits: Vec<CompassData>,
+    index: usize,
+    current_hit: CompassData,
+    is_used: bool,
+    is_eof: bool,
+}
+
+impl SimFile {
+    pub fn new(path: &Path, map: &ChannelMap) -> Result<SimFile, EVBError> {
+        let is_parquet = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("parquet"));
+        let sim_hits = if is_parquet {
+            read_parquet_hits(path)?
+        } else {
+            read_csv_hits(path)?
+        };
+
+        let detectors = get_detector_uuids(map);
+        let mut hits = Vec::with_capacity(sim_hits.len());
+        for hit in sim_hits {
+            let uuid = match detectors.iter().find(|(name, _)| *name == hit.detector) {
+                Some((_, uuid)) => *uuid,
+                None => {
+                    return Err(EVBError::Simulation(format!(
+                        "detector '{}' is not in the channel map",
+                        hit.detector
+                    )))
+                }
+            };
+            hits.push(CompassData {
+                uuid,
+                energy: hit.energy,
+                energy_short: hit.energy_short,
+                timestamp: hit.time,
+                raw_timestamp: hit.time,
+                waveform: None,
+                flags: 0,
+            });
+        }
+        hits.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+        Ok(SimFile {
+            hits,
+            index: 0,
+            current_hit: CompassData::default(),
+            is_used: false,
+            is_eof: false,
+        })
+    }
+}
+
+impl HitSource for SimFile {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            match self.hits.get(self.index) {
+                Some(hit) => {
+                    self.current_hit = hit.clone();
+                    self.index += 1;
+                    self.is_used = false;
+                }
+                None => {
+                    self.current_hit = CompassData::default();
+                    self.is_eof = true;
+                }
+            }
+        }
+
+        Ok(&self.current_hit)
+    }
+
+    fn is_eof(&self) -> bool {
+        self.is_eof
+    }
+
+    fn set_hit_used(&mut self) {
+        self.is_used = true;
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.hits.len() as u64
+    }
+}
+
+impl UsedSize for SimFile {
+    fn get_used_size(&self) -> usize {
+        self.hits.capacity() * std::mem::size_of::<CompassData>() + std::mem::size_of::<SimFile>()
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+
+//An input file left out of one run, e.g. a known corrupt segment, so the archive can stay as the
+//DAQ wrote it. Like the scaler list, the pattern is matched against the start of the file names in
+//the archive: a full name skips one segment, Data_CH4@V1730_89_run_12 every segment of a channel.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SkipEntry {
+    pub run: i32,
+    pub file_pattern: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+//Written to the report of every run with skipped files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedFile {
+    pub file: String,
+    pub file_pattern: String,
+    pub reason: String,
+}
+
+#[derive(Debug)]
+pub struct SkipList<'a> {
+    entries: Vec<&'a SkipEntry>,
+    skipped: Vec<SkippedFile>,
+}
+
+impl<'a> SkipList<'a> {
+    pub fn new(entries: &'a [SkipEntry], run_number: i32) -> Self {
+        SkipList {
+            entries: entries
+                .iter()
+                .filter(|entry| entry.run == run_number && !entry.file_pattern.is_empty())
+                .collect(),
+            skipped: vec![],
+        }
+    }
+
+    //Records the file as skipped if an entry matches it
+    pub fn check_file(&mut self, path: &Path) -> bool {
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return false,
+        };
+        match self
+            .entries
+            .iter()
+            .find(|entry| name.starts_with(&entry.file_pattern))
+        {
+            Some(entry) => {
+                emit_warning(
+                    "skip_list_file",
+                    name.to_string(),
+                    format!(
+                        "Skipping input file of the skip list ({})",
+                        if entry.reason.is_empty() {
+                            "no reason given"
+                        } else {
+                            &entry.reason
+                        }
+                    ),
+                );
+                self.skipped.push(SkippedFile {
+                    file: name.to_string(),
+                    file_pattern: entry.file_pattern.clone(),
+                    reason: entry.reason.clone(),
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    //A pattern that matched nothing is usually a typo, and the bad file was built after all
+    pub fn warn_unused(&self) {
+        for entry in self.entries.iter() {
+            if !self
+                .skipped
+                .iter()
+                .any(|skipped| skipped.file_pattern == entry.file_pattern)
+            {
+                emit_warning(
+                    "skip_list_unused",
+                    entry.file_pattern.clone(),
+                    String::from("Skip list pattern matched no input file"),
+                );
+            }
+        }
+    }
+
+    pub fn get_skipped(&self) -> Vec<SkippedFile> {
+        self.skipped.clone()
+    }
+}
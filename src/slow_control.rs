@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use polars::prelude::*;
+
+use super::channel_data::TimeUnit;
+use super::error::EVBError;
+use super::parquet_writer::{write_parquet, ParquetMetadata};
+
+const TIME_COLUMN_NAMES: [&str; 2] = ["time", "timestamp"];
+
+pub fn is_slow_control_path(path: &Path, pattern: &str) -> bool {
+    if pattern.is_empty() {
+        return false;
+    }
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| name.contains(pattern))
+}
+
+//Board temperature/HV readings exported by the DAQ alongside the data. Every file is a header
+//line followed by rows of numbers, with the time in seconds since the start of the run in the
+//time/timestamp column. Files may have different columns (e.g. one per board); the table has
+//the union of them, with missing readings left null.
+#[derive(Debug, Default)]
+pub struct SlowControlTable {
+    time: Vec<f64>,
+    columns: BTreeMap<String, Vec<Option<f64>>>,
+}
+
+impl SlowControlTable {
+    pub fn is_empty(&self) -> bool {
+        self.time.is_empty()
+    }
+
+    fn push_row(&mut self, time: f64, values: Vec<(String, Option<f64>)>) {
+        let row = self.time.len();
+        self.time.push(time);
+        for (name, value) in values {
+            let column = self.columns.entry(name).or_insert_with(|| vec![None; row]);
+            column.push(value);
+        }
+        for column in self.columns.values_mut() {
+            if column.len() <= row {
+                column.push(None);
+            }
+        }
+    }
+
+    pub fn read_file(&mut self, path: &Path) -> Result<(), EVBError> {
+        info!("Reading slow control log {}", path.display());
+        let contents = std::fs::read_to_string(path)?;
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+        let header = match lines.next() {
+            Some(header) => header,
+            None => return Ok(()),
+        };
+        let delimiter = if header.contains(';') { ';' } else { ',' };
+        let names: Vec<String> = header
+            .split(delimiter)
+            .map(|name| name.trim().to_string())
+            .collect();
+        let time_index = names
+            .iter()
+            .position(|name| TIME_COLUMN_NAMES.contains(&name.to_lowercase().as_str()))
+            .ok_or_else(|| {
+                EVBError::Csv(format!(
+                    "slow control log {} has no time column",
+                    path.display()
+                ))
+            })?;
+
+        for (line_index, line) in lines.enumerate() {
+            let values: Vec<&str> = line.split(delimiter).map(|v| v.trim()).collect();
+            let time = values
+                .get(time_index)
+                .and_then(|v| v.parse::<f64>().ok())
+                .ok_or_else(|| {
+                    EVBError::Csv(format!(
+                        "slow control log {} line {}: could not parse the time",
+                        path.display(),
+                        line_index + 2
+                    ))
+                })?;
+            let readings = names
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != time_index)
+                .map(|(index, name)| {
+                    let value = values.get(index).and_then(|v| v.parse::<f64>().ok());
+                    (name.clone(), value)
+                })
+                .collect();
+            self.push_row(time, readings);
+        }
+        Ok(())
+    }
+
+    pub fn read_files(paths: &[PathBuf]) -> Result<Self, EVBError> {
+        let mut table = SlowControlTable::default();
+        for path in paths.iter() {
+            table.read_file(path)?;
+        }
+        Ok(table)
+    }
+
+    //Time is written in the output time unit, so it lines up with the event Time columns
+    pub fn write_table(self, filepath: &Path, time_unit: TimeUnit) -> Result<(), EVBError> {
+        info!("Writing slow control table to {}", filepath.display());
+        let mut order: Vec<usize> = (0..self.time.len()).collect();
+        order.sort_by(|a, b| self.time[*a].total_cmp(&self.time[*b]));
+
+        let time: Vec<f64> = order
+            .iter()
+            .map(|row| time_unit.convert_nanoseconds(self.time[*row] * 1.0e9))
+            .collect();
+        let mut series = vec![Series::new("Time", time)];
+        for (name, column) in self.columns.iter() {
+            let values: Vec<Option<f64>> = order.iter().map(|row| column[*row]).collect();
+            series.push(Series::new(name, values));
+        }
+        let mut df = DataFrame::new(series)?;
+
+        let mut metadata = ParquetMetadata::default();
+        metadata.insert_file("time_unit", time_unit.as_ref().to_string());
+        metadata.insert_column("Time", "unit", time_unit.as_ref().to_string());
+        let mut file = File::create(filepath)?;
+        write_parquet(&mut file, &mut df, &metadata, &[])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_data::{ChannelData, ChannelDataField};
+use super::error::EVBError;
+use super::focal_plane::SpsParams;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateSpectraParams {
+    pub enabled: bool,
+    pub bins: usize,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for StateSpectraParams {
+    fn default() -> Self {
+        StateSpectraParams {
+            enabled: false,
+            bins: 4096,
+            min: 0.0,
+            max: 4096.0,
+        }
+    }
+}
+
+//Gamma energy spectrum of every state gate, summed over all CeBrA detectors, filled as events are
+//built so that a gated spectrum is available as soon as the run finishes
+#[derive(Debug, Clone)]
+pub struct StateSpectra {
+    names: Vec<String>,
+    params: StateSpectraParams,
+    counts: Vec<Vec<u64>>,
+}
+
+impl StateSpectra {
+    pub fn new(sps: &SpsParams) -> Self {
+        let params = sps.spectra.clone();
+        StateSpectra {
+            names: sps
+                .state_gates
+                .iter()
+                .map(|gate| gate.name.clone())
+                .collect(),
+            counts: vec![vec![0; params.bins]; sps.state_gates.len()],
+            params,
+        }
+    }
+
+    fn get_bin(&self, energy: f64) -> Option<usize> {
+        if energy < self.params.min || energy >= self.params.max {
+            return None;
+        }
+        let width = (self.params.max - self.params.min) / (self.params.bins as f64);
+        Some((((energy - self.params.min) / width) as usize).min(self.params.bins - 1))
+    }
+
+    //Fill from the most recently appended event
+    pub fn fill(&mut self, data: &ChannelData) {
+        let state = match data.get_last_value(&ChannelDataField::StateId) {
+            Some(id) => id as usize,
+            None => return,
+        };
+        for field in data.fields.keys().filter(|field| field.is_energy()) {
+            let bin = match data.get_last_value(field).and_then(|e| self.get_bin(e)) {
+                Some(bin) => bin,
+                None => continue,
+            };
+            if let Some(spectrum) = self.counts.get_mut(state) {
+                spectrum[bin] += 1;
+            }
+        }
+    }
+
+    //One row per bin, one column per state gate
+    pub fn write_spectra(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!("Writing state gated spectra to {}", filepath.display());
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| format!("{}_{}", id, name))
+            .collect();
+        writeln!(writer, "bin_low,bin_high,{}", headers.join(","))?;
+
+        let width = (self.params.max - self.params.min) / (self.params.bins as f64);
+        for bin in 0..self.params.bins {
+            let low = self.params.min + width * (bin as f64);
+            let counts: Vec<String> = self
+                .counts
+                .iter()
+                .map(|spectrum| spectrum[bin].to_string())
+                .collect();
+            writeln!(writer, "{},{},{}", low, low + width, counts.join(","))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
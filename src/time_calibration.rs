@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData};
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::shift_map::{ShiftMap, ShiftMapEntry};
+
+//Gaussian sigma to FWHM
+const FWHM_PER_SIGMA: f64 = 2.354_820_045;
+
+//A pulser run gets a window wide enough to catch every channel even before the offsets are known
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PulserParams {
+    pub enabled: bool,
+    //Coincidence window used for pulser runs (ns), in place of the normal one
+    pub coincidence_window: f64,
+}
+
+impl Default for PulserParams {
+    fn default() -> Self {
+        PulserParams {
+            enabled: false,
+            coincidence_window: 1.0e5,
+        }
+    }
+}
+
+//One line of run_<num>_pulser_summary.yaml. Offsets are relative to the TimeCalibrator channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct PulserSummaryEntry {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub detector: ChannelType,
+    pub hits: u64,
+    pub offset: f64,
+    pub std_dev: f64,
+    pub fwhm: f64,
+}
+
+//The offsets of the first time calibrator run of the campaign, kept in the output directory next
+//to the runs. Every run built after it has them in its shift map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CampaignTimeOffsets {
+    //The run the offsets were derived from
+    pub run_number: i32,
+    pub entries: Vec<ShiftMapEntry>,
+}
+
+pub fn get_time_offsets_path(output_dir: &Path) -> PathBuf {
+    output_dir.join("time_offsets.yaml")
+}
+
+impl CampaignTimeOffsets {
+    //None when no run of the campaign has derived offsets yet
+    pub fn read(output_dir: &Path) -> Result<Option<Self>, EVBError> {
+        let path = get_time_offsets_path(output_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let yaml_str = std::fs::read_to_string(path)?;
+        Ok(Some(serde_yaml::from_str(&yaml_str)?))
+    }
+
+    pub fn write(&self, output_dir: &Path) -> Result<(), EVBError> {
+        let yaml_str = serde_yaml::to_string(self)?;
+        let mut file = File::create(get_time_offsets_path(output_dir))?;
+        file.write_all(yaml_str.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn applies_to(&self, run_number: i32) -> bool {
+        run_number > self.run_number
+    }
+
+    //The offsets replace the shifts of the channels they cover, the other shifts are kept
+    pub fn apply(&self, shifts: &[ShiftMapEntry]) -> Vec<ShiftMapEntry> {
+        let mut entries: Vec<ShiftMapEntry> = shifts
+            .iter()
+            .filter(|shift| {
+                !self.entries.iter().any(|entry| {
+                    entry.board_number == shift.board_number
+                        && entry.channel_number == shift.channel_number
+                })
+            })
+            .cloned()
+            .collect();
+        entries.extend(self.entries.iter().cloned());
+        entries.sort_by_key(|entry| (entry.board_number, entry.channel_number));
+        entries
+    }
+}
+
+//Running mean and variance (Welford), so the spread of offsets far from 0 keeps its precision
+#[derive(Debug, Clone, Default)]
+struct OffsetAccumulator {
+    mean: f64,
+    sum_squared_deviations: f64,
+    count: u64,
+}
+
+impl OffsetAccumulator {
+    fn add(&mut self, dt: f64) {
+        self.count += 1;
+        let delta = dt - self.mean;
+        self.mean += delta / (self.count as f64);
+        self.sum_squared_deviations += delta * (dt - self.mean);
+    }
+
+    fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    fn std_dev(&self) -> f64 {
+        (self.sum_squared_deviations / (self.count as f64)).sqrt()
+    }
+}
+
+//Accumulates the time difference of every channel relative to the TimeCalibrator channel. With a
+//pulser fanned into all channels these differences are exactly the cable/CFD offsets to remove.
+#[derive(Debug, Clone, Default)]
+pub struct TimeCalibrator {
+    offsets: HashMap<u32, OffsetAccumulator>,
+    calibrator_events: u64,
+}
+
+impl TimeCalibrator {
+    pub fn add_event(&mut self, event: &[CompassData], map: &ChannelMap) {
+        let reference = match event.iter().find(|hit| {
+            map.get_channel_data(&hit.uuid)
+                .is_some_and(|data| data.channel_type == ChannelType::TimeCalibrator)
+        }) {
+            Some(hit) => hit,
+            None => return,
+        };
+        self.calibrator_events += 1;
+
+        for hit in event.iter() {
+            if hit.uuid == reference.uuid {
+                continue;
+            }
+            let dt = hit.timestamp - reference.timestamp;
+            self.offsets.entry(hit.uuid).or_default().add(dt);
+        }
+    }
+
+    //New offsets are relative to the raw timestamps, so fold in any shift already applied
+    pub fn get_shift_map_entries(&self, current_shifts: &Option<ShiftMap>) -> Vec<ShiftMapEntry> {
+        let mut entries: Vec<ShiftMapEntry> = self
+            .offsets
+            .iter()
+            .map(|(uuid, acc)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let current = match current_shifts {
+                    Some(map) => map.get_timeshift(uuid),
+                    None => 0.0,
+                };
+                ShiftMapEntry {
+                    board_number: board,
+                    channel_number: channel,
+                    time_shift: current - acc.mean(),
+                }
+            })
+            .collect();
+        entries.sort_by_key(|entry| (entry.board_number, entry.channel_number));
+        entries
+    }
+
+    //Saves the offsets of this run for the campaign, unless an earlier run already did. Rebuilding
+    //the run that derived them replaces them.
+    pub fn update_campaign_offsets(
+        &self,
+        campaign_offsets: &mut Option<CampaignTimeOffsets>,
+        output_dir: &Path,
+        run_number: i32,
+        current_shifts: &Option<ShiftMap>,
+    ) -> Result<(), EVBError> {
+        if self.calibrator_events == 0 {
+            info!("No time calibrator events found, no time offsets were derived");
+            return Ok(());
+        }
+
+        info!(
+            "Deriving time offsets from {} time calibrator events",
+            format_count(self.calibrator_events)
+        );
+        for (uuid, acc) in self.offsets.iter() {
+            let (board, channel) = decompose_uuid_to_board_channel(uuid);
+            info!(
+                "Board {} Channel {}: mean dt {:.3} ns, std dev {:.3} ns from {} hits",
+                board,
+                channel,
+                acc.mean(),
+                acc.std_dev(),
+                format_count(acc.count)
+            );
+        }
+
+        if let Some(existing) = campaign_offsets {
+            if existing.run_number != run_number {
+                emit_warning(
+                    "time_offsets",
+                    String::new(),
+                    format!(
+                        "The campaign keeps the time offsets of run {}, those of run {} were not saved; remove {} to calibrate again",
+                        existing.run_number,
+                        run_number,
+                        get_time_offsets_path(output_dir).display()
+                    ),
+                );
+                return Ok(());
+            }
+        }
+
+        let offsets = CampaignTimeOffsets {
+            run_number,
+            entries: self.get_shift_map_entries(current_shifts),
+        };
+        offsets.write(output_dir)?;
+        info!(
+            "Saved the time offsets of run {} to {}, they are applied to every later run",
+            run_number,
+            get_time_offsets_path(output_dir).display()
+        );
+        *campaign_offsets = Some(offsets);
+        Ok(())
+    }
+
+    //Offsets here are as measured, i.e. after any shifts applied to this run
+    pub fn get_summary(&self, map: &ChannelMap) -> Vec<PulserSummaryEntry> {
+        let mut summary: Vec<PulserSummaryEntry> = self
+            .offsets
+            .iter()
+            .map(|(uuid, acc)| {
+                let (board, channel) = decompose_uuid_to_board_channel(uuid);
+                let std_dev = acc.std_dev();
+                PulserSummaryEntry {
+                    board_number: board,
+                    channel_number: channel,
+                    detector: map
+                        .get_channel_data(uuid)
+                        .map_or(ChannelType::None, |data| data.channel_type),
+                    hits: acc.count,
+                    offset: acc.mean(),
+                    std_dev,
+                    fwhm: std_dev * FWHM_PER_SIGMA,
+                }
+            })
+            .collect();
+        summary.sort_by_key(|entry| (entry.board_number, entry.channel_number));
+        summary
+    }
+
+    pub fn write_summary(&self, filepath: &Path, map: &ChannelMap) -> Result<(), EVBError> {
+        if self.calibrator_events == 0 {
+            return Ok(());
+        }
+
+        let summary = self.get_summary(map);
+        for entry in summary.iter() {
+            info!(
+                "Board {} Channel {} ({}): offset {:.3} ns, resolution {:.3} ns FWHM",
+                entry.board_number,
+                entry.channel_number,
+                entry.detector.to_string(),
+                entry.offset,
+                entry.fwhm
+            );
+        }
+        let yaml_str = serde_yaml::to_string(&summary)?;
+        let mut file = File::create(filepath)?;
+        file.write_all(yaml_str.as_bytes())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::histogram::Histogram;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeDifferenceParams {
+    pub enabled: bool,
+    //Every other channel type of the channel map is histogrammed against this one
+    pub reference: ChannelType,
+    pub bins: usize,
+    //ns
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for TimeDifferenceParams {
+    fn default() -> Self {
+        TimeDifferenceParams {
+            enabled: false,
+            reference: ChannelType::Cebra(0),
+            bins: 2000,
+            min: -1000.0,
+            max: 1000.0,
+        }
+    }
+}
+
+//Written to the report of every run built with time differences
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeDifferenceSummary {
+    pub channel: ChannelType,
+    pub reference: ChannelType,
+    pub entries: u64,
+    //ns, None without entries
+    pub peak: Option<f64>,
+    //ns, None if the peak runs into the edge of the range
+    pub fwhm: Option<f64>,
+}
+
+struct ChannelDifferences {
+    channel: ChannelType,
+    histogram: Histogram,
+    entries: u64,
+    //Of the latest hit not yet paired with a later reference hit
+    last_time: Option<f64>,
+}
+
+//Time of every hit minus the time of the nearest reference hit before and after it, for each
+//channel type. It is taken from the hit stream rather than the built events, so the histograms are
+//not cut at the coincidence window and show where the window should be.
+pub struct TimeDifferences {
+    reference: ChannelType,
+    last_reference_time: Option<f64>,
+    channels: Vec<ChannelDifferences>,
+}
+
+impl TimeDifferences {
+    pub fn new(params: &TimeDifferenceParams, channel_map: &ChannelMap) -> Self {
+        let channels = ChannelType::get_types(
+            channel_map.get_detector_count(),
+            channel_map.get_passthrough_count(),
+        )
+        .into_iter()
+        .filter(|channel| {
+            *channel != ChannelType::None
+                && *channel != ChannelType::Scaler
+                && *channel != params.reference
+                && channel_map.has_channel_type(*channel)
+        })
+        .map(|channel| ChannelDifferences {
+            channel,
+            histogram: Histogram::new(
+                format!("{} - {}", channel, params.reference),
+                String::from("Time Difference (ns)"),
+                String::from("Counts"),
+                params.bins,
+                params.min,
+                params.max,
+            ),
+            entries: 0,
+            last_time: None,
+        })
+        .collect();
+        TimeDifferences {
+            reference: params.reference,
+            last_reference_time: None,
+            channels,
+        }
+    }
+
+    fn fill(channel: &mut ChannelDifferences, difference: f64) {
+        if difference >= channel.histogram.min && difference < channel.histogram.max {
+            channel.histogram.fill(difference);
+            channel.entries += 1;
+        }
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData, channel_map: &ChannelMap) {
+        let channel_type = match channel_map.get_channel_data(&hit.uuid) {
+            Some(data) => data.channel_type,
+            None => return,
+        };
+        if channel_type == self.reference {
+            for channel in self.channels.iter_mut() {
+                if let Some(time) = channel.last_time.take() {
+                    Self::fill(channel, time - hit.timestamp);
+                }
+            }
+            self.last_reference_time = Some(hit.timestamp);
+        } else if let Some(channel) = self
+            .channels
+            .iter_mut()
+            .find(|channel| channel.channel == channel_type)
+        {
+            if let Some(reference_time) = self.last_reference_time {
+                Self::fill(channel, hit.timestamp - reference_time);
+            }
+            channel.last_time = Some(hit.timestamp);
+        }
+    }
+
+    //Timestamps restart after a run boundary, so no pair spans it
+    pub fn end_run_segment(&mut self) {
+        self.last_reference_time = None;
+        for channel in self.channels.iter_mut() {
+            channel.last_time = None;
+        }
+    }
+
+    pub fn get_summary(&self) -> Vec<TimeDifferenceSummary> {
+        self.channels
+            .iter()
+            .map(|channel| TimeDifferenceSummary {
+                channel: channel.channel,
+                reference: self.reference,
+                entries: channel.entries,
+                peak: channel.histogram.get_peak(),
+                fwhm: channel.histogram.get_fwhm(),
+            })
+            .collect()
+    }
+
+    pub fn log_summary(&self) {
+        for summary in self.get_summary() {
+            let format_ns = |value: Option<f64>| match value {
+                Some(value) => format!("{:.1} ns", value),
+                None => String::from("-"),
+            };
+            info!(
+                "{} - {}: {} entries, peak at {}, FWHM {}",
+                summary.channel,
+                summary.reference,
+                summary.entries,
+                format_ns(summary.peak),
+                format_ns(summary.fwhm)
+            );
+        }
+    }
+
+    //One row per bin, one column per channel type
+    pub fn write_histograms(&self, filepath: &Path) -> Result<(), EVBError> {
+        info!("Writing time differences to {}", filepath.display());
+        let mut writer = BufWriter::new(File::create(filepath)?);
+        let headers: Vec<String> = self
+            .channels
+            .iter()
+            .map(|channel| channel.histogram.title.clone())
+            .collect();
+        writeln!(writer, "bin_low,bin_high,{}", headers.join(","))?;
+
+        if let Some(first) = self.channels.first() {
+            let width = first.histogram.get_bin_width();
+            for bin in 0..first.histogram.counts.len() {
+                let low = first.histogram.min + width * (bin as f64);
+                let counts: Vec<String> = self
+                    .channels
+                    .iter()
+                    .map(|channel| channel.histogram.counts[bin].to_string())
+                    .collect();
+                writeln!(writer, "{},{},{}", low, low + width, counts.join(","))?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
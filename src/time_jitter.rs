@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::buffered_source::{BufferedSource, HitTransform};
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::data_reduction::get_run_seed;
+use super::hit_source::HitSource;
+use super::parquet_writer::ParquetMetadata;
+
+//The jitter is cut off at this many sigma, which bounds how far a hit can move and so how long the
+//re-sorting has to wait before a hit is safe to pass on
+const MAX_JITTER_SIGMAS: f64 = 5.0;
+
+//Gaussian jitter of one detector's hit times, sigma in ns
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JitterEntry {
+    pub detector: ChannelType,
+    pub sigma: f64,
+}
+
+impl Default for JitterEntry {
+    fn default() -> Self {
+        JitterEntry {
+            detector: ChannelType::Cebra(0),
+            sigma: 1.0,
+        }
+    }
+}
+
+impl JitterEntry {
+    pub fn describe(&self) -> String {
+        format!("{}:{}", self.detector, self.sigma)
+    }
+}
+
+//Timing systematics: worsen the time resolution of some detectors on purpose and see how the
+//coincidence efficiency follows
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct TimeJitterParams {
+    pub enabled: bool,
+    //Base seed, each run gets its own seed from it. Without one a fresh seed is drawn per run.
+    pub seed: Option<u64>,
+    pub entries: Vec<JitterEntry>,
+}
+
+//Written to the report, so a jittered build can be repeated
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JitterProvenance {
+    pub seed: u64,
+    pub entries: Vec<JitterEntry>,
+}
+
+//Jitter settings of a run, shared by the jittered hit sources
+#[derive(Debug)]
+pub struct TimeJitter {
+    entries: Vec<JitterEntry>,
+    sigmas: Arc<HashMap<u32, f64>>,
+    seed: u64,
+}
+
+impl TimeJitter {
+    pub fn new(params: &TimeJitterParams, map: &ChannelMap, run_number: i32) -> Option<Self> {
+        if !params.enabled || params.entries.is_empty() {
+            return None;
+        }
+        let seed = match params.seed {
+            Some(base_seed) => get_run_seed(base_seed, run_number),
+            None => rand::random(),
+        };
+        let mut sigmas = HashMap::new();
+        for entry in params.entries.iter() {
+            for uuid in map.get_uuids_of_type(entry.detector) {
+                sigmas.insert(uuid, entry.sigma);
+            }
+        }
+        info!(
+            "Jittering hit times of run {} for {} channels, seed {}",
+            run_number,
+            sigmas.len(),
+            seed
+        );
+        Some(TimeJitter {
+            entries: params.entries.clone(),
+            sigmas: Arc::new(sigmas),
+            seed,
+        })
+    }
+
+    //Every source draws from its own generator, seeded from the run seed and its index
+    pub fn wrap<'a>(
+        &self,
+        source: Box<dyn HitSource + 'a>,
+        index: usize,
+    ) -> Box<dyn HitSource + 'a> {
+        let max_sigma = self.sigmas.values().copied().fold(0.0, f64::max);
+        let jitter = Jitter {
+            sigmas: self.sigmas.clone(),
+            rng: StdRng::seed_from_u64(get_run_seed(self.seed, index as i32)),
+        };
+        Box::new(BufferedSource::new(
+            source,
+            jitter,
+            max_sigma * MAX_JITTER_SIGMAS,
+        ))
+    }
+
+    pub fn get_provenance(&self) -> JitterProvenance {
+        JitterProvenance {
+            seed: self.seed,
+            entries: self.entries.clone(),
+        }
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file(
+            "time_jitter",
+            self.entries
+                .iter()
+                .map(|entry| entry.describe())
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+        metadata.insert_file("time_jitter_seed", self.seed.to_string());
+    }
+}
+
+//Jittered hits are no longer in time order, so they are held until no later hit of the source
+//can jitter to before them
+struct Jitter {
+    sigmas: Arc<HashMap<u32, f64>>,
+    rng: StdRng,
+}
+
+impl Jitter {
+    //Box-Muller, one standard normal number per call, cut off at MAX_JITTER_SIGMAS
+    fn get_normal(&mut self) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        let normal = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        normal.clamp(-MAX_JITTER_SIGMAS, MAX_JITTER_SIGMAS)
+    }
+}
+
+impl HitTransform for Jitter {
+    fn transform(&mut self, hit: CompassData) -> Option<CompassData> {
+        Some(match self.sigmas.get(&hit.uuid).copied() {
+            Some(sigma) => {
+                let timestamp = hit.timestamp + sigma * self.get_normal();
+                CompassData { timestamp, ..hit }
+            }
+            None => hit,
+        })
+    }
+}
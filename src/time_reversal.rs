@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use log::info;
+use serde::Serialize;
+
+use super::build_diagnostics::emit_warning;
+use super::compass_data::CompassData;
+use super::event_builder::{BuildStrategy, EventBuilder};
+use super::formatting::format_count;
+use super::used_size::UsedSize;
+
+//Differences in event count beyond this many standard deviations are reported as a warning
+const MAX_DEVIATION_SIGMA: f64 = 3.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MultiplicityCounts {
+    pub multiplicity: usize,
+    pub forward: u64,
+    pub reversed: u64,
+}
+
+//Written to the report of runs built with the time-reversed check
+#[derive(Debug, Clone, Serialize)]
+pub struct ReversalComparison {
+    pub forward_events: u64,
+    pub reversed_events: u64,
+    //(reversed - forward) / forward
+    pub event_difference: f64,
+    pub multiplicities: Vec<MultiplicityCounts>,
+}
+
+//Cross-check for window-edge biases: the hits of a run are built a second time with time running
+//backwards, so each window is opened by the latest hit instead of the earliest. Keeps the time and
+//channel of every hit until the end of the run, so it is meant for small runs.
+#[derive(Debug)]
+pub struct ReversalCheck {
+    trigger_uuids: Option<HashSet<u32>>,
+    strategy: BuildStrategy,
+    hits: Vec<(f64, u32)>,
+    forward: BTreeMap<usize, u64>,
+    reversed: BTreeMap<usize, u64>,
+}
+
+fn count_event(counts: &mut BTreeMap<usize, u64>, event: &[CompassData]) {
+    *counts.entry(event.len()).or_default() += 1;
+}
+
+impl ReversalCheck {
+    //The reversed build uses the same trigger channels and strategy as the forward one
+    pub fn new(trigger_uuids: Option<HashSet<u32>>, strategy: BuildStrategy) -> Self {
+        ReversalCheck {
+            trigger_uuids,
+            strategy,
+            hits: vec![],
+            forward: BTreeMap::new(),
+            reversed: BTreeMap::new(),
+        }
+    }
+
+    pub fn add_hit(&mut self, hit: &CompassData) {
+        self.hits.push((hit.timestamp, hit.uuid));
+    }
+
+    pub fn add_forward_event(&mut self, event: &[CompassData]) {
+        count_event(&mut self.forward, event);
+    }
+
+    //Builds the hits kept so far backwards. Called for every run of a concatenated file, since
+    //time only runs backwards within a run.
+    pub fn end_run_segment(&mut self, window: f64) {
+        let mut evb = EventBuilder::new(&window).with_strategy(self.strategy);
+        if let Some(uuids) = &self.trigger_uuids {
+            evb = evb.with_trigger(uuids.clone());
+        }
+        for (timestamp, uuid) in self.hits.iter().rev() {
+            evb.push_hit(&CompassData {
+                uuid: *uuid,
+                energy: 0.0,
+                energy_short: 0.0,
+                timestamp: -timestamp,
+                raw_timestamp: -timestamp,
+                waveform: None,
+                flags: 0,
+            });
+            while evb.is_event_ready() {
+                count_event(&mut self.reversed, &evb.get_ready_event());
+            }
+        }
+        self.hits.clear();
+    }
+
+    pub fn compare(mut self, window: f64) -> ReversalComparison {
+        self.end_run_segment(window);
+        let reversed = self.reversed;
+
+        let forward_events: u64 = self.forward.values().sum();
+        let reversed_events: u64 = reversed.values().sum();
+        let multiplicities = self
+            .forward
+            .keys()
+            .chain(reversed.keys())
+            .copied()
+            .collect::<BTreeSet<usize>>()
+            .into_iter()
+            .map(|multiplicity| MultiplicityCounts {
+                multiplicity,
+                forward: self.forward.get(&multiplicity).copied().unwrap_or(0),
+                reversed: reversed.get(&multiplicity).copied().unwrap_or(0),
+            })
+            .collect();
+        ReversalComparison {
+            forward_events,
+            reversed_events,
+            event_difference: if forward_events > 0 {
+                (reversed_events as f64 - forward_events as f64) / forward_events as f64
+            } else {
+                0.0
+            },
+            multiplicities,
+        }
+    }
+}
+
+impl UsedSize for ReversalCheck {
+    fn get_used_size(&self) -> usize {
+        self.hits.capacity() * std::mem::size_of::<(f64, u32)>()
+    }
+}
+
+impl ReversalComparison {
+    pub fn log_summary(&self) {
+        info!(
+            "Time-reversed build: {} events forward, {} reversed ({:+.2}%)",
+            format_count(self.forward_events),
+            format_count(self.reversed_events),
+            self.event_difference * 100.0
+        );
+        for counts in self.multiplicities.iter() {
+            info!(
+                "Multiplicity {}: {} forward, {} reversed",
+                counts.multiplicity,
+                format_count(counts.forward),
+                format_count(counts.reversed)
+            );
+        }
+        let difference = (self.reversed_events as f64 - self.forward_events as f64).abs();
+        if difference > MAX_DEVIATION_SIGMA * (self.forward_events as f64).sqrt() {
+            emit_warning(
+                "time_reversal",
+                String::new(),
+                format!(
+                    "Forward and reversed event counts differ by over {} sigma, check the window",
+                    MAX_DEVIATION_SIGMA
+                ),
+            );
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::path::Path;
+
+use log::info;
+use polars::prelude::*;
+
+use super::error::EVBError;
+use super::histogram::{read_columns, read_time_unit};
+use super::invalid_values::ColumnInvalidValues;
+use super::parquet_writer::{write_parquet, ParquetMetadata};
+use super::run_comparison::{get_events_paths, is_detector_time};
+use super::run_layout::RunLayout;
+
+//A time since the start of the run as typed: seconds ("300", "312.5") or a clock reading of the
+//DAQ run timer ("5:00", "1:05:00")
+pub fn parse_run_time(time: &str) -> Result<f64, String> {
+    let parts: Vec<&str> = time.trim().split(':').map(str::trim).collect();
+    if parts.len() > 3 {
+        return Err(format!("'{}' is not a time, use seconds or h:mm:ss", time));
+    }
+    let mut seconds = 0.0;
+    for (index, part) in parts.iter().enumerate() {
+        let value = part
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .ok_or_else(|| format!("'{}' is not a time, use seconds or h:mm:ss", time))?;
+        //Only the seconds may have a fraction
+        if index + 1 < parts.len() && value.fract() != 0.0 {
+            return Err(format!("'{}' is not a time, use seconds or h:mm:ss", time));
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+//The events of a built run with a detector hit in [start, stop), in seconds since the start of the
+//run (CoMPASS timestamps count from the start of the acquisition)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSliceParams {
+    pub run_number: i32,
+    pub start_seconds: f64,
+    pub stop_seconds: f64,
+}
+
+//Copies the slice of the events of a run into output_path, with the file metadata of the run plus
+//the bounds of the slice. Returns the number of events written.
+pub fn export_time_slice(
+    output_dir: &Path,
+    params: &TimeSliceParams,
+    output_path: &Path,
+) -> Result<usize, EVBError> {
+    if params.stop_seconds <= params.start_seconds {
+        return Err(EVBError::Slice(format!(
+            "it ends at {} s, before it starts at {} s",
+            params.stop_seconds, params.start_seconds
+        )));
+    }
+    let layout = RunLayout::new(output_dir, params.run_number);
+    let paths = get_events_paths(&layout);
+    let first_path = match paths.first() {
+        Some(path) => path,
+        None => {
+            return Err(EVBError::Slice(format!(
+                "run {} has no events in {}",
+                params.run_number,
+                output_dir.display()
+            )))
+        }
+    };
+
+    let time_unit = read_time_unit(first_path)?;
+    let invalid = ColumnInvalidValues::read(first_path)?;
+    let start = time_unit.convert_nanoseconds(params.start_seconds * 1.0e9);
+    let stop = time_unit.convert_nanoseconds(params.stop_seconds * 1.0e9);
+    let time_columns: Vec<String> = read_columns(first_path)?
+        .into_iter()
+        .filter(|name| is_detector_time(name))
+        .collect();
+    let in_slice = match time_columns
+        .iter()
+        .map(|name| {
+            invalid
+                .get_valid(name)
+                .and(col(name).gt_eq(lit(start)))
+                .and(col(name).lt(lit(stop)))
+        })
+        .reduce(|any, hit| any.or(hit))
+    {
+        Some(in_slice) => in_slice,
+        None => {
+            return Err(EVBError::Slice(format!(
+                "the events of run {} have no detector time columns",
+                params.run_number
+            )))
+        }
+    };
+
+    let frames = paths
+        .iter()
+        .map(|path| LazyFrame::scan_parquet(path, ScanArgsParquet::default()))
+        .collect::<PolarsResult<Vec<LazyFrame>>>()?;
+    let mut df = concat(frames, UnionArgs::default())?
+        .filter(in_slice)
+        .collect()?;
+
+    let mut metadata = ParquetMetadata::default();
+    let file_metadata = polars_parquet::read::read_metadata(&mut File::open(first_path)?)?;
+    for kv in file_metadata.key_value_metadata.unwrap_or_default() {
+        if let Some(value) = kv.value {
+            metadata.insert_file(&kv.key, value);
+        }
+    }
+    metadata.insert_file("slice_start_seconds", params.start_seconds.to_string());
+    metadata.insert_file("slice_stop_seconds", params.stop_seconds.to_string());
+    for name in time_columns.iter() {
+        metadata.insert_column(name, "unit", time_unit.as_ref().to_string());
+    }
+    invalid.insert_metadata(&mut metadata);
+
+    let mut file = File::create(output_path)?;
+    write_parquet(&mut file, &mut df, &metadata, &[])?;
+    info!(
+        "Wrote {} events of run {} between {} s and {} s to {}",
+        df.height(),
+        params.run_number,
+        params.start_seconds,
+        params.stop_seconds,
+        output_path.display()
+    );
+    Ok(df.height())
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::buffered_source::{BufferedSource, HitTransform};
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::hit_source::HitSource;
+use super::parquet_writer::ParquetMetadata;
+
+//CFD time walk of the CeBrA detectors: low energy hits trigger late, which smears the coincidence
+//timing. The walk of each detector is a polynomial in the energy of the hit, taken off its
+//timestamp before events are built.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeWalkParams {
+    pub enabled: bool,
+    //Correction file, see read_time_walk_file
+    pub file: PathBuf,
+    //Corrections are cut off at +-max_correction (ns), which bounds how far a hit can move and so
+    //how long the re-sorting has to wait before a hit is safe to pass on
+    pub max_correction: f64,
+}
+
+impl Default for TimeWalkParams {
+    fn default() -> Self {
+        TimeWalkParams {
+            enabled: false,
+            file: PathBuf::new(),
+            max_correction: 100.0,
+        }
+    }
+}
+
+//Walk of one detector in ns, c0 + c1 * E + c2 * E^2 + ... of the raw energy E of the hit (before
+//gain drift correction and energy calibration)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeWalkEntry {
+    pub detector: ChannelType,
+    pub coefficients: Vec<f64>,
+}
+
+impl TimeWalkEntry {
+    pub fn get_walk(&self, energy: f64) -> f64 {
+        self.coefficients
+            .iter()
+            .rev()
+            .fold(0.0, |walk, coefficient| walk * energy + coefficient)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}:{}",
+            self.detector,
+            self.coefficients
+                .iter()
+                .map(|coefficient| coefficient.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    }
+}
+
+//Correction files written by hand or by a fit, one detector per line: its name as in the channel
+//map followed by the coefficients, from the constant term up, separated by commas or whitespace.
+//Blank lines and everything after a # are ignored, and so is a first line of column names.
+pub fn read_time_walk_file(path: &Path) -> Result<Vec<TimeWalkEntry>, EVBError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|x| EVBError::TimeWalk(format!("unable to read {}: {}", path.display(), x)))?;
+    let error = |line: usize, message: String| {
+        EVBError::TimeWalk(format!("{} line {}: {}", path.display(), line, message))
+    };
+    let mut entries: Vec<TimeWalkEntry> = vec![];
+    let mut is_first = true;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let fields: Vec<&str> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let detector = ChannelType::try_from(fields[0].to_string());
+        let is_header = is_first && detector.is_err();
+        is_first = false;
+        if is_header {
+            continue;
+        }
+        let detector = detector.map_err(|x| error(index + 1, x))?;
+        if detector == ChannelType::None {
+            return Err(error(index + 1, String::from("time walk of detector None")));
+        }
+        if entries.iter().any(|entry| entry.detector == detector) {
+            return Err(error(
+                index + 1,
+                format!("{} has a time walk already", detector),
+            ));
+        }
+        let coefficients = fields[1..]
+            .iter()
+            .map(|field| field.parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|x| error(index + 1, format!("could not parse coefficient: {}", x)))?;
+        if coefficients.is_empty() {
+            return Err(error(
+                index + 1,
+                format!("{} has no coefficients", detector),
+            ));
+        }
+        entries.push(TimeWalkEntry {
+            detector,
+            coefficients,
+        });
+    }
+    Ok(entries)
+}
+
+//Written to the metadata of the run, so the correction can be told apart and undone
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeWalkProvenance {
+    pub file: String,
+    //ns
+    pub max_correction: f64,
+    pub entries: Vec<TimeWalkEntry>,
+}
+
+impl TimeWalkProvenance {
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        metadata.insert_file("time_walk_file", self.file.clone());
+        metadata.insert_file("time_walk_max_ns", self.max_correction.to_string());
+        metadata.insert_file(
+            "time_walk",
+            self.entries
+                .iter()
+                .map(|entry| entry.describe())
+                .collect::<Vec<String>>()
+                .join(" "),
+        );
+    }
+}
+
+//Hits corrected, and those of them cut off at the largest correction
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TimeWalkSummary {
+    pub corrected_hits: u64,
+    pub clamped_hits: u64,
+}
+
+#[derive(Debug, Default)]
+struct TimeWalkCounts {
+    corrected: AtomicU64,
+    clamped: AtomicU64,
+}
+
+//Time walk correction of a run, shared by the corrected hit sources
+#[derive(Debug)]
+pub struct TimeWalk {
+    provenance: TimeWalkProvenance,
+    walks: Arc<HashMap<u32, TimeWalkEntry>>,
+    counts: Arc<TimeWalkCounts>,
+}
+
+impl TimeWalk {
+    //The file is read again for every run, so a new fit is used from the next run built
+    pub fn new(params: &TimeWalkParams, map: &ChannelMap) -> Result<Option<Self>, EVBError> {
+        if !params.enabled {
+            return Ok(None);
+        }
+        let entries = read_time_walk_file(&params.file)?;
+        let mut walks = HashMap::new();
+        for entry in entries.iter() {
+            let uuids = map.get_uuids_of_type(entry.detector);
+            if uuids.is_empty() {
+                emit_warning(
+                    "time_walk",
+                    entry.detector.to_string(),
+                    format!(
+                        "{} has a time walk in {} but is not in the channel map",
+                        entry.detector,
+                        params.file.display()
+                    ),
+                );
+            }
+            for uuid in uuids {
+                walks.insert(uuid, entry.clone());
+            }
+        }
+        info!(
+            "Correcting the time walk of {} channels with {}",
+            walks.len(),
+            params.file.display()
+        );
+        Ok(Some(TimeWalk {
+            provenance: TimeWalkProvenance {
+                file: params.file.display().to_string(),
+                max_correction: params.max_correction,
+                entries,
+            },
+            walks: Arc::new(walks),
+            counts: Arc::new(TimeWalkCounts::default()),
+        }))
+    }
+
+    pub fn wrap<'a>(&self, source: Box<dyn HitSource + 'a>) -> Box<dyn HitSource + 'a> {
+        let correction = WalkCorrection {
+            walks: self.walks.clone(),
+            max_correction: self.provenance.max_correction,
+            counts: self.counts.clone(),
+        };
+        Box::new(BufferedSource::new(
+            source,
+            correction,
+            self.provenance.max_correction,
+        ))
+    }
+
+    pub fn get_provenance(&self) -> TimeWalkProvenance {
+        self.provenance.clone()
+    }
+
+    pub fn get_summary(&self) -> TimeWalkSummary {
+        TimeWalkSummary {
+            corrected_hits: self.counts.corrected.load(Ordering::Relaxed),
+            clamped_hits: self.counts.clamped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        info!(
+            "Corrected the time walk of {} hits",
+            format_count(summary.corrected_hits)
+        );
+        if summary.clamped_hits > 0 {
+            emit_warning(
+                "time_walk",
+                String::new(),
+                format!(
+                    "The time walk of {} hits was more than {} ns and was cut off there, check \
+                     the coefficients or raise max_correction",
+                    format_count(summary.clamped_hits),
+                    self.provenance.max_correction
+                ),
+            );
+        }
+    }
+}
+
+//Corrected hits are no longer in time order, so they are held until no later hit of the source can
+//be corrected to before them
+struct WalkCorrection {
+    walks: Arc<HashMap<u32, TimeWalkEntry>>,
+    max_correction: f64,
+    counts: Arc<TimeWalkCounts>,
+}
+
+impl HitTransform for WalkCorrection {
+    fn transform(&mut self, hit: CompassData) -> Option<CompassData> {
+        Some(match self.walks.get(&hit.uuid) {
+            Some(entry) => {
+                let walk = entry.get_walk(hit.energy);
+                self.counts.corrected.fetch_add(1, Ordering::Relaxed);
+                if walk.abs() > self.max_correction || !walk.is_finite() {
+                    self.counts.clamped.fetch_add(1, Ordering::Relaxed);
+                }
+                let walk = if walk.is_nan() {
+                    0.0
+                } else {
+                    walk.clamp(-self.max_correction, self.max_correction)
+                };
+                CompassData {
+                    timestamp: hit.timestamp - walk,
+                    ..hit
+                }
+            }
+            None => hit,
+        })
+    }
+}
@@ -0,0 +1,298 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use super::buffered_source::{BufferedSource, HitTransform};
+use super::build_diagnostics::emit_warning;
+use super::compass_data::CompassData;
+use super::error::EVBError;
+use super::formatting::format_count;
+use super::hit_source::{HitSource, Waiting};
+use super::parquet_writer::ParquetMetadata;
+use super::used_size::UsedSize;
+
+//Timestamps as the digitizers wrote them are not always usable as they are: a clock that rolls
+//over starts again from zero, and hits of a file can be slightly out of order. Each input file is
+//corrected on its own, before the hits of all files are merged.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimestampSanitizerParams {
+    pub enabled: bool,
+    //Period of the digitizer clock, 0 for a clock that never rolls over. A jump back by more than
+    //half of it is taken as a rollover, and the period is added to every later hit.
+    pub rollover_seconds: f64,
+    //Hits are held this long (ns) so that later hits arriving before them can be put in order.
+    //Hits later than that cannot be placed any more and are dropped.
+    pub reorder_window: f64,
+}
+
+impl Default for TimestampSanitizerParams {
+    fn default() -> Self {
+        TimestampSanitizerParams {
+            enabled: false,
+            rollover_seconds: 0.0,
+            reorder_window: 1000.0,
+        }
+    }
+}
+
+//Written to the report of every run built with the sanitizer
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TimestampSummary {
+    pub rollovers: u64,
+    pub reordered: u64,
+    pub dropped: u64,
+}
+
+//Shared by the corrected sources of a run
+#[derive(Debug, Default)]
+struct SanitizerCounts {
+    rollovers: AtomicU64,
+    reordered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct TimestampSanitizer {
+    params: TimestampSanitizerParams,
+    //Added to the reorder window of time-merged files (ns), see ShiftMap::get_shift_spread
+    shift_spread: f64,
+    counts: Arc<SanitizerCounts>,
+}
+
+impl TimestampSanitizer {
+    //Time shifts move the channels of a time-merged file against each other, so its hits are
+    //re-sorted by the spread of the shifts even with the sanitizer off
+    pub fn new(params: &TimestampSanitizerParams, merged_shift_spread: f64) -> Option<Self> {
+        if !params.enabled && merged_shift_spread <= 0.0 {
+            return None;
+        }
+        Some(TimestampSanitizer {
+            params: params.clone(),
+            shift_spread: merged_shift_spread,
+            counts: Arc::new(SanitizerCounts::default()),
+        })
+    }
+
+    //Rollovers are corrected on the hits as read, so a run boundary is never mistaken for one and
+    //the other way around (see run_boundary)
+    pub fn correct_rollovers<'a>(
+        &self,
+        source: Box<dyn HitSource + 'a>,
+    ) -> Box<dyn HitSource + 'a> {
+        if !self.params.enabled || self.params.rollover_seconds <= 0.0 {
+            return source;
+        }
+        Box::new(RolloverSource::new(
+            source,
+            self.params.rollover_seconds * 1.0e9,
+            self.counts.clone(),
+        ))
+    }
+
+    pub fn reorder<'a>(
+        &self,
+        source: Box<dyn HitSource + 'a>,
+        is_merged: bool,
+    ) -> Box<dyn HitSource + 'a> {
+        let window = self.get_reorder_window(is_merged);
+        if !self.params.enabled && !is_merged {
+            return source;
+        }
+        let reorder = Reorder {
+            counts: self.counts.clone(),
+            latest_read: None,
+            latest_passed: None,
+        };
+        Box::new(BufferedSource::new(source, reorder, window))
+    }
+
+    fn get_reorder_window(&self, is_merged: bool) -> f64 {
+        let window = if self.params.enabled {
+            self.params.reorder_window
+        } else {
+            0.0
+        };
+        if is_merged {
+            window + self.shift_spread
+        } else {
+            window
+        }
+    }
+
+    pub fn get_summary(&self) -> TimestampSummary {
+        TimestampSummary {
+            rollovers: self.counts.rollovers.load(Ordering::Relaxed),
+            reordered: self.counts.reordered.load(Ordering::Relaxed),
+            dropped: self.counts.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn log_summary(&self) {
+        let summary = self.get_summary();
+        info!(
+            "Timestamps: {} rollovers corrected, {} hits reordered",
+            format_count(summary.rollovers),
+            format_count(summary.reordered)
+        );
+        if summary.dropped > 0 {
+            emit_warning(
+                "out_of_order_hits",
+                String::new(),
+                format!(
+                    "Dropped {} hits more than {} ns out of order",
+                    format_count(summary.dropped),
+                    //With the sanitizer off only time-merged files are re-sorted
+                    self.get_reorder_window(!self.params.enabled)
+                ),
+            );
+        }
+    }
+
+    pub fn insert_metadata(&self, metadata: &mut ParquetMetadata) {
+        if self.shift_spread > 0.0 {
+            metadata.insert_file("merged_input_reorder_ns", self.shift_spread.to_string());
+        }
+        if !self.params.enabled {
+            return;
+        }
+        metadata.insert_file(
+            "timestamp_rollover_s",
+            self.params.rollover_seconds.to_string(),
+        );
+        metadata.insert_file(
+            "timestamp_reorder_window_ns",
+            self.params.reorder_window.to_string(),
+        );
+    }
+}
+
+//Adds the periods the clock has rolled over to the times of a source
+struct RolloverSource<'a> {
+    inner: Box<dyn HitSource + 'a>,
+    period: f64,
+    counts: Arc<SanitizerCounts>,
+    epoch: f64,
+    //Latest raw time read, with the epoch added
+    latest: Option<f64>,
+    current_hit: CompassData,
+    is_used: bool,
+}
+
+impl<'a> RolloverSource<'a> {
+    fn new(inner: Box<dyn HitSource + 'a>, period: f64, counts: Arc<SanitizerCounts>) -> Self {
+        RolloverSource {
+            inner,
+            period,
+            counts,
+            epoch: 0.0,
+            latest: None,
+            current_hit: CompassData::default(),
+            is_used: true,
+        }
+    }
+
+    //A hit just before a rollover that arrives after it still belongs to the epoch before
+    fn get_offset(&mut self, raw_time: f64) -> f64 {
+        let mut offset = self.epoch;
+        if let Some(latest) = self.latest {
+            if raw_time + offset < latest - 0.5 * self.period {
+                self.epoch += self.period;
+                offset = self.epoch;
+                self.counts.rollovers.fetch_add(1, Ordering::Relaxed);
+            } else if raw_time + offset > latest + 0.5 * self.period && offset >= self.period {
+                offset -= self.period;
+            }
+        }
+        self.latest = Some(self.latest.unwrap_or(f64::MIN).max(raw_time + offset));
+        offset
+    }
+}
+
+impl HitSource for RolloverSource<'_> {
+    fn get_top_hit(&mut self) -> Result<&CompassData, EVBError> {
+        if self.is_used {
+            let hit = self.inner.get_top_hit()?.clone();
+            self.current_hit = if hit.is_default() {
+                hit
+            } else {
+                let offset = self.get_offset(hit.raw_timestamp);
+                CompassData {
+                    timestamp: hit.timestamp + offset,
+                    raw_timestamp: hit.raw_timestamp + offset,
+                    ..hit
+                }
+            };
+            //A source waiting for its file to grow is read again on the next call
+            self.is_used = self.inner.get_waiting().is_some();
+        }
+        Ok(&self.current_hit)
+    }
+
+    fn set_hit_used(&mut self) {
+        self.inner.set_hit_used();
+        self.is_used = true;
+    }
+
+    fn is_eof(&self) -> bool {
+        self.inner.is_eof()
+    }
+
+    fn get_number_of_hits(&self) -> u64 {
+        self.inner.get_number_of_hits()
+    }
+
+    //The clock of the next run starts from zero again
+    fn start_next_run(&mut self) {
+        self.inner.start_next_run();
+        self.epoch = 0.0;
+        self.latest = None;
+        self.is_used = true;
+    }
+
+    fn get_waiting(&self) -> Option<Waiting> {
+        self.inner.get_waiting()
+    }
+}
+
+impl UsedSize for RolloverSource<'_> {
+    fn get_used_size(&self) -> usize {
+        self.inner.get_used_size()
+    }
+}
+
+//Holds the hits of a source for the reorder window so they are passed on in time order. Hits later
+//than that cannot be placed any more and are dropped.
+struct Reorder {
+    counts: Arc<SanitizerCounts>,
+    //Latest time read and latest time passed on
+    latest_read: Option<f64>,
+    latest_passed: Option<f64>,
+}
+
+impl HitTransform for Reorder {
+    fn transform(&mut self, hit: CompassData) -> Option<CompassData> {
+        if self
+            .latest_passed
+            .is_some_and(|passed| hit.timestamp < passed)
+        {
+            self.counts.dropped.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        if self.latest_read.is_some_and(|read| hit.timestamp < read) {
+            self.counts.reordered.fetch_add(1, Ordering::Relaxed);
+        }
+        self.latest_read = Some(self.latest_read.unwrap_or(f64::MIN).max(hit.timestamp));
+        Some(hit)
+    }
+
+    fn pass(&mut self, hit: &CompassData) {
+        self.latest_passed = Some(hit.timestamp);
+    }
+
+    fn start_next_run(&mut self) {
+        self.latest_read = None;
+        self.latest_passed = None;
+    }
+}
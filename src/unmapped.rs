@@ -0,0 +1,93 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::build_diagnostics::emit_warning;
+use super::channel_map::{ChannelMap, ChannelType};
+use super::compass_data::generate_board_channel_uuid;
+use super::formatting::format_count;
+use super::run_statistics::ChannelStats;
+
+//What is done with the hits of channels mapped as None, or of boards not in the channel map. They
+//are built into events either way, and count towards the Multiplicity of their event.
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum UnmappedPolicy {
+    //Left out of the columns, only counted in the report
+    #[default]
+    Drop,
+    //The same, with a warning for every unmapped channel with hits
+    Warn,
+    //UnmappedBoard<b>Chan<c>Energy and Time columns for every channel mapped as None. Boards not in
+    //the map have no columns, their channels are warned about.
+    Columns,
+}
+
+//Written to the report of every run for each unmapped channel with hits
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmappedChannelSummary {
+    pub board_number: u32,
+    pub channel_number: u32,
+    pub hits: u64,
+    //Whether its hits are in UnmappedBoard<b>Chan<c> columns
+    pub columns: bool,
+}
+
+//The unmapped channels with hits, from the hit counts of every channel of the run. Logs them, and
+//warns about them as the policy says, so a cable in the wrong input is noticed.
+pub fn get_unmapped_summary(
+    channels: &[ChannelStats],
+    map: &ChannelMap,
+    policy: UnmappedPolicy,
+) -> Vec<UnmappedChannelSummary> {
+    let summary: Vec<UnmappedChannelSummary> = channels
+        .iter()
+        .filter(|stats| stats.detector == ChannelType::None && stats.hits > 0)
+        .map(|stats| UnmappedChannelSummary {
+            board_number: stats.board_number,
+            channel_number: stats.channel_number,
+            hits: stats.hits,
+            columns: policy == UnmappedPolicy::Columns
+                && map
+                    .get_channel_data(&generate_board_channel_uuid(
+                        &stats.board_number,
+                        &stats.channel_number,
+                    ))
+                    .is_some(),
+        })
+        .collect();
+    for channel in summary.iter() {
+        let context = format!(
+            "board {} channel {}",
+            channel.board_number, channel.channel_number
+        );
+        match policy {
+            UnmappedPolicy::Columns if channel.columns => info!(
+                "{} hits of unmapped {} are in its Unmapped columns",
+                format_count(channel.hits),
+                context
+            ),
+            UnmappedPolicy::Drop => info!(
+                "{} hits of unmapped {} were left out of the columns",
+                format_count(channel.hits),
+                context
+            ),
+            UnmappedPolicy::Warn => emit_warning(
+                "unmapped_channel",
+                context,
+                format!(
+                    "{} hits of an unmapped channel were left out of the columns",
+                    format_count(channel.hits)
+                ),
+            ),
+            UnmappedPolicy::Columns => emit_warning(
+                "unmapped_channel",
+                context,
+                format!(
+                    "{} hits of a board not in the channel map have no columns",
+                    format_count(channel.hits)
+                ),
+            ),
+        }
+    }
+    summary
+}
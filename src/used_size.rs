@@ -32,3 +32,12 @@ impl UsedSize for f64 {
         std::mem::size_of::<f64>()
     }
 }
+
+impl<T: UsedSize> UsedSize for Option<T> {
+    fn get_used_size(&self) -> usize {
+        match self {
+            Some(value) => value.get_used_size(),
+            None => 0,
+        }
+    }
+}
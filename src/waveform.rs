@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, EnumIter};
+
+use super::channel_map::ChannelMap;
+use super::compass_data::{decompose_uuid_to_board_channel, CompassData, RawCompassData};
+use super::error::EVBError;
+
+//What is done with the samples of files written with wave recording enabled
+#[derive(Debug, Clone, Copy, PartialEq, Default, AsRefStr, EnumIter, Serialize, Deserialize)]
+pub enum WaveformMode {
+    //Read past the samples and build as if there were none
+    #[default]
+    Drop,
+    //Reduce every waveform to a baseline, amplitude and rise time in the event table
+    Derived,
+    //Write the samples of every hit to a file per channel next to the event table
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaveformParams {
+    pub mode: WaveformMode,
+    //Leading samples averaged for the baseline, they must come before the pulse
+    pub baseline_samples: usize,
+    //Time between two samples (ns), 2 for the V1730
+    pub sample_period: f64,
+}
+
+impl Default for WaveformParams {
+    fn default() -> Self {
+        WaveformParams {
+            mode: WaveformMode::Drop,
+            baseline_samples: 16,
+            sample_period: 2.0,
+        }
+    }
+}
+
+//The quantities of one waveform written in Derived mode. The amplitude is the largest distance of
+//a sample from the baseline, so pulses of either polarity are positive.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WaveformSummary {
+    pub baseline: f64,
+    pub amplitude: f64,
+    //From 10% to 90% of the amplitude on the leading edge (ns)
+    pub rise_time: f64,
+}
+
+//Position (in samples) where the leading edge of the pulse crosses the level, searching back from
+//the peak and interpolating between the samples either side
+fn get_crossing(deviations: &[f64], peak: usize, level: f64) -> f64 {
+    let mut index = peak;
+    while index > 0 && deviations[index - 1] >= level {
+        index -= 1;
+    }
+    if index == 0 {
+        return 0.0;
+    }
+    let (low, high) = (deviations[index - 1], deviations[index]);
+    (index - 1) as f64 + (level - low) / (high - low)
+}
+
+impl WaveformSummary {
+    pub fn from_samples(samples: &[u16], params: &WaveformParams) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let baseline_samples = params.baseline_samples.clamp(1, samples.len());
+        let baseline = samples[..baseline_samples]
+            .iter()
+            .map(|sample| *sample as f64)
+            .sum::<f64>()
+            / baseline_samples as f64;
+        let deviations: Vec<f64> = samples
+            .iter()
+            .map(|sample| (*sample as f64 - baseline).abs())
+            .collect();
+        let (peak, amplitude) =
+            deviations
+                .iter()
+                .copied()
+                .enumerate()
+                .fold((0, 0.0), |best, (index, deviation)| {
+                    if deviation > best.1 {
+                        (index, deviation)
+                    } else {
+                        best
+                    }
+                });
+        let rise_time = if amplitude > 0.0 {
+            (get_crossing(&deviations, peak, 0.9 * amplitude)
+                - get_crossing(&deviations, peak, 0.1 * amplitude))
+                * params.sample_period
+        } else {
+            0.0
+        };
+        Some(WaveformSummary {
+            baseline,
+            amplitude,
+            rise_time,
+        })
+    }
+}
+
+//Samples of one input channel, written in Write mode to waveforms/<detector>_<board>_<channel>.csv
+//with one row per hit. The timestamp is the one of the hit in the event table (ns, time shifts
+//applied), so the waveforms of an event can be found from its Time columns.
+#[derive(Debug)]
+struct WaveformFile {
+    dir: PathBuf,
+    writer: Option<BufWriter<File>>,
+    count: u64,
+}
+
+impl WaveformFile {
+    //The file is only created at the first hit, when the channel is known
+    fn write(
+        &mut self,
+        raw: &RawCompassData,
+        hit: &CompassData,
+        map: &ChannelMap,
+    ) -> Result<(), EVBError> {
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let (board, channel) = decompose_uuid_to_board_channel(&hit.uuid);
+                let name = match map.get_channel_data(&hit.uuid) {
+                    Some(data) => data.channel_type.to_string(),
+                    None => String::from("Unmapped"),
+                };
+                std::fs::create_dir_all(&self.dir)?;
+                let path = self.dir.join(format!("{}_{}_{}.csv", name, board, channel));
+                info!(
+                    "Writing waveforms of board {} channel {} to {}",
+                    board,
+                    channel,
+                    path.display()
+                );
+                let mut writer = BufWriter::new(File::create(path)?);
+                writeln!(writer, "timestamp,energy,energy_short,samples")?;
+                self.writer.insert(writer)
+            }
+        };
+        let samples: Vec<String> = raw
+            .samples
+            .iter()
+            .map(|sample| sample.to_string())
+            .collect();
+        writeln!(
+            writer,
+            "{},{},{},{}",
+            hit.timestamp,
+            raw.energy,
+            raw.energy_short,
+            samples.join(" ")
+        )?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+//Applies the waveform mode to the hits of one input file
+#[derive(Debug)]
+pub struct WaveformHandler<'a> {
+    params: WaveformParams,
+    map: &'a ChannelMap,
+    file: WaveformFile,
+}
+
+impl<'a> WaveformHandler<'a> {
+    //None in Drop mode, where there is nothing to do. dir is where Write mode puts its files.
+    pub fn new(params: &WaveformParams, map: &'a ChannelMap, dir: PathBuf) -> Option<Self> {
+        match params.mode {
+            WaveformMode::Drop => None,
+            WaveformMode::Derived | WaveformMode::Write => Some(WaveformHandler {
+                params: params.clone(),
+                map,
+                file: WaveformFile {
+                    dir,
+                    writer: None,
+                    count: 0,
+                },
+            }),
+        }
+    }
+
+    pub fn process(
+        &mut self,
+        raw: &RawCompassData,
+        hit: CompassData,
+    ) -> Result<CompassData, EVBError> {
+        match self.params.mode {
+            WaveformMode::Drop => Ok(hit),
+            WaveformMode::Derived => Ok(CompassData {
+                waveform: WaveformSummary::from_samples(&raw.samples, &self.params),
+                ..hit
+            }),
+            WaveformMode::Write => {
+                if !raw.samples.is_empty() {
+                    self.file.write(raw, &hit, self.map)?;
+                }
+                Ok(hit)
+            }
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<(), EVBError> {
+        if let Some(writer) = &mut self.file.writer {
+            writer.flush()?;
+            info!("Wrote {} waveforms", self.file.count);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::info;
+use tar::{Archive, Builder};
+
+use super::error::EVBError;
+use super::formatting::format_bytes;
+use super::ws::Workspace;
+
+//Files of the built runs that go into a bundle: reports, build metadata, scaler counts, time
+//offsets, pulser summaries and logs. Tables, spectra images and waveforms are left out, they can
+//be built again from the raw data.
+const BUNDLED_EXTENSIONS: [&str; 5] = ["json", "yaml", "txt", "csv", "log"];
+
+//Everything of a workspace but the data: its config (with the channel map, shift map and
+//calibrations), run notes, scaler lists and the reports of its built runs, as a .tar.gz with the
+//paths relative to the workspace. Returns the number of files bundled.
+pub fn export_workspace_bundle(
+    workspace_dir: &Path,
+    bundle_path: &Path,
+) -> Result<usize, EVBError> {
+    if !workspace_dir.is_dir() {
+        return Err(EVBError::Bundle(format!(
+            "{} is not a workspace",
+            workspace_dir.display()
+        )));
+    }
+    let workspace = Workspace::new(workspace_dir)
+        .map_err(|e| EVBError::Bundle(format!("{}: {}", workspace_dir.display(), e)))?;
+    let root = workspace.get_parent_dir();
+    let config_path = workspace.get_config_path();
+    if !config_path.exists() {
+        return Err(EVBError::Bundle(format!(
+            "{} has no configuration to bundle",
+            root.display()
+        )));
+    }
+    let mut files = vec![config_path];
+    let notes_path = workspace.get_run_notes_path();
+    if notes_path.exists() {
+        files.push(notes_path);
+    }
+    if let Ok(scaler_dir) = workspace.get_scaler_dir() {
+        collect_files(&scaler_dir, &mut files, false)?;
+    }
+    if let Ok(output_dir) = workspace.get_output_dir() {
+        collect_files(&output_dir, &mut files, true)?;
+    }
+
+    let mut builder = Builder::new(GzEncoder::new(
+        File::create(bundle_path)?,
+        Compression::default(),
+    ));
+    for file in files.iter() {
+        let name = file.strip_prefix(root).map_err(|_| {
+            EVBError::Bundle(format!("{} is outside the workspace", file.display()))
+        })?;
+        builder.append_path_with_name(file, name)?;
+    }
+    builder.into_inner()?.finish()?;
+    info!(
+        "Bundled {} files of {} into {} ({})",
+        files.len(),
+        root.display(),
+        bundle_path.display(),
+        format_bytes(std::fs::metadata(bundle_path)?.len() as usize)
+    );
+    Ok(files.len())
+}
+
+//Unpacks a bundle into a workspace, created if needed. A workspace with a configuration of its
+//own is never overwritten. The raw data goes into its raw_binary directory as usual.
+pub fn import_workspace_bundle(bundle_path: &Path, workspace_dir: &Path) -> Result<(), EVBError> {
+    let workspace = Workspace::new(workspace_dir)
+        .map_err(|e| EVBError::Bundle(format!("{}: {}", workspace_dir.display(), e)))?;
+    if workspace.get_config_path().exists() {
+        return Err(EVBError::Bundle(format!(
+            "{} already has a configuration",
+            workspace_dir.display()
+        )));
+    }
+    //tar keeps every entry inside the workspace
+    Archive::new(GzDecoder::new(File::open(bundle_path)?)).unpack(workspace_dir)?;
+    if !workspace.get_config_path().exists() {
+        return Err(EVBError::Bundle(format!(
+            "{} has no configuration, it is not a workspace bundle",
+            bundle_path.display()
+        )));
+    }
+    info!(
+        "Imported {} into workspace {}",
+        bundle_path.display(),
+        workspace_dir.display()
+    );
+    Ok(())
+}
+
+//Files with a bundled extension in a directory, in name order so bundles are reproducible
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>, is_recursive: bool) -> Result<(), EVBError> {
+    let mut entries: Vec<PathBuf> = dir
+        .read_dir()?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            if is_recursive {
+                collect_files(&path, files, is_recursive)?;
+            }
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| BUNDLED_EXTENSIONS.contains(&extension))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+//Writes are passed on in pieces of at most this size, so the pauses stay short and even
+const CHUNK_SIZE_BYTES: usize = 1_000_000;
+
+//Cap on the rate the large tables are written at, so a build on the shared experiment storage
+//does not starve the DAQ's own writes during beam
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WriteLimitParams {
+    pub enabled: bool,
+    pub megabytes_per_second: f64,
+}
+
+impl Default for WriteLimitParams {
+    fn default() -> Self {
+        WriteLimitParams {
+            enabled: false,
+            megabytes_per_second: 50.0,
+        }
+    }
+}
+
+impl WriteLimitParams {
+    pub fn get_bytes_per_second(&self) -> Option<f64> {
+        if self.enabled && self.megabytes_per_second > 0.0 {
+            Some(self.megabytes_per_second * 1.0e6)
+        } else {
+            None
+        }
+    }
+}
+
+//Sleeps whenever the bytes written so far run ahead of the limit
+pub struct ThrottledWriter<W: Write> {
+    inner: W,
+    bytes_per_second: Option<f64>,
+    start: Instant,
+    written_bytes: u64,
+}
+
+impl<W: Write> ThrottledWriter<W> {
+    pub fn new(inner: W, limit: &WriteLimitParams) -> Self {
+        ThrottledWriter {
+            inner,
+            bytes_per_second: limit.get_bytes_per_second(),
+            start: Instant::now(),
+            written_bytes: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for ThrottledWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let bytes_per_second = match self.bytes_per_second {
+            Some(rate) => rate,
+            None => return self.inner.write(buf),
+        };
+        let written = self.inner.write(&buf[..buf.len().min(CHUNK_SIZE_BYTES)])?;
+        self.written_bytes += written as u64;
+        let due = Duration::from_secs_f64(self.written_bytes as f64 / bytes_per_second);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            std::thread::sleep(due - elapsed);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
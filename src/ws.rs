@@ -7,7 +7,13 @@ use std::path::{Path, PathBuf};
 const RAW_BINARY: &str = "raw_binary";
 const TEMP_BINARY: &str = "temp_binary";
 const BUILT: &str = "built";
+//Quick builds of the start of a run, inside BUILT, so they never overwrite a full build
+const QUICK_BUILT: &str = "quick";
 const SCALERS: &str = "scalers";
+//Configuration of the campaign using this workspace, saved and loaded when switching workspaces
+const CONFIG: &str = "evb_config.yaml";
+//Shifter notes of every run, see RunNotes
+const RUN_NOTES: &str = "run_notes.yaml";
 
 #[derive(Debug, Clone)]
 pub enum WorkspaceError {
@@ -73,6 +79,27 @@ impl Workspace {
         }
     }
 
+    pub fn get_parent_dir(&self) -> &Path {
+        &self.parent_dir
+    }
+
+    pub fn get_config_path(&self) -> PathBuf {
+        self.parent_dir.join(CONFIG)
+    }
+
+    pub fn get_run_notes_path(&self) -> PathBuf {
+        self.parent_dir.join(RUN_NOTES)
+    }
+
+    pub fn get_scaler_dir(&self) -> Result<PathBuf, WorkspaceError> {
+        let scaler_dir = self.parent_dir.join(SCALERS);
+        if scaler_dir.exists() {
+            Ok(scaler_dir)
+        } else {
+            Err(WorkspaceError::SubdirectoryError)
+        }
+    }
+
     pub fn get_archive_dir(&self) -> Result<PathBuf, WorkspaceError> {
         let archive_dir = self.parent_dir.join(RAW_BINARY);
         if archive_dir.exists() {
@@ -100,6 +127,15 @@ impl Workspace {
         }
     }
 
+    //Created on first use
+    pub fn get_quick_build_dir(&self) -> Result<PathBuf, WorkspaceError> {
+        let quick_dir = self.get_output_dir()?.join(QUICK_BUILT);
+        if !quick_dir.exists() && fs::create_dir(&quick_dir).is_err() {
+            return Err(WorkspaceError::SubdirectoryError);
+        }
+        Ok(quick_dir)
+    }
+
     fn init_workspace(&self) -> Result<(), WorkspaceError> {
         let raw_binary = self.parent_dir.join(RAW_BINARY);
         let temp_binary = self.parent_dir.join(TEMP_BINARY);
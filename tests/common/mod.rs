@@ -0,0 +1,167 @@
+//What the integration tests share: CoMPASS files written record by record, configs of one board
+//and scratch directories removed when dropped. Each test crate uses only part of it.
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use cebra_eventbuilder::{HeadlessOptions, RunConfig};
+
+//Energy and short energy
+pub const HEADER: u16 = 0x0001 | 0x0004;
+//Board, channel, timestamp, energy, short energy and flags
+pub const RECORD_BYTES: usize = 20;
+
+//One hit as CoMPASS writes it, the time in ns
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    pub board: u16,
+    pub channel: u16,
+    pub time: u64,
+    pub energy: u16,
+    pub energy_short: u16,
+}
+
+impl Record {
+    //Times start after 0, as a hit of board 0 channel 0 at 0 is an empty hit
+    pub fn new(channel: u16, time: u64) -> Self {
+        Record {
+            board: 0,
+            channel,
+            time,
+            energy: 1000,
+            energy_short: 500,
+        }
+    }
+
+    pub fn with_energy(self, energy: u16) -> Self {
+        Record { energy, ..self }
+    }
+}
+
+//The records in the order given, without the header
+pub fn encode_records(records: &[Record]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(records.len() * RECORD_BYTES);
+    for record in records.iter() {
+        bytes.extend(record.board.to_le_bytes());
+        bytes.extend(record.channel.to_le_bytes());
+        bytes.extend((record.time * 1000).to_le_bytes());
+        bytes.extend(record.energy.to_le_bytes());
+        bytes.extend(record.energy_short.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn encode_file(records: &[Record]) -> Vec<u8> {
+    let mut bytes = HEADER.to_le_bytes().to_vec();
+    bytes.extend(encode_records(records));
+    bytes
+}
+
+//The name CoMPASS gives the file of a channel of board 0
+pub fn get_channel_file_name(channel: u16, run: i32) -> String {
+    format!("DataR_CH{}@V1730_89_run_{}.BIN", channel, run)
+}
+
+//A config of one board with the given channels, the rest None, and a 3 us window. The settings of
+//the extra YAML replace those.
+pub fn get_config_yaml(channels: &[&str], extra: &str) -> String {
+    let channel_map = if channels.is_empty() {
+        String::from("[]")
+    } else {
+        let mut names = channels.to_vec();
+        names.resize(16, "None");
+        format!("\n- channels: [{}]", names.join(", "))
+    };
+    let base = format!(
+        "workspace: null\ncoincidence_window: 3000.0\nrun_min: 1\nrun_max: 1\n\
+         channel_map_entries: {}\nshift_map_entries: []\nscaler_list_entries: []\n",
+        channel_map
+    );
+    let mut config: serde_yaml::Mapping = serde_yaml::from_str(&base).unwrap();
+    if !extra.trim().is_empty() {
+        let extra: serde_yaml::Mapping = serde_yaml::from_str(extra).unwrap();
+        config.extend(extra);
+    }
+    serde_yaml::to_string(&config).unwrap()
+}
+
+pub fn get_config(channels: &[&str], extra: &str) -> RunConfig {
+    serde_yaml::from_str(&get_config_yaml(channels, extra)).unwrap()
+}
+
+//A directory of the system temp directory for one test, removed when dropped
+pub struct ScratchDir {
+    pub dir: PathBuf,
+}
+
+impl ScratchDir {
+    pub fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("cebra_evb_test_{}_{}", name, std::process::id()));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        ScratchDir { dir }
+    }
+
+    pub fn get_path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    //Creates the file and its directories if need be, as the DAQ writes a file
+    pub fn append(&self, name: &str, bytes: &[u8]) {
+        let path = self.get_path(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    //The files as the archive of the run in raw_binary, making the directory a workspace
+    pub fn write_archive(&self, run: i32, files: &[(String, Vec<u8>)]) {
+        let archive_dir = self.get_path("raw_binary");
+        std::fs::create_dir_all(&archive_dir).unwrap();
+        let archive = std::fs::File::create(archive_dir.join(format!("run_{}.tar.gz", run)));
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            archive.unwrap(),
+            flate2::Compression::fast(),
+        ));
+        for (name, bytes) in files.iter() {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, bytes.as_slice())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    pub fn get_options(&self) -> HeadlessOptions {
+        HeadlessOptions {
+            workspace: Some(self.dir.clone()),
+            ..Default::default()
+        }
+    }
+
+    pub fn read_report(&self, run: i32) -> serde_json::Value {
+        read_json(&self.get_path(&format!("built/run_{}/report.json", run)))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn read_json(path: &Path) -> serde_json::Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
@@ -0,0 +1,37 @@
+//Parse errors of a config name what is wrong in the terms of the setting that is wrong
+mod common;
+
+use cebra_eventbuilder::{parse_config, ChannelType, ConfigFormat};
+
+fn get_config_yaml(extra: &str) -> String {
+    common::get_config_yaml(&["Cebra0", "Passthrough0"], extra)
+}
+
+fn get_error(config: &str) -> String {
+    parse_config(config, ConfigFormat::Yaml)
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn unknown_channel_type_is_an_unknown_detector() {
+    let error = get_error(&get_config_yaml("").replace("Cebra0", "Cebr0"));
+    assert!(error.contains("unknown detector name 'Cebr0'"), "{}", error);
+}
+
+#[test]
+fn unknown_setting_variant_keeps_the_expected_variants() {
+    let error = get_error(&format!("{}log_level: Loud\n", get_config_yaml("")));
+    assert!(!error.contains("detector"), "{}", error);
+    assert!(error.contains("unknown variant `Loud`"), "{}", error);
+    assert!(error.contains("`Debug`"), "{}", error);
+}
+
+#[test]
+fn channel_types_include_passthrough_channels() {
+    assert!(parse_config(&get_config_yaml(""), ConfigFormat::Yaml).is_ok());
+    let types = ChannelType::get_types(2, 2);
+    assert!(types.contains(&ChannelType::Passthrough(1)));
+    assert!(!types.contains(&ChannelType::Passthrough(2)));
+    assert_eq!(types.last(), Some(&ChannelType::None));
+}
@@ -0,0 +1,77 @@
+//Builds the bundled reference run through the library, the way a notebook or script would, and
+//checks the event counts against those of the regression build
+mod common;
+
+use std::collections::BTreeMap;
+
+use cebra_eventbuilder::{build_run_dataframe, HeadlessOptions, ResourceOverrides, RunConfig};
+use common::ScratchDir;
+
+const REFERENCE_ARCHIVE: &[u8] = include_bytes!("../regression/run_1.tar.gz");
+const REFERENCE_CONFIG: &str = include_str!("../regression/config.yaml");
+const REFERENCE_COUNTS: &str = include_str!("../regression/counts.yaml");
+const REFERENCE_RUN: i32 = 1;
+
+//A scratch workspace with the reference archive in its raw_binary directory
+fn get_workspace(name: &str) -> ScratchDir {
+    let workspace = ScratchDir::new(name);
+    let archive_dir = workspace.get_path("raw_binary");
+    std::fs::create_dir_all(&archive_dir).unwrap();
+    std::fs::write(
+        archive_dir.join(format!("run_{}.tar.gz", REFERENCE_RUN)),
+        REFERENCE_ARCHIVE,
+    )
+    .unwrap();
+    workspace
+}
+
+fn get_reference_count(name: &str) -> u64 {
+    let counts: BTreeMap<String, u64> = serde_yaml::from_str(REFERENCE_COUNTS).unwrap();
+    *counts
+        .get(name)
+        .unwrap_or_else(|| panic!("regression/counts.yaml has no {}", name))
+}
+
+fn build_reference(name: &str, threads: usize) {
+    let workspace = get_workspace(name);
+    let config: RunConfig = serde_yaml::from_str(REFERENCE_CONFIG).unwrap();
+    let options = HeadlessOptions {
+        resource_overrides: ResourceOverrides {
+            threads: Some(threads),
+            memory_budget_mb: None,
+        },
+        ..workspace.get_options()
+    };
+    let events = build_run_dataframe(config, REFERENCE_RUN, &options).unwrap();
+    assert_eq!(
+        events.height() as u64,
+        get_reference_count("events.parquet rows")
+    );
+
+    //The report is still written to the workspace
+    let report = workspace.read_report(REFERENCE_RUN);
+    assert_eq!(
+        report["total_hits"].as_u64(),
+        Some(get_reference_count("hits"))
+    );
+    for channel in report["channels"].as_array().unwrap() {
+        let detector = channel["detector"].as_str().unwrap();
+        assert_eq!(
+            channel["hits"].as_u64(),
+            Some(get_reference_count(&format!("{} hits", detector))),
+            "hits of {}",
+            detector
+        );
+    }
+}
+
+#[test]
+fn reference_run_has_reference_counts() {
+    build_reference("single_thread", 1);
+}
+
+//The counts do not depend on how the run is split over threads
+#[test]
+fn reference_run_has_reference_counts_with_threads() {
+    build_reference("threads", 4);
+}
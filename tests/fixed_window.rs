@@ -0,0 +1,34 @@
+//Fixed window events are opened by any hit, so a trigger channel is refused rather than ignored
+mod common;
+
+use cebra_eventbuilder::{
+    build_run_dataframe, ChannelType, EventStream, HeadlessOptions, RunConfig,
+};
+
+fn get_config() -> RunConfig {
+    common::get_config(
+        &["Cebra0", "Cebra1"],
+        "trigger:\n  channel: null\n  on_missing: Fail\n  strategy: FixedWindow\n",
+    )
+}
+
+#[test]
+fn build_with_fixed_window_trigger_is_rejected() {
+    let mut config = get_config();
+    config.trigger.channel = Some(ChannelType::Cebra(0));
+    let error = build_run_dataframe(config, 1, &HeadlessOptions::default())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("cannot have a trigger channel"), "{}", error);
+}
+
+#[test]
+fn stream_with_fixed_window_refuses_a_trigger() {
+    let mut config = get_config();
+    assert!(EventStream::from_params(&config)
+        .unwrap()
+        .with_trigger(ChannelType::Cebra(0))
+        .is_err());
+    config.trigger.channel = Some(ChannelType::Cebra(0));
+    assert!(EventStream::from_params(&config).is_err());
+}
@@ -0,0 +1,96 @@
+//A followed run is read through the same wrappers as a build, waiting at the end of what was
+//written
+mod common;
+
+use std::time::Duration;
+
+use cebra_eventbuilder::{FollowParams, RunFollower};
+use common::{encode_records, get_channel_file_name, get_config, Record, ScratchDir, HEADER};
+
+const RUN: i32 = 7;
+const EVENTS: u64 = 20;
+
+//The records of a channel, one per event 10 us apart, channel 1 50 ns after channel 0. Every
+//other hit of channel 1 is below its threshold.
+fn get_records(channel: u16, events: std::ops::Range<u64>) -> Vec<u8> {
+    let records: Vec<Record> = events
+        .map(|event| {
+            let record = Record::new(channel, (event + 1) * 10_000 + 50 * channel as u64);
+            if channel == 1 && event % 2 == 1 {
+                record.with_energy(100)
+            } else {
+                record
+            }
+        })
+        .collect();
+    encode_records(&records)
+}
+
+//Hits of channel 1 below 500 are dropped as they are read
+fn get_follower(run: &ScratchDir) -> RunFollower {
+    let config = get_config(
+        &["Cebra0", "Cebra1"],
+        "energy_thresholds:\n- board_number: 0\n  channel_number: 1\n  threshold: 500.0\n",
+    );
+    let params = FollowParams {
+        poll_interval: Duration::ZERO,
+        idle_timeout: Duration::from_secs(600),
+        ..Default::default()
+    };
+    RunFollower::new(&config, &run.get_path(&format!("run_{}", RUN)), params).unwrap()
+}
+
+fn get_file_name(channel: u16, segment: usize) -> String {
+    let name = get_channel_file_name(channel, RUN);
+    match segment {
+        0 => format!("run_{}/{}", RUN, name),
+        _ => format!(
+            "run_{}/{}",
+            RUN,
+            name.replace(".BIN", &format!("_{}.BIN", segment))
+        ),
+    }
+}
+
+#[test]
+fn follower_waits_for_the_rest_of_a_record() {
+    let run = ScratchDir::new("follow");
+    let (channel_0, channel_1) = (get_file_name(0, 0), get_file_name(1, 0));
+    let half = (EVENTS / 2) as usize;
+    let second_half = get_records(1, half as u64..EVENTS);
+    run.append(&channel_0, &HEADER.to_le_bytes());
+    run.append(&channel_0, &get_records(0, 0..EVENTS));
+    run.append(&channel_1, &HEADER.to_le_bytes());
+    run.append(&channel_1, &get_records(1, 0..half as u64));
+    run.append(&channel_1, &second_half[..common::RECORD_BYTES / 2]);
+    let mut follower = get_follower(&run);
+    let first = follower.poll().unwrap();
+    //Channel 1 is still growing, so nothing past its last hit above the threshold (event 8) is
+    //taken, and the window of that hit is still open
+    assert_eq!(first.len(), half - 2);
+
+    run.append(&channel_1, &second_half[common::RECORD_BYTES / 2..]);
+    let mut events = first;
+    events.extend(follower.poll().unwrap());
+    events.extend(follower.finish().unwrap());
+    assert_eq!(events.len() as u64, EVENTS - 1);
+    let pairs = events.iter().filter(|event| event.len() == 2).count();
+    //Only the even events of channel 1 pass its threshold
+    assert_eq!(pairs as u64, EVENTS / 2);
+    assert_eq!(follower.get_late_hits(), 0);
+}
+
+#[test]
+fn follower_reads_the_next_segment() {
+    let run = ScratchDir::new("follow_segments");
+    let half = EVENTS / 2;
+    run.append(&get_file_name(0, 0), &HEADER.to_le_bytes());
+    run.append(&get_file_name(0, 0), &get_records(0, 0..half));
+    let mut follower = get_follower(&run);
+    let mut events = follower.poll().unwrap();
+    run.append(&get_file_name(0, 1), &HEADER.to_le_bytes());
+    run.append(&get_file_name(0, 1), &get_records(0, half..EVENTS));
+    events.extend(follower.poll().unwrap());
+    events.extend(follower.finish().unwrap());
+    assert_eq!(events.len() as u64, EVENTS - 1);
+}
@@ -0,0 +1,97 @@
+//Generated runs with fixed seeds built through the whole pipeline, checking the coincidence logic
+//against what was generated
+mod common;
+
+use cebra_eventbuilder::{
+    generate_run, validate_generated_run, GeneratorValidation, HitGeneratorParams, RunConfig,
+};
+
+//Four CeBrA detectors on one board, with a window far longer than the spread of an event and far
+//shorter than the time between events
+fn get_config() -> RunConfig {
+    common::get_config(&["Cebra0", "Cebra1", "Cebra2", "Cebra3"], "")
+}
+
+fn validate(params: &HitGeneratorParams) -> GeneratorValidation {
+    let validation = validate_generated_run(get_config(), params).unwrap();
+    assert!(validation.truth.events > 0);
+    assert!(
+        validation.is_matched(),
+        "seed {}:\n{}",
+        params.seed,
+        validation.format_report()
+    );
+    validation
+}
+
+#[test]
+fn same_seed_generates_same_run() {
+    let params = HitGeneratorParams {
+        seed: 7,
+        ..Default::default()
+    };
+    let first = generate_run(&params, &get_config()).unwrap();
+    let second = generate_run(&params, &get_config()).unwrap();
+    assert_eq!(first.truth, second.truth);
+    let other = generate_run(
+        &HitGeneratorParams {
+            seed: 8,
+            ..Default::default()
+        },
+        &get_config(),
+    )
+    .unwrap();
+    assert_ne!(first.truth, other.truth);
+}
+
+#[test]
+fn jittered_events_are_built_as_generated() {
+    for seed in [1, 2, 3] {
+        let validation = validate(&HitGeneratorParams {
+            seed,
+            timestamp_jitter: 200.0,
+            multiplicity_weights: vec![1.0, 1.0, 1.0, 1.0],
+            ..Default::default()
+        });
+        assert!(validation.truth.multiplicities.contains_key(&4));
+    }
+}
+
+#[test]
+fn pileup_hits_join_their_events() {
+    let validation = validate(&HitGeneratorParams {
+        seed: 11,
+        pileup_fraction: 0.3,
+        pileup_window: 1000.0,
+        ..Default::default()
+    });
+    assert!(validation.truth.pileup_hits > 0);
+}
+
+#[test]
+fn corrupted_files_lose_only_their_cut_hits() {
+    let validation = validate(&HitGeneratorParams {
+        seed: 21,
+        corrupt_files: 2,
+        ..Default::default()
+    });
+    assert_eq!(validation.truth.corrupted_hits, 2);
+}
+
+//With a window shorter than the spread of the hits of an event, events are split
+#[test]
+fn short_window_splits_events() {
+    let mut config = get_config();
+    config.coincidence_window = 10.0;
+    let validation = validate_generated_run(
+        config,
+        &HitGeneratorParams {
+            seed: 31,
+            timestamp_jitter: 200.0,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(!validation.is_matched());
+    assert!(validation.built_events > validation.truth.events);
+}
@@ -0,0 +1,66 @@
+//The hits of the files of a run are merged in time order, hits of a file that is not sorted are
+//merged as they come and counted as out of order
+mod common;
+
+use cebra_eventbuilder::{ChannelMap, CompassHits, HitMerge, HitSource};
+use common::{encode_file, get_channel_file_name, Record, ScratchDir};
+
+//The file and time of each merged hit, and the hits merged out of order
+fn merge(name: &str, channel_times: &[&[u64]]) -> (Vec<(usize, f64)>, u64) {
+    let scratch = ScratchDir::new(name);
+    let map = ChannelMap::new(&[]);
+    let mut files: Vec<Box<dyn HitSource + '_>> = vec![];
+    for (channel, times) in channel_times.iter().enumerate() {
+        let path = scratch.get_path(&get_channel_file_name(channel as u16, 1));
+        let records: Vec<Record> = times
+            .iter()
+            .map(|time| Record::new(channel as u16, *time))
+            .collect();
+        std::fs::write(&path, encode_file(&records)).unwrap();
+        files.push(CompassHits::new(&[path], &map).unwrap().into_source());
+    }
+
+    let mut merge = HitMerge::new(&mut files).unwrap();
+    let mut hits = vec![];
+    while let Some(file) = merge.get_earliest_file(&mut files).unwrap() {
+        let hit = files[file].get_top_hit().unwrap();
+        hits.push((file, hit.timestamp));
+        files[file].set_hit_used();
+    }
+    (hits, merge.get_out_of_order())
+}
+
+#[test]
+fn interleaved_files_are_merged_in_time_order() {
+    let (hits, out_of_order) = merge(
+        "merge_interleaved",
+        &[&[100, 400, 700], &[200, 500, 800], &[300, 600, 900]],
+    );
+    let times: Vec<f64> = hits.iter().map(|(_, time)| *time).collect();
+    assert_eq!(
+        times,
+        (1..=9).map(|hit| hit as f64 * 100.0).collect::<Vec<_>>()
+    );
+    let files: Vec<usize> = hits.iter().map(|(file, _)| *file).collect();
+    assert_eq!(files, [0, 1, 2, 0, 1, 2, 0, 1, 2]);
+    assert_eq!(out_of_order, 0);
+}
+
+#[test]
+fn unsorted_file_is_counted_out_of_order() {
+    //Channel 2 writes its hit at 250 ns after the one at 450 ns
+    let (hits, out_of_order) = merge(
+        "merge_unsorted",
+        &[
+            &[100, 300, 500, 700],
+            &[200, 400, 600, 800],
+            &[150, 450, 250, 650],
+        ],
+    );
+    let times: Vec<f64> = hits.iter().map(|(_, time)| *time).collect();
+    assert_eq!(
+        times,
+        [100.0, 150.0, 200.0, 300.0, 400.0, 450.0, 250.0, 500.0, 600.0, 650.0, 700.0, 800.0]
+    );
+    assert_eq!(out_of_order, 1);
+}
@@ -0,0 +1,56 @@
+//HitOrder is only written while its digits fit in a float exactly
+use cebra_eventbuilder::{
+    generate_board_channel_uuid, Board, ChannelType, CompassData, EventStream,
+};
+
+const INVALID_VALUE: f64 = -1.0e6;
+
+//Twelve detectors, so each takes two digits
+fn get_boards() -> Vec<Board> {
+    let mut board = Board::default();
+    for (channel, slot) in board.channels.iter_mut().enumerate().take(12) {
+        *slot = ChannelType::Cebra(channel);
+    }
+    vec![board]
+}
+
+//The HitOrder of an event where the detectors fired in the order given, 10 ns apart
+fn get_hit_order(detectors: &[u32]) -> f64 {
+    let mut stream = EventStream::new(&get_boards(), 3000.0);
+    let hit = |channel: u32, timestamp: f64| CompassData {
+        uuid: generate_board_channel_uuid(&0, &channel),
+        energy: 1000.0,
+        energy_short: 500.0,
+        timestamp,
+        raw_timestamp: timestamp,
+        waveform: None,
+        flags: 0,
+    };
+    for (index, detector) in detectors.iter().enumerate() {
+        stream.push_hit(&hit(*detector, 1000.0 + 10.0 * index as f64));
+    }
+    //A hit past the window closes the event
+    stream.push_hit(&hit(0, 1.0e6));
+    let events = stream.take_dataframe().unwrap();
+    assert_eq!(events.height(), 1);
+    events
+        .column("HitOrder")
+        .unwrap()
+        .f64()
+        .unwrap()
+        .get(0)
+        .unwrap()
+}
+
+#[test]
+fn hit_order_of_seven_detectors_is_exact() {
+    assert_eq!(
+        get_hit_order(&[11, 0, 5, 3, 9, 1, 10]),
+        12_01_06_04_10_02_11.0
+    );
+}
+
+#[test]
+fn hit_order_past_float_precision_is_invalid() {
+    assert_eq!(get_hit_order(&[11, 0, 5, 3, 9, 1, 10, 2]), INVALID_VALUE);
+}
@@ -0,0 +1,23 @@
+//The log of a run always keeps its warnings and errors, so there is no level below Warn
+mod common;
+
+use cebra_eventbuilder::{LogLevel, RunConfig};
+
+fn get_config(extra: &str) -> RunConfig {
+    common::get_config(&[], extra)
+}
+
+#[test]
+fn error_level_configs_load_as_warn() {
+    let config = get_config("log_level: Error\n");
+    assert_eq!(config.log_level, LogLevel::Warn);
+    let config = get_config("");
+    assert_eq!(config.log_level, LogLevel::Info);
+}
+
+#[test]
+fn command_line_levels() {
+    assert_eq!(LogLevel::parse("Debug"), Some(LogLevel::Debug));
+    assert_eq!(LogLevel::parse("warn"), Some(LogLevel::Warn));
+    assert_eq!(LogLevel::parse("error"), None);
+}
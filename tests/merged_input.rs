@@ -0,0 +1,59 @@
+//Time-merged files are told from their records, not from the number of files of the run, and are
+//re-sorted after the time shifts before the hits reach the event builder
+mod common;
+
+use cebra_eventbuilder::{build_run_dataframe, HeadlessOptions};
+use common::{encode_file, get_channel_file_name, get_config, Record, ScratchDir};
+
+//Generated, the last of which is the window still open at the end of the run and never built
+const EVENTS: u64 = 1000;
+
+//A workspace with the files as the archive of run 1, built with the paranoid checks. Channel 0 is
+//shifted well past channel 1, which it comes before in the file.
+fn build(name: &str, files: Vec<(String, Vec<u8>)>) -> (usize, serde_json::Value) {
+    let workspace = ScratchDir::new(name);
+    workspace.write_archive(1, &files);
+    let config = get_config(
+        &["Cebra0", "Cebra1"],
+        "shift_map_entries:\n- board_number: 0\n  channel_number: 0\n  time_shift: 2000.0\n",
+    );
+    let options = HeadlessOptions {
+        paranoid: true,
+        ..workspace.get_options()
+    };
+    let events = build_run_dataframe(config, 1, &options).unwrap();
+    (events.height(), workspace.read_report(1))
+}
+
+//The paranoid checks stop the build if a hit reaches the event builder out of time order
+#[test]
+fn shifted_merged_file_is_built_in_order() {
+    let records: Vec<Record> = (0..EVENTS)
+        .flat_map(|event| {
+            let time = (event + 1) * 10_000;
+            [Record::new(0, time), Record::new(1, time + 50)]
+        })
+        .collect();
+    let (events, report) = build(
+        "merged",
+        vec![(String::from("DataR_run_1.BIN"), encode_file(&records))],
+    );
+    assert_eq!(events as u64, EVENTS - 1);
+    assert_eq!(report["total_hits"].as_u64(), Some(2 * EVENTS));
+    assert_eq!(report["timestamps"]["dropped"].as_u64(), Some(0));
+    assert!(report["timestamps"]["reordered"].as_u64().unwrap() > 0);
+}
+
+//A run of a single channel has a single file too, which is not re-sorted
+#[test]
+fn single_channel_file_is_not_merged() {
+    let records: Vec<Record> = (0..EVENTS)
+        .map(|event| Record::new(0, (event + 1) * 10_000))
+        .collect();
+    let (events, report) = build(
+        "single_channel",
+        vec![(get_channel_file_name(0, 1), encode_file(&records))],
+    );
+    assert_eq!(events as u64, EVENTS - 1);
+    assert!(report["timestamps"].is_null());
+}
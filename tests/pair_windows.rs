@@ -0,0 +1,74 @@
+//A window closed with pair windows keeps only the hits within the window of their pair with its
+//first hit, the others are built again into the events after it
+mod common;
+
+use cebra_eventbuilder::{build_run_dataframe, validate_generated_run, HitGeneratorParams};
+use common::{encode_file, get_channel_file_name, get_config, Record, ScratchDir};
+
+const CHANNELS: [&str; 5] = ["Cebra0", "Cebra1", "Cebra2", "Cebra3", "AnodeFront"];
+//The flight time through the spectrograph for CeBrA and SPS, 50 ns between CeBrA detectors
+const PAIR_WINDOWS: &str = "pair_windows:
+- {first: Cebra, second: Sps, window: 3, unit: Microseconds}
+- {first: Cebra, second: Cebra, window: 50, unit: Nanoseconds}
+";
+const GROUPS: u64 = 50;
+
+//Hits of an event are a few ns apart, far inside every pair window, so none is taken out
+#[test]
+fn generated_events_are_built_as_generated() {
+    for seed in [21, 22] {
+        let params = HitGeneratorParams {
+            seed,
+            ..Default::default()
+        };
+        let validation =
+            validate_generated_run(get_config(&CHANNELS, PAIR_WINDOWS), &params).unwrap();
+        assert!(validation.truth.events > 0);
+        assert!(
+            validation.is_matched(),
+            "seed {}:\n{}",
+            seed,
+            validation.format_report()
+        );
+    }
+}
+
+//Groups 10 us apart of a Cebra0 hit, a Cebra1 hit 200 ns after it and an SPS hit 2 us after it
+#[test]
+fn late_cebra_hit_starts_an_event_of_its_own() {
+    let workspace = ScratchDir::new("pair_windows");
+    let files: Vec<(String, Vec<u8>)> = [(0u16, 0u64), (1, 200), (4, 2000)]
+        .iter()
+        .map(|(channel, delay)| {
+            let records: Vec<Record> = (0..GROUPS)
+                .map(|group| Record::new(*channel, (group + 1) * 10_000 + delay))
+                .collect();
+            (get_channel_file_name(*channel, 1), encode_file(&records))
+        })
+        .collect();
+    workspace.write_archive(1, &files);
+    let config = get_config(&CHANNELS, PAIR_WINDOWS);
+    let events = build_run_dataframe(config, 1, &workspace.get_options()).unwrap();
+
+    //Each group but the last, whose window is still open at the end of the run, gives two events
+    assert_eq!(events.height() as u64, 2 * (GROUPS - 1));
+    let get_times = |column: &str| -> Vec<Option<f64>> {
+        events
+            .column(column)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .take(2)
+            .map(|time| time.filter(|time| *time >= 0.0))
+            .collect()
+    };
+    assert_eq!(get_times("Cebra0Time"), [Some(10_000.0), None]);
+    assert_eq!(get_times("AnodeFrontTime"), [Some(12_000.0), None]);
+    assert_eq!(get_times("Cebra1Time"), [None, Some(10_200.0)]);
+    let report = workspace.read_report(1);
+    assert_eq!(
+        report["event_builder"]["hits_rebuilt"].as_u64(),
+        Some(GROUPS - 1)
+    );
+}
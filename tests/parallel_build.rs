@@ -0,0 +1,65 @@
+//A run built on several builder threads gives the events and event builder counts of a single
+//builder, with the chunk cuts falling between reference trigger windows
+mod common;
+
+use cebra_eventbuilder::{
+    build_run_dataframe, generate_run, HeadlessOptions, HitGeneratorParams, ResourceOverrides,
+};
+use common::{get_config, ScratchDir};
+use polars::prelude::*;
+
+const CHANNELS: [&str; 4] = ["Cebra0", "Cebra1", "Cebra2", "Cebra3"];
+//Windows are opened by Cebra0 hits, the hits of the other detectors without one are dropped
+const TRIGGER: &str =
+    "trigger:\n  channel: Cebra0\n  on_missing: Fail\n  strategy: ReferenceTrigger\n";
+
+//The events without their energies and short energies, which are dithered afresh in every build,
+//and the event builder counts of the report
+fn build(workspace: &ScratchDir, build_threads: usize) -> (DataFrame, serde_json::Value) {
+    let config = get_config(
+        &CHANNELS,
+        &format!("{}build_threads: {}\n", TRIGGER, build_threads),
+    );
+    let options = HeadlessOptions {
+        resource_overrides: ResourceOverrides {
+            threads: Some(build_threads),
+            memory_budget_mb: None,
+        },
+        ..workspace.get_options()
+    };
+    let events = build_run_dataframe(config, 1, &options).unwrap();
+    let columns: Vec<String> = events
+        .get_column_names()
+        .into_iter()
+        .filter(|name| !name.contains("Energy") && !name.ends_with("Short"))
+        .map(String::from)
+        .collect();
+    let report = workspace.read_report(1);
+    (
+        events.select(columns).unwrap(),
+        report["event_builder"].clone(),
+    )
+}
+
+#[test]
+fn parallel_build_matches_serial_build() {
+    //About 150000 hits, a few chunks of the parallel build
+    let params = HitGeneratorParams {
+        seed: 11,
+        duration: 5.0,
+        event_rate: 20_000.0,
+        ..Default::default()
+    };
+    let run = generate_run(&params, &get_config(&CHANNELS, TRIGGER)).unwrap();
+    let workspace = ScratchDir::new("parallel_build");
+    std::fs::create_dir_all(workspace.get_path("raw_binary")).unwrap();
+    run.write_archive(&workspace.get_path("raw_binary/run_1.tar"))
+        .unwrap();
+
+    let (serial_events, serial_stats) = build(&workspace, 1);
+    let (parallel_events, parallel_stats) = build(&workspace, 4);
+    assert!(serial_events.height() > 0);
+    assert!(serial_stats["hits_dropped"].as_u64().unwrap() > 0);
+    assert!(serial_events.equals_missing(&parallel_events));
+    assert_eq!(serial_stats, parallel_stats);
+}
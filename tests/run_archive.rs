@@ -0,0 +1,219 @@
+//Zip run archives are unpacked through their central directory, stored and deflated, with data
+//descriptors and zip64 records, and entries pointing outside of the unpack directory are rejected
+mod common;
+
+use std::io::Write;
+
+use cebra_eventbuilder::{unpack_run_archive, EVBError};
+use common::ScratchDir;
+
+//Set when the sizes come after the data, in a data descriptor
+const DESCRIPTOR_FLAG: u16 = 0x0008;
+const ZIP64_MARKER: u32 = 0xFFFFFFFF;
+
+struct ZipFile {
+    name: &'static str,
+    data: Vec<u8>,
+    is_deflated: bool,
+    has_descriptor: bool,
+    //The sizes and offset only in the zip64 extra field of the central directory
+    is_zip64: bool,
+}
+
+impl ZipFile {
+    fn new(name: &'static str, data: &[u8]) -> Self {
+        ZipFile {
+            name,
+            data: data.to_vec(),
+            is_deflated: false,
+            has_descriptor: false,
+            is_zip64: false,
+        }
+    }
+}
+
+//The files as a zip archive with a comment, with a zip64 end record if asked for
+fn encode_zip(files: &[ZipFile], has_zip64_end: bool) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut directory: Vec<u8> = vec![];
+    for file in files.iter() {
+        let mut crc = flate2::Crc::new();
+        crc.update(&file.data);
+        let data = if file.is_deflated {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(&file.data).unwrap();
+            encoder.finish().unwrap()
+        } else {
+            file.data.clone()
+        };
+        let method: u16 = if file.is_deflated { 8 } else { 0 };
+        let flags = if file.has_descriptor {
+            DESCRIPTOR_FLAG
+        } else {
+            0
+        };
+        let offset = bytes.len() as u64;
+
+        bytes.extend(0x04034b50u32.to_le_bytes());
+        bytes.extend(20u16.to_le_bytes());
+        bytes.extend(flags.to_le_bytes());
+        bytes.extend(method.to_le_bytes());
+        bytes.extend([0; 4]);
+        if file.has_descriptor {
+            bytes.extend([0; 12]);
+        } else {
+            bytes.extend(crc.sum().to_le_bytes());
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend((file.data.len() as u32).to_le_bytes());
+        }
+        bytes.extend((file.name.len() as u16).to_le_bytes());
+        bytes.extend(0u16.to_le_bytes());
+        bytes.extend(file.name.as_bytes());
+        bytes.extend(&data);
+        if file.has_descriptor {
+            bytes.extend(0x08074b50u32.to_le_bytes());
+            bytes.extend(crc.sum().to_le_bytes());
+            bytes.extend((data.len() as u32).to_le_bytes());
+            bytes.extend((file.data.len() as u32).to_le_bytes());
+        }
+
+        let mut extra: Vec<u8> = vec![];
+        if file.is_zip64 {
+            extra.extend(0x0001u16.to_le_bytes());
+            extra.extend(24u16.to_le_bytes());
+            extra.extend((file.data.len() as u64).to_le_bytes());
+            extra.extend((data.len() as u64).to_le_bytes());
+            extra.extend(offset.to_le_bytes());
+        }
+        let get_field = |value: u64| {
+            if file.is_zip64 {
+                ZIP64_MARKER
+            } else {
+                value as u32
+            }
+        };
+        directory.extend(0x02014b50u32.to_le_bytes());
+        directory.extend(45u16.to_le_bytes());
+        directory.extend(45u16.to_le_bytes());
+        directory.extend(flags.to_le_bytes());
+        directory.extend(method.to_le_bytes());
+        directory.extend([0; 4]);
+        directory.extend(crc.sum().to_le_bytes());
+        directory.extend(get_field(data.len() as u64).to_le_bytes());
+        directory.extend(get_field(file.data.len() as u64).to_le_bytes());
+        directory.extend((file.name.len() as u16).to_le_bytes());
+        directory.extend((extra.len() as u16).to_le_bytes());
+        directory.extend([0; 10]);
+        directory.extend(get_field(offset).to_le_bytes());
+        directory.extend(file.name.as_bytes());
+        directory.extend(extra);
+    }
+
+    let directory_offset = bytes.len() as u64;
+    let directory_size = directory.len() as u64;
+    bytes.extend(directory);
+    if has_zip64_end {
+        let end_offset = bytes.len() as u64;
+        bytes.extend(0x06064b50u32.to_le_bytes());
+        bytes.extend(44u64.to_le_bytes());
+        bytes.extend(45u16.to_le_bytes());
+        bytes.extend(45u16.to_le_bytes());
+        bytes.extend([0; 8]);
+        bytes.extend((files.len() as u64).to_le_bytes());
+        bytes.extend((files.len() as u64).to_le_bytes());
+        bytes.extend(directory_size.to_le_bytes());
+        bytes.extend(directory_offset.to_le_bytes());
+        bytes.extend(0x07064b50u32.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(end_offset.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+    }
+    let comment = b"run 1";
+    let (entries, offset) = if has_zip64_end {
+        (0xFFFF, ZIP64_MARKER)
+    } else {
+        (files.len() as u16, directory_offset as u32)
+    };
+    bytes.extend(0x06054b50u32.to_le_bytes());
+    bytes.extend([0; 4]);
+    bytes.extend(entries.to_le_bytes());
+    bytes.extend(entries.to_le_bytes());
+    bytes.extend((directory_size as u32).to_le_bytes());
+    bytes.extend(offset.to_le_bytes());
+    bytes.extend((comment.len() as u16).to_le_bytes());
+    bytes.extend(comment);
+    bytes
+}
+
+fn unpack(scratch: &ScratchDir, files: &[ZipFile], has_zip64_end: bool) -> Result<(), EVBError> {
+    let archive = scratch.get_path("run_1.zip");
+    std::fs::write(&archive, encode_zip(files, has_zip64_end)).unwrap();
+    unpack_run_archive(&archive, &scratch.get_path("unpacked"))
+}
+
+//Long enough for the deflated data to be shorter than the file
+fn get_data(seed: u8) -> Vec<u8> {
+    (0..4096u32).map(|at| (at % 61) as u8 ^ seed).collect()
+}
+
+#[test]
+fn stored_and_deflated_entries_are_unpacked() {
+    let scratch = ScratchDir::new("zip_methods");
+    let files = [
+        ZipFile::new("run_1/", &[]),
+        ZipFile::new("run_1/stored.BIN", &get_data(1)),
+        ZipFile {
+            is_deflated: true,
+            ..ZipFile::new("run_1/RAW/deflated.BIN", &get_data(2))
+        },
+    ];
+    unpack(&scratch, &files, false).unwrap();
+    for file in files[1..].iter() {
+        let unpacked = std::fs::read(scratch.get_path("unpacked").join(file.name)).unwrap();
+        assert_eq!(unpacked, file.data, "{}", file.name);
+    }
+}
+
+#[test]
+fn descriptor_and_zip64_entries_are_unpacked() {
+    let scratch = ScratchDir::new("zip64");
+    let files = [
+        ZipFile {
+            is_deflated: true,
+            has_descriptor: true,
+            ..ZipFile::new("run_1/descriptor.BIN", &get_data(3))
+        },
+        ZipFile {
+            is_zip64: true,
+            ..ZipFile::new("run_1/zip64.BIN", &get_data(4))
+        },
+        ZipFile {
+            is_deflated: true,
+            has_descriptor: true,
+            is_zip64: true,
+            ..ZipFile::new("run_1/both.BIN", &get_data(5))
+        },
+    ];
+    unpack(&scratch, &files, true).unwrap();
+    for file in files.iter() {
+        let unpacked = std::fs::read(scratch.get_path("unpacked").join(file.name)).unwrap();
+        assert_eq!(unpacked, file.data, "{}", file.name);
+    }
+}
+
+#[test]
+fn entries_outside_of_the_unpack_directory_are_rejected() {
+    let scratch = ScratchDir::new("zip_traversal");
+    for name in ["../outside.BIN", "run_1/../../outside.BIN", "/outside.BIN"] {
+        let files = [ZipFile::new(name, &get_data(6))];
+        let error = unpack(&scratch, &files, false).unwrap_err();
+        assert!(
+            error.to_string().contains("points outside of the archive"),
+            "{}: {}",
+            name,
+            error
+        );
+        assert!(!scratch.get_path("outside.BIN").exists());
+    }
+}
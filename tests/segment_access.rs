@@ -0,0 +1,56 @@
+//Mapped and buffered reads of the same CoMPASS files give the same hits
+mod common;
+
+use std::path::Path;
+
+use cebra_eventbuilder::{ChannelMap, CompassHits, SegmentAccess};
+use common::{encode_file, Record, ScratchDir, RECORD_BYTES};
+
+//Hits 1 us apart over the 16 channels, with the last record cut short by the given bytes
+fn write_file(path: &Path, hits: u64, cut_bytes: usize) {
+    let records: Vec<Record> = (0..hits)
+        .map(|hit| Record {
+            channel: (hit % 16) as u16,
+            energy: (hit % 4096) as u16,
+            energy_short: (hit % 1024) as u16,
+            ..Record::new(0, hit * 1000)
+        })
+        .collect();
+    let mut bytes = encode_file(&records);
+    bytes.truncate(bytes.len() - cut_bytes);
+    std::fs::write(path, bytes).unwrap();
+}
+
+fn read_times(path: &Path, access: SegmentAccess) -> Vec<(u32, f64)> {
+    let map = ChannelMap::new(&[]);
+    CompassHits::with_access(&[path.to_path_buf()], &map, access)
+        .unwrap()
+        .map(|hit| {
+            let hit = hit.unwrap();
+            (hit.uuid, hit.timestamp)
+        })
+        .collect()
+}
+
+#[test]
+fn mapped_and_buffered_reads_agree() {
+    let scratch = ScratchDir::new("segment_whole");
+    let path = scratch.get_path("DataR_CH0@V1730_89_run_1.BIN");
+    write_file(&path, 1000, 0);
+    let mapped = read_times(&path, SegmentAccess::Mapped);
+    let buffered = read_times(&path, SegmentAccess::Buffered);
+    assert_eq!(mapped.len(), 1000);
+    assert_eq!(mapped, buffered);
+}
+
+//A record cut short, as the DAQ leaves a file it was writing, ends the file in both
+#[test]
+fn cut_record_ends_the_file() {
+    let scratch = ScratchDir::new("segment_cut");
+    let path = scratch.get_path("DataR_CH0@V1730_89_run_1.BIN");
+    write_file(&path, 1000, RECORD_BYTES / 2);
+    let mapped = read_times(&path, SegmentAccess::Mapped);
+    let buffered = read_times(&path, SegmentAccess::Buffered);
+    assert_eq!(mapped.len(), 999);
+    assert_eq!(mapped, buffered);
+}
@@ -0,0 +1,77 @@
+//The offsets of the campaign's time calibrator run are saved once and built into every later run
+mod common;
+
+use cebra_eventbuilder::build_run_dataframe;
+use common::{encode_file, get_channel_file_name, get_config, Record, ScratchDir};
+
+const EVENTS: u64 = 100;
+
+//One file per channel, the pulser hits of Cebra0 (channel 0) the given delay (ns) after the time
+//calibrator (channel 2)
+fn get_files(run: i32, delay: u64) -> Vec<(String, Vec<u8>)> {
+    [(0u16, delay), (2, 0)]
+        .iter()
+        .map(|(channel, offset)| {
+            let records: Vec<Record> = (0..EVENTS)
+                .map(|event| Record::new(*channel, (event + 1) * 10_000 + offset))
+                .collect();
+            (get_channel_file_name(*channel, run), encode_file(&records))
+        })
+        .collect()
+}
+
+//A workspace with an archive per run
+struct Campaign {
+    workspace: ScratchDir,
+}
+
+impl Campaign {
+    fn new(name: &str, runs: &[(i32, u64)]) -> Self {
+        let workspace = ScratchDir::new(name);
+        for (run, delay) in runs.iter() {
+            workspace.write_archive(*run, &get_files(*run, *delay));
+        }
+        Campaign { workspace }
+    }
+
+    //The Cebra0 time of the first event
+    fn build(&self, run: i32) -> f64 {
+        let config = get_config(&["Cebra0", "None", "TimeCalibrator"], "");
+        let events = build_run_dataframe(config, run, &self.workspace.get_options()).unwrap();
+        events
+            .column("Cebra0Time")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .get(0)
+            .unwrap()
+    }
+
+    fn get_offsets(&self) -> serde_yaml::Value {
+        let path = self.workspace.get_path("built/time_offsets.yaml");
+        serde_yaml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+}
+
+#[test]
+fn offsets_apply_to_later_runs() {
+    let workspace = Campaign::new("time_offsets", &[(1, 50), (2, 50)]);
+    assert_eq!(workspace.build(1), 10_050.0);
+    let offsets = workspace.get_offsets();
+    assert_eq!(offsets["run_number"].as_i64(), Some(1));
+    assert_eq!(offsets["entries"][0]["channel_number"].as_u64(), Some(0));
+    assert_eq!(offsets["entries"][0]["time_shift"].as_f64(), Some(-50.0));
+    assert_eq!(workspace.build(2), 10_000.0);
+    //Rebuilding the calibration run gives the same offsets
+    assert_eq!(workspace.build(1), 10_050.0);
+    assert_eq!(workspace.get_offsets(), offsets);
+}
+
+#[test]
+fn later_calibration_runs_keep_the_offsets() {
+    let workspace = Campaign::new("time_offsets_kept", &[(1, 50), (2, 80)]);
+    workspace.build(1);
+    let offsets = workspace.get_offsets();
+    assert_eq!(workspace.build(2), 10_030.0);
+    assert_eq!(workspace.get_offsets(), offsets);
+}